@@ -0,0 +1,18 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the left eye in layer 0 and the right eye in layer 1
+layout(binding = 1) uniform texture2DArray eyes;
+layout(binding = 9) uniform sampler eyeSampler;
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec3 left = texture(sampler2DArray(eyes, eyeSampler), vec3(fragCoords, 0.0)).rgb;
+    vec3 right = texture(sampler2DArray(eyes, eyeSampler), vec3(fragCoords, 1.0)).rgb;
+    // half color anaglyph, the red channel is converted to gray to reduce retinal rivalry
+    float red = dot(left, vec3(0.299, 0.587, 0.114));
+    outColor = vec4(red, right.g, right.b, 1.0);
+}
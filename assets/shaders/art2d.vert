@@ -1,5 +1,15 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
+
+#define FRAMING_FIT 1
 
 layout(binding = 0) uniform UniformBufferObject {
     mat4 model;
@@ -8,19 +18,55 @@ layout(binding = 0) uniform UniformBufferObject {
     vec2 resolution;
     float texture_weight;
     float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
 } ubo;
 
-layout(push_constant) uniform PushConstants {
+layout(binding = 3) uniform ArtUniforms {
     mat4 model;
-} pcs;
+    vec4 params;
+    float time;
+    float image_aspect;
+    uint framing;
+} art;
 
 layout(location = 0) in vec3 vPosition;
 
 layout(location = 0) out vec3 fragPos;
 layout(location = 1) flat out float iTime;
+layout(location = 2) flat out vec4 iMouse;
+layout(location = 3) out vec4 fog;
+
+#include "fog.glsl"
+
+// shrinks the quad to the aspect ratio of the image if the art is framed to fit, see `Framing`
+vec3 framed(vec3 pos) {
+    if (art.framing != FRAMING_FIT) {
+        return pos;
+    }
+    float quad_aspect = length(art.model[0].xyz) / length(art.model[1].xyz);
+    float ratio = art.image_aspect / quad_aspect;
+    return pos * (ratio > 1.0 ? vec3(1.0, 1.0 / ratio, 1.0) : vec3(ratio, 1.0, 1.0));
+}
 
 void main() {
+    mat4 view = ubo.eye_views[VIEW_INDEX];
     fragPos = vPosition;
-    iTime = ubo.time;
-    gl_Position = ubo.proj * ubo.view * pcs.model * vec4(vPosition, 1.0);
+    iTime = art.time;
+    iMouse = ubo.mouse;
+    vec3 cameraPos = -transpose(mat3(view)) * view[3].xyz;
+    // the fragments see the whole quad from -1 to 1 even if it is shrunk
+    vec3 pos = framed(vPosition);
+    vec3 worldPos = vec3(art.model * vec4(pos, 1.0));
+    float dist = distance(worldPos, cameraPos);
+    float amount = fogAmount(cameraPos, (worldPos - cameraPos) / dist, dist, ubo.fog_density, ubo.fog_height_falloff);
+    fog = vec4(ubo.fog_color, amount);
+    gl_Position = ubo.proj * view * art.model * vec4(pos, 1.0);
 }
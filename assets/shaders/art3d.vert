@@ -1,5 +1,13 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
 
 layout(binding = 0) uniform UniformBufferObject {
     mat4 model;
@@ -8,11 +16,22 @@ layout(binding = 0) uniform UniformBufferObject {
     vec2 resolution;
     float texture_weight;
     float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
 } ubo;
 
-layout(push_constant) uniform PushConstants {
+layout(binding = 3) uniform ArtUniforms {
     mat4 model;
-} pcs;
+    vec4 params;
+    float time;
+} art;
 
 layout(location = 0) in vec3 vPosition;
 
@@ -21,17 +40,26 @@ layout(location = 1) flat out vec3 cameraPos;
 layout(location = 2) flat out float cameraDistToContainer;
 layout(location = 3) flat out vec2 iResolution;
 layout(location = 4) flat out float iTime;
+// fog of the container, the art inside is assumed to be small enough for it
+layout(location = 5) out vec4 fog;
+
+#include "fog.glsl"
 
 void main() {
+    mat4 view = ubo.eye_views[VIEW_INDEX];
     fragPos = vPosition;
-    cameraPos = -transpose(mat3(ubo.view)) * ubo.view[3].xyz;
+    cameraPos = -transpose(mat3(view)) * view[3].xyz;
+    vec3 worldPos = vec3(art.model * vec4(vPosition, 1.0));
+    float dist = distance(worldPos, cameraPos);
+    float amount = fogAmount(cameraPos, (worldPos - cameraPos) / dist, dist, ubo.fog_density, ubo.fog_height_falloff);
+    fog = vec4(ubo.fog_color, amount);
     // apply the inverse of the model matrix to the camera, this way the
     // container can stay the unit cube which will make calulcations nicer
-    cameraPos = vec3(inverse(pcs.model) * vec4(cameraPos, 1.0));
+    cameraPos = vec3(inverse(art.model) * vec4(cameraPos, 1.0));
     // assuming container is the unit cube
     cameraDistToContainer = length(max(vec3(0.0), abs(cameraPos) - 1.0));
     iResolution = ubo.resolution;
-    iTime = ubo.time;
+    iTime = art.time;
 
-    gl_Position = ubo.proj * ubo.view * pcs.model * vec4(vPosition, 1.0);
+    gl_Position = ubo.proj * view * art.model * vec4(vPosition, 1.0);
 }
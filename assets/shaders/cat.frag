@@ -7,6 +7,7 @@
 
 layout(location = 0) in vec3 fragPos;
 layout(location = 1) in float iTime;
+layout(location = 3) in vec4 fog;
 
 layout(location = 0) out vec4 outColor;
 
@@ -149,4 +150,5 @@ void main() {
     float t = clamp(cat, 0.0, 1.0);
 
     outColor = vec4(mix(shapeColor, backgroundColor, t), 1.0);
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
@@ -0,0 +1,64 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 4) uniform sampler3D volume;
+layout(binding = 5) uniform sampler2D noise;
+
+layout(location = 0) in vec3 fragPos;
+layout(location = 1) in vec3 cameraPos;
+layout(location = 2) in float cameraDistToContainer;
+layout(location = 3) in vec2 iResolution;
+layout(location = 4) in float iTime;
+layout(location = 5) in vec4 fog;
+
+layout(location = 0) out vec4 outColor;
+
+const int STEPS = 64;
+const float ABSORPTION = 6.0;
+const vec3 LIGHT_DIR = normalize(vec3(0.3, 1.0, 0.2));
+
+float density(vec3 pos) {
+    // drift slowly through the volume, it is sampled with clamp to edge
+    vec3 uvw = pos * 0.5 + 0.5;
+    uvw.xz += vec2(sin(iTime * 0.2), cos(iTime * 0.15)) * 0.02;
+    return texture(volume, uvw).a;
+}
+
+void main() {
+    vec3 ray_dir = normalize(fragPos - cameraPos);
+
+    // intersect the ray with the unit cube container
+    vec3 inv_dir = 1.0 / ray_dir;
+    vec3 t0 = (-1.0 - cameraPos) * inv_dir;
+    vec3 t1 = (1.0 - cameraPos) * inv_dir;
+    vec3 t_min = min(t0, t1);
+    vec3 t_max = max(t0, t1);
+    float t_near = max(max(max(t_min.x, t_min.y), t_min.z), 0.0);
+    float t_far = min(min(t_max.x, t_max.y), t_max.z);
+
+    float step_size = (t_far - t_near) / float(STEPS);
+    // offset the samples of neighboring pixels with blue noise to trade banding for fine grain
+    float jitter = texture(noise, gl_FragCoord.xy / vec2(textureSize(noise, 0))).a;
+    float transmittance = 1.0;
+    vec3 color = vec3(0.0);
+    for (int i = 0; i < STEPS && transmittance > 0.01; i++) {
+        vec3 pos = cameraPos + ray_dir * (t_near + (float(i) + jitter) * step_size);
+        float d = density(pos);
+        if (d <= 0.0) {
+            continue;
+        }
+        // cheap self shadowing with a single sample towards the light
+        float shadow = exp(-density(pos + LIGHT_DIR * 0.1) * ABSORPTION * 0.2);
+        vec3 light = mix(vec3(0.45, 0.5, 0.6), vec3(1.0), shadow);
+        float absorbed = 1.0 - exp(-d * ABSORPTION * step_size);
+        color += transmittance * absorbed * light;
+        transmittance *= 1.0 - absorbed;
+    }
+
+    float alpha = 1.0 - transmittance;
+    if (alpha < 0.01) {
+        discard;
+    }
+    outColor = vec4(color / alpha, alpha);
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
+}
@@ -1,12 +1,38 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
 
-layout(binding = 1) uniform samplerCube cubeSampler;
+layout(binding = 1) uniform textureCube cubeDay;
+layout(binding = 2) uniform textureCube cubeNight;
+layout(binding = 9) uniform sampler cubeSampler;
 
 layout(location = 0) in vec3 fragDir;
+layout(location = 1) in vec3 cameraPos;
+layout(location = 2) in vec3 fogColor;
+layout(location = 3) in vec2 fogParams;
+layout(location = 4) in vec3 worldDir;
+layout(location = 5) in vec3 sunDirection;
+layout(location = 6) in float dayWeight;
 
 layout(location = 0) out vec4 outColor;
 
+// the sky is fogged as if it was as far away as the far clip plane,
+// this way the fog below the horizon hides the edge of the floor
+const float SKY_DISTANCE = 200.0;
+// cosine of the angular radius of the sun
+const float SUN_SIZE = 0.9995;
+const vec3 SUN_COLOR = vec3(1.0, 0.95, 0.8);
+
+#include "fog.glsl"
+
 void main() {
-    outColor = texture(cubeSampler, fragDir);
+    outColor = mix(
+        texture(samplerCube(cubeNight, cubeSampler), fragDir),
+        texture(samplerCube(cubeDay, cubeSampler), fragDir),
+        dayWeight
+    );
+    float sun = smoothstep(SUN_SIZE, mix(SUN_SIZE, 1.0, 0.3), dot(normalize(worldDir), sunDirection));
+    outColor.rgb = mix(outColor.rgb, SUN_COLOR, sun);
+    float fog = fogAmount(cameraPos, normalize(fragDir), SKY_DISTANCE, fogParams.x, fogParams.y);
+    outColor.rgb = mix(outColor.rgb, fogColor, fog);
 }
@@ -1,5 +1,12 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
 
 layout(binding = 0) uniform UniformBufferObject {
     mat4 model;
@@ -8,14 +15,38 @@ layout(binding = 0) uniform UniformBufferObject {
     vec2 resolution;
     float texture_weight;
     float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
 } ubo;
 
 layout(location = 0) in vec3 vPosition;
 
 layout(location = 0) out vec3 fragDir;
+layout(location = 1) flat out vec3 cameraPos;
+layout(location = 2) flat out vec3 fogColor;
+layout(location = 3) flat out vec2 fogParams;
+layout(location = 4) out vec3 worldDir;
+layout(location = 5) flat out vec3 sunDirection;
+layout(location = 6) flat out float dayWeight;
 
 void main() {
-    fragDir = vPosition;
+    mat4 view = ubo.eye_views[VIEW_INDEX];
+    float c = cos(ubo.sky_rotation);
+    float s = sin(ubo.sky_rotation);
+    fragDir = mat3(c, 0.0, -s, 0.0, 1.0, 0.0, s, 0.0, c) * vPosition;
     fragDir.x *= -1;
-    gl_Position = ubo.proj * mat4(mat3(ubo.view)) * vec4(vPosition * 100.0, 1.0);
+    worldDir = vPosition;
+    sunDirection = ubo.sun_direction;
+    dayWeight = ubo.day_weight;
+    cameraPos = -transpose(mat3(view)) * view[3].xyz;
+    fogColor = ubo.fog_color;
+    fogParams = vec2(ubo.fog_density, ubo.fog_height_falloff);
+    gl_Position = ubo.proj * mat4(mat3(view)) * vec4(vPosition * 100.0, 1.0);
 }
@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// used in place of art shaders that failed to compile
+
+layout(location = 0) in vec3 fragPos;
+
+layout(location = 0) out vec4 outColor;
+
+const float CHECKERS = 4.0;
+
+void main() {
+    ivec3 cell = ivec3(floor((fragPos + 1.0) * CHECKERS));
+    bool odd = ((cell.x + cell.y + cell.z) & 1) == 1;
+    outColor = odd ? vec4(1.0, 0.0, 1.0, 1.0) : vec4(0.0, 0.0, 0.0, 1.0);
+}
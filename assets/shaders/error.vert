@@ -0,0 +1,43 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+} ubo;
+
+layout(binding = 3) uniform ArtUniforms {
+    mat4 model;
+    vec4 params;
+    float time;
+} art;
+
+layout(location = 0) in vec3 vPosition;
+
+layout(location = 0) out vec3 fragPos;
+
+void main() {
+    mat4 view = ubo.eye_views[VIEW_INDEX];
+    fragPos = vPosition;
+    gl_Position = ubo.proj * view * art.model * vec4(vPosition, 1.0);
+}
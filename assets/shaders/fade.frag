@@ -0,0 +1,30 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+    float fade;
+} ubo;
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+// blended over the frame to fade it to black
+void main() {
+    outColor = vec4(0.0, 0.0, 0.0, ubo.fade);
+}
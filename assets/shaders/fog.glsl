@@ -0,0 +1,10 @@
+// exponential height fog integrated along the ray from the camera,
+// see <https://iquilezles.org/articles/fog/>
+float fogAmount(vec3 cameraPos, vec3 dir, float dist, float density, float falloff) {
+    float fog = density * exp(-cameraPos.y * falloff) * dist;
+    float k = dist * dir.y * falloff;
+    if (abs(k) > 0.0001) {
+        fog *= (1.0 - exp(-k)) / k;
+    }
+    return 1.0 - exp(-fog);
+}
@@ -0,0 +1,126 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define FRAME_GRAPH_LEN 128
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+    float fade;
+    vec4 frame_times[FRAME_GRAPH_LEN / 4];
+    vec4 gpu_frame_times[FRAME_GRAPH_LEN / 4];
+    float grade_strength;
+    float texture_aspect;
+    float parallax_scale;
+    float reflection_strength;
+    float volumetric_fog;
+    float dof_aperture;
+    vec2 focus_point;
+    mat4 previous_view_proj;
+    float shutter;
+    float chromatic_aberration;
+    float vignette;
+    float film_grain;
+    float render_scale;
+    // 0 to move, 1 to rotate and 2 to scale
+    float gizmo_mode;
+    // the axis of the handle under the cursor or being dragged, -1 if none
+    float gizmo_axis;
+    float _padding;
+    // the handles along the first three columns around the translation
+    mat4 gizmo;
+} ubo;
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+#define MODE_ROTATE 1.0
+#define MODE_SCALE 2.0
+// the width of the lines in pixels
+#define LINE_WIDTH 2.0
+
+const vec3 AXIS_COLORS[3] = vec3[](vec3(0.9, 0.2, 0.2), vec3(0.3, 0.85, 0.3), vec3(0.25, 0.4, 1.0));
+const vec3 ACTIVE_COLOR = vec3(1.0, 0.85, 0.1);
+
+// the distance of the ray to the handle along the axis at t along the ray and the radius of
+// the handle at the closest point, the handles get thicker at the tip
+vec3 shaft(vec3 ro, vec3 rd, vec3 center, vec3 axis) {
+    float len = length(axis);
+    vec3 a = axis / len;
+    vec3 w = center - ro;
+    float b = dot(a, rd);
+    float s = (b * dot(rd, w) - dot(a, w)) / max(1.0 - b * b, 1e-6);
+    s = clamp(s, 0.0, len);
+    vec3 point = center + a * s;
+    float t = max(dot(rd, point - ro), 0.0);
+    float tip = ubo.gizmo_mode == MODE_SCALE ? 0.9 : 0.8;
+    float radius = s > len * tip ? len * 0.05 : 0.0;
+    return vec3(length(ro + rd * t - point), t, radius);
+}
+
+// the distance of the ray to the ring around the axis at t along the ray
+vec2 ring(vec3 ro, vec3 rd, vec3 center, vec3 axis) {
+    vec3 n = normalize(axis);
+    float denom = dot(n, rd);
+    if (abs(denom) < 1e-6) {
+        return vec2(1e9, -1.0);
+    }
+    float t = dot(n, center - ro) / denom;
+    return vec2(abs(length(ro + rd * t - center) - length(axis)), t);
+}
+
+// the handles of the edited art over the gallery, see `Gizmo` in the gizmo module
+void main() {
+    mat4 inverse_view = inverse(ubo.view);
+    vec4 target = inverse(ubo.proj) * vec4(fragCoords * 2.0 - 1.0, 0.5, 1.0);
+    vec3 ro = inverse_view[3].xyz;
+    vec3 rd = normalize(mat3(inverse_view) * (target.xyz / target.w));
+    // the angle a pixel covers, so that the lines have the same width at every distance
+    float pixel = length(fwidth(rd));
+
+    vec3 center = ubo.gizmo[3].xyz;
+    float nearest = 1e9;
+    vec4 color = vec4(0.0);
+    for (int i = 0; i < 3; i++) {
+        vec3 axis = ubo.gizmo[i].xyz;
+        float dist;
+        float t;
+        float radius;
+        if (ubo.gizmo_mode == MODE_ROTATE) {
+            vec2 hit = ring(ro, rd, center, axis);
+            dist = hit.x;
+            t = hit.y;
+            radius = 0.0;
+        } else {
+            vec3 hit = shaft(ro, rd, center, axis);
+            dist = hit.x;
+            t = hit.y;
+            radius = hit.z;
+        }
+        float width = max(radius, LINE_WIDTH * 0.5 * pixel * t);
+        float coverage = clamp((width - dist) / (pixel * t) + 0.5, 0.0, 1.0);
+        if (t > 0.0 && t < nearest && coverage > 0.0) {
+            nearest = t;
+            vec3 rgb = float(i) == ubo.gizmo_axis ? ACTIVE_COLOR : AXIS_COLORS[i];
+            color = vec4(rgb, coverage);
+        }
+    }
+    if (color.a == 0.0) {
+        discard;
+    }
+    outColor = color;
+}
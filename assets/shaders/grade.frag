@@ -0,0 +1,305 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define FRAME_GRAPH_LEN 128
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+    float fade;
+    vec4 frame_times[FRAME_GRAPH_LEN / 4];
+    vec4 gpu_frame_times[FRAME_GRAPH_LEN / 4];
+    float grade_strength;
+    float texture_aspect;
+    float parallax_scale;
+    float reflection_strength;
+    float volumetric_fog;
+    float dof_aperture;
+    vec2 focus_point;
+    mat4 previous_view_proj;
+    float shutter;
+    float chromatic_aberration;
+    float vignette;
+    float film_grain;
+    float render_scale;
+} ubo;
+
+// the gallery as it would be shown without grading
+layout(binding = 1) uniform texture2D frame;
+// the depth of the environment the reflections are marched through
+layout(binding = 2) uniform texture2D depth;
+layout(binding = 9) uniform sampler frameSampler;
+// the lookup table with red along x, green along y and blue along z
+layout(binding = 4) uniform sampler3D lut;
+// the depth of the whole gallery the depth of field is focused with
+#ifdef MULTISAMPLED
+layout(binding = 18) uniform texture2DMS galleryDepth;
+#else
+layout(binding = 18) uniform texture2D galleryDepth;
+#endif
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+#define REFLECTION_STEPS 48
+#define REFLECTION_REFINE_STEPS 5
+// the farthest a reflected ray is marched in meters
+#define REFLECTION_DISTANCE 20.0
+// how far a ray may be behind the depth and still hit it, grows with the distance
+#define REFLECTION_THICKNESS 0.05
+
+vec3 viewPosition(vec2 uv, float z) {
+    vec4 position = inverse(ubo.proj) * vec4(uv * 2.0 - 1.0, z, 1.0);
+    return position.xyz / position.w;
+}
+
+// the point in view space projected to the frame with its depth
+vec3 screenPosition(vec3 position) {
+    vec4 clip = ubo.proj * vec4(position, 1.0);
+    return vec3(clip.xy / clip.w * 0.5 + 0.5, clip.z / clip.w);
+}
+
+// the depth is fetched as the sampler filters the frame
+float sceneDepth(vec2 uv) {
+    ivec2 size = textureSize(sampler2D(depth, frameSampler), 0);
+    ivec2 coords = clamp(ivec2(uv * vec2(size)), ivec2(0), size - 1);
+    return texelFetch(sampler2D(depth, frameSampler), coords, 0).r;
+}
+
+// `uv` in the part of the frame the gallery was drawn into, which is smaller than the frame
+// with a dynamic resolution, without filtering in what is outside of it
+vec2 frameUv(vec2 uv) {
+    vec2 halfTexel = 0.5 / vec2(textureSize(sampler2D(frame, frameSampler), 0));
+    return min(uv * ubo.render_scale, ubo.render_scale - halfTexel);
+}
+
+// How far the point in view space is behind the environment in meters.
+float behindScene(vec3 position) {
+    vec3 screen = screenPosition(position);
+    return viewPosition(screen.xy, sceneDepth(screen.xy)).z - position.z;
+}
+
+// The color of the gallery reflected by the floor at this fragment, with how much of it
+// was found in the frame in alpha.
+//
+// The position and the normal are reconstructed from the depth of the environment and only
+// surfaces facing up reflect. The reflected ray is marched through the depth until it is
+// behind it, refined with a binary search and the frame is looked up where it hit.
+vec4 reflection(vec2 uv, vec2 texel) {
+    float z = sceneDepth(uv);
+    // nothing but the sky is this far
+    if (z >= 1.0) {
+        return vec4(0.0);
+    }
+    vec3 position = viewPosition(uv, z);
+    vec2 uvRight = uv + vec2(texel.x, 0.0);
+    vec2 uvDown = uv + vec2(0.0, texel.y);
+    vec3 normal = normalize(cross(
+        viewPosition(uvRight, sceneDepth(uvRight)) - position,
+        viewPosition(uvDown, sceneDepth(uvDown)) - position
+    ));
+    if (dot(normal, position) > 0.0) {
+        normal = -normal;
+    }
+    vec3 up = normalize(mat3(ubo.view) * vec3(0.0, 1.0, 0.0));
+    if (dot(normal, up) < 0.95) {
+        return vec4(0.0);
+    }
+
+    vec3 view = normalize(position);
+    vec3 direction = reflect(view, up);
+    // rays towards the camera leave the frame
+    if (direction.z > 0.0) {
+        return vec4(0.0);
+    }
+    float stepLength = REFLECTION_DISTANCE / float(REFLECTION_STEPS);
+    vec3 start = position + up * 0.01;
+    float previous = 0.0;
+    for (int i = 1; i <= REFLECTION_STEPS; i++) {
+        float distance = stepLength * float(i);
+        vec3 screen = screenPosition(start + direction * distance);
+        if (any(lessThan(screen.xy, vec2(0.0))) || any(greaterThan(screen.xy, vec2(1.0)))) {
+            break;
+        }
+        float behind = behindScene(start + direction * distance);
+        if (behind > 0.0) {
+            // the ray passed behind something in front of what it reflects
+            if (behind > REFLECTION_THICKNESS * distance + stepLength) {
+                break;
+            }
+            float near = previous;
+            float far = distance;
+            for (int j = 0; j < REFLECTION_REFINE_STEPS; j++) {
+                float middle = (near + far) * 0.5;
+                if (behindScene(start + direction * middle) > 0.0) {
+                    far = middle;
+                } else {
+                    near = middle;
+                }
+            }
+            vec2 hit = screenPosition(start + direction * far).xy;
+            vec3 color = textureLod(sampler2D(frame, frameSampler), frameUv(hit), 0.0).rgb;
+            // fade out towards the edges of the frame and the end of the ray
+            vec2 edge = min(hit, 1.0 - hit);
+            float fade = smoothstep(0.0, 0.1, min(edge.x, edge.y))
+                * (1.0 - far / REFLECTION_DISTANCE);
+            // grazing angles reflect more
+            float fresnel = 0.3 + 0.7 * pow(1.0 - max(dot(-view, up), 0.0), 5.0);
+            return vec4(color, fade * fresnel);
+        }
+        previous = distance;
+    }
+    return vec4(0.0);
+}
+
+// how far red and blue are shifted apart in the corners at full intensity
+#define ABERRATION_SHIFT 0.02
+#define GRAIN_AMOUNT 0.15
+
+// The frame at `uv` with red and blue shifted apart towards the edges by the chromatic
+// aberration, used for every sample of the blurs so that they are shifted alike.
+vec3 frameColor(vec2 uv) {
+    vec3 color = textureLod(sampler2D(frame, frameSampler), frameUv(uv), 0.0).rgb;
+    if (ubo.chromatic_aberration > 0.0) {
+        vec2 shift = (uv - 0.5) * ubo.chromatic_aberration * ABERRATION_SHIFT;
+        color.r = textureLod(sampler2D(frame, frameSampler), frameUv(uv + shift), 0.0).r;
+        color.b = textureLod(sampler2D(frame, frameSampler), frameUv(uv - shift), 0.0).b;
+    }
+    return color;
+}
+
+#define DOF_SAMPLES 48
+// the radius of the blur of what is infinitely far behind the focus at full aperture,
+// relative to the height of the frame so that stills are blurred alike
+#define DOF_MAX_RADIUS 0.012
+#define GOLDEN_ANGLE 2.39996323
+
+#define MOTION_BLUR_SAMPLES 16
+// the longest streak relative to the height of the frame, cuts of the camera are not blurred
+// across the whole frame
+#define MOTION_BLUR_MAX_LENGTH 0.05
+
+// The depth of the gallery at `uv`, only the first sample is read if it is multisampled.
+float galleryZ(vec2 uv, vec2 texel) {
+    vec2 scale = vec2(ubo.render_scale);
+    ivec2 coords = clamp(ivec2(uv * scale / texel), ivec2(0), ivec2(scale / texel) - 1);
+#ifdef MULTISAMPLED
+    return texelFetch(sampler2DMS(galleryDepth, frameSampler), coords, 0).r;
+#else
+    return texelFetch(sampler2D(galleryDepth, frameSampler), coords, 0).r;
+#endif
+}
+
+// the distance from the camera along the view direction at `uv` in meters
+float galleryDistance(vec2 uv, vec2 texel) {
+    return -viewPosition(uv, galleryZ(uv, texel)).z;
+}
+
+// the radius in pixels of the circle a point at `distance` is blurred into
+float blurRadius(float distance, float focus, float maxRadius) {
+    return min(abs(1.0 - focus / distance) * maxRadius, maxRadius);
+}
+
+// The frame blurred by the depth of field at this fragment.
+//
+// Every sample in a spiral around the fragment counts if its own blur reaches the fragment,
+// see "Bokeh depth of field in a single pass" by Dennis Gustafsson. The focus is the distance
+// of whatever is at the focus point.
+vec3 depthOfField(vec2 uv, vec2 texel) {
+    float maxRadius = DOF_MAX_RADIUS * ubo.dof_aperture / texel.y;
+    float focus = galleryDistance(ubo.focus_point, texel);
+    float centerDistance = galleryDistance(uv, texel);
+    float centerRadius = blurRadius(centerDistance, focus, maxRadius);
+    vec3 color = frameColor(uv);
+    float total = 1.0;
+    for (int i = 0; i < DOF_SAMPLES; i++) {
+        float radius = maxRadius * sqrt((float(i) + 0.5) / float(DOF_SAMPLES));
+        float angle = float(i) * GOLDEN_ANGLE;
+        vec2 sampleUv = uv + vec2(cos(angle), sin(angle)) * radius * texel;
+        float sampleDistance = galleryDistance(sampleUv, texel);
+        float sampleRadius = blurRadius(sampleDistance, focus, maxRadius);
+        // what is behind the fragment does not bleed over it much more than it is blurred
+        if (sampleDistance > centerDistance) {
+            sampleRadius = min(sampleRadius, centerRadius * 2.0);
+        }
+        float weight = smoothstep(radius - 0.5, radius + 0.5, sampleRadius);
+        color += frameColor(sampleUv) * weight;
+        total += weight;
+    }
+    return color / total;
+}
+
+// How far the gallery at `uv` moved across the frame since the last frame.
+//
+// Only the camera moves the gallery, the position is reprojected with the view and
+// the projection of the last frame.
+vec2 velocity(vec2 uv, vec2 texel) {
+    vec3 position = viewPosition(uv, galleryZ(uv, texel));
+    vec4 previous = ubo.previous_view_proj * inverse(ubo.view) * vec4(position, 1.0);
+    return uv - (previous.xy / previous.w * 0.5 + 0.5);
+}
+
+// `color` of this fragment smeared along its velocity for as long as the shutter is open.
+vec3 motionBlur(vec3 color, vec2 uv, vec2 texel) {
+    vec2 streak = velocity(uv, texel) * ubo.shutter;
+    float pixels = length(streak / texel);
+    float maxPixels = MOTION_BLUR_MAX_LENGTH / texel.y;
+    if (pixels < 0.5) {
+        return color;
+    }
+    streak *= min(pixels, maxPixels) / pixels;
+    // `color` counts as one more sample, so that it keeps its depth of field
+    for (int i = 0; i < MOTION_BLUR_SAMPLES; i++) {
+        float t = float(i) / float(MOTION_BLUR_SAMPLES) - 0.5;
+        vec2 sampleUv = clamp(uv + streak * t, vec2(0.0), vec2(1.0));
+        color += frameColor(sampleUv);
+    }
+    return color / float(MOTION_BLUR_SAMPLES + 1);
+}
+
+// a different random number from -0.5 to 0.5 for every pixel and frame
+float grainNoise() {
+    vec2 seed = gl_FragCoord.xy + fract(ubo.time) * vec2(97.0, 131.0);
+    return fract(sin(dot(seed, vec2(12.9898, 78.233))) * 43758.5453) - 0.5;
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(frame, frameSampler), 0));
+    vec2 uv = gl_FragCoord.xy * texel;
+    vec3 color = frameColor(uv);
+    // the depth of field is only focused when the depth of the gallery is kept
+    if (ubo.dof_aperture > 0.0) {
+        color = depthOfField(uv, texel);
+    }
+    if (ubo.shutter > 0.0) {
+        color = motionBlur(color, uv, texel);
+    }
+    // the floor is only reflecting when the depth is drawn
+    if (ubo.reflection_strength > 0.0) {
+        vec4 reflected = reflection(uv, texel);
+        color = mix(color, reflected.rgb, reflected.a * ubo.reflection_strength);
+    }
+    // the first and the last entry of the table are at the centers of the outer texels
+    float size = float(textureSize(lut, 0).x);
+    vec3 graded = texture(lut, color * ((size - 1.0) / size) + 0.5 / size).rgb;
+    color = mix(color, graded, ubo.grade_strength);
+    // the stylistic effects go on top like they would on film
+    float corner = length(uv - 0.5) * sqrt(2.0);
+    color *= mix(1.0, smoothstep(1.0, 0.3, corner), ubo.vignette);
+    color = max(color + grainNoise() * GRAIN_AMOUNT * ubo.film_grain, 0.0);
+    outColor = vec4(color, 1.0);
+}
@@ -0,0 +1,59 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+#define FRAME_GRAPH_LEN 128
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+    float fade;
+    // frame times in milliseconds, the oldest first
+    vec4 frame_times[FRAME_GRAPH_LEN / 4];
+    vec4 gpu_frame_times[FRAME_GRAPH_LEN / 4];
+} ubo;
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+// the time at the top of the graph
+const float MAX_TIME = 50.0;
+const float TIME_60_FPS = 1000.0 / 60.0;
+const float TIME_30_FPS = 1000.0 / 30.0;
+
+void main() {
+    int i = min(int(fragCoords.x * FRAME_GRAPH_LEN), FRAME_GRAPH_LEN - 1);
+    float frame_time = ubo.frame_times[i / 4][i % 4];
+    float gpu_frame_time = ubo.gpu_frame_times[i / 4][i % 4];
+    float t = (1.0 - fragCoords.y) * MAX_TIME;
+    // one pixel wide lines
+    float line_width = fwidth(t);
+
+    vec3 color = vec3(0.05);
+    if (abs(t - TIME_60_FPS) < line_width || abs(t - TIME_30_FPS) < line_width) {
+        color = vec3(0.4);
+    }
+    if (t < frame_time) {
+        // green while running at 60 fps, yellow down to 30 fps and red below
+        color = frame_time < TIME_60_FPS * 1.05 ? vec3(0.2, 0.8, 0.2)
+            : frame_time < TIME_30_FPS * 1.05 ? vec3(0.9, 0.8, 0.1)
+            : vec3(0.9, 0.2, 0.1);
+    }
+    if (t < gpu_frame_time) {
+        color = mix(color, vec3(0.2, 0.4, 1.0), 0.6);
+    }
+    outColor = vec4(color, 1.0);
+}
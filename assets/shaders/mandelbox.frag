@@ -1,11 +1,27 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
 
+layout(binding = 3) uniform ArtUniforms {
+    mat4 model;
+    vec4 params;
+    float time;
+    float image_aspect;
+    uint framing;
+    uint joint_offset;
+    vec4 morph_weights;
+    // from 0 to 3, lower on slow GPUs
+    uint quality;
+    // from 0 to 1 as the camera comes close and the seconds since it did
+    float nearby;
+    float nearby_time;
+} art;
+
 layout(location = 0) in vec3 fragPos;
 layout(location = 1) in vec3 cameraPos;
 layout(location = 2) in float cameraDistToContainer;
 layout(location = 3) in vec2 iResolution;
 layout(location = 4) in float iTime;
+layout(location = 5) in vec4 fog;
 
 layout(location = 0) out vec4 outColor;
 
@@ -49,11 +65,11 @@ float dist_estimate(vec3 ray_pos, float constant1, float constant2) {
     return (length(v) - constant1) / dr - constant2;
 }
 
-int ray_march(vec3 pos, vec3 ray_dir, inout float dist) {
+int ray_march(vec3 pos, vec3 ray_dir, int maxSteps, inout float dist) {
     float constant1 = abs(scaleFactor - 1.0);
     float constant2 = pow(float(abs(scaleFactor)), float(1 - maxIterations));
 
-    for (int i = 0; i < MAX_STEPS; i++) {
+    for (int i = 0; i < maxSteps; i++) {
         vec3 ray_pos = pos + ray_dir * dist;
         float de = dist_estimate(ray_pos, constant1, constant2);
 
@@ -64,27 +80,33 @@ int ray_march(vec3 pos, vec3 ray_dir, inout float dist) {
         }
     }
 
-    return MAX_STEPS;
+    return maxSteps;
 }
 
 void main() {
     vec3 ray_dir = normalize(fragPos - cameraPos);
     vec3 ray_pos = (cameraPos + ray_dir * cameraDistToContainer) * INSIDE_SCALE;
 
+    // every level below the highest takes a quarter fewer steps
+    int maxSteps = MAX_STEPS * (int(min(art.quality, 3u)) + 1) / 4;
     float dist = 0;
-    int steps = ray_march(ray_pos, ray_dir, dist);
+    int steps = ray_march(ray_pos, ray_dir, maxSteps, dist);
 
-    if (dist >= MAX_DIST || steps == MAX_STEPS) {
+    if (dist >= MAX_DIST || steps == maxSteps) {
         outColor = vec4(0.0, 0.0, 0.0, 0.4);
     } else {
         // The (log(epsilon) * 2.0) offset is to compensate for the fact
         // that more steps are taken when epsilon is small.
         float adjusted = max(0.0, float(steps) + log(epsilon) * 2.0);
-        float adjustedMax = float(MAX_STEPS) + log(epsilon) * 2.0;
+        float adjustedMax = float(maxSteps) + log(epsilon) * 2.0;
 
         // Sqrt increases contrast.
         float distRatio = sqrt(adjusted / adjustedMax) * 0.8;
 
-        outColor = vec4(vec3(1.0 - distRatio), 1.0);
+        // the box lights up over two seconds when a visitor comes close
+        float awake = art.nearby * smoothstep(0.0, 2.0, art.nearby_time);
+        outColor = vec4(vec3(1.0 - distRatio) * (0.8 + 0.2 * awake), 1.0);
     }
+
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
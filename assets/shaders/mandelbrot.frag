@@ -5,6 +5,7 @@
 
 layout(location = 0) in vec3 fragPos;
 layout(location = 1) in float iTime;
+layout(location = 3) in vec4 fog;
 
 layout(location = 0) out vec4 outColor;
 
@@ -35,4 +36,5 @@ void main() {
     }
 
     outColor = vec4(color / 2.0 + 0.5, 1.0);
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
@@ -8,6 +8,7 @@ layout(location = 1) in vec3 cameraPos;
 layout(location = 2) in float cameraDistToContainer;
 layout(location = 3) in vec2 iResolution;
 layout(location = 4) in float iTime;
+layout(location = 5) in vec4 fog;
 
 layout(location = 0) out vec4 outColor;
 
@@ -121,4 +122,6 @@ void main() {
         vec3 pos = cameraPos + inter.x * dir;
         outColor = menger(vec3(-0.75), 1.5, dir, pos, plane);
     }
+
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
@@ -10,8 +10,8 @@ layout(location = 1) in vec3 cameraPos;
 layout(location = 2) in float cameraDistToContainer;
 layout(location = 3) in vec2 iResolution;
 layout(location = 4) in float iTime;
+layout(location = 5) in vec4 fog;
 
-layout(binding = 1) uniform sampler2D texSampler;
 
 layout(location = 0) out vec4 outColor;
 
@@ -126,4 +126,6 @@ void main() {
     } else {
         outColor = vec4(0.0);
     }
+
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
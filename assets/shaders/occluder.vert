@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the start of the uniform buffer object of shader.vert
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(location = 0) in vec3 vPosition;
+
+// only the depth of the environment is drawn, the art is culled against it
+void main() {
+    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(vPosition, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the text rendered with one bit per pixel, see src/text.rs
+layout(std430, binding = 19) readonly buffer Text {
+    uvec2 size;
+    // the color of the text packed into the lowest three bytes, red first
+    uint color;
+    // row after row from the top, the lowest bit of a word is its leftmost pixel
+    uint bits[];
+};
+
+layout(location = 0) in vec2 fragCoords;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    uvec2 pixel = min(uvec2(fragCoords * vec2(size)), size - 1u);
+    uint index = pixel.y * size.x + pixel.x;
+    bool lit = ((bits[index / 32u] >> (index % 32u)) & 1u) != 0u;
+    outColor = vec4(lit ? unpackUnorm4x8(color).rgb : vec3(0.05), 1.0);
+}
@@ -0,0 +1,41 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// shows the art image framed as the art says, see `Framing`
+
+#define FRAMING_LETTERBOX 2
+
+layout(binding = 1) uniform texture2D image;
+layout(binding = 9) uniform sampler imageSampler;
+
+layout(binding = 3) uniform ArtUniforms {
+    mat4 model;
+    vec4 params;
+    float time;
+    float image_aspect;
+    uint framing;
+} art;
+
+layout(location = 0) in vec3 fragPos;
+layout(location = 1) in float iTime;
+layout(location = 3) in vec4 fog;
+
+layout(location = 0) out vec4 outColor;
+
+const vec3 BAR_COLOR = vec3(0.05);
+
+void main() {
+    vec2 pos = fragPos.xy;
+    if (art.framing == FRAMING_LETTERBOX) {
+        // the quad goes from -1 to 1 and is scaled by the model matrix
+        float quad_aspect = length(art.model[0].xyz) / length(art.model[1].xyz);
+        float ratio = art.image_aspect / quad_aspect;
+        pos *= ratio > 1.0 ? vec2(1.0, ratio) : vec2(1.0 / ratio, 1.0);
+    }
+    if (any(greaterThan(abs(pos), vec2(1.0)))) {
+        outColor = vec4(BAR_COLOR, 1.0);
+    } else {
+        outColor = vec4(texture(sampler2D(image, imageSampler), pos * 0.5 + 0.5).rgb, 1.0);
+    }
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
+}
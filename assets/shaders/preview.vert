@@ -0,0 +1,39 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+} ubo;
+
+layout(location = 0) out vec3 fragPos;
+layout(location = 1) flat out float iTime;
+layout(location = 2) flat out vec4 iMouse;
+layout(location = 3) out vec4 fog;
+
+// a single triangle covering the whole viewport, drawn without vertex input
+void main() {
+    vec2 pos = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2) * 2.0 - 1.0;
+    // keep the aspect ratio of the art, the shorter side of the viewport spans [-1, 1]
+    vec2 scale = ubo.resolution / min(ubo.resolution.x, ubo.resolution.y);
+    // the art is seen from the back of its quad in the gallery, so mirror it the same way
+    fragPos = vec3(-pos.x * scale.x, -pos.y * scale.y, 0.0);
+    iTime = ubo.time;
+    iMouse = ubo.mouse;
+    // the art is seen up close without fog
+    fog = vec4(0.0);
+    gl_Position = vec4(pos, 0.0, 1.0);
+}
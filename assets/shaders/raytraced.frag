@@ -0,0 +1,20 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// shows the image that was ray traced for this art
+
+layout(binding = 1) uniform texture2D tracedImage;
+layout(binding = 9) uniform sampler tracedSampler;
+
+layout(location = 0) in vec3 fragPos;
+layout(location = 1) in float iTime;
+layout(location = 3) in vec4 fog;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // the quad goes from -1 to 1, the ray generation shader uses the same mapping
+    vec2 uv = fragPos.xy * 0.5 + 0.5;
+    outColor = vec4(texture(sampler2D(tracedImage, tracedSampler), uv).rgb, 1.0);
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
+}
@@ -0,0 +1,32 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// A polished sculpture lit by the sun, drawn with morph.vert or skinned.vert.
+
+layout(binding = 3) uniform ArtUniforms {
+    mat4 model;
+    vec4 params;
+} art;
+
+layout(location = 0) in vec3 fragPos;
+layout(location = 1) in vec3 worldPos;
+layout(location = 2) flat in vec3 sunDirection;
+layout(location = 3) flat in float ambientLight;
+layout(location = 4) flat in float iTime;
+layout(location = 5) in vec4 fog;
+
+layout(location = 0) out vec4 outColor;
+
+const vec3 BASE_COLOR = vec3(0.75, 0.55, 0.35);
+
+void main() {
+    // the model has no normals and they change with the morph anyway,
+    // y points down in the framebuffer, so this normal faces the camera
+    vec3 normal = normalize(cross(dFdy(worldPos), dFdx(worldPos)));
+    float diffuse = max(dot(normal, sunDirection), 0.0);
+    // bands of color flow over the surface with the time of the art
+    float bands = 0.5 + 0.5 * sin(fragPos.y * 12.0 - iTime);
+    vec3 color = mix(BASE_COLOR, BASE_COLOR.bgr, bands * 0.3);
+    outColor = vec4(color * (ambientLight + diffuse), 1.0);
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
+}
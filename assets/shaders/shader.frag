@@ -1,11 +1,80 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
 
 layout(location = 0) in vec3 fragColor;
 layout(location = 1) in vec2 fragCoords;
 layout(location = 2) in float texture_weight;
+layout(location = 3) in vec3 worldPos;
+layout(location = 4) in vec3 cameraPos;
+layout(location = 5) in vec3 fogColor;
+layout(location = 6) in vec3 fogParams;
+layout(location = 7) in float ambientLight;
+layout(location = 8) flat in vec3 sunDirection;
+layout(location = 9) in vec3 tangent;
+layout(location = 10) in vec3 bitangent;
+layout(location = 11) flat in float parallaxScale;
+layout(location = 12) in vec2 lightmapCoords;
+layout(location = 13) in vec4 clipPos;
 
-layout(binding = 1) uniform sampler2D texSampler;
+layout(binding = 1) uniform texture2D image;
+layout(binding = 9) uniform sampler texSampler;
+// in tangent space with green along the texture coordinate v and the height in alpha
+layout(binding = 11) uniform sampler2D normalMap;
+#ifdef LIGHTMAP
+// the light of the sky reaching the environment, baked by `--bake-lightmap`
+layout(binding = 12) uniform sampler2D lightmap;
+#endif
+
+// the clusters split the view into tiles and the tiles into slices growing exponentially
+// with the distance, must match `CLUSTER_GRID` of src/vulkan/clusters.rs
+const uvec3 GRID = uvec3(16, 9, 24);
+// each cluster is its number of lights followed by their indices
+const uint CLUSTER_STRIDE = 32;
+
+struct PointLight {
+    vec3 position;
+    float radius;
+    vec3 color;
+    float padding;
+};
+
+// the point lights of the gallery, see `--lights`
+layout(std430, binding = 13) readonly buffer Lights {
+    mat4 view;
+    vec2 projScale;
+    vec2 screenSize;
+    float zNear;
+    float zFar;
+    uint lightCount;
+    // whether the clusters were built for this view, else every light is looked at
+    uint clustered;
+    PointLight lights[];
+};
+
+// the indices of the lights reaching each cluster, built by `light_clusters.comp`
+layout(std430, binding = 14) readonly buffer Clusters {
+    uint clusters[];
+};
+
+// the cascades of the shadow map of the sun, see src/vulkan/shadows.rs
+layout(std430, binding = 15) readonly buffer Cascades {
+    mat4 shadowModel;
+    mat4 cascadeViewProj[4];
+    vec4 cascadeTexelSizes;
+    uint cascadeCount;
+};
+// the depth seen from the sun with a layer per cascade
+layout(binding = 16) uniform sampler2DArrayShadow shadowMap;
+
+// the froxels split the view into tiles and the tiles into slices growing exponentially
+// with the distance, must match `FROXEL_GRID` of src/vulkan/volumetric.rs
+const float VOLUME_SLICES = 64.0;
+const float VOLUME_NEAR = 0.5;
+const float VOLUME_FAR = 100.0;
+// the light scattered by the fog up to each froxel in rgb and the transmittance in alpha,
+// built by `volumetric_fog.comp`
+layout(binding = 17) uniform sampler3D fogVolume;
 
 layout(location = 0) out vec4 outColor;
 
@@ -18,6 +87,124 @@ float random(vec2 p) {
     return fract(cos(dot(p, K1)) * 12345.6789);
 }
 
+#include "fog.glsl"
+
+// the orthonormal frame the normal map is read in and its coordinates,
+// returns whether the coordinates are projected along a world axis
+bool tangentFrame(out vec3 t, out vec3 b, out vec3 n, out vec2 uv) {
+    // vertices are shared between faces, so the normal of the triangle is taken,
+    // y points down in the framebuffer, so this normal faces the camera
+    n = normalize(cross(dFdy(worldPos), dFdx(worldPos)));
+    t = tangent - n * dot(n, tangent);
+    b = bitangent - n * dot(n, bitangent);
+    uv = fragCoords;
+    bool projected = dot(t, t) < 1e-8 || dot(b, b) < 1e-8;
+    if (projected) {
+        // project the map along the world axis closest to the normal instead
+        vec3 a = abs(n);
+        if (a.y > a.x && a.y > a.z) {
+            t = vec3(1.0, 0.0, 0.0);
+            b = vec3(0.0, 0.0, 1.0);
+            uv = worldPos.xz;
+        } else if (a.x > a.z) {
+            t = vec3(0.0, 0.0, 1.0);
+            b = vec3(0.0, 1.0, 0.0);
+            uv = worldPos.zy;
+        } else {
+            t = vec3(1.0, 0.0, 0.0);
+            b = vec3(0.0, 1.0, 0.0);
+            uv = worldPos.xy;
+        }
+        t -= n * dot(n, t);
+        b -= n * dot(n, b);
+    }
+    t = normalize(t);
+    b = normalize(b);
+    return projected;
+}
+
+// steps along the view ray in tangent space through the height map in the alpha
+// of the normal map until it is below the surface and interpolates between the last two steps,
+// see <https://learnopengl.com/Advanced-Lighting/Parallax-Mapping>
+vec2 parallaxCoords(vec2 uv, vec2 dx, vec2 dy, vec3 view) {
+    // more steps at grazing angles where the ray crosses more of the map
+    float steps = mix(32.0, 8.0, abs(view.z));
+    float stepDepth = 1.0 / steps;
+    vec2 delta = view.xy / max(view.z, 0.05) * parallaxScale / steps;
+    float depth = 0.0;
+    float mapDepth = 1.0 - textureGrad(normalMap, uv, dx, dy).a;
+    for (int i = 0; i < int(steps) && depth < mapDepth; i++) {
+        uv -= delta;
+        depth += stepDepth;
+        mapDepth = 1.0 - textureGrad(normalMap, uv, dx, dy).a;
+    }
+    float after = mapDepth - depth;
+    float before = 1.0 - textureGrad(normalMap, uv + delta, dx, dy).a - depth + stepDepth;
+    float weight = after / min(after - before, -1e-6);
+    return uv + delta * weight;
+}
+
+// how much of the sun reaches the fragment with the surface normal `n` from 0 to 1,
+// from the nearest cascade covering it filtered over 3x3 texels
+float sunShadow(vec3 n) {
+    vec2 texel = 1.0 / vec2(textureSize(shadowMap, 0).xy);
+    for (uint i = 0; i < cascadeCount; i++) {
+        // offset along the normal against surfaces shadowing themselves at grazing angles
+        vec3 p = worldPos + n * cascadeTexelSizes[i] * 1.5;
+        vec3 coords = (cascadeViewProj[i] * vec4(p, 1.0)).xyz;
+        vec2 uv = coords.xy * 0.5 + 0.5;
+        // the kernel has to fit into the cascade
+        if (any(lessThan(uv, texel * 2.0)) || any(greaterThan(uv, 1.0 - texel * 2.0))
+                || coords.z > 1.0) {
+            continue;
+        }
+        float lit = 0.0;
+        for (int x = -1; x <= 1; x++) {
+            for (int y = -1; y <= 1; y++) {
+                lit += texture(shadowMap, vec4(uv + vec2(x, y) * texel, float(i), coords.z));
+            }
+        }
+        return lit / 9.0;
+    }
+    return 1.0;
+}
+
+// the index of the cluster the fragment is in
+uint clusterIndex() {
+    uvec2 tile = min(uvec2(gl_FragCoord.xy / screenSize * vec2(GRID.xy)), GRID.xy - 1);
+    float dist = zFar * zNear / (zFar - gl_FragCoord.z * (zFar - zNear));
+    float slice = log(dist / zNear) / log(zFar / zNear) * float(GRID.z);
+    uint z = uint(clamp(slice, 0.0, float(GRID.z - 1)));
+    return tile.x + GRID.x * (tile.y + GRID.y * z);
+}
+
+// the diffuse light of the point lights reaching a surface facing `normal`
+vec3 pointLights(vec3 normal) {
+    uint base = 0;
+    uint count = lightCount;
+    if (clustered != 0) {
+        base = clusterIndex() * CLUSTER_STRIDE;
+        count = min(clusters[base], CLUSTER_STRIDE - 1);
+    }
+    vec3 light = vec3(0.0);
+    for (uint i = 0; i < count; i++) {
+        uint index = clustered != 0 ? clusters[base + 1 + i] : i;
+        // the clusters may have been built for other lights
+        if (index >= lightCount) {
+            continue;
+        }
+        PointLight pointLight = lights[index];
+        vec3 toLight = pointLight.position - worldPos;
+        float dist = length(toLight);
+        // inverse square falloff windowed to reach zero at the radius,
+        // as in "Real Shading in Unreal Engine 4" by Brian Karis
+        float window = clamp(1.0 - pow(dist / pointLight.radius, 4.0), 0.0, 1.0);
+        float falloff = window * window / (dist * dist + 1.0);
+        light += pointLight.color * falloff * max(dot(normal, toLight / max(dist, 1e-4)), 0.0);
+    }
+    return light;
+}
+
 void main() {
     vec4 color = vec4(
         random(vec2(gl_PrimitiveID, 1.1)),
@@ -25,6 +212,50 @@ void main() {
         random(vec2(gl_PrimitiveID, 3.3)),
         1.0
     );
-    vec4 tex = texture(texSampler, fragCoords);
+    vec3 t, b, n;
+    vec2 uv;
+    bool projected = tangentFrame(t, b, n, uv);
+    vec2 dx = dFdx(uv);
+    vec2 dy = dFdy(uv);
+    vec2 imageCoords = fragCoords;
+    if (parallaxScale > 0.0) {
+        vec3 view = normalize(cameraPos - worldPos);
+        vec2 shifted = parallaxCoords(uv, dx, dy, vec3(dot(view, t), dot(view, b), dot(view, n)));
+        // the image only moves with the map if both use the texture coordinates
+        if (!projected) {
+            imageCoords = shifted;
+        }
+        uv = shifted;
+    }
+    vec3 m = textureGrad(normalMap, uv, dx, dy).xyz * 2.0 - 1.0;
+    vec3 normal = normalize(t * m.x + b * m.y + n * m.z);
+
+    vec4 tex = texture(sampler2D(image, texSampler), imageCoords);
     outColor = mix(color, tex, texture_weight);
+    vec3 albedo = outColor.rgb;
+    // the sun brightens the surfaces facing it without changing the average brightness
+    float sun = dot(normal, sunDirection);
+    if (sun > 0.0) {
+        sun *= sunShadow(n);
+    }
+    outColor.rgb *= ambientLight * (0.75 + 0.25 * sun);
+#ifdef LIGHTMAP
+    // the cells of the triangles are small, so mipmaps would blend neighbouring triangles
+    outColor.rgb *= textureLod(lightmap, lightmapCoords, 0.0).rgb;
+#endif
+    outColor.rgb += albedo * pointLights(normal);
+
+    if (fogParams.z > 0.0) {
+        // the froxel the fragment is in, the slices end at the centers of the texels
+        vec2 screen = clipPos.xy / clipPos.w * 0.5 + 0.5;
+        float slice = log(clipPos.w / VOLUME_NEAR) / log(VOLUME_FAR / VOLUME_NEAR) * VOLUME_SLICES;
+        vec4 fog = texture(fogVolume, vec3(screen, (slice - 0.5) / VOLUME_SLICES));
+        outColor.rgb = outColor.rgb * fog.a + fog.rgb;
+    } else {
+        // the environment is large, so the fog is computed per fragment
+        float dist = distance(worldPos, cameraPos);
+        vec3 dir = (worldPos - cameraPos) / dist;
+        float fog = fogAmount(cameraPos, dir, dist, fogParams.x, fogParams.y);
+        outColor.rgb = mix(outColor.rgb, fogColor, fog);
+    }
 }
@@ -1,5 +1,14 @@
 #version 450
 #extension GL_ARB_separate_shader_objects : enable
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
+
+#define FRAME_GRAPH_LEN 128
 
 layout(binding = 0) uniform UniformBufferObject {
     mat4 model;
@@ -8,19 +17,72 @@ layout(binding = 0) uniform UniformBufferObject {
     vec2 resolution;
     float texture_weight;
     float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+    float fade;
+    vec4 frame_times[FRAME_GRAPH_LEN / 4];
+    vec4 gpu_frame_times[FRAME_GRAPH_LEN / 4];
+    float grade_strength;
+    float texture_aspect;
+    float parallax_scale;
+    float reflection_strength;
+    float volumetric_fog;
+    float dof_aperture;
+    vec2 focus_point;
+    mat4 previous_view_proj;
+    float shutter;
+    float chromatic_aberration;
+    float vignette;
+    float film_grain;
 } ubo;
 
 layout(location = 0) in vec3 vPosition;
 layout(location = 1) in vec3 vColor;
 layout(location = 2) in vec2 vCoords;
+// the directions the texture coordinates grow in, zero if they do not span the triangles
+layout(location = 3) in vec3 vTangent;
+layout(location = 4) in vec3 vBitangent;
+layout(location = 5) in vec2 vLightmapCoords;
 
 layout(location = 0) out vec3 fragColor;
 layout(location = 1) out vec2 fragCoords;
 layout(location = 2) out float texture_weight;
+layout(location = 3) out vec3 worldPos;
+layout(location = 4) flat out vec3 cameraPos;
+layout(location = 5) flat out vec3 fogColor;
+// the density, the height falloff and whether the fog is volumetric
+layout(location = 6) flat out vec3 fogParams;
+layout(location = 7) flat out float ambientLight;
+layout(location = 8) flat out vec3 sunDirection;
+layout(location = 9) out vec3 tangent;
+layout(location = 10) out vec3 bitangent;
+layout(location = 11) flat out float parallaxScale;
+layout(location = 12) out vec2 lightmapCoords;
+layout(location = 13) out vec4 clipPos;
 
 void main() {
-    gl_Position = ubo.proj * ubo.view * ubo.model * vec4(vPosition, 1.0);
+    mat4 view = ubo.eye_views[VIEW_INDEX];
+    gl_Position = ubo.proj * view * ubo.model * vec4(vPosition, 1.0);
     fragColor = vColor;
-    fragCoords = vCoords;
+    // keep the image from being stretched over generated texture coordinates
+    fragCoords = vCoords / vec2(ubo.texture_aspect, 1.0);
     texture_weight = ubo.texture_weight;
+    worldPos = vec3(ubo.model * vec4(vPosition, 1.0));
+    cameraPos = -transpose(mat3(view)) * view[3].xyz;
+    fogColor = ubo.fog_color;
+    fogParams = vec3(ubo.fog_density, ubo.fog_height_falloff, ubo.volumetric_fog);
+    ambientLight = ubo.ambient_light;
+    sunDirection = ubo.sun_direction;
+    tangent = mat3(ubo.model) * vTangent;
+    bitangent = mat3(ubo.model) * vBitangent;
+    parallaxScale = ubo.parallax_scale;
+    lightmapCoords = vLightmapCoords;
+    clipPos = gl_Position;
 }
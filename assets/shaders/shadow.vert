@@ -0,0 +1,21 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the cascades of the shadow map, see src/vulkan/shadows.rs
+layout(std430, binding = 0) readonly buffer Cascades {
+    mat4 model;
+    mat4 cascadeViewProj[4];
+    vec4 cascadeTexelSizes;
+    uint cascadeCount;
+};
+
+layout(push_constant) uniform PushConstants {
+    uint cascade;
+};
+
+layout(location = 0) in vec3 vPosition;
+
+// only the depth of the environment is drawn as seen from the sun
+void main() {
+    gl_Position = cascadeViewProj[cascade] * model * vec4(vPosition, 1.0);
+}
@@ -0,0 +1,78 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+#ifdef MULTIVIEW
+#extension GL_EXT_multiview : require
+#define VIEW_INDEX gl_ViewIndex
+#else
+// without multiview only a single view is rendered
+#define VIEW_INDEX 0
+#endif
+
+// Vertex shader of art drawn with a skinned glTF model whose vertices move with up to four joints.
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec2 resolution;
+    float texture_weight;
+    float time;
+    mat4 eye_views[2];
+    vec4 mouse;
+    vec3 fog_color;
+    float fog_density;
+    float fog_height_falloff;
+    float sky_rotation;
+    float day_weight;
+    float ambient_light;
+    vec3 sun_direction;
+} ubo;
+
+layout(binding = 3) uniform ArtUniforms {
+    mat4 model;
+    vec4 params;
+    float time;
+    float image_aspect;
+    uint framing;
+    uint joint_offset;
+    vec4 morph_weights;
+} art;
+
+// the joint matrices of all skinned art, the ones of this art start at `art.joint_offset`
+layout(binding = 10) readonly buffer Joints {
+    mat4 matrices[];
+} joints;
+
+layout(location = 0) in vec3 vPosition;
+layout(location = 1) in uvec4 vJoints;
+// the weights of the joints sum up to one
+layout(location = 2) in vec4 vWeights;
+
+layout(location = 0) out vec3 fragPos;
+layout(location = 1) out vec3 worldPos;
+layout(location = 2) flat out vec3 sunDirection;
+layout(location = 3) flat out float ambientLight;
+layout(location = 4) flat out float iTime;
+layout(location = 5) out vec4 fog;
+
+#include "fog.glsl"
+
+void main() {
+    mat4 view = ubo.eye_views[VIEW_INDEX];
+    uvec4 idx = art.joint_offset + vJoints;
+    mat4 skin = vWeights.x * joints.matrices[idx.x]
+        + vWeights.y * joints.matrices[idx.y]
+        + vWeights.z * joints.matrices[idx.z]
+        + vWeights.w * joints.matrices[idx.w];
+    fragPos = vec3(skin * vec4(vPosition, 1.0));
+    worldPos = vec3(art.model * vec4(fragPos, 1.0));
+    sunDirection = ubo.sun_direction;
+    ambientLight = ubo.ambient_light;
+    iTime = art.time;
+    vec3 cameraPos = -transpose(mat3(view)) * view[3].xyz;
+    float dist = distance(worldPos, cameraPos);
+    float amount = fogAmount(cameraPos, (worldPos - cameraPos) / dist, dist, ubo.fog_density, ubo.fog_height_falloff);
+    fog = vec4(ubo.fog_color, amount);
+    gl_Position = ubo.proj * view * vec4(worldPos, 1.0);
+}
@@ -0,0 +1,100 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+#extension GL_GOOGLE_include_directive : require
+
+// procedural sky with the analytic daylight model of Preetham et al.,
+// "A Practical Analytic Model for Daylight", SIGGRAPH 1999
+
+layout(location = 0) in vec3 fragDir;
+layout(location = 1) in vec3 cameraPos;
+layout(location = 2) in vec3 fogColor;
+layout(location = 3) in vec2 fogParams;
+layout(location = 4) in vec3 worldDir;
+layout(location = 5) in vec3 sunDirection;
+layout(location = 6) in float dayWeight;
+
+layout(location = 0) out vec4 outColor;
+
+#define PI 3.1415926535897932
+
+// haziness of the atmosphere, 2 is a clear sky
+const float TURBIDITY = 2.5;
+const float EXPOSURE = 0.08;
+const vec3 NIGHT_COLOR = vec3(0.01, 0.015, 0.04);
+const vec3 GROUND_COLOR = vec3(0.2, 0.18, 0.16);
+
+// see cubemap.frag
+const float SKY_DISTANCE = 200.0;
+const float SUN_SIZE = 0.9995;
+const vec3 SUN_COLOR = vec3(1.0, 0.95, 0.8);
+
+#include "fog.glsl"
+
+// the Perez distribution of the sky luminance with the coefficients A to E
+float perez(float cosTheta, float gamma, float cosGamma, float A, float B, float C, float D, float E) {
+    return (1.0 + A * exp(B / cosTheta)) * (1.0 + C * exp(D * gamma) + E * cosGamma * cosGamma);
+}
+
+// the color of the sky in direction `dir` in the CIE xyY color space
+vec3 skyYxy(vec3 dir, vec3 sun) {
+    float T = TURBIDITY;
+    // the model is only valid for a sun above the horizon
+    float cosThetaS = clamp(sun.y, 0.01, 1.0);
+    float thetaS = acos(cosThetaS);
+    float cosTheta = max(dir.y, 0.01);
+    float cosGamma = clamp(dot(dir, sun), -1.0, 1.0);
+    float gamma = acos(cosGamma);
+
+    float chi = (4.0 / 9.0 - T / 120.0) * (PI - 2.0 * thetaS);
+    float zenithY = (4.0453 * T - 4.9710) * tan(chi) - 0.2155 * T + 2.4192;
+    vec3 t3 = vec3(thetaS * thetaS * thetaS, thetaS * thetaS, thetaS);
+    float zenithX = T * T * dot(vec3(0.00166, -0.00375, 0.00209), t3)
+        + T * (dot(vec3(-0.02903, 0.06377, -0.03202), t3) + 0.00394)
+        + dot(vec3(0.11693, -0.21196, 0.06052), t3) + 0.25886;
+    float zenithYc = T * T * dot(vec3(0.00275, -0.00610, 0.00317), t3)
+        + T * (dot(vec3(-0.04214, 0.08970, -0.04153), t3) + 0.00516)
+        + dot(vec3(0.15346, -0.26756, 0.06670), t3) + 0.26688;
+
+    float Y = zenithY
+        * perez(cosTheta, gamma, cosGamma, 0.1787 * T - 1.4630, -0.3554 * T + 0.4275,
+            -0.0227 * T + 5.3251, 0.1206 * T - 2.5771, -0.0670 * T + 0.3703)
+        / perez(1.0, thetaS, cosThetaS, 0.1787 * T - 1.4630, -0.3554 * T + 0.4275,
+            -0.0227 * T + 5.3251, 0.1206 * T - 2.5771, -0.0670 * T + 0.3703);
+    float x = zenithX
+        * perez(cosTheta, gamma, cosGamma, -0.0193 * T - 0.2592, -0.0665 * T + 0.0008,
+            -0.0004 * T + 0.2125, -0.0641 * T - 0.8989, -0.0033 * T + 0.0452)
+        / perez(1.0, thetaS, cosThetaS, -0.0193 * T - 0.2592, -0.0665 * T + 0.0008,
+            -0.0004 * T + 0.2125, -0.0641 * T - 0.8989, -0.0033 * T + 0.0452);
+    float y = zenithYc
+        * perez(cosTheta, gamma, cosGamma, -0.0167 * T - 0.2608, -0.0950 * T + 0.0092,
+            -0.0079 * T + 0.2102, -0.0441 * T - 1.6537, -0.0109 * T + 0.0529)
+        / perez(1.0, thetaS, cosThetaS, -0.0167 * T - 0.2608, -0.0950 * T + 0.0092,
+            -0.0079 * T + 0.2102, -0.0441 * T - 1.6537, -0.0109 * T + 0.0529);
+    return vec3(Y, x, y);
+}
+
+vec3 xyYToRgb(vec3 Yxy) {
+    float Y = Yxy.x;
+    vec3 XYZ = vec3(Yxy.y / Yxy.z * Y, Y, (1.0 - Yxy.y - Yxy.z) / Yxy.z * Y);
+    return mat3(
+        3.2406, -0.9689, 0.0557,
+        -1.5372, 1.8758, -0.2040,
+        -0.4986, 0.0415, 1.0570
+    ) * XYZ;
+}
+
+void main() {
+    vec3 dir = normalize(worldDir);
+    vec3 sky = 1.0 - exp(-EXPOSURE * max(xyYToRgb(skyYxy(dir, sunDirection)), 0.0));
+    // the ground below the horizon is lit by the zenith
+    vec3 zenith = 1.0 - exp(-EXPOSURE * max(xyYToRgb(skyYxy(vec3(0.0, 1.0, 0.0), sunDirection)), 0.0));
+    vec3 ground = GROUND_COLOR * zenith;
+    vec3 color = mix(ground, sky, smoothstep(-0.02, 0.0, dir.y));
+    color = mix(NIGHT_COLOR, color, dayWeight);
+
+    float sun = smoothstep(SUN_SIZE, mix(SUN_SIZE, 1.0, 0.3), dot(dir, sunDirection));
+    color = mix(color, SUN_COLOR, sun * step(0.0, dir.y));
+
+    float fog = fogAmount(cameraPos, dir, SKY_DISTANCE, fogParams.x, fogParams.y);
+    outColor = vec4(mix(color, fogColor, fog), 1.0);
+}
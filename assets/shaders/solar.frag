@@ -8,8 +8,10 @@ layout(location = 1) in vec3 cameraPos;
 layout(location = 2) in float cameraDistToContainer;
 layout(location = 3) in vec2 iResolution;
 layout(location = 4) in float iTime;
+layout(location = 5) in vec4 fog;
 
-layout(binding = 1) uniform sampler2D texSampler;
+layout(binding = 1) uniform texture2D image;
+layout(binding = 9) uniform sampler texSampler;
 
 layout(location = 0) out vec4 outColor;
 
@@ -116,9 +118,9 @@ void earth(vec3 camera, vec3 dir, inout float d, inout vec4 color) {
     vec2 dUVdy = dFdy(uv);
     if (abs(dUVdx.x) > 0.5) dUVdx.x = 0.0;
     if (abs(dUVdy.x) > 0.5) dUVdy.x = 0.0;
-    vec4 tex_day = textureGrad(texSampler, uv, dUVdx, dUVdy);
+    vec4 tex_day = textureGrad(sampler2D(image, texSampler), uv, dUVdx, dUVdy);
     uv.y -= 0.5;
-    vec4 tex_night = textureGrad(texSampler, uv, dUVdx, dUVdy);
+    vec4 tex_night = textureGrad(sampler2D(image, texSampler), uv, dUVdx, dUVdy);
 
     vec3 col = brightness * tex_day.xyz + (1.0 - brightness) * tex_night.xyz;
     blend_colors(dists.y, vec4(col, 1.0) * dists.w, d, color);
@@ -184,4 +186,6 @@ void main() {
     }
 
     outColor = vec4(color.rgb / color.a, color.a);
+
+    outColor.rgb = mix(outColor.rgb, fog.rgb, fog.a);
 }
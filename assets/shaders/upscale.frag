@@ -0,0 +1,16 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+// the art drawn at half the resolution over nothing, see src/vulkan/half_res.rs
+layout(binding = 1) uniform texture2D halfRes;
+layout(binding = 9) uniform sampler halfResSampler;
+
+layout(location = 0) out vec4 outColor;
+
+// drawn with the vertex shader of the art, so only the pixels covered by the art are scaled up
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(sampler2D(halfRes, halfResSampler), 0));
+    vec4 color = texture(sampler2D(halfRes, halfResSampler), gl_FragCoord.xy * 0.5 * texel);
+    // the color is premultiplied by the alpha after blending over nothing
+    outColor = vec4(color.rgb / max(color.a, 0.0001), color.a);
+}
@@ -7,31 +7,71 @@ fn main() {
         "shader.frag",
         "cubemap.vert",
         "cubemap.frag",
+        "sky.frag",
+        "error.vert",
+        "error.frag",
+        "fullscreen.vert",
+        "anaglyph.frag",
+        "preview.vert",
+        "graph.frag",
+        "fade.frag",
+        "gizmo.frag",
+        "overlay.frag",
+        "upscale.frag",
+        "grade.frag",
+        "occluder.vert",
+        "depth_pyramid.comp",
+        "occlusion_cull.comp",
+        "light_clusters.comp",
+        "shadow.vert",
+        "volumetric_fog.comp",
     ];
+    // shaders compiled a second time with a macro defined, as (shader, output name, macro)
+    let variants = vec![
+        ("shader.frag", "shader_lightmap.frag", "LIGHTMAP"),
+        ("grade.frag", "grade_multisampled.frag", "MULTISAMPLED"),
+        ("shader.vert", "shader_multiview.vert", "MULTIVIEW"),
+        ("cubemap.vert", "cubemap_multiview.vert", "MULTIVIEW"),
+        ("error.vert", "error_multiview.vert", "MULTIVIEW"),
+    ];
+    // files the shaders include with `#include`
+    let includes = vec!["fog.glsl"];
 
     let src_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join("shaders");
     let out_dir = std::env::var("OUT_DIR").unwrap();
 
     for shader in shaders {
-        let input_path = src_dir.join(shader);
-        let output_path = Path::new(&out_dir).join(format!("{}.spv", shader));
-
-        let output = Command::new("glslangValidator")
-            .arg("-V")
-            .arg(input_path.to_str().unwrap())
-            .arg("-o")
-            .arg(output_path.to_str().unwrap())
-            .output()
-            .expect("Failed to execute glslangValidator");
+        compile(&src_dir.join(shader), &Path::new(&out_dir).join(format!("{shader}.spv")), None);
+    }
+    for (shader, name, define) in variants {
+        let output_path = Path::new(&out_dir).join(format!("{name}.spv"));
+        compile(&src_dir.join(shader), &output_path, Some(define));
+    }
+    for include in includes {
+        println!("cargo:rerun-if-changed={}", src_dir.join(include).to_str().unwrap());
+    }
+}
 
-        if !output.status.success() {
-            panic!(
-                "glslangValidator failed with error: {}\n{}",
-                String::from_utf8_lossy(&output.stderr),
-                String::from_utf8_lossy(&output.stdout),
-            );
-        }
+fn compile(input_path: &Path, output_path: &Path, define: Option<&str>) {
+    let mut command = Command::new("glslangValidator");
+    command.arg("-V");
+    if let Some(define) = define {
+        command.arg(format!("-D{define}"));
+    }
+    let output = command
+        .arg(input_path.to_str().unwrap())
+        .arg("-o")
+        .arg(output_path.to_str().unwrap())
+        .output()
+        .expect("Failed to execute glslangValidator");
 
-        println!("cargo:rerun-if-changed={}", input_path.to_str().unwrap());
+    if !output.status.success() {
+        panic!(
+            "glslangValidator failed with error: {}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            String::from_utf8_lossy(&output.stdout),
+        );
     }
+
+    println!("cargo:rerun-if-changed={}", input_path.to_str().unwrap());
 }
@@ -0,0 +1,132 @@
+//! A gallery with three pieces of art seen from a camera circling around them.
+//!
+//! Run with `cargo run --example custom_gallery [image]` from the root of the repository.
+
+use shaderpixel::{
+    math::{Deg, Matrix4, Vector3},
+    vulkan::{ArtTime, VkApp},
+    ArtPiece, Camera, GalleryBuilder,
+};
+
+use anyhow::Context;
+use winit::{
+    application::ApplicationHandler,
+    event::WindowEvent,
+    event_loop::{ActiveEventLoop, EventLoop},
+    window::{Window, WindowId},
+};
+use std::{f32::consts::TAU, path::{Path, PathBuf}, time::Instant};
+
+/// Seconds the camera needs for one circle.
+const ORBIT_PERIOD: f32 = 30.;
+const ORBIT_RADIUS: f32 = 4.;
+
+struct Example {
+    image: PathBuf,
+    start: Instant,
+    window: Option<Window>,
+    gallery: Option<VkApp>,
+}
+
+impl Example {
+    fn init(&mut self, event_loop: &ActiveEventLoop) -> Result<(), anyhow::Error> {
+        let window_attrs = Window::default_attributes().with_title("custom gallery");
+        let window = event_loop.create_window(window_attrs).context("Failed to create window")?;
+        let art = [
+            ArtPiece::new_2d("Mandelbrot", "assets/shaders/mandelbrot.frag")
+                .at(Matrix4::from_translation([0., 1.5, -5.99].into()) * Matrix4::from_scale(0.8))
+                .time(ArtTime {
+                    speed: 0.5,
+                    ..Default::default()
+                }),
+            ArtPiece::new_3d("Menger Sponge", "assets/shaders/mengersponge.frag")
+                .at(Matrix4::from_translation([0., 1.51, -3.].into()) * Matrix4::from_scale(0.5)),
+            // morphed by assets/scripts/sculpture.rhai
+            ArtPiece::new_3d("Sculpture", "assets/shaders/sculpture.frag")
+                .vert("assets/shaders/morph.vert")
+                .model("assets/models/sculpture.obj")
+                .morph_targets([
+                    "assets/models/sculpture_twist.obj",
+                    "assets/models/sculpture_ripple.obj",
+                    "assets/models/sculpture_cube.obj",
+                ])
+                .at(Matrix4::from_translation([0., 2.6, -3.].into()) * Matrix4::from_scale(0.3)),
+            // a column swaying with the joints of its skin
+            ArtPiece::new_3d("Kinetic Column", "assets/shaders/sculpture.frag")
+                .vert("assets/shaders/skinned.vert")
+                .model("assets/models/kinetic.gltf")
+                .animation("sway")
+                .at(Matrix4::from_translation([2., 0., -3.].into()) * Matrix4::from_scale(0.4)),
+        ];
+        let mut gallery = GalleryBuilder::new(&self.image).art(art).build(&window)?;
+        // scripts are only run with the rhai feature, the sculpture stands still without it
+        if let Err(err) = gallery.set_script(Some(Path::new("assets/scripts/sculpture.rhai"))) {
+            log::warn!("{err:#}");
+        }
+        self.window = Some(window);
+        self.gallery = Some(gallery);
+        Ok(())
+    }
+}
+
+impl ApplicationHandler for Example {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(err) = self.init(event_loop) {
+            log::error!("Error while starting: {err:#}");
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        let (Some(window), Some(gallery)) = (self.window.as_ref(), self.gallery.as_mut()) else {
+            return;
+        };
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(_) => gallery.dirty_swapchain = true,
+            WindowEvent::RedrawRequested => {
+                let size = window.inner_size();
+                if gallery.dirty_swapchain {
+                    if size.width == 0 || size.height == 0 {
+                        return;
+                    }
+                    gallery.recreate_swapchain(size.width, size.height);
+                }
+                let time = self.start.elapsed().as_secs_f32();
+                let angle = TAU * time / ORBIT_PERIOD;
+                let center = Vector3::from([0., 1.5, -3.]);
+                let offset = Vector3::from([angle.sin(), 0., angle.cos()]) * ORBIT_RADIUS;
+                // look back at the center from a point on the circle
+                let yaw = Deg(-angle.to_degrees());
+                Camera::new(center + offset, yaw, Deg(0.)).apply(&mut gallery.scene);
+                gallery.dirty_swapchain = gallery.draw_frame(time);
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, _: &ActiveEventLoop) {
+        if let Some(window) = self.window.as_ref() {
+            window.request_redraw();
+        }
+    }
+
+    fn exiting(&mut self, _: &ActiveEventLoop) {
+        if let Some(gallery) = self.gallery.as_ref() {
+            gallery.wait_gpu_idle();
+        }
+    }
+}
+
+fn main() -> Result<(), anyhow::Error> {
+    env_logger::init();
+    let image = std::env::args().nth(1).unwrap_or_else(|| "assets/images/grid.png".to_owned());
+    let mut example = Example {
+        image: image.into(),
+        start: Instant::now(),
+        window: None,
+        gallery: None,
+    };
+    EventLoop::new()?.run_app(&mut example)?;
+    Ok(())
+}
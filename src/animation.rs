@@ -0,0 +1,138 @@
+use anyhow::Context;
+use image::{
+    codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder},
+    AnimationDecoder, Frames, ImageFormat, ImageReader,
+};
+use std::{fs::File, io::BufReader, path::Path};
+
+/// Frames shorter than this are shown for [`DEFAULT_DELAY`] like browsers do.
+const MIN_DELAY: f32 = 0.02;
+const DEFAULT_DELAY: f32 = 0.1;
+/// Frames beyond this many bytes of texels are dropped to not run out of memory.
+const MAX_BYTES: usize = 1 << 30;
+
+/// The frames of an animated GIF, PNG or WebP.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub width: u32,
+    pub height: u32,
+    /// The RGBA texels of every frame row by row, flipped so that y points up like other
+    /// textures.
+    frames: Vec<Vec<u8>>,
+    /// The time in seconds at which each frame ends.
+    ends: Vec<f32>,
+}
+
+impl Animation {
+    /// Decodes all frames of the image at `path`.
+    ///
+    /// Returns `None` if the image is not animated or has a single frame.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Option<Self>, anyhow::Error> {
+        let path = path.as_ref();
+        let format = ImageReader::open(path)
+            .context("Failed to open image")?
+            .with_guessed_format()?
+            .format();
+        let reader = || -> Result<_, anyhow::Error> { Ok(BufReader::new(File::open(path)?)) };
+        let frames = match format {
+            Some(ImageFormat::Gif) => GifDecoder::new(reader()?)?.into_frames(),
+            Some(ImageFormat::Png) => {
+                let decoder = PngDecoder::new(reader()?)?;
+                if !decoder.is_apng()? {
+                    return Ok(None);
+                }
+                decoder.apng()?.into_frames()
+            }
+            Some(ImageFormat::WebP) => {
+                let decoder = WebPDecoder::new(reader()?)?;
+                if !decoder.has_animation() {
+                    return Ok(None);
+                }
+                decoder.into_frames()
+            }
+            _ => return Ok(None),
+        };
+        let animation = Self::from_frames(frames).context("Failed to decode animation")?;
+        if let Some(animation) = animation.as_ref() {
+            log::info!(
+                "Loaded {} frames of {}x{} lasting {:.2}s",
+                animation.frames.len(),
+                animation.width,
+                animation.height,
+                animation.duration(),
+            );
+        }
+        Ok(animation)
+    }
+
+    fn from_frames(frames: Frames) -> Result<Option<Self>, anyhow::Error> {
+        let mut animation = Self {
+            width: 0,
+            height: 0,
+            frames: Vec::new(),
+            ends: Vec::new(),
+        };
+        let mut bytes = 0;
+        for frame in frames {
+            let frame = frame?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay = match numer as f32 / denom as f32 / 1000. {
+                delay if delay < MIN_DELAY => DEFAULT_DELAY,
+                delay => delay,
+            };
+            // the decoders compose every frame onto the whole canvas
+            let buffer = image::imageops::flip_vertical(frame.buffer());
+            bytes += buffer.len();
+            if bytes > MAX_BYTES {
+                let count = animation.frames.len();
+                log::warn!("Dropping the frames after {count} to stay below {MAX_BYTES} bytes");
+                break;
+            }
+            (animation.width, animation.height) = buffer.dimensions();
+            animation.ends.push(animation.duration() + delay);
+            animation.frames.push(buffer.into_raw());
+        }
+        Ok(Some(animation).filter(|animation| animation.frames.len() > 1))
+    }
+
+    /// Seconds one loop of the animation lasts.
+    pub fn duration(&self) -> f32 {
+        self.ends.last().copied().unwrap_or_default()
+    }
+
+    /// The index of the frame that is shown `time` seconds after the start, the animation loops.
+    pub fn frame_at(&self, time: f32) -> usize {
+        let time = time.rem_euclid(self.duration());
+        self.ends.partition_point(|&end| end <= time).min(self.frames.len() - 1)
+    }
+
+    /// The texels of the frame at `index`.
+    pub fn frame(&self, index: usize) -> &[u8] {
+        &self.frames[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Delay, Frame, RgbaImage};
+
+    #[test]
+    fn frame_timing() {
+        let frame = |ms| {
+            Ok(Frame::from_parts(RgbaImage::new(2, 1), 0, 0, Delay::from_numer_denom_ms(ms, 1)))
+        };
+        let frames = Frames::new(Box::new([frame(100), frame(0), frame(300)].into_iter()));
+        let animation = Animation::from_frames(frames).unwrap().unwrap();
+        assert_eq!([animation.width, animation.height], [2, 1]);
+        assert_eq!(animation.frame(0).len(), 8);
+        assert!((animation.duration() - 0.5).abs() < 1e-6);
+        assert_eq!(animation.frame_at(0.), 0);
+        assert_eq!(animation.frame_at(0.15), 1);
+        assert_eq!(animation.frame_at(0.45), 2);
+        assert_eq!(animation.frame_at(0.55), 0);
+
+        let single = Frames::new(Box::new([frame(100)].into_iter()));
+        assert_eq!(Animation::from_frames(single).unwrap(), None);
+    }
+}
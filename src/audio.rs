@@ -0,0 +1,446 @@
+use crate::math::Vector3;
+
+use anyhow::Context;
+#[cfg(not(feature = "rodio"))]
+use anyhow::anyhow;
+#[cfg(feature = "rodio")]
+use rodio::source::UniformSourceIterator;
+use std::{
+    f32::consts::FRAC_PI_4,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+#[cfg(feature = "rodio")]
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+#[cfg(not(feature = "rodio"))]
+use std::{
+    io::{ErrorKind, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+/// The sample rate the sounds are decoded and mixed at.
+const SAMPLE_RATE: u32 = 44100;
+/// Frames mixed at once, the volumes change smoothly over them.
+const CHUNK_FRAMES: usize = 1024;
+/// How far a sound to the side is panned, at 1 it would only be heard in one ear.
+const PAN_WIDTH: f32 = 0.8;
+
+/// A looping sound that plays around an art piece.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    /// The name of the art the sound comes from.
+    pub art: String,
+    pub path: PathBuf,
+    /// The distance within which the sound is at full volume.
+    pub near: f32,
+    /// The distance at which the sound has faded out.
+    pub far: f32,
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<Emitter>, anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read sounds {}", path.display()))?;
+    parse(&text).with_context(|| format!("Failed to parse sounds {}", path.display()))
+}
+
+/// Parses one sound per line as the name of the art in quotes, the sound file and the
+/// distances it is at full volume within and faded out at, e.g.
+///
+/// ```text
+/// # the sun hums when the camera comes close
+/// "Solar"  assets/audio/solar.ogg  1 6
+/// ```
+pub fn parse(text: &str) -> Result<Vec<Emitter>, anyhow::Error> {
+    let mut emitters = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = index + 1;
+        let (art, rest) = line.strip_prefix('"')
+            .and_then(|line| line.split_once('"'))
+            .with_context(|| format!("Expected the name of the art in quotes in line {number}"))?;
+        let fields = rest.split_whitespace().collect::<Vec<_>>();
+        let [path, near, far] = fields[..] else {
+            anyhow::bail!("Expected a file and 2 distances in line {number}: {line}");
+        };
+        let parse = |value: &str| {
+            value.parse::<f32>().with_context(|| format!("Invalid number in line {number}: {line}"))
+        };
+        let (near, far) = (parse(near)?, parse(far)?);
+        anyhow::ensure!(
+            near > 0. && far > near,
+            "The distances in line {number} have to be positive and increasing",
+        );
+        emitters.push(Emitter {
+            art: art.to_owned(),
+            path: PathBuf::from(path),
+            near,
+            far,
+        });
+    }
+    Ok(emitters)
+}
+
+/// The volumes of the left and the right channel of a sound at `source` heard from
+/// `listener`, whose right points along the normalized `right`.
+///
+/// The sound is at full volume within `near` and falls off with the inverse distance beyond,
+/// which is faded out linearly towards `far`, so that it stops there. It is panned with
+/// equal power, so that it is as loud when it goes around the listener.
+pub fn stereo_gains(
+    listener: Vector3,
+    right: Vector3,
+    source: Vector3,
+    near: f32,
+    far: f32,
+) -> [f32; 2] {
+    let offset = source - listener;
+    let distance = offset.magnitude();
+    if distance >= far {
+        return [0., 0.];
+    }
+    let attenuation = near / distance.max(near);
+    let fade = ((far - distance) / (far - near)).min(1.);
+    let volume = attenuation * fade;
+    // from -1 on the left to 1 on the right, the sound is in the middle when on the listener
+    let pan = if distance > 1e-3 { offset.dot(right) / distance } else { 0. };
+    let angle = (pan * PAN_WIDTH + 1.) * FRAC_PI_4;
+    [volume * angle.cos(), volume * angle.sin()]
+}
+
+/// Plays the sounds of [`Emitter`]s around the art, louder the closer the camera is and
+/// panned to the side the art is on.
+///
+/// With the `rodio` feature the sounds are decoded and played by rodio. Otherwise they are
+/// decoded with ffmpeg up front and mixed on a separate thread, which pipes the mix into
+/// ffplay. Both have to be in the `PATH` then.
+pub struct AmbientAudio {
+    emitters: Vec<Emitter>,
+    /// The volumes of the channels of every emitter, shared with the mixer.
+    gains: Arc<Mutex<Vec<[f32; 2]>>>,
+    _output: Output,
+}
+
+impl AmbientAudio {
+    /// Decodes the sounds of `emitters` and starts playing them silently until the first
+    /// [`Self::update`].
+    pub fn new(emitters: Vec<Emitter>) -> Result<Self, anyhow::Error> {
+        let sounds = emitters.iter()
+            .map(|emitter| decode(&emitter.path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let gains = Arc::new(Mutex::new(vec![[0., 0.]; emitters.len()]));
+        let output = Output::start(Mixer::new(sounds, Arc::clone(&gains)))?;
+        log::info!("Playing {} sounds around the art", emitters.len());
+        Ok(Self { emitters, gains, _output: output })
+    }
+
+    /// Sets the volumes for the camera at `listener` whose right points along `right`,
+    /// `art_position` finds the art the sounds come from. The sound of missing art is silent.
+    pub fn update(
+        &self,
+        listener: Vector3,
+        right: Vector3,
+        art_position: impl Fn(&str) -> Option<Vector3>,
+    ) {
+        let gains = self.emitters.iter().map(|emitter| {
+            art_position(&emitter.art).map_or([0., 0.], |source| {
+                stereo_gains(listener, right, source, emitter.near, emitter.far)
+            })
+        }).collect();
+        *self.gains.lock().unwrap() = gains;
+    }
+}
+
+/// Loops sounds with their gains into interleaved stereo samples, the gains are interpolated
+/// over each chunk to avoid clicks.
+struct Mixer {
+    sounds: Vec<Vec<f32>>,
+    gains: Arc<Mutex<Vec<[f32; 2]>>>,
+    positions: Vec<usize>,
+    previous: Vec<[f32; 2]>,
+}
+
+impl Mixer {
+    fn new(sounds: Vec<Vec<f32>>, gains: Arc<Mutex<Vec<[f32; 2]>>>) -> Self {
+        let count = sounds.len();
+        Self { sounds, gains, positions: vec![0; count], previous: vec![[0., 0.]; count] }
+    }
+
+    /// Mixes the next [`CHUNK_FRAMES`] frames into `samples`.
+    fn mix(&mut self, samples: &mut [f32; CHUNK_FRAMES * 2]) {
+        let targets = self.gains.lock().unwrap().clone();
+        samples.fill(0.);
+        for (i, sound) in self.sounds.iter().enumerate() {
+            let [left_from, right_from] = self.previous[i];
+            let [left_to, right_to] = targets[i];
+            for (frame, channels) in samples.chunks_exact_mut(2).enumerate() {
+                let t = frame as f32 / CHUNK_FRAMES as f32;
+                let sample = sound[self.positions[i]];
+                self.positions[i] = (self.positions[i] + 1) % sound.len();
+                channels[0] += sample * (left_from + (left_to - left_from) * t);
+                channels[1] += sample * (right_from + (right_to - right_from) * t);
+            }
+        }
+        self.previous = targets;
+        for sample in samples.iter_mut() {
+            *sample = sample.clamp(-1., 1.);
+        }
+    }
+}
+
+/// Where the mix is played, it stops when this is dropped.
+#[cfg(feature = "rodio")]
+struct Output {
+    _stream: rodio::OutputStream,
+    stopped: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "rodio")]
+impl Output {
+    fn start(mixer: Mixer) -> Result<Self, anyhow::Error> {
+        let (stream, handle) = rodio::OutputStream::try_default()
+            .context("Failed to open the audio output")?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let source = MixSource {
+            mixer,
+            samples: Box::new([0.; CHUNK_FRAMES * 2]),
+            next: CHUNK_FRAMES * 2,
+            stopped: Arc::clone(&stopped),
+        };
+        handle.play_raw(source).context("Failed to play the sounds")?;
+        Ok(Self { _stream: stream, stopped })
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl Drop for Output {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// The mix as a source of rodio, which ends once the [`Output`] is dropped.
+#[cfg(feature = "rodio")]
+struct MixSource {
+    mixer: Mixer,
+    samples: Box<[f32; CHUNK_FRAMES * 2]>,
+    /// The index of the next sample in `samples`, a new chunk is mixed at the end.
+    next: usize,
+    stopped: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "rodio")]
+impl Iterator for MixSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.next == self.samples.len() {
+            if self.stopped.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.mixer.mix(&mut self.samples);
+            self.next = 0;
+        }
+        self.next += 1;
+        Some(self.samples[self.next - 1])
+    }
+}
+
+#[cfg(feature = "rodio")]
+impl rodio::Source for MixSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Decodes the sound at `path` into mono samples with rodio.
+#[cfg(feature = "rodio")]
+fn decode(path: &Path) -> Result<Vec<f32>, anyhow::Error> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let decoder = rodio::Decoder::new(BufReader::new(file))
+        .with_context(|| format!("Failed to decode {}", path.display()))?;
+    let samples = UniformSourceIterator::<_, f32>::new(decoder, 1, SAMPLE_RATE)
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!samples.is_empty(), "{} has no sound", path.display());
+    Ok(samples)
+}
+
+#[cfg(not(feature = "rodio"))]
+struct Output {
+    mixer: Option<JoinHandle<()>>,
+    ffplay: Child,
+}
+
+#[cfg(not(feature = "rodio"))]
+impl Output {
+    fn start(mixer: Mixer) -> Result<Self, anyhow::Error> {
+        let rate = SAMPLE_RATE.to_string();
+        let mut ffplay = spawn(
+            Command::new("ffplay")
+                .args(["-loglevel", "error", "-nodisp", "-f", "f32le", "-ar", &rate])
+                .args(["-ch_layout", "stereo", "-i", "-"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null()),
+        )?;
+        let stdin = ffplay.stdin.take().unwrap();
+        let mixer = thread::spawn(move || play(stdin, mixer));
+        Ok(Self { mixer: Some(mixer), ffplay })
+    }
+}
+
+#[cfg(not(feature = "rodio"))]
+impl Drop for Output {
+    fn drop(&mut self) {
+        // the mixer stops once it can not write anymore
+        if let Err(err) = self.ffplay.kill() {
+            log::warn!("Failed to stop ffplay: {err}");
+        }
+        let _ = self.ffplay.wait();
+        if self.mixer.take().map(JoinHandle::join).is_some_and(|result| result.is_err()) {
+            log::error!("Audio mixer panicked");
+        }
+    }
+}
+
+/// Starts `command` of ffmpeg, telling how to get it if it is not in the `PATH`.
+#[cfg(not(feature = "rodio"))]
+fn spawn(command: &mut Command) -> Result<Child, anyhow::Error> {
+    let name = command.get_program().to_string_lossy().into_owned();
+    command.spawn().map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "{name} is not in the PATH, install ffmpeg or build with the rodio feature \
+             to play sounds",
+        ),
+        _ => anyhow!(err).context(format!("Failed to start {name}")),
+    })
+}
+
+/// Decodes the sound at `path` into mono samples with ffmpeg.
+#[cfg(not(feature = "rodio"))]
+fn decode(path: &Path) -> Result<Vec<f32>, anyhow::Error> {
+    let output = spawn(
+        Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-i"])
+            .arg(path)
+            .args(["-vn", "-f", "f32le", "-ac", "1", "-ar", &SAMPLE_RATE.to_string(), "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )?.wait_with_output()?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "Failed to decode {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim(),
+        ));
+    }
+    let samples = output.stdout.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+        .collect::<Vec<_>>();
+    anyhow::ensure!(!samples.is_empty(), "{} has no sound", path.display());
+    Ok(samples)
+}
+
+/// Writes the mix of `mixer` into `stdin` until it is closed. Writing blocks while ffplay has
+/// enough samples, which keeps the mix in time.
+#[cfg(not(feature = "rodio"))]
+fn play(mut stdin: ChildStdin, mut mixer: Mixer) {
+    let mut samples = [0.; CHUNK_FRAMES * 2];
+    let mut bytes = Vec::with_capacity(samples.len() * 4);
+    loop {
+        mixer.mix(&mut samples);
+        bytes.clear();
+        bytes.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+        if stdin.write_all(&bytes).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_emitters() {
+        let text = "# sounds\n\n\"Menger Sponge\" sponge.ogg 1 5\n \"Solar\"\tsolar.wav 0.5 4 \n";
+        let emitters = parse(text).unwrap();
+        assert_eq!(emitters, [
+            Emitter {
+                art: "Menger Sponge".to_owned(),
+                path: PathBuf::from("sponge.ogg"),
+                near: 1.,
+                far: 5.,
+            },
+            Emitter {
+                art: "Solar".to_owned(),
+                path: PathBuf::from("solar.wav"),
+                near: 0.5,
+                far: 4.,
+            },
+        ]);
+        assert!(parse("Solar solar.wav 1 4").is_err());
+        assert!(parse("\"Solar\" solar.wav 4 1").is_err());
+        assert!(parse("\"Solar\" solar.wav 1").is_err());
+    }
+
+    #[test]
+    fn mix_sounds() {
+        let gains = Arc::new(Mutex::new(vec![[1., 0.5], [0., 0.]]));
+        let mut mixer = Mixer::new(vec![vec![0.5, -0.5], vec![1.]], Arc::clone(&gains));
+        let mut samples = [0.; CHUNK_FRAMES * 2];
+        // the gains fade in from silence over the first chunk
+        mixer.mix(&mut samples);
+        assert_eq!(samples[..2], [0., 0.]);
+        let [left, right] = [samples[samples.len() - 2], samples[samples.len() - 1]];
+        assert!(left < -0.49 && right < -0.24);
+        mixer.mix(&mut samples);
+        assert_eq!(samples[..4], [0.5, 0.25, -0.5, -0.25]);
+        // the mix is clipped
+        *gains.lock().unwrap() = vec![[4., 4.], [4., 4.]];
+        mixer.mix(&mut samples);
+        mixer.mix(&mut samples);
+        assert_eq!(samples[..4], [1., 1., 1., 1.]);
+    }
+
+    #[test]
+    fn attenuate_and_pan() {
+        let listener = Vector3::from([0., 1.5, 0.]);
+        let right = Vector3::from([1., 0., 0.]);
+        let gains = |x, z| stereo_gains(listener, right, Vector3::from([x, 1.5, z]), 1., 5.);
+        // in front within the near distance at full power in both ears
+        let [left, right_gain] = gains(0., -0.5);
+        assert!((left - right_gain).abs() < 1e-6);
+        assert!((left * left + right_gain * right_gain - 1.).abs() < 1e-5);
+        // to the right louder on the right
+        let [left, right_gain] = gains(2., 0.);
+        assert!(right_gain > left && left > 0.);
+        let [mirrored_left, mirrored_right] = gains(-2., 0.);
+        assert!((mirrored_left - right_gain).abs() < 1e-6);
+        assert!((mirrored_right - left).abs() < 1e-6);
+        // quieter further away and silent at the far distance
+        assert!(gains(0., -3.)[0] < gains(0., -2.)[0]);
+        assert_eq!(gains(0., -5.), [0., 0.]);
+    }
+}
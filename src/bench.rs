@@ -0,0 +1,178 @@
+use crate::math::{Deg, Vector3};
+use crate::slideshow::Orbit;
+use crate::vulkan::GpuTimes;
+
+use std::io::{self, Write};
+
+/// The camera path of the benchmark.
+const PATH: Orbit = Orbit {
+    period: 20.,
+    radius: 3.,
+    height: 1.5,
+};
+
+/// The times of a single frame in milliseconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameTimes {
+    /// Seconds since the start of the benchmark.
+    pub time: f32,
+    /// Time since the previous frame.
+    pub frame: f32,
+    /// Time the CPU spent updating and submitting the frame.
+    pub cpu: f32,
+    pub gpu: Option<GpuTimes>,
+}
+
+/// Summary statistics of a series of times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub min: f32,
+    pub mean: f32,
+    pub median: f32,
+    /// The 99th percentile.
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Stats {
+    /// Returns `None` if there are no times.
+    pub fn new(times: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let mut times = times.into_iter().collect::<Vec<_>>();
+        if times.is_empty() {
+            return None;
+        }
+        times.sort_by(f32::total_cmp);
+        let percentile = |p: f32| times[((times.len() - 1) as f32 * p).round() as usize];
+        Some(Self {
+            min: times[0],
+            mean: times.iter().sum::<f32>() / times.len() as f32,
+            median: percentile(0.5),
+            p99: percentile(0.99),
+            max: times[times.len() - 1],
+        })
+    }
+}
+
+/// Flies the camera along a fixed path for a fixed time and records the frame times,
+/// so that the performance can be compared across machines and changes.
+#[derive(Debug, Clone)]
+pub struct Benchmark {
+    /// Seconds the benchmark runs for.
+    duration: f32,
+    /// Names of the pipelines in the order of [`GpuTimes::pipelines`].
+    pipeline_names: Vec<String>,
+    frames: Vec<FrameTimes>,
+}
+
+impl Benchmark {
+    pub fn new(duration: f32, pipeline_names: Vec<String>) -> Self {
+        Self {
+            duration,
+            pipeline_names,
+            frames: Vec::new(),
+        }
+    }
+
+    /// The position and the yaw of the camera `time` seconds into the benchmark.
+    ///
+    /// The camera circles the center of the gallery while looking at it.
+    pub fn camera(time: f32) -> (Vector3, Deg<f32>) {
+        PATH.camera(time)
+    }
+
+    pub fn is_done(&self, time: f32) -> bool {
+        time >= self.duration
+    }
+
+    pub fn record(&mut self, times: FrameTimes) {
+        self.frames.push(times);
+    }
+
+    /// Writes one line per frame with the times in milliseconds,
+    /// columns of pipelines that did not draw in a frame are left empty.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "time,frame_ms,cpu_ms,gpu_ms")?;
+        for name in self.pipeline_names.iter() {
+            write!(writer, ",\"{}\"", name.replace('"', "\"\""))?;
+        }
+        writeln!(writer)?;
+        for frame in self.frames.iter() {
+            write!(writer, "{},{},{},", frame.time, frame.frame, frame.cpu)?;
+            if let Some(gpu) = frame.gpu.as_ref() {
+                write!(writer, "{}", gpu.frame)?;
+            }
+            for i in 0..self.pipeline_names.len() {
+                write!(writer, ",")?;
+                let time = frame.gpu.as_ref().and_then(|gpu| gpu.pipelines.get(i).copied().flatten());
+                if let Some(time) = time {
+                    write!(writer, "{time}")?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    /// The statistics of the frame, CPU and GPU times and the mean GPU time of every pipeline.
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} frames in {}s\n", self.frames.len(), self.duration);
+        let series = [
+            ("frame", Stats::new(self.frames.iter().map(|frame| frame.frame))),
+            ("cpu", Stats::new(self.frames.iter().map(|frame| frame.cpu))),
+            ("gpu", Stats::new(self.gpu_frames().map(|gpu| gpu.frame))),
+        ];
+        for (name, stats) in series {
+            if let Some(stats) = stats {
+                summary += &format!(
+                    "{name:>5}: mean {:.3}ms, median {:.3}ms, p99 {:.3}ms, min {:.3}ms, max {:.3}ms\n",
+                    stats.mean, stats.median, stats.p99, stats.min, stats.max,
+                );
+            }
+        }
+        for (i, name) in self.pipeline_names.iter().enumerate() {
+            let times = self.gpu_frames().filter_map(|gpu| gpu.pipelines.get(i).copied().flatten());
+            if let Some(stats) = Stats::new(times) {
+                summary += &format!("{name}: mean {:.3}ms, p99 {:.3}ms\n", stats.mean, stats.p99);
+            }
+        }
+        summary
+    }
+
+    fn gpu_frames(&self) -> impl Iterator<Item = &GpuTimes> {
+        self.frames.iter().filter_map(|frame| frame.gpu.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        assert_eq!(Stats::new([]), None);
+        let stats = Stats::new((1..=100).rev().map(|i| i as f32)).unwrap();
+        assert_eq!(stats.min, 1.);
+        assert_eq!(stats.mean, 50.5);
+        assert_eq!(stats.median, 51.);
+        assert_eq!(stats.p99, 99.);
+        assert_eq!(stats.max, 100.);
+    }
+
+    #[test]
+    fn csv() {
+        let mut bench = Benchmark::new(1., vec!["a".to_owned(), "b".to_owned()]);
+        bench.record(FrameTimes {
+            time: 0.5,
+            frame: 16.,
+            cpu: 2.,
+            gpu: Some(GpuTimes { frame: 8., pipelines: vec![Some(1.), None] }),
+        });
+        bench.record(FrameTimes { time: 1., frame: 17., cpu: 3., gpu: None });
+        let mut csv = Vec::new();
+        bench.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "time,frame_ms,cpu_ms,gpu_ms,\"a\",\"b\"\n0.5,16,2,8,1,\n1,17,3,,,\n",
+        );
+    }
+}
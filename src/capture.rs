@@ -0,0 +1,408 @@
+use crate::math::Vector3;
+
+use anyhow::{anyhow, Context};
+use image::{imageops, Rgba, RgbaImage};
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    io::{self, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    str::FromStr,
+    sync::{
+        mpsc::{self, SyncSender, TrySendError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Number of frames waiting for ffmpeg before new frames are dropped.
+const VIDEO_QUEUE_LEN: usize = 8;
+
+/// A face of a cube around the camera, seen with a field of view of 90 degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubeFace {
+    /// The direction the camera looks at.
+    pub forward: Vector3,
+    /// The direction that is at the top of the image.
+    pub up: Vector3,
+}
+
+impl CubeFace {
+    const fn new(forward: [f32; 3], up: [f32; 3]) -> Self {
+        Self {
+            forward: Vector3::new_init(forward),
+            up: Vector3::new_init(up),
+        }
+    }
+
+    /// The coordinates in `[0, 1]` from the top left of this face where `dir` is seen,
+    /// `None` if `dir` does not point through this face.
+    fn project(&self, dir: Vector3) -> Option<[f32; 2]> {
+        // the same basis as `Matrix4::look_to_rh`
+        let right = self.forward.cross(self.up).normalize();
+        let up = right.cross(self.forward);
+        let depth = dir.dot(self.forward);
+        let x = dir.dot(right) / depth;
+        let y = dir.dot(up) / depth;
+        (depth > 0. && x.abs() <= 1. && y.abs() <= 1.).then_some([(x + 1.) / 2., (1. - y) / 2.])
+    }
+}
+
+/// The faces a panorama is rendered from, looking along +x, -x, +y, -y, +z and -z.
+pub const CUBE_FACES: [CubeFace; 6] = [
+    CubeFace::new([1., 0., 0.], [0., 1., 0.]),
+    CubeFace::new([-1., 0., 0.], [0., 1., 0.]),
+    CubeFace::new([0., 1., 0.], [0., 0., 1.]),
+    CubeFace::new([0., -1., 0.], [0., 0., -1.]),
+    CubeFace::new([0., 0., 1.], [0., 1., 0.]),
+    CubeFace::new([0., 0., -1.], [0., 1., 0.]),
+];
+
+/// Stitches the images of the [`CUBE_FACES`] into an equirectangular panorama
+/// that is `width` pixels wide and half as high.
+///
+/// The center of the panorama looks along -z and the left and right edges along +z.
+pub fn equirectangular(faces: &[RgbaImage; 6], width: u32) -> RgbaImage {
+    let height = width / 2;
+    RgbaImage::from_fn(width, height, |x, y| {
+        let longitude = (x as f32 + 0.5) / width as f32 * 2. * PI - PI;
+        let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+        let dir = Vector3::from([
+            latitude.cos() * longitude.sin(),
+            latitude.sin(),
+            -latitude.cos() * longitude.cos(),
+        ]);
+        CUBE_FACES.iter()
+            .zip(faces)
+            .find_map(|(face, image)| {
+                let [u, v] = face.project(dir)?;
+                imageops::sample_bilinear(image, u, v)
+            })
+            .unwrap_or(Rgba([0, 0, 0, u8::MAX]))
+    })
+}
+
+/// The codecs videos are encoded with.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum VideoCodec {
+    /// H.264 in an MP4 container.
+    #[default]
+    H264,
+    /// VP9 in a WebM container.
+    Vp9,
+}
+
+impl VideoCodec {
+    /// The file extension of the container.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::H264 => "mp4",
+            Self::Vp9 => "webm",
+        }
+    }
+
+    /// The ffmpeg arguments for the encoder, fast enough to encode in real time.
+    fn ffmpeg_args(self) -> Vec<&'static str> {
+        let encoder: &[_] = match self {
+            Self::H264 => &["-c:v", "libx264", "-preset", "veryfast"],
+            Self::Vp9 => &["-c:v", "libvpx-vp9", "-deadline", "realtime", "-cpu-used", "8"],
+        };
+        // yuv420p plays everywhere but needs even dimensions
+        let pixel_format = ["-pix_fmt", "yuv420p", "-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"];
+        [encoder, &pixel_format].concat()
+    }
+}
+
+impl FromStr for VideoCodec {
+    type Err = anyhow::Error;
+
+    /// Parses `h264` or `vp9`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "h264" => Ok(Self::H264),
+            "vp9" => Ok(Self::Vp9),
+            _ => Err(anyhow!("unknown video codec {s:?}")),
+        }
+    }
+}
+
+/// Records frames into a video by piping them into an ffmpeg child process.
+///
+/// The frames are written on a separate thread, if ffmpeg falls behind they are dropped.
+pub struct VideoRecorder {
+    dimensions: [u32; 2],
+    fps: u32,
+    /// The time of the first frame.
+    start: Option<f32>,
+    /// Number of frames passed to ffmpeg.
+    frames: u64,
+    sender: Option<SyncSender<Arc<[u8]>>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+    ffmpeg: Child,
+}
+
+impl VideoRecorder {
+    /// Starts ffmpeg to encode frames of `dimensions` with `codec` at `fps` into `path`.
+    ///
+    /// Fails if ffmpeg can not be started, it has to be in the `PATH`.
+    pub fn new(
+        path: impl AsRef<Path>,
+        codec: VideoCodec,
+        dimensions: [u32; 2],
+        fps: u32,
+    ) -> Result<Self, anyhow::Error> {
+        let (ffmpeg, stdin) = spawn_ffmpeg(dimensions, fps as f32, &codec.ffmpeg_args(), path)?;
+        let (sender, receiver) = mpsc::sync_channel(VIDEO_QUEUE_LEN);
+        let writer = thread::spawn(move || Self::write_frames(stdin, receiver));
+        Ok(Self {
+            dimensions,
+            fps,
+            start: None,
+            frames: 0,
+            sender: Some(sender),
+            writer: Some(writer),
+            ffmpeg,
+        })
+    }
+
+    fn write_frames(
+        mut stdin: ChildStdin,
+        receiver: mpsc::Receiver<Arc<[u8]>>,
+    ) -> io::Result<()> {
+        for frame in receiver {
+            stdin.write_all(&frame)?;
+        }
+        // closing stdin lets ffmpeg finish the file
+        Ok(())
+    }
+
+    /// The dimensions of the frames.
+    pub fn dimensions(&self) -> [u32; 2] {
+        self.dimensions
+    }
+
+    /// Adds `frame` drawn at `time` in seconds.
+    ///
+    /// The video has a constant frame rate, so the frame is repeated to fill a gap since the
+    /// previous frame or dropped if it comes too early.
+    pub fn push(&mut self, time: f32, frame: RgbaImage) -> Result<(), anyhow::Error> {
+        if frame.dimensions() != self.dimensions.into() {
+            return Err(anyhow!(
+                "Frame has the dimensions {:?} instead of {:?}",
+                frame.dimensions(),
+                self.dimensions,
+            ));
+        }
+        let start = *self.start.get_or_insert(time);
+        let due = frames_due(time - start, self.fps);
+        if due <= self.frames {
+            return Ok(());
+        }
+        let sender = self.sender.as_ref().context("The recorder is finished")?;
+        let frame: Arc<[u8]> = frame.into_raw().into();
+        while self.frames < due {
+            match sender.try_send(frame.clone()) {
+                Ok(()) => self.frames += 1,
+                // the frames are repeated once there is space again
+                Err(TrySendError::Full(_)) => {
+                    log::debug!("Dropped a frame, ffmpeg is too slow");
+                    break;
+                }
+                Err(TrySendError::Disconnected(_)) => return self.close(),
+            }
+        }
+        Ok(())
+    }
+
+    /// Waits until ffmpeg wrote all frames and finished the video.
+    pub fn finish(mut self) -> Result<(), anyhow::Error> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<(), anyhow::Error> {
+        self.sender = None;
+        let written = match self.writer.take() {
+            Some(writer) => writer.join().map_err(|_| anyhow!("Frame writer panicked"))?,
+            None => Ok(()),
+        };
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            return Err(anyhow!("ffmpeg failed with {status}"));
+        }
+        written.context("Failed to write frames to ffmpeg")?;
+        Ok(())
+    }
+}
+
+impl Drop for VideoRecorder {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            if let Err(err) = self.close() {
+                log::error!("Failed to finish the video: {err}");
+            }
+        }
+    }
+}
+
+/// The formats of exported loops.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LoopFormat {
+    #[default]
+    Gif,
+    /// Animated WebP, smaller than a GIF and not limited to 256 colors.
+    Webp,
+}
+
+impl LoopFormat {
+    /// The file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Webp => "webp",
+        }
+    }
+
+    /// The ffmpeg arguments for the encoder, the output loops forever.
+    fn ffmpeg_args(self) -> &'static [&'static str] {
+        match self {
+            // a palette generated from all frames looks better than the default one
+            Self::Gif => &["-vf", "split[a][b];[a]palettegen[p];[b][p]paletteuse", "-loop", "0"],
+            Self::Webp => &["-c:v", "libwebp_anim", "-quality", "90", "-loop", "0"],
+        }
+    }
+}
+
+impl FromStr for LoopFormat {
+    type Err = anyhow::Error;
+
+    /// Parses `gif` or `webp`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gif" => Ok(Self::Gif),
+            "webp" => Ok(Self::Webp),
+            _ => Err(anyhow!("unknown loop format {s:?}")),
+        }
+    }
+}
+
+/// One loop of the animation of an art piece, see `VkApp::render_art_loop`.
+pub struct ArtLoop {
+    /// The name of the art.
+    pub name: String,
+    /// Seconds the loop lasts.
+    pub period: f32,
+    /// The frames evenly spaced over the period.
+    pub frames: Vec<RgbaImage>,
+}
+
+impl ArtLoop {
+    /// Encodes the frames into an endlessly looping animation of `format` at `path` with ffmpeg.
+    pub fn save(&self, path: impl AsRef<Path>, format: LoopFormat) -> Result<(), anyhow::Error> {
+        let first = self.frames.first().context("The loop has no frames")?;
+        let dimensions = [first.width(), first.height()];
+        let fps = self.frames.len() as f32 / self.period;
+        let (mut ffmpeg, mut stdin) = spawn_ffmpeg(dimensions, fps, format.ffmpeg_args(), path)?;
+        let written = self.frames.iter().try_for_each(|frame| stdin.write_all(frame.as_raw()));
+        // closing stdin lets ffmpeg finish the file
+        drop(stdin);
+        let status = ffmpeg.wait()?;
+        if !status.success() {
+            return Err(anyhow!("ffmpeg failed with {status}"));
+        }
+        written.context("Failed to write frames to ffmpeg")?;
+        Ok(())
+    }
+}
+
+/// Starts ffmpeg to encode raw RGBA frames of `dimensions` at `fps` written to the returned
+/// stdin into `path` with the output `args`.
+fn spawn_ffmpeg(
+    dimensions: [u32; 2],
+    fps: f32,
+    args: &[&str],
+    path: impl AsRef<Path>,
+) -> Result<(Child, ChildStdin), anyhow::Error> {
+    let [width, height] = dimensions;
+    let mut ffmpeg = Command::new("ffmpeg")
+        .args(["-loglevel", "error", "-y", "-f", "rawvideo", "-pixel_format", "rgba"])
+        .args(["-video_size", &format!("{width}x{height}")])
+        .args(["-framerate", &fps.to_string(), "-i", "-"])
+        .args(args)
+        .arg(path.as_ref())
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("Failed to start ffmpeg, is it installed?")?;
+    let stdin = ffmpeg.stdin.take().unwrap();
+    Ok((ffmpeg, stdin))
+}
+
+/// Number of frames a video at `fps` has once `elapsed` seconds passed since its first frame.
+fn frames_due(elapsed: f32, fps: u32) -> u64 {
+    (elapsed.max(0.) * fps as f32) as u64 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn project_onto_faces() {
+        let dirs = [
+            [1., 0., 0.],
+            [-1., 0., 0.],
+            [0., 1., 0.],
+            [0., -1., 0.],
+            [0., 0., 1.],
+            [0., 0., -1.],
+        ];
+        for (face, dir) in CUBE_FACES.iter().zip(dirs) {
+            assert_eq!(face.project(dir.into()), Some([0.5, 0.5]));
+            assert_eq!(face.project(-Vector3::from(dir)), None);
+        }
+        // the top of the front face is up and its right is +x
+        let front = CUBE_FACES[5];
+        assert_eq!(front.project([0., 1., -1.].into()), Some([0.5, 0.]));
+        assert_eq!(front.project([1., 0., -1.].into()), Some([1., 0.5]));
+    }
+
+    #[test]
+    fn stitch_faces() {
+        let colors = [
+            [255, 0, 0],
+            [0, 255, 0],
+            [0, 0, 255],
+            [255, 255, 0],
+            [0, 255, 255],
+            [255, 0, 255],
+        ];
+        let faces = colors.map(|[r, g, b]| RgbaImage::from_pixel(4, 4, Rgba([r, g, b, 255])));
+        let panorama = equirectangular(&faces, 16);
+        assert_eq!(panorama.dimensions(), (16, 8));
+        // center looks along -z, the quarters along -x and +x, the edges along +z
+        assert_eq!(panorama.get_pixel(8, 4), faces[5].get_pixel(0, 0));
+        assert_eq!(panorama.get_pixel(4, 4), faces[1].get_pixel(0, 0));
+        assert_eq!(panorama.get_pixel(12, 4), faces[0].get_pixel(0, 0));
+        assert_eq!(panorama.get_pixel(0, 4), faces[4].get_pixel(0, 0));
+        // the top and bottom rows look up and down
+        assert_eq!(panorama.get_pixel(3, 0), faces[2].get_pixel(0, 0));
+        assert_eq!(panorama.get_pixel(3, 7), faces[3].get_pixel(0, 0));
+    }
+
+    #[test]
+    fn frames_at_constant_rate() {
+        assert_eq!(frames_due(0., 30), 1);
+        assert_eq!(frames_due(0.03, 30), 1);
+        assert_eq!(frames_due(0.034, 30), 2);
+        assert_eq!(frames_due(1., 30), 31);
+        assert_eq!(frames_due(-1., 30), 1);
+    }
+
+    #[test]
+    fn parse_video_codec() {
+        assert_eq!("h264".parse::<VideoCodec>().unwrap(), VideoCodec::H264);
+        assert_eq!("vp9".parse::<VideoCodec>().unwrap(), VideoCodec::Vp9);
+        assert!("gif".parse::<VideoCodec>().is_err());
+        assert_eq!("webp".parse::<LoopFormat>().unwrap(), LoopFormat::Webp);
+        assert!("mp4".parse::<LoopFormat>().is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use crate::math::Vector3;
+
+/// An axis aligned box the camera can not walk through.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3,
+    pub max: Vector3,
+}
+
+impl Aabb {
+    /// Whether a vertical cylinder with its top at `eye` intersects the box.
+    fn intersects_body(&self, eye: Vector3, radius: f32, height: f32) -> bool {
+        if eye.y() <= self.min.y() || eye.y() - height >= self.max.y() {
+            return false;
+        }
+        let x = eye.x().clamp(self.min.x(), self.max.x()) - eye.x();
+        let z = eye.z().clamp(self.min.z(), self.max.z()) - eye.z();
+        x * x + z * z < radius * radius
+    }
+}
+
+/// Moves a vertical cylinder with the radius `radius` and the height `height` below the eye
+/// from `from` to `to` and returns where the eye ends up.
+///
+/// Movement into a box is dropped per horizontal axis, so that the body slides along walls
+/// instead of stopping at them. A body that already is inside a box moves freely so that it
+/// can not get stuck.
+pub fn slide(colliders: &[Aabb], from: Vector3, to: Vector3, radius: f32, height: f32) -> Vector3 {
+    let collides = |eye: Vector3| {
+        colliders.iter().any(|collider| collider.intersects_body(eye, radius, height))
+    };
+    if collides(from) {
+        return to;
+    }
+    let mut eye = Vector3::from([from.x(), to.y(), from.z()]);
+    if collides(eye) {
+        eye[1] = from.y();
+    }
+    for axis in [0, 2] {
+        let old = eye[axis];
+        eye[axis] = to[axis];
+        if collides(eye) {
+            eye[axis] = old;
+        }
+    }
+    eye
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slide_along_wall() {
+        let wall = Aabb {
+            min: Vector3::from([6., 0., -9.]),
+            max: Vector3::from([6.2, 3., 0.]),
+        };
+        let from = Vector3::from([5.5, 1.5, -4.]);
+        // moving diagonally into the wall keeps the movement along it
+        let to = slide(&[wall], from, Vector3::from([6., 1.5, -3.]), 0.25, 1.5);
+        assert_eq!(to, Vector3::from([5.5, 1.5, -3.]));
+        // moving away is not restricted
+        let to = slide(&[wall], from, Vector3::from([5., 1.5, -4.]), 0.25, 1.5);
+        assert_eq!(to, Vector3::from([5., 1.5, -4.]));
+        // above the wall nothing collides
+        let from = Vector3::from([5.5, 5., -4.]);
+        let to = slide(&[wall], from, Vector3::from([6.1, 5., -4.]), 0.25, 1.5);
+        assert_eq!(to, Vector3::from([6.1, 5., -4.]));
+    }
+}
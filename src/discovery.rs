@@ -0,0 +1,155 @@
+//! Art that is found by dropping its fragment shader into a directory.
+
+use crate::env_generator::Wall;
+use crate::gallery::ArtPiece;
+use crate::math::{Matrix4, Rad, Vector3};
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How far apart the centers of discovered art on a wall are.
+const SPACING: f32 = 1.5;
+/// How high the centers of discovered art hang.
+const HEIGHT: f32 = 1.5;
+/// Discovered art is as large as the paintings of the gallery.
+const SCALE: f32 = 0.5;
+/// How far in front of the wall the art hangs, so that it does not flicker with it.
+const WALL_OFFSET: f32 = 0.01;
+
+/// 2D art for every fragment shader in `dir`, hung on the free places of `walls`, see
+/// [`wall_slots`]. Shaders that find no place are left out with a warning.
+pub fn discover_art(dir: &Path, walls: &[Wall], taken: &[Matrix4]) -> Vec<ArtPiece> {
+    let shaders = find_shaders(dir);
+    let slots = wall_slots(walls, taken);
+    if shaders.len() > slots.len() {
+        log::warn!(
+            "Found {} shaders in {} but only {} free places on the walls",
+            shaders.len(),
+            dir.display(),
+            slots.len(),
+        );
+    }
+    shaders.into_iter().zip(slots).map(|(path, model)| {
+        let name = art_name(&path);
+        log::info!("Hanging {name} from {}", path.display());
+        ArtPiece::new_2d(name, path).at(model)
+    }).collect()
+}
+
+/// The fragment shaders in `dir` sorted by name, nothing if there is no such directory.
+pub fn find_shaders(dir: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(err) => {
+            log::warn!("Failed to look for art in {}: {err}", dir.display());
+            return Vec::new();
+        }
+    };
+    let mut shaders = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| is_shader(path))
+        .collect::<Vec<_>>();
+    shaders.sort();
+    shaders
+}
+
+/// Whether `path` is named like a fragment shader.
+pub fn is_shader(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "frag") && path.is_file()
+}
+
+/// The name of the art drawn by the shader at `path`, e.g. "Star Field" for `star_field.frag`.
+pub fn art_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    stem.split(['_', '-', ' '])
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The model matrices of the free places for 2D art along both long sides of `walls`,
+/// a place is taken if art of `taken` hangs on the same side closer than the spacing.
+pub fn wall_slots(walls: &[Wall], taken: &[Matrix4]) -> Vec<Matrix4> {
+    let taken = taken.iter().map(|model| model[3].resize::<3>()).collect::<Vec<_>>();
+    let mut slots = Vec::new();
+    for wall in walls {
+        let min = [wall.start[0].min(wall.end[0]), wall.start[1].min(wall.end[1])];
+        let max = [wall.start[0].max(wall.end[0]), wall.start[1].max(wall.end[1])];
+        // the art hangs along the longer axis of the wall
+        let along = if max[0] - min[0] >= max[1] - min[1] { 0 } else { 1 };
+        let across = 1 - along;
+        let length = max[along] - min[along];
+        let count = (length / SPACING) as usize;
+        let margin = (length - count as f32 * SPACING) / 2.;
+        for (side, direction) in [(min[across], -1.), (max[across], 1.)] {
+            let mut normal = [0.; 2];
+            normal[across] = direction;
+            for i in 0..count {
+                let mut point = [0.; 2];
+                point[along] = min[along] + margin + SPACING * (i as f32 + 0.5);
+                point[across] = side + direction * WALL_OFFSET;
+                let position = Vector3::from([point[0], HEIGHT, point[1]]);
+                let normal3 = Vector3::from([normal[0], 0., normal[1]]);
+                let is_taken = taken.iter().any(|&other| {
+                    let offset = other - position;
+                    offset.dot(normal3).abs() < 0.1 && offset.magnitude() < SPACING
+                });
+                if is_taken {
+                    continue;
+                }
+                // the front of the quad faces -z
+                let angle = Rad((-normal[0]).atan2(-normal[1]));
+                slots.push(
+                    Matrix4::from_translation(position)
+                        * Matrix4::from_scale(SCALE)
+                        * Matrix4::from_angle_y(angle),
+                );
+            }
+        }
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env_generator::DEFAULT_WALLS;
+    use crate::math::Deg;
+
+    #[test]
+    fn name_art() {
+        assert_eq!(art_name(Path::new("assets/shaders/art/star_field.frag")), "Star Field");
+        assert_eq!(art_name(Path::new("neon--rain.frag")), "Neon Rain");
+        assert_eq!(art_name(Path::new("Plasma.frag")), "Plasma");
+    }
+
+    #[test]
+    fn find_free_places() {
+        let painting = |x: f32, z: f32, angle: f32| {
+            Matrix4::from_translation([x, 1.5, z].into())
+                * Matrix4::from_scale(0.5)
+                * Matrix4::from_angle_y(Deg(angle))
+        };
+        let taken = [
+            painting(5.99, -1.5, 90.),
+            painting(5.99, -4.5, 90.),
+            painting(5.99, -7.5, 90.),
+            painting(6.21, -4.5, -90.),
+        ];
+        let slots = wall_slots(DEFAULT_WALLS, &taken);
+        let expected = [-8.25, -6.75, -2.25, -0.75].map(|z| painting(6.21, z, -90.));
+        assert_eq!(slots.len(), expected.len());
+        // they hang on the back of the wall and face away from it like the photo
+        for (slot, expected) in slots.iter().zip(expected) {
+            for row in [0, 2, 3] {
+                assert!((slot[row] - expected[row]).magnitude() < 1e-5, "{slot:?}");
+            }
+        }
+    }
+}
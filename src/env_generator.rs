@@ -1,31 +1,46 @@
+use crate::collision::Aabb;
 use crate::math::Vector3;
 use crate::obj::{Indices, Obj};
 
 use std::num::NonZeroU32;
 
+/// The walls of the environment generated by [`default_env`].
+pub const DEFAULT_WALLS: &[Wall] = &[
+    Wall { start: [6., -9.], end: [6.2, 0.], height: 3. },
+];
+/// The corners of the podests of the environment generated by [`default_env`] with the
+/// smallest x and z, the podests are unit cubes.
+const DEFAULT_PODESTS: &[[f32; 2]] = &[
+    [-3., -1.], [2., -1.],
+    [-3., -6.], [2., -6.],
+];
+
 pub fn default_env() -> Obj {
-    let podests = [
-        [-3., -1.], [2., -1.],
-        [-3., -6.], [2., -6.],
-    ];
-    let walls = [
-        Wall { start: [6., -9.], end: [6.2, 0.], height: 3. },
-    ];
     generate_env(
         [-10.0, 0.0, -10.0],
         [  8.2, 0.0,   4.2],
-        &podests,
-        &walls,
+        DEFAULT_PODESTS,
+        DEFAULT_WALLS,
     )
 }
 
+/// The boxes of the podests and walls of the environment generated by [`default_env`].
+pub fn default_colliders() -> Vec<Aabb> {
+    let podests = DEFAULT_PODESTS.iter().map(|podest| Aabb {
+        min: Vector3::from([podest[0], 0., podest[1]]),
+        max: Vector3::from([podest[0] + 1., 1., podest[1] + 1.]),
+    });
+    let walls = DEFAULT_WALLS.iter().map(Wall::aabb);
+    podests.chain(walls).collect()
+}
+
 fn add_surface(
     start: Vector3,
     end: Vector3,
     dir_x: Vector3,
     dir_y: Vector3,
     vertices: &mut Vec<[f32; 3]>,
-    faces: &mut Vec<([Indices; 3], Option<Indices>)>,
+    faces: &mut Vec<Vec<Indices>>,
 ) {
     let vidx = vertices.len() as u32;
     let diag = end - start;
@@ -135,23 +150,63 @@ fn generate_env(
         );
     }
 
-    Obj { vertices, tex_coords, faces }
+    Obj { vertices, tex_coords, normal_count: 0, faces }
 }
 
-fn indices_to_face(indices: [u32; 4]) -> ([Indices; 3], Option<Indices>) {
-    let [a, b, c, d] = indices.map(|i| NonZeroU32::new(i + 1).unwrap());
-    (
-        [
-            Indices { vertex: a, texture: None, normal: None },
-            Indices { vertex: b, texture: None, normal: None },
-            Indices { vertex: c, texture: None, normal: None },
-        ],
-        Some(Indices { vertex: d, texture: None, normal: None }),
-    )
+fn indices_to_face(indices: [u32; 4]) -> Vec<Indices> {
+    indices
+        .map(|i| Indices { vertex: NonZeroU32::new(i + 1).unwrap(), texture: None, normal: None })
+        .into()
+}
+
+/// A box standing on the floor between the corners `start` and `end` in the x-z-plane.
+pub struct Wall {
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+    pub height: f32,
+}
+
+impl Wall {
+    pub fn aabb(&self) -> Aabb {
+        let [x0, z0] = self.start;
+        let [x1, z1] = self.end;
+        Aabb {
+            min: Vector3::from([x0.min(x1), 0., z0.min(z1)]),
+            max: Vector3::from([x0.max(x1), self.height, z0.max(z1)]),
+        }
+    }
+
+    /// Returns the point on the sides of the wall closest to `point` in the x-z-plane
+    /// and the outward normal of that side.
+    pub fn closest_side(&self, point: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let min = [self.start[0].min(self.end[0]), self.start[1].min(self.end[1])];
+        let max = [self.start[0].max(self.end[0]), self.start[1].max(self.end[1])];
+        let x = point[0].clamp(min[0], max[0]);
+        let z = point[1].clamp(min[1], max[1]);
+        let sides = [
+            ([min[0], z], [-1., 0.]),
+            ([max[0], z], [1., 0.]),
+            ([x, min[1]], [0., -1.]),
+            ([x, max[1]], [0., 1.]),
+        ];
+        let dist = |side: &[f32; 2]| (side[0] - point[0]).powi(2) + (side[1] - point[1]).powi(2);
+        sides.into_iter()
+            .min_by(|(a, _), (b, _)| dist(a).total_cmp(&dist(b)))
+            .unwrap()
+    }
 }
 
-struct Wall {
-    start: [f32; 2],
-    end: [f32; 2],
-    height: f32,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_side() {
+        let wall = Wall { start: [6., -9.], end: [6.2, 0.], height: 3. };
+        assert_eq!(wall.closest_side([5.5, -4.5]), ([6., -4.5], [-1., 0.]));
+        assert_eq!(wall.closest_side([7., -1.]), ([6.2, -1.], [1., 0.]));
+        assert_eq!(wall.closest_side([6.1, 0.5]), ([6.1, 0.], [0., 1.]));
+        // inside the wall
+        assert_eq!(wall.closest_side([6.15, -8.]), ([6.2, -8.], [1., 0.]));
+    }
 }
@@ -1,5 +1,15 @@
+use anyhow::Context;
+use image::{imageops::FilterType, DynamicImage, ImageDecoder, ImageReader};
+use notify_debouncer_full::{
+    new_debouncer, notify, DebounceEventResult, Debouncer, RecommendedCache,
+};
+use std::collections::{HashMap, HashSet};
 use std::io::{self, Cursor};
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
 
 pub fn load<P: AsRef<Path>>(path: P) -> Result<Cursor<Vec<u8>>, io::Error> {
     use std::fs::File;
@@ -11,6 +21,30 @@ pub fn load<P: AsRef<Path>>(path: P) -> Result<Cursor<Vec<u8>>, io::Error> {
     Ok(Cursor::new(buf))
 }
 
+/// Decodes the image at `path` and turns it upright as its EXIF orientation says,
+/// e.g. for photos taken with the phone held sideways.
+pub fn load_image<P: AsRef<Path>>(path: P) -> Result<DynamicImage, anyhow::Error> {
+    let mut decoder = ImageReader::open(path)
+        .context("Failed to open image")?
+        .with_guessed_format()
+        .context("Failed to read image")?
+        .into_decoder()
+        .context("Failed to decode image")?;
+    let orientation = decoder.orientation().context("Failed to read the orientation")?;
+    let mut image = DynamicImage::from_decoder(decoder).context("Failed to decode image")?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Scales `image` down so that neither side is longer than `max_dimension`, keeping its
+/// aspect ratio. Images that are small enough are returned as they are.
+pub fn fit_image(image: DynamicImage, max_dimension: u32) -> DynamicImage {
+    if image.width().max(image.height()) <= max_dimension {
+        return image;
+    }
+    image.resize(max_dimension, max_dimension, FilterType::Triangle)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct Carousel {
     dir: &'static str,
@@ -27,6 +61,31 @@ impl Carousel {
     }
 
     pub fn get_next<F>(&mut self, offset: isize, filter: F) -> Result<PathBuf, io::Error>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        let files = self.files(filter)?;
+        // take euclidian remainder and not modulus to get a positive value
+        self.curr = (self.curr as isize + offset).rem_euclid(files.len() as isize) as usize;
+        Ok(files[self.curr].clone())
+    }
+
+    /// Makes `path` the current file if it is one of the files in the directory,
+    /// e.g. to show the image of the last session again. Returns whether it was found.
+    pub fn select<F>(&mut self, path: &Path, filter: F) -> bool
+    where
+        F: Fn(&Path) -> bool,
+    {
+        let index = self.files(filter).ok()
+            .and_then(|files| files.iter().position(|file| file == path));
+        if let Some(index) = index {
+            self.curr = index;
+        }
+        index.is_some()
+    }
+
+    /// The matching files in the directory sorted by name, at least one.
+    fn files<F>(&self, filter: F) -> Result<Vec<PathBuf>, io::Error>
     where
         F: Fn(&Path) -> bool,
     {
@@ -47,8 +106,136 @@ impl Carousel {
             return Err(io::Error::new(io::ErrorKind::Other, "no matching file found"));
         }
         files.sort();
-        // take euclidian remainder and not modulus to get a positive value
-        self.curr = (self.curr as isize + offset).rem_euclid(files.len() as isize) as usize;
-        Ok(files[self.curr].clone())
+        Ok(files)
+    }
+}
+
+/// Watches single files for changes, e.g. images to load again after they were edited.
+pub struct FileWatcher {
+    /// `None` if the watcher could not be created, then no changes are reported.
+    debouncer: Option<Debouncer<notify::RecommendedWatcher, RecommendedCache>>,
+    events: Receiver<DebounceEventResult>,
+    /// The directories of the files are watched, so that files replaced by editors are noticed.
+    dirs: HashSet<PathBuf>,
+    /// The canonical paths of the watched files with the paths they were watched with.
+    files: HashMap<PathBuf, PathBuf>,
+    /// The canonical paths of the directories whose files are all watched with the paths
+    /// they were watched with.
+    whole_dirs: HashMap<PathBuf, PathBuf>,
+}
+
+impl Default for FileWatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        let (tx, events) = mpsc::channel();
+        let debouncer = new_debouncer(DEBOUNCE_TIME, None, tx)
+            .inspect_err(|err| log::error!("failed to create file watcher: {err}"))
+            .ok();
+        Self {
+            debouncer,
+            events,
+            dirs: HashSet::new(),
+            files: HashMap::new(),
+            whole_dirs: HashMap::new(),
+        }
+    }
+
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let Some(debouncer) = self.debouncer.as_mut() else { return };
+        let Ok(canonical) = std::fs::canonicalize(path) else {
+            log::warn!("failed to watch {}: file not found", path.display());
+            return;
+        };
+        if let Some(dir) = canonical.parent().filter(|dir| !self.dirs.contains(*dir)) {
+            if let Err(err) = debouncer.watch(dir, notify::RecursiveMode::NonRecursive) {
+                log::error!("failed to watch {}: {err}", dir.display());
+                return;
+            }
+            self.dirs.insert(dir.to_owned());
+        }
+        log::debug!("watching file {}", path.display());
+        self.files.insert(canonical, path.to_owned());
+    }
+
+    /// Watches every file in the directory at `dir`, also the ones created later.
+    pub fn watch_dir<P: AsRef<Path>>(&mut self, dir: P) {
+        let dir = dir.as_ref();
+        let Some(debouncer) = self.debouncer.as_mut() else { return };
+        let Ok(canonical) = std::fs::canonicalize(dir) else {
+            log::warn!("failed to watch {}: directory not found", dir.display());
+            return;
+        };
+        if !self.dirs.contains(&canonical) {
+            if let Err(err) = debouncer.watch(&canonical, notify::RecursiveMode::NonRecursive) {
+                log::error!("failed to watch {}: {err}", dir.display());
+                return;
+            }
+            self.dirs.insert(canonical.clone());
+        }
+        log::debug!("watching directory {}", dir.display());
+        self.whole_dirs.insert(canonical, dir.to_owned());
+    }
+
+    /// Stops reporting changes of `path`, its directory stays watched.
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) {
+        if let Ok(canonical) = std::fs::canonicalize(path) {
+            self.files.remove(&canonical);
+        }
+    }
+
+    /// The watched files that changed since the last call, with the paths they were watched with.
+    pub fn changed(&self) -> Vec<PathBuf> {
+        use notify::EventKind::*;
+        use notify::event::{AccessKind::*, AccessMode::*, ModifyKind::*};
+
+        let mut changed = Vec::new();
+        for result in self.events.try_iter() {
+            let events = match result {
+                Ok(events) => events,
+                Err(errors) => {
+                    log::info!("watch error: {errors:?}");
+                    continue;
+                }
+            };
+            for event in events {
+                if let Access(Close(Write)) | Modify(Data(_) | Name(_)) | Create(_) = event.kind {
+                    for path in event.paths.iter().filter_map(|path| self.watched_path(path)) {
+                        if !changed.contains(&path) {
+                            changed.push(path);
+                        }
+                    }
+                }
+            }
+        }
+        changed
+    }
+
+    /// The path `canonical` was watched with, directly or through its directory.
+    fn watched_path(&self, canonical: &Path) -> Option<PathBuf> {
+        if let Some(path) = self.files.get(canonical) {
+            return Some(path.clone());
+        }
+        let dir = self.whole_dirs.get(canonical.parent()?)?;
+        Some(dir.join(canonical.file_name()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_large_images() {
+        let image = DynamicImage::new_rgba8(800, 200);
+        let fitted = fit_image(image.clone(), 400);
+        assert_eq!([fitted.width(), fitted.height()], [400, 100]);
+        let fitted = fit_image(image, 800);
+        assert_eq!([fitted.width(), fitted.height()], [800, 200]);
     }
 }
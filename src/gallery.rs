@@ -0,0 +1,582 @@
+//! Builds the shader gallery for programs that embed it, see `examples/custom_gallery.rs`.
+//!
+//! The types here only grow new methods in minor versions, the modules they wrap may change.
+
+use crate::env_generator::default_env;
+use crate::math::{Deg, Matrix4, Projection, Vector3, Vector4};
+use crate::obj::NormalizedObj;
+use crate::vulkan::{
+    ArtTime, Framing, GpuSelection, Proximity, RustGpuCrate, SamplerConfig, Scene, Shader,
+    ShaderArt, ShaderInner, Shaders, TextureSamplers, Validation, VkApp, Volume,
+};
+
+use glslang::ShaderStage;
+use std::path::PathBuf;
+use winit::window::Window;
+
+const ART_2D_VERT: &str = "assets/shaders/art2d.vert";
+const ART_3D_VERT: &str = "assets/shaders/art3d.vert";
+/// The most frames in flight, more only add latency.
+pub const MAX_FRAMES_IN_FLIGHT: usize = 4;
+
+/// Parses the number of frames in flight, from 1 to [`MAX_FRAMES_IN_FLIGHT`].
+pub fn parse_frames_in_flight(text: &str) -> Result<usize, anyhow::Error> {
+    match text.parse() {
+        Ok(count) if (1..=MAX_FRAMES_IN_FLIGHT).contains(&count) => Ok(count),
+        _ => Err(anyhow::anyhow!("expected a number from 1 to {MAX_FRAMES_IN_FLIGHT}")),
+    }
+}
+
+/// Parses the number of swapchain images to request, `auto` for one more than the minimum of
+/// the surface. The count is clamped to the limits of the surface when the swapchain is created.
+pub fn parse_swapchain_images(text: &str) -> Result<Option<u32>, anyhow::Error> {
+    if text == "auto" {
+        return Ok(None);
+    }
+    match text.parse() {
+        Ok(count) if count > 0 => Ok(Some(count)),
+        _ => Err(anyhow::anyhow!("expected auto or a number of images")),
+    }
+}
+
+/// An art piece of the gallery drawn by a fragment shader that is loaded from a file
+/// and reloaded when the file changes.
+///
+/// 2D art is drawn on a quad from -1 to 1 in x and y, 3D art inside a cube from -1 to 1.
+pub struct ArtPiece {
+    name: String,
+    is_3d: bool,
+    vert: Option<PathBuf>,
+    frag: PathBuf,
+    vert_entry_point: Option<String>,
+    frag_entry_point: Option<String>,
+    defines: Vec<(String, Option<String>)>,
+    tessellation: Option<[PathBuf; 2]>,
+    ray_tracing: Option<[PathBuf; 3]>,
+    model_path: Option<PathBuf>,
+    morph_targets: Vec<PathBuf>,
+    morph_weights: Vector4,
+    animation: Option<String>,
+    model_matrix: Matrix4,
+    params: Vector4,
+    time: ArtTime,
+    proximity: Proximity,
+    volume: Option<Volume>,
+    framing: Framing,
+    half_resolution: bool,
+}
+
+impl ArtPiece {
+    /// Flat art like a painting with the fragment shader at `frag`.
+    pub fn new_2d<N: Into<String>, P: Into<PathBuf>>(name: N, frag: P) -> Self {
+        Self::new(name.into(), false, frag.into())
+    }
+
+    /// Art in a cube that is ray marched by the fragment shader at `frag`.
+    pub fn new_3d<N: Into<String>, P: Into<PathBuf>>(name: N, frag: P) -> Self {
+        Self::new(name.into(), true, frag.into())
+    }
+
+    fn new(name: String, is_3d: bool, frag: PathBuf) -> Self {
+        Self {
+            name,
+            is_3d,
+            vert: None,
+            frag,
+            vert_entry_point: None,
+            frag_entry_point: None,
+            defines: Vec::new(),
+            tessellation: None,
+            ray_tracing: None,
+            model_path: None,
+            morph_targets: Vec::new(),
+            morph_weights: Vector4::default(),
+            animation: None,
+            model_matrix: Matrix4::unit(),
+            params: Vector4::default(),
+            time: ArtTime::default(),
+            proximity: Proximity::default(),
+            volume: None,
+            framing: Framing::default(),
+            half_resolution: false,
+        }
+    }
+
+    /// Places the art in the gallery.
+    pub fn at(mut self, model_matrix: Matrix4) -> Self {
+        self.model_matrix = model_matrix;
+        self
+    }
+
+    pub fn model_matrix(&self) -> Matrix4 {
+        self.model_matrix
+    }
+
+    /// Replaces the vertex shader shared by all 2D or all 3D art.
+    pub fn vert<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.vert = Some(path.into());
+        self
+    }
+
+    /// The function the vertex shader starts at instead of `main`, it only applies to
+    /// a vertex shader set with [`Self::vert`].
+    pub fn vert_entry_point<S: Into<String>>(mut self, name: S) -> Self {
+        self.vert_entry_point = Some(name.into());
+        self
+    }
+
+    /// The function the fragment shader starts at instead of `main`, e.g. the name of
+    /// the function in a module built from a [`RustGpuCrate`].
+    pub fn frag_entry_point<S: Into<String>>(mut self, name: S) -> Self {
+        self.frag_entry_point = Some(name.into());
+        self
+    }
+
+    /// Adds a `#define` to the GLSL shaders of the art, e.g. to hang several variants of
+    /// the same shader. It does not apply to the vertex shader shared by all 2D or all 3D art.
+    pub fn define<K: Into<String>>(mut self, name: K, value: Option<&str>) -> Self {
+        self.defines.push((name.into(), value.map(str::to_owned)));
+        self
+    }
+
+    /// Adds tessellation control and evaluation shaders.
+    pub fn tessellation<P: Into<PathBuf>>(mut self, [control, evaluation]: [P; 2]) -> Self {
+        self.tessellation = Some([control.into(), evaluation.into()]);
+        self
+    }
+
+    /// Adds ray generation, miss and closest hit shaders that trace into an image
+    /// the fragment shader samples at binding 1.
+    pub fn ray_tracing<P: Into<PathBuf>>(mut self, [raygen, miss, hit]: [P; 3]) -> Self {
+        self.ray_tracing = Some([raygen.into(), miss.into(), hit.into()]);
+        self
+    }
+
+    /// Draws the art with the OBJ or glTF model at `path` instead of the quad or the cube.
+    ///
+    /// A glTF model can be skinned, its vertices then have the joints at location 1 and the
+    /// weights at location 2 and the vertex shader blends the matrices of the joints in the
+    /// storage buffer at binding 10 from `joint_offset` in the art uniforms on,
+    /// see `assets/shaders/skinned.vert`.
+    pub fn model<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.model_path = Some(path.into());
+        self
+    }
+
+    /// Up to four OBJ models with the same faces as the model of [`Self::model`] that its
+    /// vertices move towards by the weights set with [`Self::morph_weights`] or a script.
+    ///
+    /// The vertex shader reads the offsets to the targets at locations 4 to 7 and the weights
+    /// from `morph_weights` after `framing` in the art uniforms, see `assets/shaders/morph.vert`.
+    pub fn morph_targets<I: IntoIterator<Item = P>, P: Into<PathBuf>>(mut self, paths: I) -> Self {
+        self.morph_targets = paths.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn morph_weights(mut self, weights: Vector4) -> Self {
+        self.morph_weights = weights;
+        self
+    }
+
+    /// Plays the animation called `name` of a skinned glTF model instead of its first one.
+    ///
+    /// The animation follows the local time of the art, see [`Self::time`].
+    pub fn animation<N: Into<String>>(mut self, name: N) -> Self {
+        self.animation = Some(name.into());
+        self
+    }
+
+    /// Free parameters the shaders read from their art uniforms.
+    pub fn params(mut self, params: Vector4) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn time(mut self, time: ArtTime) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// How the art reacts to the distance of the camera, e.g. to draw it at a lower quality or
+    /// not at all when it is far away.
+    pub fn proximity(mut self, proximity: Proximity) -> Self {
+        self.proximity = proximity;
+        self
+    }
+
+    /// A 3D texture the fragment shader samples at binding 4.
+    pub fn volume(mut self, volume: Volume) -> Self {
+        self.volume = Some(volume);
+        self
+    }
+
+    /// How 2D art fits the image it samples into its quad.
+    pub fn framing(mut self, framing: Framing) -> Self {
+        self.framing = framing;
+        self
+    }
+
+    /// Draws 3D art at half the resolution of the window and scales it up, which trades
+    /// sharpness for speed with expensive ray marchers. Only art drawn in its cube can be
+    /// scaled up and its vertex shader draws the scaled image too.
+    ///
+    /// The art is drawn at full resolution in stereo and with several views.
+    pub fn half_resolution(mut self) -> Self {
+        self.half_resolution = true;
+        self
+    }
+
+    /// Hangs the art in the running gallery `app`, see [`VkApp::add_art`].
+    pub fn add_to(self, app: &mut VkApp) -> Result<(), anyhow::Error> {
+        let vert_2d = ShaderInner::new(ShaderStage::Vertex).path(ART_2D_VERT).into();
+        let vert_3d = ShaderInner::new(ShaderStage::Vertex).path(ART_3D_VERT).into();
+        app.add_art(self.into_shader_art(&vert_2d, &vert_3d))
+    }
+
+    fn into_shader_art(self, vert_2d: &Shader, vert_3d: &Shader) -> ShaderArt {
+        let shader = |stage, path| {
+            self.defines.iter().fold(ShaderInner::new(stage).path(path), |inner, (name, value)| {
+                inner.define(name.clone(), value.as_deref())
+            })
+        };
+        let with_entry_point = |inner: ShaderInner, name: Option<String>| match name {
+            Some(name) => inner.entry_point(&name).into(),
+            None => inner.into(),
+        };
+        let vert = match self.vert {
+            Some(path) => {
+                with_entry_point(shader(ShaderStage::Vertex, path), self.vert_entry_point)
+            }
+            None if self.is_3d => vert_3d.clone(),
+            None => vert_2d.clone(),
+        };
+        ShaderArt {
+            name: self.name,
+            is_3d: self.is_3d,
+            vert,
+            frag: with_entry_point(
+                shader(ShaderStage::Fragment, self.frag),
+                self.frag_entry_point,
+            ),
+            tessellation: self.tessellation.map(|[control, evaluation]| [
+                shader(ShaderStage::TesselationControl, control).into(),
+                shader(ShaderStage::TesselationEvaluation, evaluation).into(),
+            ]),
+            ray_tracing: self.ray_tracing.map(|[raygen, miss, hit]| [
+                shader(ShaderStage::RayGeneration, raygen).into(),
+                shader(ShaderStage::Miss, miss).into(),
+                shader(ShaderStage::ClosestHit, hit).into(),
+            ]),
+            model_path: self.model_path,
+            morph_targets: self.morph_targets,
+            morph_weights: self.morph_weights,
+            animation: self.animation,
+            model_matrix: self.model_matrix,
+            params: self.params,
+            time: self.time,
+            proximity: self.proximity,
+            volume: self.volume,
+            framing: self.framing,
+            half_resolution: self.half_resolution,
+        }
+    }
+}
+
+/// Where the gallery is seen from.
+///
+/// A yaw and a pitch of zero look along -z, a positive yaw turns right and a positive pitch
+/// looks down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Camera {
+    pub position: Vector3,
+    pub yaw: Deg<f32>,
+    pub pitch: Deg<f32>,
+    pub projection: Projection,
+}
+
+impl Camera {
+    pub fn new(position: Vector3, yaw: Deg<f32>, pitch: Deg<f32>) -> Self {
+        Self {
+            position,
+            yaw,
+            pitch,
+            projection: Projection::default(),
+        }
+    }
+
+    pub fn view_matrix(&self) -> Matrix4 {
+        Matrix4::from_angle_x(self.pitch)
+            * Matrix4::from_angle_y(self.yaw)
+            * Matrix4::from_translation(-self.position)
+    }
+
+    /// The unit vector the camera looks along.
+    pub fn forward(&self) -> Vector3 {
+        let rotation = Matrix4::from_angle_y(-self.yaw) * Matrix4::from_angle_x(-self.pitch);
+        (Vector4::from([0., 0., -1., 0.]) * rotation).resize()
+    }
+
+    /// The direction to the right of the camera, level with the floor.
+    pub fn right(&self) -> Vector3 {
+        (Vector4::from([1., 0., 0., 0.]) * Matrix4::from_angle_y(-self.yaw)).resize()
+    }
+
+    /// Draws the next frames of `scene` from this camera.
+    pub fn apply(&self, scene: &mut Scene) {
+        scene.view_matrix = self.view_matrix();
+        scene.projection = self.projection;
+    }
+}
+
+/// Creates the renderer of a gallery with custom art.
+///
+/// Everything but the image has a default: the cubemap in `assets/cubemap`, the generated
+/// environment, two frames in flight and the validation chosen by [`Validation::from_env`].
+pub struct GalleryBuilder {
+    image: PathBuf,
+    cubemap: PathBuf,
+    environment: Option<NormalizedObj>,
+    samplers: TextureSamplers,
+    frames_in_flight: usize,
+    swapchain_images: Option<u32>,
+    validation: Validation,
+    gpu: GpuSelection,
+    openxr: bool,
+    art: Vec<ArtPiece>,
+    rust_gpu: Vec<RustGpuCrate>,
+}
+
+impl GalleryBuilder {
+    /// A gallery whose environment and 2D art sample the image at `image`.
+    pub fn new<P: Into<PathBuf>>(image: P) -> Self {
+        Self {
+            image: image.into(),
+            cubemap: "assets/cubemap".into(),
+            environment: None,
+            samplers: TextureSamplers {
+                image: SamplerConfig::REPEAT,
+                art: SamplerConfig::CLAMP,
+                cubemap: SamplerConfig::CLAMP,
+            },
+            frames_in_flight: 2,
+            swapchain_images: None,
+            validation: Validation::from_env(),
+            gpu: GpuSelection::default(),
+            openxr: false,
+            art: Vec::new(),
+            rust_gpu: Vec::new(),
+        }
+    }
+
+    /// The directory of the six faces of the skybox, without one the sky is procedural.
+    pub fn cubemap<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.cubemap = dir.into();
+        self
+    }
+
+    /// Replaces the generated rooms of the gallery.
+    pub fn environment(mut self, nobj: NormalizedObj) -> Self {
+        self.environment = Some(nobj);
+        self
+    }
+
+    pub fn samplers(mut self, samplers: TextureSamplers) -> Self {
+        self.samplers = samplers;
+        self
+    }
+
+    /// Number of frames the CPU can prepare while the GPU is still drawing previous ones,
+    /// from 1 to [`MAX_FRAMES_IN_FLIGHT`].
+    pub fn frames_in_flight(mut self, count: usize) -> Self {
+        self.frames_in_flight = count;
+        self
+    }
+
+    /// Number of swapchain images to request, `None` requests one more than the minimum.
+    /// It is clamped to the limits of the surface.
+    pub fn swapchain_images(mut self, count: Option<u32>) -> Self {
+        self.swapchain_images = count;
+        self
+    }
+
+    pub fn validation(mut self, validation: Validation) -> Self {
+        self.validation = validation;
+        self
+    }
+
+    pub fn gpu(mut self, gpu: GpuSelection) -> Self {
+        self.gpu = gpu;
+        self
+    }
+
+    /// Shows the gallery in a headset too, needs the `openxr` feature and an OpenXR runtime.
+    ///
+    /// The headset decides which GPU is used.
+    pub fn openxr(mut self, enabled: bool) -> Self {
+        self.openxr = enabled;
+        self
+    }
+
+    /// Adds art to the gallery, 2D art should come before 3D art so that it can be seen
+    /// through transparent 3D art.
+    pub fn art<I: IntoIterator<Item = ArtPiece>>(mut self, pieces: I) -> Self {
+        self.art.extend(pieces);
+        self
+    }
+
+    /// Builds the shaders of a rust-gpu crate before the gallery is created and again
+    /// whenever its sources change.
+    pub fn rust_gpu(mut self, rust_gpu: RustGpuCrate) -> Self {
+        self.rust_gpu.push(rust_gpu);
+        self
+    }
+
+    /// Creates the renderer drawing into `window`.
+    pub fn build(self, window: &Window) -> Result<VkApp, anyhow::Error> {
+        anyhow::ensure!(
+            (1..=MAX_FRAMES_IN_FLIGHT).contains(&self.frames_in_flight),
+            "{} frames in flight, expected 1 to {MAX_FRAMES_IN_FLIGHT}",
+            self.frames_in_flight,
+        );
+        for rust_gpu in self.rust_gpu {
+            rust_gpu.watch()?;
+        }
+        let nobj = match self.environment {
+            Some(nobj) => nobj,
+            None => default_env().normalize()?,
+        };
+        let vert_2d = ShaderInner::new(ShaderStage::Vertex).path(ART_2D_VERT).into();
+        let vert_3d = ShaderInner::new(ShaderStage::Vertex).path(ART_3D_VERT).into();
+        let shaders_art = self.art.into_iter()
+            .map(|piece| piece.into_shader_art(&vert_2d, &vert_3d))
+            .collect();
+        let size = window.inner_size();
+        VkApp::new(
+            window,
+            [size.width, size.height],
+            self.image,
+            self.cubemap,
+            self.samplers,
+            self.frames_in_flight,
+            self.swapchain_images,
+            nobj,
+            builtin_shaders(shaders_art)?,
+            self.validation,
+            &self.gpu,
+            self.openxr,
+        )
+    }
+}
+
+/// The shaders of the gallery itself, compiled by the build script.
+fn builtin_shaders(shaders_art: Vec<ShaderArt>) -> Result<Shaders, anyhow::Error> {
+    Ok(Shaders {
+        main_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader_multiview.vert.spv")))?.into(),
+        main_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv")))?.into(),
+        main_lightmap_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader_lightmap.frag.spv")))?.into(),
+        cube_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/cubemap_multiview.vert.spv")))?.into(),
+        cube_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/cubemap.frag.spv")))?.into(),
+        sky_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/sky.frag.spv")))?.into(),
+        error_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/error_multiview.vert.spv")))?.into(),
+        error_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/error.frag.spv")))?.into(),
+        fullscreen_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/fullscreen.vert.spv")))?.into(),
+        anaglyph_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/anaglyph.frag.spv")))?.into(),
+        preview_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/preview.vert.spv")))?.into(),
+        graph_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/graph.frag.spv")))?.into(),
+        fade_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/fade.frag.spv")))?.into(),
+        gizmo_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/gizmo.frag.spv")))?.into(),
+        overlay_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/overlay.frag.spv")))?.into(),
+        upscale_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/upscale.frag.spv")))?.into(),
+        grade_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/grade.frag.spv")))?.into(),
+        grade_multisampled_frag: ShaderInner::new(ShaderStage::Fragment)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/grade_multisampled.frag.spv")))?
+            .into(),
+        occluder_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/occluder.vert.spv")))?.into(),
+        depth_pyramid_comp: ShaderInner::new(ShaderStage::Compute)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/depth_pyramid.comp.spv")))?.into(),
+        occlusion_cull_comp: ShaderInner::new(ShaderStage::Compute)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/occlusion_cull.comp.spv")))?.into(),
+        light_clusters_comp: ShaderInner::new(ShaderStage::Compute)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/light_clusters.comp.spv")))?.into(),
+        shadow_vert: ShaderInner::new(ShaderStage::Vertex)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shadow.vert.spv")))?.into(),
+        volumetric_fog_comp: ShaderInner::new(ShaderStage::Compute)
+            .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/volumetric_fog.comp.spv")))?.into(),
+        single_view_verts: [
+            ShaderInner::new(ShaderStage::Vertex)
+                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv")))?.into(),
+            ShaderInner::new(ShaderStage::Vertex)
+                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/cubemap.vert.spv")))?.into(),
+            ShaderInner::new(ShaderStage::Vertex)
+                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/error.vert.spv")))?.into(),
+        ],
+        shaders_art,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_forward() {
+        let camera = Camera::new(Vector3::default(), Deg(0.), Deg(0.));
+        assert_eq!(camera.view_matrix(), Matrix4::unit());
+        let forward = camera.forward();
+        assert!((forward - Vector3::from([0., 0., -1.])).magnitude() < 1e-6);
+
+        // a quarter turn to the right looks along +x
+        let camera = Camera::new(Vector3::from([1., 2., 3.]), Deg(90.), Deg(0.));
+        let forward = camera.forward();
+        assert!((forward - Vector3::from([1., 0., 0.])).magnitude() < 1e-6);
+    }
+
+    #[test]
+    fn parse_frame_counts() {
+        assert_eq!(parse_frames_in_flight("1").unwrap(), 1);
+        assert_eq!(parse_frames_in_flight("3").unwrap(), 3);
+        assert!(parse_frames_in_flight("0").is_err());
+        assert!(parse_frames_in_flight(&(MAX_FRAMES_IN_FLIGHT + 1).to_string()).is_err());
+        assert!(parse_frames_in_flight("-1").is_err());
+        assert!(parse_frames_in_flight("two").is_err());
+
+        assert_eq!(parse_swapchain_images("auto").unwrap(), None);
+        assert_eq!(parse_swapchain_images("3").unwrap(), Some(3));
+        assert!(parse_swapchain_images("0").is_err());
+        assert!(parse_swapchain_images("-2").is_err());
+    }
+
+    #[test]
+    fn define_art_variants() {
+        let vert = Shader::from(ShaderInner::new(ShaderStage::Vertex));
+        let art = ArtPiece::new_2d("Red", "red.frag")
+            .define("RED", None)
+            .define("STEPS", Some("64"))
+            .into_shader_art(&vert, &vert);
+        assert_eq!(art.defines(), [
+            ("RED".to_owned(), None),
+            ("STEPS".to_owned(), Some("64".to_owned())),
+        ]);
+        // the shared vertex shader is left alone
+        assert!(art.vert.defines().is_empty());
+    }
+}
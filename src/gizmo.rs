@@ -0,0 +1,332 @@
+use crate::math::{Matrix4, Rad, Vector3, Vector4};
+
+/// How long the handles are relative to their distance from the camera,
+/// so that they keep their size on the screen.
+const HANDLE_SIZE: f32 = 0.15;
+/// How close to a handle relative to its length the cursor has to be to pick it.
+const PICK_RADIUS: f32 = 0.08;
+/// The art is not scaled down further than this by a single drag.
+const MIN_SCALE: f32 = 0.05;
+
+/// What dragging a handle of the [`Gizmo`] does to the edited art.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GizmoMode {
+    /// Moves the art along the axes of the world.
+    #[default]
+    Translate,
+    /// Turns the art around the axes of the world through its center.
+    Rotate,
+    /// Scales the art uniformly, the handles lie along its own axes.
+    Scale,
+}
+
+impl GizmoMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Translate => Self::Rotate,
+            Self::Rotate => Self::Scale,
+            Self::Scale => Self::Translate,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Translate => "move",
+            Self::Rotate => "rotate",
+            Self::Scale => "scale",
+        }
+    }
+}
+
+/// A ray in world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vector3,
+    /// Of unit length.
+    pub direction: Vector3,
+}
+
+impl Ray {
+    /// The ray from the camera through `ndc`, the point on the screen from -1 to 1
+    /// from the top left, seen with the rigid `view` and the perspective `proj`.
+    pub fn from_screen(ndc: [f32; 2], view: Matrix4, proj: Matrix4) -> Self {
+        let direction = Vector3::from([ndc[0] / proj[0][0], ndc[1] / proj[1][1], -1.]);
+        Self {
+            origin: camera_position(view),
+            direction: view_to_world(view, direction).normalize(),
+        }
+    }
+
+    fn at(&self, t: f32) -> Vector3 {
+        self.origin + self.direction * t
+    }
+
+    /// Where the ray hits the plane through `point` with the normal `normal`.
+    fn hit_plane(&self, point: Vector3, normal: Vector3) -> Option<Vector3> {
+        let denom = normal.dot(self.direction);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let t = normal.dot(point - self.origin) / denom;
+        (t > 0.).then(|| self.at(t))
+    }
+
+    /// How far along the line through `point` along the unit vector `axis`
+    /// the line comes closest to the ray.
+    fn closest_on_line(&self, point: Vector3, axis: Vector3) -> Option<f32> {
+        let b = axis.dot(self.direction);
+        let denom = 1. - b * b;
+        if denom < 1e-6 {
+            return None;
+        }
+        let w = point - self.origin;
+        Some((b * self.direction.dot(w) - axis.dot(w)) / denom)
+    }
+
+    fn distance_to(&self, point: Vector3) -> f32 {
+        let t = self.direction.dot(point - self.origin).max(0.);
+        (self.at(t) - point).magnitude()
+    }
+}
+
+/// Turns a direction in view space into world space with the rigid `view`.
+fn view_to_world(view: Matrix4, direction: Vector3) -> Vector3 {
+    // the rotation of the view is orthonormal, so its transpose undoes it
+    Vector3::from(std::array::from_fn(|i| view[i].resize::<3>().dot(direction)))
+}
+
+fn camera_position(view: Matrix4) -> Vector3 {
+    -view_to_world(view, view[3].resize())
+}
+
+fn unit_axis(i: usize) -> Vector3 {
+    let mut axis = Vector3::default();
+    axis[i] = 1.;
+    axis
+}
+
+/// A handle being dragged, with what it started from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Drag {
+    axis: usize,
+    model: Matrix4,
+    /// Where along the axis or at which angle around it the handle was grabbed.
+    start: f32,
+}
+
+/// Handles drawn on the edited art that move, turn or scale it when dragged with the mouse.
+///
+/// There is one handle per axis, a shaft for [`GizmoMode::Translate`] and
+/// [`GizmoMode::Scale`] and a ring around it for [`GizmoMode::Rotate`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    /// The axis of the handle under the cursor.
+    pub hovered: Option<usize>,
+    drag: Option<Drag>,
+}
+
+impl Gizmo {
+    /// The axis of the handle being dragged, else the one under the cursor.
+    pub fn active_axis(&self) -> Option<usize> {
+        self.drag.map(|drag| drag.axis).or(self.hovered)
+    }
+
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// The center of the handles in the columns of the translation and their axes
+    /// scaled to their length in the others, as the shader draws them
+    /// seen with the rigid `view`.
+    pub fn frame(&self, model: Matrix4, view: Matrix4) -> Matrix4 {
+        let center = model[3].resize::<3>();
+        let size = (center - camera_position(view)).magnitude() * HANDLE_SIZE;
+        let axes = self.axes(model).map(|axis| {
+            Vector4::from([axis[0], axis[1], axis[2], 0.]) * size
+        });
+        Matrix4::from([axes[0], axes[1], axes[2], model[3]])
+    }
+
+    /// Updates which handle is under the cursor and returns it.
+    pub fn hover(&mut self, ray: Ray, model: Matrix4) -> Option<usize> {
+        if self.drag.is_none() {
+            self.hovered = self.pick(ray, model);
+        }
+        self.hovered
+    }
+
+    /// Grabs the handle under the cursor, returns false if there is none.
+    pub fn begin_drag(&mut self, ray: Ray, model: Matrix4) -> bool {
+        self.drag = self.pick(ray, model).and_then(|axis| {
+            let start = self.drag_param(ray, model, axis)?;
+            Some(Drag { axis, model, start })
+        });
+        self.hovered = self.active_axis();
+        self.drag.is_some()
+    }
+
+    /// The model matrix of the art with the grabbed handle dragged to the cursor.
+    pub fn drag(&self, ray: Ray) -> Option<Matrix4> {
+        let Drag { axis, model, start } = self.drag?;
+        let param = self.drag_param(ray, model, axis)?;
+        let center = model[3].resize::<3>();
+        Some(match self.mode {
+            GizmoMode::Translate => {
+                Matrix4::from_translation(unit_axis(axis) * (param - start)) * model
+            }
+            GizmoMode::Rotate => {
+                let angle = Rad(param - start);
+                let rotation = match axis {
+                    0 => Matrix4::from_angle_x(angle),
+                    1 => Matrix4::from_angle_y(angle),
+                    _ => Matrix4::from_angle_z(angle),
+                };
+                Matrix4::from_translation(center) * rotation
+                    * Matrix4::from_translation(-center) * model
+            }
+            GizmoMode::Scale => {
+                let factor = (param / start).max(MIN_SCALE);
+                model * Matrix4::from_diag(Vector4::from([factor, factor, factor, 1.]))
+            }
+        })
+    }
+
+    /// Lets go of the grabbed handle, returns false if none was grabbed.
+    pub fn end_drag(&mut self) -> bool {
+        self.drag.take().is_some()
+    }
+
+    fn axes(&self, model: Matrix4) -> [Vector3; 3] {
+        match self.mode {
+            GizmoMode::Translate | GizmoMode::Rotate => std::array::from_fn(unit_axis),
+            GizmoMode::Scale => std::array::from_fn(|i| model[i].resize::<3>().normalize()),
+        }
+    }
+
+    /// The axis of the handle the ray hits first.
+    fn pick(&self, ray: Ray, model: Matrix4) -> Option<usize> {
+        let center = model[3].resize::<3>();
+        let size = (center - ray.origin).magnitude() * HANDLE_SIZE;
+        let radius = size * PICK_RADIUS;
+        let mut best: Option<(usize, f32)> = None;
+        for (i, axis) in self.axes(model).into_iter().enumerate() {
+            let hit = match self.mode {
+                GizmoMode::Translate | GizmoMode::Scale => {
+                    let s = ray.closest_on_line(center, axis).unwrap_or(0.).clamp(0., size);
+                    let point = center + axis * s;
+                    (ray.distance_to(point) < radius).then_some(point)
+                }
+                GizmoMode::Rotate => ray.hit_plane(center, axis)
+                    .filter(|point| ((*point - center).magnitude() - size).abs() < radius),
+            };
+            if let Some(point) = hit {
+                let distance = (point - ray.origin).magnitude();
+                if best.is_none_or(|(_, best)| distance < best) {
+                    best = Some((i, distance));
+                }
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    /// How far along the axis the ray passes for moving and scaling,
+    /// the angle around it where the ray hits the plane of the ring for rotating.
+    fn drag_param(&self, ray: Ray, model: Matrix4, axis: usize) -> Option<f32> {
+        let center = model[3].resize::<3>();
+        let axis_vector = self.axes(model)[axis];
+        match self.mode {
+            GizmoMode::Translate => ray.closest_on_line(center, axis_vector),
+            GizmoMode::Scale => ray.closest_on_line(center, axis_vector).filter(|s| *s > 1e-3),
+            GizmoMode::Rotate => {
+                let point = ray.hit_plane(center, axis_vector)? - center;
+                // two directions in the plane, turning from the first to the second
+                // is a positive rotation around the axis
+                let u = unit_axis((axis + 1) % 3);
+                let v = axis_vector.cross(u);
+                Some(point.dot(v).atan2(point.dot(u)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{perspective, Deg};
+
+    fn assert_close(a: Vector3, b: [f32; 3]) {
+        assert!((a - Vector3::from(b)).magnitude() < 1e-3, "{a:?} != {b:?}");
+    }
+
+    // the camera at (0, 0, 5) looking down the negative z-axis at the art at the origin
+    fn view() -> Matrix4 {
+        let up = Vector3::from([0., 1., 0.]);
+        Matrix4::look_to_rh(Vector3::from([0., 0., 5.]), Vector3::from([0., 0., -1.]), up)
+    }
+
+    fn ray(ndc: [f32; 2]) -> Ray {
+        Ray::from_screen(ndc, view(), perspective(Deg(90.), 1., 0.1, 100.))
+    }
+
+    // the point of the screen the world space point is drawn at
+    fn project(point: [f32; 3]) -> [f32; 2] {
+        [point[0] / (5. - point[2]), -point[1] / (5. - point[2])]
+    }
+
+    #[test]
+    fn ray_through_screen() {
+        let center = ray([0., 0.]);
+        assert_close(center.origin, [0., 0., 5.]);
+        assert_close(center.direction, [0., 0., -1.]);
+        // the top right corner of a 90 degree view, y points down on the screen
+        let corner = ray([1., -1.]);
+        assert_close(corner.direction, Vector3::from([1., 1., -1.]).normalize().into());
+    }
+
+    #[test]
+    fn pick_and_drag() {
+        let model = Matrix4::unit();
+        let mut gizmo = Gizmo::default();
+        // the handles are 0.75 long at a distance of 5
+        let frame = gizmo.frame(model, view());
+        assert_close(frame[0].resize(), [0.75, 0., 0.]);
+        assert_close(frame[3].resize(), [0., 0., 0.]);
+        assert_eq!(gizmo.hover(ray(project([0.5, 0., 0.])), model), Some(0));
+        assert_eq!(gizmo.hover(ray(project([0., 0.5, 0.])), model), Some(1));
+        assert_eq!(gizmo.hover(ray(project([1.5, 1.5, 0.])), model), None);
+
+        assert!(gizmo.begin_drag(ray(project([0.5, 0., 0.])), model));
+        let moved = gizmo.drag(ray(project([1.5, 0., 0.]))).unwrap();
+        assert_close(moved[3].resize(), [1., 0., 0.]);
+        assert!(gizmo.end_drag());
+        assert!(!gizmo.end_drag());
+
+        gizmo.mode = GizmoMode::Scale;
+        assert!(gizmo.begin_drag(ray(project([0., 0.5, 0.])), model));
+        let scaled = gizmo.drag(ray(project([0., 1., 0.]))).unwrap();
+        assert_close(scaled[0].resize(), [2., 0., 0.]);
+        assert_close(scaled[1].resize(), [0., 2., 0.]);
+        assert_close(scaled[3].resize(), [0., 0., 0.]);
+        gizmo.end_drag();
+
+        // the ring around z faces the camera
+        gizmo.mode = GizmoMode::Rotate;
+        assert!(gizmo.begin_drag(ray(project([0.75, 0., 0.])), model));
+        assert_eq!(gizmo.active_axis(), Some(2));
+        let rotated = gizmo.drag(ray(project([0., 0.75, 0.]))).unwrap();
+        assert_close(rotated[0].resize(), [0., 1., 0.]);
+        assert_close(rotated[1].resize(), [-1., 0., 0.]);
+    }
+
+    #[test]
+    fn rotate_around_center() {
+        let model = Matrix4::from_translation(Vector3::from([1., 0., 0.]));
+        let mut gizmo = Gizmo { mode: GizmoMode::Rotate, ..Default::default() };
+        let size = Vector3::from([1., 0., -5.]).magnitude() * HANDLE_SIZE;
+        assert!(gizmo.begin_drag(ray(project([1. + size, 0., 0.])), model));
+        let rotated = gizmo.drag(ray(project([1., size, 0.]))).unwrap();
+        assert_close(rotated[3].resize(), [1., 0., 0.]);
+        assert_close(rotated[0].resize(), [0., 1., 0.]);
+    }
+}
@@ -0,0 +1,702 @@
+//! Loads rigged and animated models from glTF 2.0 files,
+//! see <https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html>.
+//!
+//! Only what is needed to exhibit a skinned model is read: the triangles of one mesh with the
+//! joints and weights of its vertices, its skin and the animations of the nodes.
+
+use crate::json::Json;
+use crate::math::{Matrix4, Vector3, Vector4};
+
+use anyhow::Context;
+use std::path::Path;
+
+/// Number of joints that can influence a vertex.
+pub const JOINTS_PER_VERTEX: usize = 4;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_CHUNK_JSON: u32 = 0x4E4F534A;
+const GLB_CHUNK_BIN: u32 = 0x004E4942;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SkinnedVertex {
+    pub position: [f32; 3],
+    /// Indices into the joints of the [`Skeleton`].
+    pub joints: [u32; JOINTS_PER_VERTEX],
+    /// How much each joint moves the vertex, the weights add up to one.
+    pub weights: [f32; JOINTS_PER_VERTEX],
+}
+
+/// The mesh of a glTF file with the skeleton that moves it.
+///
+/// A mesh without a skin is bound to a single joint, the node of the mesh, so that animations
+/// of the node still move it.
+#[derive(Debug, Clone)]
+pub struct SkinnedModel {
+    pub vertices: Vec<SkinnedVertex>,
+    pub indices: Vec<u32>,
+    pub skeleton: Skeleton,
+    pub animations: Vec<Animation>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Skeleton {
+    nodes: Vec<Node>,
+    /// The nodes that are the joints of the skin.
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<Matrix4>,
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    parent: Option<usize>,
+    transform: Transform,
+}
+
+/// The local transform of a node, only nodes given as translation, rotation and scale
+/// can be animated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transform {
+    Matrix(Matrix4),
+    Trs {
+        translation: [f32; 3],
+        /// A unit quaternion with the scalar last.
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub name: String,
+    /// Seconds until the last keyframe, the animation repeats afterwards.
+    pub duration: f32,
+    channels: Vec<Channel>,
+}
+
+#[derive(Debug, Clone)]
+struct Channel {
+    node: usize,
+    property: Property,
+    interpolation: Interpolation,
+    times: Vec<f32>,
+    /// One value per time, translations and scales leave the last component unused.
+    values: Vec<[f32; 4]>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Property {
+    Translation,
+    Rotation,
+    Scale,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interpolation {
+    Step,
+    Linear,
+}
+
+impl SkinnedModel {
+    /// Loads a `.gltf` file with its buffers or a binary `.glb` file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read model {}", path.display()))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Self::from_slice(&bytes, dir)
+            .with_context(|| format!("Failed to load model {}", path.display()))
+    }
+
+    /// Reads a glTF or GLB file, external buffers are loaded relative to `dir`.
+    pub fn from_slice(bytes: &[u8], dir: &Path) -> Result<Self, anyhow::Error> {
+        let (json, bin) = if bytes.starts_with(GLB_MAGIC) {
+            parse_glb(bytes)?
+        } else {
+            (std::str::from_utf8(bytes).context("glTF file is not UTF-8")?, None)
+        };
+        let gltf = Gltf::new(Json::parse(json)?, bin, dir)?;
+        gltf.skinned_model()
+    }
+}
+
+impl Skeleton {
+    /// Number of joint matrices returned by [`Self::joint_matrices`].
+    pub fn joint_count(&self) -> usize {
+        self.joints.len()
+    }
+
+    /// The matrices moving the vertices bound to each joint from the bind pose to the pose of
+    /// `animation` at `time` in seconds, the animation repeats after its duration.
+    pub fn joint_matrices(&self, animation: Option<&Animation>, time: f32) -> Vec<Matrix4> {
+        let mut transforms = self.nodes.iter().map(|node| node.transform).collect::<Vec<_>>();
+        if let Some(animation) = animation {
+            let time = if animation.duration > 0. {
+                time.rem_euclid(animation.duration)
+            } else {
+                0.
+            };
+            for channel in animation.channels.iter() {
+                let Transform::Trs { translation, rotation, scale } = &mut transforms[channel.node]
+                else {
+                    continue;
+                };
+                let value = channel.sample(time);
+                match channel.property {
+                    Property::Translation => *translation = [value[0], value[1], value[2]],
+                    Property::Rotation => *rotation = value,
+                    Property::Scale => *scale = [value[0], value[1], value[2]],
+                }
+            }
+        }
+
+        let mut globals = vec![None; self.nodes.len()];
+        self.joints.iter().zip(self.inverse_bind_matrices.iter())
+            .map(|(&joint, &inverse_bind)| {
+                self.global_matrix(joint, &transforms, &mut globals) * inverse_bind
+            })
+            .collect()
+    }
+
+    /// The matrix from the space of the node at `index` to the space of the scene.
+    fn global_matrix(
+        &self,
+        index: usize,
+        transforms: &[Transform],
+        globals: &mut [Option<Matrix4>],
+    ) -> Matrix4 {
+        if let Some(global) = globals[index] {
+            return global;
+        }
+        let local = transforms[index].matrix();
+        let global = match self.nodes[index].parent {
+            Some(parent) => self.global_matrix(parent, transforms, globals) * local,
+            None => local,
+        };
+        globals[index] = Some(global);
+        global
+    }
+}
+
+impl Transform {
+    fn matrix(&self) -> Matrix4 {
+        match *self {
+            Self::Matrix(matrix) => matrix,
+            Self::Trs { translation, rotation, scale } => {
+                let [sx, sy, sz] = scale;
+                Matrix4::from_translation(Vector3::from(translation))
+                    * quaternion_matrix(rotation)
+                    * Matrix4::from_diag(Vector4::from([sx, sy, sz, 1.]))
+            }
+        }
+    }
+}
+
+impl Channel {
+    fn sample(&self, time: f32) -> [f32; 4] {
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return self.values[0];
+        }
+        if next == self.times.len() || self.interpolation == Interpolation::Step {
+            return self.values[next - 1];
+        }
+        let (start, end) = (self.times[next - 1], self.times[next]);
+        let t = if end > start { (time - start) / (end - start) } else { 0. };
+        let (a, b) = (self.values[next - 1], self.values[next]);
+        match self.property {
+            Property::Rotation => slerp(a, b, t),
+            _ => [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t),
+        }
+    }
+}
+
+/// The rotation matrix of the unit quaternion `[x, y, z, w]`.
+fn quaternion_matrix([x, y, z, w]: [f32; 4]) -> Matrix4 {
+    Matrix4::from([
+        [1. - 2. * (y * y + z * z), 2. * (x * y + z * w), 2. * (x * z - y * w), 0.],
+        [2. * (x * y - z * w), 1. - 2. * (x * x + z * z), 2. * (y * z + x * w), 0.],
+        [2. * (x * z + y * w), 2. * (y * z - x * w), 1. - 2. * (x * x + y * y), 0.],
+        [0., 0., 0., 1.],
+    ])
+}
+
+/// Spherical interpolation between two unit quaternions along the shorter arc.
+fn slerp(a: [f32; 4], mut b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut dot = (0..4).map(|i| a[i] * b[i]).sum::<f32>();
+    if dot < 0. {
+        b = b.map(|x| -x);
+        dot = -dot;
+    }
+    let (wa, wb) = if dot > 0.9995 {
+        // nearly the same rotation, interpolate linearly to avoid dividing by zero
+        (1. - t, t)
+    } else {
+        let angle = dot.acos();
+        let sin = angle.sin();
+        (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+    };
+    let q = [0, 1, 2, 3].map(|i| wa * a[i] + wb * b[i]);
+    let len = q.iter().map(|x| x * x).sum::<f32>().sqrt();
+    q.map(|x| x / len)
+}
+
+/// Splits a GLB file into its JSON and its binary chunk.
+fn parse_glb(bytes: &[u8]) -> Result<(&str, Option<&[u8]>), anyhow::Error> {
+    let u32_at = |offset: usize| {
+        bytes.get(offset..offset + 4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .ok_or_else(|| anyhow::anyhow!("GLB file is truncated"))
+    };
+    let version = u32_at(4)?;
+    if version != 2 {
+        anyhow::bail!("GLB version {version} is not supported");
+    }
+    let length = (u32_at(8)? as usize).min(bytes.len());
+    let (mut json, mut bin) = (None, None);
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = u32_at(offset)? as usize;
+        let chunk_type = u32_at(offset + 4)?;
+        let chunk = bytes.get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| anyhow::anyhow!("GLB chunk is truncated"))?;
+        match chunk_type {
+            GLB_CHUNK_JSON => {
+                json = Some(std::str::from_utf8(chunk).context("GLB JSON is not UTF-8")?);
+            }
+            GLB_CHUNK_BIN => bin = Some(chunk),
+            _ => {}
+        }
+        offset += 8 + chunk_length.next_multiple_of(4);
+    }
+    Ok((json.ok_or_else(|| anyhow::anyhow!("GLB file has no JSON chunk"))?, bin))
+}
+
+/// Decodes standard base64 as used by data URIs, padding is optional.
+fn decode_base64(text: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let sextet = |byte: u8| match byte {
+        b'A'..=b'Z' => Ok(byte - b'A'),
+        b'a'..=b'z' => Ok(byte - b'a' + 26),
+        b'0'..=b'9' => Ok(byte - b'0' + 52),
+        b'+' => Ok(62),
+        b'/' => Ok(63),
+        _ => Err(anyhow::anyhow!("invalid base64 character {:?}", byte as char)),
+    };
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    for chunk in text.chunks(4) {
+        let mut bits = 0u32;
+        for (i, &byte) in chunk.iter().enumerate() {
+            bits |= (sextet(byte)? as u32) << (18 - 6 * i);
+        }
+        let count = chunk.len() * 6 / 8;
+        bytes.extend_from_slice(&bits.to_be_bytes()[1..1 + count]);
+    }
+    Ok(bytes)
+}
+
+struct Gltf {
+    json: Json,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl Gltf {
+    fn new(json: Json, bin: Option<&[u8]>, dir: &Path) -> Result<Self, anyhow::Error> {
+        let buffers = json["buffers"].members().iter().map(|buffer| {
+            match buffer["uri"].as_str() {
+                Some(uri) if uri.starts_with("data:") => {
+                    let (_, data) = uri.split_once(";base64,")
+                        .ok_or_else(|| anyhow::anyhow!("only base64 data URIs are supported"))?;
+                    decode_base64(data)
+                }
+                Some(uri) => std::fs::read(dir.join(uri))
+                    .with_context(|| format!("Failed to read buffer {uri}")),
+                None => bin.map(<[u8]>::to_vec)
+                    .ok_or_else(|| anyhow::anyhow!("buffer without uri outside of a GLB file")),
+            }
+        }).collect::<Result<_, _>>()?;
+        Ok(Self { json, buffers })
+    }
+
+    fn skinned_model(&self) -> Result<SkinnedModel, anyhow::Error> {
+        let nodes = self.json["nodes"].members();
+        // prefer a skinned mesh, otherwise take the first mesh
+        let mesh_node = nodes.iter().position(|node| !node["skin"].is_null())
+            .or_else(|| nodes.iter().position(|node| !node["mesh"].is_null()))
+            .ok_or_else(|| anyhow::anyhow!("glTF file has no mesh"))?;
+        let mesh_index = nodes[mesh_node]["mesh"].as_usize()
+            .ok_or_else(|| anyhow::anyhow!("node {mesh_node} has no mesh"))?;
+        let skin = &self.json["skins"][nodes[mesh_node]["skin"].as_usize().unwrap_or(usize::MAX)];
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for primitive in self.json["meshes"][mesh_index]["primitives"].members() {
+            if primitive["mode"].as_usize().unwrap_or(4) != 4 {
+                log::warn!("skipping primitive of mesh {mesh_index} that is not made of triangles");
+                continue;
+            }
+            let attributes = &primitive["attributes"];
+            let positions = self.accessor(&attributes["POSITION"])?;
+            if positions.components != 3 {
+                anyhow::bail!("positions must have 3 components");
+            }
+            let count = positions.count();
+            let (joints, weights) = if skin.is_null() {
+                (None, None)
+            } else {
+                (self.optional_accessor(&attributes["JOINTS_0"])?,
+                    self.optional_accessor(&attributes["WEIGHTS_0"])?)
+            };
+            for accessor in joints.iter().chain(weights.iter()) {
+                if accessor.components != JOINTS_PER_VERTEX || accessor.count() != count {
+                    anyhow::bail!("joints and weights need 4 components for every vertex");
+                }
+            }
+            let first = vertices.len() as u32;
+            for i in 0..count {
+                let mut vertex = SkinnedVertex {
+                    position: positions.get(i),
+                    joints: [0; JOINTS_PER_VERTEX],
+                    weights: [1., 0., 0., 0.],
+                };
+                if let (Some(joints), Some(weights)) = (&joints, &weights) {
+                    let vertex_weights = weights.get::<JOINTS_PER_VERTEX>(i);
+                    let sum = vertex_weights.iter().sum::<f32>();
+                    if sum > 0. {
+                        vertex.joints = joints.get(i).map(|joint: f32| joint as u32);
+                        vertex.weights = vertex_weights.map(|weight| weight / sum);
+                    }
+                }
+                vertices.push(vertex);
+            }
+            match self.optional_accessor(&primitive["indices"])? {
+                Some(accessor) => indices.extend(accessor.values.iter().map(|&i| first + i as u32)),
+                None => indices.extend(first..first + count as u32),
+            }
+        }
+        if indices.iter().any(|&i| i as usize >= vertices.len()) {
+            anyhow::bail!("index out of bounds");
+        }
+
+        let skeleton = self.skeleton(skin, mesh_node)?;
+        let joint_count = skeleton.joint_count();
+        if vertices.iter().flat_map(|vertex| vertex.joints).any(|j| j as usize >= joint_count) {
+            anyhow::bail!("vertex bound to a joint that is not part of the skin");
+        }
+        let animations = self.json["animations"].members().iter().enumerate()
+            .map(|(i, animation)| self.animation(i, animation))
+            .collect::<Result<_, _>>()?;
+        Ok(SkinnedModel { vertices, indices, skeleton, animations })
+    }
+
+    fn skeleton(&self, skin: &Json, mesh_node: usize) -> Result<Skeleton, anyhow::Error> {
+        let json_nodes = self.json["nodes"].members();
+        let mut nodes = json_nodes.iter().map(|node| {
+            let transform = match node["matrix"].as_f32_array::<16>() {
+                Some(m) => Transform::Matrix(Matrix4::from([0, 1, 2, 3].map(|col| {
+                    [m[col * 4], m[col * 4 + 1], m[col * 4 + 2], m[col * 4 + 3]]
+                }))),
+                None => Transform::Trs {
+                    translation: node["translation"].as_f32_array().unwrap_or([0.; 3]),
+                    rotation: node["rotation"].as_f32_array().unwrap_or([0., 0., 0., 1.]),
+                    scale: node["scale"].as_f32_array().unwrap_or([1.; 3]),
+                },
+            };
+            Node { parent: None, transform }
+        }).collect::<Vec<_>>();
+        for (parent, node) in json_nodes.iter().enumerate() {
+            for child in node["children"].members() {
+                let child = child.as_usize().filter(|&child| child < nodes.len())
+                    .ok_or_else(|| anyhow::anyhow!("node {parent} has an invalid child"))?;
+                if nodes[child].parent.is_some() {
+                    anyhow::bail!("node {child} has more than one parent");
+                }
+                nodes[child].parent = Some(parent);
+            }
+        }
+        // a cycle would never reach a root
+        for start in 0..nodes.len() {
+            let mut node = start;
+            for _ in 0..=nodes.len() {
+                match nodes[node].parent {
+                    Some(parent) => node = parent,
+                    None => break,
+                }
+            }
+            if nodes[node].parent.is_some() {
+                anyhow::bail!("the nodes contain a cycle");
+            }
+        }
+
+        if skin.is_null() {
+            return Ok(Skeleton {
+                nodes,
+                joints: vec![mesh_node],
+                inverse_bind_matrices: vec![Matrix4::unit()],
+            });
+        }
+        let joints = skin["joints"].members().iter()
+            .map(|joint| joint.as_usize().filter(|&joint| joint < nodes.len()))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| anyhow::anyhow!("skin has an invalid joint"))?;
+        let inverse_bind_matrices = match self.optional_accessor(&skin["inverseBindMatrices"])? {
+            Some(accessor) => {
+                if accessor.components != 16 || accessor.count() != joints.len() {
+                    anyhow::bail!("skin needs an inverse bind matrix for every joint");
+                }
+                (0..joints.len()).map(|i| {
+                    let m = accessor.get::<16>(i);
+                    Matrix4::from([0, 1, 2, 3].map(|col| {
+                        [m[col * 4], m[col * 4 + 1], m[col * 4 + 2], m[col * 4 + 3]]
+                    }))
+                }).collect()
+            }
+            None => vec![Matrix4::unit(); joints.len()],
+        };
+        Ok(Skeleton { nodes, joints, inverse_bind_matrices })
+    }
+
+    fn animation(&self, index: usize, animation: &Json) -> Result<Animation, anyhow::Error> {
+        let name = animation["name"].as_str()
+            .map_or_else(|| format!("animation {index}"), str::to_owned);
+        let node_count = self.json["nodes"].members().len();
+        let mut channels = Vec::new();
+        for channel in animation["channels"].members() {
+            let target = &channel["target"];
+            let property = match target["path"].as_str() {
+                Some("translation") => Property::Translation,
+                Some("rotation") => Property::Rotation,
+                Some("scale") => Property::Scale,
+                // e.g. the weights of morph targets
+                _ => continue,
+            };
+            let Some(node) = target["node"].as_usize().filter(|&node| node < node_count) else {
+                continue;
+            };
+            let sampler_index = channel["sampler"].as_usize().unwrap_or(usize::MAX);
+            let sampler = &animation["samplers"][sampler_index];
+            let interpolation = match sampler["interpolation"].as_str() {
+                Some("STEP") => Interpolation::Step,
+                Some("LINEAR") | None => Interpolation::Linear,
+                Some(other) => anyhow::bail!("{other} interpolation is not supported in {name}"),
+            };
+            let times = self.accessor(&sampler["input"])?;
+            let output = self.accessor(&sampler["output"])?;
+            if times.components != 1 || output.count() != times.count() || times.count() == 0 {
+                anyhow::bail!("a sampler of {name} needs one value for every time");
+            }
+            let values = (0..output.count()).map(|i| {
+                let mut value = [0.; 4];
+                for (c, v) in value.iter_mut().enumerate().take(output.components) {
+                    *v = output.values[i * output.components + c] as f32;
+                }
+                value
+            }).collect();
+            let times = times.values.iter().map(|&t| t as f32).collect();
+            channels.push(Channel { node, property, interpolation, times, values });
+        }
+        let duration = channels.iter().filter_map(|channel| channel.times.last().copied())
+            .fold(0., f32::max);
+        Ok(Animation { name, duration, channels })
+    }
+
+    fn optional_accessor(&self, index: &Json) -> Result<Option<Accessor>, anyhow::Error> {
+        if index.is_null() { Ok(None) } else { self.accessor(index).map(Some) }
+    }
+
+    /// Reads the accessor at `index` into floats, normalized integers are mapped to 0 to 1
+    /// or -1 to 1.
+    fn accessor(&self, index: &Json) -> Result<Accessor, anyhow::Error> {
+        let index = index.as_usize().ok_or_else(|| anyhow::anyhow!("missing accessor"))?;
+        let accessor = &self.json["accessors"][index];
+        let error = |msg: &str| anyhow::anyhow!("accessor {index}: {msg}");
+        if !accessor["sparse"].is_null() {
+            return Err(error("sparse accessors are not supported"));
+        }
+        let count = accessor["count"].as_usize().ok_or_else(|| error("missing count"))?;
+        let components = match accessor["type"].as_str() {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            _ => return Err(error("invalid type")),
+        };
+        let component_type = accessor["componentType"].as_usize().unwrap_or(0);
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            _ => return Err(error("invalid component type")),
+        };
+        let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+        let Some(view_index) = accessor["bufferView"].as_usize() else {
+            return Ok(Accessor { components, values: vec![0.; count * components] });
+        };
+        let view = &self.json["bufferViews"][view_index];
+        let buffer = view["buffer"].as_usize().and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| error("invalid buffer"))?;
+        let offset = view["byteOffset"].as_usize().unwrap_or(0)
+            + accessor["byteOffset"].as_usize().unwrap_or(0);
+        let stride = view["byteStride"].as_usize().unwrap_or(components * size);
+        let end = offset + stride * count.saturating_sub(1) + components * size;
+        if count > 0 && end > buffer.len() {
+            return Err(error("out of bounds of its buffer"));
+        }
+
+        let mut values = Vec::with_capacity(count * components);
+        for element in 0..count {
+            for component in 0..components {
+                let at = offset + element * stride + component * size;
+                let bytes = &buffer[at..at + size];
+                let value = match component_type {
+                    5120 => bytes[0] as i8 as f64,
+                    5121 => bytes[0] as f64,
+                    5122 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    5123 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+                    5125 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                    _ => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                };
+                let value = match (normalized, component_type) {
+                    (true, 5120) => (value / 127.).max(-1.),
+                    (true, 5121) => value / 255.,
+                    (true, 5122) => (value / 32767.).max(-1.),
+                    (true, 5123) => value / 65535.,
+                    _ => value,
+                };
+                values.push(value);
+            }
+        }
+        Ok(Accessor { components, values })
+    }
+}
+
+struct Accessor {
+    components: usize,
+    values: Vec<f64>,
+}
+
+impl Accessor {
+    fn count(&self) -> usize {
+        self.values.len() / self.components
+    }
+
+    fn get<const N: usize>(&self, index: usize) -> [f32; N] {
+        std::array::from_fn(|i| self.values[index * self.components + i] as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two joints on top of each other, the upper one turns by 90 degrees around z in one second.
+    const ARM: &str = r#"{
+        "asset": {"version": "2.0"},
+        "nodes": [
+            {"mesh": 0, "skin": 0},
+            {"children": [2], "translation": [0, 0, 0]},
+            {"translation": [0, 1, 0]}
+        ],
+        "skins": [{"joints": [1, 2], "inverseBindMatrices": 4}],
+        "meshes": [{"primitives": [{
+            "attributes": {"POSITION": 0, "JOINTS_0": 1, "WEIGHTS_0": 2}, "indices": 3
+        }]}],
+        "animations": [{"name": "wave", "channels": [
+            {"sampler": 0, "target": {"node": 2, "path": "rotation"}}
+        ], "samplers": [{"input": 5, "output": 6}]}],
+        "buffers": [{"byteLength": 0, "uri": "BUFFER"}],
+        "bufferViews": [{"buffer": 0, "byteOffset":0, "byteLength": 0}],
+        "accessors": [
+            {"bufferView": 0, "byteOffset":0, "componentType":5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 0, "byteOffset":36, "componentType":5121, "count": 3, "type": "VEC4"},
+            {"bufferView": 0, "byteOffset":48, "componentType":5126, "count": 3, "type": "VEC4"},
+            {"bufferView": 0, "byteOffset":96, "componentType":5123, "count": 3, "type": "SCALAR"},
+            {"bufferView": 0, "byteOffset":104, "componentType":5126, "count": 2, "type": "MAT4"},
+            {"bufferView": 0, "byteOffset":232, "componentType":5126, "count": 2, "type": "SCALAR"},
+            {"bufferView": 0, "byteOffset":240, "componentType":5126, "count": 2, "type": "VEC4"}
+        ]
+    }"#;
+
+    fn arm_buffer() -> Vec<u8> {
+        fn floats<'a>(values: impl IntoIterator<Item = &'a f32>) -> impl Iterator<Item = u8> {
+            values.into_iter().flat_map(|value| value.to_le_bytes())
+        }
+        let mut buffer = Vec::new();
+        // positions, joints, weights and indices padded to four bytes
+        buffer.extend(floats(&[0., 0., 0., 0., 1., 0., 1., 1., 0.]));
+        buffer.extend([0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+        buffer.extend(floats(&[1., 0., 0., 0., 1., 0., 0., 0., 0.5, 0., 0., 0.]));
+        buffer.extend([0u16, 1, 2, 0].iter().flat_map(|index| index.to_le_bytes()));
+        // inverse bind matrices
+        for matrix in [Matrix4::unit(), Matrix4::from_translation(Vector3::from([0., -1., 0.]))] {
+            let cols: [[f32; 4]; 4] = matrix.into();
+            buffer.extend(floats(cols.as_flattened()));
+        }
+        // keyframe times and rotations
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        buffer.extend(floats(&[0., 1., 0., 0., 0., 1., 0., 0., half, half]));
+        buffer
+    }
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        bytes.chunks(3).flat_map(|chunk| {
+            let bits = chunk.iter().enumerate()
+                .fold(0u32, |bits, (i, &b)| bits | (b as u32) << (16 - 8 * i));
+            (0..chunk.len() + 1).map(move |i| CHARS[(bits >> (18 - 6 * i) & 63) as usize] as char)
+        }).collect()
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        assert_eq!(decode_base64(&encode_base64(&bytes)).unwrap(), bytes);
+        assert!(decode_base64("a*").is_err());
+    }
+
+    #[test]
+    fn load_skinned_model() {
+        let uri = format!("data:application/octet-stream;base64,{}", encode_base64(&arm_buffer()));
+        let gltf = ARM.replace("BUFFER", &uri);
+        let model = SkinnedModel::from_slice(gltf.as_bytes(), Path::new(".")).unwrap();
+        assert_eq!(model.indices, [0, 1, 2]);
+        assert_eq!(model.vertices[2].joints, [1, 0, 0, 0]);
+        // the weights are normalized
+        assert_eq!(model.vertices[2].weights, [1., 0., 0., 0.]);
+        assert_eq!(model.skeleton.joint_count(), 2);
+        assert_eq!(model.animations[0].name, "wave");
+        assert_eq!(model.animations[0].duration, 1.);
+
+        let transform = |matrix: Matrix4, pos: [f32; 3]| {
+            (Vector4::from([pos[0], pos[1], pos[2], 1.]) * matrix).resize::<3>()
+        };
+        let close = |a: Vector3, b: [f32; 3]| (a - Vector3::from(b)).magnitude() < 1e-5;
+
+        // in the bind pose nothing moves
+        let matrices = model.skeleton.joint_matrices(None, 0.);
+        assert!(close(transform(matrices[1], [1., 1., 0.]), [1., 1., 0.]));
+
+        // after one second the upper vertex is turned around the upper joint
+        let matrices = model.skeleton.joint_matrices(Some(&model.animations[0]), 0.999_999);
+        assert!(close(transform(matrices[0], [0., 1., 0.]), [0., 1., 0.]));
+        assert!(close(transform(matrices[1], [1., 1., 0.]), [0., 2., 0.]));
+        // halfway through it is turned by 45 degrees
+        let matrices = model.skeleton.joint_matrices(Some(&model.animations[0]), 0.5);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert!(close(transform(matrices[1], [1., 1., 0.]), [half, 1. + half, 0.]));
+    }
+
+    #[test]
+    fn reject_invalid_models() {
+        let empty = r#"{"asset": {"version": "2.0"}}"#;
+        assert!(SkinnedModel::from_slice(empty.as_bytes(), Path::new(".")).is_err());
+        let uri = format!("data:application/octet-stream;base64,{}", encode_base64(&arm_buffer()));
+        let cycle = ARM.replace("BUFFER", &uri)
+            .replace(r#"{"translation": [0, 1, 0]}"#, r#"{"children": [1]}"#);
+        assert!(SkinnedModel::from_slice(cycle.as_bytes(), Path::new(".")).is_err());
+        assert!(parse_glb(b"glTF\x01\0\0\0").is_err());
+    }
+}
@@ -0,0 +1,192 @@
+//! The state of the keyboard as art samples it, laid out like the keyboard channel of Shadertoy
+//! so that interactive shaders can be ported without changes.
+//!
+//! Keys are indexed by their JavaScript key code, e.g. 65 for A or 37 for the left arrow.
+//! Row 0 of the texture is 1 while a key is down, row 1 is 1 in the frame it was pressed
+//! and row 2 switches between 0 and 1 every time it is pressed.
+
+use winit::keyboard::KeyCode;
+
+/// Width of the texture, one column per key code.
+pub const KEY_COUNT: usize = 256;
+/// Height of the texture, see the module documentation.
+pub const ROWS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardState {
+    down: [bool; KEY_COUNT],
+    pressed: [bool; KEY_COUNT],
+    toggled: [bool; KEY_COUNT],
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        Self {
+            down: [false; KEY_COUNT],
+            pressed: [false; KEY_COUNT],
+            toggled: [false; KEY_COUNT],
+        }
+    }
+}
+
+impl KeyboardState {
+    /// Records that `key` went down or up, keys without a JavaScript key code are ignored.
+    pub fn set(&mut self, key: KeyCode, down: bool) {
+        let Some(code) = js_key_code(key) else { return };
+        let code = code as usize;
+        if down && !self.down[code] {
+            self.pressed[code] = true;
+            self.toggled[code] = !self.toggled[code];
+        }
+        self.down[code] = down;
+    }
+
+    /// Lets go of every key, e.g. when the window loses focus and the releases would be missed.
+    pub fn release_all(&mut self) {
+        self.down = [false; KEY_COUNT];
+    }
+
+    /// Forgets which keys were pressed, called after every frame.
+    pub fn end_frame(&mut self) {
+        self.pressed = [false; KEY_COUNT];
+    }
+
+    /// The texels of the R8 texture row by row.
+    pub fn texels(&self) -> [u8; KEY_COUNT * ROWS] {
+        let mut texels = [0; KEY_COUNT * ROWS];
+        for (row, states) in [&self.down, &self.pressed, &self.toggled].into_iter().enumerate() {
+            for (texel, &state) in texels[row * KEY_COUNT..].iter_mut().zip(states) {
+                *texel = if state { 255 } else { 0 };
+            }
+        }
+        texels
+    }
+}
+
+/// The JavaScript key code of a physical key as used by Shadertoy.
+pub fn js_key_code(key: KeyCode) -> Option<u8> {
+    let code = match key {
+        KeyCode::Backspace => 8,
+        KeyCode::Tab => 9,
+        KeyCode::Enter | KeyCode::NumpadEnter => 13,
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => 16,
+        KeyCode::ControlLeft | KeyCode::ControlRight => 17,
+        KeyCode::AltLeft | KeyCode::AltRight => 18,
+        KeyCode::Pause => 19,
+        KeyCode::CapsLock => 20,
+        KeyCode::Escape => 27,
+        KeyCode::Space => 32,
+        KeyCode::PageUp => 33,
+        KeyCode::PageDown => 34,
+        KeyCode::End => 35,
+        KeyCode::Home => 36,
+        KeyCode::ArrowLeft => 37,
+        KeyCode::ArrowUp => 38,
+        KeyCode::ArrowRight => 39,
+        KeyCode::ArrowDown => 40,
+        KeyCode::Insert => 45,
+        KeyCode::Delete => 46,
+        KeyCode::Digit0 => 48,
+        KeyCode::Digit1 => 49,
+        KeyCode::Digit2 => 50,
+        KeyCode::Digit3 => 51,
+        KeyCode::Digit4 => 52,
+        KeyCode::Digit5 => 53,
+        KeyCode::Digit6 => 54,
+        KeyCode::Digit7 => 55,
+        KeyCode::Digit8 => 56,
+        KeyCode::Digit9 => 57,
+        KeyCode::KeyA => 65,
+        KeyCode::KeyB => 66,
+        KeyCode::KeyC => 67,
+        KeyCode::KeyD => 68,
+        KeyCode::KeyE => 69,
+        KeyCode::KeyF => 70,
+        KeyCode::KeyG => 71,
+        KeyCode::KeyH => 72,
+        KeyCode::KeyI => 73,
+        KeyCode::KeyJ => 74,
+        KeyCode::KeyK => 75,
+        KeyCode::KeyL => 76,
+        KeyCode::KeyM => 77,
+        KeyCode::KeyN => 78,
+        KeyCode::KeyO => 79,
+        KeyCode::KeyP => 80,
+        KeyCode::KeyQ => 81,
+        KeyCode::KeyR => 82,
+        KeyCode::KeyS => 83,
+        KeyCode::KeyT => 84,
+        KeyCode::KeyU => 85,
+        KeyCode::KeyV => 86,
+        KeyCode::KeyW => 87,
+        KeyCode::KeyX => 88,
+        KeyCode::KeyY => 89,
+        KeyCode::KeyZ => 90,
+        KeyCode::Numpad0 => 96,
+        KeyCode::Numpad1 => 97,
+        KeyCode::Numpad2 => 98,
+        KeyCode::Numpad3 => 99,
+        KeyCode::Numpad4 => 100,
+        KeyCode::Numpad5 => 101,
+        KeyCode::Numpad6 => 102,
+        KeyCode::Numpad7 => 103,
+        KeyCode::Numpad8 => 104,
+        KeyCode::Numpad9 => 105,
+        KeyCode::NumpadMultiply => 106,
+        KeyCode::NumpadAdd => 107,
+        KeyCode::NumpadSubtract => 109,
+        KeyCode::NumpadDecimal => 110,
+        KeyCode::NumpadDivide => 111,
+        KeyCode::F1 => 112,
+        KeyCode::F2 => 113,
+        KeyCode::F3 => 114,
+        KeyCode::F4 => 115,
+        KeyCode::F5 => 116,
+        KeyCode::F6 => 117,
+        KeyCode::F7 => 118,
+        KeyCode::F8 => 119,
+        KeyCode::F9 => 120,
+        KeyCode::F10 => 121,
+        KeyCode::F11 => 122,
+        KeyCode::F12 => 123,
+        KeyCode::Semicolon => 186,
+        KeyCode::Equal => 187,
+        KeyCode::Comma => 188,
+        KeyCode::Minus => 189,
+        KeyCode::Period => 190,
+        KeyCode::Slash => 191,
+        KeyCode::Backquote => 192,
+        KeyCode::BracketLeft => 219,
+        KeyCode::Backslash => 220,
+        KeyCode::BracketRight => 221,
+        KeyCode::Quote => 222,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn press_and_toggle() {
+        let mut keyboard = KeyboardState::default();
+        keyboard.set(KeyCode::KeyA, true);
+        // repeated key down events do not press again
+        keyboard.set(KeyCode::KeyA, true);
+        let texels = keyboard.texels();
+        assert_eq!([texels[65], texels[KEY_COUNT + 65], texels[2 * KEY_COUNT + 65]], [255; 3]);
+
+        keyboard.end_frame();
+        keyboard.set(KeyCode::KeyA, false);
+        let texels = keyboard.texels();
+        assert_eq!([texels[65], texels[KEY_COUNT + 65], texels[2 * KEY_COUNT + 65]], [0, 0, 255]);
+
+        keyboard.set(KeyCode::KeyA, true);
+        keyboard.release_all();
+        let texels = keyboard.texels();
+        assert_eq!([texels[65], texels[KEY_COUNT + 65], texels[2 * KEY_COUNT + 65]], [0, 255, 0]);
+        assert_eq!(texels.iter().filter(|&&texel| texel != 0).count(), 1);
+    }
+}
@@ -1,5 +1,37 @@
+//! A gallery of shader art rendered with Vulkan.
+//!
+//! Programs embedding the gallery build it with a [`GalleryBuilder`] from [`ArtPiece`]s and
+//! draw it from a [`Camera`], the other modules are the building blocks of the gallery.
+
+pub mod animation;
+pub mod audio;
+pub mod bench;
+pub mod capture;
+pub mod collision;
+pub mod discovery;
 pub mod env_generator;
 pub mod fs;
+pub mod gallery;
+pub mod gizmo;
+pub mod gltf;
+pub mod json;
+pub mod keyboard;
+pub mod lightmap;
+pub mod lights;
+pub mod lut;
 pub mod math;
+pub mod noise;
 pub mod obj;
+pub mod palette;
+pub mod post_effects;
+pub mod quality;
+pub mod remote;
+pub mod scene_config;
+pub mod script;
+pub mod session;
+pub mod slideshow;
+pub mod text;
+pub mod video;
 pub mod vulkan;
+
+pub use gallery::{ArtPiece, Camera, GalleryBuilder};
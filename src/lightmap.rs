@@ -0,0 +1,463 @@
+//! Baking the light of the sky into a lightmap of the environment.
+//!
+//! Every triangle gets its own square cell of the lightmap in the order of the indices,
+//! so the environment and its lightmap only match as long as the model does not change.
+//! A texel stores how much of the light of the sky reaches the surface after bouncing around
+//! the environment, found by path tracing on the CPU. The gallery multiplies the environment
+//! with it, see [`crate::vulkan::VkApp::set_lightmap`].
+
+use crate::math::Vector3;
+
+use image::{Rgba, RgbaImage};
+use std::thread;
+
+/// Texels along each side of the cell of a triangle.
+pub const CELL_SIZE: u32 = 8;
+/// Texels between a triangle and the border of its cell, so that filtering stays in the cell.
+const CELL_PADDING: f32 = 1.;
+/// Radiance of the sky above the horizon for rays leaving the environment.
+const SKY_RADIANCE: f32 = 1.;
+/// Radiance of the ground below the horizon for rays leaving the environment.
+const GROUND_RADIANCE: f32 = 0.2;
+/// Rays start this far from the surface, so that they do not hit the triangle they start on.
+const RAY_OFFSET: f32 = 1e-3;
+/// Most triangles in a leaf of the bounding volume hierarchy.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Paths traced per texel.
+    pub samples: u32,
+    /// Times a path bounces off the environment before it is cut off.
+    pub bounces: u32,
+    /// Fraction of the light the environment reflects.
+    pub albedo: f32,
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            samples: 128,
+            bounces: 3,
+            albedo: 0.6,
+        }
+    }
+}
+
+/// Where the triangles of a model are in its lightmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LightmapLayout {
+    cells_per_row: u32,
+    rows: u32,
+}
+
+impl LightmapLayout {
+    /// The smallest square-ish lightmap with a cell for each of `triangle_count` triangles.
+    pub fn new(triangle_count: usize) -> Self {
+        let cells_per_row = ((triangle_count as f64).sqrt().ceil() as u32).max(1);
+        let rows = (triangle_count as u32).div_ceil(cells_per_row).max(1);
+        Self { cells_per_row, rows }
+    }
+
+    /// Width and height of the lightmap in texels.
+    pub fn size(&self) -> [u32; 2] {
+        [self.cells_per_row * CELL_SIZE, self.rows * CELL_SIZE]
+    }
+
+    /// The lightmap coordinates of the corners of each triangle in `indices` from 0 to 1
+    /// with v pointing up, one for each index.
+    pub fn coords(&self, positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 2]> {
+        let [width, height] = self.size().map(|size| size as f32);
+        indices.chunks_exact(3).enumerate().flat_map(|(triangle, corners)| {
+            let corners = [0, 1, 2].map(|i| Vector3::from(positions[corners[i] as usize]));
+            self.texel_coords(triangle, corners).map(|[u, v]| [u / width, v / height])
+        }).collect()
+    }
+
+    /// The corners of the triangle with index `triangle` in texels of the lightmap.
+    ///
+    /// The triangle keeps its shape and is scaled to fit into its cell.
+    fn texel_coords(&self, triangle: usize, corners: [Vector3; 3]) -> [[f32; 2]; 3] {
+        let cell = [triangle as u32 % self.cells_per_row, triangle as u32 / self.cells_per_row]
+            .map(|cell| (cell * CELL_SIZE) as f32 + CELL_PADDING);
+        let edge = corners[1] - corners[0];
+        let other = corners[2] - corners[0];
+        let length = edge.magnitude();
+        let axis = if length > 0. { edge / length } else { Vector3::from([1., 0., 0.]) };
+        let along = other.dot(axis);
+        let across = (other - axis * along).magnitude();
+        let min_x = along.min(0.);
+        let extent = (length.max(along) - min_x).max(across).max(f32::EPSILON);
+        let scale = (CELL_SIZE as f32 - 2. * CELL_PADDING) / extent;
+        [[0., 0.], [length, 0.], [along, across]]
+            .map(|[x, y]| [cell[0] + (x - min_x) * scale, cell[1] + y * scale])
+    }
+}
+
+/// Path traces the light of the sky reaching each triangle in `indices` into a lightmap
+/// laid out by [`LightmapLayout`].
+///
+/// The front of a triangle is the side its corners go around counterclockwise, the texels
+/// store the light on that side. The rows of the image go from top to bottom, so v points up
+/// once it is loaded with y up like the other textures.
+pub fn bake(positions: &[[f32; 3]], indices: &[u32], settings: &BakeSettings) -> RgbaImage {
+    let triangles = indices.chunks_exact(3)
+        .map(|corners| [0, 1, 2].map(|i| Vector3::from(positions[corners[i] as usize])))
+        .collect::<Vec<_>>();
+    let layout = LightmapLayout::new(triangles.len());
+    let bvh = Bvh::new(&triangles);
+
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = triangles.len().div_ceil(threads).max(1);
+    let cells = thread::scope(|scope| {
+        let handles = triangles.chunks(chunk_size).enumerate().map(|(chunk, triangles)| {
+            let bvh = &bvh;
+            scope.spawn(move || {
+                triangles.iter().enumerate().map(|(i, &corners)| {
+                    let triangle = chunk * chunk_size + i;
+                    bake_cell(bvh, &layout, triangle, corners, settings)
+                }).collect::<Vec<_>>()
+            })
+        }).collect::<Vec<_>>();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap()).collect::<Vec<_>>()
+    });
+
+    let [width, height] = layout.size();
+    let mut image = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+    for texels in cells {
+        for (x, y, value) in texels {
+            let value = (value.clamp(0., 1.) * 255.).round() as u8;
+            image.put_pixel(x, height - 1 - y, Rgba([value, value, value, 255]));
+        }
+    }
+    image
+}
+
+/// The texels of the cell of `triangle` that are covered by it or next to it
+/// with the light reaching them.
+fn bake_cell(
+    bvh: &Bvh,
+    layout: &LightmapLayout,
+    triangle: usize,
+    corners: [Vector3; 3],
+    settings: &BakeSettings,
+) -> Vec<(u32, u32, f32)> {
+    let cross = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+    if cross.magnitude() == 0. {
+        return Vec::new();
+    }
+    let normal = cross.normalize();
+    let texel_coords = layout.texel_coords(triangle, corners);
+    let cell = [
+        triangle as u32 % layout.cells_per_row * CELL_SIZE,
+        triangle as u32 / layout.cells_per_row * CELL_SIZE,
+    ];
+    let mut texels = Vec::new();
+    for y in cell[1]..cell[1] + CELL_SIZE {
+        for x in cell[0]..cell[0] + CELL_SIZE {
+            let center = [x as f32 + 0.5, y as f32 + 0.5];
+            // texels next to the triangle take the closest point on it, so that filtering
+            // at the edges does not blend with the black around it
+            let Some(weights) = barycentric(texel_coords, center) else { continue };
+            let weights = weights.map(|weight| weight.max(0.));
+            let sum = weights.iter().sum::<f32>();
+            let weights = weights.map(|weight| weight / sum);
+            let closest: [f32; 2] =
+                [0, 1].map(|i| (0..3).map(|j| texel_coords[j][i] * weights[j]).sum());
+            if (closest[0] - center[0]).hypot(closest[1] - center[1]) > 1. {
+                continue;
+            }
+            let position = (0..3).fold(Vector3::default(), |position, j| {
+                position + corners[j] * weights[j]
+            });
+            let origin = position + normal * RAY_OFFSET;
+            let mut rng = Rng::new(y.wrapping_mul(0x9e37_79b9) ^ x);
+            let light = (0..settings.samples)
+                .map(|_| trace(bvh, origin, cosine_sample(normal, &mut rng), &mut rng, settings))
+                .sum::<f32>();
+            texels.push((x, y, light / settings.samples.max(1) as f32));
+        }
+    }
+    texels
+}
+
+/// The light coming from `dir` towards `origin`.
+fn trace(
+    bvh: &Bvh,
+    mut origin: Vector3,
+    mut dir: Vector3,
+    rng: &mut Rng,
+    settings: &BakeSettings,
+) -> f32 {
+    let mut throughput = 1.;
+    for bounce in 0..=settings.bounces {
+        let Some((t, normal)) = bvh.intersect(origin, dir) else {
+            return throughput * if dir.y() > 0. { SKY_RADIANCE } else { GROUND_RADIANCE };
+        };
+        if bounce == settings.bounces {
+            break;
+        }
+        throughput *= settings.albedo;
+        // the environment reflects on both sides
+        let normal = if normal.dot(dir) > 0. { -normal } else { normal };
+        origin = origin + dir * t + normal * RAY_OFFSET;
+        dir = cosine_sample(normal, rng);
+    }
+    0.
+}
+
+/// A direction around `normal` that is more likely the closer it is to `normal`,
+/// so that the mean of the light from these directions is the irradiance over pi.
+fn cosine_sample(normal: Vector3, rng: &mut Rng) -> Vector3 {
+    let helper = if normal.x().abs() < 0.9 { [1., 0., 0.] } else { [0., 1., 0.] };
+    let tangent = normal.cross(Vector3::from(helper)).normalize();
+    let bitangent = normal.cross(tangent);
+    let phi = std::f32::consts::TAU * rng.next_f32();
+    let r2 = rng.next_f32();
+    let r = r2.sqrt();
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1. - r2).sqrt()
+}
+
+/// The barycentric coordinates of `point` in the 2D triangle `corners`.
+fn barycentric(corners: [[f32; 2]; 3], point: [f32; 2]) -> Option<[f32; 3]> {
+    let [a, b, c] = corners;
+    let det = (b[1] - c[1]) * (a[0] - c[0]) + (c[0] - b[0]) * (a[1] - c[1]);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let u = ((b[1] - c[1]) * (point[0] - c[0]) + (c[0] - b[0]) * (point[1] - c[1])) / det;
+    let v = ((c[1] - a[1]) * (point[0] - c[0]) + (a[0] - c[0]) * (point[1] - c[1])) / det;
+    Some([u, v, 1. - u - v])
+}
+
+/// A xorshift generator, good enough to spread the samples of a texel.
+struct Rng(u32);
+
+impl Rng {
+    fn new(seed: u32) -> Self {
+        // xorshift never leaves zero
+        let mut rng = Self(seed.wrapping_mul(0x2c1b_3c6d) | 1);
+        rng.next();
+        rng
+    }
+
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    /// A number from 0 to 1 excluding 1.
+    fn next_f32(&mut self) -> f32 {
+        (self.next() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    min: Vector3,
+    max: Vector3,
+    /// The first triangle of a leaf or the first of the two children of an inner node.
+    start: usize,
+    /// Number of triangles of a leaf, zero for an inner node.
+    count: usize,
+}
+
+/// A bounding volume hierarchy over the triangles of the environment.
+struct Bvh {
+    nodes: Vec<BvhNode>,
+    triangles: Vec<[Vector3; 3]>,
+}
+
+impl Bvh {
+    fn new(triangles: &[[Vector3; 3]]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::new(),
+            triangles: triangles.to_vec(),
+        };
+        bvh.nodes.push(bvh.leaf(0, triangles.len()));
+        bvh.split(0);
+        bvh
+    }
+
+    fn leaf(&self, start: usize, count: usize) -> BvhNode {
+        let (min, max) = self.triangles[start..start + count].iter().flatten().fold(
+            (Vector3::new(f32::MAX), Vector3::new(f32::MIN)),
+            |(mut min, mut max), corner| {
+                for i in 0..3 {
+                    min[i] = min[i].min(corner[i]);
+                    max[i] = max[i].max(corner[i]);
+                }
+                (min, max)
+            },
+        );
+        BvhNode { min, max, start, count }
+    }
+
+    /// Splits the triangles of the leaf at `index` at the median along its longest axis.
+    fn split(&mut self, index: usize) {
+        let BvhNode { min, max, start, count } = self.nodes[index];
+        if count <= LEAF_SIZE {
+            return;
+        }
+        let size = max - min;
+        let axis = (0..3).max_by(|&a, &b| size[a].total_cmp(&size[b])).unwrap();
+        let centroid =
+            |triangle: &[Vector3; 3]| triangle.iter().map(|corner| corner[axis]).sum::<f32>();
+        self.triangles[start..start + count]
+            .sort_unstable_by(|a, b| centroid(a).total_cmp(&centroid(b)));
+        let half = count / 2;
+        let children = self.nodes.len();
+        let left = self.leaf(start, half);
+        let right = self.leaf(start + half, count - half);
+        self.nodes.extend([left, right]);
+        self.nodes[index].start = children;
+        self.nodes[index].count = 0;
+        self.split(children);
+        self.split(children + 1);
+    }
+
+    /// The distance to the closest triangle hit by the ray from `origin` along `dir`
+    /// and the normal of the triangle.
+    fn intersect(&self, origin: Vector3, dir: Vector3) -> Option<(f32, Vector3)> {
+        let inv_dir = Vector3::from([0, 1, 2].map(|i| 1. / dir[i]));
+        let mut closest: Option<(f32, usize)> = None;
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = self.nodes[index];
+            let max_t = closest.map_or(f32::MAX, |(t, _)| t);
+            if !hits_box(node.min, node.max, origin, inv_dir, max_t) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend([node.start, node.start + 1]);
+                continue;
+            }
+            for triangle in node.start..node.start + node.count {
+                if let Some(t) = hit_triangle(self.triangles[triangle], origin, dir) {
+                    if closest.is_none_or(|(closest, _)| t < closest) {
+                        closest = Some((t, triangle));
+                    }
+                }
+            }
+        }
+        closest.map(|(t, triangle)| {
+            let [a, b, c] = self.triangles[triangle];
+            (t, (b - a).cross(c - a).normalize())
+        })
+    }
+}
+
+/// Whether the ray enters the box before `max_t`, see the slab method.
+fn hits_box(min: Vector3, max: Vector3, origin: Vector3, inv_dir: Vector3, max_t: f32) -> bool {
+    let (mut near, mut far) = (0f32, max_t);
+    for i in 0..3 {
+        let t0 = (min[i] - origin[i]) * inv_dir[i];
+        let t1 = (max[i] - origin[i]) * inv_dir[i];
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    near <= far
+}
+
+/// The distance along the ray to `triangle` if it hits it, see Möller and Trumbore.
+fn hit_triangle([a, b, c]: [Vector3; 3], origin: Vector3, dir: Vector3) -> Option<f32> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+    let p = dir.cross(edge_2);
+    let det = edge_1.dot(p);
+    if det.abs() < 1e-9 {
+        return None;
+    }
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) / det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(edge_1);
+    let v = dir.dot(q) / det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    Some(edge_2.dot(q) / det).filter(|&t| t > 0.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A square from `min` to `max` in xz at height `y` facing up or down.
+    fn square(min: [f32; 2], max: [f32; 2], y: f32, up: bool) -> ([[f32; 3]; 4], [u32; 6]) {
+        let corners = [
+            [min[0], y, min[1]],
+            [min[0], y, max[1]],
+            [max[0], y, max[1]],
+            [max[0], y, min[1]],
+        ];
+        let indices = if up { [0, 1, 2, 2, 3, 0] } else { [0, 3, 2, 2, 1, 0] };
+        (corners, indices)
+    }
+
+    #[test]
+    fn layout() {
+        let layout = LightmapLayout::new(5);
+        assert_eq!(layout.size(), [3 * CELL_SIZE, 2 * CELL_SIZE]);
+        let positions = [[0., 0., 0.], [2., 0., 0.], [0., 0., 1.], [5., 5., 5.]];
+        let indices = [0, 1, 2, 0, 2, 1, 0, 1, 2, 0, 1, 2, 3, 3, 3];
+        let coords = layout.coords(&positions, &indices);
+        assert_eq!(coords.len(), indices.len());
+        for (triangle, corners) in coords.chunks(3).enumerate() {
+            let cell = [triangle as u32 % 3, triangle as u32 / 3];
+            for corner in corners {
+                for axis in 0..2 {
+                    let texel = corner[axis] * layout.size()[axis] as f32;
+                    let start = (cell[axis] * CELL_SIZE) as f32;
+                    let end = start + CELL_SIZE as f32 - CELL_PADDING;
+                    assert!(texel >= start + CELL_PADDING - 1e-4 && texel <= end + 1e-4);
+                }
+            }
+        }
+        // the first triangle keeps its shape, twice as long as wide
+        let [a, b, c] = [0, 1, 2].map(|i| {
+            let [width, height] = layout.size();
+            [coords[i][0] * width as f32, coords[i][1] * height as f32]
+        });
+        let ab = (b[0] - a[0]).hypot(b[1] - a[1]);
+        let ac = (c[0] - a[0]).hypot(c[1] - a[1]);
+        assert!((ab / ac - 2.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn bake_open_and_covered() {
+        let (open, open_indices) = square([-1., -1.], [1., 1.], 0., true);
+        let (floor, _) = square([10., -1.], [12., 1.], 0., true);
+        let (ceiling, _) = square([8., -3.], [14., 3.], 0.2, false);
+        let positions = [open, floor, ceiling].concat();
+        let indices = [0, 4, 8].iter()
+            .flat_map(|&offset| {
+                let up = offset != 8;
+                square([0.; 2], [0.; 2], 0., up).1.map(|index| index + offset)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(indices[..6], open_indices);
+        let settings = BakeSettings { samples: 32, bounces: 1, albedo: 0.5 };
+        let image = bake(&positions, &indices, &settings);
+
+        let layout = LightmapLayout::new(indices.len() / 3);
+        let height = layout.size()[1];
+        let coords = layout.coords(&positions, &indices);
+        let light = |triangle: usize| {
+            let corners = &coords[triangle * 3..triangle * 3 + 3];
+            let [u, v] = [0, 1].map(|i| corners.iter().map(|corner| corner[i]).sum::<f32>() / 3.);
+            let [x, y] = [u * layout.size()[0] as f32, v * height as f32].map(|t| t as u32);
+            image.get_pixel(x, height - 1 - y)[0]
+        };
+        // nothing is above the open square
+        assert_eq!(light(0), 255);
+        assert_eq!(light(1), 255);
+        // the ceiling covers the floor and only reflects half of the light
+        assert!(light(2) < 160, "{}", light(2));
+        assert!(light(3) < 160, "{}", light(3));
+    }
+}
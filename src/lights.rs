@@ -0,0 +1,93 @@
+use crate::math::Vector3;
+
+use anyhow::Context;
+use std::path::Path;
+
+/// A light shining in all directions that fades out towards its `radius`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vector3,
+    /// The color multiplied by the intensity, may be larger than 1.
+    pub color: Vector3,
+    pub radius: f32,
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<PointLight>, anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read lights {}", path.display()))?;
+    parse(&text).with_context(|| format!("Failed to parse lights {}", path.display()))
+}
+
+/// Parses one light per line as its position, color and radius, e.g.
+///
+/// ```text
+/// # a warm light in front of the first painting
+/// 4.5 2.5 -1.5   1.0 0.7 0.4   3
+/// ```
+pub fn parse(text: &str) -> Result<Vec<PointLight>, anyhow::Error> {
+    let mut lights = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = index + 1;
+        let values = line.split_whitespace()
+            .map(str::parse::<f32>)
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Invalid number in line {number}: {line}"))?;
+        let [x, y, z, r, g, b, radius] = values[..] else {
+            anyhow::bail!("Expected 7 numbers in line {number} but found {}", values.len());
+        };
+        anyhow::ensure!(
+            radius > 0.,
+            "The radius {radius} in line {number} is not positive",
+        );
+        anyhow::ensure!(
+            [r, g, b].iter().all(|&c| c >= 0.),
+            "The color in line {number} is negative",
+        );
+        lights.push(PointLight {
+            position: Vector3::from([x, y, z]),
+            color: Vector3::from([r, g, b]),
+            radius,
+        });
+    }
+    Ok(lights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_lights() {
+        let text = "# accents\n\
+            \n\
+            1 2 -3  1 0.5 0  4\n\
+            \t0 0 0 2 2 2 0.5 \n";
+        let lights = parse(text).unwrap();
+        assert_eq!(lights, [
+            PointLight {
+                position: Vector3::from([1., 2., -3.]),
+                color: Vector3::from([1., 0.5, 0.]),
+                radius: 4.,
+            },
+            PointLight {
+                position: Vector3::default(),
+                color: Vector3::from([2., 2., 2.]),
+                radius: 0.5,
+            },
+        ]);
+    }
+
+    #[test]
+    fn reject_invalid_lights() {
+        assert!(parse("1 2 3 1 1 1\n").is_err());
+        assert!(parse("1 2 3 1 1 1 1 1\n").is_err());
+        assert!(parse("1 2 3 1 one 1 1\n").is_err());
+        assert!(parse("1 2 3 1 1 1 0\n").is_err());
+        assert!(parse("1 2 3 -1 1 1 2\n").is_err());
+    }
+}
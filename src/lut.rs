@@ -0,0 +1,137 @@
+use anyhow::Context;
+
+use std::path::Path;
+
+/// A 3D color lookup table as stored in `.cube` files of Adobe and Resolve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub title: Option<String>,
+    /// Number of entries along each of the red, green and blue axes.
+    pub size: u32,
+    /// The output colors with red changing fastest, then green, then blue.
+    pub colors: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Largest size allowed by the specification of the format.
+    pub const MAX_SIZE: u32 = 256;
+
+    /// A table that maps every color to itself.
+    pub fn identity(size: u32) -> Self {
+        let scale = 1. / (size - 1) as f32;
+        let colors = (0..size * size * size)
+            .map(|i| [i % size, i / size % size, i / (size * size)].map(|c| c as f32 * scale))
+            .collect();
+        Self { title: None, size, colors }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read LUT {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to parse LUT {}", path.display()))
+    }
+
+    /// Parses the text of a `.cube` file.
+    ///
+    /// Only 3D tables with the default input domain from 0 to 1 are supported.
+    pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        let mut title = None;
+        let mut size = None;
+        let mut colors = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let number = index + 1;
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match keyword {
+                "TITLE" => title = Some(rest.trim_matches('"').to_owned()),
+                "LUT_3D_SIZE" => {
+                    let value = rest.parse::<u32>()
+                        .with_context(|| format!("Invalid size {rest:?} in line {number}"))?;
+                    anyhow::ensure!(
+                        (2..=Self::MAX_SIZE).contains(&value),
+                        "Size {value} in line {number} is not between 2 and {}",
+                        Self::MAX_SIZE,
+                    );
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => anyhow::bail!("1D tables are not supported"),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let default = if keyword == "DOMAIN_MIN" { 0. } else { 1. };
+                    let domain = parse_triple(rest)
+                        .with_context(|| format!("Invalid domain in line {number}"))?;
+                    anyhow::ensure!(
+                        domain == [default; 3],
+                        "Only the domain from 0 to 1 is supported, line {number} sets {domain:?}",
+                    );
+                }
+                _ => {
+                    let color = parse_triple(line)
+                        .with_context(|| format!("Invalid line {number}: {line}"))?;
+                    colors.push(color);
+                }
+            }
+        }
+
+        let size = size.context("Missing LUT_3D_SIZE")?;
+        let expected = (size * size * size) as usize;
+        anyhow::ensure!(
+            colors.len() == expected,
+            "Expected {expected} colors for size {size} but found {}",
+            colors.len(),
+        );
+        Ok(Self { title, size, colors })
+    }
+
+    /// The colors as RGBA texels of a 3D texture with the red axis along x.
+    pub fn texels(&self) -> Vec<u8> {
+        self.colors.iter()
+            .flat_map(|color| {
+                let [r, g, b] = color.map(|c| (c.clamp(0., 1.) * 255.).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect()
+    }
+}
+
+fn parse_triple(text: &str) -> Result<[f32; 3], anyhow::Error> {
+    let mut values = text.split_whitespace().map(str::parse::<f32>);
+    let mut next = || values.next().context("Expected three numbers")?.context("Invalid number");
+    let triple = [next()?, next()?, next()?];
+    anyhow::ensure!(values.next().is_none(), "Expected three numbers");
+    Ok(triple)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cube() {
+        let text = "# a comment\n\
+            TITLE \"identity\"\n\
+            LUT_3D_SIZE 2\n\
+            DOMAIN_MIN 0 0 0\n\
+            DOMAIN_MAX 1.0 1.0 1.0\n\
+            \n\
+            0 0 0\n1 0 0\n0 1 0\n1 1 0\n\
+            0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let lut = Lut::parse(text).unwrap();
+        assert_eq!(lut.title.as_deref(), Some("identity"));
+        assert_eq!(lut, Lut { title: lut.title.clone(), ..Lut::identity(2) });
+        assert_eq!(&lut.texels()[4..8], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn reject_invalid_cubes() {
+        assert!(Lut::parse("0 0 0\n").is_err());
+        assert!(Lut::parse("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+        assert!(Lut::parse("LUT_1D_SIZE 2\n0 0 0\n1 1 1\n").is_err());
+        assert!(Lut::parse("LUT_3D_SIZE 2\nDOMAIN_MAX 2 2 2\n").is_err());
+        assert!(Lut::parse("LUT_3D_SIZE 2\n0 0\n").is_err());
+    }
+}
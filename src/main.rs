@@ -1,59 +1,549 @@
 use shaderpixel::{
-    env_generator::default_env,
-    fs::Carousel,
-    math::{Deg, Matrix4, Vector3, Vector4},
-    vulkan::{Shader, Shaders, ShaderArt, ShaderInner, VkApp},
+    ArtPiece, Camera, GalleryBuilder,
+    gallery::{MAX_FRAMES_IN_FLIGHT, parse_frames_in_flight, parse_swapchain_images},
+    audio::{self, AmbientAudio},
+    bench::{Benchmark, FrameTimes, Stats},
+    capture::{self, LoopFormat, VideoCodec, VideoRecorder},
+    collision::{self, Aabb},
+    discovery::{art_name, discover_art, find_shaders, is_shader, wall_slots},
+    env_generator::{default_colliders, default_env, DEFAULT_WALLS},
+    fs::{self, Carousel, FileWatcher},
+    lightmap::{self, BakeSettings},
+    math::{Deg, Matrix4, Projection, Vector3, Vector4},
+    obj::NormalizedObj,
+    post_effects::{PostEffect, PostEffects},
+    quality::{Quality, QualityMode, TARGET_FPS},
+    remote::{RemoteCommand, RemoteServer, Response},
+    scene_config::{self, ArtChange, ArtConfig},
+    session::Session,
+    json::Json,
+    palette::Palette,
+    slideshow::{Orbit, Slideshow, Viewpoint},
+    vulkan::{
+        Fog, SamplerConfig, Sky, StereoOutput, list_gpus, GpuSelection,
+        TextureSamplers, Validation, VkApp, Volume, OVERLAY_MAX_COLUMNS, OVERLAY_MAX_ERROR_LINES,
+        OVERLAY_MAX_LINES, VALIDATION_ENV, take_printf_messages,
+    },
 };
 
 use anyhow::Context;
-use glslang::ShaderStage;
 use winit::{
     application::ApplicationHandler,
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
+    keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey},
     window::{Fullscreen, Window, WindowId},
 };
 use std::{
-    path::Path,
-    time::Instant,
+    collections::VecDeque,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
 };
 
 const WIDTH: u32 = 800;
 const HEIGHT: u32 = 600;
 const TITLE: &str = "shaderpixel";
+/// Where the window and the shown image are saved on exit, see [`Session`].
+const SESSION_FILE: &str = "shaderpixel_session.json";
 const START_POSITION: Vector3 = Vector3::new_init([0., 1.5, 3.]);
+/// Radius and height of the body of the camera, which can not walk through the podests and
+/// walls of the generated environment. The camera is at the top of its body.
+const CAMERA_RADIUS: f32 = 0.25;
+const CAMERA_HEIGHT: f32 = 1.5;
+/// Distance of the point the split views orbit around in front of the camera.
+const SPLIT_VIEW_DISTANCE: f32 = 3.;
+const PROJECTION: Projection = Projection {
+    fovy: Deg(75.),
+    near: 0.1,
+    far: 200.,
+};
+/// Field of view while the zoom key is held.
+const ZOOM_FOVY: Deg<f32> = Deg(20.);
+/// Hours of the day and night cycle that pass per second, a whole day takes four minutes.
+const DAY_CYCLE_SPEED: f32 = 0.1;
+const START_TIME_OF_DAY: f32 = 12.;
+/// Directories of the cubemaps to cycle through, each with the six faces and an optional
+/// `night` subdirectory with the faces shown at night.
+const CUBEMAPS: &[&str] = &["assets/cubemap"];
+/// The OBJ file of the environment, it is reloaded when it changes.
+/// `None` generates the default environment.
+const ENV_MODEL: Option<&str> = None;
+/// The art of the gallery, changes are applied while it runs, see `scene_config::parse`.
+const SCENE_CONFIG: &str = "assets/scene.txt";
+/// Every fragment shader in this directory is hung on the walls as 2D art.
+const ART_DIR: &str = "assets/shaders/art";
+/// The normal map the environment is bumped with unless another one is given on the command line.
+const NORMAL_MAP: &str = "assets/textures/tiles_normal.png";
+/// Depth of the height map in the alpha channel of the normal map, see `VkApp::set_normal_map`.
+const PARALLAX_SCALE: f32 = 0.04;
+const SAMPLERS: TextureSamplers = TextureSamplers {
+    image: SamplerConfig::REPEAT,
+    // photos sampled by art must not wrap around at their edges
+    art: SamplerConfig::CLAMP,
+    cubemap: SamplerConfig::CLAMP,
+};
+/// Number of frames the CPU can prepare while the GPU is still drawing previous ones unless
+/// another number is given on the command line.
+const FRAMES_IN_FLIGHT: usize = 2;
 const TEXTURE_WEIGHT_CHANGE_SPEED: f32 = 0.5; // change will take 2 secs from 0 to 1
+/// Seconds the benchmark runs for if not given on the command line.
+const BENCH_DURATION: f32 = 30.;
+/// The file the frame times of the benchmark are written to.
+const BENCH_CSV: &str = "bench.csv";
+/// Seconds the shader time moves per second while scrubbing.
+const SCRUB_SPEED: f32 = 1.;
+/// Degrees per second the edited art rotates while an arrow key is held.
+const EDIT_ROTATION_SPEED: f32 = 45.;
+/// The edited art grows or shrinks by a factor of e per second.
+const EDIT_SCALE_SPEED: f32 = 0.5;
+/// The size of the grid the position of the edited art snaps to, `None` disables snapping.
+const EDIT_GRID: Option<f32> = Some(0.25);
+/// The range of the playback speed of the shader time.
+const MIN_TIME_SPEED: f32 = 1. / 16.;
+const MAX_TIME_SPEED: f32 = 16.;
+/// Stills are rendered at this multiple of the window resolution, from 2 to 8.
+const STILL_SCALE: u32 = 4;
+/// Every pixel of a saved still averages this many rendered pixels in each direction.
+const STILL_SUPERSAMPLING: u32 = 2;
+/// Width of saved panoramas, they are half as high.
+const PANORAMA_WIDTH: u32 = 4096;
+/// Frame rate of recorded videos, frames are repeated or dropped to keep it.
+const VIDEO_FPS: u32 = 60;
+/// Size of exported loops of 2D art, they are rendered bigger by `STILL_SUPERSAMPLING`.
+const LOOP_SIZE: u32 = 512;
+/// Approximate frame rate of exported loops, the frames are evenly spaced over the loop.
+const LOOP_FPS: f32 = 25.;
+/// Seconds of exported loops of art without a `loop_period`, which may not loop seamlessly.
+const LOOP_PERIOD: f32 = 4.;
+/// Default seconds the slideshow shows each art piece.
+const SLIDESHOW_INTERVAL: f32 = 20.;
+/// Seconds the slideshow takes to fade to black and back between art pieces.
+const SLIDESHOW_FADE: f32 = 2.;
+/// Distance of the camera from the art in the slideshow, relative to the size of the art.
+const SLIDESHOW_DISTANCE: f32 = 3.;
+/// Default seconds without input after which the camera starts flying through the gallery.
+const ATTRACT_TIMEOUT: f32 = 60.;
+/// The slow circle the camera flies along without input.
+const ATTRACT_ORBIT: Orbit = Orbit {
+    period: 90.,
+    radius: 3.,
+    height: CAMERA_HEIGHT,
+};
+/// The directory with the `.cube` files to grade the gallery with.
+const LUT_DIR: &str = "assets/luts";
+/// The extensions of the images in the carousel.
+const IMAGE_EXTENSIONS: [&str; 8] = ["bmp", "gif", "jpeg", "jpg", "png", "tif", "tiff", "webp"];
+/// How much the strength of the color grading changes with each key press.
+const GRADE_STRENGTH_STEP: f32 = 0.25;
+/// How much the intensity of a post effect changes with each key press.
+const POST_EFFECT_STEP: f32 = 0.1;
+/// Number of frames the statistics in the title bar are computed from.
+const FRAME_HISTORY: usize = 1000;
+/// Time between frames while the window is in the background, so that the gallery keeps moving
+/// without keeping the GPU busy.
+const BACKGROUND_FRAME_TIME: Duration = Duration::from_millis(100);
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Seconds since the Unix epoch to name the saved images after.
+fn unix_secs() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
 
 fn check_if_image(path: &Path) -> bool {
-    path.extension().map(|ext| ext == "jpg" || ext == "png").unwrap_or_default()
+    // phones and cameras often write upper case extensions
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| IMAGE_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+fn check_if_lut(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "cube")
+}
+
+/// The density of the cloud art in its alpha, a flattened ball with a wavy surface.
+fn cloud_density([x, y, z]: [f32; 3]) -> [u8; 4] {
+    let [x, y, z] = [x * 2. - 1., y * 2. - 1., z * 2. - 1.];
+    let radius = (x * x + y * y * 2. + z * z).sqrt();
+    let waves = (x * 9.).sin() * (y * 7. + 1.).sin() * (z * 8. + 2.).sin();
+    let density = ((0.8 - radius) * 2. + waves * 0.3).clamp(0., 1.);
+    [255, 255, 255, (density * 255.) as u8]
+}
+
+/// The art piece of `config` with the procedural volume it names.
+fn art_piece(config: &ArtConfig) -> ArtPiece {
+    let piece = config.art_piece();
+    match config.volume.as_deref() {
+        Some("cloud") => piece.volume(Volume::Procedural {
+            extent: [64, 64, 64],
+            fill: cloud_density,
+        }),
+        Some(name) => {
+            log::warn!("There is no volume {name} for {}", config.name);
+            piece
+        }
+        None => piece,
+    }
+}
+
+/// Changes the art of `app` hung for the scene config `old` to the one of `new`.
+fn apply_scene_config(app: &mut VkApp, old: &[ArtConfig], new: &[ArtConfig]) {
+    for change in scene_config::diff(old, new) {
+        let result = match change {
+            ArtChange::Remove(name) => app.remove_art(name),
+            ArtChange::Add(config) => art_piece(config).add_to(app),
+            ArtChange::Update(config) => {
+                app.set_art_model(&config.name, config.model_matrix());
+                app.set_art_params(&config.name, config.params);
+                Ok(())
+            }
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to apply the scene config: {err:#}");
+        }
+    }
+}
+
+/// Writes the placement of the edited art named `name` back into `SCENE_CONFIG`.
+fn save_placement(scene_config: &mut [ArtConfig], name: &str, model: Matrix4) {
+    let Some(art) = scene_config.iter_mut().find(|art| art.name == name) else {
+        log::info!("{name} is not in {SCENE_CONFIG}, its model matrix is {model:?}");
+        return;
+    };
+    art.set_model_matrix(model);
+    match scene_config::save_placement(SCENE_CONFIG, art) {
+        Ok(()) => log::info!("Saved the placement of {name} to {SCENE_CONFIG}"),
+        Err(err) => log::warn!("Failed to save the placement of {name}: {err:#}"),
+    }
 }
 
 fn main() {
     println!("Usage:");
     println!("Run with RUST_LOG=debug to see logging output");
+    println!("Run with --bench [seconds] to fly a fixed path and write the frame times to {BENCH_CSV}");
+    println!("Run with --slideshow [seconds] to start cycling through the art, e.g. for exhibitions");
+    println!("Run with --attract [seconds] to fly through the gallery after that long without input");
+    println!("Run with --validation off|on|gpu|best-practices|printf or set {VALIDATION_ENV}");
+    println!("    to choose the Vulkan validation, several options can be separated with commas,");
+    println!("    with printf the shaders can print values with debugPrintfEXT into the overlay");
+    println!("Run with --list-gpus to list the GPUs and with --gpu <index|name> to choose one of them");
+    println!("Run with --openxr to show the gallery in a headset too, needs the openxr feature");
+    println!("Run with --video-codec h264|vp9 to choose the codec of recorded videos, needs ffmpeg");
+    println!("Run with --loop-format gif|webp to choose the format of exported loops, needs ffmpeg");
+    println!("Run with --lut <file> to color grade the gallery with a .cube lookup table");
+    println!("Run with --video-texture <file> to play a video the art can sample, needs ffmpeg");
+    println!("Run with --script <file> to move the art with Rhai, needs the rhai feature");
+    println!("    see assets/scripts/motion.rhai");
+    println!("Run with --normal-map <file|none> to bump the environment, default {NORMAL_MAP}");
+    println!("Run with --parallax <scale> to set the depth of its height map, 0 turns it off");
+    println!("Run with --bake-lightmap <file> to bake the light of the environment into a PNG");
+    println!("Run with --lightmap <file> to light the environment with a baked lightmap");
+    println!("Run with --no-occlusion-culling to draw the art hidden behind the environment too");
+    println!("Run with --no-session to neither restore nor save the window and the shown image");
+    println!("Run with --lights <file> to add point lights, see assets/lights/accent.txt");
+    println!("Run with --sounds <file> to play sounds around the art that get louder nearby,");
+    println!("    one per line as \"art name\" file near far, needs ffmpeg and ffplay unless the");
+    println!("    rodio feature is enabled");
+    println!("Run with --reflections <strength> to let the floors reflect the gallery, 0 to 1");
+    println!("Run with --volumetric-fog to light the fog with the sun and the point lights");
+    println!("Run with --dof <aperture> to blur what is out of focus, 0 to 1");
+    println!("Run with --motion-blur <shutter> to blur the motion of the camera, 0 to 1");
+    println!("Run with --post-effects <effect[=intensity],...> to stylize the frame with");
+    println!("    aberration, vignette and grain, the intensities are from 0 to 1");
+    println!("Run with --dynamic-resolution <fps> to lower the resolution when the GPU is slow");
+    println!("Run with --quality auto|0-3 to set the quality level of the art, auto by default");
+    println!("Run with --frames-in-flight <1-{MAX_FRAMES_IN_FLIGHT}> to let the CPU work ahead of");
+    println!("    the GPU by that many frames, {FRAMES_IN_FLIGHT} by default, fewer lower latency");
+    println!("Run with --swapchain-images auto|<count> to request 2 images for double buffering");
+    println!("    with less latency or 3 for triple buffering, within the limits of the display");
+    println!("Run with --remote <[ip:]port> to control the gallery over HTTP, e.g. from a kiosk,");
+    println!("    GET /status, POST /art, /camera, /image and /screenshot with JSON bodies");
+    println!("Put fragment shaders into {ART_DIR} to hang them on the walls as 2D art");
     println!();
     println!("Right-Click: rotate camera with mouse");
+    println!("Middle-Click: focus the depth of field on the clicked point, again on the center");
     println!("Mouse-Wheel: change movement speed");
     println!("WASD: move around");
     println!("Space and Left-Shift: move up and down");
-    println!("Left-Ctrl: enter fly mode");
+    println!("Left-Ctrl: enter fly mode, which also passes through walls");
+    println!("Z: hold to zoom in");
+    println!("Slash: pause or resume the shader time");
+    println!("Comma and Period: hold to scrub the shader time backward and forward");
+    println!("Brackets: halve or double the speed of the shader time");
     println!("Right-Ctrl: hot reload shaders");
+    println!("E: edit the art in front of the camera, WASD, Space and Left-Shift move it,");
+    println!("   Left and Right rotate it, Up and Down scale it, E again saves it to the scene");
+    println!("Q: put the edited 2D art flush against the closest wall");
+    println!("R: switch the handles of the edited art between moving, rotating and scaling,");
+    println!("   drag them with the left mouse button");
     println!("B: toggle skybox");
+    println!("M: switch to the next cubemap");
+    println!("K: switch between the cubemap and the procedural sky");
+    println!("G: toggle fog");
+    println!("N: pause or resume the day and night cycle");
+    println!("H: skip an hour of the day");
+    println!("X: toggle per sample shading of the art to antialias the inside of the shaders");
+    println!("U: switch the quality of the art between auto and the levels from 0 to 3");
+    println!("V: switch between mono, side by side stereo and anaglyph stereo");
+    println!("J: color grade the gallery with the next lookup table in {LUT_DIR}");
+    println!("Minus and Equal: lower or raise the strength of the color grading, zero turns it off");
+    println!("1, 2 and 3: toggle the chromatic aberration, the vignette and the film grain");
+    println!("9 and 0: lower or raise the intensity of the post effect toggled last");
+    println!("Alt and 1 to 0: show or hide the first ten art pieces, e.g. to disable heavy ones");
+    println!("Enter: show the 2D art in front of the camera on the whole screen, Escape returns");
+    println!("P: preview the shader of the next 2D art in a corner of the screen");
+    println!("O: show the entered, looked at or previewed 2D art in a second window, again closes it");
+    println!("C: split the screen into one, two or four views of the point in front of the camera");
+    println!("F: toggle fullscreen, exclusive on Windows if supported");
     println!("R: reset camera and object");
+    println!("Ctrl+P: search every action in the command palette, Enter runs the selected one");
+    println!("F1: show the GPU, the driver, the swapchain and the VRAM usage for bug reports");
+    println!("F2: list the art with the keys that show and hide it");
+    println!("F3: toggle the frame time graph and the GPU times in the title bar");
+    println!("F5: start or stop the slideshow, which shows every art piece for a while");
+    println!("F9: export a {LOOP_SIZE}x{LOOP_SIZE} loop of the entered, looked at or previewed 2D art");
+    println!("F10: start or stop recording a video of the window at {VIDEO_FPS} fps");
+    println!("F11: save a {PANORAMA_WIDTH}x{} panorama around the camera", PANORAMA_WIDTH / 2);
+    println!("F12: save a still at {STILL_SCALE} times the window resolution downsampled by {STILL_SUPERSAMPLING}");
     println!();
 
     env_logger::init();
 
+    let mut bench_duration = None;
+    let mut slideshow_interval = None;
+    let mut attract_timeout = None;
+    let mut validation = Validation::from_env();
+    let mut gpu = GpuSelection::Auto;
+    let mut video_codec = VideoCodec::default();
+    let mut loop_format = LoopFormat::default();
+    let mut lut = None;
+    let mut video_texture = None;
+    let mut script = None;
+    let mut normal_map = Some(PathBuf::from(NORMAL_MAP));
+    let mut parallax_scale = PARALLAX_SCALE;
+    let mut lightmap = None;
+    let mut no_occlusion_culling = false;
+    let mut no_session = false;
+    let mut openxr = false;
+    let mut lights = None;
+    let mut sounds = None;
+    let mut reflections = 0.;
+    let mut volumetric_fog = false;
+    let mut dof_aperture = 0.;
+    let mut shutter = 0.;
+    let mut post_effects = None;
+    let mut target_fps = None;
+    let mut quality_mode = QualityMode::Auto;
+    let mut remote_addr = None;
+    let mut frames_in_flight = FRAMES_IN_FLIGHT;
+    let mut swapchain_images = None;
+    let mut args = std::env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bench" => {
+                let duration = args.next_if(|arg| arg.parse::<f32>().is_ok());
+                bench_duration = Some(duration.map_or(BENCH_DURATION, |arg| arg.parse().unwrap()));
+            }
+            "--slideshow" => {
+                let interval = args.next_if(|arg| arg.parse::<f32>().is_ok());
+                slideshow_interval =
+                    Some(interval.map_or(SLIDESHOW_INTERVAL, |arg| arg.parse().unwrap()));
+            }
+            "--attract" => {
+                let timeout = args.next_if(|arg| arg.parse::<f32>().is_ok());
+                attract_timeout = Some(timeout.map_or(ATTRACT_TIMEOUT, |arg| arg.parse().unwrap()));
+            }
+            "--validation" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => validation = value,
+                Some(Err(err)) => log::warn!("Ignoring --validation: {err}"),
+                None => log::warn!("--validation expects a value"),
+            },
+            "--list-gpus" => {
+                match list_gpus() {
+                    Ok(gpus) => {
+                        for (index, gpu) in gpus.iter().enumerate() {
+                            println!("{index}: {gpu}");
+                        }
+                    }
+                    Err(err) => log::error!("Failed to list the GPUs: {err}"),
+                }
+                return;
+            }
+            "--gpu" => match args.next() {
+                Some(arg) => gpu = arg.parse().unwrap(),
+                None => log::warn!("--gpu expects an index or a name"),
+            },
+            "--video-codec" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => video_codec = value,
+                Some(Err(err)) => log::warn!("Ignoring --video-codec: {err}"),
+                None => log::warn!("--video-codec expects a value"),
+            },
+            "--loop-format" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => loop_format = value,
+                Some(Err(err)) => log::warn!("Ignoring --loop-format: {err}"),
+                None => log::warn!("--loop-format expects a value"),
+            },
+            "--lut" => match args.next() {
+                Some(arg) => lut = Some(PathBuf::from(arg)),
+                None => log::warn!("--lut expects a file"),
+            },
+            "--video-texture" => match args.next() {
+                Some(arg) => video_texture = Some(PathBuf::from(arg)),
+                None => log::warn!("--video-texture expects a file"),
+            },
+            "--script" => match args.next() {
+                Some(arg) => script = Some(PathBuf::from(arg)),
+                None => log::warn!("--script expects a file"),
+            },
+            "--normal-map" => match args.next() {
+                Some(arg) if arg == "none" => normal_map = None,
+                Some(arg) => normal_map = Some(PathBuf::from(arg)),
+                None => log::warn!("--normal-map expects a file or none"),
+            },
+            "--parallax" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => parallax_scale = value,
+                Some(Err(err)) => log::warn!("Ignoring --parallax: {err}"),
+                None => log::warn!("--parallax expects a scale"),
+            },
+            "--bake-lightmap" => {
+                match args.next() {
+                    Some(arg) => {
+                        if let Err(err) = bake_lightmap(Path::new(&arg)) {
+                            log::error!("Failed to bake the lightmap: {err:#}");
+                        }
+                    }
+                    None => log::warn!("--bake-lightmap expects a file"),
+                }
+                return;
+            }
+            "--lightmap" => match args.next() {
+                Some(arg) => lightmap = Some(PathBuf::from(arg)),
+                None => log::warn!("--lightmap expects a file"),
+            },
+            "--no-occlusion-culling" => no_occlusion_culling = true,
+            "--no-session" => no_session = true,
+            "--openxr" => openxr = true,
+            "--sounds" => match args.next() {
+                Some(arg) => sounds = Some(PathBuf::from(arg)),
+                None => log::warn!("--sounds expects a file"),
+            },
+            "--lights" => match args.next() {
+                Some(arg) => lights = Some(PathBuf::from(arg)),
+                None => log::warn!("--lights expects a file"),
+            },
+            "--reflections" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => reflections = value,
+                Some(Err(err)) => log::warn!("Ignoring --reflections: {err}"),
+                None => log::warn!("--reflections expects a strength"),
+            },
+            "--volumetric-fog" => volumetric_fog = true,
+            "--dof" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => dof_aperture = value,
+                Some(Err(err)) => log::warn!("Ignoring --dof: {err}"),
+                None => log::warn!("--dof expects an aperture"),
+            },
+            "--motion-blur" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => shutter = value,
+                Some(Err(err)) => log::warn!("Ignoring --motion-blur: {err}"),
+                None => log::warn!("--motion-blur expects a shutter"),
+            },
+            "--post-effects" => match args.next().map(|arg| PostEffects::parse(&arg)) {
+                Some(Ok(effects)) => post_effects = Some(effects),
+                Some(Err(err)) => log::warn!("Ignoring --post-effects: {err:#}"),
+                None => log::warn!("--post-effects expects a list of effects"),
+            },
+            "--dynamic-resolution" => match args.next().map(|arg| arg.parse()) {
+                Some(Ok(value)) => target_fps = Some(value),
+                Some(Err(err)) => log::warn!("Ignoring --dynamic-resolution: {err}"),
+                None => log::warn!("--dynamic-resolution expects a frame rate"),
+            },
+            "--quality" => match args.next().map(|arg| QualityMode::parse(&arg)) {
+                Some(Ok(mode)) => quality_mode = mode,
+                Some(Err(err)) => log::warn!("Ignoring --quality: {err}"),
+                None => log::warn!("--quality expects auto or a level"),
+            },
+            "--frames-in-flight" => match args.next().map(|arg| parse_frames_in_flight(&arg)) {
+                Some(Ok(count)) => frames_in_flight = count,
+                Some(Err(err)) => log::warn!("Ignoring --frames-in-flight: {err}"),
+                None => log::warn!("--frames-in-flight expects a number"),
+            },
+            "--swapchain-images" => match args.next().map(|arg| parse_swapchain_images(&arg)) {
+                Some(Ok(count)) => swapchain_images = count,
+                Some(Err(err)) => log::warn!("Ignoring --swapchain-images: {err}"),
+                None => log::warn!("--swapchain-images expects auto or a number"),
+            },
+            "--remote" => match args.next() {
+                Some(arg) => remote_addr = Some(arg),
+                None => log::warn!("--remote expects an address or a port"),
+            },
+            _ => log::warn!("Unknown argument: {arg}"),
+        }
+    }
+
+    // the benchmark always runs in a window of the same size
+    let session = (!no_session && bench_duration.is_none()).then(|| {
+        if !Path::new(SESSION_FILE).exists() {
+            return Session::default();
+        }
+        Session::load(SESSION_FILE).unwrap_or_else(|err| {
+            log::warn!("Ignoring the last session: {err:#}");
+            Session::default()
+        })
+    });
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
+    let remote = remote_addr.and_then(|addr| {
+        let proxy = event_loop.create_proxy();
+        let server = RemoteServer::start(&addr, move || {
+            let _ = proxy.send_event(());
+        });
+        server
+            .inspect(|server| log::info!("Remote control listening on http://{}", server.addr()))
+            .inspect_err(|err| log::error!("Failed to start the remote control: {err:#}"))
+            .ok()
+    });
 
     let mut app = App {
         position: START_POSITION,
+        time_of_day: START_TIME_OF_DAY,
+        // the benchmark always sees the same time of day
+        day_cycle_paused: bench_duration.is_some(),
+        bench_duration,
+        slideshow_interval,
+        attract_timeout,
+        validation,
+        frames_in_flight,
+        swapchain_images,
+        gpu,
+        openxr,
+        video_codec,
+        loop_format,
+        lut,
+        video_texture,
+        script,
+        normal_map,
+        parallax_scale,
+        lightmap,
+        no_occlusion_culling,
+        lights,
+        sounds,
+        reflections,
+        volumetric_fog,
+        dof_aperture,
+        shutter,
+        post_effects,
+        target_fps,
+        // the quality holds the frame rate the resolution is scaled for if there is one
+        quality: Quality::new(quality_mode, target_fps.unwrap_or(TARGET_FPS)),
+        remote,
+        session,
+        shader_time_speed: 1.,
+        focused: true,
         ..Default::default()
     };
     app.image_carousel.set_dir("assets/images");
+    app.lut_carousel.set_dir(LUT_DIR);
     event_loop.run_app(&mut app).unwrap();
 }
 
@@ -65,21 +555,207 @@ pub struct KeyStates {
     right: bool,
     up: bool,
     down: bool,
+    zoom: bool,
+    scrub_backward: bool,
+    scrub_forward: bool,
+    rotate_left: bool,
+    rotate_right: bool,
+    grow: bool,
+    shrink: bool,
+}
+
+/// What is shown in the text overlay over the gallery.
+#[derive(Default)]
+struct Overlay {
+    /// Whether the GPU, the swapchain and the VRAM usage are shown.
+    system_info: bool,
+    /// Whether the art is listed with the keys that show and hide it.
+    art_list: bool,
+    /// The last lines the shaders printed with `debugPrintfEXT`, shown at the bottom.
+    console: VecDeque<String>,
+    /// The command palette opened with Ctrl+P, which is shown instead of everything else.
+    palette: Option<Palette<Command>>,
+    /// The compile errors of the shaders, which are shown in red instead of everything
+    /// but the palette.
+    errors: Vec<String>,
+    /// The placement of the edited art, shown at the top, see [`edit_lines`].
+    editing: Vec<String>,
+}
+
+impl Overlay {
+    /// Shows what is toggled on and the last lines printed by the shaders below it,
+    /// or hides the overlay if there is nothing to show.
+    fn update(&self, app: &mut VkApp) {
+        if let Some(palette) = self.palette.as_ref() {
+            app.set_overlay_text(Some(&palette.lines(OVERLAY_MAX_LINES)));
+            return;
+        }
+        if !self.errors.is_empty() {
+            app.set_overlay_errors(Some(&self.errors));
+            return;
+        }
+        let mut lines = self.editing.clone();
+        if self.system_info {
+            lines.extend(app.system_info());
+        }
+        if self.art_list {
+            let art = app.art_visibility();
+            lines.extend(art.iter().enumerate().map(|(index, (name, active))| {
+                let key = art_key(index).map_or(String::new(), |digit| format!("Alt+{digit}"));
+                format!("{key:>5} [{}] {name}", if *active { 'x' } else { ' ' })
+            }));
+        }
+        let console_lines = OVERLAY_MAX_LINES.saturating_sub(lines.len());
+        let console = &self.console;
+        lines.extend(console.iter().skip(console.len().saturating_sub(console_lines)).cloned());
+        app.set_overlay_text(Some(&lines).filter(|lines| !lines.is_empty()).map(Vec::as_slice));
+    }
+}
+
+/// The placement of the edited art and how to change it as lines of the overlay,
+/// empty if no art is edited.
+fn edit_lines(app: &VkApp) -> Vec<String> {
+    let (Some(name), Some(model)) = (app.edited_art(), app.edited_model()) else {
+        return Vec::new();
+    };
+    let (position, rotation, scale) = scene_config::placement(model);
+    vec![
+        format!("Editing {name}"),
+        format!("Position {:.2} {:.2} {:.2}", position[0], position[1], position[2]),
+        format!("Rotation {:.0} {:.0} {:.0}", rotation[0].0, rotation[1].0, rotation[2].0),
+        format!("Scale    {scale:.2}"),
+        format!("Drag the handles to {}, R switches", app.gizmo_mode().name()),
+        "E saves, Q puts 2D art against a wall".to_owned(),
+    ]
+}
+
+/// The compile errors of the shaders as lines of the overlay, long lines are wrapped and
+/// the last line says if there are more than fit.
+fn error_lines(errors: &[(&str, Vec<String>)]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, errors) in errors {
+        lines.push(format!("{name} failed to compile:"));
+        for line in errors.iter().flat_map(|error| error.lines()) {
+            let chars = line.trim_end().chars().collect::<Vec<_>>();
+            lines.extend(chars.chunks(OVERLAY_MAX_COLUMNS).map(|chunk| chunk.iter().collect()));
+        }
+    }
+    if lines.len() > OVERLAY_MAX_ERROR_LINES {
+        lines.truncate(OVERLAY_MAX_ERROR_LINES - 1);
+        lines.push("... the rest is in the log".to_owned());
+    }
+    lines
+}
+
+/// What a command of the palette does.
+#[derive(Debug, Clone)]
+enum Command {
+    /// The same as pressing the key.
+    Key(Key<&'static str>),
+    ReloadShaders,
+    ToggleFlyMode,
+    PauseShaderTime,
+    /// Moves the camera in front of the art.
+    Teleport(Viewpoint),
+}
+
+/// The actions of the palette that are pressed with a single key, named like in the help.
+const KEY_COMMANDS: &[(&str, Key<&str>)] = &[
+    ("Toggle the skybox (B)", Key::Character("b")),
+    ("Switch to the next cubemap (M)", Key::Character("m")),
+    ("Switch between the cubemap and the procedural sky (K)", Key::Character("k")),
+    ("Toggle the fog (G)", Key::Character("g")),
+    ("Pause or resume the day and night cycle (N)", Key::Character("n")),
+    ("Skip an hour of the day (H)", Key::Character("h")),
+    ("Load the next image (I)", Key::Character("i")),
+    ("Blend the texture of the environment in or out (T)", Key::Character("t")),
+    ("Toggle per sample shading (X)", Key::Character("x")),
+    ("Switch the quality of the art (U)", Key::Character("u")),
+    ("Switch between mono and stereo (V)", Key::Character("v")),
+    ("Color grade with the next lookup table (J)", Key::Character("j")),
+    ("Lower the strength of the color grading (Minus)", Key::Character("-")),
+    ("Raise the strength of the color grading (Equal)", Key::Character("=")),
+    ("Toggle the chromatic aberration (1)", Key::Character("1")),
+    ("Toggle the vignette (2)", Key::Character("2")),
+    ("Toggle the film grain (3)", Key::Character("3")),
+    ("Edit the art in front of the camera (E)", Key::Character("e")),
+    ("Put the edited art against the closest wall (Q)", Key::Character("q")),
+    ("Switch the handles of the edited art (R)", Key::Character("r")),
+    ("Enter the 2D art in front of the camera (Enter)", Key::Named(NamedKey::Enter)),
+    ("Preview the next 2D art (P)", Key::Character("p")),
+    ("Show the art in a second window (O)", Key::Character("o")),
+    ("Split the screen into more views (C)", Key::Character("c")),
+    ("Toggle fullscreen (F)", Key::Character("f")),
+    ("Reset the camera and the object (L)", Key::Character("l")),
+    ("Show the system info (F1)", Key::Named(NamedKey::F1)),
+    ("List the art (F2)", Key::Named(NamedKey::F2)),
+    ("Toggle the frame time graph (F3)", Key::Named(NamedKey::F3)),
+    ("Start or stop the slideshow (F5)", Key::Named(NamedKey::F5)),
+    ("Export a loop of the 2D art (F9)", Key::Named(NamedKey::F9)),
+    ("Start or stop recording a video (F10)", Key::Named(NamedKey::F10)),
+    ("Save a panorama (F11)", Key::Named(NamedKey::F11)),
+    ("Save a still (F12)", Key::Named(NamedKey::F12)),
+];
+
+/// The digit that toggles the art at `index` together with Alt, the tenth piece is on 0.
+fn art_key(index: usize) -> Option<usize> {
+    (index < 10).then_some((index + 1) % 10)
+}
+
+/// The index of the art toggled by the digit key `code` together with Alt.
+fn art_index(code: KeyCode) -> Option<usize> {
+    const DIGITS: [KeyCode; 10] = [
+        KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+        KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    ];
+    let digit = DIGITS.iter().position(|&digit| digit == code)?;
+    Some((digit + 9) % 10)
+}
+
+/// The automated flythrough started when there was no input for a while.
+struct Attract {
+    start: f32,
+    /// The camera before the flythrough, which is restored on input.
+    position: Vector3,
+    angle_yaw: Deg<f32>,
+    angle_pitch: Deg<f32>,
 }
 
 #[derive(Default)]
 struct App {
     window: Option<Window>,
+    /// A second window showing a single art piece, see `VkApp::open_projector`.
+    projector_window: Option<Window>,
     vulkan: Option<VkApp>,
 
-    fps: Option<(Instant, u32)>,
+    title: String,
+    /// The last frame times in milliseconds, see `FRAME_HISTORY`.
+    frame_history: VecDeque<f32>,
+    /// The frame statistics shown in the title bar and when they were updated.
+    frame_stats: Option<(Instant, String)>,
+    overlay: Overlay,
     last_frame: Option<Instant>,
+    /// Whether the window has the keyboard focus, otherwise it is drawn every
+    /// `BACKGROUND_FRAME_TIME`.
+    focused: bool,
+    /// Whether the window is hidden, e.g. minimized or covered, nothing is drawn while it is.
+    occluded: bool,
     time: f32, // time passed since app start in seconds
+    /// The time passed to the shaders, which can be paused, scrubbed and sped up.
+    shader_time: f32,
+    shader_time_paused: bool,
+    shader_time_speed: f32,
 
     pressed: KeyStates,
+    modifiers: ModifiersState,
+    /// Whether Ctrl is held without another key pressed since, see the key events.
+    control_tapped: bool,
     load_next_image: bool,
+    load_next_cubemap: bool,
+    cubemap_idx: usize,
     reload_shaders: bool,
     is_right_clicked: bool,
+    is_left_clicked: bool,
     cursor_position: Option<[i32; 2]>,
     cursor_delta: [i32; 2],
     tex_weight_change: f32,
@@ -90,242 +766,916 @@ struct App {
     angle_pitch: Deg<f32>,
     position: Vector3,
     fly_mode: bool,
+    /// The boxes the camera collides with when it is not in fly mode.
+    colliders: Vec<Aabb>,
+    split_views: usize,
+    time_of_day: f32, // in hours
+    day_cycle_paused: bool,
 
     image_carousel: Carousel,
+    /// The lookup table given on the command line, it is taken once the app is created.
+    lut: Option<PathBuf>,
+    lut_carousel: Carousel,
+    /// True once a lookup table from `lut_carousel` is shown, the first press shows the first table.
+    lut_carousel_started: bool,
+    /// The video given on the command line, it is taken once the app is created.
+    video_texture: Option<PathBuf>,
+    /// The script given on the command line, it is taken once the app is created.
+    script: Option<PathBuf>,
+    /// The normal map of the environment, it is taken once the app is created.
+    normal_map: Option<PathBuf>,
+    parallax_scale: f32,
+    /// The lightmap of the environment, it is taken once the app is created.
+    lightmap: Option<PathBuf>,
+    /// Draws all art even if it is hidden, e.g. to compare the frame times with culling.
+    no_occlusion_culling: bool,
+    /// The point lights of the environment, they are taken once the app is created.
+    lights: Option<PathBuf>,
+    /// The sounds around the art, they are taken once the app is created.
+    sounds: Option<PathBuf>,
+    audio: Option<AmbientAudio>,
+    /// How much the floors reflect the gallery.
+    reflections: f32,
+    /// Lights the fog in froxels, which shows shafts of sunlight.
+    volumetric_fog: bool,
+    /// How much what is out of focus is blurred.
+    dof_aperture: f32,
+    /// How much of a frame the motion of the camera is blurred over.
+    shutter: f32,
+    /// The stylistic effects given on the command line, they are taken once the app is created.
+    post_effects: Option<PostEffects>,
+    /// The frame rate the resolution is scaled for, see `VkApp::set_dynamic_resolution`.
+    target_fps: Option<f32>,
+    /// Chooses the quality level of the art from the frame times, see `VkApp::set_quality`.
+    quality: Quality,
+    /// Lets other programs drive the gallery over HTTP if it was requested on the command line.
+    remote: Option<RemoteServer>,
+    /// The session restored on launch and saved on exit unless it is turned off.
+    session: Option<Session>,
+    /// The post effect whose intensity is changed with the keys.
+    post_effect: PostEffect,
+    /// Whether the depth of field is focused on a clicked point instead of the center.
+    is_focus_clicked: bool,
+    /// Watches `ENV_MODEL` if it is set.
+    env_watcher: Option<FileWatcher>,
+    /// The art last loaded from `SCENE_CONFIG` and the watcher of the file.
+    scene_config: Vec<ArtConfig>,
+    scene_watcher: Option<FileWatcher>,
+    /// Watches `ART_DIR` for new shaders.
+    art_watcher: Option<FileWatcher>,
+    /// The shaders of `ART_DIR` that hang in the gallery.
+    discovered_art: Vec<PathBuf>,
+
+    /// Seconds to run the benchmark for if it was requested on the command line.
+    bench_duration: Option<f32>,
+    bench: Option<Benchmark>,
+    /// Seconds the slideshow shows each art piece, it starts with the app if set on the command line.
+    slideshow_interval: Option<f32>,
+    slideshow: Option<Slideshow>,
+    /// Seconds without input after which the camera flies through the gallery if set.
+    attract_timeout: Option<f32>,
+    /// The value of `time` when the last input was received.
+    last_input: f32,
+    attract: Option<Attract>,
+    validation: Validation,
+    frames_in_flight: usize,
+    /// Number of swapchain images to request, `None` for one more than the minimum.
+    swapchain_images: Option<u32>,
+    gpu: GpuSelection,
+    /// Shows the gallery in a headset too if it was requested on the command line.
+    openxr: bool,
+
+    video_codec: VideoCodec,
+    /// Started with the first captured frame while recording, see `App::toggle_recording`.
+    recorder: Option<VideoRecorder>,
+    loop_format: LoopFormat,
+}
+
+fn load_environment() -> Result<NormalizedObj, anyhow::Error> {
+    match ENV_MODEL {
+        Some(path) => Ok(NormalizedObj::from_reader(fs::load(path)?)?),
+        None => Ok(default_env().normalize()?),
+    }
+}
+
+/// Bakes the lightmap of the environment into the PNG at `path`, see [`lightmap::bake`].
+fn bake_lightmap(path: &Path) -> Result<(), anyhow::Error> {
+    let nobj = load_environment()?;
+    let positions = nobj.vertices.iter().map(|vertex| vertex.pos_coords).collect::<Vec<_>>();
+    let settings = BakeSettings::default();
+    log::info!(
+        "Baking the lightmap of {} triangles with {} samples per texel",
+        nobj.indices.len() / 3,
+        settings.samples,
+    );
+    let start = Instant::now();
+    let image = lightmap::bake(&positions, &nobj.indices, &settings);
+    image.save(path).with_context(|| format!("Failed to save {}", path.display()))?;
+    println!(
+        "Baked the {}x{} lightmap {} in {:.1?}",
+        image.width(),
+        image.height(),
+        path.display(),
+        start.elapsed(),
+    );
+    Ok(())
 }
 
 impl App {
     fn init(&mut self, event_loop: &ActiveEventLoop) -> Result<(), anyhow::Error> {
-        let window_attrs = Window::default_attributes()
+        let mut window_attrs = Window::default_attributes()
             .with_title(TITLE)
             .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT));
+        if let Some(session) = self.session.as_ref() {
+            let monitor = session.monitor.as_ref().and_then(|name| {
+                let mut monitors = event_loop.available_monitors();
+                monitors.find(|monitor| monitor.name().as_ref() == Some(name))
+            });
+            if let Some([width, height]) = session.size {
+                window_attrs = window_attrs.with_inner_size(PhysicalSize::new(width, height));
+            }
+            // the window could end up outside of the screens if its monitor is gone
+            if let Some([x, y]) = session.position.filter(|_| monitor.is_some()) {
+                window_attrs = window_attrs.with_position(PhysicalPosition::new(x, y));
+            }
+            if session.fullscreen {
+                window_attrs = window_attrs.with_fullscreen(Some(Fullscreen::Borderless(monitor)));
+                self.is_fullscreen = true;
+            }
+            if let Some(image) = session.image.as_ref() {
+                if !self.image_carousel.select(image, check_if_image) {
+                    log::info!("The image {} of the last session is gone", image.display());
+                }
+            }
+        }
         let window = event_loop.create_window(window_attrs).context("Failed to create window")?;
 
-        let nobj = default_env().normalize()?;
-        //let nobj = NormalizedObj::from_reader(fs::load("assets/models/env.obj")?)?;
+        let nobj = load_environment()?;
+        if let Some(path) = ENV_MODEL {
+            let mut watcher = FileWatcher::new();
+            watcher.watch(path);
+            self.env_watcher = Some(watcher);
+        } else {
+            self.colliders = default_colliders();
+        }
         let image_path = self.image_carousel.get_next(0, check_if_image)
             .context("Failed to find an image")?;
-        let dims = [WIDTH, HEIGHT];
-
-        let vert_shader_art2d: Shader = ShaderInner::new(ShaderStage::Vertex)
-            .path("assets/shaders/art2d.vert").into();
-        let vert_shader_art3d: Shader = ShaderInner::new(ShaderStage::Vertex)
-            .path("assets/shaders/art3d.vert").into();
-        let shaders = Shaders {
-            main_vert: ShaderInner::new(ShaderStage::Vertex)
-                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader.vert.spv")))?.into(),
-            main_frag: ShaderInner::new(ShaderStage::Fragment)
-                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/shader.frag.spv")))?.into(),
-            cube_vert: ShaderInner::new(ShaderStage::Vertex)
-                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/cubemap.vert.spv")))?.into(),
-            cube_frag: ShaderInner::new(ShaderStage::Fragment)
-                .bytes(include_bytes!(concat!(env!("OUT_DIR"), "/cubemap.frag.spv")))?.into(),
-            // draw 2D art before 3D so that it can be seen through transparent stuff
-            shaders_art: vec![
-                ShaderArt {
-                    name: "Mandelbrot".to_owned(),
-                    is_3d: false,
-                    vert: vert_shader_art2d.clone(),
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/mandelbrot.frag").into(),
-                    model_matrix: Matrix4::from_translation([5.99, 1.5, -1.5].into())
-                        * Matrix4::from_scale(0.5)
-                        * Matrix4::from_angle_y(Deg(90.)),
-                },
-                ShaderArt {
-                    name: "Sdf Cat".to_owned(),
-                    is_3d: false,
-                    vert: vert_shader_art2d,
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/cat.frag").into(),
-                    model_matrix: Matrix4::from_translation([5.99, 1.5, -4.5].into())
-                        * Matrix4::from_scale(0.5)
-                        * Matrix4::from_angle_y(Deg(90.)),
-                },
-                ShaderArt {
-                    name: "Mandelbox".to_owned(),
-                    is_3d: true,
-                    vert: vert_shader_art3d.clone(),
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/mandelbox.frag").into(),
-                    model_matrix: Matrix4::from_translation([-2.5, 1.51, -0.5].into())
-                        * Matrix4::from_scale(0.5),
-                },
-                ShaderArt {
-                    name: "Menger Sponge".to_owned(),
-                    is_3d: true,
-                    vert: vert_shader_art3d.clone(),
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/mengersponge.frag").into(),
-                    model_matrix: Matrix4::from_translation([2.5, 1.51, -0.5].into())
-                        * Matrix4::from_scale(0.5),
-                },
-                ShaderArt {
-                    name: "Solar".to_owned(),
-                    is_3d: true,
-                    vert: vert_shader_art3d.clone(),
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/solar.frag").into(),
-                    model_matrix: Matrix4::from_translation([-2.5, 1.51, -5.5].into())
-                        * Matrix4::from_scale(0.5),
-                },
-                ShaderArt {
-                    name: "Mountain".to_owned(),
-                    is_3d: true,
-                    vert: vert_shader_art3d,
-                    frag: ShaderInner::new(ShaderStage::Fragment)
-                        .path("assets/shaders/mountain.frag").into(),
-                    model_matrix: Matrix4::from_translation([2.5, 1.51, -5.5].into())
-                        * Matrix4::from_scale(0.5),
-                },
-            ],
-        };
 
-        let vulkan = VkApp::new(
-            &window,
-            dims,
-            &image_path,
-            nobj,
-            shaders,
-        )?;
+        let scene_config = scene_config::load(SCENE_CONFIG)?;
+        let mut art = scene_config.iter().map(art_piece).collect::<Vec<_>>();
+        self.scene_config = scene_config;
+        let mut scene_watcher = FileWatcher::new();
+        scene_watcher.watch(SCENE_CONFIG);
+        self.scene_watcher = Some(scene_watcher);
+        // the shaders dropped into the art directory hang on the free places of the walls
+        let taken = art.iter().map(ArtPiece::model_matrix).collect::<Vec<_>>();
+        let walls = if ENV_MODEL.is_none() { DEFAULT_WALLS } else { &[] };
+        let discovered = discover_art(Path::new(ART_DIR), walls, &taken);
+        self.discovered_art = find_shaders(Path::new(ART_DIR));
+        art.extend(discovered);
+        let mut art_watcher = FileWatcher::new();
+        art_watcher.watch_dir(ART_DIR);
+        self.art_watcher = Some(art_watcher);
+
+        let mut vulkan = GalleryBuilder::new(image_path)
+            .cubemap(CUBEMAPS[self.cubemap_idx])
+            .environment(nobj)
+            .samplers(SAMPLERS)
+            .frames_in_flight(self.frames_in_flight)
+            .swapchain_images(self.swapchain_images)
+            .validation(self.validation)
+            .gpu(self.gpu.clone())
+            .openxr(self.openxr)
+            .art(art)
+            .build(&window)?;
+        if self.is_fullscreen {
+            vulkan.set_full_screen_exclusive(&window, true);
+        }
 
+        if let Some(duration) = self.bench_duration {
+            if let Err(err) = vulkan.set_gpu_timing(true) {
+                log::warn!("Benchmarking without GPU times: {err}");
+            }
+            let names = vulkan.pipeline_names().into_iter().map(str::to_owned).collect();
+            self.bench = Some(Benchmark::new(duration, names));
+        }
+        if let Some(path) = self.lut.take() {
+            if let Err(err) = vulkan.set_lut(Some(&path)) {
+                log::error!("Failed to load the LUT {}: {err:#}", path.display());
+            }
+        }
+        if let Some(path) = self.video_texture.take() {
+            if let Err(err) = vulkan.set_video(Some(&path)) {
+                log::error!("Failed to play the video {}: {err:#}", path.display());
+            }
+        }
+        if let Some(path) = self.script.take() {
+            if let Err(err) = vulkan.set_script(Some(&path)) {
+                log::error!("Failed to load the script {}: {err:#}", path.display());
+            }
+        }
+        if let Some(path) = self.normal_map.take() {
+            if let Err(err) = vulkan.set_normal_map(Some(&path), self.parallax_scale) {
+                log::error!("Failed to load the normal map {}: {err:#}", path.display());
+            }
+        }
+        if let Some(path) = self.lightmap.take() {
+            if let Err(err) = vulkan.set_lightmap(Some(&path)) {
+                log::error!("Failed to load the lightmap {}: {err:#}", path.display());
+            }
+        }
+        if self.no_occlusion_culling {
+            vulkan.set_occlusion_culling(false);
+        }
+        if let Some(path) = self.lights.take() {
+            if let Err(err) = vulkan.set_lights(Some(&path)) {
+                log::error!("Failed to load the lights {}: {err:#}", path.display());
+            }
+        }
+        if let Some(path) = self.sounds.take() {
+            match audio::load(&path).and_then(AmbientAudio::new) {
+                Ok(audio) => self.audio = Some(audio),
+                Err(err) => log::error!("Failed to play the sounds {}: {err:#}", path.display()),
+            }
+        }
+        if self.reflections > 0. {
+            vulkan.set_reflections(self.reflections);
+        }
+        if self.volumetric_fog {
+            vulkan.set_volumetric_fog(true);
+        }
+        if self.dof_aperture > 0. {
+            vulkan.set_depth_of_field(self.dof_aperture);
+        }
+        if self.shutter > 0. {
+            vulkan.set_motion_blur(self.shutter);
+        }
+        if let Some(effects) = self.post_effects.take() {
+            vulkan.set_post_effects(effects);
+        }
+        vulkan.set_quality(self.quality.level());
+        if let Some(fps) = self.target_fps {
+            match vulkan.set_gpu_timing(true) {
+                Ok(()) => vulkan.set_dynamic_resolution(Some(fps)),
+                Err(err) => log::warn!("Keeping the resolution without GPU times: {err}"),
+            }
+        }
         self.vulkan = Some(vulkan);
         self.window = Some(window);
+        if self.slideshow_interval.is_some() {
+            self.toggle_slideshow();
+        }
         Ok(())
     }
-}
 
-impl ApplicationHandler for App {
-    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-        if let Err(err) = self.init(event_loop) {
-            log::error!("Error while starting: {err}");
-            log::error!("{err:#?}");
-            event_loop.exit();
+    /// Records that there was input and returns control to the user if the camera was flying
+    /// through the gallery.
+    ///
+    /// Returns true if the flythrough was stopped.
+    fn wake_up(&mut self) -> bool {
+        self.last_input = self.time;
+        let Some(attract) = self.attract.take() else {
+            return false;
+        };
+        self.position = attract.position;
+        self.angle_yaw = attract.angle_yaw;
+        self.angle_pitch = attract.angle_pitch;
+        if let Some(vulkan) = self.vulkan.as_mut() {
+            vulkan.set_fade(None);
         }
+        log::info!("Stopped the flythrough");
+        true
     }
 
-    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
-        match event {
-            WindowEvent::CloseRequested
-            | WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state: ElementState::Pressed,
-                        logical_key: Key::Named(NamedKey::Escape),
-                        ..
-                    },
-                ..
-            } => {
-                event_loop.exit();
+    /// Does what pressing the logical `key` does, also for the commands of the palette.
+    fn press_key(&mut self, event_loop: &ActiveEventLoop, key: Key<&str>) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        match key {
+            Key::Character("p") if self.modifiers.control_key() => self.open_palette(),
+            Key::Named(NamedKey::Enter)
+                if vulkan.entered_art().is_none() && !vulkan.enter_looked_at_art() =>
+            {
+                log::info!("There is no 2D art in front of the camera to enter");
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state,
-                        logical_key,
-                        physical_key: PhysicalKey::Code(physical_key_code),
-                        repeat: false,
-                        ..
-                    },
-                ..
-            } => {
-                let pressed = state.is_pressed();
-                match physical_key_code {
-                    KeyCode::KeyW => self.pressed.forward = pressed,
-                    KeyCode::KeyA => self.pressed.left = pressed,
-                    KeyCode::KeyS => self.pressed.backward = pressed,
-                    KeyCode::KeyD => self.pressed.right = pressed,
-                    KeyCode::Space => self.pressed.up = pressed,
-                    KeyCode::ShiftLeft => self.pressed.down = pressed,
-                    KeyCode::ControlRight if pressed => self.reload_shaders = true,
-                    KeyCode::ControlLeft if pressed => self.fly_mode = !self.fly_mode,
-                    _ => {}
-                }
-
-                let Some(vulkan) = self.vulkan.as_mut() else { return };
-                match (logical_key.as_ref(), pressed) {
-                    (Key::Character("b"), true) => {
-                        vulkan.toggle_cubemap();
-                        vulkan.dirty_swapchain = true;
-                    }
-                    (Key::Character("f"), true) => {
-                        let fullscreen = if self.is_fullscreen {
-                            None
-                        } else {
-                            Some(Fullscreen::Borderless(None))
-                        };
-                        self.window.as_mut().unwrap().set_fullscreen(fullscreen);
-                        self.is_fullscreen = !self.is_fullscreen;
-                    }
-                    (Key::Character("i"), true) => {
-                        self.load_next_image = true;
-                        if vulkan.texture_weight == 0. || self.tex_weight_change < 0. {
-                            self.tex_weight_change = TEXTURE_WEIGHT_CHANGE_SPEED;
+            Key::Character("b") => {
+                vulkan.toggle_cubemap();
+                vulkan.dirty_swapchain = true;
+            }
+            Key::Character("m") => self.load_next_cubemap = true,
+            Key::Character("j") => {
+                let offset = if self.lut_carousel_started { 1 } else { 0 };
+                match self.lut_carousel.get_next(offset, check_if_lut) {
+                    Ok(path) => {
+                        self.lut_carousel_started = true;
+                        if let Err(err) = vulkan.set_lut(Some(&path)) {
+                            log::error!("Failed to load the LUT {}: {err:#}", path.display());
                         }
                     }
-                    (Key::Character("l"), true) => {
-                        vulkan.reset_ubo();
-                        self.angle_yaw = Default::default();
-                        self.angle_pitch = Default::default();
-                        self.position = START_POSITION;
-                        self.scroll_lines = 0.0;
+                    Err(err) => log::warn!("Failed to find a LUT in {LUT_DIR}: {err}"),
+                }
+            }
+            Key::Character("-") => {
+                vulkan.set_grade_strength(vulkan.grade_strength() - GRADE_STRENGTH_STEP);
+                log::info!("Color grading strength: {}", vulkan.grade_strength());
+            }
+            Key::Character("=") => {
+                vulkan.set_grade_strength(vulkan.grade_strength() + GRADE_STRENGTH_STEP);
+                log::info!("Color grading strength: {}", vulkan.grade_strength());
+            }
+            Key::Character(digit @ ("1" | "2" | "3")) => {
+                let effect = match digit {
+                    "1" => PostEffect::ChromaticAberration,
+                    "2" => PostEffect::Vignette,
+                    _ => PostEffect::FilmGrain,
+                };
+                let mut effects = vulkan.post_effects();
+                effects.toggle(effect);
+                vulkan.set_post_effects(effects);
+                self.post_effect = effect;
+                let state = if effects.is_enabled(effect) { "on" } else { "off" };
+                log::info!("Post effect {}: {state}", effect.name());
+            }
+            Key::Character(key @ ("9" | "0")) => {
+                let step = if key == "9" { -POST_EFFECT_STEP } else { POST_EFFECT_STEP };
+                let effect = self.post_effect;
+                let mut effects = vulkan.post_effects();
+                effects.set_intensity(effect, effects.intensity(effect) + step);
+                vulkan.set_post_effects(effects);
+                let intensity = effects.intensity(effect);
+                log::info!("Post effect {} intensity: {intensity}", effect.name());
+            }
+            Key::Character("q") => {
+                // the walls are only known for the generated environment
+                let walls = if ENV_MODEL.is_none() { DEFAULT_WALLS } else { &[] };
+                if !vulkan.align_edited_art(walls) {
+                    log::info!("There is no edited 2D art or no wall to align it to");
+                }
+            }
+            Key::Character("r") => {
+                if vulkan.edited_art().is_some() {
+                    let mode = vulkan.gizmo_mode().next();
+                    vulkan.set_gizmo_mode(mode);
+                    log::info!("Gizmo: {}", mode.name());
+                } else {
+                    log::info!("There is no edited art whose handles could be switched");
+                }
+            }
+            Key::Character("e") => {
+                if vulkan.edited_art().is_some() {
+                    if let Some((name, model)) = vulkan.stop_editing() {
+                        save_placement(&mut self.scene_config, &name, model);
                     }
-                    (Key::Character("t"), true) => {
-                        self.tex_weight_change = if self.tex_weight_change == 0. {
-                            TEXTURE_WEIGHT_CHANGE_SPEED
-                        } else {
-                            -self.tex_weight_change
-                        };
+                } else if !vulkan.edit_looked_at_art() {
+                    log::info!("There is no art in front of the camera to edit");
+                }
+            }
+            Key::Named(NamedKey::F3) => {
+                let show = !vulkan.show_frame_graph();
+                vulkan.set_show_frame_graph(show);
+                // the benchmark and the dynamic resolution keep their timer
+                if self.bench.is_none() && vulkan.dynamic_resolution().is_none() {
+                    if let Err(err) = vulkan.set_gpu_timing(show) {
+                        log::warn!("Showing frame times without GPU times: {err}");
                     }
-                    _ => {}
                 }
             }
-            WindowEvent::Resized { .. } => {
-                self.vulkan.as_mut().unwrap().dirty_swapchain = true;
+            Key::Named(NamedKey::F1) => {
+                self.overlay.system_info = !self.overlay.system_info;
+                self.overlay.update(vulkan);
             }
-            WindowEvent::MouseInput { button: MouseButton::Right, state, .. } => {
-                self.is_right_clicked = state == ElementState::Pressed;
+            Key::Named(NamedKey::F2) => {
+                self.overlay.art_list = !self.overlay.art_list;
+                self.overlay.update(vulkan);
             }
-            WindowEvent::CursorMoved { position, .. } => {
-                let new_pos: (i32, i32) = position.into();
-                if self.is_right_clicked {
-                    if let Some(old_pos) = self.cursor_position {
-                        self.cursor_delta[0] += new_pos.0 - old_pos[0];
-                        self.cursor_delta[1] += new_pos.1 - old_pos[1];
-                    }
+            Key::Named(NamedKey::F5) => self.toggle_slideshow(),
+            Key::Named(NamedKey::F9) => self.save_loop(),
+            Key::Named(NamedKey::F10) => self.toggle_recording(),
+            Key::Named(NamedKey::F11) => self.save_panorama(),
+            Key::Named(NamedKey::F12) => {
+                self.save_still();
+            }
+            Key::Character("x") => vulkan.set_sample_shading(!vulkan.sample_shading()),
+            Key::Character("u") => {
+                self.quality.cycle();
+                log::info!("Quality mode: {:?}", self.quality.mode());
+                vulkan.set_quality(self.quality.level());
+            }
+            Key::Character("k") => {
+                vulkan.toggle_procedural_sky();
+                vulkan.dirty_swapchain = true;
+            }
+            Key::Character("g") => {
+                vulkan.scene.fog.density = if vulkan.scene.fog.density > 0. {
+                    0.
+                } else {
+                    Fog::default().density
+                };
+            }
+            Key::Character("n") => {
+                self.day_cycle_paused = !self.day_cycle_paused;
+            }
+            Key::Character("h") => {
+                self.time_of_day = (self.time_of_day + 1.) % 24.;
+            }
+            Key::Character("f") => {
+                let fullscreen = if self.is_fullscreen {
+                    None
+                } else {
+                    Some(Fullscreen::Borderless(None))
+                };
+                let window = self.window.as_ref().unwrap();
+                window.set_fullscreen(fullscreen);
+                self.is_fullscreen = !self.is_fullscreen;
+                vulkan.set_full_screen_exclusive(window, self.is_fullscreen);
+            }
+            Key::Character("i") => {
+                self.load_next_image = true;
+                if vulkan.scene.texture_weight == 0. || self.tex_weight_change < 0. {
+                    self.tex_weight_change = TEXTURE_WEIGHT_CHANGE_SPEED;
                 }
-                self.cursor_position = Some([new_pos.0, new_pos.1]);
             }
-            WindowEvent::MouseWheel {
-                delta: MouseScrollDelta::LineDelta(_, v_lines),
-                ..
-            } => {
-                self.scroll_lines += v_lines;
+            Key::Character("l") => {
+                vulkan.reset_ubo();
+                self.angle_yaw = Default::default();
+                self.angle_pitch = Default::default();
+                self.position = START_POSITION;
+                self.scroll_lines = 0.0;
+            }
+            Key::Character("v") => {
+                let output = match vulkan.stereo_output() {
+                    None => Some(StereoOutput::SideBySide),
+                    Some(StereoOutput::SideBySide) => Some(StereoOutput::Anaglyph),
+                    Some(StereoOutput::Anaglyph) => None,
+                };
+                vulkan.set_stereo_output(output);
+            }
+            Key::Character("p") => {
+                vulkan.cycle_preview_art();
+            }
+            Key::Character("o") => self.toggle_projector(event_loop),
+            Key::Character("c") => {
+                self.split_views = match self.split_views {
+                    0 | 1 => 2,
+                    2 => 4,
+                    _ => 1,
+                };
+            }
+            Key::Character("t") => {
+                self.tex_weight_change = if self.tex_weight_change == 0. {
+                    TEXTURE_WEIGHT_CHANGE_SPEED
+                } else {
+                    -self.tex_weight_change
+                };
             }
             _ => {}
         }
     }
 
-    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
-        if event_loop.exiting() {
+    /// Opens the command palette with every action that has a key and the art to go to.
+    fn open_palette(&mut self) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        let mut commands = KEY_COMMANDS.iter()
+            .map(|(name, key)| (name.to_string(), Command::Key(key.clone())))
+            .chain([
+                ("Reload the shaders (Right-Ctrl)", Command::ReloadShaders),
+                ("Toggle fly mode (Left-Ctrl)", Command::ToggleFlyMode),
+                ("Pause or resume the shader time (Slash)", Command::PauseShaderTime),
+            ].map(|(name, command)| (name.to_owned(), command)))
+            .collect::<Vec<_>>();
+        commands.extend(vulkan.art_viewpoints(SLIDESHOW_DISTANCE).into_iter().map(|viewpoint| {
+            (format!("Go to {}", viewpoint.name), Command::Teleport(viewpoint))
+        }));
+        self.overlay.palette = Some(Palette::new(commands));
+        self.overlay.update(vulkan);
+    }
+
+    /// Types `text` into the open command palette, Enter runs the selected command
+    /// and Escape closes the palette.
+    fn palette_key(&mut self, event_loop: &ActiveEventLoop, key: Key<&str>, text: Option<&str>) {
+        let Some(palette) = self.overlay.palette.as_mut() else { return };
+        match key {
+            Key::Named(NamedKey::Escape) => self.overlay.palette = None,
+            Key::Named(NamedKey::Enter) => {
+                let command = palette.selected().cloned();
+                self.overlay.palette = None;
+                if let Some(command) = command {
+                    self.run_command(event_loop, command);
+                }
+            }
+            Key::Named(NamedKey::ArrowUp) => palette.move_selection(-1),
+            Key::Named(NamedKey::ArrowDown) => palette.move_selection(1),
+            Key::Named(NamedKey::Backspace) => palette.pop(),
+            _ => palette.push_str(text.unwrap_or_default()),
+        }
+        if let Some(vulkan) = self.vulkan.as_mut() {
+            self.overlay.update(vulkan);
+        }
+    }
+
+    fn run_command(&mut self, event_loop: &ActiveEventLoop, command: Command) {
+        match command {
+            Command::Key(key) => self.press_key(event_loop, key),
+            Command::ReloadShaders => self.reload_shaders = true,
+            Command::ToggleFlyMode => self.fly_mode = !self.fly_mode,
+            Command::PauseShaderTime => self.toggle_shader_time(),
+            Command::Teleport(viewpoint) => self.go_to(viewpoint),
+        }
+    }
+
+    /// Moves the camera to `viewpoint` in the gallery.
+    fn go_to(&mut self, viewpoint: Viewpoint) {
+        let vulkan = self.vulkan.as_mut();
+        if let Some(vulkan) = vulkan.filter(|vulkan| vulkan.entered_art().is_some()) {
+            vulkan.exit_art();
+        }
+        self.position = viewpoint.position;
+        self.angle_yaw = viewpoint.yaw;
+        self.angle_pitch = Deg(0.);
+        log::info!("Moved to {}", viewpoint.name);
+    }
+
+    /// Saves the window and the shown image to `SESSION_FILE` unless the session is turned off.
+    fn save_session(&mut self) {
+        let (Some(session), Some(window)) = (self.session.as_mut(), self.window.as_ref()) else {
             return;
+        };
+        // fullscreen keeps the size and the position to return to
+        let size = window.inner_size();
+        if !self.is_fullscreen && size.width > 0 && size.height > 0 {
+            session.size = Some([size.width, size.height]);
+            let position = window.outer_position().ok();
+            session.position = position.map(|position| [position.x, position.y]);
         }
+        session.monitor = window.current_monitor().and_then(|monitor| monitor.name());
+        session.fullscreen = self.is_fullscreen;
+        session.image = self.image_carousel.get_next(0, check_if_image).ok();
+        if let Err(err) = session.save(SESSION_FILE) {
+            log::warn!("Failed to save the session: {err:#}");
+        }
+    }
 
-        if let Some((start, count)) = self.fps.as_mut() {
-            let time = start.elapsed();
-            *count += 1;
-            if time.as_millis() > 1000 {
-                use std::io::Write;
+    /// Answers the requests the remote control received since the last frame.
+    fn handle_remote_requests(&mut self) {
+        let Some(remote) = self.remote.as_ref() else { return };
+        let requests = remote.requests().collect::<Vec<_>>();
+        for request in requests {
+            let response = self.run_remote_command(&request.command);
+            request.reply(response);
+        }
+    }
 
-                eprint!("fps: {}        \r", *count as f32 / time.as_secs_f32());
-                std::io::stdout().flush().unwrap();
-                *start = Instant::now();
-                *count = 0;
+    fn run_remote_command(&mut self, command: &RemoteCommand) -> Response {
+        if self.vulkan.is_none() {
+            return Response::error(503, "the gallery is not running yet");
+        }
+        // the remote control takes over the camera like a visitor
+        if *command != RemoteCommand::Status {
+            self.wake_up();
+            if self.slideshow.is_some() {
+                self.toggle_slideshow();
+            }
+        }
+        let vulkan = self.vulkan.as_mut().unwrap();
+        match command {
+            RemoteCommand::Status => {
+                let number = |value: f32| Json::Number(value.into());
+                let string = |value: &str| Json::String(value.to_owned());
+                let art = vulkan.art_viewpoints(SLIDESHOW_DISTANCE)
+                    .into_iter()
+                    .map(|viewpoint| Json::String(viewpoint.name))
+                    .collect();
+                let position = [self.position.x(), self.position.y(), self.position.z()];
+                Response::ok(Json::Object(vec![
+                    ("position".to_owned(), Json::Array(position.map(number).into())),
+                    ("yaw".to_owned(), number(self.angle_yaw.0)),
+                    ("pitch".to_owned(), number(self.angle_pitch.0)),
+                    ("art".to_owned(), Json::Array(art)),
+                    ("entered_art".to_owned(), vulkan.entered_art().map_or(Json::Null, string)),
+                ]))
+            }
+            RemoteCommand::GoToArt(name) => {
+                let viewpoint = vulkan.art_viewpoints(SLIDESHOW_DISTANCE)
+                    .into_iter()
+                    .find(|viewpoint| viewpoint.name.eq_ignore_ascii_case(name));
+                match viewpoint {
+                    Some(viewpoint) => {
+                        self.go_to(viewpoint);
+                        Response::ok(Json::Object(Vec::new()))
+                    }
+                    None => Response::error(404, &format!("there is no art named {name}")),
+                }
             }
+            RemoteCommand::SetCamera { position, yaw, pitch } => {
+                self.position = position.unwrap_or(self.position);
+                self.angle_yaw = yaw.unwrap_or(self.angle_yaw);
+                self.angle_pitch = pitch.unwrap_or(self.angle_pitch);
+                Response::ok(Json::Object(Vec::new()))
+            }
+            RemoteCommand::LoadImage(path) => {
+                if let Some(path) = path {
+                    if let Err(err) = vulkan.load_new_texture(path) {
+                        return Response::error(400, &format!("{err:#}"));
+                    }
+                } else {
+                    self.load_next_image = true;
+                }
+                // the image is only seen if the texture is blended in
+                if vulkan.scene.texture_weight == 0. || self.tex_weight_change < 0. {
+                    self.tex_weight_change = TEXTURE_WEIGHT_CHANGE_SPEED;
+                }
+                Response::ok(Json::Object(Vec::new()))
+            }
+            RemoteCommand::Screenshot => match self.save_still() {
+                Some(path) => Response::ok(Json::Object(vec![
+                    ("path".to_owned(), Json::String(path)),
+                ])),
+                None => Response::error(500, "failed to save the still, see the log"),
+            },
+        }
+    }
+
+    fn toggle_shader_time(&mut self) {
+        self.shader_time_paused = !self.shader_time_paused;
+        log::info!("Shader time paused: {}", self.shader_time_paused);
+    }
+
+    /// Starts or stops moving the camera from one art piece to the next with fades in between.
+    fn toggle_slideshow(&mut self) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        if self.slideshow.take().is_some() {
+            vulkan.set_fade(None);
+            log::info!("Stopped the slideshow");
+            return;
+        }
+        let interval = self.slideshow_interval.unwrap_or(SLIDESHOW_INTERVAL);
+        let viewpoints = vulkan.art_viewpoints(SLIDESHOW_DISTANCE);
+        self.slideshow = Slideshow::new(viewpoints, interval, SLIDESHOW_FADE, self.time);
+        match self.slideshow {
+            Some(_) => {
+                // the art is seen in the gallery
+                if vulkan.entered_art().is_some() {
+                    vulkan.exit_art();
+                }
+                log::info!("Started the slideshow, showing each art for {interval}s");
+            }
+            None => log::info!("There is no art for a slideshow"),
+        }
+    }
+
+    /// Opens a second window showing the selected art or closes it if it is open.
+    fn toggle_projector(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        if self.projector_window.is_some() {
+            // the surface has to be destroyed before its window
+            vulkan.close_projector();
+            self.projector_window = None;
+            return;
+        }
+        let window_attrs = Window::default_attributes()
+            .with_title(format!("{TITLE} - projector"))
+            .with_inner_size(PhysicalSize::new(WIDTH, HEIGHT));
+        let result = event_loop.create_window(window_attrs)
+            .context("Failed to create window")
+            .and_then(|window| {
+                let size = window.inner_size();
+                vulkan.open_projector(&window, [size.width, size.height])?;
+                Ok(window)
+            });
+        match result {
+            Ok(window) => {
+                if let Some(name) = vulkan.projector_art() {
+                    window.set_title(&format!("{TITLE} - {name}"));
+                }
+                self.projector_window = Some(window);
+            }
+            Err(err) => log::warn!("Failed to open a second window: {err}"),
+        }
+    }
+
+    /// Handles the events of the second window, it is only drawn together with the main window.
+    fn projector_event(&mut self, event: WindowEvent) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        match event {
+            WindowEvent::CloseRequested => {
+                vulkan.close_projector();
+                self.projector_window = None;
+            }
+            WindowEvent::Resized(size) => vulkan.resize_projector([size.width, size.height]),
+            _ => {}
+        }
+    }
+
+    /// Writes the frame times of the benchmark to `BENCH_CSV` and prints their statistics.
+    fn finish_benchmark(&mut self) {
+        let Some(bench) = self.bench.take() else { return };
+        let result = std::fs::File::create(BENCH_CSV)
+            .and_then(|file| bench.write_csv(std::io::BufWriter::new(file)));
+        match result {
+            Ok(()) => println!("Wrote frame times to {BENCH_CSV}"),
+            Err(err) => log::error!("Failed to write {BENCH_CSV}: {err}"),
+        }
+        print!("{}", bench.summary());
+    }
+
+    /// Renders the current view at `STILL_SCALE` times the window resolution and saves it
+    /// downsampled by `STILL_SUPERSAMPLING` as a PNG named after the current time.
+    /// Returns the path of the PNG if it was saved.
+    fn save_still(&mut self) -> Option<String> {
+        let (Some(vulkan), Some(window)) = (self.vulkan.as_mut(), self.window.as_ref()) else {
+            return None;
+        };
+        let size = window.inner_size();
+        let dimensions = [size.width * STILL_SCALE, size.height * STILL_SCALE];
+        let start = Instant::now();
+        let still = match vulkan.render_still(self.shader_time, dimensions) {
+            Ok(still) => still,
+            Err(err) => {
+                log::warn!("Failed to render a still: {err}");
+                return None;
+            }
+        };
+        let still = image::imageops::thumbnail(
+            &still,
+            dimensions[0] / STILL_SUPERSAMPLING,
+            dimensions[1] / STILL_SUPERSAMPLING,
+        );
+        let path = format!("still_{}.png", unix_secs());
+        let saved = match still.save(&path) {
+            Ok(()) => {
+                log::info!(
+                    "Saved a still of {}x{} to {path} in {:.1}s",
+                    still.width(),
+                    still.height(),
+                    start.elapsed().as_secs_f32(),
+                );
+                Some(path)
+            }
+            Err(err) => {
+                log::error!("Failed to write {path}: {err}");
+                None
+            }
+        };
+        // the rendering took a while, which should not move the camera
+        self.last_frame = Some(Instant::now());
+        saved
+    }
+
+    /// Renders the gallery around the camera into the faces of a cube and saves them stitched
+    /// into an equirectangular panorama of `PANORAMA_WIDTH` named after the current time.
+    fn save_panorama(&mut self) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        let start = Instant::now();
+        // a face spans a quarter of the panorama, render it bigger to supersample it
+        let face_size = PANORAMA_WIDTH / 4 * STILL_SUPERSAMPLING;
+        let faces = match vulkan.render_cube_faces(self.shader_time, self.position, face_size) {
+            Ok(faces) => faces,
+            Err(err) => {
+                log::warn!("Failed to render a panorama: {err}");
+                return;
+            }
+        };
+        let panorama = capture::equirectangular(&faces, PANORAMA_WIDTH);
+        let path = format!("panorama_{}.png", unix_secs());
+        match panorama.save(&path) {
+            Ok(()) => log::info!(
+                "Saved a panorama to {path} in {:.1}s",
+                start.elapsed().as_secs_f32(),
+            ),
+            Err(err) => log::error!("Failed to write {path}: {err}"),
+        }
+        self.last_frame = Some(Instant::now());
+    }
+
+    /// Renders one loop of the animation of the entered, looked at or previewed 2D art
+    /// and saves it as an animation of `loop_format` named after the art and the current time.
+    fn save_loop(&mut self) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        let start = Instant::now();
+        let size = LOOP_SIZE * STILL_SUPERSAMPLING;
+        let art_loop = match vulkan.render_art_loop(
+            self.shader_time,
+            [size, size],
+            LOOP_FPS,
+            LOOP_PERIOD,
+        ) {
+            Ok(art_loop) => art_loop,
+            Err(err) => {
+                log::warn!("Failed to render a loop: {err}");
+                return;
+            }
+        };
+        let art_loop = capture::ArtLoop {
+            frames: art_loop.frames.iter()
+                .map(|frame| image::imageops::thumbnail(frame, LOOP_SIZE, LOOP_SIZE))
+                .collect(),
+            ..art_loop
+        };
+        let name = art_loop.name.to_lowercase().replace(' ', "_");
+        let path = format!("loop_{name}_{}.{}", unix_secs(), self.loop_format.extension());
+        match art_loop.save(&path, self.loop_format) {
+            Ok(()) => log::info!(
+                "Saved a loop of {} frames over {:.2}s to {path} in {:.1}s",
+                art_loop.frames.len(),
+                art_loop.period,
+                start.elapsed().as_secs_f32(),
+            ),
+            Err(err) => log::error!("Failed to write {path}: {err}"),
+        }
+        self.last_frame = Some(Instant::now());
+    }
+
+    /// Starts or stops copying the frames to an ffmpeg process that encodes them into a video
+    /// named after the current time.
+    fn toggle_recording(&mut self) {
+        let Some(vulkan) = self.vulkan.as_mut() else { return };
+        if vulkan.is_capturing_frames() {
+            if let Err(err) = vulkan.set_frame_capture(false) {
+                log::error!("Failed to stop capturing frames: {err}");
+            }
+            Self::finish_video(self.recorder.take());
+        } else if let Err(err) = vulkan.set_frame_capture(true) {
+            log::warn!("Failed to record a video: {err}");
         } else {
-            self.fps = Some((Instant::now(), 0));
+            log::info!("Started recording");
+        }
+    }
+
+    /// Passes a captured frame drawn at `time` to the recorder, which is started with the first
+    /// frame and started again with a new file if the window is resized.
+    fn record_frame(
+        recorder: &mut Option<VideoRecorder>,
+        codec: VideoCodec,
+        time: f32,
+        frame: image::RgbaImage,
+    ) -> Result<(), anyhow::Error> {
+        let (width, height) = frame.dimensions();
+        let dimensions = [width, height];
+        if recorder.as_ref().is_some_and(|recorder| recorder.dimensions() != dimensions) {
+            Self::finish_video(recorder.take());
+        }
+        let recorder = match recorder {
+            Some(recorder) => recorder,
+            None => {
+                let path = format!("video_{}.{}", unix_secs(), codec.extension());
+                log::info!("Recording {width}x{height} to {path}");
+                recorder.insert(VideoRecorder::new(path, codec, dimensions, VIDEO_FPS)?)
+            }
+        };
+        recorder.push(time, frame)
+    }
+
+    fn finish_video(recorder: Option<VideoRecorder>) {
+        if let Some(recorder) = recorder {
+            match recorder.finish() {
+                Ok(()) => log::info!("Finished recording"),
+                Err(err) => log::error!("Failed to finish the video: {err}"),
+            }
         }
+    }
+
+    /// The cursor position from 0 to 1 from the top left of the window.
+    fn window_cursor_position(&self) -> Option<[f32; 2]> {
+        let [x, y] = self.cursor_position?;
+        let size = self.window.as_ref()?.inner_size();
+        Some([x as f32 / size.width as f32, y as f32 / size.height as f32])
+    }
+
+    /// The cursor position in pixels from the bottom left of the window like Shadertoy.
+    fn art_cursor_position(&self) -> Option<[f32; 2]> {
+        let [x, y] = self.cursor_position?;
+        let height = self.window.as_ref()?.inner_size().height as i32;
+        Some([x as f32, (height - y) as f32])
+    }
+
+    fn change_shader_time_speed(&mut self, factor: f32) {
+        self.shader_time_speed =
+            (self.shader_time_speed * factor).clamp(MIN_TIME_SPEED, MAX_TIME_SPEED);
+        log::info!("Shader time speed: {}", self.shader_time_speed);
+    }
+
+    /// The fps, the 1% low fps and the GPU times of the last frame with its slowest pipeline.
+    fn frame_stats(frame_history: &VecDeque<f32>, app: &VkApp) -> String {
+        let Some(stats) = Stats::new(frame_history.iter().copied()) else {
+            return String::new();
+        };
+        let mut text = format!("{:.0} fps, 1% low {:.0} fps", 1000. / stats.mean, 1000. / stats.p99);
+        if let Some(gpu) = app.gpu_times() {
+            text += &format!(", gpu {:.2}ms", gpu.frame);
+            let slowest = gpu.pipelines.iter()
+                .zip(app.pipeline_names())
+                .filter_map(|(time, name)| Some(((*time)?, name)))
+                .max_by(|(a, _), (b, _)| a.total_cmp(b));
+            if let Some((time, name)) = slowest {
+                text += &format!(", slowest {name} {time:.2}ms");
+            }
+        }
+        text
+    }
 
+    /// Updates the camera and draws a frame, done on every redraw request so that
+    /// the window keeps being drawn while it is resized.
+    fn redraw(&mut self) {
+        let frame_start = Instant::now();
         let app = self.vulkan.as_mut().unwrap();
         let window = self.window.as_ref().unwrap();
 
@@ -342,14 +1692,27 @@ impl ApplicationHandler for App {
         let delta = elapsed.as_secs_f32() * (self.scroll_lines * 0.4).exp();
         self.last_frame = Some(Instant::now());
         self.time += elapsed.as_secs_f32();
+        if !self.shader_time_paused {
+            self.shader_time += elapsed.as_secs_f32() * self.shader_time_speed;
+        }
+        let scrub = (self.pressed.scrub_forward as i8 - self.pressed.scrub_backward as i8) as f32;
+        self.shader_time = (self.shader_time + scrub * elapsed.as_secs_f32() * SCRUB_SPEED).max(0.);
+        if !self.day_cycle_paused {
+            self.time_of_day = (self.time_of_day + elapsed.as_secs_f32() * DAY_CYCLE_SPEED) % 24.;
+        }
+        app.scene.sky = Sky::at_time_of_day(self.time_of_day);
 
         let extent = window.inner_size();
         let x_ratio = self.cursor_delta[0] as f32 / extent.width as f32;
         let y_ratio = self.cursor_delta[1] as f32 / extent.height as f32;
 
+        let fovy = if self.pressed.zoom { ZOOM_FOVY } else { PROJECTION.fovy };
+
         if self.is_right_clicked {
-            self.angle_yaw += Deg(x_ratio * 180.);
-            self.angle_pitch += Deg(y_ratio * 180.);
+            // rotate slower while zoomed in to keep aiming precise
+            let sensitivity = 180. * fovy.0 / PROJECTION.fovy.0;
+            self.angle_yaw += Deg(x_ratio * sensitivity);
+            self.angle_pitch += Deg(y_ratio * sensitivity);
         }
         self.cursor_delta = [0, 0];
 
@@ -364,11 +1727,69 @@ impl ApplicationHandler for App {
         } else {
             Matrix4::from_angle_y(-self.angle_yaw)
         };
-        self.position += (-translation * rot).resize();
+        let movement: Vector3 = (-translation * rot).resize();
+        if app.edited_art().is_some() {
+            let seconds = elapsed.as_secs_f32();
+            let rotation = (self.pressed.rotate_left as i8 - self.pressed.rotate_right as i8) as f32;
+            let scaling = (self.pressed.grow as i8 - self.pressed.shrink as i8) as f32;
+            let angle = Deg(rotation * EDIT_ROTATION_SPEED * seconds);
+            let scale = (scaling * EDIT_SCALE_SPEED * seconds).exp();
+            app.transform_edited_art(movement, angle, scale, EDIT_GRID);
+        } else if self.fly_mode {
+            self.position += movement;
+        } else {
+            let target = self.position + movement;
+            self.position =
+                collision::slide(&self.colliders, self.position, target, CAMERA_RADIUS, CAMERA_HEIGHT);
+        }
+        if self.bench.is_some() {
+            (self.position, self.angle_yaw) = Benchmark::camera(self.time);
+            self.angle_pitch = Deg(0.);
+        }
+        let idle = self.time - self.last_input;
+        let unattended = self.bench.is_some() || self.slideshow.is_some();
+        if self.attract.is_none() && !unattended && self.attract_timeout.is_some_and(|t| idle >= t) {
+            if app.entered_art().is_some() {
+                app.exit_art();
+            }
+            self.attract = Some(Attract {
+                start: self.time,
+                position: self.position,
+                angle_yaw: self.angle_yaw,
+                angle_pitch: self.angle_pitch,
+            });
+            log::info!("Started the flythrough after {idle:.0}s without input");
+        }
+        if let Some(attract) = self.attract.as_ref() {
+            let time = self.time - attract.start;
+            (self.position, self.angle_yaw) = ATTRACT_ORBIT.camera(time);
+            self.angle_pitch = Deg(0.);
+            // cut to black and fade into the flythrough
+            let fade = 1. - time / (SLIDESHOW_FADE / 2.);
+            app.set_fade((fade > 0.).then_some(fade));
+        }
+        if let Some(slideshow) = self.slideshow.as_ref() {
+            let (viewpoint, fade) = slideshow.at(self.time);
+            self.position = viewpoint.position;
+            self.angle_yaw = viewpoint.yaw;
+            self.angle_pitch = Deg(0.);
+            app.set_fade(Some(fade));
+        }
 
-        app.view_matrix = Matrix4::from_angle_x(self.angle_pitch)
-            * Matrix4::from_angle_y(self.angle_yaw)
-            * Matrix4::from_translation(-self.position);
+        let mut camera = Camera::new(self.position, self.angle_yaw, self.angle_pitch);
+        camera.projection = Projection { fovy, ..PROJECTION };
+        camera.apply(&mut app.scene);
+        if let Some(audio) = self.audio.as_ref() {
+            audio.update(self.position, camera.right(), |name| app.art_position(name));
+        }
+        // the additional views orbit around a point in front of the camera
+        let pivot = self.position + camera.forward() * SPLIT_VIEW_DISTANCE;
+        app.scene.split_view_matrices = (1..self.split_views.max(1)).map(|i| {
+            app.scene.view_matrix
+                * Matrix4::from_translation(pivot)
+                * Matrix4::from_angle_y(Deg(360. / self.split_views as f32 * i as f32))
+                * Matrix4::from_translation(-pivot)
+        }).collect();
 
         if self.load_next_image {
             match self.image_carousel.get_next(1, check_if_image) {
@@ -382,19 +1803,397 @@ impl ApplicationHandler for App {
             };
             self.load_next_image = false;
         }
+        if self.load_next_cubemap {
+            self.cubemap_idx = (self.cubemap_idx + 1) % CUBEMAPS.len();
+            if let Err(err) = app.load_cubemap(CUBEMAPS[self.cubemap_idx]) {
+                log::warn!("Error while loading new cubemap: {err}");
+                log::warn!("{err:#?}");
+            }
+            self.load_next_cubemap = false;
+        }
         if self.reload_shaders {
             app.reload_shaders();
             self.reload_shaders = false;
         }
+        if self.env_watcher.as_ref().is_some_and(|watcher| !watcher.changed().is_empty()) {
+            match load_environment() {
+                Ok(nobj) => app.set_environment(nobj),
+                Err(err) => log::warn!("Error while reloading environment: {err}"),
+            }
+        }
+        if self.scene_watcher.as_ref().is_some_and(|watcher| !watcher.changed().is_empty()) {
+            match scene_config::load(SCENE_CONFIG) {
+                Ok(scene_config) => {
+                    apply_scene_config(app, &self.scene_config, &scene_config);
+                    self.scene_config = scene_config;
+                }
+                Err(err) => log::warn!("Error while reloading scene config: {err:#}"),
+            }
+        }
+        if let Some(watcher) = self.art_watcher.as_ref() {
+            for path in watcher.changed() {
+                if is_shader(&path) && !self.discovered_art.contains(&path) {
+                    let name = art_name(&path);
+                    // the new art hangs on the first free place of the walls
+                    let taken = app.art_models().into_iter()
+                        .map(|(_, model)| model)
+                        .collect::<Vec<_>>();
+                    let walls = if ENV_MODEL.is_none() { DEFAULT_WALLS } else { &[] };
+                    match wall_slots(walls, &taken).first() {
+                        Some(&model) => {
+                            log::info!("Hanging the new art {name} from {}", path.display());
+                            let piece = ArtPiece::new_2d(name, path.clone()).at(model);
+                            if let Err(err) = piece.add_to(app) {
+                                log::warn!("Failed to add art: {err:#}");
+                            }
+                        }
+                        None => {
+                            log::warn!("Found the new art {name} but no free place on the walls");
+                        }
+                    }
+                    self.discovered_art.push(path);
+                }
+            }
+        }
 
-        app.texture_weight = (app.texture_weight + self.tex_weight_change * delta).clamp(0., 1.);
+        let texture_weight = app.scene.texture_weight + self.tex_weight_change * delta;
+        app.scene.texture_weight = texture_weight.clamp(0., 1.);
 
-        app.dirty_swapchain = app.draw_frame(self.time);
+        // the first frame has no previous frame to measure from
+        if !elapsed.is_zero() {
+            let frame_ms = elapsed.as_secs_f32() * 1000.;
+            if self.frame_history.len() == FRAME_HISTORY {
+                self.frame_history.pop_front();
+            }
+            self.frame_history.push_back(frame_ms);
+            app.frame_graph.push(frame_ms, app.gpu_times().map(|gpu| gpu.frame));
+            // the GPU time is not capped by the refresh rate, frames in the background are
+            let quality_time = app.gpu_times().map_or(frame_ms, |gpu| gpu.frame);
+            if self.focused && self.quality.push(quality_time) {
+                app.set_quality(self.quality.level());
+            }
+        }
+        let update_stats = self.frame_stats.as_ref()
+            .is_none_or(|(updated, _)| updated.elapsed() >= STATS_INTERVAL);
+        if update_stats {
+            self.frame_stats = Some((Instant::now(), Self::frame_stats(&self.frame_history, app)));
+            // the VRAM usage changes while art is loaded
+            if self.overlay.system_info {
+                self.overlay.update(app);
+            }
+        }
+        let printed = take_printf_messages();
+        if !printed.is_empty() {
+            for line in printed.iter().flat_map(|message| message.lines()) {
+                if self.overlay.console.len() == OVERLAY_MAX_LINES {
+                    self.overlay.console.pop_front();
+                }
+                self.overlay.console.push_back(line.to_owned());
+            }
+            self.overlay.update(app);
+        }
+
+        // show the placement of the edited art while it changes
+        let editing = edit_lines(app);
+        if editing != self.overlay.editing {
+            self.overlay.editing = editing;
+            self.overlay.update(app);
+        }
+        // show the whole shader errors in the overlay
+        let errors = error_lines(&app.shader_errors());
+        if errors != self.overlay.errors {
+            self.overlay.errors = errors;
+            self.overlay.update(app);
+        }
+        // show the first line of the first shader error in the title bar, otherwise the statistics
+        let title = match (app.shader_errors().first(), self.frame_stats.as_ref()) {
+            (Some((name, errors)), _) => {
+                let error = errors.first().and_then(|error| error.lines().next()).unwrap_or_default();
+                format!("{TITLE} - {name} failed to compile: {error}")
+            }
+            (None, Some((_, stats))) if !stats.is_empty() => format!("{TITLE} - {stats}"),
+            (None, _) => TITLE.to_owned(),
+        };
+        let title = if self.shader_time_paused {
+            format!("{title} - paused at {:.2}s", self.shader_time)
+        } else if self.shader_time_speed != 1. {
+            format!("{title} - speed x{}", self.shader_time_speed)
+        } else {
+            title
+        };
+        let title = match app.edited_art() {
+            Some(name) => format!("{title} - editing {name}"),
+            None => title,
+        };
+        if title != self.title {
+            window.set_title(&title);
+            self.title = title;
+        }
+
+        app.dirty_swapchain = app.draw_frame(self.shader_time);
+        if let Some(frame) = app.take_captured_frame() {
+            let result = Self::record_frame(&mut self.recorder, self.video_codec, self.time, frame);
+            if let Err(err) = result {
+                log::error!("Stopped recording: {err}");
+                self.recorder = None;
+                if let Err(err) = app.set_frame_capture(false) {
+                    log::error!("Failed to stop capturing frames: {err}");
+                }
+            }
+        }
+
+        if let Some(bench) = self.bench.as_mut() {
+            bench.record(FrameTimes {
+                time: self.time,
+                frame: elapsed.as_secs_f32() * 1000.,
+                cpu: frame_start.elapsed().as_secs_f32() * 1000.,
+                gpu: app.gpu_times().cloned(),
+            });
+        }
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if let Err(err) = self.init(event_loop) {
+            log::error!("Error while starting: {err}");
+            log::error!("{err:#?}");
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        if self.projector_window.as_ref().is_some_and(|window| window.id() == id) {
+            self.projector_event(event);
+            return;
+        }
+        let is_input = matches!(
+            event,
+            WindowEvent::KeyboardInput { .. }
+                | WindowEvent::MouseInput { .. }
+                | WindowEvent::CursorMoved { .. }
+                | WindowEvent::MouseWheel { .. }
+        );
+        let is_key_press = matches!(
+            event,
+            WindowEvent::KeyboardInput {
+                event: KeyEvent { state: ElementState::Pressed, .. },
+                ..
+            }
+        );
+        // the key only ends the flythrough, so that e.g. Escape does not quit
+        if is_input && self.wake_up() && is_key_press {
+            return;
+        }
+        // the open palette takes the typed keys, the releases still stop moving
+        if let WindowEvent::KeyboardInput { event: KeyEvent { logical_key, text, .. }, .. } = &event
+            && is_key_press
+            && self.overlay.palette.is_some()
+        {
+            self.palette_key(event_loop, logical_key.as_ref(), text.as_deref());
+            return;
+        }
+        match event {
+            WindowEvent::CloseRequested => {
+                event_loop.exit();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state: ElementState::Pressed,
+                        logical_key: Key::Named(NamedKey::Escape),
+                        ..
+                    },
+                ..
+            } => {
+                match self.vulkan.as_mut() {
+                    Some(vulkan) if vulkan.entered_art().is_some() => vulkan.exit_art(),
+                    _ => event_loop.exit(),
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        logical_key,
+                        physical_key: PhysicalKey::Code(physical_key_code),
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = state.is_pressed();
+                match physical_key_code {
+                    KeyCode::KeyW => self.pressed.forward = pressed,
+                    KeyCode::KeyA => self.pressed.left = pressed,
+                    KeyCode::KeyS => self.pressed.backward = pressed,
+                    KeyCode::KeyD => self.pressed.right = pressed,
+                    KeyCode::Space => self.pressed.up = pressed,
+                    KeyCode::ShiftLeft => self.pressed.down = pressed,
+                    KeyCode::KeyZ => self.pressed.zoom = pressed,
+                    KeyCode::Comma => self.pressed.scrub_backward = pressed,
+                    KeyCode::Period => self.pressed.scrub_forward = pressed,
+                    KeyCode::ArrowLeft => self.pressed.rotate_left = pressed,
+                    KeyCode::ArrowRight => self.pressed.rotate_right = pressed,
+                    KeyCode::ArrowUp => self.pressed.grow = pressed,
+                    KeyCode::ArrowDown => self.pressed.shrink = pressed,
+                    KeyCode::Slash if pressed => self.toggle_shader_time(),
+                    KeyCode::BracketLeft if pressed => self.change_shader_time_speed(0.5),
+                    KeyCode::BracketRight if pressed => self.change_shader_time_speed(2.),
+                    // Ctrl acts when it is released without another key, so that Ctrl+P
+                    // only opens the palette
+                    KeyCode::ControlLeft | KeyCode::ControlRight if pressed => {
+                        self.control_tapped = true;
+                    }
+                    KeyCode::ControlRight if self.control_tapped => self.reload_shaders = true,
+                    KeyCode::ControlLeft if self.control_tapped => self.fly_mode = !self.fly_mode,
+                    _ => {}
+                }
+                let is_control =
+                    matches!(physical_key_code, KeyCode::ControlLeft | KeyCode::ControlRight);
+                if pressed && !is_control {
+                    self.control_tapped = false;
+                }
+
+                let Some(vulkan) = self.vulkan.as_mut() else { return };
+                vulkan.scene.keyboard.set(physical_key_code, pressed);
+                // the physical key, as Alt changes the character of a digit on some layouts
+                let art = art_index(physical_key_code).filter(|_| self.modifiers.alt_key());
+                if let Some(index) = art.filter(|_| pressed) {
+                    match vulkan.toggle_art(index) {
+                        Some((name, true)) => log::info!("Showing {name}"),
+                        Some((name, false)) => log::info!("Hiding {name}"),
+                        None => log::info!("There is no art number {}", index + 1),
+                    }
+                    self.overlay.update(vulkan);
+                }
+                if art.is_some() {
+                    return;
+                }
+                if pressed {
+                    self.press_key(event_loop, logical_key.as_ref());
+                }
+            }
+            // the releases of keys held while the window is in the background are missed
+            WindowEvent::Focused(false) => {
+                self.focused = false;
+                if let Some(vulkan) = self.vulkan.as_mut() {
+                    vulkan.scene.keyboard.release_all();
+                }
+            }
+            WindowEvent::Focused(true) => self.focused = true,
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::Occluded(occluded) => self.occluded = occluded,
+            WindowEvent::Resized { .. } => {
+                self.vulkan.as_mut().unwrap().dirty_swapchain = true;
+                self.window.as_ref().unwrap().request_redraw();
+            }
+            WindowEvent::RedrawRequested => {
+                self.redraw();
+                if self.bench.as_ref().is_some_and(|bench| bench.is_done(self.time)) {
+                    self.finish_benchmark();
+                    event_loop.exit();
+                }
+            }
+            WindowEvent::MouseInput { button: MouseButton::Right, state, .. } => {
+                self.is_right_clicked = state == ElementState::Pressed;
+            }
+            WindowEvent::MouseInput {
+                button: MouseButton::Middle,
+                state: ElementState::Pressed,
+                ..
+            } => {
+                let point = self.window_cursor_position().filter(|_| !self.is_focus_clicked);
+                self.is_focus_clicked = point.is_some();
+                self.vulkan.as_mut().unwrap().set_focus_point(point);
+            }
+            WindowEvent::MouseInput { button: MouseButton::Left, state, .. } => {
+                self.is_left_clicked = state == ElementState::Pressed;
+                let cursor_position = self.art_cursor_position();
+                let window_cursor_position = self.window_cursor_position();
+                let vulkan = self.vulkan.as_mut().unwrap();
+                // the handles of the gizmo are grabbed while the button is held
+                if let (true, Some(cursor)) = (self.is_left_clicked, window_cursor_position) {
+                    vulkan.grab_gizmo(cursor);
+                } else {
+                    vulkan.release_gizmo();
+                }
+                match cursor_position {
+                    Some([x, y]) if self.is_left_clicked => {
+                        vulkan.scene.art_mouse = Vector4::from([x, y, x, y]);
+                    }
+                    // like Shadertoy the sign of zw tells if the button is held
+                    _ => {
+                        vulkan.scene.art_mouse[2] = -vulkan.scene.art_mouse[2].abs();
+                        vulkan.scene.art_mouse[3] = -vulkan.scene.art_mouse[3].abs();
+                    }
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                let new_pos: (i32, i32) = position.into();
+                if self.is_right_clicked {
+                    if let Some(old_pos) = self.cursor_position {
+                        self.cursor_delta[0] += new_pos.0 - old_pos[0];
+                        self.cursor_delta[1] += new_pos.1 - old_pos[1];
+                    }
+                }
+                self.cursor_position = Some([new_pos.0, new_pos.1]);
+                if let Some(cursor) = self.window_cursor_position() {
+                    self.vulkan.as_mut().unwrap().hover_gizmo(cursor);
+                }
+                if let (true, Some([x, y])) = (self.is_left_clicked, self.art_cursor_position()) {
+                    let vulkan = self.vulkan.as_mut().unwrap();
+                    vulkan.scene.art_mouse[0] = x;
+                    vulkan.scene.art_mouse[1] = y;
+                }
+            }
+            WindowEvent::MouseWheel {
+                delta: MouseScrollDelta::LineDelta(_, v_lines),
+                ..
+            } => {
+                self.scroll_lines += v_lines;
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if event_loop.exiting() {
+            return;
+        }
+        self.handle_remote_requests();
+
+        let window = self.window.as_ref().unwrap();
+        // videos, benchmarks and the projector on another screen need every frame
+        let busy = self.bench.is_some()
+            || self.projector_window.is_some()
+            || self.vulkan.as_ref().is_some_and(VkApp::is_capturing_frames);
+        let size = window.inner_size();
+        if !busy && (self.occluded || size.width == 0 || size.height == 0) {
+            // nothing is drawn until the window is shown again, which sends an event,
+            // and the time does not jump forward then
+            self.last_frame = None;
+            event_loop.set_control_flow(ControlFlow::Wait);
+        } else if !busy && !self.focused {
+            let next_frame = self.last_frame
+                .map_or_else(Instant::now, |last| last + BACKGROUND_FRAME_TIME);
+            event_loop.set_control_flow(ControlFlow::WaitUntil(next_frame));
+            if Instant::now() >= next_frame {
+                window.request_redraw();
+            }
+        } else {
+            event_loop.set_control_flow(ControlFlow::Poll);
+            window.request_redraw();
+        }
     }
 
     fn exiting(&mut self, _: &ActiveEventLoop) {
-        if let Some(vulkan) = self.vulkan.as_ref() {
+        self.save_session();
+        if let Some(vulkan) = self.vulkan.as_mut() {
             vulkan.wait_gpu_idle();
+            vulkan.close_projector();
         }
+        self.projector_window = None;
+        Self::finish_video(self.recorder.take());
     }
 }
@@ -12,6 +12,34 @@ pub type Matrix2 = matrix::Matrix<f32, 2>;
 pub type Matrix3 = matrix::Matrix<f32, 3>;
 pub type Matrix4 = matrix::Matrix<f32, 4>;
 
+/// Parameters of a perspective projection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Projection {
+    /// Vertical field of view.
+    pub fovy: Deg<f32>,
+    /// Distance of the near clip plane.
+    pub near: f32,
+    /// Distance of the far clip plane.
+    pub far: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self {
+            fovy: Deg(75.),
+            near: 0.1,
+            far: 200.,
+        }
+    }
+}
+
+impl Projection {
+    /// The perspective matrix for a viewport with the given aspect ratio, see [`perspective`].
+    pub fn matrix(&self, aspect: f32) -> Matrix4 {
+        perspective(self.fovy, aspect, self.near, self.far)
+    }
+}
+
 /// Perspective matrix that is suitable for Vulkan.
 ///
 /// It inverts the projected y-axis and sets the depth range to 0..1
@@ -28,3 +56,20 @@ where
         Vector4::from([0., 0., -(far * near) / (far - near), 0.]),
     ])
 }
+
+/// Orthographic matrix that is suitable for Vulkan like [`perspective`].
+///
+/// `near` and `far` are the distances of the clip planes along the negative z-axis.
+pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4 {
+    Matrix4::from([
+        Vector4::from([2. / (right - left), 0., 0., 0.]),
+        Vector4::from([0., -2. / (top - bottom), 0., 0.]),
+        Vector4::from([0., 0., -1. / (far - near), 0.]),
+        Vector4::from([
+            -(right + left) / (right - left),
+            (top + bottom) / (top - bottom),
+            -near / (far - near),
+            1.,
+        ]),
+    ])
+}
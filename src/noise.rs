@@ -0,0 +1,287 @@
+//! Tileable noise textures every art piece can sample.
+//!
+//! The 2D texture at binding 5 holds value noise in red, Perlin noise in green,
+//! fractal Perlin noise in blue and blue noise in alpha.
+//! The 3D texture at binding 6 holds value noise in red, Perlin noise in green,
+//! fractal Perlin noise in blue and white noise in alpha.
+
+/// Width and height of the 2D noise texture.
+pub const SIZE_2D: u32 = 256;
+/// Width, height and depth of the 3D noise texture.
+pub const SIZE_3D: u32 = 64;
+/// Number of lattice cells along each side of the textures for value and Perlin noise.
+const CELLS_2D: u32 = 16;
+const CELLS_3D: u32 = 8;
+/// Octaves of the fractal noise, each one with double the frequency and half the amplitude.
+const OCTAVES: u32 = 4;
+/// Size of the blue noise tile, the 2D texture repeats it.
+const BLUE_NOISE_SIZE: u32 = 64;
+/// Standard deviation of the Gaussian used to find clusters and voids in the blue noise.
+const BLUE_NOISE_SIGMA: f32 = 1.5;
+
+/// Returns the RGBA texels of the 2D noise texture row by row.
+pub fn texels_2d() -> Vec<u8> {
+    let blue = blue_noise(BLUE_NOISE_SIZE);
+    let mut texels = Vec::with_capacity((SIZE_2D * SIZE_2D * 4) as usize);
+    for y in 0..SIZE_2D {
+        for x in 0..SIZE_2D {
+            let p = [x, y].map(|c| (c as f32 + 0.5) / SIZE_2D as f32 * CELLS_2D as f32);
+            let blue = blue[((y % BLUE_NOISE_SIZE) * BLUE_NOISE_SIZE + x % BLUE_NOISE_SIZE) as usize];
+            texels.extend([
+                to_byte(value_2d(p, CELLS_2D)),
+                to_byte(perlin_2d(p, CELLS_2D)),
+                to_byte(fractal(|octave| {
+                    perlin_2d(p.map(|c| c * octave as f32), CELLS_2D * octave)
+                })),
+                blue,
+            ]);
+        }
+    }
+    texels
+}
+
+/// Returns the RGBA texels of the 3D noise texture with x changing fastest, then y, then z.
+pub fn texels_3d() -> Vec<u8> {
+    let mut texels = Vec::with_capacity((SIZE_3D * SIZE_3D * SIZE_3D * 4) as usize);
+    for z in 0..SIZE_3D {
+        for y in 0..SIZE_3D {
+            for x in 0..SIZE_3D {
+                let p = [x, y, z].map(|c| (c as f32 + 0.5) / SIZE_3D as f32 * CELLS_3D as f32);
+                texels.extend([
+                    to_byte(value_3d(p, CELLS_3D)),
+                    to_byte(perlin_3d(p, CELLS_3D)),
+                    to_byte(fractal(|octave| {
+                        perlin_3d(p.map(|c| c * octave as f32), CELLS_3D * octave)
+                    })),
+                    hash([x, y, z, 3]) as u8,
+                ]);
+            }
+        }
+    }
+    texels
+}
+
+/// Value noise in [0, 1] that repeats every `period` cells.
+pub fn value_2d([x, y]: [f32; 2], period: u32) -> f32 {
+    let ([x0, y0], [x1, y1], [fx, fy]) = lattice(x, y, period);
+    let corner = |x, y| hash([x, y, 0, 0]) as f32 / u32::MAX as f32;
+    let [sx, sy] = [fade(fx), fade(fy)];
+    lerp(
+        lerp(corner(x0, y0), corner(x1, y0), sx),
+        lerp(corner(x0, y1), corner(x1, y1), sx),
+        sy,
+    )
+}
+
+/// Value noise in [0, 1] that repeats every `period` cells.
+pub fn value_3d([x, y, z]: [f32; 3], period: u32) -> f32 {
+    let ([x0, y0], [x1, y1], [fx, fy]) = lattice(x, y, period);
+    let (z0, z1, fz) = lattice_1d(z, period);
+    let corner = |x, y, z| hash([x, y, z, 1]) as f32 / u32::MAX as f32;
+    let [sx, sy, sz] = [fade(fx), fade(fy), fade(fz)];
+    let layer = |z| {
+        lerp(
+            lerp(corner(x0, y0, z), corner(x1, y0, z), sx),
+            lerp(corner(x0, y1, z), corner(x1, y1, z), sx),
+            sy,
+        )
+    };
+    lerp(layer(z0), layer(z1), sz)
+}
+
+/// Perlin noise mapped to [0, 1] that repeats every `period` cells.
+pub fn perlin_2d([x, y]: [f32; 2], period: u32) -> f32 {
+    let ([x0, y0], [x1, y1], [fx, fy]) = lattice(x, y, period);
+    let corner = |x, y, dx: f32, dy: f32| {
+        let angle = hash([x, y, 0, 2]) as f32 / u32::MAX as f32 * std::f32::consts::TAU;
+        angle.cos() * dx + angle.sin() * dy
+    };
+    let [sx, sy] = [fade(fx), fade(fy)];
+    let noise = lerp(
+        lerp(corner(x0, y0, fx, fy), corner(x1, y0, fx - 1., fy), sx),
+        lerp(corner(x0, y1, fx, fy - 1.), corner(x1, y1, fx - 1., fy - 1.), sx),
+        sy,
+    );
+    // the range of 2D Perlin noise with unit gradients is ±sqrt(1/2)
+    noise * std::f32::consts::FRAC_1_SQRT_2 + 0.5
+}
+
+/// Perlin noise mapped to [0, 1] that repeats every `period` cells.
+pub fn perlin_3d([x, y, z]: [f32; 3], period: u32) -> f32 {
+    // the gradients point to the edges of a cube
+    const GRADIENTS: [[f32; 3]; 12] = [
+        [1., 1., 0.], [-1., 1., 0.], [1., -1., 0.], [-1., -1., 0.],
+        [1., 0., 1.], [-1., 0., 1.], [1., 0., -1.], [-1., 0., -1.],
+        [0., 1., 1.], [0., -1., 1.], [0., 1., -1.], [0., -1., -1.],
+    ];
+    let ([x0, y0], [x1, y1], [fx, fy]) = lattice(x, y, period);
+    let (z0, z1, fz) = lattice_1d(z, period);
+    let corner = |x, y, z, d: [f32; 3]| {
+        let [gx, gy, gz] = GRADIENTS[hash([x, y, z, 4]) as usize % GRADIENTS.len()];
+        gx * d[0] + gy * d[1] + gz * d[2]
+    };
+    let [sx, sy, sz] = [fade(fx), fade(fy), fade(fz)];
+    let layer = |z, dz| {
+        lerp(
+            lerp(corner(x0, y0, z, [fx, fy, dz]), corner(x1, y0, z, [fx - 1., fy, dz]), sx),
+            lerp(
+                corner(x0, y1, z, [fx, fy - 1., dz]),
+                corner(x1, y1, z, [fx - 1., fy - 1., dz]),
+                sx,
+            ),
+            sy,
+        )
+    };
+    (lerp(layer(z0, fz), layer(z1, fz - 1.), sz) * 0.5 + 0.5).clamp(0., 1.)
+}
+
+/// Ranks the texels of a `size`² tile so that texels with similar ranks are far apart,
+/// using the void and cluster method of Ulichney.
+///
+/// Returns the ranks scaled to bytes row by row.
+pub fn blue_noise(size: u32) -> Vec<u8> {
+    let n = (size * size) as usize;
+    // the Gaussian between two texels only depends on their offset on the torus
+    let kernel = (0..n)
+        .map(|i| {
+            let [dx, dy] = [i as u32 % size, i as u32 / size].map(|d| d.min(size - d) as f32);
+            (-(dx * dx + dy * dy) / (2. * BLUE_NOISE_SIGMA * BLUE_NOISE_SIGMA)).exp()
+        })
+        .collect::<Vec<_>>();
+    let toggle = |pattern: &mut [bool], energy: &mut [f32], i: usize| {
+        pattern[i] = !pattern[i];
+        let sign = if pattern[i] { 1. } else { -1. };
+        let [x, y] = [i as u32 % size, i as u32 / size];
+        for (j, energy) in energy.iter_mut().enumerate() {
+            let [dx, dy] = [(j as u32 % size + size - x) % size, (j as u32 / size + size - y) % size];
+            *energy += sign * kernel[(dy * size + dx) as usize];
+        }
+    };
+    // the densest texel that is set or the emptiest that is not
+    let find = |pattern: &[bool], energy: &[f32], set: bool| {
+        let candidates = energy.iter().enumerate().filter(|(i, _)| pattern[*i] == set);
+        if set {
+            candidates.max_by(|a, b| a.1.total_cmp(b.1)).unwrap().0
+        } else {
+            candidates.min_by(|a, b| a.1.total_cmp(b.1)).unwrap().0
+        }
+    };
+
+    // start with a tenth of the texels set at random
+    let mut pattern = vec![false; n];
+    let mut energy = vec![0.; n];
+    let initial = (n / 10).max(1);
+    let mut set = 0;
+    for i in (0..n).map(|i| hash([i as u32, 0, 0, 5]) as usize % n) {
+        if set == initial {
+            break;
+        }
+        if !pattern[i] {
+            toggle(&mut pattern, &mut energy, i);
+            set += 1;
+        }
+    }
+    // move texels from clusters to voids until that no longer changes anything
+    for _ in 0..n {
+        let cluster = find(&pattern, &energy, true);
+        toggle(&mut pattern, &mut energy, cluster);
+        let void = find(&pattern, &energy, false);
+        toggle(&mut pattern, &mut energy, void);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0; n];
+    // the initial texels get the lowest ranks by removing them from the densest clusters
+    let (mut removed, mut removed_energy) = (pattern.clone(), energy.clone());
+    for rank in (0..initial).rev() {
+        let cluster = find(&removed, &removed_energy, true);
+        toggle(&mut removed, &mut removed_energy, cluster);
+        ranks[cluster] = rank;
+    }
+    // the others are ranked by filling the largest voids
+    for rank in initial..n {
+        let void = find(&pattern, &energy, false);
+        toggle(&mut pattern, &mut energy, void);
+        ranks[void] = rank;
+    }
+    ranks.into_iter().map(|rank| (rank * 256 / n) as u8).collect()
+}
+
+/// Sums the octaves of the noise returned by `octave` for the frequencies 1, 2, 4, ...
+fn fractal(octave: impl Fn(u32) -> f32) -> f32 {
+    let (sum, total) = (0..OCTAVES).fold((0., 0.), |(sum, total), i| {
+        let amplitude = 0.5f32.powi(i as i32);
+        (sum + (octave(1 << i) - 0.5) * amplitude, total + amplitude)
+    });
+    sum / total + 0.5
+}
+
+/// The lattice points around `(x, y)` wrapped to `period` and the position between them.
+fn lattice(x: f32, y: f32, period: u32) -> ([u32; 2], [u32; 2], [f32; 2]) {
+    let (x0, x1, fx) = lattice_1d(x, period);
+    let (y0, y1, fy) = lattice_1d(y, period);
+    ([x0, y0], [x1, y1], [fx, fy])
+}
+
+fn lattice_1d(x: f32, period: u32) -> (u32, u32, f32) {
+    let floor = x.floor();
+    let x0 = (floor as i64).rem_euclid(period as i64) as u32;
+    (x0, (x0 + 1) % period, x - floor)
+}
+
+/// The quintic curve of improved Perlin noise.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn to_byte(value: f32) -> u8 {
+    (value.clamp(0., 1.) * 255.).round() as u8
+}
+
+/// Hashes a lattice point, the last component selects an independent noise.
+fn hash(p: [u32; 4]) -> u32 {
+    // the integer hash of Chris Wellons' hash prospector, applied once per component
+    p.iter().fold(0x9e37_79b9, |h: u32, &c| {
+        let mut h = (h ^ c).wrapping_add(0x7f4a_7c15);
+        h ^= h >> 16;
+        h = h.wrapping_mul(0x7feb_352d);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x846c_a68b);
+        h ^ (h >> 16)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn noise_tiles() {
+        let period = 4;
+        for p in [[0.3, 1.7], [2.5, 3.9], [0., 0.]] {
+            let shifted = [p[0] + period as f32, p[1] - period as f32];
+            assert!((value_2d(p, period) - value_2d(shifted, period)).abs() < 1e-5);
+            assert!((perlin_2d(p, period) - perlin_2d(shifted, period)).abs() < 1e-5);
+            let p = [p[0], p[1], 0.6];
+            let shifted = [p[0], p[1] + period as f32, p[2] + period as f32];
+            assert!((value_3d(p, period) - value_3d(shifted, period)).abs() < 1e-5);
+            assert!((perlin_3d(p, period) - perlin_3d(shifted, period)).abs() < 1e-5);
+        }
+        // Perlin noise is zero at the lattice points
+        assert_eq!(perlin_2d([1., 2.], period), 0.5);
+        assert_eq!(perlin_3d([1., 2., 3.], period), 0.5);
+    }
+
+    #[test]
+    fn blue_noise_ranks_every_texel_once() {
+        let mut noise = blue_noise(16);
+        noise.sort_unstable();
+        assert_eq!(noise, (0..=255).collect::<Vec<u8>>());
+    }
+}
@@ -9,7 +9,10 @@ use std::str;
 pub struct Obj {
     pub vertices: Vec<[f32; 3]>,
     pub tex_coords: Vec<[f32; 2]>,
-    pub faces: Vec<([Indices; 3], Option<Indices>)>,
+    /// Only counted, so that negative normal indices can be checked.
+    pub normal_count: usize,
+    /// The corners of each polygon, at least 3.
+    pub faces: Vec<Vec<Indices>>,
 }
 
 impl Obj {
@@ -33,14 +36,19 @@ impl Obj {
             .filter(|part| !part.is_empty());
         let Some(iden) = parts.next() else { return Ok(()) };
         match iden {
-            b"f" => self.faces.push((
-                [
-                    Self::parse_part::<_, 3>(0, parts.next())?,
-                    Self::parse_part::<_, 3>(1, parts.next())?,
-                    Self::parse_part::<_, 3>(2, parts.next())?,
-                ],
-                parts.next().map(|part| Self::parse_part::<_, 3>(3, Some(part))).transpose()?,
-            )),
+            b"f" => {
+                let counts = [self.vertices.len(), self.tex_coords.len(), self.normal_count];
+                let face = parts.by_ref()
+                    .take_while(|part| part[0] != b'#')
+                    .map(|part| Indices::parse(part, counts))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if face.len() < 3 {
+                    return Err(ObjError::NotEnoughNums(face.len() as u32, 3));
+                }
+                self.faces.push(face);
+                // the rest of the line is a comment if there is one
+                return Ok(());
+            }
             b"v" => self.vertices.push([
                 Self::parse_part::<_, 3>(0, parts.next())?,
                 Self::parse_part::<_, 3>(1, parts.next())?,
@@ -50,8 +58,12 @@ impl Obj {
                 Self::parse_part::<_, 2>(0, parts.next())?,
                 Self::parse_part::<_, 2>(1, parts.next())?,
             ]),
+            b"vn" => {
+                self.normal_count += 1;
+                return Ok(());
+            }
             // not implemented
-            b"g" | b"o" | b"s" | b"vn" | b"mtllib" | b"usemtl" => return Ok(()),
+            b"g" | b"o" | b"s" | b"mtllib" | b"usemtl" => return Ok(()),
             other => {
                 return Err(ObjError::InvalidIden(String::from_utf8_lossy(other).into_owned()));
             }
@@ -90,17 +102,15 @@ impl Obj {
                 Ok(vert_idx)
             }
 
-            let indices: Vec<_> = if let Some(v4) = face.1 {
-                let v = face.0;
-                [v[0], v[1], v[2], v[2], v4, v[0]]
-                    .map(|x| map_indices(x, self, &mut nobj, &mut map))
-                    .into_iter().collect::<Result<_, _>>()?
-            } else {
-                face.0
-                    .map(|x| map_indices(x, self, &mut nobj, &mut map))
-                    .into_iter().collect::<Result<_, _>>()?
-            };
-            nobj.indices.extend(indices);
+            let corners = face.iter()
+                .map(|&x| map_indices(x, self, &mut nobj, &mut map))
+                .collect::<Result<Vec<_>, _>>()?;
+            let points = corners.iter()
+                .map(|&corner| nobj.vertices[corner as usize].pos_coords)
+                .collect::<Vec<_>>();
+            for triangle in triangulate(&points) {
+                nobj.indices.extend(triangle.map(|corner| corners[corner]));
+            }
         }
         Ok(nobj)
     }
@@ -119,6 +129,69 @@ impl Obj {
     }
 }
 
+/// Splits the polygon with the corners `points` into triangles by clipping ears, which also
+/// works for concave polygons like the n-gons Blender exports. Returns the corners of the
+/// triangles in the winding order of the polygon.
+///
+/// The ears are searched from the second corner on and the polygon continues after the clipped
+/// ear, so a quad is split into `[0, 1, 2]` and `[2, 3, 0]` like before n-gons were supported,
+/// which keeps baked lightmaps matching.
+fn triangulate(points: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    // Newell's method, which works for polygons that are not quite planar
+    let mut normal = [0.; 3];
+    for (i, &[x0, y0, z0]) in points.iter().enumerate() {
+        let [x1, y1, z1] = points[(i + 1) % count];
+        normal[0] += (y0 - y1) * (z0 + z1);
+        normal[1] += (z0 - z1) * (x0 + x1);
+        normal[2] += (x0 - x1) * (y0 + y1);
+    }
+    // project onto the plane the polygon is largest in, counterclockwise
+    let axis = (0..3).max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs())).unwrap();
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let flip = if normal[axis] < 0. { -1. } else { 1. };
+    let flat = points.iter().map(|point| [point[u], point[v] * flip]).collect::<Vec<_>>();
+
+    let mut remaining = (0..count).collect::<Vec<_>>();
+    let mut triangles = Vec::with_capacity(count.saturating_sub(2));
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let corners = |i: usize| {
+            [remaining[(i + len - 1) % len], remaining[i], remaining[(i + 1) % len]]
+        };
+        // a degenerate polygon has no ears, then the corners are clipped in order
+        let ear = (1..=len)
+            .map(|i| i % len)
+            .find(|&i| is_ear(&flat, corners(i), &remaining))
+            .unwrap_or(1);
+        triangles.push(corners(ear));
+        remaining.remove(ear);
+        remaining.rotate_left(ear % (len - 1));
+    }
+    if let [a, b, c] = remaining[..] {
+        triangles.push([a, b, c]);
+    }
+    triangles
+}
+
+/// Whether the corner `b` between `a` and `c` of a counterclockwise polygon can be clipped,
+/// that is it is convex and none of the `remaining` corners is inside of the triangle.
+fn is_ear(flat: &[[f32; 2]], [a, b, c]: [usize; 3], remaining: &[usize]) -> bool {
+    let cross = |o: [f32; 2], p: [f32; 2], q: [f32; 2]| {
+        (p[0] - o[0]) * (q[1] - o[1]) - (p[1] - o[1]) * (q[0] - o[0])
+    };
+    let (pa, pb, pc) = (flat[a], flat[b], flat[c]);
+    if cross(pa, pb, pc) <= 0. {
+        return false;
+    }
+    remaining.iter()
+        .filter(|&&corner| corner != a && corner != b && corner != c)
+        .map(|&corner| flat[corner])
+        // corners at the same place, e.g. where a hole is bridged, do not block the ear
+        .filter(|&p| p != pa && p != pb && p != pc)
+        .all(|p| cross(pa, pb, p) < 0. || cross(pb, pc, p) < 0. || cross(pc, pa, p) < 0.)
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct NormalizedObj {
     pub indices: Vec<u32>,
@@ -145,24 +218,30 @@ pub struct Indices {
     pub normal: Option<NonZeroU32>,
 }
 
-impl str::FromStr for Indices {
-    type Err = ObjError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut parts = s.split('/');
-        let Some(part) = parts.next() else {
-            return Err(ObjError::NotEnoughNums(0, 1));
+impl Indices {
+    /// Parses the indices of a corner like `1/2/3`, `-1//2` or `4`.
+    ///
+    /// Negative indices count back from the end of the vertices, texture coordinates and
+    /// normals read so far, whose numbers are `counts`, and are turned into absolute ones.
+    fn parse(part: &[u8], counts: [usize; 3]) -> Result<Self, ObjError> {
+        let part = str::from_utf8(part)
+            .map_err(|_| ObjError::InvalidNum(String::from_utf8_lossy(part).into_owned()))?;
+        let mut parts = part.split('/');
+        let index = |part: &str, count: usize| -> Result<NonZeroU32, ObjError> {
+            let index = part.parse::<i64>().map_err(|_| ObjError::InvalidNum(part.to_owned()))?;
+            let absolute = if index < 0 { count as i64 + 1 + index } else { index };
+            u32::try_from(absolute).ok()
+                .and_then(NonZeroU32::new)
+                .ok_or(ObjError::InvalidIndex(index))
         };
-        let vertex = part.parse().map_err(|_| ObjError::InvalidNum(part.to_owned()))?;
+        let vertex = index(parts.next().unwrap_or_default(), counts[0])?;
         let texture = match parts.next() {
-            Some(part) if !part.is_empty() =>
-                Some(part.parse().map_err(|_| ObjError::InvalidNum(part.to_owned()))?),
+            Some(part) if !part.is_empty() => Some(index(part, counts[1])?),
             _ => None,
         };
-        let normal = if let Some(part) = parts.next() {
-            Some(part.parse().map_err(|_| ObjError::InvalidNum(part.to_owned()))?)
-        } else {
-            None
+        let normal = match parts.next() {
+            Some(part) if !part.is_empty() => Some(index(part, counts[2])?),
+            _ => None,
         };
 
         Ok(Self { vertex, texture, normal })
@@ -172,6 +251,8 @@ impl str::FromStr for Indices {
 #[derive(Debug)]
 pub enum ObjError {
    InvalidIden(String),
+   /// An index of zero or a negative one before the first element.
+   InvalidIndex(i64),
    InvalidNum(String),
    InvalidTextureIndex(u32),
    InvalidVertexIndex(u32),
@@ -184,6 +265,7 @@ impl fmt::Display for ObjError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InvalidIden(iden) => write!(f, "Invalid identifier at line start: {iden}"),
+            Self::InvalidIndex(idx) => write!(f, "Invalid index: {idx}"),
             Self::InvalidNum(num) => write!(f, "Invalid number: {num}"),
             Self::InvalidTextureIndex(idx) => write!(f, "Invalid texture index: {idx}"),
             Self::InvalidVertexIndex(idx) => write!(f, "Invalid vertex index: {idx}"),
@@ -299,4 +381,56 @@ f 2/1 1/2 3/4
         ]);
         assert_eq!(nobj.indices, [0, 1, 2, 3, 4, 5]);
     }
+
+    #[test]
+    fn parse_negative_indices() {
+        let file = r#"
+v 0 0 0
+v 1 0 0
+v 1 1 0
+vt 0 0
+vn 0 0 1
+f -3/-1/-1 -2/-1/-1 -1/1/1 # a comment
+v 0 1 0
+f 1 -2 -1
+f 1 -5 2
+"#;
+        let obj = Obj::from_reader(Cursor::new(file.as_bytes()));
+        assert!(matches!(obj, Err((ObjError::InvalidIndex(-5), 10))));
+        let file = file.replace("f 1 -5 2\n", "");
+        let obj = Obj::from_reader(Cursor::new(file.as_bytes())).expect("failed to parse");
+        let vertices = obj.faces.iter()
+            .map(|face| face.iter().map(|indices| indices.vertex.get()).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(vertices, [[1, 2, 3], [1, 3, 4]]);
+        assert_eq!(obj.faces[0][0].texture, NonZeroU32::new(1));
+        assert_eq!(obj.faces[0][0].normal, NonZeroU32::new(1));
+        assert!(matches!(
+            Obj::from_reader(Cursor::new("v 0 0 0\nf 1 0 1".as_bytes())),
+            Err((ObjError::InvalidIndex(0), 2)),
+        ));
+    }
+
+    #[test]
+    fn triangulate_ngons() {
+        // an L seen from below, so the polygon is clockwise when projected
+        let points = [
+            [0., 0., 0.], [0., 0., 2.], [1., 0., 2.], [1., 0., 1.], [2., 0., 1.], [2., 0., 0.],
+        ];
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        let area = |[a, b, c]: [usize; 3]| {
+            let [pa, pb, pc] = [points[a], points[b], points[c]];
+            ((pb[2] - pa[2]) * (pc[0] - pa[0]) - (pb[0] - pa[0]) * (pc[2] - pa[2])) / 2.
+        };
+        // the triangles cover the L once and keep its winding
+        assert!(triangles.iter().all(|&triangle| area(triangle) > 0.));
+        assert_eq!(triangles.iter().map(|&triangle| area(triangle)).sum::<f32>(), 3.);
+        // the concave corner is never clipped
+        assert!(!triangles.contains(&[2, 3, 4]));
+
+        let file = "v 0 0 0\nv 1 0 0\nv 2 0 0\nv 2 1 0\nv 0 1 0\nf 1 2 3 4 5\n";
+        let nobj = NormalizedObj::from_reader(Cursor::new(file.as_bytes())).unwrap();
+        assert_eq!(nobj.indices.len(), 3 * 3);
+    }
 }
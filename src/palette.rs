@@ -0,0 +1,138 @@
+/// A list of commands searched by typing, so that actions can be found without knowing
+/// their keys.
+#[derive(Debug, Clone)]
+pub struct Palette<T> {
+    /// The name shown for every command.
+    entries: Vec<(String, T)>,
+    query: String,
+    /// Index into the matches of the query.
+    selected: usize,
+}
+
+impl<T> Palette<T> {
+    pub fn new(entries: Vec<(String, T)>) -> Self {
+        Self {
+            entries,
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Appends typed text to the query, control characters like Backspace are ignored.
+    pub fn push_str(&mut self, text: &str) {
+        self.query.extend(text.chars().filter(|c| !c.is_control()));
+        self.selected = 0;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    /// Moves the selection down by `offset` matches, or up if it is negative, wrapping around.
+    pub fn move_selection(&mut self, offset: isize) {
+        let count = self.matches().len();
+        if count > 0 {
+            self.selected = (self.selected as isize + offset).rem_euclid(count as isize) as usize;
+        }
+    }
+
+    /// The names and the commands matching the query, the best match first.
+    pub fn matches(&self) -> Vec<&(String, T)> {
+        let mut matches = self.entries.iter()
+            .filter_map(|entry| Some((fuzzy_score(&self.query, &entry.0)?, entry)))
+            .collect::<Vec<_>>();
+        // without a query everything is shown in order, the sort is stable for equal matches
+        if !self.query.trim().is_empty() {
+            matches.sort_by_key(|(score, _)| -score);
+        }
+        matches.into_iter().map(|(_, entry)| entry).collect()
+    }
+
+    pub fn selected(&self) -> Option<&T> {
+        self.matches().get(self.selected).map(|(_, command)| command)
+    }
+
+    /// The query and at most `max_lines - 1` matches around the selection as text lines.
+    pub fn lines(&self, max_lines: usize) -> Vec<String> {
+        let matches = self.matches();
+        let shown = max_lines.saturating_sub(1);
+        let first = (self.selected + 1).saturating_sub(shown);
+        let mut lines = vec![format!("> {}_", self.query)];
+        lines.extend(matches.iter().enumerate().skip(first).take(shown).map(|(i, (name, _))| {
+            format!("{} {name}", if i == self.selected { '*' } else { ' ' })
+        }));
+        if matches.is_empty() {
+            lines.push("  no matching command".to_owned());
+        }
+        lines
+    }
+}
+
+/// How well `query` matches `text`, `None` if not every character of the query is found in
+/// order in the text, ignoring case.
+///
+/// Characters that follow each other or start a word score higher, so that `fog` prefers
+/// "toggle fog" over "frame of the gallery".
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut text_chars = text.chars().flat_map(char::to_lowercase).enumerate();
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+    for query_char in query.chars().flat_map(char::to_lowercase) {
+        if query_char == ' ' {
+            continue;
+        }
+        loop {
+            let (index, text_char) = text_chars.next()?;
+            let word_start = previous.is_none_or(|c| !c.is_alphanumeric());
+            previous = Some(text_char);
+            if text_char == query_char {
+                let consecutive = last_match.is_some_and(|i| i + 1 == index);
+                score += 1 + 3 * i32::from(word_start) + 4 * i32::from(consecutive);
+                last_match = Some(index);
+                break;
+            }
+        }
+    }
+    // shorter texts match more closely
+    Some(score * 100 - text.len() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fuzzy_matches() {
+        assert!(fuzzy_score("skb", "Toggle skybox").is_some());
+        assert!(fuzzy_score("SKY", "toggle skybox").is_some());
+        assert_eq!(fuzzy_score("xs", "toggle skybox"), None);
+        assert!(fuzzy_score("", "anything").is_some());
+        let fog = fuzzy_score("fog", "Toggle fog").unwrap();
+        let gallery = fuzzy_score("fog", "Frame of the gallery").unwrap();
+        assert!(fog > gallery);
+    }
+
+    #[test]
+    fn select_matches() {
+        let names = ["Toggle skybox", "Toggle fog", "Reload shaders"];
+        let entries = names.iter().map(|name| (name.to_string(), *name)).collect();
+        let mut palette = Palette::new(entries);
+        assert_eq!(palette.selected(), Some(&"Toggle skybox"));
+        palette.move_selection(-1);
+        assert_eq!(palette.selected(), Some(&"Reload shaders"));
+        palette.push_str("fog");
+        assert_eq!(palette.matches().len(), 1);
+        assert_eq!(palette.selected(), Some(&"Toggle fog"));
+        palette.push_str("x");
+        assert_eq!(palette.selected(), None);
+        assert_eq!(palette.lines(5), ["> fogx_", "  no matching command"]);
+        palette.pop();
+        assert_eq!(palette.lines(5), ["> fog_", "* Toggle fog"]);
+    }
+}
@@ -0,0 +1,130 @@
+use anyhow::Context;
+
+/// A stylistic effect applied to the whole frame by the post pass.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PostEffect {
+    /// Shifts red and blue apart towards the edges like a cheap lens.
+    #[default]
+    ChromaticAberration,
+    /// Darkens the corners.
+    Vignette,
+    /// Adds noise that changes every frame.
+    FilmGrain,
+}
+
+impl PostEffect {
+    pub const ALL: [Self; 3] = [Self::ChromaticAberration, Self::Vignette, Self::FilmGrain];
+
+    /// The name used on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ChromaticAberration => "aberration",
+            Self::Vignette => "vignette",
+            Self::FilmGrain => "grain",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// The intensity of every effect from 0 to 1 and whether it is on,
+/// so that an effect keeps its intensity while it is toggled off.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostEffects {
+    intensities: [f32; 3],
+    enabled: [bool; 3],
+}
+
+impl Default for PostEffects {
+    fn default() -> Self {
+        Self {
+            intensities: [0.5; 3],
+            enabled: [false; 3],
+        }
+    }
+}
+
+impl PostEffects {
+    /// The intensity `effect` is applied with, 0 while it is off.
+    pub fn strength(&self, effect: PostEffect) -> f32 {
+        if self.is_enabled(effect) { self.intensity(effect) } else { 0. }
+    }
+
+    pub fn intensity(&self, effect: PostEffect) -> f32 {
+        self.intensities[effect.index()]
+    }
+
+    pub fn is_enabled(&self, effect: PostEffect) -> bool {
+        self.enabled[effect.index()]
+    }
+
+    pub fn toggle(&mut self, effect: PostEffect) {
+        self.enabled[effect.index()] ^= true;
+    }
+
+    /// Sets the intensity of `effect` clamped to 0 to 1, it stays on or off.
+    pub fn set_intensity(&mut self, effect: PostEffect, intensity: f32) {
+        self.intensities[effect.index()] = intensity.clamp(0., 1.);
+    }
+
+    /// Whether any effect is applied at all.
+    pub fn any(&self) -> bool {
+        PostEffect::ALL.into_iter().any(|effect| self.strength(effect) > 0.)
+    }
+
+    /// Parses the effects that are on separated by commas, each optionally with its intensity,
+    /// e.g. `vignette=0.8,grain`.
+    pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        let mut effects = Self::default();
+        for item in text.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (name, intensity) = match item.split_once('=') {
+                Some((name, intensity)) => {
+                    let intensity = intensity.trim().parse::<f32>()
+                        .with_context(|| format!("Invalid intensity of {item}"))?;
+                    (name.trim(), Some(intensity))
+                }
+                None => (item, None),
+            };
+            let Some(effect) = PostEffect::ALL.into_iter().find(|effect| effect.name() == name)
+            else {
+                let names = PostEffect::ALL.map(PostEffect::name).join(", ");
+                anyhow::bail!("Unknown post effect {name}, expected one of {names}");
+            };
+            if let Some(intensity) = intensity {
+                effects.set_intensity(effect, intensity);
+            }
+            effects.enabled[effect.index()] = true;
+        }
+        Ok(effects)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_post_effects() {
+        let effects = PostEffects::parse("vignette=0.8, grain").unwrap();
+        assert_eq!(effects.strength(PostEffect::ChromaticAberration), 0.);
+        assert_eq!(effects.strength(PostEffect::Vignette), 0.8);
+        assert_eq!(effects.strength(PostEffect::FilmGrain), 0.5);
+        assert!(PostEffects::parse("").is_ok_and(|effects| !effects.any()));
+        assert!(PostEffects::parse("blur").is_err());
+        assert!(PostEffects::parse("grain=much").is_err());
+    }
+
+    #[test]
+    fn toggle_keeps_intensity() {
+        let mut effects = PostEffects::default();
+        effects.set_intensity(PostEffect::FilmGrain, 2.);
+        assert!(!effects.any());
+        effects.toggle(PostEffect::FilmGrain);
+        assert_eq!(effects.strength(PostEffect::FilmGrain), 1.);
+        effects.toggle(PostEffect::FilmGrain);
+        assert_eq!(effects.strength(PostEffect::FilmGrain), 0.);
+        assert_eq!(effects.intensity(PostEffect::FilmGrain), 1.);
+    }
+}
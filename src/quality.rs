@@ -0,0 +1,155 @@
+/// The highest quality level the art sees, at which it draws as it was written.
+pub const MAX_QUALITY: u32 = 3;
+/// The frame rate the automatic level is chosen for unless another one is given.
+pub const TARGET_FPS: f32 = 30.;
+/// The frames averaged before the level changes, so that it does not flicker.
+const SAMPLES: usize = 60;
+/// How much longer than the target the frames may take before the level is lowered.
+const LOWER_ABOVE: f32 = 1.1;
+/// How much shorter than the target the frames have to be before the level is raised,
+/// the next level is assumed to take a lot longer.
+const RAISE_BELOW: f32 = 0.6;
+
+/// How the quality level of the art is chosen.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMode {
+    /// Follows the frame time.
+    #[default]
+    Auto,
+    Fixed(u32),
+}
+
+impl QualityMode {
+    /// Parses `auto` or a level from 0 to [`MAX_QUALITY`] as given on the command line.
+    pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        if text == "auto" {
+            return Ok(Self::Auto);
+        }
+        match text.parse() {
+            Ok(level) if level <= MAX_QUALITY => Ok(Self::Fixed(level)),
+            _ => Err(anyhow::anyhow!("expected auto or a level from 0 to {MAX_QUALITY}")),
+        }
+    }
+}
+
+/// The quality level art shaders lower their iteration counts with, so that the same shader
+/// runs on integrated and desktop GPUs, see `VkApp::set_quality`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quality {
+    mode: QualityMode,
+    level: u32,
+    /// The longest a frame should take in milliseconds.
+    target: f32,
+    frame_times: Vec<f32>,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Self::new(QualityMode::Auto, TARGET_FPS)
+    }
+}
+
+impl Quality {
+    pub fn new(mode: QualityMode, target_fps: f32) -> Self {
+        let mut quality = Self {
+            mode: QualityMode::Auto,
+            level: MAX_QUALITY,
+            target: 1000. / target_fps,
+            frame_times: Vec::with_capacity(SAMPLES),
+        };
+        quality.set_mode(mode);
+        quality
+    }
+
+    pub fn mode(&self) -> QualityMode {
+        self.mode
+    }
+
+    pub fn level(&self) -> u32 {
+        self.level
+    }
+
+    /// Switches to `mode`, the automatic level starts where the fixed one was.
+    pub fn set_mode(&mut self, mode: QualityMode) {
+        self.mode = mode;
+        if let QualityMode::Fixed(level) = mode {
+            self.level = level.min(MAX_QUALITY);
+        }
+        self.frame_times.clear();
+    }
+
+    /// Switches from automatic to every fixed level from the lowest and back.
+    pub fn cycle(&mut self) {
+        self.set_mode(match self.mode {
+            QualityMode::Auto => QualityMode::Fixed(0),
+            QualityMode::Fixed(level) if level < MAX_QUALITY => QualityMode::Fixed(level + 1),
+            QualityMode::Fixed(_) => QualityMode::Auto,
+        });
+    }
+
+    /// Takes the time of a frame in milliseconds, returns true if the automatic level changed.
+    pub fn push(&mut self, frame_time: f32) -> bool {
+        if self.mode != QualityMode::Auto {
+            return false;
+        }
+        self.frame_times.push(frame_time);
+        if self.frame_times.len() < SAMPLES {
+            return false;
+        }
+        let average = self.frame_times.iter().sum::<f32>() / SAMPLES as f32;
+        self.frame_times.clear();
+        let level = if average > self.target * LOWER_ABOVE {
+            self.level.saturating_sub(1)
+        } else if average < self.target * RAISE_BELOW {
+            (self.level + 1).min(MAX_QUALITY)
+        } else {
+            self.level
+        };
+        let changed = level != self.level;
+        self.level = level;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_frames(quality: &mut Quality, frame_time: f32) -> bool {
+        (0..SAMPLES).fold(false, |changed, _| quality.push(frame_time) || changed)
+    }
+
+    #[test]
+    fn follow_frame_time() {
+        let mut quality = Quality::new(QualityMode::Auto, 50.);
+        assert_eq!(quality.level(), MAX_QUALITY);
+        assert!(!push_frames(&mut quality, 10.));
+        assert!(push_frames(&mut quality, 30.));
+        assert_eq!(quality.level(), MAX_QUALITY - 1);
+        assert!(!push_frames(&mut quality, 20.));
+        assert!(push_frames(&mut quality, 10.));
+        assert_eq!(quality.level(), MAX_QUALITY);
+    }
+
+    #[test]
+    fn fixed_level() {
+        let mut quality = Quality::new(QualityMode::Fixed(1), 50.);
+        assert!(!push_frames(&mut quality, 100.));
+        assert_eq!(quality.level(), 1);
+        quality.cycle();
+        assert_eq!(quality.mode(), QualityMode::Fixed(2));
+        quality.cycle();
+        quality.cycle();
+        assert_eq!(quality.mode(), QualityMode::Auto);
+        assert!(push_frames(&mut quality, 100.));
+        assert_eq!(quality.level(), MAX_QUALITY - 1);
+    }
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!(QualityMode::parse("auto").unwrap(), QualityMode::Auto);
+        assert_eq!(QualityMode::parse("2").unwrap(), QualityMode::Fixed(2));
+        assert!(QualityMode::parse("4").is_err());
+        assert!(QualityMode::parse("high").is_err());
+    }
+}
@@ -0,0 +1,350 @@
+use crate::json::Json;
+use crate::math::{Deg, Vector3};
+
+use anyhow::Context;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// The longest a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest a client may take to take the answer.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The longest a request waits for its answer, saving a screenshot takes a while.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+/// The longest request that is read, the bodies are small JSON objects.
+const MAX_REQUEST_LEN: usize = 64 * 1024;
+/// The most connections answered at once, more are refused until one is done.
+const MAX_CONNECTIONS: usize = 16;
+
+/// What a request to the remote control asks the gallery to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RemoteCommand {
+    /// `GET /status`: where the camera is and which art can be gone to.
+    Status,
+    /// `POST /art` with `{"name": "Mandelbox"}`: moves the camera in front of the art.
+    GoToArt(String),
+    /// `POST /camera` with `{"position": [0, 1.5, 0], "yaw": 90, "pitch": 0}`, the angles are
+    /// in degrees and everything that is missing stays as it is.
+    SetCamera {
+        position: Option<Vector3>,
+        yaw: Option<Deg<f32>>,
+        pitch: Option<Deg<f32>>,
+    },
+    /// `POST /image` with `{"path": "photo.jpg"}` loads the image, without a path the next one
+    /// of the image directory.
+    LoadImage(Option<PathBuf>),
+    /// `POST /screenshot`: saves a still, the answer has its path.
+    Screenshot,
+}
+
+impl RemoteCommand {
+    /// Parses a request with `method` for `path` and its `body`, which may be empty,
+    /// or returns the answer for a request that is not understood.
+    pub fn parse(method: &str, path: &str, body: &str) -> Result<Self, Response> {
+        let json = match body.trim() {
+            "" => Json::Object(Vec::new()),
+            body => Json::parse(body)
+                .map_err(|err| Response::error(400, &format!("{err:#}")))?,
+        };
+        let expected_method = if path == "/status" { "GET" } else { "POST" };
+        let command = match path {
+            "/status" => Self::Status,
+            "/art" => match json["name"].as_str() {
+                Some(name) => Self::GoToArt(name.to_owned()),
+                None => return Err(Response::error(400, "expected the name of the art")),
+            },
+            "/camera" => {
+                let position = optional(&json["position"], Json::as_f32_array::<3>, "position")?;
+                let yaw = optional(&json["yaw"], Json::as_f32, "yaw")?;
+                let pitch = optional(&json["pitch"], Json::as_f32, "pitch")?;
+                Self::SetCamera {
+                    position: position.map(Vector3::from),
+                    yaw: yaw.map(Deg),
+                    pitch: pitch.map(Deg),
+                }
+            }
+            "/image" => {
+                let path = optional(&json["path"], Json::as_str, "path")?;
+                Self::LoadImage(path.map(PathBuf::from))
+            }
+            "/screenshot" => Self::Screenshot,
+            _ => return Err(Response::error(404, &format!("there is nothing at {path}"))),
+        };
+        if method != expected_method {
+            return Err(Response::error(405, &format!("{path} expects {expected_method}")));
+        }
+        Ok(command)
+    }
+}
+
+/// Reads `value` with `read` unless it is missing.
+fn optional<'a, T>(
+    value: &'a Json,
+    read: impl Fn(&'a Json) -> Option<T>,
+    name: &str,
+) -> Result<Option<T>, Response> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    read(value).map(Some).ok_or_else(|| Response::error(400, &format!("invalid {name}")))
+}
+
+/// The HTTP status and the JSON body of an answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Json,
+}
+
+impl Response {
+    pub fn ok(body: Json) -> Self {
+        Self { status: 200, body }
+    }
+
+    /// An answer with `{"error": message}`.
+    pub fn error(status: u16, message: &str) -> Self {
+        let body = Json::Object(vec![("error".to_owned(), Json::String(message.to_owned()))]);
+        Self { status, body }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            500 => "Internal Server Error",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            _ => "",
+        }
+    }
+}
+
+/// A command received by the [`RemoteServer`], the client waits until it is answered.
+pub struct RemoteRequest {
+    pub command: RemoteCommand,
+    reply: Sender<Response>,
+}
+
+impl RemoteRequest {
+    pub fn reply(self, response: Response) {
+        // the client may have given up waiting
+        let _ = self.reply.send(response);
+    }
+}
+
+/// A small HTTP server that lets other programs drive the gallery, e.g. the scheduling
+/// software of an installation, see [`RemoteCommand`] for the endpoints.
+///
+/// Every connection is read in its own thread and its request handed to the event loop,
+/// which takes them with [`Self::requests`], so a slow client does not hold up the others.
+/// There is no authentication, so it should only listen on addresses the installation
+/// controls.
+pub struct RemoteServer {
+    addr: SocketAddr,
+    requests: Receiver<RemoteRequest>,
+}
+
+impl RemoteServer {
+    /// Listens on `addr`, a bare port listens on localhost. `wake_up` is called for every
+    /// request, so that an event loop waiting for the window handles it.
+    pub fn start(
+        addr: &str,
+        wake_up: impl Fn() + Send + Sync + 'static,
+    ) -> Result<Self, anyhow::Error> {
+        let addr = match addr.parse::<u16>() {
+            Ok(port) => format!("127.0.0.1:{port}"),
+            Err(_) => addr.to_owned(),
+        };
+        let listener = TcpListener::bind(&addr)
+            .with_context(|| format!("Failed to listen on {addr}"))?;
+        let addr = listener.local_addr()?;
+        let (sender, requests) = mpsc::channel();
+        let wake_up = Arc::new(wake_up);
+        let connections = Arc::new(AtomicUsize::new(0));
+        thread::Builder::new()
+            .name("remote control".to_owned())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            log::warn!("Failed to accept a remote control connection: {err}");
+                            continue;
+                        }
+                    };
+                    if connections.fetch_add(1, Ordering::AcqRel) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::AcqRel);
+                        log::warn!("Refused a remote control connection, too many are open");
+                        continue;
+                    }
+                    let (sender, wake_up) = (sender.clone(), Arc::clone(&wake_up));
+                    let done = Arc::clone(&connections);
+                    let spawned = thread::Builder::new()
+                        .name("remote control connection".to_owned())
+                        .spawn(move || {
+                            if let Err(err) = handle_connection(stream, &sender, &*wake_up) {
+                                log::warn!("Failed to answer a remote control request: {err:#}");
+                            }
+                            done.fetch_sub(1, Ordering::AcqRel);
+                        });
+                    if let Err(err) = spawned {
+                        connections.fetch_sub(1, Ordering::AcqRel);
+                        log::warn!("Failed to answer a remote control connection: {err}");
+                    }
+                }
+            })
+            .context("Failed to start the remote control")?;
+        Ok(Self { addr, requests })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// The requests received since the last call, each must be answered.
+    pub fn requests(&self) -> impl Iterator<Item = RemoteRequest> + '_ {
+        self.requests.try_iter()
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    sender: &Sender<RemoteRequest>,
+    wake_up: &dyn Fn(),
+) -> Result<(), anyhow::Error> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+    let response = match read_request(&stream) {
+        Ok((method, path, body)) => match RemoteCommand::parse(&method, &path, &body) {
+            Ok(command) => {
+                let (reply, response) = mpsc::channel();
+                if sender.send(RemoteRequest { command, reply }).is_ok() {
+                    wake_up();
+                }
+                match response.recv_timeout(REPLY_TIMEOUT) {
+                    Ok(response) => response,
+                    Err(RecvTimeoutError::Timeout) => {
+                        Response::error(504, "the gallery did not answer in time")
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        Response::error(503, "the gallery is not running")
+                    }
+                }
+            }
+            Err(response) => response,
+        },
+        Err(err) => Response::error(400, &format!("{err:#}")),
+    };
+    write_response(&stream, &response)
+}
+
+/// Reads the method, the path without the query and the body of a request.
+fn read_request(stream: &TcpStream) -> Result<(String, String, String), anyhow::Error> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_LEN as u64));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("invalid request line");
+    };
+    let method = method.to_owned();
+    let path = target.split('?').next().unwrap_or_default().to_owned();
+
+    let mut content_len = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            anyhow::bail!("the request ended in the headers");
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_len = value.trim().parse().context("invalid Content-Length")?;
+            }
+        }
+    }
+    anyhow::ensure!(content_len <= MAX_REQUEST_LEN, "the body is too long");
+    let mut body = vec![0; content_len];
+    reader.read_exact(&mut body).context("the body ended early")?;
+    let body = String::from_utf8(body).context("the body is not UTF-8")?;
+    Ok((method, path, body))
+}
+
+fn write_response(mut stream: &TcpStream, response: &Response) -> Result<(), anyhow::Error> {
+    let body = response.body.to_string();
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{body}",
+        response.status,
+        response.reason(),
+        body.len(),
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(RemoteCommand::parse("GET", "/status", ""), Ok(RemoteCommand::Status));
+        assert_eq!(
+            RemoteCommand::parse("POST", "/art", r#"{"name": "Cloud"}"#),
+            Ok(RemoteCommand::GoToArt("Cloud".to_owned())),
+        );
+        assert_eq!(
+            RemoteCommand::parse("POST", "/camera", r#"{"position": [1, 2, 3], "yaw": 90}"#),
+            Ok(RemoteCommand::SetCamera {
+                position: Some([1., 2., 3.].into()),
+                yaw: Some(Deg(90.)),
+                pitch: None,
+            }),
+        );
+        assert_eq!(
+            RemoteCommand::parse("POST", "/image", ""),
+            Ok(RemoteCommand::LoadImage(None)),
+        );
+        let status = |method, path, body| {
+            RemoteCommand::parse(method, path, body).map_err(|response| response.status)
+        };
+        assert_eq!(status("POST", "/camera", r#"{"position": [1, 2]}"#), Err(400));
+        assert_eq!(status("POST", "/art", "{"), Err(400));
+        assert_eq!(status("GET", "/screenshot", ""), Err(405));
+        assert_eq!(status("GET", "/missing", ""), Err(404));
+    }
+
+    #[test]
+    fn serve_requests() {
+        let server = RemoteServer::start("127.0.0.1:0", || {}).unwrap();
+        let addr = server.addr();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let body = r#"{"name": "Solar"}"#;
+            write!(stream, "POST /art HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}", body.len())
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+        let request = server.requests.recv_timeout(READ_TIMEOUT).unwrap();
+        assert_eq!(request.command, RemoteCommand::GoToArt("Solar".to_owned()));
+        request.reply(Response::error(404, "no art named Solar"));
+        let response = client.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.ends_with(r#"{"error":"no art named Solar"}"#));
+    }
+}
@@ -0,0 +1,490 @@
+//! The art of the gallery read from a file, so that it can be placed without recompiling.
+
+use crate::gallery::ArtPiece;
+use crate::math::{Deg, Matrix4, Vector3, Vector4};
+use crate::vulkan::{ArtTime, Framing, Proximity};
+
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// An art piece of the scene config, see [`parse`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArtConfig {
+    pub name: String,
+    pub is_3d: bool,
+    pub frag: PathBuf,
+    pub position: Vector3,
+    /// Rotations around the x, y and z axes applied in this order.
+    pub rotation: [Deg<f32>; 3],
+    pub scale: f32,
+    pub params: Vector4,
+    pub time: ArtTime,
+    pub proximity: Proximity,
+    pub framing: Framing,
+    pub half_resolution: bool,
+    pub ray_tracing: Option<[PathBuf; 3]>,
+    /// The name of a volume the program creates for the art.
+    pub volume: Option<String>,
+}
+
+/// How the art of a running gallery changes when the scene config is loaded again,
+/// see [`diff`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArtChange<'a> {
+    /// The art is taken down.
+    Remove(&'a str),
+    /// The art is hung.
+    Add(&'a ArtConfig),
+    /// Only the model matrix or the parameters of the art changed.
+    Update(&'a ArtConfig),
+}
+
+impl ArtConfig {
+    fn new(name: String, is_3d: bool, frag: PathBuf) -> Self {
+        Self {
+            name,
+            is_3d,
+            frag,
+            position: Vector3::default(),
+            rotation: [Deg(0.); 3],
+            scale: 1.,
+            params: Vector4::default(),
+            time: ArtTime::default(),
+            proximity: Proximity::default(),
+            framing: Framing::default(),
+            half_resolution: false,
+            ray_tracing: None,
+            volume: None,
+        }
+    }
+
+    /// The art is scaled and rotated around its own origin before it is moved to `position`.
+    pub fn model_matrix(&self) -> Matrix4 {
+        let [x, y, z] = self.rotation;
+        Matrix4::from_translation(self.position)
+            * Matrix4::from_angle_z(z)
+            * Matrix4::from_angle_y(y)
+            * Matrix4::from_angle_x(x)
+            * Matrix4::from_scale(self.scale)
+    }
+
+    /// Sets the position, the rotation and the scale of the art to those of `model`,
+    /// rounded so that they can be saved. A scale that differs between the axes is averaged.
+    pub fn set_model_matrix(&mut self, model: Matrix4) {
+        let (position, rotation, scale) = placement(model);
+        self.position = Vector3::from(<[f32; 3]>::from(position).map(round));
+        self.rotation = rotation.map(|angle| Deg(round(angle.0)));
+        self.scale = round(scale);
+    }
+
+    /// The art piece hung for this config, without the volume the program has to add.
+    pub fn art_piece(&self) -> ArtPiece {
+        let piece = if self.is_3d {
+            ArtPiece::new_3d(self.name.clone(), self.frag.clone())
+        } else {
+            ArtPiece::new_2d(self.name.clone(), self.frag.clone())
+        };
+        let piece = piece
+            .at(self.model_matrix())
+            .params(self.params)
+            .time(self.time)
+            .proximity(self.proximity)
+            .framing(self.framing);
+        let piece = match self.ray_tracing.clone() {
+            Some(shaders) => piece.ray_tracing(shaders),
+            None => piece,
+        };
+        if self.half_resolution {
+            piece.half_resolution()
+        } else {
+            piece
+        }
+    }
+
+    /// Whether `other` differs from this art only in what can be changed while it hangs.
+    fn is_updated_by(&self, other: &ArtConfig) -> bool {
+        let placed = ArtConfig {
+            position: other.position,
+            rotation: other.rotation,
+            scale: other.scale,
+            params: other.params,
+            ..self.clone()
+        };
+        placed == *other
+    }
+
+    fn set(&mut self, key: &str, values: &[String], number: usize) -> Result<(), anyhow::Error> {
+        match key {
+            "position" => self.position = Vector3::from(numbers(key, values, number)?),
+            "rotation" => self.rotation = numbers::<3>(key, values, number)?.map(Deg),
+            "scale" => {
+                [self.scale] = numbers(key, values, number)?;
+                anyhow::ensure!(
+                    self.scale > 0.,
+                    "The scale {} in line {number} is not positive",
+                    self.scale,
+                );
+            }
+            "params" => self.params = Vector4::from(numbers(key, values, number)?),
+            "time_offset" => [self.time.offset] = numbers(key, values, number)?,
+            "speed" => [self.time.speed] = numbers(key, values, number)?,
+            "restart_distance" => {
+                self.time.restart_distance = Some(numbers::<1>(key, values, number)?[0]);
+            }
+            "loop_period" => self.time.loop_period = Some(numbers::<1>(key, values, number)?[0]),
+            "nearby_distance" => [self.proximity.nearby_distance] = numbers(key, values, number)?,
+            "low_quality_distance" => {
+                self.proximity.low_quality_distance = Some(numbers::<1>(key, values, number)?[0]);
+            }
+            "sleep_distance" => {
+                self.proximity.sleep_distance = Some(numbers::<1>(key, values, number)?[0]);
+            }
+            "framing" => {
+                self.framing = match values {
+                    [framing] if framing == "stretch" => Framing::Stretch,
+                    [framing] if framing == "fit" => Framing::Fit,
+                    [framing] if framing == "letterbox" => Framing::Letterbox,
+                    _ => anyhow::bail!(
+                        "Expected stretch, fit or letterbox for framing in line {number}",
+                    ),
+                };
+            }
+            "half_resolution" => {
+                anyhow::ensure!(values.is_empty(), "Unexpected value in line {number}");
+                self.half_resolution = true;
+            }
+            "ray_tracing" => {
+                let [raygen, miss, hit] = values else {
+                    anyhow::bail!("Expected 3 shaders for ray_tracing in line {number}");
+                };
+                self.ray_tracing = Some([raygen.into(), miss.into(), hit.into()]);
+            }
+            "volume" => {
+                let [volume] = values else {
+                    anyhow::bail!("Expected the name of a volume in line {number}");
+                };
+                self.volume = Some(volume.clone());
+            }
+            _ => anyhow::bail!("Unknown setting {key} in line {number}"),
+        }
+        Ok(())
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Vec<ArtConfig>, anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scene config {}", path.display()))?;
+    parse(&text).with_context(|| format!("Failed to parse scene config {}", path.display()))
+}
+
+/// Parses a block for every art piece with its kind, name and fragment shader followed by
+/// one setting per line, e.g.
+///
+/// ```text
+/// # the zoom starts over for every visitor
+/// art 2d "Mandelbrot" "assets/shaders/mandelbrot.frag" {
+///     position 5.99 1.5 -1.5
+///     rotation 0 90 0
+///     scale 0.5
+///     restart_distance 2
+/// }
+/// ```
+/// The art is scaled, rotated around the x, y and z axes in degrees and moved to `position`.
+/// `params` sets its free parameters and `framing` how 2D art fits its image. `time_offset`,
+/// `speed`, `restart_distance` and `loop_period` set its [`ArtTime`], `nearby_distance`,
+/// `low_quality_distance` and `sleep_distance` its [`Proximity`]. `half_resolution` draws 3D
+/// art at half resolution, `ray_tracing` takes the ray generation, miss and closest hit shaders
+/// and `volume` names a volume the program creates.
+pub fn parse(text: &str) -> Result<Vec<ArtConfig>, anyhow::Error> {
+    let mut arts = Vec::<ArtConfig>::new();
+    let mut current = None::<ArtConfig>;
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let words = words(line, number)?;
+        let Some((first, rest)) = words.split_first() else {
+            continue;
+        };
+        match current.as_mut() {
+            None => {
+                let [kind, name, frag, open] = rest else {
+                    anyhow::bail!("Expected art 2d or 3d, a name, a shader and {{ in line {number}");
+                };
+                anyhow::ensure!(
+                    first == "art" && open == "{",
+                    "Expected art 2d or 3d, a name, a shader and {{ in line {number}",
+                );
+                let is_3d = match kind.as_str() {
+                    "2d" => false,
+                    "3d" => true,
+                    _ => anyhow::bail!("Expected 2d or 3d instead of {kind} in line {number}"),
+                };
+                anyhow::ensure!(
+                    arts.iter().all(|art| art.name != *name),
+                    "The art {name} in line {number} is hung twice",
+                );
+                current = Some(ArtConfig::new(name.clone(), is_3d, frag.into()));
+            }
+            Some(_) if first == "}" => {
+                anyhow::ensure!(rest.is_empty(), "Unexpected {} in line {number}", rest[0]);
+                arts.extend(current.take());
+            }
+            Some(art) => art.set(first, rest, number)?,
+        }
+    }
+    if let Some(art) = current {
+        anyhow::bail!("The block of the art {} is not closed with }}", art.name);
+    }
+    Ok(arts)
+}
+
+/// Writes the position, the rotation and the scale of `art` into its block in the scene
+/// config at `path`, see [`with_placement`].
+pub fn save_placement<P: AsRef<Path>>(path: P, art: &ArtConfig) -> Result<(), anyhow::Error> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read scene config {}", path.display()))?;
+    let text = with_placement(&text, art)?;
+    std::fs::write(path, text)
+        .with_context(|| format!("Failed to write scene config {}", path.display()))
+}
+
+/// Replaces the `position`, `rotation` and `scale` lines in the block of `art` in the scene
+/// config `text` with those of `art`. Missing lines are added at the end of the block unless
+/// they would set the default, everything else is kept.
+pub fn with_placement(text: &str, art: &ArtConfig) -> Result<String, anyhow::Error> {
+    let [x, y, z] = art.rotation.map(|angle| angle.0);
+    let mut placement = [
+        ("position", Some(<[f32; 3]>::from(art.position).to_vec())),
+        ("rotation", (art.rotation != [Deg(0.); 3]).then(|| vec![x, y, z])),
+        ("scale", (art.scale != 1.).then(|| vec![art.scale])),
+    ];
+    let line_of = |key: &str, values: &[f32]| {
+        let values = values.iter().map(f32::to_string).collect::<Vec<_>>();
+        format!("{key} {}", values.join(" "))
+    };
+    let mut lines = Vec::new();
+    let mut found = false;
+    let mut in_block = false;
+    for (index, line) in text.lines().enumerate() {
+        let words = words(line, index + 1)?;
+        let first = words.first().map(String::as_str);
+        if !in_block {
+            in_block = first == Some("art") && words.get(2) == Some(&art.name);
+            found |= in_block;
+        } else if first == Some("}") {
+            for (key, values) in placement.iter_mut() {
+                if let Some(values) = values.take() {
+                    lines.push(format!("    {}", line_of(key, &values)));
+                }
+            }
+            in_block = false;
+        } else if let Some((key, values)) =
+            placement.iter_mut().find(|(key, _)| Some(*key) == first)
+        {
+            let indent = &line[..line.len() - line.trim_start().len()];
+            // a rotation or a scale that is back at the default is still written
+            let values = values.take().unwrap_or_else(|| match *key {
+                "rotation" => vec![0.; 3],
+                _ => vec![1.],
+            });
+            lines.push(format!("{indent}{}", line_of(key, &values)));
+            continue;
+        }
+        lines.push(line.to_owned());
+    }
+    anyhow::ensure!(found, "There is no art {} in the scene config", art.name);
+    Ok(lines.join("\n") + "\n")
+}
+
+/// What has to change for the art of `old` to become the art of `new`. The art taken down
+/// comes first, then the art that is hung in the order of `new` and then the updates.
+pub fn diff<'a>(old: &'a [ArtConfig], new: &'a [ArtConfig]) -> Vec<ArtChange<'a>> {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    let mut updated = Vec::new();
+    for art in old.iter().filter(|art| !new.iter().any(|other| other.name == art.name)) {
+        removed.push(ArtChange::Remove(&art.name));
+    }
+    for art in new {
+        match old.iter().find(|other| other.name == art.name) {
+            None => added.push(ArtChange::Add(art)),
+            Some(other) if other == art => {}
+            Some(other) if other.is_updated_by(art) => updated.push(ArtChange::Update(art)),
+            // everything else is set when the art is created
+            Some(_) => {
+                removed.push(ArtChange::Remove(&art.name));
+                added.push(ArtChange::Add(art));
+            }
+        }
+    }
+    removed.into_iter().chain(added).chain(updated).collect()
+}
+
+/// The words of `line` up to a `#`, words in quotes may contain spaces.
+fn words(line: &str, number: usize) -> Result<Vec<String>, anyhow::Error> {
+    let mut words = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() && !rest.starts_with('#') {
+        let (word, after) = match rest.strip_prefix('"') {
+            Some(after) => after.split_once('"')
+                .with_context(|| format!("Unterminated string in line {number}"))?,
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        words.push(word.to_owned());
+        rest = after.trim_start();
+    }
+    Ok(words)
+}
+
+/// The position, the rotations around the x, y and z axes and the scale of `model` like
+/// [`ArtConfig`] places art. A scale that differs between the axes is averaged.
+pub fn placement(model: Matrix4) -> (Vector3, [Deg<f32>; 3], f32) {
+    let axes = [0, 1, 2].map(|i| model[i].resize::<3>());
+    let scale = axes.iter().map(|axis| axis.magnitude()).sum::<f32>() / 3.;
+    let [x_axis, y_axis, z_axis] = axes.map(|axis| axis / scale);
+    // the rotation is Rz * Ry * Rx, whose x axis holds the sine of the y angle
+    let y = (-x_axis[2]).clamp(-1., 1.).asin();
+    let (x, z) = if x_axis[2].abs() < 0.9999 {
+        (y_axis[2].atan2(z_axis[2]), x_axis[1].atan2(x_axis[0]))
+    } else {
+        // gimbal lock, the x and the z rotation turn around the same axis
+        (0., (-y_axis[0]).atan2(y_axis[1]))
+    };
+    (model[3].resize(), [x, y, z].map(|angle| Deg(angle.to_degrees())), scale)
+}
+
+/// Rounds to four decimals, so that placed art is saved with readable numbers.
+fn round(value: f32) -> f32 {
+    (value * 1e4).round() / 1e4
+}
+
+fn numbers<const N: usize>(
+    key: &str,
+    values: &[String],
+    number: usize,
+) -> Result<[f32; N], anyhow::Error> {
+    let numbers = values.iter()
+        .map(|value| value.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid number for {key} in line {number}"))?;
+    let count = numbers.len();
+    numbers.try_into().map_err(|_| {
+        anyhow::anyhow!("Expected {N} numbers for {key} in line {number} but found {count}")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = "# the gallery\n\
+        art 2d \"Sdf Cat\" \"assets/shaders/cat.frag\" {\n\
+            position 5.99 1.5 -4.5 # on the wall\n\
+            rotation 0 90 0\n\
+            scale 0.5\n\
+            loop_period 6.25\n\
+        }\n\
+        \n\
+        art 3d \"Cloud\" \"assets/shaders/cloud.frag\" {\n\
+            params 1 0 0 2\n\
+            low_quality_distance 8\n\
+            half_resolution\n\
+            volume cloud\n\
+        }\n";
+
+    #[test]
+    fn parse_scene() {
+        let arts = parse(SCENE).unwrap();
+        assert_eq!(arts.len(), 2);
+        let cat = &arts[0];
+        assert_eq!(cat.name, "Sdf Cat");
+        assert!(!cat.is_3d);
+        assert_eq!(cat.frag, Path::new("assets/shaders/cat.frag"));
+        assert_eq!(cat.position, Vector3::from([5.99, 1.5, -4.5]));
+        assert_eq!(cat.rotation, [Deg(0.), Deg(90.), Deg(0.)]);
+        assert_eq!(cat.time.loop_period, Some(6.25));
+        let expected = Matrix4::from_translation([5.99, 1.5, -4.5].into())
+            * Matrix4::from_scale(0.5)
+            * Matrix4::from_angle_y(Deg(90.));
+        let model = cat.model_matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((model[i][j] - expected[i][j]).abs() < 1e-6);
+            }
+        }
+        let cloud = &arts[1];
+        assert!(cloud.is_3d);
+        assert_eq!(cloud.params, Vector4::from([1., 0., 0., 2.]));
+        assert_eq!(cloud.proximity.low_quality_distance, Some(8.));
+        assert!(cloud.half_resolution);
+        assert_eq!(cloud.volume.as_deref(), Some("cloud"));
+    }
+
+    #[test]
+    fn reject_invalid_scene() {
+        assert!(parse("art 2d \"A\" \"a.frag\" {\n").is_err());
+        assert!(parse("art 4d \"A\" \"a.frag\" {\n}\n").is_err());
+        assert!(parse("art 2d \"A\" \"a.frag\" {\nscale 0\n}\n").is_err());
+        assert!(parse("art 2d \"A\" \"a.frag\" {\nposition 1 2\n}\n").is_err());
+        assert!(parse("art 2d \"A\" \"a.frag\" {\nglow 1\n}\n").is_err());
+        assert!(parse("art 2d \"A\" \"a.frag\" {\n}\nart 3d \"A\" \"b.frag\" {\n}\n").is_err());
+        assert!(parse("art 2d \"A\" \"a.frag {\n}\n").is_err());
+    }
+
+    #[test]
+    fn set_model_matrix() {
+        let mut art = parse(SCENE).unwrap().remove(0);
+        let mut placed = art.clone();
+        placed.rotation = [Deg(10.), Deg(-30.), Deg(45.)];
+        placed.scale = 0.75;
+        art.set_model_matrix(placed.model_matrix());
+        assert_eq!(art, placed);
+        // gimbal lock
+        placed.rotation = [Deg(0.), Deg(90.), Deg(20.)];
+        art.set_model_matrix(placed.model_matrix());
+        assert_eq!(art, placed);
+    }
+
+    #[test]
+    fn write_placement() {
+        let mut arts = parse(SCENE).unwrap();
+        arts[0].position = Vector3::from([1., 2., 3.]);
+        arts[0].rotation = [Deg(0.); 3];
+        arts[1].rotation = [Deg(0.), Deg(45.), Deg(0.)];
+        arts[1].scale = 2.;
+        let text = with_placement(SCENE, &arts[0]).unwrap();
+        let text = with_placement(&text, &arts[1]).unwrap();
+        assert!(text.starts_with("# the gallery\n"));
+        assert!(text.contains("position 1 2 3\nrotation 0 0 0\nscale 0.5\n"));
+        assert!(text.contains("    position 0 0 0\n    rotation 0 45 0\n    scale 2\n}\n"));
+        assert_eq!(parse(&text).unwrap(), arts);
+        let mut unknown = arts[0].clone();
+        unknown.name = "Sdf Dog".to_owned();
+        assert!(with_placement(SCENE, &unknown).is_err());
+    }
+
+    #[test]
+    fn diff_scenes() {
+        let old = parse(SCENE).unwrap();
+        let mut new = old.clone();
+        new[0].position = Vector3::from([0., 1., 0.]);
+        new[1].frag = PathBuf::from("assets/shaders/smoke.frag");
+        let mut added = new[0].clone();
+        added.name = "Sdf Dog".to_owned();
+        new.push(added);
+        assert_eq!(diff(&old, &new), [
+            ArtChange::Remove("Cloud"),
+            ArtChange::Add(&new[1]),
+            ArtChange::Add(&new[2]),
+            ArtChange::Update(&new[0]),
+        ]);
+        assert_eq!(diff(&new, &old), [
+            ArtChange::Remove("Sdf Dog"),
+            ArtChange::Remove("Cloud"),
+            ArtChange::Add(&old[1]),
+            ArtChange::Update(&old[0]),
+        ]);
+        assert!(diff(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+use crate::math::{Deg, Matrix4, Vector3, Vector4};
+
+use anyhow::Context;
+#[cfg(feature = "rhai")]
+use anyhow::anyhow;
+#[cfg(feature = "rhai")]
+use rhai::{Dynamic, Engine, Map, AST, FLOAT};
+#[cfg(feature = "rhai")]
+use std::{cell::RefCell, rc::Rc};
+use std::path::Path;
+
+/// Distance to the camera below which art counts as near if its state sets no `proximity`.
+#[cfg(feature = "rhai")]
+const DEFAULT_PROXIMITY: f32 = 2.;
+/// Operations a function can run in a frame before it is stopped, so that an endless loop
+/// does not hang the gallery.
+#[cfg(feature = "rhai")]
+const MAX_OPERATIONS: u64 = 1_000_000;
+/// What a function sets for the art, they are unset before every frame.
+#[cfg(feature = "rhai")]
+const OUTPUTS: [&str; 7] =
+    ["translate", "rotate_x", "rotate_y", "rotate_z", "scale", "params", "morph"];
+
+/// Moves art and drives its parameters every frame, so that motion can be designed without
+/// recompiling.
+///
+/// Scripts are written in [Rhai](https://rhai.rs) and need the `rhai` feature. A script
+/// registers a function for every scripted art piece with `art(name, function, state)`:
+/// ```text
+/// // the sponge turns faster with every visit
+/// art("Menger Sponge", "sponge", #{ proximity: 3.0, visits: 0 });
+///
+/// fn sponge() {
+///     if this.entered { this.visits += 1; }
+///     this.rotate_y = this.time * 10.0 * (1 + this.visits);
+///     this.translate = [0, 0.1 * sin(this.time), 0];
+///     this.params = [this.near_time, this.visits, 0, 0];
+/// }
+/// ```
+/// The function runs every frame with `this` bound to the state of the art, which keeps its
+/// values between frames and starts as the optional map. The state is given the shader `time`,
+/// the `delta` since the last frame, the `distance` of the camera to the art, whether the camera
+/// is `near`, that is closer than `proximity`, the `near_time` in seconds since it came near,
+/// and the events `entered` and `left`, which are true in the frame the camera came near or
+/// went away. Besides the functions of Rhai `mix`, `smoothstep` and `clamp` work on floats.
+///
+/// Afterwards the art is scaled by `scale`, a number or a vector, rotated by `rotate_x`,
+/// `rotate_y` and `rotate_z` in degrees and translated by `translate` in the gallery.
+/// `params` replaces the free parameters of the art and `morph` the weights of its morph targets.
+#[cfg(feature = "rhai")]
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    arts: Vec<ArtScript>,
+}
+
+/// Scripts need the `rhai` feature, without it they fail to load.
+#[cfg(not(feature = "rhai"))]
+pub struct Script;
+
+#[cfg(feature = "rhai")]
+struct ArtScript {
+    name: String,
+    function: String,
+    proximity: f32,
+    /// The map bound to `this` in the function.
+    state: Map,
+    /// The time the camera came near the art if it is near.
+    near_since: Option<f32>,
+    /// Set once the function failed, the art is no longer scripted.
+    failed: bool,
+}
+
+/// What the script of an art piece reads in a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtInput {
+    pub time: f32,
+    pub delta: f32,
+    /// Distance of the camera to the origin of the art.
+    pub distance: f32,
+}
+
+/// What the script of an art piece set in a frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtMotion {
+    pub translation: Vector3,
+    /// Rotations around the x, y and z axes applied in this order.
+    pub rotation: [Deg<f32>; 3],
+    pub scale: Vector3,
+    pub params: Option<Vector4>,
+    pub morph_weights: Option<Vector4>,
+}
+
+impl ArtMotion {
+    /// Moves art with the model matrix `model`.
+    ///
+    /// The art is scaled and rotated around its own origin before it is translated in the gallery.
+    pub fn model_matrix(&self, model: Matrix4) -> Matrix4 {
+        let [x, y, z] = self.rotation;
+        Matrix4::from_translation(self.translation)
+            * model
+            * Matrix4::from_angle_z(z)
+            * Matrix4::from_angle_y(y)
+            * Matrix4::from_angle_x(x)
+            * Matrix4::from_diag(Vector4::from([self.scale[0], self.scale[1], self.scale[2], 1.]))
+    }
+}
+
+impl Script {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to parse script {}", path.display()))
+    }
+}
+
+#[cfg(feature = "rhai")]
+impl Script {
+    /// Compiles the script and runs it once to register the functions of the art.
+    pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.register_fn("mix", |a: FLOAT, b: FLOAT, t: FLOAT| a + (b - a) * t);
+        engine.register_fn("smoothstep", |edge0: FLOAT, edge1: FLOAT, x: FLOAT| {
+            let t = ((x - edge0) / (edge1 - edge0)).clamp(0., 1.);
+            t * t * (3. - 2. * t)
+        });
+        engine.register_fn("clamp", |x: FLOAT, min: FLOAT, max: FLOAT| x.max(min).min(max));
+
+        // art can only be registered while the script runs the first time
+        let registered = Rc::new(RefCell::new(Some(Vec::new())));
+        let register = |registered: &Rc<RefCell<Option<Vec<_>>>>| {
+            let registered = registered.clone();
+            move |name: &str, function: &str, state: Map| -> Result<(), Box<rhai::EvalAltResult>> {
+                let mut registered = registered.borrow_mut();
+                let arts = registered.as_mut().ok_or("art is only allowed outside of functions")?;
+                arts.push((name.to_owned(), function.to_owned(), state));
+                Ok(())
+            }
+        };
+        let with_state = register(&registered);
+        let without_state = register(&registered);
+        engine.register_fn("art", with_state);
+        engine.register_fn("art", move |name: &str, function: &str| {
+            without_state(name, function, Map::new())
+        });
+
+        let ast = engine.compile(text).map_err(|err| anyhow!("{err}"))?;
+        engine.run_ast(&ast).map_err(|err| anyhow!("{err}"))?;
+        let registered = registered.borrow_mut().take().unwrap_or_default();
+        let mut arts = Vec::with_capacity(registered.len());
+        for (name, function, state) in registered {
+            let exists = ast.iter_functions()
+                .any(|f| f.name == function && f.params.is_empty());
+            anyhow::ensure!(exists, "{name} needs a function {function}() without parameters");
+            let proximity = match state.get("proximity") {
+                Some(value) => number(value).context("proximity has to be a number")?,
+                None => DEFAULT_PROXIMITY,
+            };
+            arts.push(ArtScript {
+                name,
+                function,
+                proximity,
+                state,
+                near_since: None,
+                failed: false,
+            });
+        }
+        Ok(Self { engine, ast, arts })
+    }
+
+    /// The names of the scripted art.
+    pub fn art_names(&self) -> impl Iterator<Item = &str> {
+        self.arts.iter().map(|art| art.name.as_str())
+    }
+
+    /// Runs the function of the art called `name` for one frame.
+    ///
+    /// Returns `None` if the art has no function or its function failed before. A function
+    /// that fails is not run again.
+    pub fn run(&mut self, name: &str, input: ArtInput) -> Result<Option<ArtMotion>, anyhow::Error> {
+        let Some(art) = self.arts.iter_mut().find(|art| art.name == name && !art.failed) else {
+            return Ok(None);
+        };
+        let result = art.run(&self.engine, &self.ast, input);
+        art.failed = result.is_err();
+        result.map(Some)
+    }
+}
+
+#[cfg(not(feature = "rhai"))]
+impl Script {
+    pub fn parse(_text: &str) -> Result<Self, anyhow::Error> {
+        anyhow::bail!("Scripts are run with Rhai, build with the rhai feature to run them")
+    }
+
+    /// The names of the scripted art.
+    pub fn art_names(&self) -> impl Iterator<Item = &str> {
+        std::iter::empty()
+    }
+
+    /// Runs the function of the art called `name` for one frame.
+    pub fn run(
+        &mut self,
+        _name: &str,
+        _input: ArtInput,
+    ) -> Result<Option<ArtMotion>, anyhow::Error> {
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "rhai")]
+impl ArtScript {
+    fn run(
+        &mut self,
+        engine: &Engine,
+        ast: &AST,
+        input: ArtInput,
+    ) -> Result<ArtMotion, anyhow::Error> {
+        let was_near = self.near_since.is_some();
+        let near = input.distance < self.proximity;
+        if near && !was_near {
+            self.near_since = Some(input.time);
+        } else if !near {
+            self.near_since = None;
+        }
+        let near_time = self.near_since.map_or(0., |since| (input.time - since).max(0.));
+        for output in OUTPUTS {
+            self.state.remove(output);
+        }
+        let inputs = [
+            ("time", input.time.into()),
+            ("delta", input.delta.into()),
+            ("distance", input.distance.into()),
+            ("near", near.into()),
+            ("near_time", near_time.into()),
+            ("entered", (near && !was_near).into()),
+            ("left", (!near && was_near).into()),
+        ];
+        self.state.extend(inputs.map(|(name, value): (&str, Dynamic)| (name.into(), value)));
+
+        let mut this = Dynamic::from_map(std::mem::take(&mut self.state));
+        let options = rhai::CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        // what the function returns is ignored, it sets the outputs in the state
+        let _: Dynamic = engine
+            .call_fn_with_options(options, &mut rhai::Scope::new(), ast, &self.function, ())
+            .map_err(|err| anyhow!("{err}"))?;
+        self.state = this.try_cast().context("this has to stay a map")?;
+
+        let angle = |name| -> Result<Deg<f32>, anyhow::Error> {
+            Ok(Deg(output::<1>(&self.state, name)?.map_or(0., |[value]| value)))
+        };
+        let scale = match self.state.get("scale").and_then(number) {
+            Some(scale) => [scale; 3],
+            None => output(&self.state, "scale")?.unwrap_or([1.; 3]),
+        };
+        Ok(ArtMotion {
+            translation: output(&self.state, "translate")?.unwrap_or_default().into(),
+            rotation: [angle("rotate_x")?, angle("rotate_y")?, angle("rotate_z")?],
+            scale: scale.into(),
+            params: output(&self.state, "params")?.map(Vector4::from),
+            morph_weights: output(&self.state, "morph")?.map(Vector4::from),
+        })
+    }
+}
+
+/// An integer or a float as float.
+#[cfg(feature = "rhai")]
+fn number(value: &Dynamic) -> Option<f32> {
+    value.as_float().ok().or_else(|| value.as_int().ok().map(|value| value as f32))
+}
+
+/// The value of the output `name` if it was set, which must have `N` components.
+#[cfg(feature = "rhai")]
+fn output<const N: usize>(state: &Map, name: &str) -> Result<Option<[f32; N]>, anyhow::Error> {
+    let Some(value) = state.get(name) else {
+        return Ok(None);
+    };
+    let values = match value.as_array_ref() {
+        Ok(values) => values.iter().map(number).collect::<Option<Vec<_>>>(),
+        Err(_) => number(value).map(|value| vec![value]),
+    };
+    let values = values.with_context(|| format!("{name} has to be numbers"))?;
+    let values = values.try_into().map_err(|values: Vec<_>| {
+        anyhow!("{name} needs {N} components instead of {}", values.len())
+    })?;
+    Ok(Some(values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "rhai")]
+    const SCRIPT: &str = r#"
+        // a comment
+        art("Sponge", "sponge", #{ proximity: 3, visits: 0 });
+
+        fn sponge() {
+            if this.entered { this.visits += 1; }
+            this.rotate_y = if this.visits > 0 { 90 } else { 0 };
+            this.scale = 2;
+            this.translate = [0, -this.distance, 1 + 2 * 3];
+            this.params = [this.near_time, this.visits, clamp(this.distance, 0.0, 1.0),
+                mix(0.0, 10.0, 0.5)];
+        }
+    "#;
+
+    #[cfg(feature = "rhai")]
+    #[test]
+    fn run_script() {
+        let mut script = Script::parse(SCRIPT).unwrap();
+        assert_eq!(script.art_names().collect::<Vec<_>>(), ["Sponge"]);
+        let input = |time, distance| ArtInput { time, delta: 0.1, distance };
+        assert_eq!(script.run("Mandelbrot", input(0., 1.)).unwrap(), None);
+
+        let far = script.run("Sponge", input(0., 5.)).unwrap().unwrap();
+        assert_eq!(far.rotation[1], Deg(0.));
+        assert_eq!(far.scale, Vector3::from([2.; 3]));
+        assert_eq!(far.translation, Vector3::from([0., -5., 7.]));
+        assert_eq!(far.params, Some(Vector4::from([0., 0., 1., 5.])));
+        assert_eq!(far.morph_weights, None);
+
+        script.run("Sponge", input(1., 2.)).unwrap();
+        let near = script.run("Sponge", input(1.5, 0.5)).unwrap().unwrap();
+        assert_eq!(near.rotation[1], Deg(90.));
+        assert_eq!(near.params, Some(Vector4::from([0.5, 1., 0.5, 5.])));
+    }
+
+    #[cfg(feature = "rhai")]
+    #[test]
+    fn reject_invalid_scripts() {
+        assert!(Script::parse("art(\"Sponge\", \"sponge\");").is_err());
+        assert!(Script::parse("art(\"Sponge\", \"sponge\");\nfn sponge() { 1 + }").is_err());
+        assert!(Script::parse("art(\"Sponge\", \"sponge\", #{ proximity: \"far\" });\n\
+            fn sponge() {}").is_err());
+
+        // failing functions are not run again
+        let mut script =
+            Script::parse("art(\"Sponge\", \"sponge\");\nfn sponge() { this.translate = [1, 2]; }")
+                .unwrap();
+        let input = ArtInput { time: 0., delta: 0., distance: 1. };
+        assert!(script.run("Sponge", input).is_err());
+        assert_eq!(script.run("Sponge", input).unwrap(), None);
+
+        // endless loops are stopped
+        let mut script = Script::parse("art(\"Sponge\", \"sponge\");\nfn sponge() { loop {} }")
+            .unwrap();
+        assert!(script.run("Sponge", input).is_err());
+    }
+
+    #[cfg(not(feature = "rhai"))]
+    #[test]
+    fn scripts_need_rhai() {
+        let err = Script::parse("art(\"Sponge\", \"sponge\");").err().unwrap();
+        assert!(err.to_string().contains("rhai feature"), "{err}");
+    }
+}
@@ -0,0 +1,98 @@
+use crate::json::Json;
+
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// What the gallery looked like when it was closed, so that it reopens the same way.
+///
+/// It is saved as JSON, e.g.
+/// ```text
+/// {"size":[1280,720],"position":[40,60],"monitor":"DP-1","fullscreen":false,
+///  "image":"assets/images/photo.jpg"}
+/// ```
+/// Everything is optional, a session from an older version loads what it has.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Session {
+    /// The inner size of the window when it was not fullscreen.
+    pub size: Option<[u32; 2]>,
+    /// The outer position of the window when it was not fullscreen, not every platform has one.
+    pub position: Option<[i32; 2]>,
+    /// The name of the monitor the window was on.
+    pub monitor: Option<String>,
+    pub fullscreen: bool,
+    /// The image of the carousel that was shown last.
+    pub image: Option<PathBuf>,
+}
+
+impl Session {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        let json = Json::parse(text)?;
+        let size = json["size"].as_f32_array::<2>()
+            .filter(|size| size.iter().all(|&side| side >= 1.))
+            .map(|size| size.map(|side| side as u32));
+        let position = json["position"].as_f32_array::<2>()
+            .map(|position| position.map(|coord| coord as i32));
+        Ok(Self {
+            size,
+            position,
+            monitor: json["monitor"].as_str().map(str::to_owned),
+            fullscreen: json["fullscreen"].as_bool().unwrap_or_default(),
+            image: json["image"].as_str().map(PathBuf::from),
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), anyhow::Error> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_json().to_string())
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn to_json(&self) -> Json {
+        let numbers = |values: &[f64]| {
+            Json::Array(values.iter().map(|&value| Json::Number(value)).collect())
+        };
+        let mut members = Vec::new();
+        if let Some([width, height]) = self.size {
+            members.push(("size".to_owned(), numbers(&[width.into(), height.into()])));
+        }
+        if let Some([x, y]) = self.position {
+            members.push(("position".to_owned(), numbers(&[x.into(), y.into()])));
+        }
+        if let Some(monitor) = self.monitor.as_ref() {
+            members.push(("monitor".to_owned(), Json::String(monitor.clone())));
+        }
+        members.push(("fullscreen".to_owned(), Json::Bool(self.fullscreen)));
+        // a path that is not UTF-8 is not saved, the first image is shown then
+        if let Some(image) = self.image.as_ref().and_then(|image| image.to_str()) {
+            members.push(("image".to_owned(), Json::String(image.to_owned())));
+        }
+        Json::Object(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn save_and_load() {
+        let session = Session {
+            size: Some([1280, 720]),
+            position: Some([-1920, 40]),
+            monitor: Some("DP-1".to_owned()),
+            fullscreen: true,
+            image: Some(PathBuf::from("assets/images/photo.jpg")),
+        };
+        assert_eq!(Session::parse(&session.to_json().to_string()).unwrap(), session);
+        assert_eq!(Session::parse("{}").unwrap(), Session::default());
+        assert_eq!(Session::parse(r#"{"size": [0, 720]}"#).unwrap().size, None);
+        assert!(Session::parse("[").is_err());
+    }
+}
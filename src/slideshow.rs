@@ -0,0 +1,156 @@
+use crate::math::{Deg, Vector3};
+
+use std::f32::consts::TAU;
+
+/// A place the camera can look at an art piece from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewpoint {
+    /// The name of the art seen from here.
+    pub name: String,
+    pub position: Vector3,
+    /// The yaw of the camera, the pitch is zero.
+    pub yaw: Deg<f32>,
+}
+
+impl Viewpoint {
+    /// A viewpoint at `distance` from `target` in the horizontal `direction` looking back at it.
+    pub fn facing(name: String, target: Vector3, direction: Vector3, distance: f32) -> Self {
+        let direction = Vector3::from([direction.x(), 0., direction.z()]).normalize();
+        Self {
+            name,
+            position: target + direction * distance,
+            // a yaw of zero looks along -z
+            yaw: Deg(-direction.x().atan2(direction.z()).to_degrees()),
+        }
+    }
+}
+
+/// A camera path circling the center of the gallery while looking at it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Orbit {
+    /// Seconds for one round.
+    pub period: f32,
+    /// Distance from the center of the gallery.
+    pub radius: f32,
+    pub height: f32,
+}
+
+impl Orbit {
+    /// The position and the yaw of the camera `time` seconds into the orbit.
+    pub fn camera(&self, time: f32) -> (Vector3, Deg<f32>) {
+        let angle = time / self.period * TAU;
+        let position = Vector3::from([
+            angle.sin() * self.radius,
+            self.height,
+            angle.cos() * self.radius,
+        ]);
+        (position, Deg(-angle.to_degrees()))
+    }
+}
+
+/// Shows the viewpoints one after another for `interval` seconds each,
+/// fading to black and back in between.
+#[derive(Debug, Clone)]
+pub struct Slideshow {
+    viewpoints: Vec<Viewpoint>,
+    interval: f32,
+    /// Seconds it takes to fade out and in again.
+    fade_duration: f32,
+    /// The time the first viewpoint was shown.
+    start: f32,
+}
+
+impl Slideshow {
+    /// Returns `None` if there are no viewpoints.
+    pub fn new(
+        viewpoints: Vec<Viewpoint>,
+        interval: f32,
+        fade_duration: f32,
+        start: f32,
+    ) -> Option<Self> {
+        (!viewpoints.is_empty()).then(|| Self {
+            viewpoints,
+            interval: interval.max(f32::EPSILON),
+            fade_duration: fade_duration.clamp(0., interval),
+            start,
+        })
+    }
+
+    /// The viewpoint shown at `time` and the opacity of the black it is faded to.
+    ///
+    /// The camera moves to the next viewpoint while the screen is black.
+    pub fn at(&self, time: f32) -> (&Viewpoint, f32) {
+        let elapsed = (time - self.start).max(0.);
+        let index = (elapsed / self.interval) as usize % self.viewpoints.len();
+        let local = elapsed % self.interval;
+        let half = self.fade_duration / 2.;
+        let fade = if half <= 0. {
+            0.
+        } else if local < half {
+            1. - local / half
+        } else {
+            ((local - (self.interval - half)) / half).max(0.)
+        };
+        (&self.viewpoints[index], fade.min(1.))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewpoint(name: &str) -> Viewpoint {
+        Viewpoint {
+            name: name.to_owned(),
+            position: Vector3::default(),
+            yaw: Deg(0.),
+        }
+    }
+
+    #[test]
+    fn face_the_target() {
+        // in front of art on the wall at x = 6 facing -x
+        let viewpoint = Viewpoint::facing(
+            "art".to_owned(),
+            [6., 1.5, 0.].into(),
+            [-1., 0.5, 0.].into(),
+            2.,
+        );
+        assert_eq!(viewpoint.position, Vector3::from([4., 1.5, 0.]));
+        assert_eq!(viewpoint.yaw, Deg(90.));
+
+        let viewpoint = Viewpoint::facing(
+            "art".to_owned(),
+            [0., 1.5, -3.].into(),
+            [0., 0., 1.].into(),
+            1.,
+        );
+        assert_eq!(viewpoint.position, Vector3::from([0., 1.5, -2.]));
+        assert_eq!(viewpoint.yaw, Deg(0.));
+    }
+
+    #[test]
+    fn orbit_looks_at_center() {
+        let orbit = Orbit { period: 4., radius: 2., height: 1.5 };
+        assert_eq!(orbit.camera(0.), (Vector3::from([0., 1.5, 2.]), Deg(0.)));
+        // a quarter round later the camera is at +x looking along -x
+        let (position, yaw) = orbit.camera(1.);
+        assert!((position - Vector3::from([2., 1.5, 0.])).magnitude() < 1e-5);
+        assert_eq!(yaw, Deg(-90.));
+    }
+
+    #[test]
+    fn cycle_with_fades() {
+        assert!(Slideshow::new(Vec::new(), 10., 2., 0.).is_none());
+        let slideshow = Slideshow::new(vec![viewpoint("a"), viewpoint("b")], 10., 2., 5.).unwrap();
+        // fades in from black
+        assert_eq!(slideshow.at(5.), (&viewpoint("a"), 1.));
+        assert_eq!(slideshow.at(5.5), (&viewpoint("a"), 0.5));
+        assert_eq!(slideshow.at(10.), (&viewpoint("a"), 0.));
+        // fades out before the next viewpoint
+        assert_eq!(slideshow.at(14.5), (&viewpoint("a"), 0.5));
+        assert_eq!(slideshow.at(15.5), (&viewpoint("b"), 0.5));
+        // starts over after the last viewpoint
+        assert_eq!(slideshow.at(25.), (&viewpoint("a"), 1.));
+    }
+}
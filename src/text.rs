@@ -0,0 +1,164 @@
+/// Width and height of a glyph of the built-in font in pixels.
+const GLYPH_SIZE: [u32; 2] = [5, 7];
+/// Width and height of the cell a glyph is drawn in, with the space to the next one.
+const CELL_SIZE: [u32; 2] = [6, 9];
+/// The empty pixels around the text.
+const PADDING: u32 = 2;
+
+/// The glyphs of the built-in font, each row from the top with its leftmost pixel in the
+/// highest of the five bits. Lowercase letters are drawn as uppercase ones.
+const GLYPHS: &[(char, [u8; 7])] = &[
+    (' ', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('!', [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04]),
+    ('"', [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00]),
+    ('#', [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A]),
+    ('%', [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03]),
+    ('\'', [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00]),
+    ('(', [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02]),
+    (')', [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08]),
+    ('*', [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00]),
+    ('+', [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00]),
+    (',', [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08]),
+    ('-', [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00]),
+    ('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C]),
+    ('/', [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00]),
+    ('0', [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E]),
+    ('1', [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('2', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F]),
+    ('3', [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E]),
+    ('4', [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02]),
+    ('5', [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E]),
+    ('6', [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E]),
+    ('7', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08]),
+    ('8', [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E]),
+    ('9', [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C]),
+    (':', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00]),
+    (';', [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08]),
+    ('<', [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02]),
+    ('=', [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00]),
+    ('>', [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08]),
+    ('?', [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04]),
+    ('A', [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('B', [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E]),
+    ('C', [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E]),
+    ('D', [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C]),
+    ('E', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F]),
+    ('F', [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10]),
+    ('G', [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F]),
+    ('H', [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11]),
+    ('I', [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E]),
+    ('J', [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C]),
+    ('K', [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11]),
+    ('L', [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F]),
+    ('M', [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11]),
+    ('N', [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11]),
+    ('O', [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('P', [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10]),
+    ('Q', [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D]),
+    ('R', [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11]),
+    ('S', [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E]),
+    ('T', [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+    ('U', [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E]),
+    ('V', [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04]),
+    ('W', [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A]),
+    ('X', [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11]),
+    ('Y', [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04]),
+    ('Z', [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F]),
+    ('[', [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E]),
+    (']', [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E]),
+    ('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F]),
+    ('|', [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04]),
+];
+
+/// The rows of `c`, characters the font does not have are drawn as a question mark.
+fn glyph(c: char) -> [u8; 7] {
+    let c = c.to_ascii_uppercase();
+    GLYPHS.iter()
+        .find(|(glyph, _)| *glyph == c)
+        .or_else(|| GLYPHS.iter().find(|(glyph, _)| *glyph == '?'))
+        .map(|(_, rows)| *rows)
+        .unwrap_or_default()
+}
+
+/// Lines of text drawn with the built-in font into one bit per pixel,
+/// e.g. to be shown as an overlay without loading a font.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextBitmap {
+    size: [u32; 2],
+    /// Row after row from the top, the lowest bit of a word is its leftmost pixel.
+    bits: Vec<u32>,
+}
+
+impl TextBitmap {
+    /// Draws `lines` with at most `max_columns` characters each, longer lines are cut off.
+    pub fn new<S: AsRef<str>>(lines: &[S], max_columns: usize) -> Self {
+        let columns = lines.iter()
+            .map(|line| line.as_ref().chars().count().min(max_columns))
+            .max()
+            .unwrap_or(0) as u32;
+        let width = columns * CELL_SIZE[0] + 2 * PADDING;
+        let height = lines.len() as u32 * CELL_SIZE[1] + 2 * PADDING;
+        let mut bitmap = Self {
+            size: [width, height],
+            bits: vec![0; (width * height).div_ceil(32) as usize],
+        };
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.as_ref().chars().take(max_columns).enumerate() {
+                let x = PADDING + column as u32 * CELL_SIZE[0];
+                let y = PADDING + row as u32 * CELL_SIZE[1];
+                bitmap.draw_glyph(x, y, glyph(c));
+            }
+        }
+        bitmap
+    }
+
+    fn draw_glyph(&mut self, x: u32, y: u32, rows: [u8; 7]) {
+        let [glyph_width, _] = GLYPH_SIZE;
+        for (dy, row) in rows.into_iter().enumerate() {
+            for dx in 0..glyph_width {
+                if row & (1 << (glyph_width - 1 - dx)) != 0 {
+                    let index = (y + dy as u32) * self.size[0] + x + dx;
+                    self.bits[index as usize / 32] |= 1 << (index % 32);
+                }
+            }
+        }
+    }
+
+    /// Width and height in pixels.
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn bits(&self) -> &[u32] {
+        &self.bits
+    }
+
+    pub fn is_set(&self, x: u32, y: u32) -> bool {
+        let index = y * self.size[0] + x;
+        self.bits[index as usize / 32] & (1 << (index % 32)) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draw_text() {
+        let bitmap = TextBitmap::new(&["i-", "ok"], 80);
+        assert_eq!(bitmap.size(), [2 * 6 + 4, 2 * 9 + 4]);
+        // the top bar of the I and the middle of the dash
+        assert!((1..4).all(|x| bitmap.is_set(PADDING + x, PADDING)));
+        assert!((0..5).all(|x| bitmap.is_set(PADDING + 6 + x, PADDING + 3)));
+        assert!(!bitmap.is_set(0, 0));
+        assert!(!bitmap.is_set(PADDING + 5, PADDING + 3));
+    }
+
+    #[test]
+    fn cut_off_long_lines() {
+        let bitmap = TextBitmap::new(&["abcdef"], 3);
+        assert_eq!(bitmap.size()[0], 3 * 6 + 4);
+        assert_eq!(glyph('~'), glyph('?'));
+        assert_eq!(glyph('a'), glyph('A'));
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Context};
+use std::{
+    io::{self, Read},
+    path::Path,
+    process::{Child, ChildStdout, Command, Stdio},
+    sync::mpsc::{self, Receiver, SyncSender, TryRecvError},
+    thread::{self, JoinHandle},
+};
+
+/// Number of decoded frames waiting to be shown before the decoder pauses.
+const FRAME_QUEUE_LEN: usize = 4;
+
+/// The dimensions and the frame rate of the video stream of a file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoInfo {
+    pub width: u32,
+    pub height: u32,
+    pub fps: f32,
+}
+
+impl VideoInfo {
+    /// Runs ffprobe to find the first video stream of `path`.
+    ///
+    /// Fails if ffprobe can not be started, it has to be in the `PATH`.
+    pub fn probe(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let output = Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args(["-show_entries", "stream=width,height,avg_frame_rate", "-of", "csv=p=0"])
+            .arg(path)
+            .output()
+            .context("Failed to start ffprobe, is it installed?")?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "ffprobe failed with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim(),
+            ));
+        }
+        let text = String::from_utf8_lossy(&output.stdout);
+        Self::parse(&text).with_context(|| format!("No video stream in {}", path.display()))
+    }
+
+    /// Parses the output of ffprobe like `1920,1080,30000/1001`.
+    fn parse(text: &str) -> Result<Self, anyhow::Error> {
+        let line = text.lines().next().context("Empty output")?.trim();
+        let mut fields = line.split(',');
+        let mut next = || fields.next().with_context(|| format!("Invalid output {line:?}"));
+        let width = next()?.parse().context("Invalid width")?;
+        let height = next()?.parse().context("Invalid height")?;
+        let rate = next()?;
+        let fps = match rate.split_once('/') {
+            Some((num, den)) => num.parse::<f32>()? / den.parse::<f32>()?,
+            None => rate.parse()?,
+        };
+        anyhow::ensure!(width > 0 && height > 0, "Invalid dimensions {width}x{height}");
+        anyhow::ensure!(fps.is_finite() && fps > 0., "Invalid frame rate {rate}");
+        Ok(Self { width, height, fps })
+    }
+
+    /// Bytes of a decoded RGBA frame.
+    pub fn frame_size(&self) -> usize {
+        self.width as usize * self.height as usize * 4
+    }
+}
+
+/// Decodes a video file with an ffmpeg child process and hands out the frame that is due.
+///
+/// The frames are read on a separate thread, ffmpeg waits while enough frames are queued.
+/// The video loops forever.
+pub struct VideoDecoder {
+    info: VideoInfo,
+    /// The time the first frame was shown.
+    start: Option<f32>,
+    /// The latest frame and its number counted from the start.
+    frame: Option<(u64, Vec<u8>)>,
+    receiver: Option<Receiver<Vec<u8>>>,
+    reader: Option<JoinHandle<io::Result<()>>>,
+    ffmpeg: Child,
+}
+
+impl VideoDecoder {
+    /// Starts decoding the video at `path` into RGBA frames with y pointing up
+    /// like other textures.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self, anyhow::Error> {
+        let path = path.as_ref();
+        let info = VideoInfo::probe(path)?;
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-loglevel", "error", "-stream_loop", "-1", "-i"])
+            .arg(path)
+            .args(["-an", "-vf", "vflip", "-f", "rawvideo", "-pix_fmt", "rgba", "-"])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .spawn()
+            .context("Failed to start ffmpeg, is it installed?")?;
+        let stdout = ffmpeg.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::sync_channel(FRAME_QUEUE_LEN);
+        let frame_size = info.frame_size();
+        let reader = thread::spawn(move || Self::read_frames(stdout, frame_size, sender));
+        log::info!(
+            "Playing the {}x{} video {} at {} fps",
+            info.width,
+            info.height,
+            path.display(),
+            info.fps,
+        );
+        Ok(Self {
+            info,
+            start: None,
+            frame: None,
+            receiver: Some(receiver),
+            reader: Some(reader),
+            ffmpeg,
+        })
+    }
+
+    fn read_frames(
+        mut stdout: ChildStdout,
+        frame_size: usize,
+        sender: SyncSender<Vec<u8>>,
+    ) -> io::Result<()> {
+        loop {
+            let mut frame = vec![0; frame_size];
+            stdout.read_exact(&mut frame)?;
+            if sender.send(frame).is_err() {
+                // the decoder was dropped
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn info(&self) -> VideoInfo {
+        self.info
+    }
+
+    /// Advances the video to `time` in seconds and returns the frame that is shown then
+    /// together with its number.
+    ///
+    /// Frames that were missed are skipped, if the decoder falls behind the last frame is shown
+    /// for longer. `None` until the first frame is decoded.
+    pub fn frame(&mut self, time: f32) -> Option<(u64, &[u8])> {
+        let start = *self.start.get_or_insert(time);
+        let due = frame_due(time - start, self.info.fps);
+        if let Some(receiver) = self.receiver.as_ref() {
+            while self.frame.as_ref().is_none_or(|(number, _)| *number < due) {
+                match receiver.try_recv() {
+                    Ok(frame) => {
+                        let number = self.frame.as_ref().map_or(0, |(number, _)| number + 1);
+                        self.frame = Some((number, frame));
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.close();
+                        break;
+                    }
+                }
+            }
+        }
+        self.frame.as_ref().map(|(number, frame)| (*number, frame.as_slice()))
+    }
+
+    /// Stops ffmpeg, the last frame stays.
+    fn close(&mut self) {
+        self.receiver = None;
+        // ffmpeg does not stop by itself because the video loops
+        if let Err(err) = self.ffmpeg.kill() {
+            log::warn!("Failed to stop ffmpeg: {err}");
+        }
+        let _ = self.ffmpeg.wait();
+        match self.reader.take().map(JoinHandle::join) {
+            Some(Ok(Err(err))) if err.kind() != io::ErrorKind::UnexpectedEof => {
+                log::error!("Failed to read frames from ffmpeg: {err}");
+            }
+            Some(Err(_)) => log::error!("Frame reader panicked"),
+            _ => {}
+        }
+    }
+}
+
+impl Drop for VideoDecoder {
+    fn drop(&mut self) {
+        if self.reader.is_some() {
+            self.close();
+        }
+    }
+}
+
+/// Number of the frame of a video at `fps` that is shown once `elapsed` seconds passed
+/// since its first frame.
+fn frame_due(elapsed: f32, fps: f32) -> u64 {
+    (elapsed.max(0.) * fps) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_probe() {
+        let info = VideoInfo::parse("1920,1080,30000/1001\n").unwrap();
+        assert_eq!([info.width, info.height], [1920, 1080]);
+        assert!((info.fps - 29.97).abs() < 0.01);
+        assert_eq!(VideoInfo::parse("640,480,25").unwrap().fps, 25.);
+        assert!(VideoInfo::parse("").is_err());
+        assert!(VideoInfo::parse("640,480").is_err());
+        assert!(VideoInfo::parse("640,480,0/0").is_err());
+        assert_eq!(frame_due(1., 24.), 24);
+        assert_eq!(frame_due(-1., 24.), 0);
+    }
+}
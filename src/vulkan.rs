@@ -1,15 +1,58 @@
 mod app;
 mod buffer;
+mod clusters;
 mod cmd;
+mod compiler;
 mod context;
+mod crash;
 mod debug;
+mod descriptor;
+mod dynamic_resolution;
+mod feedback;
 mod geometry;
+mod grade;
+mod half_res;
+mod occlusion;
+mod overlay;
 mod pipeline;
+mod projector;
+mod raytracing;
+mod render_graph;
+mod renderer;
+mod resources;
+mod rust_gpu;
+mod sampler;
+mod scene;
 mod shader;
+mod shadows;
+mod skin;
+mod stereo;
+mod still;
+mod stream;
 mod structs;
 mod swapchain;
 mod texture;
+mod timer;
 mod vertex;
+mod volume;
+mod volumetric;
+#[cfg(feature = "openxr")]
+mod xr;
 
-pub use app::VkApp;
+pub use app::{VkApp, OVERLAY_MAX_COLUMNS, OVERLAY_MAX_ERROR_LINES, OVERLAY_MAX_LINES};
+pub use compiler::{default_compiler, include_path, Glslang, ShaderCompiler};
+#[cfg(feature = "shaderc")]
+pub use compiler::Shaderc;
+pub use context::{list_gpus, DeviceRequirements, GpuSelection, VkContext};
+pub use debug::{take_printf_messages, Validation, VALIDATION_ENV};
+pub use renderer::{ArtRenderer, FrameState, RenderTarget, ViewMatrices};
+pub use rust_gpu::RustGpuCrate;
+pub use sampler::{SamplerConfig, TextureSamplers};
+pub use scene::Scene;
 pub use shader::{Shader, Shaders, ShaderArt, ShaderInner};
+pub use stereo::StereoOutput;
+pub use structs::{ArtTime, Fog, Framing, Proximity, Sky};
+pub use timer::GpuTimes;
+pub use volume::Volume;
+#[cfg(feature = "openxr")]
+pub use xr::OpenXr;
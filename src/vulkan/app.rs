@@ -1,88 +1,287 @@
-use crate::fs;
-use crate::math::{self, Deg, Matrix4, Vector2, Vector3};
+use crate::animation::Animation;
+use crate::capture::{ArtLoop, CUBE_FACES};
+use crate::slideshow::Viewpoint;
+use crate::video::VideoDecoder;
+use crate::fs::{self, FileWatcher};
+use crate::gizmo::{GizmoMode, Ray};
+use crate::gltf::SkinnedModel;
+use crate::keyboard;
+use crate::lightmap::LightmapLayout;
+use crate::lights::{self, PointLight};
+use crate::lut::Lut;
+use crate::env_generator::Wall;
+use crate::math::{Deg, Matrix4, Rad, Projection, Vector2, Vector3, Vector4};
+use crate::noise;
 use crate::obj::NormalizedObj;
+use crate::post_effects::{PostEffect, PostEffects};
+use crate::quality::MAX_QUALITY;
+use crate::script::{ArtInput, Script};
+use crate::text::TextBitmap;
 use super::{
     buffer, cmd,
-    context::VkContext,
-    geometry::Geometry,
+    clusters::{ClusterView, LightClusters, MAX_LIGHTS},
+    context::{DeviceRequirements, GpuSelection, VkContext},
+    crash::{CrashMarkers, Marker},
+    feedback::PipelineFeedback,
+    geometry::{Geometry, MorphDeltas},
+    grade::GradeTarget,
+    half_res::HalfResArt,
+    occlusion::{CullBox, OcclusionCulling},
+    overlay::TextOverlay,
     debug::*,
+    descriptor::DescriptorAllocator,
+    dynamic_resolution::DynamicResolution,
     pipeline::{Pipeline, PipelineConfig},
-    shader::{Shader, Shaders},
-    structs::{PushConstants, UniformBufferObject},
-    swapchain::{SwapchainProperties, SwapchainSupportDetails},
+    projector::Projector,
+    raytracing::{AccelerationStructure, RayTracingPipeline, RAY_TRACING_EXTENT},
+    renderer::{
+        ArtRenderer, FrameInputs, FrameState, RenderTarget, Renderer, ScreenPipelines,
+        ViewMatrices,
+        PIPELINE_IDX_ART, PIPELINE_IDX_CUBE, PIPELINE_IDX_MAIN, PIPELINE_IDX_SKY,
+    },
+    resources::{AnimatedImage, ResourceManager, RuntimeShaders, Textures, VideoStream},
+    scene::Scene,
+    sampler::{SamplerConfig, TextureSamplers},
+    shader::{Shader, ShaderArt, ShaderWatcher, Shaders},
+    shadows::{self, ShadowCascades},
+    skin::{JointBuffer, Skin},
+    stereo::{StereoOutput, StereoTarget, EYE_COUNT},
+    still::{self, FrameReadback, StillCamera, StillTarget},
+    stream::StreamTexture,
+    structs::{ArtUniforms, FrameGraph, PushConstants, UniformBufferObject},
+    swapchain::SwapchainProperties,
     texture::Texture,
-    vertex::{Vertex, VertexColorCoords, VertexSimple},
+    timer::{GpuTimer, GpuTimes},
+    volume::Volume,
+    volumetric::{FogView, VolumetricFog},
+    vertex::{Vertex, VertexSimple, VertexSkinned, VertexTangents},
 };
+#[cfg(feature = "openxr")]
+use super::xr::OpenXr;
 
 use anyhow::Context;
 use ash::{
-    ext::debug_utils,
-    khr::{surface, swapchain as khr_swapchain},
-    vk, Device, Entry, Instance,
+    khr::surface,
+    prelude::VkResult,
+    vk, Device, Entry,
 };
-use image::ImageReader;
+use image::RgbaImage;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use std::{
-    ffi::CString,
-    mem::{align_of, size_of},
-    path::Path,
+    ffi::c_void,
+    mem::size_of,
+    path::{Path, PathBuf},
     sync::mpsc,
     thread,
 };
 use winit::window::Window;
 
-const MAX_FRAMES_IN_FLIGHT: u32 = 2;
 
-const _PIPELINE_IDX_MAIN: usize = 0;
-const PIPELINE_IDX_CUBE: usize = 1;
-const PIPELINE_IDX_ART: usize = 2;
+/// Number of art pieces that can be added while the gallery runs, see [`VkApp::add_art`].
+const ADDED_ART_CAPACITY: usize = 16;
+
+/// Distance of 2D art aligned to a wall from its side, so that they do not overlap.
+const WALL_OFFSET: f32 = 0.01;
+
+/// Art further away than this is shaded at a coarser rate.
+const COARSE_SHADING_DISTANCE: f32 = 8.;
+/// Art closer than this is always shaded at full rate, e.g. when standing inside a 3D art piece.
+const FULL_SHADING_DISTANCE: f32 = 2.;
+/// Art is shaded at a coarser rate if the cosine of its angle to the view direction is below this.
+const COARSE_SHADING_COS: f32 = 0.8;
+const COARSE_SHADING_RATE: vk::Extent2D = vk::Extent2D { width: 2, height: 2 };
+const FULL_SHADING_RATE: vk::Extent2D = vk::Extent2D { width: 1, height: 1 };
+
+/// The image the art can sample.
+const ART_IMAGE: &str = "assets/downloads/earth.jpg";
+/// The model of the skybox and of 3D art.
+const SKYBOX_MODEL: &str = "assets/cubemap/skybox.obj";
+/// The model of 2D art.
+const QUAD_MODEL: &str = "assets/models/quad.obj";
+/// A normal map texel pointing straight out of the surface.
+const FLAT_NORMAL: [u8; 4] = [128, 128, 255, 255];
+
+/// Distance between the left and the right eye when rendering in stereo.
+const EYE_SEPARATION: f32 = 0.064;
+
+/// Maximal number of views the screen can be split into, each with its own uniform buffer.
+const MAX_VIEWS: usize = 4;
+
+/// Index of the uniform buffer object of the previewed or entered art, after those of the views.
+pub const UBO_IDX_ART: usize = MAX_VIEWS;
+/// Index of the uniform buffer object of the art shown in the second window.
+const UBO_IDX_PROJECTOR: usize = UBO_IDX_ART + 1;
+
+/// The most lines and characters per line of the text overlay, the rest is cut off.
+pub const OVERLAY_MAX_LINES: usize = 12;
+pub const OVERLAY_MAX_COLUMNS: usize = 64;
+/// The most lines of compile errors shown in the text overlay.
+pub const OVERLAY_MAX_ERROR_LINES: usize = 32;
+const OVERLAY_TEXT_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
+const OVERLAY_ERROR_COLOR: [f32; 3] = [1., 0.3, 0.25];
+
+/// 2D art can be entered if it is closer than this
+/// and the cosine of its angle to the view direction is above `ENTER_ART_COS`.
+const ENTER_ART_DISTANCE: f32 = 4.;
+const ENTER_ART_COS: f32 = 0.9;
+/// Fraction of the samples of a pixel the art shades with sample shading enabled.
+const MIN_SAMPLE_SHADING: f32 = 1.;
+/// Largest side of the tiles a still is rendered in, keeps the multisampled attachments small.
+const STILL_TILE_SIZE: u32 = 2048;
 
 pub struct VkApp {
     pub dirty_swapchain: bool,
+    /// What is drawn and from where.
+    pub scene: Scene,
 
-    pub view_matrix: Matrix4,
-    model_matrix: Matrix4,
-    pub texture_weight: f32,
-
-    vk_context: VkContext,
-    graphics_queue: vk::Queue,
-    present_queue: vk::Queue,
-    swapchain: khr_swapchain::Device,
-    swapchain_khr: vk::SwapchainKHR,
-    swapchain_properties: SwapchainProperties,
-    images: Vec<vk::Image>,
-    swapchain_image_views: Vec<vk::ImageView>,
-    render_pass: vk::RenderPass,
+    /// The device, the swapchain, the pipelines and the frames in flight.
+    renderer: Renderer,
     descriptor_set_layout: vk::DescriptorSetLayout,
-    pipelines: Vec<Pipeline>,
-    swapchain_framebuffers: Vec<vk::Framebuffer>,
-    command_pool: vk::CommandPool,
-    transient_command_pool: vk::CommandPool,
-    msaa_samples: vk::SampleCountFlags,
-    color_texture: Texture,
-    depth_format: vk::Format,
-    depth_texture: Texture,
-    textures: Vec<Texture>,
-    uniform_buffers: Vec<vk::Buffer>,
-    uniform_buffer_memories: Vec<vk::DeviceMemory>,
-    descriptor_pool: vk::DescriptorPool,
+    /// The most art pieces the uniform buffer and the occlusion culling have room for.
+    art_capacity: usize,
+    /// The art shaders are compiled on another thread, see [`Shader::set_hot_reload`].
+    compile_sender: mpsc::Sender<Shader>,
+    /// Recompiles the shaders of art added while the gallery runs when their files change.
+    shader_watcher: ShaderWatcher,
+    /// The bounds of the cube raymarched 3D art is drawn with.
+    art_cube_bounds: [Vector3; 2],
+    /// The art drawn at half resolution and scaled up, see [`crate::ArtPiece::half_resolution`].
+    half_res_art: Vec<HalfResArt>,
+    ray_tracing_pipelines: Vec<RayTracingPipeline>,
+    /// Art drawn by downstream code after the shader art.
+    renderers: Vec<Box<dyn ArtRenderer>>,
+    acceleration_structures: Vec<AccelerationStructure>,
+    /// Renders both eyes with multiview if stereo rendering is enabled.
+    stereo_target: Option<StereoTarget>,
+    descriptor_sets_anaglyph: Vec<vk::DescriptorSet>,
+    /// Exists while a color lookup table is loaded, the floors reflect the gallery, it is
+    /// blurred or stylized, see [`Self::set_lut`], [`Self::set_reflections`],
+    /// [`Self::set_depth_of_field`], [`Self::set_motion_blur`] and [`Self::set_post_effects`].
+    grade_target: Option<GradeTarget>,
+    /// Reflects the image of `grade_target` and grades it with `lut` in the grade pipeline.
+    descriptor_sets_grade: Vec<vk::DescriptorSet>,
+    lut: Option<Texture>,
+    grade_strength: f32,
+    reflection_strength: f32,
+    /// Whether the depth of the gallery can be sampled for the depth of field
+    /// and the motion blur.
+    sampled_depth: bool,
+    dof_aperture: f32,
+    /// Where the focus is taken from, from 0 to 1 from the top left.
+    focus_point: Vector2,
+    shutter: f32,
+    /// The view and the projection of the last frame while blurring the motion.
+    previous_view_proj: Option<Matrix4>,
+    post_effects: PostEffects,
+    /// Moves the art every frame, reloaded when the file at `script_path` changes.
+    script: Option<Script>,
+    script_path: Option<PathBuf>,
+    /// The time of the last frame the script ran in.
+    script_time: Option<f32>,
+    has_lightmap: bool,
+    show_frame_graph: bool,
+    text_overlay: TextOverlay,
+    overlay_text: Option<TextBitmap>,
+    overlay_color: [f32; 3],
+    /// The times of the last frames shown in the frame graph.
+    pub frame_graph: FrameGraph,
+    /// A second window showing a single art piece.
+    projector: Option<Projector>,
+    /// A headset the eyes are shown in while rendering side by side.
+    #[cfg(feature = "openxr")]
+    openxr: Option<OpenXr>,
+    /// Measures the GPU time of the frames if enabled.
+    gpu_timer: Option<GpuTimer>,
+    /// Tells what was drawn when the device is lost if the driver supports it.
+    crash_markers: Option<CrashMarkers>,
+    /// The times of the last frame that was done drawing with the timer.
+    gpu_times: Option<GpuTimes>,
+    /// Scales the gallery to hold a frame rate with the GPU times of `gpu_timer`.
+    dynamic_resolution: Option<DynamicResolution>,
+    /// The quality level the art lowers its iteration counts with, see [`VkApp::set_quality`].
+    quality: u32,
+    /// The last frame that was done drawing while capturing frames.
+    captured_frame: Option<RgbaImage>,
+    procedural_sky: bool,
+    /// Shade the art per sample instead of per pixel, see `PipelineConfig::min_sample_shading`.
+    sample_shading: bool,
+    /// The textures the art samples and the files they come from.
+    resources: ResourceManager,
+    /// Whether the environment has its own texture coordinates, see
+    /// [`UniformBufferObject::texture_aspect`].
+    env_tex_coords: bool,
+    /// See [`UniformBufferObject::parallax_scale`].
+    parallax_scale: f32,
+    /// Holds the uniform buffer objects of all frames in flight one after another.
+    uniform_buffer: vk::Buffer,
+    uniform_buffer_memory: vk::DeviceMemory,
+    /// The uniform buffer stays mapped for the lifetime of the app.
+    uniform_buffer_ptr: *mut c_void,
+    /// Distance between the uniform buffer objects of the views of a frame.
+    ubo_stride: u32,
+    /// Distance between the uniforms of the art pieces of a frame.
+    art_uniform_stride: u32,
+    /// The parts of the uniform buffer of each frame, see [`Self::create_uniform_buffer`].
+    uniform_buffers: Vec<[vk::DescriptorBufferInfo; 2]>,
+    /// The views the command buffers were recorded with.
+    views: Vec<vk::Rect2D>,
+    descriptor_allocator: DescriptorAllocator,
     descriptor_sets_main: Vec<vk::DescriptorSet>,
-    command_buffers: Vec<vk::CommandBuffer>,
-    in_flight_frames: InFlightFrames,
+    descriptor_sets_cubemap: Vec<vk::DescriptorSet>,
+    /// Shared by the art that is not ray traced and has no volume.
+    descriptor_sets_art: Vec<vk::DescriptorSet>,
+    /// The descriptor sets of all art with a volume, they sample the art image like `descriptor_sets_art`.
+    descriptor_sets_volume: Vec<vk::DescriptorSet>,
+    /// The joint matrices of all skinned art, see [`Pipeline::skin`].
+    joint_buffer: JointBuffer,
+    /// Skips the art hidden behind the environment if it is supported and enabled.
+    occlusion: Option<OcclusionCulling>,
+    occlusion_culling: bool,
+    /// The point lights of the environment, reloaded when the file at `lights_path` changes.
+    lights: Vec<PointLight>,
+    lights_path: Option<PathBuf>,
+    light_clusters: LightClusters,
+    /// The shadows of the sun on the environment.
+    shadows: ShadowCascades,
+    /// The fog lit by the sun and the lights, see [`Self::set_volumetric_fog`].
+    fog_volume: VolumetricFog,
+    volumetric_fog: bool,
 }
 
 impl VkApp {
-    pub fn new<P: AsRef<Path>>(
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(
         window: &Window,
         window_dimensions: [u32; 2],
         image_path: P,
+        cubemap_dir: Q,
+        samplers: TextureSamplers,
+        frames_in_flight: usize,
+        preferred_image_count: Option<u32>,
         nobj: NormalizedObj,
         mut shaders: Shaders,
+        validation: Validation,
+        gpu: &GpuSelection,
+        openxr: bool,
     ) -> Result<Self, anyhow::Error> {
         log::debug!("Creating application.");
 
         let entry = unsafe { Entry::load().expect("Failed to create entry.") };
-        let instance = Self::create_instance(&entry, window);
+        // the runtime of a headset has a say in the extensions and the GPU
+        #[cfg(feature = "openxr")]
+        let openxr = openxr.then(OpenXr::new).and_then(|result| {
+            result.inspect_err(|err| log::error!("OpenXR is disabled: {err:#}")).ok()
+        });
+        #[cfg(feature = "openxr")]
+        let extra_extensions = openxr.as_ref().map(OpenXr::instance_extensions).unwrap_or_default();
+        #[cfg(not(feature = "openxr"))]
+        let extra_extensions = &[];
+        #[cfg(not(feature = "openxr"))]
+        if openxr {
+            log::error!("OpenXR is disabled, shaderpixel has to be built with the openxr feature");
+        }
+        let (instance, instance_extensions, validation) =
+            Renderer::create_instance(&entry, window, validation, extra_extensions);
 
         let surface = surface::Instance::new(&entry, &instance);
         let surface_khr = unsafe {
@@ -96,109 +295,296 @@ impl VkApp {
             .unwrap()
         };
 
-        let vk_context = VkContext::new(entry, instance, surface, surface_khr)
-            .context("Failed to create vulkan context")?;
-        let graphics_queue = unsafe {
-            vk_context.device().get_device_queue(vk_context.graphics_queue_index(), 0)
-        };
-        let present_queue = unsafe {
-            vk_context.device().get_device_queue(vk_context.present_queue_index(), 0)
-        };
-
-        let (swapchain, swapchain_khr, properties, images) =
-            Self::create_swapchain_and_images(&vk_context, window_dimensions);
-        let swapchain_image_views =
-            Self::create_swapchain_image_views(vk_context.device(), &images, properties);
-
-        let msaa_samples = vk_context.get_max_usable_sample_count();
-        log::debug!("Chosen msaa: {msaa_samples:?}");
-        let depth_format = Self::find_depth_format(&vk_context);
-
-        let render_pass =
-            Self::create_render_pass(vk_context.device(), properties, msaa_samples, depth_format);
-        let descriptor_set_layout = Self::create_descriptor_set_layout(vk_context.device());
-
-        let command_pool =
-            vk_context.create_command_pool(vk::CommandPoolCreateFlags::empty());
-        let transient_command_pool =
-            vk_context.create_command_pool(vk::CommandPoolCreateFlags::TRANSIENT);
-
-        let color_texture = Self::create_color_texture(
-            &vk_context,
-            command_pool,
-            graphics_queue,
-            properties,
-            msaa_samples,
-        );
-
-        let depth_texture = Self::create_depth_texture(
-            &vk_context,
-            command_pool,
-            graphics_queue,
-            depth_format,
-            properties.extent,
-            msaa_samples,
-        );
-
-        let swapchain_framebuffers = Self::create_framebuffers(
-            vk_context.device(),
-            &swapchain_image_views,
-            color_texture,
-            depth_texture,
-            render_pass,
-            properties,
+        #[allow(unused_mut)]
+        let mut requirements = DeviceRequirements::default();
+        #[cfg(feature = "openxr")]
+        let openxr = openxr.and_then(|xr| match xr.physical_device(&instance) {
+            Ok(physical_device) => {
+                requirements.physical_device = Some(physical_device);
+                requirements.extensions = xr.device_extensions().to_vec();
+                Some(xr)
+            }
+            Err(err) => {
+                log::error!("OpenXR is disabled: {err:#}");
+                None
+            }
+        });
+        let vk_context = VkContext::new(
+            entry,
+            instance,
+            surface,
+            surface_khr,
+            instance_extensions,
+            validation,
+            gpu,
+            &requirements,
+        ).context("Failed to create vulkan context")?;
+        let mut renderer = Renderer::new(
+            vk_context,
+            window_dimensions,
+            preferred_image_count,
+            frames_in_flight,
         );
+        let vk_context = &renderer.vk_context;
+        let (graphics_queue, command_pool, transient_command_pool) =
+            (renderer.graphics_queue, renderer.command_pool, renderer.transient_command_pool);
+        let properties = renderer.swapchain_properties;
+        let (msaa_samples, depth_format) = (renderer.msaa_samples, renderer.depth_format);
+        let (render_pass, composite_render_pass) =
+            (renderer.render_pass, renderer.composite_render_pass);
+
+        // the depth of field samples the depth the gallery is drawn with
+        let sampled_depth = vk_context
+            .find_supported_format(
+                &[depth_format],
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            )
+            .is_some()
+            && vk_context.physical_device_properties().limits
+                .sampled_image_depth_sample_counts
+                .contains(msaa_samples);
+
+        let supports_tessellation = vk_context.enabled_features().tessellation_shader == vk::TRUE;
+        let supports_mesh_shaders = vk_context.mesh_shader().is_some();
+        let supports_ray_tracing = vk_context.ray_tracing().is_some();
+        let supports_shading_rate = vk_context.fragment_shading_rate().is_some();
+        let mut ubo_stages = vk::ShaderStageFlags::VERTEX;
+        if supports_tessellation {
+            ubo_stages |= vk::ShaderStageFlags::TESSELLATION_CONTROL
+                | vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+        }
+        if supports_mesh_shaders {
+            ubo_stages |= vk::ShaderStageFlags::MESH_EXT;
+        }
+        let mut descriptor_allocator = DescriptorAllocator::new();
+        let descriptor_set_layout = Self::create_descriptor_set_layout(
+            vk_context,
+            &mut descriptor_allocator,
+            ubo_stages,
+        )?;
 
-        let texture = Self::create_texture_image(
-            &vk_context,
+        let (texture, image_extent) = ResourceManager::create_texture_image(
+            vk_context,
             command_pool,
             graphics_queue,
-            image_path,
+            &image_path,
+            samplers.image,
         ).unwrap();
-        let texture_art = Self::create_texture_image(
-            &vk_context,
+        let mut file_watcher = FileWatcher::new();
+        file_watcher.watch(&image_path);
+        file_watcher.watch(ART_IMAGE);
+        file_watcher.watch(SKYBOX_MODEL);
+        file_watcher.watch(QUAD_MODEL);
+        let (texture_art, art_extent) = ResourceManager::create_texture_image(
+            vk_context,
             command_pool,
             graphics_queue,
-            "assets/downloads/earth.jpg",
+            ART_IMAGE,
+            samplers.art,
         ).unwrap();
-        let texture_cubemap = Self::create_cubemap(
-            &vk_context,
+        // without a cubemap the procedural sky is drawn instead
+        let cubemaps = ResourceManager::create_cubemaps(
+            vk_context,
             command_pool,
             graphics_queue,
-            [
-                "assets/cubemap/left.png",
-                "assets/cubemap/right.png",
-                "assets/cubemap/top.png",
-                "assets/cubemap/bottom.png",
-                "assets/cubemap/back.png",
-                "assets/cubemap/front.png",
-            ],
-        ).unwrap();
+            cubemap_dir,
+            samplers.cubemap,
+        );
+        let (texture_cubemap, texture_cubemap_night, procedural_sky) = match cubemaps {
+            Ok((day, night)) => (day, night, false),
+            Err(err) => {
+                log::warn!("Using the procedural sky: {err:#}");
+                let black = || ResourceManager::create_cubemap(
+                    vk_context,
+                    command_pool,
+                    graphics_queue,
+                    vk::Extent2D { width: 1, height: 1 },
+                    vec![vec![0, 0, 0, 255]; 6],
+                    samplers.cubemap,
+                );
+                (black()?, black()?, true)
+            }
+        };
 
-        let (uniform_buffers, uniform_buffer_memories) =
-            Self::create_uniform_buffers(&vk_context, images.len());
+        shaders.shaders_art.retain(|shader| {
+            if shader.tessellation.is_some() && !supports_tessellation {
+                log::error!("Skipping {}, tessellation shaders are not supported", shader.name);
+                false
+            } else if shader.is_mesh() && !supports_mesh_shaders {
+                log::error!("Skipping {}, mesh shaders are not supported", shader.name);
+                false
+            } else if shader.ray_tracing.is_some() && !supports_ray_tracing {
+                log::error!("Skipping {}, ray tracing is not supported", shader.name);
+                false
+            } else {
+                true
+            }
+        });
+        // the uniforms of the art added while the gallery runs are part of every frame
+        let art_capacity = shaders.shaders_art.len() + ADDED_ART_CAPACITY;
+
+        let ubo_alignment = vk_context.physical_device_properties()
+            .limits
+            .min_uniform_buffer_offset_alignment as usize;
+        let ubo_stride = size_of::<UniformBufferObject>().next_multiple_of(ubo_alignment) as u32;
+        let art_uniform_stride = size_of::<ArtUniforms>().next_multiple_of(ubo_alignment) as u32;
+        let (uniform_buffer, uniform_buffer_memory, uniform_buffers) = Self::create_uniform_buffer(
+            vk_context,
+            frames_in_flight,
+            ubo_stride,
+            art_uniform_stride,
+            art_capacity,
+        );
+        let uniform_buffer_ptr = unsafe {
+            vk_context.device()
+                .map_memory(uniform_buffer_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map uniform buffer")?
+        };
 
-        let descriptor_pool = Self::create_descriptor_pool(vk_context.device(), images.len() as _);
+        let ray_traced_art_count = shaders.shaders_art.iter()
+            .filter(|shader| supports_ray_tracing && shader.ray_tracing.is_some())
+            .count();
         let descriptor_sets_main = Self::create_descriptor_sets(
             vk_context.device(),
-            descriptor_pool,
+            &mut descriptor_allocator,
             descriptor_set_layout,
             &uniform_buffers,
             texture,
-        );
+        )?;
         let descriptor_sets_cubemap = Self::create_descriptor_sets(
             vk_context.device(),
-            descriptor_pool,
+            &mut descriptor_allocator,
             descriptor_set_layout,
             &uniform_buffers,
             texture_cubemap,
+        )?;
+        Self::update_descriptor_sets_image(
+            vk_context.device(),
+            &descriptor_sets_cubemap,
+            2,
+            texture_cubemap_night,
         );
         let descriptor_sets_art = Self::create_descriptor_sets(
             vk_context.device(),
-            descriptor_pool,
+            &mut descriptor_allocator,
             descriptor_set_layout,
             &uniform_buffers,
             texture_art,
+        )?;
+        let texture_volume_empty = ResourceManager::create_volume_texture(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            &Volume::EMPTY,
+            SamplerConfig::CLAMP,
+        )?;
+        let texture_noise = ResourceManager::create_texture_from_rgba(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            vk::Extent2D { width: noise::SIZE_2D, height: noise::SIZE_2D },
+            &noise::texels_2d(),
+            SamplerConfig::REPEAT,
+        )?;
+        let texture_noise_3d = ResourceManager::create_texture_3d(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            [noise::SIZE_3D; 3],
+            &noise::texels_3d(),
+            SamplerConfig::REPEAT,
+        )?;
+        let keyboard_texture = StreamTexture::new(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            vk::Format::R8_UNORM,
+            1,
+            vk::Extent2D { width: keyboard::KEY_COUNT as u32, height: keyboard::ROWS as u32 },
+            SamplerConfig::NEAREST,
+            frames_in_flight,
+        )?;
+        // replaced by the frames of a video, see [`Self::set_video`]
+        let texture_video_empty = ResourceManager::create_texture_from_rgba(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            vk::Extent2D { width: 1, height: 1 },
+            &[0, 0, 0, 255],
+            SamplerConfig::CLAMP,
+        )?;
+        let update_art_descriptor_sets = |sets: &[vk::DescriptorSet], volume| {
+            Self::update_descriptor_sets_image(vk_context.device(), sets, 4, volume);
+            Self::update_descriptor_sets_image(vk_context.device(), sets, 5, texture_noise);
+            Self::update_descriptor_sets_image(vk_context.device(), sets, 6, texture_noise_3d);
+            Self::update_descriptor_sets_image(
+                vk_context.device(),
+                sets,
+                7,
+                keyboard_texture.texture(),
+            );
+            Self::update_descriptor_sets_image(vk_context.device(), sets, 8, texture_video_empty);
+        };
+        update_art_descriptor_sets(&descriptor_sets_art, texture_volume_empty);
+        // replaced by a normal map from a file, see [`Self::set_normal_map`]
+        let texture_normal_map = ResourceManager::create_texture_from_rgba(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            vk::Extent2D { width: 1, height: 1 },
+            &FLAT_NORMAL,
+            SamplerConfig::REPEAT,
+        )?;
+        Self::update_descriptor_sets_image(
+            vk_context.device(),
+            &descriptor_sets_main,
+            11,
+            texture_normal_map,
+        );
+        // only sampled once a lightmap is loaded, see [`Self::set_lightmap`]
+        let texture_lightmap = ResourceManager::create_texture_from_rgba(
+            vk_context,
+            command_pool,
+            graphics_queue,
+            vk::Extent2D { width: 1, height: 1 },
+            &[255; 4],
+            SamplerConfig::CLAMP,
+        )?;
+        Self::update_descriptor_sets_image(
+            vk_context.device(),
+            &descriptor_sets_main,
+            12,
+            texture_lightmap,
+        );
+        // the image is replaced by the eyes once an anaglyph stereo target is created
+        let descriptor_sets_anaglyph = Self::create_descriptor_sets(
+            vk_context.device(),
+            &mut descriptor_allocator,
+            descriptor_set_layout,
+            &uniform_buffers,
+            texture,
+        )?;
+        // the image and the table are replaced once a table is loaded
+        let descriptor_sets_grade = Self::create_descriptor_sets(
+            vk_context.device(),
+            &mut descriptor_allocator,
+            descriptor_set_layout,
+            &uniform_buffers,
+            texture,
+        )?;
+        // both only ever sample resolved frames
+        Self::update_descriptor_sets_sampler(
+            vk_context.device(),
+            &[descriptor_sets_anaglyph.as_slice(), &descriptor_sets_grade].concat(),
+            vk_context.sampler(SamplerConfig::NEAREST)?,
+        );
+        Self::update_descriptor_sets_image(
+            vk_context.device(),
+            &descriptor_sets_grade,
+            4,
+            texture_volume_empty,
         );
 
 
@@ -209,37 +595,45 @@ impl VkApp {
         thread::spawn(move || {
             while let Ok(shader) = rx.recv() {
                 if let Err(err) = shader.compile_code() {
+                    let err = shader.error().unwrap_or_else(|| format!("{err:#}"));
                     match shader.path() {
-                        Some(path) => log::error!("Error compiling Shader {}:\n{err:#}", path.display()),
-                        None => log::error!("Error compiling Shader:\n{err:#}"),
+                        Some(path) => log::error!("Error compiling Shader {}:\n{err}", path.display()),
+                        None => log::error!("Error compiling Shader:\n{err}"),
                     }
                 }
             }
         });
-        for shader in shaders.shaders_art.iter_mut() {
-            shader.vert.set_hot_reload(tx.clone());
-            shader.frag.set_hot_reload(tx.clone());
+        // the art shaders select the view of an eye like the builtin ones if multiview is enabled
+        if !vk_context.multiview() {
+            shaders.use_single_view();
+        }
+        for shader in shaders.shaders_art.iter_mut().flat_map(ShaderArt::shaders_mut) {
+            if vk_context.multiview() {
+                shader.set_define("MULTIVIEW", None);
+            }
+            shader.set_hot_reload(tx.clone());
         }
 
         // watch shader files for changes
-        shaders.watch_art();
+        let shader_watcher = shaders.watch_art();
 
-        let geometry_skybox = {
-            let nobj = NormalizedObj::from_reader(fs::load("assets/cubemap/skybox.obj")?)?;
-            let (vertices, indices, _) = Self::load_model::<VertexSimple>(nobj);
-            Geometry::new(
-                &vk_context,
+        let (geometry_skybox, skybox_bounds) = {
+            let nobj = NormalizedObj::from_reader(fs::load(SKYBOX_MODEL)?)?;
+            let (vertices, indices, (min, max)) = Self::load_model::<VertexSimple>(nobj);
+            let geometry = Geometry::new(
+                vk_context,
                 transient_command_pool,
                 graphics_queue,
                 &vertices,
                 &indices,
-            )
+            );
+            (geometry, [min, max])
         };
         let geometry_quad = {
-            let nobj = NormalizedObj::from_reader(fs::load("assets/models/quad.obj")?)?;
+            let nobj = NormalizedObj::from_reader(fs::load(QUAD_MODEL)?)?;
             let (vertices, indices, _) = Self::load_model::<VertexSimple>(nobj);
             Geometry::new(
-                &vk_context,
+                vk_context,
                 transient_command_pool,
                 graphics_queue,
                 &vertices,
@@ -247,1672 +641,3765 @@ impl VkApp {
             )
         };
 
+        let mut acceleration_structures = Vec::new();
+        let env_tex_coords = nobj.has_tex_coords;
         let pipeline_main = {
-            let (vertices, indices, _) = Self::load_model::<VertexColorCoords>(nobj);
+            let (vertices, indices) = Self::load_environment_model(nobj);
             let geometry = Geometry::new(
-                &vk_context,
+                vk_context,
                 transient_command_pool,
                 graphics_queue,
                 &vertices,
                 &indices,
             );
+            if ray_traced_art_count > 0 {
+                // ray traced art sees the environment
+                let blas = AccelerationStructure::new_bottom_level(
+                    vk_context,
+                    transient_command_pool,
+                    graphics_queue,
+                    &geometry,
+                );
+                let tlas = AccelerationStructure::new_top_level(
+                    vk_context,
+                    transient_command_pool,
+                    graphics_queue,
+                    &[(&blas, Matrix4::unit())],
+                );
+                acceleration_structures = vec![blas, tlas];
+            }
             Pipeline::new(
                 "main".to_owned(),
                 vk_context.device(),
-                properties,
                 msaa_samples,
                 render_pass,
                 descriptor_set_layout,
                 descriptor_sets_main.clone(),
-                geometry,
+                Some(geometry),
                 PipelineConfig::default(),
-                [shaders.main_vert, shaders.main_frag],
+                vec![shaders.main_vert, shaders.main_frag],
+                None,
                 None,
             )?
         };
         let pipeline_cube = Pipeline::new(
             "skybox".to_owned(),
             vk_context.device(),
-            properties,
             msaa_samples,
             render_pass,
             descriptor_set_layout,
-            descriptor_sets_cubemap,
-            geometry_skybox.clone(),
+            descriptor_sets_cubemap.clone(),
+            Some(geometry_skybox.clone()),
+            PipelineConfig::default(),
+            vec![shaders.cube_vert.clone(), shaders.cube_frag],
+            None,
+            None,
+        )?;
+        let pipeline_sky = Pipeline::new(
+            "procedural sky".to_owned(),
+            vk_context.device(),
+            msaa_samples,
+            render_pass,
+            descriptor_set_layout,
+            descriptor_sets_cubemap.clone(),
+            Some(geometry_skybox.clone()),
             PipelineConfig::default(),
-            [shaders.cube_vert, shaders.cube_frag],
+            vec![shaders.cube_vert, shaders.sky_frag],
+            None,
+            None,
+        )?;
+        let mut pipelines = vec![pipeline_main, pipeline_cube, pipeline_sky];
+        pipelines[PIPELINE_IDX_CUBE].active = !procedural_sky;
+        pipelines[PIPELINE_IDX_SKY].active = procedural_sky;
+        let pipeline_anaglyph = Pipeline::new(
+            "anaglyph".to_owned(),
+            vk_context.device(),
+            vk::SampleCountFlags::TYPE_1,
+            composite_render_pass,
+            descriptor_set_layout,
+            descriptor_sets_anaglyph.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert.clone(), shaders.anaglyph_frag],
+            None,
+            None,
+        )?;
+        // the frame stands in for the depth of the gallery if it can not be sampled
+        let grade_frag = if sampled_depth && msaa_samples != vk::SampleCountFlags::TYPE_1 {
+            shaders.grade_multisampled_frag
+        } else {
+            shaders.grade_frag
+        };
+        let pipeline_grade = Pipeline::new(
+            "grade".to_owned(),
+            vk_context.device(),
+            vk::SampleCountFlags::TYPE_1,
+            composite_render_pass,
+            descriptor_set_layout,
+            descriptor_sets_grade.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert.clone(), grade_frag],
+            None,
+            None,
+        )?;
+        let pipeline_graph = Pipeline::new(
+            "graph".to_owned(),
+            vk_context.device(),
+            msaa_samples,
+            render_pass,
+            descriptor_set_layout,
+            descriptor_sets_main.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert.clone(), shaders.graph_frag],
+            None,
+            None,
+        )?;
+        let pipeline_gizmo = Pipeline::new(
+            "gizmo".to_owned(),
+            vk_context.device(),
+            msaa_samples,
+            render_pass,
+            descriptor_set_layout,
+            descriptor_sets_main.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert.clone(), shaders.gizmo_frag],
+            None,
+            None,
+        )?;
+        let pipeline_fade = Pipeline::new(
+            "fade".to_owned(),
+            vk_context.device(),
+            msaa_samples,
+            render_pass,
+            descriptor_set_layout,
+            descriptor_sets_main.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert.clone(), shaders.fade_frag],
+            None,
+            None,
+        )?;
+        let pipeline_overlay = Pipeline::new(
+            "overlay".to_owned(),
+            vk_context.device(),
+            msaa_samples,
+            render_pass,
+            descriptor_set_layout,
+            descriptor_sets_main.clone(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                ..Default::default()
+            },
+            vec![shaders.fullscreen_vert, shaders.overlay_frag],
+            None,
             None,
         )?;
-        let mut pipelines = vec![pipeline_main, pipeline_cube];
-        for shader in shaders.shaders_art {
-            let pipeline = Pipeline::new(
-                shader.name,
+        let mut ray_tracing_pipelines = Vec::new();
+        let mut art_2d = Vec::new();
+        let mut textures_volume = Vec::new();
+        let mut descriptor_sets_volume = Vec::new();
+        // every art with its own descriptor sets needs the joint buffer too
+        let mut descriptor_sets_joints = vec![descriptor_sets_art.clone()];
+        let mut joint_count = 0;
+        // authors see how long their shaders take to compile and how many registers they need
+        let pipeline_feedback = PipelineFeedback {
+            creation: vk_context.pipeline_creation_feedback(),
+            executable_properties: vk_context.pipeline_executable_properties().cloned(),
+        };
+        let mut half_res_indices = Vec::new();
+        let error_shaders = [shaders.error_vert.clone(), shaders.error_frag.clone()];
+        for mut shader in shaders.shaders_art {
+            let is_3d = shader.is_3d;
+            let is_mesh = shader.is_mesh();
+            let art_time = shader.time;
+            let proximity = shader.proximity;
+            // raymarched art stays inside of the cube it is drawn with and can be culled
+            let bounds = (is_3d && !is_mesh && shader.model_path.is_none())
+                .then_some(skybox_bounds)
+                .filter(|_| shader.tessellation.is_none());
+            // the image is scaled up by drawing the cube again
+            match (shader.half_resolution, bounds) {
+                (true, Some(_)) => half_res_indices.push(pipelines.len()),
+                (true, None) => {
+                    log::warn!("Drawing {} at full resolution, it has no cube", shader.name);
+                }
+                (false, _) => {}
+            }
+            let (geometry, skin) = match (is_mesh, is_3d, shader.model_path.take()) {
+                (true, _, _) => (None, None),
+                (false, _, Some(path)) => {
+                    let (geometry, skin) = Self::load_art_model(
+                        vk_context,
+                        transient_command_pool,
+                        graphics_queue,
+                        &path,
+                        &shader,
+                    ).with_context(|| format!("Failed to load the model of {}", shader.name))?;
+                    (Some(geometry), skin)
+                }
+                (false, true, None) => (Some(geometry_skybox.clone()), None),
+                (false, false, None) => (Some(geometry_quad.clone()), None),
+            };
+            let art_uniforms = ArtUniforms {
+                model: shader.model_matrix,
+                params: shader.params,
+                time: 0.,
+                image_aspect: aspect_ratio(art_extent),
+                framing: shader.framing as u32,
+                joint_offset: joint_count as u32,
+                morph_weights: shader.morph_weights,
+                ..Default::default()
+            };
+            joint_count += skin.as_ref().map_or(0, |skin| skin.skeleton.joint_count());
+            let descriptor_sets = match shader.ray_tracing.take() {
+                Some(ray_tracing_shaders) => {
+                    let ray_tracing_pipeline = RayTracingPipeline::new(
+                        shader.name.clone(),
+                        vk_context,
+                        ray_tracing_shaders,
+                        &acceleration_structures[1],
+                        &uniform_buffers.iter().map(|[ubo, _]| *ubo).collect::<Vec<_>>(),
+                        Self::create_ray_tracing_images(
+                            vk_context,
+                            command_pool,
+                            graphics_queue,
+                            frames_in_flight,
+                        ),
+                        PushConstants {
+                            model: art_uniforms.model,
+                        },
+                    );
+                    // the art samples the image that is traced for the same frame
+                    let descriptor_sets = Self::create_descriptor_sets_with_images(
+                        vk_context.device(),
+                        &mut descriptor_allocator,
+                        descriptor_set_layout,
+                        &uniform_buffers,
+                        ray_tracing_pipeline.images(),
+                        vk::ImageLayout::GENERAL,
+                    )?;
+                    ray_tracing_pipelines.push(ray_tracing_pipeline);
+                    descriptor_sets
+                }
+                None if shader.volume.is_some() => {
+                    let descriptor_sets = Self::create_descriptor_sets(
+                        vk_context.device(),
+                        &mut descriptor_allocator,
+                        descriptor_set_layout,
+                        &uniform_buffers,
+                        texture_art,
+                    )?;
+                    descriptor_sets_volume.extend_from_slice(&descriptor_sets);
+                    descriptor_sets
+                }
+                None => descriptor_sets_art.clone(),
+            };
+            let texture_volume = match shader.volume.take() {
+                Some(volume) => {
+                    let texture = ResourceManager::create_volume_texture(
+                        vk_context,
+                        command_pool,
+                        graphics_queue,
+                        &volume,
+                        SamplerConfig::CLAMP,
+                    ).with_context(|| format!("Failed to create the volume of {}", shader.name))?;
+                    textures_volume.push(texture);
+                    texture
+                }
+                None => texture_volume_empty,
+            };
+            if descriptor_sets != descriptor_sets_art {
+                update_art_descriptor_sets(&descriptor_sets, texture_volume);
+                descriptor_sets_joints.push(descriptor_sets.clone());
+            }
+            let mut pipeline = Pipeline::new(
+                shader.name.clone(),
                 vk_context.device(),
-                properties,
                 msaa_samples,
                 render_pass,
                 descriptor_set_layout,
-                descriptor_sets_art.clone(),
-                if shader.is_3d { geometry_skybox.clone() } else { geometry_quad.clone() },
-                PipelineConfig::default(),
-                [shader.vert, shader.frag],
-                Some(PushConstants {
-                    model: shader.model_matrix,
-                }),
+                descriptor_sets,
+                geometry,
+                PipelineConfig {
+                    variable_shading_rate: supports_shading_rate,
+                    art_uniform_offset: (pipelines.len() - PIPELINE_IDX_ART) as u32 * art_uniform_stride,
+                    ..Default::default()
+                },
+                shader.into_shaders(),
+                Some(error_shaders.clone()),
+                Some(art_uniforms),
             )?;
+            pipeline.set_art_time(art_time);
+            pipeline.set_proximity(proximity);
+            pipeline.set_skin(skin);
+            pipeline.set_bounds(bounds);
+            if is_mesh {
+                pipeline.set_mesh_shader(vk_context.mesh_shader().cloned());
+            }
+            pipeline.set_fragment_shading_rate(vk_context.fragment_shading_rate().cloned());
+            pipeline.set_feedback(Some(pipeline_feedback.clone()));
+            if !is_3d && !is_mesh {
+                art_2d.push(pipelines.len());
+            }
             pipelines.push(pipeline);
         }
 
-        // we need to call cleanup on these, else dropping them will panic
-        unsafe { geometry_skybox.cleanup(vk_context.device()); }
-        unsafe { geometry_quad.cleanup(vk_context.device()); }
-
-        let command_buffers = Self::create_and_register_command_buffers(
-            vk_context.device(),
-            command_pool,
-            &swapchain_framebuffers,
-            render_pass,
-            properties,
-            &pipelines,
-        );
+        let mut half_res_art = Vec::new();
+        for index in half_res_indices {
+            let art = &pipelines[index];
+            let descriptor_sets = Self::create_descriptor_sets(
+                vk_context.device(),
+                &mut descriptor_allocator,
+                descriptor_set_layout,
+                &uniform_buffers,
+                texture_art,
+            )?;
+            let mut upscale = Pipeline::new(
+                format!("{} upscale", art.name()),
+                vk_context.device(),
+                msaa_samples,
+                render_pass,
+                descriptor_set_layout,
+                descriptor_sets,
+                art.geometry.clone(),
+                PipelineConfig {
+                    art_uniform_offset: (index - PIPELINE_IDX_ART) as u32 * art_uniform_stride,
+                    ..Default::default()
+                },
+                vec![art.vertex_shader().unwrap().clone(), shaders.upscale_frag.clone()],
+                None,
+                None,
+            )?;
+            upscale.set_bounds(art.bounds());
+            let mut art = HalfResArt::new(index, upscale);
+            art.resize(
+                vk_context,
+                properties.extent,
+                properties.format.format,
+                msaa_samples,
+                depth_format,
+            );
+            half_res_art.push(art);
+        }
+        if half_res_art.is_empty() {
+            shaders.upscale_frag.cleanup(vk_context.device());
+        }
 
-        let in_flight_frames = Self::create_sync_objects(vk_context.device());
+        let joint_buffer = JointBuffer::new(vk_context, frames_in_flight, joint_count)?;
+        for descriptor_sets in descriptor_sets_joints.iter() {
+            joint_buffer.update_descriptor_sets(vk_context.device(), descriptor_sets);
+        }
 
-        Ok(Self {
-            view_matrix: Matrix4::unit(),
-            model_matrix: Matrix4::unit(),
-            texture_weight: 0.,
-            dirty_swapchain: false,
+        let occlusion = OcclusionCulling::new(
             vk_context,
+            command_pool,
             graphics_queue,
-            present_queue,
-            swapchain,
-            swapchain_khr,
-            swapchain_properties: properties,
-            images,
-            swapchain_image_views,
-            render_pass,
+            &mut descriptor_allocator,
             descriptor_set_layout,
-            pipelines,
-            swapchain_framebuffers,
+            [shaders.occluder_vert, shaders.depth_pyramid_comp, shaders.occlusion_cull_comp],
+            properties.extent,
+            frames_in_flight,
+            PIPELINE_IDX_ART + art_capacity,
+        );
+        let occlusion = occlusion
+            .inspect_err(|err| log::warn!("Occlusion culling is not available: {err:#}"))
+            .ok();
+        let light_clusters = LightClusters::new(
+            vk_context,
+            &mut descriptor_allocator,
+            shaders.light_clusters_comp,
+            frames_in_flight,
+        )?;
+        light_clusters.update_descriptor_sets(vk_context.device(), &descriptor_sets_main);
+        let shadows = ShadowCascades::new(
+            vk_context,
+            &mut descriptor_allocator,
+            shaders.shadow_vert,
+            frames_in_flight,
+        )?;
+        shadows.update_descriptor_sets(vk_context.device(), &descriptor_sets_main);
+        let fog_volume = VolumetricFog::new(
+            vk_context,
             command_pool,
-            transient_command_pool,
-            msaa_samples,
-            color_texture,
-            depth_format,
-            depth_texture,
-            textures: vec![texture, texture_cubemap, texture_art],
+            graphics_queue,
+            &mut descriptor_allocator,
+            shaders.volumetric_fog_comp,
+            frames_in_flight,
+        )?;
+        light_clusters.update_descriptor_sets(vk_context.device(), fog_volume.descriptor_sets());
+        shadows.update_descriptor_sets(vk_context.device(), fog_volume.descriptor_sets());
+        fog_volume.update_descriptor_sets(vk_context.device(), &descriptor_sets_main);
+        let text_overlay = TextOverlay::new(vk_context, frames_in_flight)?;
+        text_overlay.update_descriptor_sets(vk_context.device(), &descriptor_sets_main);
+        let crash_markers = CrashMarkers::new(vk_context)
+            .inspect_err(|err| log::debug!("{err}"))
+            .ok();
+
+        let views = vec![vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: properties.extent,
+        }];
+        renderer.pipelines = pipelines;
+        renderer.screen = ScreenPipelines {
+            anaglyph: Some(pipeline_anaglyph),
+            grade: Some(pipeline_grade),
+            graph: Some(pipeline_graph),
+            gizmo: Some(pipeline_gizmo),
+            fade: Some(pipeline_fade),
+            overlay: Some(pipeline_overlay),
+            ..Default::default()
+        };
+
+        let mut app = Self {
+            scene: Scene::new(art_2d),
+            dirty_swapchain: false,
+            renderer,
+            descriptor_set_layout,
+            art_capacity,
+            compile_sender: tx,
+            shader_watcher,
+            art_cube_bounds: skybox_bounds,
+            half_res_art,
+            ray_tracing_pipelines,
+            renderers: Vec::new(),
+            acceleration_structures,
+            stereo_target: None,
+            descriptor_sets_anaglyph,
+            grade_target: None,
+            descriptor_sets_grade,
+            lut: None,
+            grade_strength: 1.,
+            reflection_strength: 0.,
+            sampled_depth,
+            dof_aperture: 0.,
+            focus_point: Vector2::from([0.5, 0.5]),
+            shutter: 0.,
+            previous_view_proj: None,
+            post_effects: PostEffects::default(),
+            script: None,
+            script_path: None,
+            script_time: None,
+            has_lightmap: false,
+            show_frame_graph: false,
+            text_overlay,
+            overlay_text: None,
+            overlay_color: OVERLAY_TEXT_COLOR,
+            frame_graph: FrameGraph::default(),
+            projector: None,
+            #[cfg(feature = "openxr")]
+            openxr: None,
+            gpu_timer: None,
+            crash_markers,
+            gpu_times: None,
+            dynamic_resolution: None,
+            quality: MAX_QUALITY,
+            captured_frame: None,
+            procedural_sky,
+            sample_shading: false,
+            resources: ResourceManager {
+                textures: Textures {
+                    image: texture,
+                    cubemap: texture_cubemap,
+                    cubemap_night: texture_cubemap_night,
+                    art_image: texture_art,
+                    volume_empty: texture_volume_empty,
+                    noise: texture_noise,
+                    noise_3d: texture_noise_3d,
+                    video_empty: texture_video_empty,
+                    normal_map: texture_normal_map,
+                    lightmap: texture_lightmap,
+                },
+                volumes: textures_volume,
+                skybox: geometry_skybox,
+                quad: geometry_quad,
+                shaders: RuntimeShaders {
+                    preview_vert: shaders.preview_vert,
+                    error: error_shaders,
+                    main_frag_unused: shaders.main_lightmap_frag,
+                },
+                samplers,
+                image_path: image_path.as_ref().to_owned(),
+                image_aspect: aspect_ratio(image_extent),
+                art_image_aspect: aspect_ratio(art_extent),
+                keyboard_texture,
+                video: None,
+                animated_image: None,
+                file_watcher,
+            },
+            env_tex_coords,
+            parallax_scale: 0.,
+            uniform_buffer,
+            uniform_buffer_memory,
+            uniform_buffer_ptr,
+            ubo_stride,
+            art_uniform_stride,
             uniform_buffers,
-            uniform_buffer_memories,
-            descriptor_pool,
+            views,
+            descriptor_allocator,
             descriptor_sets_main,
-            command_buffers,
-            in_flight_frames,
-        })
-    }
-
-    fn create_instance(entry: &Entry, window: &Window) -> Instance {
-        let app_name = CString::new("Vulkan Application").unwrap();
-        let engine_name = CString::new("No Engine").unwrap();
-        let app_info = vk::ApplicationInfo::default()
-            .application_name(app_name.as_c_str())
-            .application_version(vk::make_api_version(0, 0, 1, 0))
-            .engine_name(engine_name.as_c_str())
-            .engine_version(vk::make_api_version(0, 0, 1, 0))
-            .api_version(vk::make_api_version(0, 1, 0, 0));
-
-        let extension_names =
-            ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw())
-                .unwrap();
-        let mut extension_names = extension_names.to_vec();
-        if ENABLE_VALIDATION_LAYERS {
-            extension_names.push(debug_utils::NAME.as_ptr());
-        }
-        #[cfg(any(target_os = "macos", target_os = "ios"))]
-        {
-            extension_names.push(ash::khr::portability_enumeration::NAME.as_ptr());
-            // Enabling this extension is a requirement when using `VK_KHR_portability_subset`
-            extension_names.push(ash::khr::get_physical_device_properties2::NAME.as_ptr());
-        }
-
-        let (_layer_names, layer_names_ptrs) = get_layer_names_and_pointers();
-
-        let create_flags = if cfg!(any(target_os = "macos", target_os = "ios")) {
-            vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
-        } else {
-            vk::InstanceCreateFlags::default()
+            descriptor_sets_cubemap,
+            descriptor_sets_art,
+            descriptor_sets_volume,
+            joint_buffer,
+            occlusion,
+            occlusion_culling: true,
+            lights: Vec::new(),
+            lights_path: None,
+            light_clusters,
+            shadows,
+            fog_volume,
+            volumetric_fog: false,
         };
-        let mut instance_create_info = vk::InstanceCreateInfo::default()
-            .application_info(&app_info)
-            .enabled_extension_names(&extension_names)
-            .flags(create_flags);
-        if ENABLE_VALIDATION_LAYERS {
-            check_validation_layer_support(entry);
-            instance_create_info = instance_create_info.enabled_layer_names(&layer_names_ptrs);
+        app.animate_image(ResourceManager::load_animation(image_path.as_ref()));
+        app.recreate_command_buffers();
+        #[cfg(feature = "openxr")]
+        if let Some(xr) = openxr {
+            app.start_openxr(xr);
         }
-
-        unsafe { entry.create_instance(&instance_create_info, None).unwrap() }
+        Ok(app)
     }
 
-    /// Create the swapchain with optimal settings possible with `device`.
-    ///
-    /// # Returns
-    ///
-    /// A tuple containing the swapchain loader and the actual swapchain.
-    fn create_swapchain_and_images(
+    /// The images at binding 1 and 2 are sampled with the sampler at binding 9 that is set
+    /// together with the descriptor set, every other texture with an immutable sampler.
+    fn create_descriptor_set_layout(
         vk_context: &VkContext,
-        dimensions: [u32; 2],
-    ) -> (
-        khr_swapchain::Device,
-        vk::SwapchainKHR,
-        SwapchainProperties,
-        Vec<vk::Image>,
-    ) {
-        let details = SwapchainSupportDetails::new(
-            vk_context.physical_device(),
-            vk_context.surface(),
-            vk_context.surface_khr(),
-        );
-        let properties = details.get_ideal_swapchain_properties(dimensions);
-
-        let format = properties.format;
-        let present_mode = properties.present_mode;
-        let extent = properties.extent;
-        let image_count = {
-            let max = details.capabilities.max_image_count;
-            let mut preferred = details.capabilities.min_image_count + 1;
-            if max > 0 && preferred > max {
-                preferred = max;
-            }
-            preferred
-        };
-
-        log::debug!("Creating swapchain.");
-
-        let graphics = vk_context.graphics_queue_index();
-        let present = vk_context.present_queue_index();
-        let families_indices = [graphics, present];
-
-        let create_info = {
-            let mut builder = vk::SwapchainCreateInfoKHR::default()
-                .surface(vk_context.surface_khr())
-                .min_image_count(image_count)
-                .image_format(format.format)
-                .image_color_space(format.color_space)
-                .image_extent(extent)
-                .image_array_layers(1)
-                .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT);
-
-            builder = if graphics != present {
-                builder
-                    .image_sharing_mode(vk::SharingMode::CONCURRENT)
-                    .queue_family_indices(&families_indices)
-            } else {
-                builder.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
-            };
-
-            builder
-                .pre_transform(details.capabilities.current_transform)
-                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
-                .present_mode(present_mode)
-                .clipped(true)
-        };
-
-        let swapchain = khr_swapchain::Device::new(vk_context.instance(), vk_context.device());
-        let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None).unwrap() };
-        let images = unsafe { swapchain.get_swapchain_images(swapchain_khr).unwrap() };
-        (swapchain, swapchain_khr, properties, images)
-    }
-
-    /// Create one image view for each image of the swapchain.
-    fn create_swapchain_image_views(
-        device: &Device,
-        swapchain_images: &[vk::Image],
-        swapchain_properties: SwapchainProperties,
-    ) -> Vec<vk::ImageView> {
-        swapchain_images.iter()
-            .map(|image| {
-                Self::create_image_view(
-                    device,
-                    *image,
-                    1,
-                    swapchain_properties.format.format,
-                    vk::ImageAspectFlags::COLOR,
-                )
-            })
-            .collect::<Vec<_>>()
-    }
-
-    fn create_image_view(
-        device: &Device,
-        image: vk::Image,
-        mip_levels: u32,
-        format: vk::Format,
-        aspect_mask: vk::ImageAspectFlags,
-    ) -> vk::ImageView {
-        let create_info = vk::ImageViewCreateInfo::default()
-            .image(image)
-            .view_type(vk::ImageViewType::TYPE_2D)
-            .format(format)
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask,
-                base_mip_level: 0,
-                level_count: mip_levels,
-                base_array_layer: 0,
-                layer_count: 1,
-            });
-
-        unsafe { device.create_image_view(&create_info, None).unwrap() }
-    }
-
-    fn create_render_pass(
-        device: &Device,
-        swapchain_properties: SwapchainProperties,
-        msaa_samples: vk::SampleCountFlags,
-        depth_format: vk::Format,
-    ) -> vk::RenderPass {
-        let color_attachment_desc = vk::AttachmentDescription::default()
-            .format(swapchain_properties.format.format)
-            .samples(msaa_samples)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        let depth_attachement_desc = vk::AttachmentDescription::default()
-            .format(depth_format)
-            .samples(msaa_samples)
-            .load_op(vk::AttachmentLoadOp::CLEAR)
-            .store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-        let resolve_attachment_desc = vk::AttachmentDescription::default()
-            .format(swapchain_properties.format.format)
-            .samples(vk::SampleCountFlags::TYPE_1)
-            .load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .store_op(vk::AttachmentStoreOp::STORE)
-            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
-            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
-        let attachment_descs = [
-            color_attachment_desc,
-            depth_attachement_desc,
-            resolve_attachment_desc,
-        ];
-
-        let color_attachment_ref = vk::AttachmentReference::default()
-            .attachment(0)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        let color_attachment_refs = [color_attachment_ref];
-
-        let depth_attachment_ref = vk::AttachmentReference::default()
-            .attachment(1)
-            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
-
-        let resolve_attachment_ref = vk::AttachmentReference::default()
-            .attachment(2)
-            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
-        let resolve_attachment_refs = [resolve_attachment_ref];
-
-        let subpass_desc = vk::SubpassDescription::default()
-            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
-            .color_attachments(&color_attachment_refs)
-            .resolve_attachments(&resolve_attachment_refs)
-            .depth_stencil_attachment(&depth_attachment_ref);
-        let subpass_descs = [subpass_desc];
-
-        let subpass_dep = vk::SubpassDependency::default()
-            .src_subpass(vk::SUBPASS_EXTERNAL)
-            .dst_subpass(0)
-            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .src_access_mask(vk::AccessFlags::empty())
-            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
-            .dst_access_mask(
-                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-            );
-        let subpass_deps = [subpass_dep];
-
-        let render_pass_info = vk::RenderPassCreateInfo::default()
-            .attachments(&attachment_descs)
-            .subpasses(&subpass_descs)
-            .dependencies(&subpass_deps);
-
-        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
-    }
-
-    fn create_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
-        let ubo_binding = UniformBufferObject::get_descriptor_set_layout_binding();
-        let sampler_binding = vk::DescriptorSetLayoutBinding::default()
+        allocator: &mut DescriptorAllocator,
+        ubo_stages: vk::ShaderStageFlags,
+    ) -> Result<vk::DescriptorSetLayout, anyhow::Error> {
+        let ubo_binding = UniformBufferObject::get_descriptor_set_layout_binding(ubo_stages);
+        let image_binding = vk::DescriptorSetLayoutBinding::default()
             .binding(1)
             .descriptor_count(1)
-            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
             .stage_flags(vk::ShaderStageFlags::FRAGMENT);
-        let bindings = [ubo_binding, sampler_binding];
-
-        let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
-        unsafe { device.create_descriptor_set_layout(&layout_info, None).unwrap() }
-    }
-
-    /// Create a descriptor pool to allocate the descriptor sets.
-    fn create_descriptor_pool(device: &Device, size: u32) -> vk::DescriptorPool {
-        // double size because we will create different descriptor sets for different pipelines
-        let size = size * 3;
-        let pool_sizes = [
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::UNIFORM_BUFFER,
-                descriptor_count: size,
-            },
-            vk::DescriptorPoolSize {
-                ty: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
-                descriptor_count: size * 2,
-            },
+        // a second image that is only written for the skybox to blend between day and night
+        let image_binding_2 = image_binding.binding(2);
+        let sampler_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(9)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::SAMPLER)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT);
+        let art_binding = ArtUniforms::get_descriptor_set_layout_binding(
+            ubo_stages | vk::ShaderStageFlags::FRAGMENT,
+        );
+        let clamp = [vk_context.sampler(SamplerConfig::CLAMP)?];
+        let repeat = [vk_context.sampler(SamplerConfig::REPEAT)?];
+        let nearest = [vk_context.sampler(SamplerConfig::NEAREST)?];
+        let immutable_binding = |binding, samplers| {
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .immutable_samplers(samplers)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        };
+        let bindings = [
+            ubo_binding,
+            image_binding,
+            image_binding_2,
+            art_binding,
+            // the joint matrices of skinned art
+            JointBuffer::descriptor_set_layout_binding(ubo_stages),
+            // the 3D texture of the art, see [`Volume`]
+            immutable_binding(4, &clamp),
+            // the noise textures of every art, see [`crate::noise`]
+            immutable_binding(5, &repeat),
+            immutable_binding(6, &repeat),
+            // the pressed keys, see [`crate::keyboard`]
+            immutable_binding(7, &nearest),
+            // the frames of a video, see [`VkApp::set_video`]
+            immutable_binding(8, &clamp),
+            sampler_binding,
+            // the normal map of the environment, see [`VkApp::set_normal_map`]
+            immutable_binding(11, &repeat),
+            // the baked light of the environment, see [`VkApp::set_lightmap`]
+            immutable_binding(12, &clamp),
         ];
-        let pool_info = vk::DescriptorPoolCreateInfo::default()
-            .pool_sizes(&pool_sizes)
-            .max_sets(size);
-
-        unsafe { device.create_descriptor_pool(&pool_info, None).unwrap() }
+        // the point lights of the environment and their clusters, see [`LightClusters`]
+        let bindings = [&bindings[..], &LightClusters::descriptor_set_layout_bindings()].concat();
+        // the shadows of the sun, see [`ShadowCascades`]
+        let bindings = [&bindings[..], &ShadowCascades::descriptor_set_layout_bindings()].concat();
+        // the light scattered by the fog, see [`VolumetricFog`]
+        let bindings = [&bindings[..], &[VolumetricFog::descriptor_set_layout_binding()]].concat();
+        // the depth of the gallery, see [`VkApp::set_depth_of_field`]
+        let gallery_depth_binding = image_binding.binding(18);
+        let bindings = [&bindings[..], &[gallery_depth_binding]].concat();
+        // the text drawn over the gallery, see [`VkApp::set_overlay_text`]
+        let bindings = [&bindings[..], &[TextOverlay::descriptor_set_layout_binding()]].concat();
+
+        allocator.create_layout(vk_context.device(), &bindings)
     }
 
-    /// Create one descriptor set for each uniform buffer.
+    /// Create one descriptor set for each part of the uniform buffer.
     fn create_descriptor_sets(
         device: &Device,
-        pool: vk::DescriptorPool,
+        allocator: &mut DescriptorAllocator,
         layout: vk::DescriptorSetLayout,
-        uniform_buffers: &[vk::Buffer],
+        uniform_buffers: &[[vk::DescriptorBufferInfo; 2]],
         texture: Texture,
-    ) -> Vec<vk::DescriptorSet> {
-        let layouts = (0..uniform_buffers.len())
-            .map(|_| layout)
-            .collect::<Vec<_>>();
-        let alloc_info = vk::DescriptorSetAllocateInfo::default()
-            .descriptor_pool(pool)
-            .set_layouts(&layouts);
-        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info).unwrap() };
-
-        for (set, buffer) in descriptor_sets.iter().zip(uniform_buffers.iter()) {
-            let buffer_info = vk::DescriptorBufferInfo::default()
-                .buffer(*buffer)
-                .offset(0)
-                .range(size_of::<UniformBufferObject>() as vk::DeviceSize);
-            let buffer_infos = [buffer_info];
+    ) -> Result<Vec<vk::DescriptorSet>, anyhow::Error> {
+        Self::create_descriptor_sets_with_images(
+            device,
+            allocator,
+            layout,
+            uniform_buffers,
+            &vec![texture; uniform_buffers.len()],
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        )
+    }
+
+    /// Create one descriptor set for each part of the uniform buffer,
+    /// each with the texture at the same index in `textures`.
+    fn create_descriptor_sets_with_images(
+        device: &Device,
+        allocator: &mut DescriptorAllocator,
+        layout: vk::DescriptorSetLayout,
+        uniform_buffers: &[[vk::DescriptorBufferInfo; 2]],
+        textures: &[Texture],
+        image_layout: vk::ImageLayout,
+    ) -> Result<Vec<vk::DescriptorSet>, anyhow::Error> {
+        let descriptor_sets = allocator.allocate(device, layout, uniform_buffers.len())?;
+
+        for ((set, [ubo_info, art_info]), texture) in descriptor_sets.iter().zip(uniform_buffers).zip(textures) {
+            let buffer_infos = [*ubo_info];
             let ubo_descriptor_write = vk::WriteDescriptorSet::default()
                 .dst_set(*set)
                 .dst_binding(0)
                 .dst_array_element(0)
-                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
                 .buffer_info(&buffer_infos);
 
-            let image_info = vk::DescriptorImageInfo::default()
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .image_view(texture.view)
-                .sampler(texture.sampler.unwrap());
-            let image_infos = [image_info];
-            let sampler_descriptor_write = vk::WriteDescriptorSet::default()
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_layout(image_layout)
+                .image_view(texture.view)];
+            let image_descriptor_write = vk::WriteDescriptorSet::default()
                 .dst_set(*set)
                 .dst_binding(1)
                 .dst_array_element(0)
-                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
                 .image_info(&image_infos);
 
-            let writes = [ubo_descriptor_write, sampler_descriptor_write];
+            let sampler_infos = [vk::DescriptorImageInfo::default()
+                .sampler(texture.sampler.unwrap())];
+            let sampler_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(9)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .image_info(&sampler_infos);
+
+            let art_infos = [*art_info];
+            let art_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(3)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+                .buffer_info(&art_infos);
+
+            let writes = [
+                ubo_descriptor_write,
+                image_descriptor_write,
+                sampler_descriptor_write,
+                art_descriptor_write,
+            ];
             unsafe { device.update_descriptor_sets(&writes, &[]) }
         }
 
-        descriptor_sets
-    }
-
-    fn create_framebuffers(
-        device: &Device,
-        image_views: &[vk::ImageView],
-        color_texture: Texture,
-        depth_texture: Texture,
-        render_pass: vk::RenderPass,
-        swapchain_properties: SwapchainProperties,
-    ) -> Vec<vk::Framebuffer> {
-        image_views.iter()
-            .map(|view| [color_texture.view, depth_texture.view, *view])
-            .map(|attachments| {
-                let framebuffer_info = vk::FramebufferCreateInfo::default()
-                    .render_pass(render_pass)
-                    .attachments(&attachments)
-                    .width(swapchain_properties.extent.width)
-                    .height(swapchain_properties.extent.height)
-                    .layers(1);
-                unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
-            })
-            .collect::<Vec<_>>()
-    }
-
-    fn create_color_texture(
-        vk_context: &VkContext,
-        command_pool: vk::CommandPool,
-        transition_queue: vk::Queue,
-        swapchain_properties: SwapchainProperties,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> Texture {
-        let format = swapchain_properties.format.format;
-        let (image, memory) = Self::create_image(
-            vk_context,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            swapchain_properties.extent,
-            1,
-            msaa_samples,
-            format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
-        );
-
-        Self::transition_image_layout(
-            vk_context.device(),
-            command_pool,
-            transition_queue,
-            image,
-            1,
-            format,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
-            1,
-        );
-
-        let view = Self::create_image_view(
-            vk_context.device(),
-            image,
-            1,
-            format,
-            vk::ImageAspectFlags::COLOR,
-        );
-
-        Texture::new(image, memory, view, None)
+        Ok(descriptor_sets)
     }
 
-    /// Create the depth buffer texture (image, memory and view).
+    /// Create `count` images for ray traced art to write into and other pipelines to sample.
     ///
-    /// This function also transitions the image to be ready to be used
-    /// as a depth/stencil attachement.
-    fn create_depth_texture(
+    /// The images are transitioned to the general layout which they keep for their lifetime.
+    fn create_ray_tracing_images(
         vk_context: &VkContext,
         command_pool: vk::CommandPool,
         transition_queue: vk::Queue,
-        format: vk::Format,
-        extent: vk::Extent2D,
-        msaa_samples: vk::SampleCountFlags,
-    ) -> Texture {
-        let (image, mem) = Self::create_image(
-            vk_context,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            extent,
-            1,
-            msaa_samples,
-            format,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-        );
-
+        count: usize,
+    ) -> Vec<Texture> {
         let device = vk_context.device();
-        Self::transition_image_layout(
-            device,
-            command_pool,
-            transition_queue,
-            image,
-            1,
-            format,
-            vk::ImageLayout::UNDEFINED,
-            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-            1,
-        );
+        let format = vk::Format::R8G8B8A8_UNORM;
+        (0..count).map(|_| {
+            let (image, memory) = ResourceManager::create_image(
+                vk_context,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                RAY_TRACING_EXTENT,
+                1,
+                vk::SampleCountFlags::TYPE_1,
+                format,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+            );
 
-        let view = Self::create_image_view(device, image, 1, format, vk::ImageAspectFlags::DEPTH);
+            ResourceManager::transition_image_layout(
+                vk_context,
+                command_pool,
+                transition_queue,
+                image,
+                1,
+                format,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::GENERAL,
+                1,
+            );
 
-        Texture::new(image, mem, view, None)
-    }
+            let aspect = vk::ImageAspectFlags::COLOR;
+            let view = ResourceManager::create_image_view(device, image, 1, format, aspect);
 
-    fn find_depth_format(vk_context: &VkContext) -> vk::Format {
-        let candidates = [
-            vk::Format::D32_SFLOAT,
-            vk::Format::D32_SFLOAT_S8_UINT,
-            vk::Format::D24_UNORM_S8_UINT,
-        ];
-        vk_context
-            .find_supported_format(
-                &candidates,
-                vk::ImageTiling::OPTIMAL,
-                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
-            )
-            .expect("Failed to find a supported depth format")
-    }
+            let sampler = vk_context.sampler(SamplerConfig::CLAMP).unwrap();
 
-    fn has_stencil_component(format: vk::Format) -> bool {
-        format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT
+            Texture::new(image, memory, view, Some(sampler))
+        }).collect()
     }
 
-    fn create_cubemap<P: AsRef<Path>>(
-        vk_context: &VkContext,
-        command_pool: vk::CommandPool,
-        copy_queue: vk::Queue,
-        pathes: [P; 6],
-    ) -> Result<Texture, anyhow::Error> {
-        let mut dims = None;
-        let mut images = Vec::new();
-        for path in pathes.iter() {
-            let image = ImageReader::open(path)
-                .with_context(|| format!("Failed to open image at {:?}", path.as_ref()))?
-                .decode()
-                .with_context(|| format!("Failed to decode image at {:?}", path.as_ref()))?;
-            let image_as_rgb = image.to_rgba8();
-            let width = image_as_rgb.width();
-            let height = image_as_rgb.height();
-            if let Some((w, h)) = dims {
-                if w != width || h != height {
-                    return Err(anyhow::anyhow!("cubemap images must have all the same size"))
-                }
-            } else {
-                dims = Some((width, height));
+    fn load_model<V: Vertex>(nobj: NormalizedObj) -> (Vec<V>, Vec<u32>, (Vector3, Vector3)) {
+        let mut min = Vector3::new(f32::MAX);
+        let mut max = Vector3::new(f32::MIN);
+        for vertex in &nobj.vertices {
+            for (i, &coord) in vertex.pos_coords.iter().enumerate() {
+                min[i] = min[i].min(coord);
+                max[i] = max[i].max(coord);
             }
-            let pixels = image_as_rgb.into_raw();
-            images.push(pixels);
         }
-        let (width, height) = dims.unwrap();
-        let max_mip_levels = ((width.min(height) as f32).log2().floor() + 1.0) as u32;
-        let extent = vk::Extent2D { width, height };
-        let image_size = (images[0].len() * size_of::<u8>()) as vk::DeviceSize;
-        let device = vk_context.device();
-
-        let (buffer, memory, mem_size) = buffer::create_buffer(
-            vk_context,
-            image_size * 6,
-            vk::BufferUsageFlags::TRANSFER_SRC,
-            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-        );
-
-        unsafe {
-            for (i, image) in images.into_iter().enumerate() {
-                let offset = image_size * i as vk::DeviceSize;
-                let ptr = device
-                    .map_memory(memory, offset, image_size, vk::MemoryMapFlags::empty())
-                    .context("Failed to map memory for cubemap image")?;
-                let mut align = ash::util::Align::new(ptr, align_of::<u8>() as _, mem_size);
-                align.copy_from_slice(&image);
-                device.unmap_memory(memory);
-            }
-        }
-
-        let (image, image_memory) = {
-            let image_info = vk::ImageCreateInfo::default()
-                .image_type(vk::ImageType::TYPE_2D)
-                .extent(vk::Extent3D {
-                    width: extent.width,
-                    height: extent.height,
-                    depth: 1,
-                })
-                .mip_levels(max_mip_levels)
-                .array_layers(6)
-                .format(vk::Format::R8G8B8A8_UNORM)
-                .tiling(vk::ImageTiling::OPTIMAL)
-                .initial_layout(vk::ImageLayout::UNDEFINED)
-                .usage(vk::ImageUsageFlags::TRANSFER_SRC
-                    | vk::ImageUsageFlags::TRANSFER_DST
-                    | vk::ImageUsageFlags::SAMPLED)
-                .sharing_mode(vk::SharingMode::EXCLUSIVE)
-                .samples(vk::SampleCountFlags::TYPE_1)
-                .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE);
-            let device = vk_context.device();
-            let image = unsafe { device.create_image(&image_info, None).unwrap() };
-            let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
-            let mem_type_index = vk_context.find_memory_type(
-                mem_requirements,
-                vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            );
-            let alloc_info = vk::MemoryAllocateInfo::default()
-                .allocation_size(mem_requirements.size)
-                .memory_type_index(mem_type_index);
-            let memory = unsafe {
-                let mem = device.allocate_memory(&alloc_info, None).unwrap();
-                device.bind_image_memory(image, mem, 0).unwrap();
-                mem
+        let vertices = nobj.vertices.iter().map(|vertex| {
+            let tex_coords = if nobj.has_tex_coords {
+                vertex.tex_coords
+            } else {
+                [vertex.pos_coords[2], vertex.pos_coords[1]]
             };
-            (image, memory)
-        };
+            V::new(vertex.pos_coords, [1.0, 1.0, 1.0], tex_coords)
+        }).collect();
 
-        // Transition the image layout and copy the buffer into the image
-        // and transition the layout again to be readable from fragment shader.
-        {
-            Self::transition_image_layout(
-                device,
-                command_pool,
-                copy_queue,
-                image,
-                max_mip_levels,
-                vk::Format::R8G8B8A8_UNORM,
-                vk::ImageLayout::UNDEFINED,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                6,
-            );
+        (vertices, nobj.indices, (min, max))
+    }
 
-            Self::copy_buffer_to_image(device, command_pool, copy_queue, buffer, image, extent, 6);
+    /// Loads the environment with the tangents its normal map is read along
+    /// and the coordinates of its lightmap.
+    fn load_environment_model(nobj: NormalizedObj) -> (Vec<VertexTangents>, Vec<u32>) {
+        let (vertices, indices, _) = Self::load_model::<VertexTangents>(nobj);
+        let (mut vertices, indices) = VertexTangents::with_lightmap_coords(&vertices, &indices);
+        VertexTangents::compute_tangents(&mut vertices, &indices);
+        (vertices, indices)
+    }
 
-            Self::generate_mipmaps(
+    /// Loads the model of `art` at `path`, either an OBJ model with the morph targets of `art`
+    /// or a glTF model with the skin playing the animation of `art`.
+    fn load_art_model(
+        vk_context: &VkContext,
+        transient_command_pool: vk::CommandPool,
+        graphics_queue: vk::Queue,
+        path: &Path,
+        art: &ShaderArt,
+    ) -> Result<(Geometry, Option<Skin>), anyhow::Error> {
+        if path.extension().is_some_and(|ext| ext == "gltf" || ext == "glb") {
+            if !art.morph_targets.is_empty() {
+                anyhow::bail!("glTF models have no morph targets");
+            }
+            let model = SkinnedModel::load(path)?;
+            let animation = match &art.animation {
+                Some(name) => Some(model.animations.iter()
+                    .find(|animation| &animation.name == name)
+                    .ok_or_else(|| anyhow::anyhow!("no animation called {name}"))?
+                    .clone()),
+                None => model.animations.first().cloned(),
+            };
+            let vertices = model.vertices.into_iter().map(VertexSkinned::from).collect::<Vec<_>>();
+            let geometry = Geometry::new(
                 vk_context,
-                command_pool,
-                copy_queue,
-                image,
-                extent,
-                vk::Format::R8G8B8A8_UNORM,
-                max_mip_levels,
-                6,
+                transient_command_pool,
+                graphics_queue,
+                &vertices,
+                &model.indices,
             );
+            return Ok((geometry, Some(Skin { skeleton: model.skeleton, animation })));
         }
-
-        unsafe {
-            device.destroy_buffer(buffer, None);
-            device.free_memory(memory, None);
-        }
-
-        let create_info = vk::ImageViewCreateInfo::default()
-            .image(image)
-            .view_type(vk::ImageViewType::CUBE)
-            .format(vk::Format::R8G8B8A8_UNORM)
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: vk::ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: max_mip_levels,
-                base_array_layer: 0,
-                layer_count: 6,
-            });
-        let image_view = unsafe {
-            device.create_image_view(&create_info, None).unwrap()
+        let morph_targets = &art.morph_targets;
+        let positions = |nobj: &NormalizedObj| {
+            nobj.vertices.iter().map(|vertex| vertex.pos_coords).collect::<Vec<_>>()
         };
-
-        let max_aniso = vk_context.physical_device_properties().limits.max_sampler_anisotropy;
-        let sampler_info = vk::SamplerCreateInfo::default()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(max_aniso.max(16.))
-            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-            .unnormalized_coordinates(false)
-            .compare_enable(false)
-            .compare_op(vk::CompareOp::ALWAYS)
-            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-            .mip_lod_bias(0.0)
-            .min_lod(0.0)
-            .max_lod(max_mip_levels as _);
-        let sampler = unsafe {
-            device.create_sampler(&sampler_info, None)
-                .context("Failed to create sampler for cubemap")?
-        };
-
-        Ok(Texture::new(image, image_memory, image_view, Some(sampler)))
+        let nobj = NormalizedObj::from_reader(fs::load(path)?)?;
+        let targets = morph_targets.iter()
+            .map(|path| Ok(positions(&NormalizedObj::from_reader(fs::load(path)?)?)))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        let morph_deltas = MorphDeltas::from_targets(&positions(&nobj), &targets)?;
+        let (vertices, indices, _) = Self::load_model::<VertexSimple>(nobj);
+        Ok((if targets.is_empty() {
+            Geometry::new(vk_context, transient_command_pool, graphics_queue, &vertices, &indices)
+        } else {
+            Geometry::with_morph_targets(
+                vk_context,
+                transient_command_pool,
+                graphics_queue,
+                &vertices,
+                &indices,
+                &morph_deltas,
+            )
+        }, None))
     }
 
-    fn create_texture_image<P: AsRef<Path>>(
+    /// Create an uniform buffer with room for `count` frames in flight, each with an uniform buffer
+    /// object per view and one for the previewed or entered art followed by the art uniforms.
+    ///
+    /// Returns the buffer, its memory and the parts of the buffer of each frame
+    /// with the uniform buffer objects and with the art uniforms.
+    /// The objects and art are selected with dynamic offsets relative to these parts.
+    fn create_uniform_buffer(
         vk_context: &VkContext,
-        command_pool: vk::CommandPool,
-        copy_queue: vk::Queue,
-        path: P,
-    ) -> Result<Texture, anyhow::Error> {
-        let image = ImageReader::open(path)
-            .context("Failed to open image")?
-            .decode()
-            .context("Failed to decode image")?
-            .flipv();
-        let image_as_rgb = image.to_rgba8();
-        let width = image_as_rgb.width();
-        let height = image_as_rgb.height();
-        let max_mip_levels = ((width.min(height) as f32).log2().floor() + 1.0) as u32;
-        let extent = vk::Extent2D { width, height };
-        let pixels = image_as_rgb.into_raw();
-        let image_size = (pixels.len() * size_of::<u8>()) as vk::DeviceSize;
-        let device = vk_context.device();
-
-        let (buffer, memory, mem_size) = buffer::create_buffer(
+        count: usize,
+        ubo_stride: u32,
+        art_uniform_stride: u32,
+        art_count: usize,
+    ) -> (vk::Buffer, vk::DeviceMemory, Vec<[vk::DescriptorBufferInfo; 2]>) {
+        let (ubos_size, frame_size) =
+            Self::uniform_buffer_frame_size(ubo_stride, art_uniform_stride, art_count);
+        let (buffer, memory, _) = buffer::create_buffer(
             vk_context,
-            image_size,
-            vk::BufferUsageFlags::TRANSFER_SRC,
+            (frame_size * count) as vk::DeviceSize,
+            vk::BufferUsageFlags::UNIFORM_BUFFER,
             vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
         );
+        let parts = (0..count)
+            .map(|i| [
+                vk::DescriptorBufferInfo::default()
+                    .buffer(buffer)
+                    .offset((i * frame_size) as _)
+                    .range(size_of::<UniformBufferObject>() as _),
+                vk::DescriptorBufferInfo::default()
+                    .buffer(buffer)
+                    .offset((i * frame_size + ubos_size) as _)
+                    .range(size_of::<ArtUniforms>() as _),
+            ])
+            .collect();
+        (buffer, memory, parts)
+    }
 
-        unsafe {
-            let ptr = device.map_memory(memory, 0, image_size, vk::MemoryMapFlags::empty())
-                .context("Failed to map memory for texture image")?;
-            let mut align = ash::util::Align::new(ptr, align_of::<u8>() as _, mem_size);
-            align.copy_from_slice(&pixels);
-            device.unmap_memory(memory);
-        }
-
-        let (image, image_memory) = Self::create_image(
-            vk_context,
-            vk::MemoryPropertyFlags::DEVICE_LOCAL,
-            extent,
-            max_mip_levels,
-            vk::SampleCountFlags::TYPE_1,
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ImageTiling::OPTIMAL,
-            vk::ImageUsageFlags::TRANSFER_SRC
-                | vk::ImageUsageFlags::TRANSFER_DST
-                | vk::ImageUsageFlags::SAMPLED,
-        );
-
-        // Transition the image layout and copy the buffer into the image
-        // and transition the layout again to be readable from fragment shader.
-        {
-            Self::transition_image_layout(
-                device,
-                command_pool,
-                copy_queue,
-                image,
-                max_mip_levels,
-                vk::Format::R8G8B8A8_UNORM,
-                vk::ImageLayout::UNDEFINED,
-                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                1,
-            );
+    /// Returns the size of the uniform buffer objects and the whole size of a frame
+    /// in the uniform buffer.
+    fn uniform_buffer_frame_size(
+        ubo_stride: u32,
+        art_uniform_stride: u32,
+        art_count: usize,
+    ) -> (usize, usize) {
+        let ubos_size = ubo_stride as usize * (UBO_IDX_PROJECTOR + 1);
+        // pipelines without art still bind the first art uniforms
+        let art_size = art_uniform_stride as usize * art_count.max(1);
+        (ubos_size, ubos_size + art_size)
+    }
 
-            Self::copy_buffer_to_image(device, command_pool, copy_queue, buffer, image, extent, 1);
+    /// Records the command buffers of every frame in flight again.
+    fn recreate_command_buffers(&mut self) {
+        self.views = self.views();
+        let reflecting = self.reflecting();
+        let grading = self.lut.is_some() && self.grade_strength > 0. || reflecting
+            || self.focusing() || self.blurring_motion() || self.post_effects.any()
+            || self.dynamic_resolution.is_some();
+        let render_extent = self.render_extent();
+        for index in 0..self.renderer.frames.len() {
+            let device = self.renderer.vk_context.device();
+            let pool = self.renderer.command_pool;
+            self.renderer.frames[index].free_command_buffers(device, pool);
+            let inputs = FrameInputs {
+                half_res_art: &self.half_res_art,
+                ray_tracing_pipelines: &self.ray_tracing_pipelines,
+                renderers: &self.renderers,
+                stereo_target: self.stereo_target.as_ref(),
+                grade_target: self.grade_target.as_ref().filter(|_| grading),
+                show_frame_graph: self.show_frame_graph,
+                overlay_size: self.overlay_text.as_ref().map(TextBitmap::size),
+                views: &self.views,
+                render_extent,
+                ubo_stride: self.ubo_stride,
+                timer: self.gpu_timer.as_ref(),
+                crash_markers: self.crash_markers.as_ref(),
+                occlusion: self.occlusion.as_ref().filter(|_| self.occlusion_culling || reflecting),
+                occlusion_culling: self.occlusion_culling,
+                reflections: reflecting,
+                light_clusters: Some(&self.light_clusters).filter(|_| !self.lights.is_empty()),
+                shadows: &self.shadows,
+                fog_volume: Some(&self.fog_volume).filter(|_| self.volumetric_fog),
+            };
+            let command_buffers =
+                self.renderer.record_command_buffers(index, &self.scene, &self.resources, &inputs);
+            self.renderer.frames[index].command_buffers = command_buffers;
+        }
+    }
 
-            Self::generate_mipmaps(
-                vk_context,
-                command_pool,
-                copy_queue,
-                image,
-                extent,
-                vk::Format::R8G8B8A8_UNORM,
-                max_mip_levels,
-                1,
-            );
+    /// The parts of the render target the scene is drawn into, one per camera.
+    ///
+    /// Up to two views are placed side by side, more are arranged in a grid.
+    fn views(&self) -> Vec<vk::Rect2D> {
+        if let Some(target) = self.stereo_target.as_ref() {
+            return vec![vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: target.extent(),
+            }];
         }
+        let extent = self.render_extent();
+        let count = (1 + self.scene.split_view_matrices.len()).min(MAX_VIEWS) as u32;
+        let (columns, rows) = if count <= 2 { (count, 1) } else { (2, 2) };
+        let width = extent.width / columns;
+        let height = extent.height / rows;
+        (0..count).map(|i| vk::Rect2D {
+            offset: vk::Offset2D {
+                x: ((i % columns) * width) as i32,
+                y: ((i / columns) * height) as i32,
+            },
+            extent: vk::Extent2D { width, height },
+        }).collect()
+    }
 
-        unsafe {
-            device.destroy_buffer(buffer, None);
-            device.free_memory(memory, None);
+    /// The part of the swapchain extent the gallery is drawn into before it is scaled up by the
+    /// post pass, see [`VkApp::set_dynamic_resolution`].
+    fn render_extent(&self) -> vk::Extent2D {
+        let extent = self.renderer.swapchain_properties.extent;
+        let scale = self.render_scale();
+        vk::Extent2D {
+            width: ((extent.width as f32 * scale).round() as u32).max(1),
+            height: ((extent.height as f32 * scale).round() as u32).max(1),
         }
+    }
 
-        let image_view = Self::create_image_view(
-            device,
-            image,
-            max_mip_levels,
-            vk::Format::R8G8B8A8_UNORM,
-            vk::ImageAspectFlags::COLOR,
-        );
+    /// The scale of the dynamic resolution, which needs the post pass and is one in stereo.
+    fn render_scale(&self) -> f32 {
+        match self.dynamic_resolution.as_ref() {
+            Some(resolution) if self.stereo_target.is_none() && self.grade_target.is_some() => {
+                resolution.scale()
+            }
+            _ => 1.,
+        }
+    }
 
-        let max_aniso = vk_context.physical_device_properties().limits.max_sampler_anisotropy;
-        let sampler_info = vk::SamplerCreateInfo::default()
-            .mag_filter(vk::Filter::LINEAR)
-            .min_filter(vk::Filter::LINEAR)
-            .address_mode_u(vk::SamplerAddressMode::REPEAT)
-            .address_mode_v(vk::SamplerAddressMode::REPEAT)
-            .address_mode_w(vk::SamplerAddressMode::REPEAT)
-            .anisotropy_enable(true)
-            .max_anisotropy(max_aniso.max(16.))
-            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
-            .unnormalized_coordinates(false)
-            .compare_enable(false)
-            .compare_op(vk::CompareOp::ALWAYS)
-            .mipmap_mode(vk::SamplerMipmapMode::LINEAR)
-            .mip_lod_bias(0.0)
-            .min_lod(0.0)
-            .max_lod(max_mip_levels as _);
-        let sampler = unsafe {
-            device.create_sampler(&sampler_info, None)
-                .context("Failed to create sampler for texture")?
-        };
+    /// Adds art drawn by `renderer` after the shader art, see [`ArtRenderer`].
+    pub fn register_renderer(
+        &mut self,
+        mut renderer: Box<dyn ArtRenderer>,
+    ) -> Result<(), anyhow::Error> {
+        renderer.create(&self.renderer.vk_context, self.renderer_target())
+            .with_context(|| format!("Failed to create the renderer {}", renderer.name()))?;
+        log::info!("Registered the renderer {}", renderer.name());
+        self.renderers.push(renderer);
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+        Ok(())
+    }
 
-        Ok(Texture::new(image, image_memory, image_view, Some(sampler)))
+    /// Lets the renderers recreate what depends on the render target after it changed.
+    ///
+    /// A renderer that fails is dropped.
+    fn resize_renderers(&mut self) {
+        let target = self.renderer_target();
+        let device = self.renderer.vk_context.device();
+        self.renderers.retain_mut(|renderer| {
+            match renderer.resize(&self.renderer.vk_context, target) {
+                Ok(()) => true,
+                Err(err) => {
+                    log::error!("Dropping the renderer {}: {err:#}", renderer.name());
+                    unsafe { renderer.destroy(device) };
+                    false
+                }
+            }
+        });
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn create_image(
-        vk_context: &VkContext,
-        mem_properties: vk::MemoryPropertyFlags,
-        extent: vk::Extent2D,
-        mip_levels: u32,
-        sample_count: vk::SampleCountFlags,
-        format: vk::Format,
-        tiling: vk::ImageTiling,
-        usage: vk::ImageUsageFlags,
-    ) -> (vk::Image, vk::DeviceMemory) {
-        let image_info = vk::ImageCreateInfo::default()
-            .image_type(vk::ImageType::TYPE_2D)
-            .extent(vk::Extent3D {
-                width: extent.width,
-                height: extent.height,
-                depth: 1,
-            })
-            .mip_levels(mip_levels)
-            .array_layers(1)
-            .format(format)
-            .tiling(tiling)
-            .initial_layout(vk::ImageLayout::UNDEFINED)
-            .usage(usage)
-            .sharing_mode(vk::SharingMode::EXCLUSIVE)
-            .samples(sample_count)
-            .flags(vk::ImageCreateFlags::empty());
+    fn renderer_target(&self) -> RenderTarget {
+        RenderTarget {
+            render_pass: self.render_target(),
+            samples: self.renderer.msaa_samples,
+            extent: self.stereo_target.as_ref()
+                .map_or(self.renderer.swapchain_properties.extent, StereoTarget::extent),
+            frames_in_flight: self.renderer.frames.len(),
+        }
+    }
 
-        let device = vk_context.device();
-        let image = unsafe { device.create_image(&image_info, None).unwrap() };
-        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
-        let mem_type_index = vk_context.find_memory_type(mem_requirements, mem_properties);
-        let alloc_info = vk::MemoryAllocateInfo::default()
-            .allocation_size(mem_requirements.size)
-            .memory_type_index(mem_type_index);
-        let memory = unsafe {
-            let mem = device.allocate_memory(&alloc_info, None).unwrap();
-            device.bind_image_memory(image, mem, 0).unwrap();
-            mem
-        };
+    /// The render pass the graphics pipelines are used with.
+    ///
+    /// This is the one of the swapchain unless rendering in stereo.
+    fn render_target(&self) -> vk::RenderPass {
+        self.stereo_target.as_ref().map_or(self.renderer.render_pass, StereoTarget::render_pass)
+    }
 
-        (image, memory)
+    /// Whether the swapchain can be pre-rotated, which is only done while nothing but the scene
+    /// is drawn through a single view. The split views, the preview, the entered art, the frame
+    /// graph, the text overlay and the captured frames are laid out in the orientation of the
+    /// window, so while one of them is shown the compositor rotates the images. The gizmo is
+    /// picked with the cursor in the window, so it is not rotated either.
+    fn can_pre_rotate(&self) -> bool {
+        self.views.len() == 1
+            && self.stereo_target.is_none()
+            && self.renderer.screen.preview.is_none()
+            && self.renderer.screen.entered.is_none()
+            && !self.show_frame_graph
+            && self.overlay_text.is_none()
+            && self.scene.edited_art().is_none()
+            && !self.is_capturing_frames()
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn transition_image_layout(
-        device: &Device,
-        command_pool: vk::CommandPool,
-        transition_queue: vk::Queue,
-        image: vk::Image,
-        mip_levels: u32,
-        format: vk::Format,
-        old_layout: vk::ImageLayout,
-        new_layout: vk::ImageLayout,
-        layer_count: u32,
-    ) {
-        cmd::execute_one_time_commands(device, command_pool, transition_queue, |buffer| {
-            let (src_access_mask, dst_access_mask, src_stage, dst_stage) =
-                match (old_layout, new_layout) {
-                    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL) => (
-                        vk::AccessFlags::empty(),
-                        vk::AccessFlags::TRANSFER_WRITE,
-                        vk::PipelineStageFlags::TOP_OF_PIPE,
-                        vk::PipelineStageFlags::TRANSFER,
-                    ),
-                    (
-                        vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
-                    ) => (
-                        vk::AccessFlags::TRANSFER_WRITE,
-                        vk::AccessFlags::SHADER_READ,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                    ),
-                    (
-                        vk::ImageLayout::UNDEFINED,
-                        vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
-                    ) => (
-                        vk::AccessFlags::empty(),
-                        vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
-                            | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
-                        vk::PipelineStageFlags::TOP_OF_PIPE,
-                        vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
-                    ),
-                    (vk::ImageLayout::UNDEFINED, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL) => (
-                        vk::AccessFlags::empty(),
-                        vk::AccessFlags::COLOR_ATTACHMENT_READ
-                            | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
-                        vk::PipelineStageFlags::TOP_OF_PIPE,
-                        vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-                    ),
-                    _ => panic!(
-                        "Unsupported layout transition({:?} => {:?}).",
-                        old_layout, new_layout
-                    ),
-                };
+    pub fn wait_gpu_idle(&self) {
+        self.check_device(self.renderer.wait_idle());
+    }
 
-            let aspect_mask = if new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
-                let mut mask = vk::ImageAspectFlags::DEPTH;
-                if Self::has_stencil_component(format) {
-                    mask |= vk::ImageAspectFlags::STENCIL;
-                }
-                mask
-            } else {
-                vk::ImageAspectFlags::COLOR
-            };
-
-            let barrier = vk::ImageMemoryBarrier::default()
-                .old_layout(old_layout)
-                .new_layout(new_layout)
-                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                .image(image)
-                .subresource_range(vk::ImageSubresourceRange {
-                    aspect_mask,
-                    base_mip_level: 0,
-                    level_count: mip_levels,
-                    base_array_layer: 0,
-                    layer_count,
-                })
-                .src_access_mask(src_access_mask)
-                .dst_access_mask(dst_access_mask);
+    /// Unwraps `result` and panics with what the GPU was drawing if the device was lost.
+    fn check_device<T>(&self, result: VkResult<T>) -> T {
+        match result {
+            Ok(value) => value,
+            Err(vk::Result::ERROR_DEVICE_LOST) => self.device_lost(),
+            Err(err) => panic!("{err}"),
+        }
+    }
 
-            unsafe {
-                device.cmd_pipeline_barrier(
-                    buffer,
-                    src_stage,
-                    dst_stage,
-                    vk::DependencyFlags::empty(),
-                    &[],
-                    &[],
-                    &[barrier],
-                )
+    /// Panics with the art that was drawn when the device was lost according to the crash markers,
+    /// e.g. a shader that took too long.
+    fn device_lost(&self) -> ! {
+        let Some(markers) = self.crash_markers.as_ref() else {
+            panic!("The device was lost, the driver does not support crash markers to tell why");
+        };
+        let report = markers.report(self.renderer.graphics_queue);
+        let name = |marker: Option<Marker>| {
+            let pipeline = match marker {
+                Some(Marker::Pipeline(index)) => self.renderer.pipelines.get(index),
+                Some(Marker::Entered) => self.renderer.screen.entered.as_ref(),
+                Some(Marker::Preview) => self.renderer.screen.preview.as_ref(),
+                None => return "nothing".to_owned(),
             };
-        });
+            pipeline.map_or_else(|| format!("{marker:?}"), |pipeline| pipeline.name().to_owned())
+        };
+        log::error!(
+            "Last pipeline started: {}, last pipeline finished: {}",
+            name(report.started),
+            name(report.finished),
+        );
+        match report.executing() {
+            Some(marker) => panic!("The device was lost while drawing {}", name(Some(marker))),
+            None => panic!("The device was lost after drawing {}", name(report.finished)),
+        }
     }
 
-    fn copy_buffer_to_image(
-        device: &Device,
-        command_pool: vk::CommandPool,
-        transition_queue: vk::Queue,
-        buffer: vk::Buffer,
-        image: vk::Image,
-        extent: vk::Extent2D,
-        layer_count: u32,
-    ) {
-        cmd::execute_one_time_commands(device, command_pool, transition_queue, |command_buffer| {
-            let region = vk::BufferImageCopy::default()
-                .buffer_offset(0)
-                .buffer_row_length(0)
-                .buffer_image_height(0)
-                .image_subresource(vk::ImageSubresourceLayers {
-                    aspect_mask: vk::ImageAspectFlags::COLOR,
-                    mip_level: 0,
-                    base_array_layer: 0,
-                    layer_count,
-                })
-                .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
-                .image_extent(vk::Extent3D {
-                    width: extent.width,
-                    height: extent.height,
-                    depth: 1,
-                });
-            let regions = [region];
-            unsafe {
-                device.cmd_copy_buffer_to_image(
-                    command_buffer,
-                    buffer,
-                    image,
-                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                    &regions,
-                )
-            }
-        })
-    }
+    /// Draws a frame. Takes as argument the time passed in seconds as f32.
+    ///
+    /// #Returns
+    ///
+    /// True if the swapchain is dirty and needs to be recreated.
+    pub fn draw_frame(&mut self, time: f32) -> bool {
+        log::trace!("Drawing frame.");
 
-    #[allow(clippy::too_many_arguments)]
-    fn generate_mipmaps(
-        vk_context: &VkContext,
-        command_pool: vk::CommandPool,
-        transfer_queue: vk::Queue,
-        image: vk::Image,
-        extent: vk::Extent2D,
-        format: vk::Format,
-        mip_levels: u32,
-        layer_count: u32,
-    ) {
-        let format_properties = unsafe {
-            vk_context.instance()
-                .get_physical_device_format_properties(vk_context.physical_device(), format)
-        };
-        if !format_properties.optimal_tiling_features
-            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
-        {
-            panic!("Linear blitting is not supported for format {:?}.", format)
+        self.reload_changed_files();
+
+        let mut recreate_command_buffers = self.update_shading_rates();
+        recreate_command_buffers |= self.views() != self.views;
+        let render_pass = self.render_target();
+        let replaces_fallback = self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+            .filter(|pipeline| !pipeline.has_changed() && !pipeline.waiting_for_shaders)
+            .any(Pipeline::fallback_replaceable);
+        if replaces_fallback {
+            // the fallback pipelines may still be in use by a frame in flight
+            self.check_device(self.renderer.wait_for_frames());
+        }
+        let device = self.renderer.vk_context.device();
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            if pipeline.has_changed() {
+                recreate_command_buffers = true;
+            } else if pipeline.waiting_for_shaders || pipeline.fallback_replaceable() {
+                pipeline.recreate(
+                    device,
+                    self.renderer.msaa_samples,
+                    render_pass,
+                    self.descriptor_set_layout,
+                );
+                recreate_command_buffers |= !pipeline.waiting_for_shaders;
+            }
+        }
+        for pipeline in self.half_res_art.iter_mut().map(|art| &mut art.upscale) {
+            if pipeline.waiting_for_shaders {
+                pipeline.recreate(
+                    device,
+                    self.renderer.msaa_samples,
+                    render_pass,
+                    self.descriptor_set_layout,
+                );
+                recreate_command_buffers |= !pipeline.waiting_for_shaders;
+            }
+        }
+        for pipeline in self.ray_tracing_pipelines.iter_mut() {
+            if pipeline.has_changed() {
+                recreate_command_buffers = true;
+            } else if pipeline.waiting_for_shaders {
+                pipeline.recreate(&self.renderer.vk_context);
+                recreate_command_buffers |= !pipeline.waiting_for_shaders;
+            }
+        }
+        let view_matrix = self.scene.view_matrix;
+        let sleep_changed = self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut()
+            .fold(false, |changed, pipeline| pipeline.update_sleep(view_matrix) | changed);
+        if recreate_command_buffers {
+            self.check_device(self.renderer.wait_for_frames());
+            for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+                pipeline.reload_shaders(device, false);
+            }
+            for pipeline in self.ray_tracing_pipelines.iter_mut() {
+                pipeline.reload_shaders(device, false);
+            }
+            // the preview and the entered art share the fragment shader of their art
+            // and the art scaled up shares its vertex shader
+            let pipelines = self.renderer.screen.preview.iter_mut()
+                .chain(self.renderer.screen.entered.as_mut())
+                .chain(self.half_res_art.iter_mut().map(|art| &mut art.upscale));
+            for pipeline in pipelines {
+                pipeline.recreate(
+                    device,
+                    self.renderer.msaa_samples,
+                    render_pass,
+                    self.descriptor_set_layout,
+                );
+            }
+            if let Some(projector) = self.projector.as_mut() {
+                let render_pass = projector.render_pass();
+                if let Some(pipeline) = projector.pipeline.as_mut() {
+                    pipeline.recreate(
+                        device,
+                        vk::SampleCountFlags::TYPE_1,
+                        render_pass,
+                        self.descriptor_set_layout,
+                    );
+                }
+            }
+            self.recreate_command_buffers();
+        } else if sleep_changed {
+            self.check_device(self.renderer.wait_for_frames());
+            self.recreate_command_buffers();
+        }
+        if let Some(projector) = self.projector.as_mut() {
+            if projector.recreate_swapchain_if_dirty(&self.renderer.vk_context) {
+                let render_pass = projector.render_pass();
+                if let Some(pipeline) = projector.pipeline.as_mut() {
+                    pipeline.recreate(
+                        self.renderer.vk_context.device(),
+                        vk::SampleCountFlags::TYPE_1,
+                        render_pass,
+                        self.descriptor_set_layout,
+                    );
+                }
+            }
         }
 
-        cmd::execute_one_time_commands(
-            vk_context.device(),
-            command_pool,
-            transfer_queue,
-            |buffer| {
-                let mut barrier = vk::ImageMemoryBarrier::default()
-                    .image(image)
-                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
-                    .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_array_layer: 0,
-                        layer_count,
-                        level_count: 1,
-                        ..Default::default()
-                    });
-
-                let mut mip_width = extent.width as i32;
-                let mut mip_height = extent.height as i32;
-                for level in 1..mip_levels {
-                    let next_mip_width = if mip_width > 1 {
-                        mip_width / 2
-                    } else {
-                        mip_width
-                    };
-                    let next_mip_height = if mip_height > 1 {
-                        mip_height / 2
-                    } else {
-                        mip_height
-                    };
-
-                    barrier.subresource_range.base_mip_level = level - 1;
-                    barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-                    barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-                    barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-                    barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
-                    let barriers = [barrier];
-
-                    unsafe {
-                        vk_context.device().cmd_pipeline_barrier(
-                            buffer,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::DependencyFlags::empty(),
-                            &[],
-                            &[],
-                            &barriers,
-                        )
-                    };
-
-                    let blit = vk::ImageBlit::default()
-                        .src_offsets([
-                            vk::Offset3D { x: 0, y: 0, z: 0 },
-                            vk::Offset3D {
-                                x: mip_width,
-                                y: mip_height,
-                                z: 1,
-                            },
-                        ])
-                        .src_subresource(vk::ImageSubresourceLayers {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            mip_level: level - 1,
-                            base_array_layer: 0,
-                            layer_count,
-                        })
-                        .dst_offsets([
-                            vk::Offset3D { x: 0, y: 0, z: 0 },
-                            vk::Offset3D {
-                                x: next_mip_width,
-                                y: next_mip_height,
-                                z: 1,
-                            },
-                        ])
-                        .dst_subresource(vk::ImageSubresourceLayers {
-                            aspect_mask: vk::ImageAspectFlags::COLOR,
-                            mip_level: level,
-                            base_array_layer: 0,
-                            layer_count,
-                        });
-                    let blits = [blit];
-
-                    unsafe {
-                        vk_context.device().cmd_blit_image(
-                            buffer,
-                            image,
-                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
-                            image,
-                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                            &blits,
-                            vk::Filter::LINEAR,
-                        )
-                    };
-
-                    barrier.old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
-                    barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-                    barrier.src_access_mask = vk::AccessFlags::TRANSFER_READ;
-                    barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
-                    let barriers = [barrier];
-
-                    unsafe {
-                        vk_context.device().cmd_pipeline_barrier(
-                            buffer,
-                            vk::PipelineStageFlags::TRANSFER,
-                            vk::PipelineStageFlags::FRAGMENT_SHADER,
-                            vk::DependencyFlags::empty(),
-                            &[],
-                            &[],
-                            &barriers,
-                        )
-                    };
-
-                    mip_width = next_mip_width;
-                    mip_height = next_mip_height;
+        let frame_index = self.renderer.next_frame();
+        self.check_device(self.renderer.wait_for_frame(frame_index));
+        if let Some(timer) = self.gpu_timer.as_ref() {
+            if let Some(times) = timer.read(self.renderer.vk_context.device(), frame_index) {
+                if let Some(resolution) = self.dynamic_resolution.as_mut() {
+                    // the views are compared with the new scale on the next frame
+                    if resolution.push(times.frame) {
+                        log::debug!("Render scale: {:.1}", resolution.scale());
+                    }
                 }
+                self.gpu_times = Some(times);
+            }
+        }
+        let device = self.renderer.vk_context.device();
+        if let Some(readback) = self.renderer.frames[frame_index].readback.as_mut() {
+            if readback.pending {
+                readback.pending = false;
+                self.captured_frame = Some(unsafe { readback.read(device) });
+            }
+        }
 
-                barrier.subresource_range.base_mip_level = mip_levels - 1;
-                barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
-                barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
-                barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
-                barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
-                let barriers = [barrier];
+        let Some(image_index) = self.renderer.acquire_next_image(frame_index) else {
+            return true;
+        };
 
-                unsafe {
-                    vk_context.device().cmd_pipeline_barrier(
-                        buffer,
-                        vk::PipelineStageFlags::TRANSFER,
-                        vk::PipelineStageFlags::FRAGMENT_SHADER,
-                        vk::DependencyFlags::empty(),
-                        &[],
-                        &[],
-                        &barriers,
-                    )
-                };
-            },
+        // the second window is skipped while its swapchain is out of date
+        let projector_image = self.projector.as_mut()
+            .and_then(|projector| projector.acquire_next_image(frame_index));
+
+        self.run_script(time);
+        #[cfg(feature = "openxr")]
+        self.begin_openxr_frame();
+        self.update_uniform_buffers(frame_index, time, None);
+        self.resources.keyboard_texture.write(frame_index, &self.scene.keyboard.texels());
+        self.scene.keyboard.end_frame();
+        if let Some(video) = self.resources.video.as_mut() {
+            video.update(frame_index, time);
+        }
+        if let Some(animated) = self.resources.animated_image.as_mut() {
+            animated.update(frame_index, time);
+        }
+
+        let device = self.renderer.vk_context.device();
+        let frame = &self.renderer.frames[frame_index];
+        let mut wait_semaphores = vec![frame.image_available_semaphore];
+        let mut signal_semaphores = vec![frame.render_finished_semaphore];
+        let mut command_buffers = vec![frame.command_buffers[image_index as usize]];
+        let mut other_swapchains = Vec::new();
+        if let (Some(projector), Some((projector_image_index, projector_image_available))) =
+            (self.projector.as_ref(), projector_image)
+        {
+            wait_semaphores.push(projector_image_available);
+            signal_semaphores.push(projector.render_finished_semaphore(frame_index));
+            command_buffers.push(projector.record_command_buffer(
+                device,
+                frame_index,
+                projector_image_index,
+                UBO_IDX_PROJECTOR as u32 * self.ubo_stride,
+            ));
+            other_swapchains.push((projector.swapchain_khr(), projector_image_index));
+        }
+        #[cfg(feature = "openxr")]
+        if let (Some(openxr), Some(target)) = (self.openxr.as_mut(), self.stereo_target.as_ref()) {
+            // the eyes are scaled into the headset after they are copied into the window
+            let buffer = unsafe { openxr.record_command_buffer(device, frame_index, target) };
+            command_buffers.extend(buffer);
+        }
+
+        let result = self.renderer.submit(
+            frame_index,
+            &wait_semaphores,
+            &command_buffers,
+            &signal_semaphores,
         );
+        self.check_device(result);
+        if let Some(readback) = self.renderer.frames[frame_index].readback.as_mut() {
+            readback.pending = true;
+        }
+        #[cfg(feature = "openxr")]
+        if let Some(openxr) = self.openxr.as_mut() {
+            let extent = self.stereo_target.as_ref()
+                .map_or(self.renderer.swapchain_properties.extent, StereoTarget::extent);
+            let aspect = extent.width as f32 / extent.height as f32;
+            openxr.end_frame(Rad::from(self.scene.projection.fovy).0, aspect);
+        }
+
+        // the gallery and the second window are presented together
+        let results =
+            self.renderer.present(frame_index, image_index, &signal_semaphores, &other_swapchains);
+        if let Some(projector) = self.projector.as_mut().filter(|_| results.len() > 1) {
+            projector.dirty |= results[1] != vk::Result::SUCCESS;
+        }
+        let pre_rotate = self.can_pre_rotate();
+        // only a rotated surface needs a new swapchain when pre-rotating starts or stops
+        let rotation_changed = pre_rotate != self.renderer.pre_rotate
+            && self.renderer.swapchain_properties.surface_transform
+                != vk::SurfaceTransformFlagsKHR::IDENTITY;
+        self.renderer.pre_rotate = pre_rotate;
+        results[0] != vk::Result::SUCCESS || rotation_changed
     }
 
-    fn load_model<V: Vertex>(nobj: NormalizedObj) -> (Vec<V>, Vec<u32>, (Vector3, Vector3)) {
-        let mut min = Vector3::new(f32::MAX);
-        let mut max = Vector3::new(f32::MIN);
-        for vertex in &nobj.vertices {
-            for (i, &coord) in vertex.pos_coords.iter().enumerate() {
-                min[i] = min[i].min(coord);
-                max[i] = max[i].max(coord);
+    /// Shade art that is far away or at the edge of the view at a coarser rate.
+    ///
+    /// # Returns
+    ///
+    /// True if a shading rate changed and the command buffers need to be recorded again.
+    fn update_shading_rates(&mut self) -> bool {
+        let mut changed = false;
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            if !pipeline.has_variable_shading_rate() {
+                continue;
             }
-        }
-        let vertices = nobj.vertices.iter().map(|vertex| {
-            let tex_coords = if nobj.has_tex_coords {
-                vertex.tex_coords
+            let Some(model) = pipeline.model_matrix() else {
+                continue;
+            };
+            // position of the art in view space, the camera looks along -z
+            let pos = (model[3] * self.scene.view_matrix).resize::<3>();
+            let dist = pos.magnitude();
+
+            // make it harder to switch back to avoid flickering at the thresholds
+            let is_coarse = pipeline.shading_rate() == COARSE_SHADING_RATE;
+            let (coarse_dist, coarse_cos) = if is_coarse {
+                (COARSE_SHADING_DISTANCE - 1., COARSE_SHADING_COS + 0.05)
             } else {
-                [vertex.pos_coords[2], vertex.pos_coords[1]]
+                (COARSE_SHADING_DISTANCE, COARSE_SHADING_COS)
             };
-            V::new(vertex.pos_coords, [1.0, 1.0, 1.0], tex_coords)
-        }).collect();
-
-        (vertices, nobj.indices, (min, max))
+            let far = dist > coarse_dist;
+            let peripheral = -pos.z() < dist * coarse_cos;
+            let shading_rate = if dist > FULL_SHADING_DISTANCE && (far || peripheral) {
+                COARSE_SHADING_RATE
+            } else {
+                FULL_SHADING_RATE
+            };
+            if shading_rate != pipeline.shading_rate() {
+                log::debug!("shading {} at {shading_rate:?}", pipeline.name());
+                pipeline.set_shading_rate(shading_rate);
+                changed = true;
+            }
+        }
+        changed
     }
 
-    fn create_uniform_buffers(
-        vk_context: &VkContext,
-        count: usize,
-    ) -> (Vec<vk::Buffer>, Vec<vk::DeviceMemory>) {
-        let size = size_of::<UniformBufferObject>() as vk::DeviceSize;
-        let mut buffers = Vec::new();
-        let mut memories = Vec::new();
+    pub fn load_new_texture<P: AsRef<Path>>(&mut self, path: P) -> Result<(), anyhow::Error> {
+        log::info!("Loading image {:?}", path.as_ref().as_os_str());
+        self.wait_gpu_idle();
 
-        for _ in 0..count {
-            let (buffer, memory, _) = buffer::create_buffer(
-                vk_context,
-                size,
-                vk::BufferUsageFlags::UNIFORM_BUFFER,
-                vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
-            );
-            buffers.push(buffer);
-            memories.push(memory);
+        if path.as_ref() != self.resources.image_path {
+            // the art image can be the same file
+            if self.resources.image_path != Path::new(ART_IMAGE) {
+                self.resources.file_watcher.unwatch(&self.resources.image_path);
+            }
+            self.resources.image_path = path.as_ref().to_owned();
+            self.resources.file_watcher.watch(&self.resources.image_path);
         }
 
-        (buffers, memories)
+        // the old image is kept if the new one fails to load, e.g. while it is still written
+        let (texture, extent) = ResourceManager::create_texture_image(
+            &self.renderer.vk_context,
+            self.renderer.command_pool,
+            self.renderer.graphics_queue,
+            &path,
+            self.resources.samplers.image,
+        )?;
+        let animation = ResourceManager::load_animation(path.as_ref());
+        self.resources.image_aspect = aspect_ratio(extent);
+
+        self.resources.textures.image.destroy(self.renderer.vk_context.device());
+        self.resources.textures.image = texture;
+        self.animate_image(animation);
+        self.recreate_command_buffers();
+        Ok(())
     }
 
-    fn recreate_command_buffers(&mut self) {
-        let device = self.vk_context.device();
-        unsafe {
-            device.free_command_buffers(self.command_pool, &self.command_buffers);
+    /// Shows the frames of `animation` instead of `textures.image`,
+    /// which is shown again if `animation` is `None`.
+    ///
+    /// The command buffers have to be recorded again afterwards.
+    fn animate_image(&mut self, animation: Option<Animation>) {
+        let animated = animation.and_then(|animation| {
+            let texture = StreamTexture::new(
+                &self.renderer.vk_context,
+                self.renderer.command_pool,
+                self.renderer.graphics_queue,
+                vk::Format::R8G8B8A8_UNORM,
+                4,
+                vk::Extent2D { width: animation.width, height: animation.height },
+                self.resources.samplers.image,
+                self.renderer.frames.len(),
+            );
+            match texture {
+                Ok(texture) => Some(AnimatedImage {
+                    animation,
+                    texture,
+                    start: None,
+                    uploaded: vec![None; self.renderer.frames.len()],
+                }),
+                Err(err) => {
+                    log::warn!("Showing only the first frame of the animation: {err:#}");
+                    None
+                }
+            }
+        });
+
+        let device = self.renderer.vk_context.device();
+        if let Some(mut animated) = self.resources.animated_image.take() {
+            unsafe { animated.texture.destroy(device) };
         }
+        let texture = animated.as_ref()
+            .map_or(self.resources.textures.image, |animated| animated.texture.texture());
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_main, 1, texture);
+        self.resources.animated_image = animated;
+    }
 
-        self.command_buffers = Self::create_and_register_command_buffers(
-            device,
-            self.command_pool,
-            &self.swapchain_framebuffers,
-            self.render_pass,
-            self.swapchain_properties,
-            &self.pipelines,
-        );
+    /// Bumps the surface of the environment with the tangent space normal map at `path`,
+    /// `None` makes it flat again.
+    ///
+    /// Green points along the texture coordinate v like in OpenGL, the map repeats every unit
+    /// of the coordinates. Surfaces whose coordinates do not span an area read the map along
+    /// the world axis closest to their normal instead.
+    ///
+    /// The alpha channel is the height of the surface, the lowest parts are `parallax_scale`
+    /// units of the coordinates deep. Parallax occlusion mapping shifts the coordinates along
+    /// the view ray by that depth, a scale of zero turns it off.
+    pub fn set_normal_map(
+        &mut self,
+        path: Option<&Path>,
+        parallax_scale: f32,
+    ) -> Result<(), anyhow::Error> {
+        let texture = match path {
+            Some(path) => ResourceManager::create_texture_image(
+                &self.renderer.vk_context,
+                self.renderer.command_pool,
+                self.renderer.graphics_queue,
+                path,
+                SamplerConfig::REPEAT,
+            )?.0,
+            None => ResourceManager::create_texture_from_rgba(
+                &self.renderer.vk_context,
+                self.renderer.command_pool,
+                self.renderer.graphics_queue,
+                vk::Extent2D { width: 1, height: 1 },
+                &FLAT_NORMAL,
+                SamplerConfig::REPEAT,
+            )?,
+        };
+
+        self.wait_gpu_idle();
+        let device = self.renderer.vk_context.device();
+        self.resources.textures.normal_map.destroy(device);
+        self.resources.textures.normal_map = texture;
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_main, 11, texture);
+        self.parallax_scale = if path.is_some() { parallax_scale.max(0.) } else { 0. };
+        self.recreate_command_buffers();
+        Ok(())
     }
 
-    #[allow(clippy::too_many_arguments)]
-    fn create_and_register_command_buffers(
-        device: &Device,
-        pool: vk::CommandPool,
-        framebuffers: &[vk::Framebuffer],
-        render_pass: vk::RenderPass,
-        swapchain_properties: SwapchainProperties,
-        pipelines: &[Pipeline],
-    ) -> Vec<vk::CommandBuffer> {
-        let allocate_info = vk::CommandBufferAllocateInfo::default()
-            .command_pool(pool)
-            .level(vk::CommandBufferLevel::PRIMARY)
-            .command_buffer_count(framebuffers.len() as _);
-        let buffers = unsafe { device.allocate_command_buffers(&allocate_info).unwrap() };
-
-        for (i, &buffer) in buffers.iter().enumerate() {
-            // begin command buffer
-            let command_buffer_begin_info = vk::CommandBufferBeginInfo::default()
-                .flags(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE);
-            unsafe {
-                device.begin_command_buffer(buffer, &command_buffer_begin_info).unwrap()
-            };
+    /// Lights the environment with the lightmap at `path` baked by [`crate::lightmap::bake`]
+    /// for the current environment, `None` lights it without one again.
+    ///
+    /// The lightmap has to have the size of the layout of the environment, otherwise it was
+    /// baked for another one.
+    pub fn set_lightmap(&mut self, path: Option<&Path>) -> Result<(), anyhow::Error> {
+        let texture = match path {
+            Some(path) => {
+                let index_count = self.renderer.pipelines[PIPELINE_IDX_MAIN].geometry.as_ref()
+                    .and_then(Geometry::get)
+                    .map(|(_, _, index_count)| index_count)
+                    .context("The environment has no geometry")?;
+                let [width, height] = LightmapLayout::new(index_count as usize / 3).size();
+                let (mut texture, extent) = ResourceManager::create_texture_image(
+                    &self.renderer.vk_context,
+                    self.renderer.command_pool,
+                    self.renderer.graphics_queue,
+                    path,
+                    SamplerConfig::CLAMP,
+                )?;
+                if [extent.width, extent.height] != [width, height] {
+                    texture.destroy(self.renderer.vk_context.device());
+                    anyhow::bail!(
+                        "The lightmap {} is {}x{} instead of {width}x{height}, \
+                            it was baked for another environment",
+                        path.display(),
+                        extent.width,
+                        extent.height,
+                    );
+                }
+                texture
+            }
+            None => ResourceManager::create_texture_from_rgba(
+                &self.renderer.vk_context,
+                self.renderer.command_pool,
+                self.renderer.graphics_queue,
+                vk::Extent2D { width: 1, height: 1 },
+                &[255; 4],
+                SamplerConfig::CLAMP,
+            )?,
+        };
 
-            // begin render pass
-            let clear_values = [
-                vk::ClearValue {
-                    color: vk::ClearColorValue {
-                        float32: [0.0, 0.0, 0.0, 1.0],
-                    },
-                },
-                vk::ClearValue {
-                    depth_stencil: vk::ClearDepthStencilValue {
-                        depth: 1.0,
-                        stencil: 0,
-                    },
-                },
-            ];
-            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
-                .render_pass(render_pass)
-                .framebuffer(framebuffers[i])
-                .render_area(vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: swapchain_properties.extent,
+        self.wait_gpu_idle();
+        let device = self.renderer.vk_context.device();
+        self.resources.textures.lightmap.destroy(device);
+        self.resources.textures.lightmap = texture;
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_main, 12, texture);
+        if path.is_some() != self.has_lightmap {
+            let render_pass = self.render_target();
+            let pipeline = &mut self.renderer.pipelines[PIPELINE_IDX_MAIN];
+            let unused = &mut self.resources.shaders.main_frag_unused;
+            if let Some(shader) = pipeline.replace_shader(unused.clone()) {
+                *unused = shader;
+            }
+            pipeline.recreate(
+                device,
+                self.renderer.msaa_samples,
+                render_pass,
+                self.descriptor_set_layout,
+            );
+            self.has_lightmap = path.is_some();
+        }
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// Plays the video at `path` in a loop as the texture art samples at binding 8
+    /// or stops the video if `path` is `None`.
+    ///
+    /// The video is decoded by ffmpeg, which has to be installed.
+    pub fn set_video(&mut self, path: Option<&Path>) -> Result<(), anyhow::Error> {
+        let video = path
+            .map(|path| {
+                let decoder = VideoDecoder::new(path)?;
+                let info = decoder.info();
+                let texture = StreamTexture::new(
+                    &self.renderer.vk_context,
+                    self.renderer.command_pool,
+                    self.renderer.graphics_queue,
+                    vk::Format::R8G8B8A8_UNORM,
+                    4,
+                    vk::Extent2D { width: info.width, height: info.height },
+                    SamplerConfig::CLAMP,
+                    self.renderer.frames.len(),
+                )?;
+                Ok::<_, anyhow::Error>(VideoStream {
+                    decoder,
+                    texture,
+                    uploaded: vec![None; self.renderer.frames.len()],
                 })
-                .clear_values(&clear_values);
-            unsafe {
-                device.cmd_begin_render_pass(
-                    buffer,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
-                )
-            };
+            })
+            .transpose()?;
+
+        self.wait_gpu_idle();
+        let device = self.renderer.vk_context.device();
+        if let Some(mut video) = self.resources.video.take() {
+            unsafe { video.texture.destroy(device) };
+        }
+        let texture = video.as_ref()
+            .map_or(self.resources.textures.video_empty, |video| video.texture.texture());
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter() {
+            Self::update_descriptor_sets_image(device, pipeline.descriptor_sets(), 8, texture);
+        }
+        self.resources.video = video;
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// Loads the image the art samples again from [`ART_IMAGE`].
+    fn reload_art_texture(&mut self) -> Result<(), anyhow::Error> {
+        log::info!("Loading art image {ART_IMAGE:?}");
+        self.wait_gpu_idle();
+
+        let (texture, extent) = ResourceManager::create_texture_image(
+            &self.renderer.vk_context,
+            self.renderer.command_pool,
+            self.renderer.graphics_queue,
+            ART_IMAGE,
+            self.resources.samplers.art,
+        )?;
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            pipeline.set_image_aspect(aspect_ratio(extent));
+        }
+        self.resources.art_image_aspect = aspect_ratio(extent);
+        let device = self.renderer.vk_context.device();
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_art, 1, texture);
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_volume, 1, texture);
+        self.resources.textures.art_image.destroy(device);
+        self.resources.textures.art_image = texture;
+        self.recreate_command_buffers();
+        Ok(())
+    }
 
-            for pipeline in pipelines.iter() {
-                if !pipeline.active || pipeline.waiting_for_shaders {
-                    continue;
+    /// Loads the images and the models of the art again if their files changed.
+    fn reload_changed_files(&mut self) {
+        for path in self.resources.file_watcher.changed() {
+            if path == self.resources.image_path {
+                if let Err(err) = self.load_new_texture(&path) {
+                    log::warn!("Error while reloading image: {err}");
                 }
-                unsafe {
-                    // bind pipeline, vertex and index buffer
-                    // bind descriptor set
-                    // draw
-                    pipeline.bind_to_cmd_buffer(device, buffer, i);
+            }
+            if path == Path::new(ART_IMAGE) {
+                if let Err(err) = self.reload_art_texture() {
+                    log::warn!("Error while reloading art image: {err}");
+                }
+            }
+            if path == Path::new(SKYBOX_MODEL) {
+                if let Err(err) = self.reload_model(&path) {
+                    log::warn!("Error while reloading model: {err}");
+                }
+            }
+            if self.script_path.as_ref().is_some_and(|script_path| path == *script_path) {
+                match Script::load(&path) {
+                    Ok(script) => self.replace_script(Some(script)),
+                    Err(err) => log::warn!("Error while reloading script: {err:#}"),
                 }
             }
+            if self.lights_path.as_ref().is_some_and(|lights_path| path == *lights_path) {
+                match lights::load(&path) {
+                    Ok(lights) => self.replace_lights(lights),
+                    Err(err) => log::warn!("Error while reloading lights: {err:#}"),
+                }
+            }
+            if path == Path::new(QUAD_MODEL) {
+                if let Err(err) = self.reload_model(&path) {
+                    log::warn!("Error while reloading model: {err}");
+                }
+            }
+        }
+    }
 
-            // end render pass and command buffer
-            unsafe {
-                device.cmd_end_render_pass(buffer);
-                device.end_command_buffer(buffer).unwrap();
-            };
+    /// Loads the skybox or the quad at `path` again and draws it in every pipeline that
+    /// draws the old one.
+    fn reload_model(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        log::info!("Loading model {:?}", path.as_os_str());
+        let nobj = NormalizedObj::from_reader(fs::load(path)?)?;
+        let (vertices, indices, _) = Self::load_model::<VertexSimple>(nobj);
+        self.wait_gpu_idle();
+        let geometry = Geometry::new(
+            &self.renderer.vk_context,
+            self.renderer.transient_command_pool,
+            self.renderer.graphics_queue,
+            &vertices,
+            &indices,
+        );
+        let shared = if path == Path::new(SKYBOX_MODEL) {
+            &mut self.resources.skybox
+        } else {
+            &mut self.resources.quad
+        };
+        let mut old = std::mem::replace(shared, geometry.clone());
+        let buffers = old.get();
+        unsafe { old.release(self.renderer.vk_context.device()) };
+        self.replace_geometry(buffers, geometry);
+        Ok(())
+    }
+
+    /// Replaces the environment with `nobj`, the ray traced art sees the new one as well.
+    ///
+    /// The lightmap is dropped as it belongs to the old environment.
+    pub fn set_environment(&mut self, nobj: NormalizedObj) {
+        log::info!("Loading new environment");
+        if self.has_lightmap {
+            log::warn!("Dropping the lightmap baked for the old environment");
+            if let Err(err) = self.set_lightmap(None) {
+                log::error!("Failed to drop the lightmap: {err:#}");
+            }
+        }
+        self.wait_gpu_idle();
+        self.env_tex_coords = nobj.has_tex_coords;
+        let (vertices, indices) = Self::load_environment_model(nobj);
+        let geometry = Geometry::new(
+            &self.renderer.vk_context,
+            self.renderer.transient_command_pool,
+            self.renderer.graphics_queue,
+            &vertices,
+            &indices,
+        );
+        let ray_tracing = self.renderer.vk_context.ray_tracing()
+            .filter(|_| !self.acceleration_structures.is_empty());
+        if let Some(ray_tracing) = ray_tracing {
+            let blas = AccelerationStructure::new_bottom_level(
+                &self.renderer.vk_context,
+                self.renderer.transient_command_pool,
+                self.renderer.graphics_queue,
+                &geometry,
+            );
+            let tlas = AccelerationStructure::new_top_level(
+                &self.renderer.vk_context,
+                self.renderer.transient_command_pool,
+                self.renderer.graphics_queue,
+                &[(&blas, Matrix4::unit())],
+            );
+            let device = self.renderer.vk_context.device();
+            for pipeline in self.ray_tracing_pipelines.iter() {
+                pipeline.set_tlas(device, &tlas);
+            }
+            let old = std::mem::replace(&mut self.acceleration_structures, vec![blas, tlas]);
+            for acceleration_structure in old.iter() {
+                unsafe { acceleration_structure.cleanup(device, ray_tracing) };
+            }
+        }
+        let buffers = self.renderer.pipelines[PIPELINE_IDX_MAIN].geometry.as_ref()
+            .and_then(Geometry::get);
+        self.replace_geometry(buffers, geometry);
+    }
+
+    /// Replaces the geometry with the buffers `old` in all pipelines by `geometry` and records
+    /// the command buffers again, the GPU must be idle.
+    fn replace_geometry(&mut self, old: Option<(vk::Buffer, vk::Buffer, u32)>, geometry: Geometry) {
+        let device = self.renderer.vk_context.device();
+        for pipeline in self.renderer.pipelines.iter_mut() {
+            if old.is_some() && pipeline.geometry.as_ref().and_then(Geometry::get) == old {
+                if let Some(old) = pipeline.geometry.replace(geometry.clone()) {
+                    unsafe { old.cleanup(device) };
+                }
+            }
+        }
+        unsafe { geometry.cleanup(device) };
+        self.recreate_command_buffers();
+    }
+
+    /// Replaces the day and night cubemaps with the ones in `dir`, see `create_cubemaps`.
+    pub fn load_cubemap<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), anyhow::Error> {
+        log::info!("Loading cubemap {:?}", dir.as_ref().as_os_str());
+        self.wait_gpu_idle();
+
+        let (day, night) = ResourceManager::create_cubemaps(
+            &self.renderer.vk_context,
+            self.renderer.command_pool,
+            self.renderer.graphics_queue,
+            dir,
+            self.resources.samplers.cubemap,
+        )?;
+        let device = self.renderer.vk_context.device();
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_cubemap, 1, day);
+        Self::update_descriptor_sets_image(device, &self.descriptor_sets_cubemap, 2, night);
+        self.resources.textures.cubemap.destroy(device);
+        self.resources.textures.cubemap_night.destroy(device);
+        self.resources.textures.cubemap = day;
+        self.resources.textures.cubemap_night = night;
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// Let every descriptor set in `sets` sample `texture` at `binding` in the shader read only layout.
+    ///
+    /// Only the image is written, the sampler of the binding stays,
+    /// see [`Self::create_descriptor_set_layout`].
+    fn update_descriptor_sets_image(
+        device: &Device,
+        sets: &[vk::DescriptorSet],
+        binding: u32,
+        texture: Texture,
+    ) {
+        let descriptor_type = match binding {
+            1 | 2 => vk::DescriptorType::SAMPLED_IMAGE,
+            _ => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        };
+        for set in sets.iter() {
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                .image_view(texture.view)];
+            let image_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(binding)
+                .dst_array_element(0)
+                .descriptor_type(descriptor_type)
+                .image_info(&image_infos);
+            unsafe { device.update_descriptor_sets(&[image_descriptor_write], &[]) }
+        }
+    }
+
+    /// Let every descriptor set in `sets` sample the images at binding 1 and 2 with `sampler`.
+    fn update_descriptor_sets_sampler(
+        device: &Device,
+        sets: &[vk::DescriptorSet],
+        sampler: vk::Sampler,
+    ) {
+        for set in sets.iter() {
+            let sampler_infos = [vk::DescriptorImageInfo::default().sampler(sampler)];
+            let sampler_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(9)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::SAMPLER)
+                .image_info(&sampler_infos);
+            unsafe { device.update_descriptor_sets(&[sampler_descriptor_write], &[]) }
+        }
+    }
+
+    /// Returns the name of every art piece whose shaders failed to compile
+    /// together with the compilation errors.
+    pub fn shader_errors(&self) -> Vec<(&str, Vec<String>)> {
+        let ray_tracing_errors = self.ray_tracing_pipelines.iter()
+            .map(|pipeline| (pipeline.name(), pipeline.compile_errors()));
+        self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+            .map(|pipeline| (pipeline.name(), pipeline.compile_errors()))
+            .chain(ray_tracing_errors)
+            .filter(|(_, errors)| !errors.is_empty())
+            .collect()
+    }
+
+    pub fn reload_shaders(&mut self) {
+        self.wait_gpu_idle();
+
+        let device = self.renderer.vk_context.device();
+        let mut reloading = false;
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            reloading |= pipeline.reload_shaders(device, true);
+        }
+        for pipeline in self.ray_tracing_pipelines.iter_mut() {
+            reloading |= pipeline.reload_shaders(device, true);
+        }
+        if reloading {
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Recreates the swapchain with new dimensions.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either `width` or `height` is zero.
+    pub fn recreate_swapchain(&mut self, width: u32, height: u32) {
+        log::debug!("Recreating swapchain");
+        if width == 0 || height == 0 {
+            panic!("invalid dimensions: ({width}, {height})");
+        }
+
+        // the resources of the swapchain are only used by the frames in flight
+        self.check_device(self.renderer.wait_for_frames());
+        self.renderer.release_full_screen_exclusive();
+        self.cleanup_swapchain();
+        self.renderer.recreate_swapchain([width, height]);
+
+        let device = self.renderer.vk_context.device();
+        let properties = self.renderer.swapchain_properties;
+        let stereo_target = self.stereo_target.as_ref()
+            .map(|target| self.create_stereo_target(properties, target.output()));
+        for pipeline in self.renderer.screen.composite_mut() {
+            pipeline.recreate(
+                device,
+                vk::SampleCountFlags::TYPE_1,
+                self.renderer.composite_render_pass,
+                self.descriptor_set_layout,
+            );
+        }
+        if let Some(target) = stereo_target.as_ref() {
+            Self::update_descriptor_sets_image(
+                device,
+                &self.descriptor_sets_anaglyph,
+                1,
+                target.resolve_texture(),
+            );
+        }
+
+        let pipeline_render_pass = stereo_target.as_ref()
+            .map_or(self.renderer.render_pass, StereoTarget::render_pass);
+        let pipelines = self.renderer.pipelines.iter_mut()
+            .chain(self.renderer.screen.gallery_mut())
+            .chain(self.half_res_art.iter_mut().map(|art| &mut art.upscale));
+        for pipeline in pipelines {
+            pipeline.recreate(
+                device,
+                self.renderer.msaa_samples,
+                pipeline_render_pass,
+                self.descriptor_set_layout,
+            );
+        }
+        for art in self.half_res_art.iter_mut() {
+            art.resize(
+                &self.renderer.vk_context,
+                properties.extent,
+                properties.format.format,
+                self.renderer.msaa_samples,
+                self.renderer.depth_format,
+            );
+        }
+
+        if let Some(occlusion) = self.occlusion.as_mut() {
+            unsafe {
+                occlusion.resize(
+                    &self.renderer.vk_context,
+                    self.renderer.command_pool,
+                    self.renderer.graphics_queue,
+                    properties.extent,
+                );
+            }
+        }
+        let (color_texture, depth_texture) =
+            (self.renderer.color_texture, self.renderer.depth_texture);
+        let grade_target = self.grade_target.as_ref()
+            .map(|_| self.create_grade_target(properties, color_texture, depth_texture));
+
+        self.stereo_target = stereo_target;
+        self.grade_target = grade_target;
+        let capturing = self.is_capturing_frames();
+        let readable = properties.readable && still::can_read_back(properties.format.format);
+        if capturing && !readable {
+            log::warn!("Stopped capturing frames, the new swapchain images can not be read back");
+        }
+        self.recreate_frame_readbacks(capturing && readable);
+        self.resize_renderers();
+        self.recreate_command_buffers();
+        self.renderer.acquire_full_screen_exclusive();
+    }
+
+    /// Requests exclusive fullscreen on the monitor of `window` when `exclusive` is true,
+    /// which lowers the latency and lets the app control the refresh rate.
+    ///
+    /// The window has to be fullscreen already. This is only supported on Windows.
+    pub fn set_full_screen_exclusive(&mut self, window: &Window, exclusive: bool) {
+        if self.renderer.vk_context.full_screen_exclusive().is_none() {
+            if exclusive {
+                log::info!("Exclusive fullscreen is not supported");
+            }
+            return;
+        }
+        self.renderer.exclusive_monitor = if exclusive { Renderer::hmonitor(window) } else { None };
+        if exclusive && self.renderer.exclusive_monitor.is_none() {
+            log::warn!("Failed to find the monitor of the window for exclusive fullscreen");
+        }
+        // the swapchain has to be created with the monitor
+        self.dirty_swapchain = true;
+    }
+
+    fn create_stereo_target(
+        &self,
+        swapchain_properties: SwapchainProperties,
+        output: StereoOutput,
+    ) -> StereoTarget {
+        let extent = match output {
+            StereoOutput::SideBySide => vk::Extent2D {
+                width: (swapchain_properties.extent.width / EYE_COUNT).max(1),
+                height: swapchain_properties.extent.height,
+            },
+            StereoOutput::Anaglyph => swapchain_properties.extent,
+        };
+        StereoTarget::new(
+            &self.renderer.vk_context,
+            output,
+            extent,
+            swapchain_properties.format.format,
+            self.renderer.msaa_samples,
+            self.renderer.depth_format,
+        )
+    }
+
+    /// How both eyes are shown if rendering in stereo.
+    pub fn stereo_output(&self) -> Option<StereoOutput> {
+        self.stereo_target.as_ref().map(StereoTarget::output)
+    }
+
+    /// Switches between rendering a single view with `None` and rendering
+    /// both eyes in one multiview pass that are shown according to `output`.
+    ///
+    /// Stays in mono if the device does not support multiview.
+    pub fn set_stereo_output(&mut self, output: Option<StereoOutput>) {
+        if output.is_some() && !self.renderer.vk_context.multiview() {
+            log::warn!("Stereo rendering needs multiview, which is not supported");
+            return;
+        }
+        #[cfg(feature = "openxr")]
+        if self.openxr.is_some() && output != Some(StereoOutput::SideBySide) {
+            log::warn!("Stereo output stays side by side for the headset");
+            return;
+        }
+        self.wait_gpu_idle();
+        if let Some(mut target) = self.stereo_target.take() {
+            unsafe { target.cleanup(self.renderer.vk_context.device()) };
+        }
+        self.stereo_target = output
+            .map(|output| self.create_stereo_target(self.renderer.swapchain_properties, output));
+        if let Some(target) = self.stereo_target.as_ref() {
+            Self::update_descriptor_sets_image(
+                self.renderer.vk_context.device(),
+                &self.descriptor_sets_anaglyph,
+                1,
+                target.resolve_texture(),
+            );
+        }
+        log::info!("Stereo output: {output:?}");
+
+        let render_pass = self.render_target();
+        let device = self.renderer.vk_context.device();
+        let pipelines = self.renderer.pipelines.iter_mut()
+            .chain(self.renderer.screen.gallery_mut())
+            .chain(self.half_res_art.iter_mut().map(|art| &mut art.upscale));
+        for pipeline in pipelines {
+            pipeline.recreate(
+                device,
+                self.renderer.msaa_samples,
+                render_pass,
+                self.descriptor_set_layout,
+            );
+        }
+        self.resize_renderers();
+        self.recreate_command_buffers();
+    }
+
+    pub fn show_frame_graph(&self) -> bool {
+        self.show_frame_graph
+    }
+
+    /// Shows or hides the graph of the last frame times in the corner of the screen.
+    pub fn set_show_frame_graph(&mut self, show: bool) {
+        self.show_frame_graph = show;
+        log::info!("Frame graph: {show}");
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+    }
+
+    /// Skips drawing the art hidden behind the environment, see [`OcclusionCulling`].
+    ///
+    /// Does nothing if the device can not cull.
+    pub fn set_occlusion_culling(&mut self, enabled: bool) {
+        if self.occlusion.is_none() {
+            return;
+        }
+        self.occlusion_culling = enabled;
+        log::info!("Occlusion culling: {enabled}");
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+    }
+
+    /// Fogs the environment with fog lit by the shadowed sun and the point lights in
+    /// froxels of the view instead of fog of a single color, see [`VolumetricFog`].
+    ///
+    /// Only a single view is fogged this way.
+    pub fn set_volumetric_fog(&mut self, enabled: bool) {
+        self.volumetric_fog = enabled;
+        log::info!("Volumetric fog: {enabled}");
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+    }
+
+    /// Lights the environment with the point lights in the file at `path`, see [`crate::lights`],
+    /// the file is reloaded when it changes. `None` removes the lights.
+    pub fn set_lights(&mut self, path: Option<&Path>) -> Result<(), anyhow::Error> {
+        let lights = path.map(lights::load).transpose()?.unwrap_or_default();
+        if let Some(path) = path {
+            self.resources.file_watcher.watch(path);
+        }
+        self.lights_path = path.map(Path::to_owned);
+        self.replace_lights(lights);
+        Ok(())
+    }
+
+    fn replace_lights(&mut self, lights: Vec<PointLight>) {
+        if lights.len() > MAX_LIGHTS {
+            log::warn!("Only the first {MAX_LIGHTS} of {} lights are drawn", lights.len());
+        }
+        // the clusters are only built with lights
+        let rerecord = lights.is_empty() != self.lights.is_empty();
+        self.lights = lights;
+        if rerecord {
+            self.wait_gpu_idle();
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Fades the whole screen to black with an opacity from 0 to 1, `None` stops fading.
+    pub fn set_fade(&mut self, fade: Option<f32>) {
+        // the black is only drawn while fading
+        if self.scene.set_fade(fade) {
+            self.wait_gpu_idle();
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Grades the whole gallery with the color lookup table in the `.cube` file at `path`
+    /// in a post pass, `None` stops grading. Grading is skipped while rendering in stereo.
+    pub fn set_lut(&mut self, path: Option<&Path>) -> Result<(), anyhow::Error> {
+        let texture = path
+            .map(|path| {
+                let lut = Lut::load(path)?;
+                log::info!(
+                    "Loaded the {0}x{0}x{0} LUT {1}",
+                    lut.size,
+                    lut.title.as_deref().unwrap_or(&path.display().to_string()),
+                );
+                ResourceManager::create_texture_3d(
+                    &self.renderer.vk_context,
+                    self.renderer.command_pool,
+                    self.renderer.graphics_queue,
+                    [lut.size; 3],
+                    &lut.texels(),
+                    SamplerConfig::CLAMP,
+                )
+            })
+            .transpose()?;
+
+        self.wait_gpu_idle();
+        let device = self.renderer.vk_context.device();
+        if let Some(mut lut) = self.lut.take() {
+            lut.destroy(device);
+        }
+        // the empty volume stands in while the frame is only reflected
+        Self::update_descriptor_sets_image(
+            device,
+            &self.descriptor_sets_grade,
+            4,
+            texture.unwrap_or(self.resources.textures.volume_empty),
+        );
+        self.lut = texture;
+        self.update_grade_target();
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// Runs the script at `path` every frame to move the art, `None` stops scripting.
+    ///
+    /// The script is reloaded when the file changes, see [`Script`].
+    pub fn set_script(&mut self, path: Option<&Path>) -> Result<(), anyhow::Error> {
+        let script = path.map(Script::load).transpose()?;
+        if let Some(path) = path {
+            self.resources.file_watcher.watch(path);
+        }
+        self.script_path = path.map(Path::to_owned);
+        self.replace_script(script);
+        Ok(())
+    }
+
+    fn replace_script(&mut self, script: Option<Script>) {
+        if let Some(script) = script.as_ref() {
+            let names = self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+                .map(Pipeline::name)
+                .collect::<Vec<_>>();
+            for name in script.art_names().filter(|name| !names.contains(name)) {
+                log::warn!("The script moves the art {name}, which is not in the gallery");
+            }
+        }
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            pipeline.set_motion(None);
+        }
+        self.script = script;
+        self.script_time = None;
+    }
+
+    /// Moves the art with the script for the frame at `time`.
+    fn run_script(&mut self, time: f32) {
+        let Some(script) = self.script.as_mut() else {
+            return;
+        };
+        let delta = self.script_time.map_or(0., |script_time| time - script_time);
+        self.script_time = Some(time);
+        for pipeline in self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut() {
+            let Some(model) = pipeline.model_matrix() else {
+                continue;
+            };
+            let distance = (model[3] * self.scene.view_matrix).resize::<3>().magnitude();
+            match script.run(pipeline.name(), ArtInput { time, delta, distance }) {
+                Ok(motion) => pipeline.set_motion(motion),
+                Err(err) => {
+                    log::error!("Stopped the script of {}: {err:#}", pipeline.name());
+                    pipeline.set_motion(None);
+                }
+            }
+        }
+    }
+
+    /// How much the color lookup table changes the frame from 0 to 1.
+    pub fn grade_strength(&self) -> f32 {
+        self.grade_strength
+    }
+
+    /// Blends between the frame and the graded frame, 0 skips the grading pass.
+    pub fn set_grade_strength(&mut self, strength: f32) {
+        let was_grading = self.grade_strength > 0.;
+        self.grade_strength = strength.clamp(0., 1.);
+        if was_grading != (self.grade_strength > 0.) && self.lut.is_some() {
+            self.wait_gpu_idle();
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Lets polished floors reflect the gallery with a strength from 0 to 1, 0 stops reflecting.
+    ///
+    /// The reflected rays are marched through the depth of the environment in a post pass,
+    /// so only what is in the frame is reflected. Only a single view is reflected and nothing
+    /// is if the device can not draw the depth of the environment, see [`OcclusionCulling`].
+    pub fn set_reflections(&mut self, strength: f32) {
+        if self.occlusion.is_none() {
+            log::warn!("Reflections are not supported");
+            return;
+        }
+        self.reflection_strength = strength.clamp(0., 1.);
+        log::info!("Reflection strength: {}", self.reflection_strength);
+        self.wait_gpu_idle();
+        self.update_grade_target();
+        self.recreate_command_buffers();
+    }
+
+    /// Whether the floors reflect the gallery in the post pass.
+    fn reflecting(&self) -> bool {
+        self.reflection_strength > 0. && self.occlusion.is_some() && self.grade_target.is_some()
+    }
+
+    /// Blurs what is nearer or farther than the focus like a camera lens with an aperture
+    /// from 0 to 1, 0 keeps everything sharp.
+    ///
+    /// The focus is the distance of whatever is at the focus point, see [`Self::set_focus_point`].
+    /// Only a single view is blurred and nothing is if the device can not sample the depth.
+    pub fn set_depth_of_field(&mut self, aperture: f32) {
+        if !self.sampled_depth {
+            log::warn!("Depth of field is not supported");
+            return;
+        }
+        self.dof_aperture = aperture.clamp(0., 1.);
+        log::info!("Depth of field aperture: {}", self.dof_aperture);
+        self.wait_gpu_idle();
+        self.update_grade_target();
+        self.recreate_command_buffers();
+    }
+
+    /// Focuses on whatever is at `point` from 0 to 1 from the top left of the window,
+    /// `None` focuses on the center.
+    pub fn set_focus_point(&mut self, point: Option<[f32; 2]>) {
+        let [x, y] = point.unwrap_or([0.5, 0.5]);
+        self.focus_point = Vector2::from([x.clamp(0., 1.), y.clamp(0., 1.)]);
+    }
+
+    /// Whether what is out of focus is blurred in the post pass.
+    fn focusing(&self) -> bool {
+        self.dof_aperture > 0. && self.grade_target.is_some()
+    }
+
+    /// Smears the gallery along the motion of the camera like a shutter that is open for
+    /// `shutter` of the frame from 0 to 1, 0 stops blurring.
+    ///
+    /// The velocity of every pixel is reprojected from the depth of the gallery in the post
+    /// pass, so moving art is not blurred. Only a single view is blurred and nothing is if
+    /// the device can not sample the depth.
+    pub fn set_motion_blur(&mut self, shutter: f32) {
+        if !self.sampled_depth {
+            log::warn!("Motion blur is not supported");
+            return;
+        }
+        self.shutter = shutter.clamp(0., 1.);
+        log::info!("Motion blur shutter: {}", self.shutter);
+        self.wait_gpu_idle();
+        self.update_grade_target();
+        self.recreate_command_buffers();
+    }
+
+    /// Whether the motion of the camera is blurred in the post pass.
+    fn blurring_motion(&self) -> bool {
+        self.shutter > 0. && self.grade_target.is_some()
+    }
+
+    pub fn quality(&self) -> u32 {
+        self.quality
+    }
+
+    /// Sets the quality level from 0 to [`MAX_QUALITY`] the art sees in its uniforms,
+    /// so that expensive shaders can take fewer steps on slow GPUs.
+    pub fn set_quality(&mut self, level: u32) {
+        self.quality = level.min(MAX_QUALITY);
+        log::info!("Quality: {}", self.quality);
+    }
+
+    /// The frame rate the resolution is scaled for, see [`VkApp::set_dynamic_resolution`].
+    pub fn dynamic_resolution(&self) -> Option<f32> {
+        self.dynamic_resolution.as_ref().map(DynamicResolution::target_fps)
+    }
+
+    /// Draws the gallery at a lower resolution whenever the GPU takes longer than a frame at
+    /// `target_fps`, `None` always draws at the resolution of the window.
+    ///
+    /// The frame is scaled up to the window in the post pass. This needs GPU timing,
+    /// see [`VkApp::set_gpu_timing`], and is skipped in stereo.
+    pub fn set_dynamic_resolution(&mut self, target_fps: Option<f32>) {
+        if target_fps.is_some() && self.gpu_timer.is_none() {
+            log::warn!("Dynamic resolution needs GPU timing");
+            return;
+        }
+        self.dynamic_resolution = target_fps
+            .filter(|&fps| fps > 0.)
+            .map(DynamicResolution::new);
+        log::info!("Dynamic resolution: {target_fps:?}");
+        self.wait_gpu_idle();
+        self.update_grade_target();
+        self.recreate_command_buffers();
+    }
+
+    pub fn post_effects(&self) -> PostEffects {
+        self.post_effects
+    }
+
+    /// Applies the stylistic effects like the vignette on top of the frame in the post pass.
+    pub fn set_post_effects(&mut self, effects: PostEffects) {
+        let was_applied = self.post_effects.any();
+        self.post_effects = effects;
+        if was_applied != effects.any() {
+            self.wait_gpu_idle();
+            self.update_grade_target();
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Shows `lines` in the top right corner over everything else or hides the text if `None`.
+    /// Only the first lines and the start of long lines are shown.
+    pub fn set_overlay_text<S: AsRef<str>>(&mut self, lines: Option<&[S]>) {
+        self.show_overlay(lines, OVERLAY_MAX_LINES, OVERLAY_TEXT_COLOR);
+    }
+
+    /// Shows the compile errors of the shaders in `lines` like [`Self::set_overlay_text`],
+    /// but in red and with up to [`OVERLAY_MAX_ERROR_LINES`].
+    pub fn set_overlay_errors<S: AsRef<str>>(&mut self, lines: Option<&[S]>) {
+        self.show_overlay(lines, OVERLAY_MAX_ERROR_LINES, OVERLAY_ERROR_COLOR);
+    }
+
+    fn show_overlay<S: AsRef<str>>(
+        &mut self,
+        lines: Option<&[S]>,
+        max_lines: usize,
+        color: [f32; 3],
+    ) {
+        let text = lines.map(|lines| {
+            TextBitmap::new(&lines[..lines.len().min(max_lines)], OVERLAY_MAX_COLUMNS)
+        });
+        if text.as_ref().is_some_and(|text| !TextOverlay::fits(text)) {
+            log::warn!("Overlay text is too large");
+            return;
+        }
+        let size = |text: &Option<TextBitmap>| text.as_ref().map(TextBitmap::size);
+        let resized = size(&text) != size(&self.overlay_text);
+        self.overlay_text = text;
+        self.overlay_color = color;
+        if resized {
+            self.wait_gpu_idle();
+            self.recreate_command_buffers();
+        }
+    }
+
+    /// Lines describing the GPU, the swapchain and the memory usage, e.g. for bug reports.
+    pub fn system_info(&self) -> Vec<String> {
+        let props = self.renderer.vk_context.physical_device_properties();
+        let name = props.device_name_as_c_str().unwrap_or_default().to_string_lossy();
+        let version = |version| format!(
+            "{}.{}.{}",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_patch(version),
+        );
+        let driver = self.renderer.vk_context.driver_info()
+            .unwrap_or_else(|| format!("{:#x}", props.driver_version));
+        let properties = &self.renderer.swapchain_properties;
+        let extent = properties.extent;
+        const MIB: u64 = 1 << 20;
+        let vram = match self.renderer.vk_context.vram_usage() {
+            (Some(used), budget) => format!("{} / {} MiB", used / MIB, budget / MIB),
+            (None, size) => format!("{} MiB", size / MIB),
+        };
+        vec![
+            format!("GPU: {name} ({:?})", props.device_type),
+            format!("Driver: {driver}"),
+            format!("Vulkan: {}", version(props.api_version)),
+            format!("Format: {:?} {:?}", properties.format.format, properties.format.color_space),
+            format!("Present mode: {:?}", properties.present_mode),
+            format!("MSAA: {}x", self.renderer.msaa_samples.as_raw()),
+            format!("Resolution: {}x{}", extent.width, extent.height),
+            format!("VRAM: {vram}"),
+        ]
+    }
+
+    /// Creates the target of the post pass while there is a lookup table, reflections, depth
+    /// of field, motion blur or a stylistic effect and destroys it otherwise.
+    /// The GPU must be idle.
+    fn update_grade_target(&mut self) {
+        let needed = self.lut.is_some()
+            || self.reflection_strength > 0.
+            || self.dof_aperture > 0.
+            || self.shutter > 0.
+            || self.post_effects.any()
+            || self.dynamic_resolution.is_some();
+        if needed == self.grade_target.is_some() {
+            return;
+        }
+        match self.grade_target.take() {
+            Some(mut target) => unsafe { target.cleanup(self.renderer.vk_context.device()) },
+            None => {
+                self.grade_target = Some(self.create_grade_target(
+                    self.renderer.swapchain_properties,
+                    self.renderer.color_texture,
+                    self.renderer.depth_texture,
+                ));
+            }
+        }
+    }
+
+    /// Creates the target of the post pass, the depth of the environment has to be resized
+    /// to the swapchain already.
+    fn create_grade_target(
+        &self,
+        swapchain_properties: SwapchainProperties,
+        color_texture: Texture,
+        depth_texture: Texture,
+    ) -> GradeTarget {
+        let target = GradeTarget::new(
+            &self.renderer.vk_context,
+            swapchain_properties.extent,
+            swapchain_properties.format.format,
+            self.renderer.msaa_samples,
+            self.renderer.depth_format,
+            color_texture.view,
+            depth_texture.view,
+            self.sampled_depth,
+        );
+        Self::update_descriptor_sets_image(
+            self.renderer.vk_context.device(),
+            &self.descriptor_sets_grade,
+            1,
+            target.resolve_texture(),
+        );
+        let gallery_depth = match target.depth_texture() {
+            Some(texture) => (texture.view, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+            None => (target.resolve_texture().view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+        };
+        // the frame stands in for the depth when it is never reflected
+        let (view, layout) = match self.occlusion.as_ref() {
+            Some(occlusion) => {
+                (occlusion.depth_view(), vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+            }
+            None => (target.resolve_texture().view, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL),
+        };
+        for &set in self.descriptor_sets_grade.iter() {
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_layout(layout)
+                .image_view(view)];
+            let gallery_depth_infos = [vk::DescriptorImageInfo::default()
+                .image_layout(gallery_depth.1)
+                .image_view(gallery_depth.0)];
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(2)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .image_info(&image_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(18)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .image_info(&gallery_depth_infos),
+            ];
+            unsafe { self.renderer.vk_context.device().update_descriptor_sets(&writes, &[]) };
+        }
+        target
+    }
+
+    /// The name of the art whose fragment shader is previewed in the corner of the screen.
+    pub fn preview_art(&self) -> Option<&str> {
+        self.scene.preview_art().map(|index| self.renderer.pipelines[index].name())
+    }
+
+    /// Previews the next 2D art, or stops previewing after the last one.
+    pub fn cycle_preview_art(&mut self) {
+        let preview_art = self.scene.cycle_preview_art();
+
+        self.wait_gpu_idle();
+        let device = self.renderer.vk_context.device();
+        if let Some(mut pipeline) = self.renderer.screen.preview.take() {
+            unsafe { pipeline.cleanup_pip(device) };
+        }
+        self.renderer.screen.preview = preview_art
+            .and_then(|index| {
+                self.create_fullscreen_art_pipeline(
+                    index,
+                    "preview",
+                    self.renderer.msaa_samples,
+                    self.render_target(),
+                )
+            });
+        log::info!("Shader preview: {:?}", self.preview_art());
+        self.recreate_command_buffers();
+    }
+
+    pub fn sample_shading(&self) -> bool {
+        self.sample_shading
+    }
+
+    /// Enables or disables per sample shading of the art, does nothing if it is not supported.
+    pub fn set_sample_shading(&mut self, enabled: bool) {
+        if self.renderer.vk_context.enabled_features().sample_rate_shading != vk::TRUE {
+            log::warn!("Sample shading is not supported");
+            return;
+        }
+        self.sample_shading = enabled;
+        log::info!("Sample shading: {enabled}");
+
+        self.wait_gpu_idle();
+        let render_pass = self.render_target();
+        let device = self.renderer.vk_context.device();
+        let min_sample_shading = enabled.then_some(MIN_SAMPLE_SHADING);
+        let pipelines = self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut()
+            .chain(self.renderer.screen.preview.as_mut())
+            .chain(self.renderer.screen.entered.as_mut());
+        for pipeline in pipelines {
+            pipeline.set_min_sample_shading(min_sample_shading);
+            pipeline.recreate(
+                device,
+                self.renderer.msaa_samples,
+                render_pass,
+                self.descriptor_set_layout,
+            );
+        }
+        self.recreate_command_buffers();
+    }
+
+    /// The name of the art that is drawn over the whole screen instead of the gallery.
+    pub fn entered_art(&self) -> Option<&str> {
+        self.scene.entered_art().map(|index| self.renderer.pipelines[index].name())
+    }
+
+    /// The places the art can be seen from at `distance` times its size, 2D art from the front
+    /// and 3D art from +z, in the order the art was given.
+    pub fn art_viewpoints(&self, distance: f32) -> Vec<Viewpoint> {
+        (PIPELINE_IDX_ART..self.renderer.pipelines.len())
+            .filter_map(|index| {
+                let pipeline = &self.renderer.pipelines[index];
+                let model = pipeline.model_matrix()?;
+                // the front of 2D art faces -z
+                let front = if self.scene.art_2d().contains(&index) { -1. } else { 1. };
+                let direction = Vector4::from([0., 0., front, 0.]) * model;
+                let size = model[0].resize::<3>().magnitude();
+                Some(Viewpoint::facing(
+                    pipeline.name().to_owned(),
+                    model[3].resize(),
+                    direction.resize(),
+                    distance * size,
+                ))
+            })
+            .collect()
+    }
+
+    /// The position of the art named `name` in the gallery, which follows scripts and edits.
+    pub fn art_position(&self, name: &str) -> Option<Vector3> {
+        self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+            .find(|pipeline| pipeline.name() == name)?
+            .model_matrix()
+            .map(|model| model[3].resize())
+    }
+
+    /// The names and the model matrices of the art in the order it was given, which follow
+    /// scripts and edits.
+    pub fn art_models(&self) -> Vec<(&str, Matrix4)> {
+        self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+            .filter_map(|pipeline| Some((pipeline.name(), pipeline.model_matrix()?)))
+            .collect()
+    }
+
+    /// Returns the index of the closest 2D art in front of the camera if there is one.
+    fn looked_at_art(&self) -> Option<usize> {
+        self.scene.art_2d().iter()
+            .filter_map(|&index| {
+                let model = self.renderer.pipelines[index].model_matrix()?;
+                // position of the art in view space, the camera looks along -z
+                let pos = (model[3] * self.scene.view_matrix).resize::<3>();
+                let dist = pos.magnitude();
+                (dist < ENTER_ART_DISTANCE && -pos.z() > dist * ENTER_ART_COS)
+                    .then_some((index, dist))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Draws the 2D art in front of the camera over the whole screen with its own time and mouse.
+    ///
+    /// Returns false if there is no 2D art in front of the camera.
+    pub fn enter_looked_at_art(&mut self) -> bool {
+        let Some(index) = self.looked_at_art() else {
+            return false;
+        };
+        self.wait_gpu_idle();
+        if let Some(mut pipeline) = self.renderer.screen.entered.take() {
+            unsafe { pipeline.cleanup_pip(self.renderer.vk_context.device()) };
+        }
+        self.renderer.screen.entered = self.create_fullscreen_art_pipeline(
+            index,
+            "fullscreen",
+            self.renderer.msaa_samples,
+            self.render_target(),
+        );
+        self.scene.enter_art(self.renderer.screen.entered.as_ref().map(|_| index));
+        log::info!("Entered art: {:?}", self.entered_art());
+        self.recreate_command_buffers();
+        self.scene.entered_art().is_some()
+    }
+
+    /// Returns to the gallery from the entered art.
+    pub fn exit_art(&mut self) {
+        self.wait_gpu_idle();
+        if let Some(mut pipeline) = self.renderer.screen.entered.take() {
+            unsafe { pipeline.cleanup_pip(self.renderer.vk_context.device()) };
+        }
+        self.scene.exit_art();
+        self.recreate_command_buffers();
+    }
+
+    /// Returns the index of the closest art hit by a ray from the camera along its view direction.
+    ///
+    /// The art is approximated by the sphere around the cube from -1 to 1 it is drawn in.
+    fn picked_art(&self) -> Option<usize> {
+        (PIPELINE_IDX_ART..self.renderer.pipelines.len())
+            .filter(|&index| self.renderer.pipelines[index].active)
+            .filter_map(|index| {
+                let model = self.renderer.pipelines[index].model_matrix()?;
+                let center = (model[3] * self.scene.view_matrix).resize::<3>();
+                let radius = model[0].resize::<3>().magnitude() * 3f32.sqrt();
+                let dist = -center.z();
+                let off_axis = center.x() * center.x() + center.y() * center.y();
+                (dist > 0. && off_axis < radius * radius).then_some((index, dist))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// The name of the art that is currently edited.
+    pub fn edited_art(&self) -> Option<&str> {
+        self.scene.edited_art().map(|index| self.renderer.pipelines[index].name())
+    }
+
+    /// Starts editing the art the camera looks at.
+    ///
+    /// Returns false if the camera does not look at any art.
+    pub fn edit_looked_at_art(&mut self) -> bool {
+        let index = self.picked_art();
+        let model = index.and_then(|index| self.renderer.pipelines[index].model_matrix());
+        self.scene.edit_art(index, model);
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+        log::info!("Editing art: {:?}", self.edited_art());
+        self.scene.edited_art().is_some()
+    }
+
+    /// Stops editing and returns the name and the model matrix of the edited art, so that
+    /// its placement can be saved.
+    pub fn stop_editing(&mut self) -> Option<(String, Matrix4)> {
+        let index = self.scene.stop_editing()?;
+        self.wait_gpu_idle();
+        self.recreate_command_buffers();
+        let pipeline = &self.renderer.pipelines[index];
+        Some((pipeline.name().to_owned(), pipeline.model_matrix()?))
+    }
+
+    /// The model matrix of the edited art.
+    pub fn edited_model(&self) -> Option<Matrix4> {
+        self.scene.edited_art().and_then(|index| self.renderer.pipelines[index].model_matrix())
+    }
+
+    pub fn gizmo_mode(&self) -> GizmoMode {
+        self.scene.gizmo().mode
+    }
+
+    /// Switches what dragging the handles of the gizmo does, a drag in progress is dropped.
+    pub fn set_gizmo_mode(&mut self, mode: GizmoMode) {
+        self.scene.set_gizmo_mode(mode);
+    }
+
+    /// The ray from the camera through `cursor`, from 0 to 1 from the top left of the window,
+    /// or `None` if the cursor is not over the first view.
+    fn cursor_ray(&self, cursor: [f32; 2]) -> Option<Ray> {
+        if self.stereo_target.is_some() {
+            return None;
+        }
+        let extent = self.render_extent();
+        let rect = self.views.first()?;
+        let ndc = [
+            (cursor[0] * extent.width as f32 - rect.offset.x as f32) / rect.extent.width as f32,
+            (cursor[1] * extent.height as f32 - rect.offset.y as f32) / rect.extent.height as f32,
+        ].map(|coord| coord * 2. - 1.);
+        if ndc.iter().any(|coord| coord.abs() > 1.) {
+            return None;
+        }
+        let aspect = rect.extent.width as f32 / rect.extent.height as f32;
+        let proj = self.scene.projection.matrix(aspect);
+        Some(Ray::from_screen(ndc, self.scene.view_matrix, proj))
+    }
+
+    /// Highlights the handle of the gizmo under `cursor`, from 0 to 1 from the top left of the
+    /// window, or drags the grabbed handle to it.
+    pub fn hover_gizmo(&mut self, cursor: [f32; 2]) {
+        let (Some(index), Some(model)) = (self.scene.edited_art(), self.edited_model()) else {
+            return;
+        };
+        let Some(ray) = self.cursor_ray(cursor) else {
+            return;
+        };
+        if !self.scene.gizmo().is_dragging() {
+            self.scene.gizmo_mut().hover(ray, model);
+        } else if let Some(model) = self.scene.gizmo().drag(ray) {
+            self.renderer.pipelines[index].set_model_matrix(model);
+            self.scene.set_edit_position(model[3].resize());
+        }
+    }
+
+    /// Grabs the handle of the gizmo under `cursor` like [`Self::hover_gizmo`].
+    ///
+    /// Returns false if no art is edited or there is no handle under the cursor.
+    pub fn grab_gizmo(&mut self, cursor: [f32; 2]) -> bool {
+        let Some(model) = self.edited_model() else {
+            return false;
+        };
+        let ray = self.cursor_ray(cursor);
+        ray.is_some_and(|ray| self.scene.gizmo_mut().begin_drag(ray, model))
+    }
+
+    /// Lets go of the grabbed handle, returns false if none was grabbed.
+    pub fn release_gizmo(&mut self) -> bool {
+        self.scene.gizmo_mut().end_drag()
+    }
+
+    /// Moves the edited art by `translation` in world space, then rotates it around its own
+    /// y axis by `angle` and scales it by `scale`.
+    ///
+    /// The coordinates the art moves along are snapped to `grid` if it is given, the others
+    /// are kept so that art aligned to a wall stays there while it is moved along the wall.
+    pub fn transform_edited_art(
+        &mut self,
+        translation: Vector3,
+        angle: Deg<f32>,
+        scale: f32,
+        grid: Option<f32>,
+    ) {
+        let Some(index) = self.scene.edited_art() else {
+            return;
+        };
+        let pipeline = &mut self.renderer.pipelines[index];
+        let Some(model) = pipeline.model_matrix() else {
+            return;
+        };
+        let edit_position = self.scene.edit_position() + translation;
+        self.scene.set_edit_position(edit_position);
+        let mut position = model[3];
+        for i in 0..3 {
+            if translation[i] != 0. {
+                position[i] = match grid {
+                    Some(grid) => (edit_position[i] / grid).round() * grid,
+                    None => edit_position[i],
+                };
+            }
+        }
+        let mut model = model * Matrix4::from_angle_y(angle) * Matrix4::from_scale(scale);
+        model[3] = position;
+        pipeline.set_model_matrix(model);
+    }
+
+    /// Puts the edited art flush against the closest side of `walls` facing away from it,
+    /// keeping its height and size.
+    ///
+    /// Returns false if no 2D art is edited or there are no walls.
+    pub fn align_edited_art(&mut self, walls: &[Wall]) -> bool {
+        let edited_2d = self.scene.edited_art().filter(|index| self.scene.art_2d().contains(index));
+        let Some(index) = edited_2d else {
+            return false;
+        };
+        let Some(model) = self.renderer.pipelines[index].model_matrix() else {
+            return false;
+        };
+        let point = [model[3].x(), model[3].z()];
+        let dist = |side: &[f32; 2]| (side[0] - point[0]).powi(2) + (side[1] - point[1]).powi(2);
+        let Some((side, normal)) = walls.iter()
+            .map(|wall| wall.closest_side(point))
+            .min_by(|(a, _), (b, _)| dist(a).total_cmp(&dist(b)))
+        else {
+            return false;
+        };
+        let position = Vector3::from([
+            side[0] + normal[0] * WALL_OFFSET,
+            model[3].y(),
+            side[1] + normal[1] * WALL_OFFSET,
+        ]);
+        let scale = model[0].resize::<3>().magnitude();
+        // the front of the quad faces -z
+        let angle = Rad((-normal[0]).atan2(-normal[1]));
+        let model = Matrix4::from_translation(position)
+            * Matrix4::from_scale(scale)
+            * Matrix4::from_angle_y(angle);
+        self.renderer.pipelines[index].set_model_matrix(model);
+        self.scene.set_edit_position(position);
+        log::info!("Aligned {} to the wall at {side:?}", self.renderer.pipelines[index].name());
+        true
+    }
+
+    /// Starts or stops measuring how long the GPU takes to draw the frames and each pipeline.
+    pub fn set_gpu_timing(&mut self, enabled: bool) -> Result<(), anyhow::Error> {
+        self.wait_gpu_idle();
+        if let Some(mut timer) = self.gpu_timer.take() {
+            timer.destroy(self.renderer.vk_context.device());
+        }
+        self.gpu_times = None;
+        if !enabled && self.dynamic_resolution.take().is_some() {
+            log::info!("Dynamic resolution: None");
+            self.update_grade_target();
+        }
+        if enabled {
+            self.gpu_timer = Some(GpuTimer::new(
+                &self.renderer.vk_context,
+                self.renderer.transient_command_pool,
+                self.renderer.graphics_queue,
+                self.renderer.frames.len(),
+                self.renderer.pipelines.len(),
+            )?);
+        }
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// The GPU times of the last frame if GPU timing is enabled, see [`VkApp::set_gpu_timing`].
+    ///
+    /// The pipeline times are in the order of [`VkApp::pipeline_names`].
+    pub fn gpu_times(&self) -> Option<&GpuTimes> {
+        self.gpu_times.as_ref()
+    }
+
+    /// Starts or stops copying every drawn frame to the host, e.g. to record a video.
+    ///
+    /// The frames are the swapchain images, which are not pre-rotated while capturing.
+    pub fn set_frame_capture(&mut self, enabled: bool) -> Result<(), anyhow::Error> {
+        let properties = self.renderer.swapchain_properties;
+        if enabled && !properties.readable {
+            return Err(anyhow::anyhow!("The swapchain images can not be copied from"));
+        }
+        if enabled && !still::can_read_back(properties.format.format) {
+            return Err(anyhow::anyhow!(
+                "Can not read back images with the format {:?}",
+                properties.format.format,
+            ));
+        }
+        self.wait_gpu_idle();
+        self.recreate_frame_readbacks(enabled);
+        self.recreate_command_buffers();
+        Ok(())
+    }
+
+    /// Replaces the buffers the frames are copied into with ones for the current swapchain.
+    fn recreate_frame_readbacks(&mut self, enabled: bool) {
+        let device = self.renderer.vk_context.device();
+        for frame in self.renderer.frames.iter_mut() {
+            if let Some(mut readback) = frame.readback.take() {
+                unsafe { readback.destroy(device) };
+            }
+            if enabled {
+                let properties = self.renderer.swapchain_properties;
+                frame.readback = Some(FrameReadback::new(
+                    &self.renderer.vk_context,
+                    properties.extent,
+                    properties.format.format,
+                ));
+            }
+        }
+        self.captured_frame = None;
+    }
+
+    /// Whether the drawn frames are copied to the host, see [`VkApp::set_frame_capture`].
+    pub fn is_capturing_frames(&self) -> bool {
+        self.renderer.frames.iter().any(|frame| frame.readback.is_some())
+    }
+
+    /// The last frame done drawing since the previous call if capturing frames,
+    /// see [`VkApp::set_frame_capture`].
+    pub fn take_captured_frame(&mut self) -> Option<RgbaImage> {
+        self.captured_frame.take()
+    }
+
+    /// The names of the pipelines drawing the gallery.
+    pub fn pipeline_names(&self) -> Vec<&str> {
+        self.renderer.pipelines.iter().map(Pipeline::name).collect()
+    }
+
+    /// The name of the art shown in the second window.
+    pub fn projector_art(&self) -> Option<&str> {
+        self.projector.as_ref().map(|projector| self.renderer.pipelines[projector.art].name())
+    }
+
+    /// Shows the entered art, the 2D art in front of the camera or the previewed art
+    /// in `window` with `dimensions`, replacing the window shown before.
+    ///
+    /// The window has to outlive the projector, see [`VkApp::close_projector`].
+    pub fn open_projector(
+        &mut self,
+        window: &Window,
+        dimensions: [u32; 2],
+    ) -> Result<(), anyhow::Error> {
+        let index = self.scene.entered_art()
+            .or_else(|| self.looked_at_art())
+            .or(self.scene.preview_art())
+            .context("There is no entered, looked at or previewed 2D art to show")?;
+        self.close_projector();
+        let mut projector = Projector::new(
+            &self.renderer.vk_context,
+            window,
+            dimensions,
+            self.renderer.frames.len(),
+            index,
+        )?;
+        projector.pipeline = self.create_fullscreen_art_pipeline(
+            index,
+            "projector",
+            vk::SampleCountFlags::TYPE_1,
+            projector.render_pass(),
+        );
+        self.projector = Some(projector);
+        log::info!("Second window shows: {:?}", self.projector_art());
+        Ok(())
+    }
+
+    /// Destroys the resources of the second window, it can be closed afterwards.
+    pub fn close_projector(&mut self) {
+        if let Some(mut projector) = self.projector.take() {
+            self.wait_gpu_idle();
+            projector.destroy(&self.renderer.vk_context);
         }
+    }
 
-        buffers
+    /// Starts a session of the headset and renders side by side for it, both eyes are still
+    /// shown in the window.
+    #[cfg(feature = "openxr")]
+    fn start_openxr(&mut self, mut openxr: OpenXr) {
+        if !self.renderer.vk_context.multiview() {
+            log::error!("OpenXR is disabled, stereo rendering needs multiview");
+            return;
+        }
+        let format = self.renderer.swapchain_properties.format.format;
+        let frames_in_flight = self.renderer.frames.len();
+        let result = openxr.start_session(&self.renderer.vk_context, format, frames_in_flight);
+        if let Err(err) = result {
+            log::error!("OpenXR is disabled: {err:#}");
+            return;
+        }
+        self.set_stereo_output(Some(StereoOutput::SideBySide));
+        self.openxr = Some(openxr);
     }
 
-    fn create_sync_objects(device: &Device) -> InFlightFrames {
-        let mut sync_objects_vec = Vec::new();
-        for _ in 0..MAX_FRAMES_IN_FLIGHT {
-            let image_available_semaphore = {
-                let semaphore_info = vk::SemaphoreCreateInfo::default();
-                unsafe { device.create_semaphore(&semaphore_info, None).unwrap() }
-            };
+    /// Waits for the headset to want the next frame and sees the gallery with its eyes.
+    #[cfg(feature = "openxr")]
+    fn begin_openxr_frame(&mut self) {
+        let Some(openxr) = self.openxr.as_mut() else {
+            return;
+        };
+        let tracked = openxr.begin_frame();
+        if let Some((_, fovy)) = tracked {
+            self.scene.projection.fovy = Rad(fovy).into();
+        }
+        // the head moves relative to the camera of the gallery
+        let view_matrix = self.scene.view_matrix;
+        self.scene.tracked_eye_views =
+            tracked.map(|(eye_views, _)| eye_views.map(|eye_view| eye_view * view_matrix));
+    }
 
-            let render_finished_semaphore = {
-                let semaphore_info = vk::SemaphoreCreateInfo::default();
-                unsafe { device.create_semaphore(&semaphore_info, None).unwrap() }
-            };
+    /// The second window was resized to `dimensions`.
+    pub fn resize_projector(&mut self, dimensions: [u32; 2]) {
+        if let Some(projector) = self.projector.as_mut() {
+            projector.resize(dimensions);
+        }
+    }
 
-            let in_flight_fence = {
-                let fence_info =
-                    vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
-                unsafe { device.create_fence(&fence_info, None).unwrap() }
-            };
+    /// Renders the view of the first camera into an image of `dimensions`, e.g. to print it.
+    ///
+    /// The image is rendered in tiles if it is larger than the attachments the GPU supports.
+    /// It shows the entered art or the gallery without the preview and the frame graph.
+    pub fn render_still(
+        &mut self,
+        time: f32,
+        dimensions: [u32; 2],
+    ) -> Result<RgbaImage, anyhow::Error> {
+        let [width, height] = dimensions;
+        let camera = StillCamera {
+            extent: vk::Extent2D { width, height },
+            view: self.scene.view_matrix,
+            // the still is not presented, so it is not rotated for the surface
+            proj: self.scene.projection.matrix(width as f32 / height as f32),
+            art_time: None,
+        };
+        self.render_offscreen(time, &camera, None)
+    }
 
-            let sync_objects = SyncObjects {
-                image_available_semaphore,
-                render_finished_semaphore,
-                fence: in_flight_fence,
+    /// Renders the gallery seen from `position` in the direction of the six [`CUBE_FACES`]
+    /// into square images of `size`, e.g. to stitch them into a panorama.
+    pub fn render_cube_faces(
+        &mut self,
+        time: f32,
+        position: Vector3,
+        size: u32,
+    ) -> Result<[RgbaImage; 6], anyhow::Error> {
+        if self.scene.entered_art().is_some() {
+            return Err(anyhow::anyhow!("The entered art has no sides, exit it first"));
+        }
+        let mut faces = Vec::new();
+        for face in CUBE_FACES {
+            let camera = StillCamera {
+                extent: vk::Extent2D { width: size, height: size },
+                view: Matrix4::look_to_rh(position, face.forward, face.up),
+                proj: Projection { fovy: Deg(90.), ..self.scene.projection }.matrix(1.),
+                art_time: None,
             };
-            sync_objects_vec.push(sync_objects)
+            faces.push(self.render_offscreen(time, &camera, None)?);
         }
-
-        InFlightFrames::new(sync_objects_vec)
+        Ok(faces.try_into().unwrap())
     }
 
-    pub fn wait_gpu_idle(&self) {
-        unsafe { self.vk_context.device().device_wait_idle().unwrap() };
+    /// Renders one loop of the animation of the entered, looked at or previewed 2D art over
+    /// images of `dimensions` at about `fps`, e.g. to export it as a GIF.
+    ///
+    /// The loop lasts [`ArtTime::loop_period`] or otherwise `default_period` seconds.
+    pub fn render_art_loop(
+        &mut self,
+        time: f32,
+        dimensions: [u32; 2],
+        fps: f32,
+        default_period: f32,
+    ) -> Result<ArtLoop, anyhow::Error> {
+        let index = self.scene.entered_art()
+            .or_else(|| self.looked_at_art())
+            .or(self.scene.preview_art())
+            .context("There is no entered, looked at or previewed 2D art to export")?;
+        let name = self.renderer.pipelines[index].name().to_owned();
+        let period = self.renderer.pipelines[index].art_time().loop_period
+            .unwrap_or(default_period);
+        // the frames are evenly spaced, so the last one leads into the first one
+        let frame_count = ((period * fps).round() as u32).max(1);
+
+        let [width, height] = dimensions;
+        let camera = StillCamera {
+            extent: vk::Extent2D { width, height },
+            view: self.scene.view_matrix,
+            proj: self.scene.projection.matrix(width as f32 / height as f32),
+            art_time: None,
+        };
+        self.wait_gpu_idle();
+        let mut pipeline = self.create_fullscreen_art_pipeline(
+            index,
+            "loop",
+            self.renderer.msaa_samples,
+            self.render_target(),
+        ).with_context(|| format!("Failed to create a pipeline for {name}"))?;
+        let frames = (0..frame_count).map(|i| {
+            let art_time = i as f32 * period / frame_count as f32;
+            let camera = StillCamera { art_time: Some(art_time), ..camera };
+            self.render_offscreen(time, &camera, Some(&pipeline))
+        }).collect::<Result<Vec<_>, _>>();
+        unsafe { pipeline.cleanup_pip(self.renderer.vk_context.device()) };
+        Ok(ArtLoop { name, period, frames: frames? })
     }
 
-    /// Draws a frame. Takes as argument the time passed in seconds as f32.
+    /// Renders the first view seen by `camera` into an image, see [`VkApp::render_still`].
     ///
-    /// #Returns
+    /// Only `art` is drawn over the whole image if it is given, like the entered art.
+    fn render_offscreen(
+        &mut self,
+        time: f32,
+        camera: &StillCamera,
+        art: Option<&Pipeline>,
+    ) -> Result<RgbaImage, anyhow::Error> {
+        if self.stereo_target.is_some() {
+            return Err(anyhow::anyhow!("Stills can not be rendered in stereo"));
+        }
+        let vk::Extent2D { width, height } = camera.extent;
+        let limits = self.renderer.vk_context.physical_device_properties().limits;
+        // every tile is drawn with a viewport over the whole image
+        let [max_width, max_height] = limits.max_viewport_dimensions;
+        if width == 0 || height == 0 || width > max_width || height > max_height {
+            return Err(anyhow::anyhow!(
+                "A still of {width}x{height} is larger than the viewports of {max_width}x{max_height}"
+            ));
+        }
+        let max_tile = STILL_TILE_SIZE
+            .min(limits.max_image_dimension2_d)
+            .min(limits.max_framebuffer_width)
+            .min(limits.max_framebuffer_height);
+        let extent = camera.extent;
+
+        self.wait_gpu_idle();
+        let mut target = StillTarget::new(
+            &self.renderer.vk_context,
+            vk::Extent2D { width: width.min(max_tile), height: height.min(max_tile) },
+            self.renderer.swapchain_properties.format.format,
+            self.renderer.msaa_samples,
+            self.renderer.depth_format,
+        )?;
+        // the frame is drawn again with the uniforms of the window afterwards
+        let frame = self.renderer.current_frame;
+        self.update_uniform_buffers(frame, time, Some(camera));
+
+        let device = self.renderer.vk_context.device();
+        let pipelines = match art.or(self.renderer.screen.entered.as_ref()) {
+            Some(pipeline) => vec![(pipeline, UBO_IDX_ART as u32 * self.ubo_stride)],
+            None => self.renderer.pipelines.iter()
+                .filter(|pipeline| pipeline.active)
+                .map(|pipeline| (pipeline, 0))
+                .collect(),
+        };
+        let clear_values = [
+            vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
+                },
+            },
+        ];
+        let mut image = RgbaImage::new(width, height);
+        for (i, tile) in still::tiles(extent, max_tile).into_iter().enumerate() {
+            let tile_rect = vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: tile.extent,
+            };
+            let pool = self.renderer.transient_command_pool;
+            let queue = self.renderer.graphics_queue;
+            cmd::execute_one_time_commands(device, pool, queue, |buffer| {
+                // the traced images are the same for every tile
+                if i == 0 {
+                    for pipeline in self.ray_tracing_pipelines.iter() {
+                        unsafe { pipeline.trace_to_cmd_buffer(device, buffer, frame) };
+                    }
+                }
+                let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                    .render_pass(target.render_pass())
+                    .framebuffer(target.framebuffer())
+                    .render_area(tile_rect)
+                    .clear_values(&clear_values);
+                // move the whole image so that the tile is at the origin of the attachments
+                let viewport = vk::Viewport {
+                    x: -tile.offset.x as f32,
+                    y: -tile.offset.y as f32,
+                    width: width as f32,
+                    height: height as f32,
+                    min_depth: 0.0,
+                    max_depth: 1.0,
+                };
+                unsafe {
+                    device.cmd_begin_render_pass(
+                        buffer,
+                        &render_pass_begin_info,
+                        vk::SubpassContents::INLINE,
+                    );
+                    device.cmd_set_viewport(buffer, 0, &[viewport]);
+                    device.cmd_set_scissor(buffer, 0, &[tile_rect]);
+                    for &(pipeline, ubo_offset) in pipelines.iter() {
+                        if !pipeline.waiting_for_shaders {
+                            pipeline.bind_to_cmd_buffer(device, buffer, frame, ubo_offset);
+                        }
+                    }
+                    device.cmd_end_render_pass(buffer);
+                    target.copy_to_buffer(device, buffer, tile.extent);
+                }
+            });
+            let pixels = unsafe { target.read(device, tile.extent) };
+            let tile_image =
+                RgbaImage::from_raw(tile.extent.width, tile.extent.height, pixels).unwrap();
+            image::imageops::replace(&mut image, &tile_image, tile.offset.x as _, tile.offset.y as _);
+        }
+        unsafe { target.cleanup(device) };
+        Ok(image)
+    }
+
+    /// Creates a pipeline that draws the fragment shader of the 2D art at `index` over a viewport.
     ///
-    /// True if the swapchain is dirty and needs to be recreated.
-    pub fn draw_frame(&mut self, time: f32) -> bool {
-        log::trace!("Drawing frame.");
+    /// The shaders are shared with other pipelines, so only `cleanup_pip` may be called on it.
+    fn create_fullscreen_art_pipeline(
+        &self,
+        index: usize,
+        kind: &str,
+        msaa_samples: vk::SampleCountFlags,
+        render_pass: vk::RenderPass,
+    ) -> Option<Pipeline> {
+        let art = &self.renderer.pipelines[index];
+        let frag = art.fragment_shader()?.clone();
+        let pipeline = Pipeline::new(
+            format!("{} {kind}", art.name()),
+            self.renderer.vk_context.device(),
+            msaa_samples,
+            render_pass,
+            self.descriptor_set_layout,
+            art.descriptor_sets().to_vec(),
+            None,
+            PipelineConfig {
+                cull_mode: vk::CullModeFlags::NONE,
+                min_sample_shading: self.sample_shading.then_some(MIN_SAMPLE_SHADING),
+                art_uniform_offset: (index - PIPELINE_IDX_ART) as u32 * self.art_uniform_stride,
+                ..Default::default()
+            },
+            vec![self.resources.shaders.preview_vert.clone(), frag],
+            None,
+            None,
+        );
+        pipeline.inspect_err(|err| log::warn!("Failed to create {kind} of {}: {err}", art.name())).ok()
+    }
 
-        let device = self.vk_context.device();
-        let mut recreate_command_buffers = false;
-        for pipeline in self.pipelines[PIPELINE_IDX_ART..].iter_mut() {
-            if pipeline.has_changed() {
-                recreate_command_buffers = true;
-            } else if pipeline.waiting_for_shaders {
-                pipeline.recreate(
-                    device,
-                    self.swapchain_properties,
-                    self.msaa_samples,
-                    self.render_pass,
-                    self.descriptor_set_layout,
-                );
-                recreate_command_buffers |= !pipeline.waiting_for_shaders;
+    /// Clean up the swapchain and all resources that depend on it.
+    /// Destroys the targets and the pipelines of the app that depend on the swapchain,
+    /// the renderer cleans up its own, see [`Renderer::recreate_swapchain`].
+    fn cleanup_swapchain(&mut self) {
+        let device = self.renderer.vk_context.device();
+        unsafe {
+            for art in self.half_res_art.iter_mut() {
+                art.cleanup(device);
+                art.upscale.cleanup_pip(device);
             }
-        }
-        if recreate_command_buffers {
-            self.wait_gpu_idle();
-            for pipeline in self.pipelines[PIPELINE_IDX_ART..].iter_mut() {
-                pipeline.reload_shaders(device, false);
+            if let Some(target) = self.stereo_target.as_mut() {
+                target.cleanup(device);
+            }
+            if let Some(target) = self.grade_target.as_mut() {
+                target.cleanup(device);
             }
-            self.recreate_command_buffers();
         }
+    }
 
-        let sync_objects = self.in_flight_frames.next().unwrap();
-        let image_available_semaphore = sync_objects.image_available_semaphore;
-        let render_finished_semaphore = sync_objects.render_finished_semaphore;
-        let in_flight_fence = sync_objects.fence;
-        let wait_fences = [in_flight_fence];
-
-        unsafe {
-            self.vk_context.device().wait_for_fences(&wait_fences, true, u64::MAX).unwrap()
+    /// Writes the uniforms of `frame_index`, with `still` the first view is
+    /// seen by its camera instead of the one of the window.
+    fn update_uniform_buffers(
+        &mut self,
+        frame_index: usize,
+        time: f32,
+        still: Option<&StillCamera>,
+    ) {
+        let scene = &self.scene;
+        let eye_views = match (self.stereo_target.is_some(), scene.tracked_eye_views) {
+            (true, Some(eye_views)) => eye_views,
+            // move the world to the right for the left eye and to the left for the right eye
+            (true, None) => [EYE_SEPARATION / 2., -EYE_SEPARATION / 2.]
+                .map(|x| Matrix4::from_translation(Vector3::from([x, 0., 0.])) * scene.view_matrix),
+            (false, _) => [scene.view_matrix; 2],
         };
-
-        let result = unsafe {
-            self.swapchain.acquire_next_image(
-                self.swapchain_khr,
-                u64::MAX,
-                image_available_semaphore,
-                vk::Fence::null(),
-            )
+        let view_matrices = std::iter::once(scene.view_matrix)
+            .chain(scene.split_view_matrices.iter().copied());
+        // the post pass passes the frame through without a table and reflects a single view
+        let grade_strength = if self.lut.is_some() { self.grade_strength } else { 0. };
+        let single_view = still.is_none()
+            && self.views.len() == 1
+            && self.stereo_target.is_none()
+            && self.renderer.screen.entered.is_none();
+        let reflecting = self.reflecting() && single_view;
+        let fogging = self.volumetric_fog && single_view;
+        let focusing = self.focusing() && single_view;
+        let blurring_motion = self.blurring_motion() && single_view;
+        let previous_view_proj = self.previous_view_proj;
+        // the stylistic effects are applied to the whole frame, even with several views
+        let post_effects = self.post_effects;
+        let render_scale = if still.is_some() { 1. } else { self.render_scale() };
+        // the swapchain images are not rotated by the compositor, stills are never rotated
+        let pre_rotation = match still {
+            Some(_) => Matrix4::unit(),
+            None => self.renderer.swapchain_properties.pre_rotation(),
         };
-        let image_index = match result {
-            // ignore suboptimal swap chain here because we already acquired an image
-            Ok((image_index, _suboptimal)) => image_index,
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
-                return true;
+        let edited_model = scene.edited_art()
+            .and_then(|index| self.renderer.pipelines[index].model_matrix());
+        let mut ubos = self.views.iter().zip(view_matrices).enumerate().map(|(i, (rect, view))| {
+            let extent = rect.extent;
+            let aspect = match still {
+                Some(_) => extent.width as f32 / extent.height as f32,
+                None => self.renderer.swapchain_properties.aspect(extent),
+            };
+            let proj = pre_rotation * scene.projection.matrix(aspect);
+            UniformBufferObject {
+                model: scene.model_matrix,
+                view,
+                proj,
+                resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                texture_weight: scene.texture_weight,
+                time,
+                eye_views: if i == 0 { eye_views } else { [view; 2] },
+                mouse: Vector4::default(),
+                // the fog is lit like the environment
+                fog_color: self.scene.fog.color * self.scene.sky.ambient_light,
+                fog_density: self.scene.fog.density,
+                fog_height_falloff: self.scene.fog.height_falloff,
+                sky_rotation: self.scene.sky.rotation,
+                day_weight: self.scene.sky.day_weight,
+                ambient_light: self.scene.sky.ambient_light,
+                sun_direction: self.scene.sky.sun_direction,
+                fade: self.scene.fade().unwrap_or(0.),
+                frame_times: FrameGraph::packed(&self.frame_graph.frame_times),
+                gpu_frame_times: FrameGraph::packed(&self.frame_graph.gpu_frame_times),
+                grade_strength,
+                texture_aspect: if self.env_tex_coords { 1. } else { self.resources.image_aspect },
+                parallax_scale: self.parallax_scale,
+                reflection_strength: if reflecting { self.reflection_strength } else { 0. },
+                volumetric_fog: if fogging { 1. } else { 0. },
+                dof_aperture: if focusing { self.dof_aperture } else { 0. },
+                focus_point: self.focus_point,
+                previous_view_proj: previous_view_proj.unwrap_or(proj * view),
+                shutter: if blurring_motion { self.shutter } else { 0. },
+                chromatic_aberration: post_effects.strength(PostEffect::ChromaticAberration),
+                vignette: post_effects.strength(PostEffect::Vignette),
+                film_grain: post_effects.strength(PostEffect::FilmGrain),
+                render_scale,
+                gizmo_mode: scene.gizmo().mode as u8 as f32,
+                gizmo_axis: scene.gizmo().active_axis().map_or(-1., |axis| axis as f32),
+                _padding: 0.,
+                gizmo: edited_model.map_or_else(Matrix4::default, |model| {
+                    scene.gizmo().frame(model, view)
+                }),
             }
-            Err(error) => panic!("Error while acquiring next image. Cause: {}", error),
-        };
-
-        // it is important to only reset the fence when we know that we are going to do work
-        unsafe { self.vk_context.device().reset_fences(&wait_fences).unwrap() };
-
-        self.update_uniform_buffers(image_index, time);
-
-        let device = self.vk_context.device();
-        let wait_semaphores = [image_available_semaphore];
-        let signal_semaphores = [render_finished_semaphore];
+        }).collect::<Vec<_>>();
+        if let Some(still) = still {
+            let extent = still.extent;
+            ubos[0] = UniformBufferObject {
+                view: still.view,
+                proj: still.proj,
+                resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                eye_views: [still.view; 2],
+                ..ubos[0]
+            };
+        } else {
+            // the first frame after a pause of the motion blur is not blurred
+            self.previous_view_proj = blurring_motion.then(|| ubos[0].proj * ubos[0].view);
+        }
 
-        // Submit command buffer
-        {
-            let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
-            let command_buffers = [self.command_buffers[image_index as usize]];
-            let submit_info = vk::SubmitInfo::default()
-                .wait_semaphores(&wait_semaphores)
-                .wait_dst_stage_mask(&wait_stages)
-                .command_buffers(&command_buffers)
-                .signal_semaphores(&signal_semaphores);
-            let submit_infos = [submit_info];
-            unsafe {
-                device.queue_submit(self.graphics_queue, &submit_infos, in_flight_fence).unwrap()
+        if still.is_none() && !self.renderers.is_empty() {
+            let frame = FrameState {
+                frame: frame_index,
+                time,
+                views: ubos.iter().map(|ubo| ViewMatrices {
+                    view: ubo.view,
+                    proj: ubo.proj,
+                    eye_views: ubo.eye_views,
+                }).collect(),
             };
+            for renderer in self.renderers.iter_mut() {
+                renderer.update(&self.renderer.vk_context, &frame);
+            }
         }
 
-        let swapchains = [self.swapchain_khr];
-        let images_indices = [image_index];
-        let present_info = vk::PresentInfoKHR::default()
-            .wait_semaphores(&signal_semaphores)
-            .swapchains(&swapchains)
-            .image_indices(&images_indices);
-        // .results() null since we only have one swapchain
-        let result = unsafe {
-            self.swapchain.queue_present(self.present_queue, &present_info)
+        // the previewed or entered art has its own resolution, time and mouse
+        let extent = still.map(|still| still.extent).unwrap_or_else(|| {
+            self.stereo_target.as_ref().map_or(self.render_extent(), StereoTarget::extent)
+        });
+        let art_ubo = match (still.and_then(|still| still.art_time), self.scene.entered_art()) {
+            (Some(art_time), _) => UniformBufferObject {
+                resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                time: art_time,
+                mouse: Vector4::default(),
+                ..ubos[0]
+            },
+            (None, Some(_)) => UniformBufferObject {
+                resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                time: self.scene.entered_time(time),
+                // the mouse is in the pixels of the window
+                mouse: self.scene.art_mouse * render_scale,
+                ..ubos[0]
+            },
+            (None, None) => {
+                let extent = Renderer::preview_rect(extent).extent;
+                UniformBufferObject {
+                    resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                    ..ubos[0]
+                }
+            }
         };
-        match result {
-            Ok(value) => value,
-            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => true,
-            Err(error) => panic!("Failed to present queue. Cause: {}", error),
+        // fill the unused views so that the art is at its offset
+        ubos.resize(UBO_IDX_ART, ubos[0]);
+        ubos.push(art_ubo);
+        let projector_ubo = match self.projector.as_mut() {
+            Some(projector) => {
+                let extent = projector.extent();
+                UniformBufferObject {
+                    resolution: Vector2::from([extent.width as f32, extent.height as f32]),
+                    time: time - *projector.start_time.get_or_insert(time),
+                    mouse: Vector4::default(),
+                    ..ubos[0]
+                }
+            }
+            None => ubos[0],
+        };
+        ubos.push(projector_ubo);
+
+        let quality = self.quality;
+        let art_uniforms = self.renderer.pipelines[PIPELINE_IDX_ART..].iter_mut()
+            .map(|pipeline| {
+                let art_uniforms = pipeline.art_uniforms(time, self.scene.view_matrix)
+                    .unwrap_or_default();
+                // art far away may be at a lower quality than the rest
+                ArtUniforms { quality: quality.min(art_uniforms.quality), ..art_uniforms }
+            })
+            .collect::<Vec<_>>();
+        if let Some(occlusion) = self.occlusion.as_mut().filter(|_| still.is_none()) {
+            let boxes = self.renderer.pipelines.iter().enumerate().map(|(index, pipeline)| {
+                let model = index.checked_sub(PIPELINE_IDX_ART)
+                    .map_or(Matrix4::unit(), |art| art_uniforms[art].model);
+                let index_count = pipeline.geometry.as_ref()
+                    .and_then(Geometry::get)
+                    .map_or(0, |(_, _, index_count)| index_count);
+                CullBox::new(model, pipeline.bounds(), index_count)
+            }).collect::<Vec<_>>();
+            occlusion.write(frame_index, ubos[0].proj * ubos[0].view, &boxes);
+        }
+        let clustered = still.is_none()
+            && self.views.len() == 1
+            && self.stereo_target.is_none()
+            && self.renderer.screen.entered.is_none();
+        let cluster_view = clustered.then(|| ClusterView {
+            view: ubos[0].view,
+            proj: ubos[0].proj,
+            pre_rotation,
+            extent: self.views[0].extent,
+            near: self.scene.projection.near,
+            far: self.scene.projection.far,
+        });
+        self.light_clusters.write(frame_index, cluster_view, &self.lights);
+        if let Some(text) = self.overlay_text.as_ref() {
+            self.text_overlay.write(frame_index, text, self.overlay_color);
+        }
+        // the shadows follow the first view
+        let aspect = match still {
+            Some(still) => still.extent.width as f32 / still.extent.height as f32,
+            None => self.renderer.swapchain_properties.aspect(self.views[0].extent),
+        };
+        let cascades = shadows::fit_cascades(
+            ubos[0].view,
+            self.scene.projection,
+            aspect,
+            self.scene.sky.sun_direction,
+        );
+        self.shadows.write(frame_index, self.scene.model_matrix, &cascades);
+        if fogging {
+            self.fog_volume.write(frame_index, FogView {
+                view: ubos[0].view,
+                proj: ubos[0].proj,
+                color: ubos[0].fog_color,
+                density: self.scene.fog.density,
+                height_falloff: self.scene.fog.height_falloff,
+                sun_direction: self.scene.sky.sun_direction,
+                sun_light: self.scene.sky.day_weight,
+            });
+        }
+        let skins = self.renderer.pipelines[PIPELINE_IDX_ART..].iter().map(Pipeline::skin);
+        for (skin, art_uniforms) in skins.zip(art_uniforms.iter()) {
+            if let Some(skin) = skin {
+                let matrices = skin.joint_matrices(art_uniforms.time);
+                self.joint_buffer.write(frame_index, art_uniforms.joint_offset as _, &matrices);
+            }
         }
-    }
 
-    pub fn load_new_texture<P: AsRef<Path>>(&mut self, path: P) -> Result<(), anyhow::Error> {
-        log::info!("Loading image {:?}", path.as_ref().as_os_str());
-        self.wait_gpu_idle();
+        let (ubos_size, frame_size) = Self::uniform_buffer_frame_size(
+            self.ubo_stride,
+            self.art_uniform_stride,
+            art_uniforms.len(),
+        );
+        // the uniform buffer objects of the frame start at its part of the buffer
+        let frame_offset = self.uniform_buffers[frame_index][0].offset as usize;
+        unsafe {
+            let data_ptr = self.uniform_buffer_ptr.add(frame_offset);
+            // the alignment places every uniform buffer object at the offset of its view
+            let mut align = ash::util::Align::new(data_ptr, self.ubo_stride as _, ubos_size as _);
+            align.copy_from_slice(&ubos);
+            let mut align = ash::util::Align::new(
+                data_ptr.add(ubos_size),
+                self.art_uniform_stride as _,
+                (frame_size - ubos_size) as _,
+            );
+            align.copy_from_slice(&art_uniforms);
+        }
+    }
 
-        self.textures[0].destroy(self.vk_context.device());
-        let texture = Self::create_texture_image(
-            &self.vk_context,
-            self.command_pool,
-            self.graphics_queue,
-            path,
-        )?;
-        let device = self.vk_context.device();
+    pub fn reset_ubo(&mut self) {
+        self.scene.model_matrix = Matrix4::unit();
+    }
 
-        for set in self.descriptor_sets_main.iter() {
-            let image_info = vk::DescriptorImageInfo::default()
-                .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
-                .image_view(texture.view)
-                .sampler(texture.sampler.unwrap());
-            let image_infos = [image_info];
-            let sampler_descriptor_write = vk::WriteDescriptorSet::default()
-                .dst_set(*set)
-                .dst_binding(1)
-                .dst_array_element(0)
-                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
-                .image_info(&image_infos);
-            unsafe { device.update_descriptor_sets(&[sampler_descriptor_write], &[]) }
+    /// Hangs `art` in the running gallery after the art it was created with. It is drawn once
+    /// its shaders are compiled, which are compiled again whenever their files change.
+    ///
+    /// There is room for [`ADDED_ART_CAPACITY`] more pieces. Art with a model or ray tracing
+    /// shaders can only be hung when the gallery is created, art at half resolution is drawn
+    /// at full resolution.
+    pub fn add_art(&mut self, mut art: ShaderArt) -> Result<(), anyhow::Error> {
+        let index = self.renderer.pipelines.len();
+        if index - PIPELINE_IDX_ART >= self.art_capacity {
+            anyhow::bail!("There is no room for {}, the gallery is full", art.name);
+        }
+        if art.model_path.is_some() || art.ray_tracing.is_some() {
+            anyhow::bail!("{} can only be hung when the gallery is created", art.name);
+        }
+        if art.tessellation.is_some()
+            && self.renderer.vk_context.enabled_features().tessellation_shader != vk::TRUE
+        {
+            anyhow::bail!("Skipping {}, tessellation shaders are not supported", art.name);
+        }
+        let is_mesh = art.is_mesh();
+        if is_mesh && self.renderer.vk_context.mesh_shader().is_none() {
+            anyhow::bail!("Skipping {}, mesh shaders are not supported", art.name);
         }
+        if art.half_resolution {
+            log::warn!("Drawing {} at full resolution, it is added while running", art.name);
+        }
+        log::info!("Adding art {}", art.name);
 
-        self.textures[0] = texture;
-        self.recreate_command_buffers();
-        Ok(())
-    }
+        let is_3d = art.is_3d;
+        let geometry = match (is_mesh, is_3d) {
+            (true, _) => None,
+            (false, true) => Some(self.resources.skybox.clone()),
+            (false, false) => Some(self.resources.quad.clone()),
+        };
+        // raymarched art stays inside of the cube it is drawn with and can be culled
+        let bounds = (is_3d && !is_mesh && art.tessellation.is_none())
+            .then_some(self.art_cube_bounds);
 
-    pub fn reload_shaders(&mut self) {
         self.wait_gpu_idle();
+        let descriptor_sets = match art.volume.take() {
+            Some(volume) => {
+                let texture_volume = ResourceManager::create_volume_texture(
+                    &self.renderer.vk_context,
+                    self.renderer.command_pool,
+                    self.renderer.graphics_queue,
+                    &volume,
+                    SamplerConfig::CLAMP,
+                ).with_context(|| format!("Failed to create the volume of {}", art.name))?;
+                let descriptor_sets = Self::create_descriptor_sets(
+                    self.renderer.vk_context.device(),
+                    &mut self.descriptor_allocator,
+                    self.descriptor_set_layout,
+                    &self.uniform_buffers,
+                    self.resources.textures.art_image,
+                )?;
+                let device = self.renderer.vk_context.device();
+                let textures = &self.resources.textures;
+                let video = self.resources.video.as_ref()
+                    .map_or(textures.video_empty, |video| video.texture.texture());
+                Self::update_descriptor_sets_image(device, &descriptor_sets, 4, texture_volume);
+                Self::update_descriptor_sets_image(device, &descriptor_sets, 5, textures.noise);
+                Self::update_descriptor_sets_image(device, &descriptor_sets, 6, textures.noise_3d);
+                Self::update_descriptor_sets_image(
+                    device,
+                    &descriptor_sets,
+                    7,
+                    self.resources.keyboard_texture.texture(),
+                );
+                Self::update_descriptor_sets_image(device, &descriptor_sets, 8, video);
+                self.joint_buffer.update_descriptor_sets(device, &descriptor_sets);
+                self.descriptor_sets_volume.extend_from_slice(&descriptor_sets);
+                self.resources.volumes.push(texture_volume);
+                descriptor_sets
+            }
+            None => self.descriptor_sets_art.clone(),
+        };
 
-        let device = self.vk_context.device();
-        let mut reloading = false;
-        for pipeline in self.pipelines[PIPELINE_IDX_ART..].iter_mut() {
-            reloading |= pipeline.reload_shaders(device, true);
+        for shader in art.shaders_mut() {
+            if self.renderer.vk_context.multiview() {
+                shader.set_define("MULTIVIEW", None);
+            }
+            shader.set_hot_reload(self.compile_sender.clone());
+            self.shader_watcher.watch(shader);
         }
-        if reloading {
+        let art_uniforms = ArtUniforms {
+            model: art.model_matrix,
+            params: art.params,
+            time: 0.,
+            image_aspect: self.resources.art_image_aspect,
+            framing: art.framing as u32,
+            morph_weights: art.morph_weights,
+            ..Default::default()
+        };
+        let art_time = art.time;
+        let proximity = art.proximity;
+        let mut pipeline = Pipeline::new(
+            art.name.clone(),
+            self.renderer.vk_context.device(),
+            self.renderer.msaa_samples,
+            self.render_target(),
+            self.descriptor_set_layout,
+            descriptor_sets,
+            geometry,
+            PipelineConfig {
+                variable_shading_rate: self.renderer.vk_context.fragment_shading_rate().is_some(),
+                min_sample_shading: self.sample_shading.then_some(MIN_SAMPLE_SHADING),
+                art_uniform_offset: (index - PIPELINE_IDX_ART) as u32 * self.art_uniform_stride,
+                ..Default::default()
+            },
+            art.into_shaders(),
+            Some(self.resources.shaders.error.clone()),
+            Some(art_uniforms),
+        )?;
+        pipeline.set_art_time(art_time);
+        pipeline.set_proximity(proximity);
+        pipeline.set_bounds(bounds);
+        if is_mesh {
+            pipeline.set_mesh_shader(self.renderer.vk_context.mesh_shader().cloned());
+        }
+        let vk_context = &self.renderer.vk_context;
+        pipeline.set_fragment_shading_rate(vk_context.fragment_shading_rate().cloned());
+        pipeline.set_feedback(Some(PipelineFeedback {
+            creation: vk_context.pipeline_creation_feedback(),
+            executable_properties: vk_context.pipeline_executable_properties().cloned(),
+        }));
+        if !is_3d && !is_mesh {
+            self.scene.add_2d_art(index);
+        }
+        self.renderer.pipelines.push(pipeline);
+
+        // the timer measures every pipeline
+        if self.gpu_timer.is_some() {
+            self.set_gpu_timing(true)?;
+        } else {
             self.recreate_command_buffers();
         }
+        Ok(())
     }
 
-    /// Recreates the swapchain with new dimensions.
-    ///
-    /// # Panics
+    /// Takes the art named `name` down from the running gallery, the art after it moves up
+    /// in the order the art was given.
     ///
-    /// Panics if either `width` or `height` is zero.
-    pub fn recreate_swapchain(&mut self, width: u32, height: u32) {
-        log::debug!("Recreating swapchain");
-        if width == 0 || height == 0 {
-            panic!("invalid dimensions: ({width}, {height})");
+    /// Ray traced art can not be taken down, a volume of the art stays loaded until the
+    /// gallery is dropped.
+    pub fn remove_art(&mut self, name: &str) -> Result<(), anyhow::Error> {
+        let index = self.art_index(name).with_context(|| format!("There is no art {name}"))?;
+        if self.ray_tracing_pipelines.iter().any(|pipeline| pipeline.name() == name) {
+            anyhow::bail!("{name} is ray traced, it can only be taken down by a restart");
         }
-
+        log::info!("Removing art {name}");
         self.wait_gpu_idle();
-        self.cleanup_swapchain();
 
-        let device = self.vk_context.device();
-        let dimensions = [width, height];
-        let (swapchain, swapchain_khr, properties, images) = Self::create_swapchain_and_images(
-            &self.vk_context,
-            dimensions,
-        );
-        let swapchain_image_views = Self::create_swapchain_image_views(device, &images, properties);
+        // the fullscreen pipelines share the shaders of the art
+        if self.scene.preview_art() == Some(index) {
+            if let Some(mut pipeline) = self.renderer.screen.preview.take() {
+                unsafe { pipeline.cleanup_pip(self.renderer.vk_context.device()) };
+            }
+        }
+        if self.scene.entered_art() == Some(index) {
+            if let Some(mut pipeline) = self.renderer.screen.entered.take() {
+                unsafe { pipeline.cleanup_pip(self.renderer.vk_context.device()) };
+            }
+        }
+        if self.projector.as_ref().is_some_and(|projector| projector.art == index) {
+            self.close_projector();
+        }
+        self.scene.remove_art(index);
+
+        let device = self.renderer.vk_context.device();
+        let mut removed = vec![self.renderer.pipelines.remove(index)];
+        if let Some(position) = self.half_res_art.iter().position(|art| art.index == index) {
+            let mut art = self.half_res_art.remove(position);
+            unsafe { art.cleanup(device) };
+            removed.push(art.upscale);
+        }
+        for mut pipeline in removed {
+            unsafe { pipeline.cleanup_pip(device) };
+            if let Some(geometry) = pipeline.geometry.take() {
+                unsafe { geometry.cleanup(device) };
+            }
+            // shaders can be shared with other art, e.g. the vertex shaders
+            let in_use = |shader: &Shader| {
+                self.resources.shaders.error.contains(shader)
+                    || self.renderer.pipelines.iter()
+                        .chain(self.half_res_art.iter().map(|art| &art.upscale))
+                        .any(|other| other.shaders().contains(shader))
+            };
+            for shader in pipeline.shaders().iter().filter(|shader| !in_use(shader)) {
+                shader.cleanup(device);
+            }
+        }
 
-        let render_pass =
-            Self::create_render_pass(device, properties, self.msaa_samples, self.depth_format);
+        let shift = |other: usize| if other > index { other - 1 } else { other };
+        if let Some(projector) = self.projector.as_mut() {
+            projector.art = shift(projector.art);
+        }
+        for art in self.half_res_art.iter_mut() {
+            art.index = shift(art.index);
+            let offset = (art.index - PIPELINE_IDX_ART) as u32 * self.art_uniform_stride;
+            art.upscale.set_art_uniform_offset(offset);
+        }
+        for (other, pipeline) in self.renderer.pipelines.iter_mut().enumerate().skip(index) {
+            let offset = (other - PIPELINE_IDX_ART) as u32 * self.art_uniform_stride;
+            pipeline.set_art_uniform_offset(offset);
+        }
 
-        for pipeline in self.pipelines.iter_mut() {
-            pipeline.recreate(
-                device,
-                properties,
-                self.msaa_samples,
-                render_pass,
-                self.descriptor_set_layout,
-            );
+        if self.gpu_timer.is_some() {
+            self.set_gpu_timing(true)?;
+        } else {
+            self.recreate_command_buffers();
         }
+        Ok(())
+    }
 
-        let color_texture = Self::create_color_texture(
-            &self.vk_context,
-            self.command_pool,
-            self.graphics_queue,
-            properties,
-            self.msaa_samples,
-        );
+    /// Moves the art named `name` to `model`, a script still moves it on top of that.
+    ///
+    /// Returns false if there is no such art.
+    pub fn set_art_model(&mut self, name: &str, model: Matrix4) -> bool {
+        let Some(index) = self.art_index(name) else {
+            return false;
+        };
+        self.renderer.pipelines[index].set_model_matrix(model);
+        true
+    }
 
-        let depth_texture = Self::create_depth_texture(
-            &self.vk_context,
-            self.command_pool,
-            self.graphics_queue,
-            self.depth_format,
-            properties.extent,
-            self.msaa_samples,
-        );
+    /// Replaces the free parameters of the art named `name`, a script still overrides them.
+    ///
+    /// Returns false if there is no such art.
+    pub fn set_art_params(&mut self, name: &str, params: Vector4) -> bool {
+        let Some(index) = self.art_index(name) else {
+            return false;
+        };
+        self.renderer.pipelines[index].set_params(params);
+        true
+    }
 
-        let swapchain_framebuffers = Self::create_framebuffers(
-            device,
-            &swapchain_image_views,
-            color_texture,
-            depth_texture,
-            render_pass,
-            properties,
-        );
+    /// The index of the pipeline of the art named `name`.
+    fn art_index(&self, name: &str) -> Option<usize> {
+        (PIPELINE_IDX_ART..self.renderer.pipelines.len())
+            .find(|&index| self.renderer.pipelines[index].name() == name)
+    }
 
-        self.swapchain = swapchain;
-        self.swapchain_khr = swapchain_khr;
-        self.swapchain_properties = properties;
-        self.images = images;
-        self.swapchain_image_views = swapchain_image_views;
-        self.render_pass = render_pass;
-        self.color_texture = color_texture;
-        self.depth_texture = depth_texture;
-        self.swapchain_framebuffers = swapchain_framebuffers;
-        self.recreate_command_buffers();
+    /// The names of the art in the order it was given and whether each piece is drawn.
+    pub fn art_visibility(&self) -> Vec<(&str, bool)> {
+        self.renderer.pipelines[PIPELINE_IDX_ART..].iter()
+            .map(|pipeline| (pipeline.name(), pipeline.active))
+            .collect()
     }
 
-    /// Clean up the swapchain and all resources that depend on it.
-    fn cleanup_swapchain(&mut self) {
-        let device = self.vk_context.device();
-        unsafe {
-            self.depth_texture.destroy(device);
-            self.color_texture.destroy(device);
-            for framebuffer in self.swapchain_framebuffers.iter() {
-                device.destroy_framebuffer(*framebuffer, None);
-            }
-            for pipeline in self.pipelines.iter_mut() {
-                pipeline.cleanup_pip(device);
-            }
-            device.destroy_render_pass(self.render_pass, None);
-            for image_view in self.swapchain_image_views.iter() {
-                device.destroy_image_view(*image_view, None);
-            }
-            self.swapchain.destroy_swapchain(self.swapchain_khr, None);
+    /// Shows or hides the art at `index` in the order it was given, e.g. to keep a heavy piece
+    /// from slowing down the work on another one.
+    ///
+    /// Returns the name of the art and whether it is drawn now, or `None` if there is no art
+    /// at `index`.
+    pub fn toggle_art(&mut self, index: usize) -> Option<(&str, bool)> {
+        let index = PIPELINE_IDX_ART + index;
+        if index >= self.renderer.pipelines.len() {
+            return None;
         }
+        self.wait_gpu_idle();
+        self.renderer.pipelines[index].active = !self.renderer.pipelines[index].active;
+        self.recreate_command_buffers();
+        let pipeline = &self.renderer.pipelines[index];
+        Some((pipeline.name(), pipeline.active))
     }
 
-    fn update_uniform_buffers(&mut self, current_image: u32, time: f32) {
-        let extent = self.swapchain_properties.extent;
-        let aspect = extent.width as f32 / extent.height as f32;
-        let ubo = UniformBufferObject {
-            model: self.model_matrix,
-            view: self.view_matrix,
-            proj: math::perspective(Deg(75.0), aspect, 0.1, 200.0),
-            resolution: Vector2::from([extent.width as f32, extent.height as f32]),
-            texture_weight: self.texture_weight,
-            time,
-        };
-        let ubos = [ubo];
-
-        let buffer_mem = self.uniform_buffer_memories[current_image as usize];
-        let size = size_of::<UniformBufferObject>() as vk::DeviceSize;
-        unsafe {
-            let device = self.vk_context.device();
-            let data_ptr = device
-                .map_memory(buffer_mem, 0, size, vk::MemoryMapFlags::empty())
-                .unwrap();
-            let mut align = ash::util::Align::new(data_ptr, align_of::<f32>() as _, size);
-            align.copy_from_slice(&ubos);
-            device.unmap_memory(buffer_mem);
-        }
+    /// Shows or hides the sky, either the cubemap or the procedural one.
+    pub fn toggle_cubemap(&mut self) {
+        let idx = self.sky_pipeline_idx();
+        self.renderer.pipelines[idx].active = !self.renderer.pipelines[idx].active;
     }
 
-    pub fn reset_ubo(&mut self) {
-        self.model_matrix = Matrix4::unit();
+    /// Switches between the cubemap and the procedural sky.
+    pub fn toggle_procedural_sky(&mut self) {
+        let active = self.renderer.pipelines[self.sky_pipeline_idx()].active;
+        self.procedural_sky = !self.procedural_sky;
+        self.renderer.pipelines[PIPELINE_IDX_CUBE].active = active && !self.procedural_sky;
+        self.renderer.pipelines[PIPELINE_IDX_SKY].active = active && self.procedural_sky;
     }
 
-    pub fn toggle_cubemap(&mut self) {
-        self.pipelines[PIPELINE_IDX_CUBE].active = !self.pipelines[PIPELINE_IDX_CUBE].active;
+    fn sky_pipeline_idx(&self) -> usize {
+        if self.procedural_sky { PIPELINE_IDX_SKY } else { PIPELINE_IDX_CUBE }
     }
 }
 
 impl Drop for VkApp {
     fn drop(&mut self) {
         log::debug!("Dropping application.");
+        self.close_projector();
+        #[cfg(feature = "openxr")]
+        if let Some(openxr) = self.openxr.take() {
+            self.wait_gpu_idle();
+            drop(openxr);
+        }
+        if let Some(mut timer) = self.gpu_timer.take() {
+            timer.destroy(self.renderer.vk_context.device());
+        }
+        if let Some(mut markers) = self.crash_markers.take() {
+            unsafe { markers.destroy(self.renderer.vk_context.device()) };
+        }
+        // the renderer destroys the swapchain, the frames and the pipelines of the gallery
+        // and of the screen once it is dropped after everything else
         self.cleanup_swapchain();
 
-        let device = self.vk_context.device();
-        self.in_flight_frames.destroy(device);
+        let device = self.renderer.vk_context.device();
         unsafe {
-            for pipeline in self.pipelines.iter_mut() {
+            for art in self.half_res_art.iter_mut() {
+                art.upscale.cleanup(device);
+            }
+            for renderer in self.renderers.iter_mut() {
+                renderer.destroy(device);
+            }
+            for pipeline in self.ray_tracing_pipelines.iter_mut() {
                 pipeline.cleanup(device);
             }
-            device.destroy_descriptor_pool(self.descriptor_pool, None);
-            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
-            for &mem in &self.uniform_buffer_memories {
-                device.free_memory(mem, None);
+            if let Some(ray_tracing) = self.renderer.vk_context.ray_tracing() {
+                for acceleration_structure in self.acceleration_structures.iter() {
+                    acceleration_structure.cleanup(device, ray_tracing);
+                }
             }
-            for &buffer in &self.uniform_buffers {
-                device.destroy_buffer(buffer, None);
+            self.descriptor_allocator.destroy(device);
+            device.unmap_memory(self.uniform_buffer_memory);
+            device.free_memory(self.uniform_buffer_memory, None);
+            device.destroy_buffer(self.uniform_buffer, None);
+            self.joint_buffer.destroy(device);
+            self.light_clusters.destroy(device);
+            self.shadows.destroy(device);
+            self.fog_volume.destroy(device);
+            self.text_overlay.destroy(device);
+            if let Some(occlusion) = self.occlusion.as_mut() {
+                occlusion.destroy(device);
             }
-            for texture in &mut self.textures {
-                texture.destroy(device);
+            if let Some(lut) = self.lut.as_mut() {
+                lut.destroy(device);
             }
-            device.free_command_buffers(self.command_pool, &self.command_buffers);
-            device.destroy_command_pool(self.transient_command_pool, None);
-            device.destroy_command_pool(self.command_pool, None);
-        }
-    }
-}
-
-#[derive(Clone, Copy)]
-struct SyncObjects {
-    image_available_semaphore: vk::Semaphore,
-    render_finished_semaphore: vk::Semaphore,
-    fence: vk::Fence,
-}
-
-impl SyncObjects {
-    fn destroy(&self, device: &Device) {
-        unsafe {
-            device.destroy_semaphore(self.image_available_semaphore, None);
-            device.destroy_semaphore(self.render_finished_semaphore, None);
-            device.destroy_fence(self.fence, None);
-        }
-    }
-}
-
-struct InFlightFrames {
-    sync_objects: Vec<SyncObjects>,
-    current_frame: usize,
-}
-
-impl InFlightFrames {
-    fn new(sync_objects: Vec<SyncObjects>) -> Self {
-        Self {
-            sync_objects,
-            current_frame: 0,
+            self.resources.destroy(device);
         }
     }
-
-    fn destroy(&self, device: &Device) {
-        self.sync_objects.iter().for_each(|o| o.destroy(device));
-    }
 }
 
-impl Iterator for InFlightFrames {
-    type Item = SyncObjects;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let next = self.sync_objects[self.current_frame];
-
-        self.current_frame = (self.current_frame + 1) % self.sync_objects.len();
-
-        Some(next)
-    }
+/// Width over height of `extent`.
+fn aspect_ratio(extent: vk::Extent2D) -> f32 {
+    extent.width as f32 / extent.height as f32
 }
@@ -27,9 +27,15 @@ pub fn create_buffer(
     let mem_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
     let memory = {
         let mem_type_index = vk_context.find_memory_type(mem_requirements, mem_properties);
-        let alloc_info = vk::MemoryAllocateInfo::default()
+        let mut alloc_flags_info = vk::MemoryAllocateFlagsInfo::default()
+            .flags(vk::MemoryAllocateFlags::DEVICE_ADDRESS);
+        let mut alloc_info = vk::MemoryAllocateInfo::default()
             .allocation_size(mem_requirements.size)
             .memory_type_index(mem_type_index);
+        // buffers used for ray tracing are referenced by their device address
+        if usage.contains(vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS) {
+            alloc_info = alloc_info.push_next(&mut alloc_flags_info);
+        }
         unsafe { device.allocate_memory(&alloc_info, None).unwrap() }
     };
 
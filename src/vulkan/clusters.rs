@@ -0,0 +1,320 @@
+use crate::lights::PointLight;
+use crate::math::Matrix4;
+use super::{
+    buffer,
+    context::VkContext,
+    descriptor::DescriptorAllocator,
+    shader::Shader,
+};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// The bindings of the lights and of the clusters in the descriptor sets of the environment.
+pub const LIGHTS_BINDING: u32 = 13;
+pub const CLUSTERS_BINDING: u32 = 14;
+/// The most lights there is room for, the others are ignored.
+pub const MAX_LIGHTS: usize = 256;
+/// The tiles across and down the view and the slices along its depth, `GRID` in the shaders.
+const CLUSTER_GRID: [u32; 3] = [16, 9, 24];
+/// A cluster is its number of lights followed by their indices, `CLUSTER_STRIDE` in the shaders.
+const CLUSTER_STRIDE: usize = 32;
+/// The workgroup size of `light_clusters.comp`.
+const GROUP_SIZE: u32 = 64;
+
+/// The view the clusters are built for.
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterView {
+    pub view: Matrix4,
+    pub proj: Matrix4,
+    /// The rotation of the swapchain applied after `proj`, the clusters are split along the
+    /// axes of the rotated images.
+    pub pre_rotation: Matrix4,
+    pub extent: vk::Extent2D,
+    pub near: f32,
+    pub far: f32,
+}
+
+/// The header of the lights in the shaders that the lights follow.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct LightsHeader {
+    view: Matrix4,
+    proj_scale: [f32; 2],
+    screen_size: [f32; 2],
+    near: f32,
+    far: f32,
+    count: u32,
+    clustered: u32,
+}
+
+/// A light as it is read by the shaders.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct GpuLight {
+    position: [f32; 3],
+    radius: f32,
+    color: [f32; 3],
+    _padding: f32,
+}
+
+/// Assigns the point lights to the clusters of the view so that the environment only
+/// looks at the few lights reaching a fragment, which keeps dozens of lights cheap.
+///
+/// The view is split into a grid of tiles across the screen and slices growing
+/// exponentially with the distance. A compute shader tests the box around each of these
+/// clusters against the sphere of every light and writes the indices of the lights
+/// touching it, which the fragment shader of the environment finds from its position
+/// on the screen and its depth.
+///
+/// Only a single view is clustered, other frames look at every light.
+pub struct LightClusters {
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    /// The header and the lights of every frame in flight, one after another.
+    lights_buffer: vk::Buffer,
+    lights_memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the lights of a frame aligned to the storage buffer offset alignment.
+    lights_size: usize,
+    /// The clusters of every frame in flight, one after another.
+    clusters_buffer: vk::Buffer,
+    clusters_memory: vk::DeviceMemory,
+    /// Size of the clusters of a frame aligned to the storage buffer offset alignment.
+    clusters_size: usize,
+}
+
+impl LightClusters {
+    /// The shader is the compute shader building the clusters, it is cleaned up.
+    pub fn new(
+        vk_context: &VkContext,
+        descriptor_allocator: &mut DescriptorAllocator,
+        shader: Shader,
+        frames_in_flight: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let device = vk_context.device();
+        let storage = |binding| vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::COMPUTE);
+        let descriptor_set_layout = descriptor_allocator
+            .create_layout(device, &[storage(0), storage(1)])?;
+        let module = shader.module(device).context("The cluster shader is not compiled")?;
+        let (pipeline, layout) = Self::create_pipeline(
+            device,
+            descriptor_set_layout,
+            module,
+            &shader.entry_point(),
+        );
+        shader.cleanup(device);
+        let descriptor_sets = descriptor_allocator
+            .allocate(device, descriptor_set_layout, frames_in_flight)?;
+
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let lights_size = Self::lights_range().next_multiple_of(alignment);
+        let (lights_buffer, lights_memory, _) = buffer::create_buffer(
+            vk_context,
+            (lights_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(lights_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map light buffer")?
+        };
+        let clusters_size = Self::clusters_range().next_multiple_of(alignment);
+        let (clusters_buffer, clusters_memory, _) = buffer::create_buffer(
+            vk_context,
+            (clusters_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+
+        let clusters = Self {
+            pipeline,
+            layout,
+            descriptor_sets,
+            lights_buffer,
+            lights_memory,
+            ptr,
+            lights_size,
+            clusters_buffer,
+            clusters_memory,
+            clusters_size,
+        };
+        for (frame, &set) in clusters.descriptor_sets.iter().enumerate() {
+            clusters.write_descriptor_set(device, frame, set, [0, 1]);
+        }
+        Ok(clusters)
+    }
+
+    pub fn descriptor_set_layout_bindings<'a>() -> [vk::DescriptorSetLayoutBinding<'a>; 2] {
+        [LIGHTS_BINDING, CLUSTERS_BINDING].map(|binding| {
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+        })
+    }
+
+    /// Points the descriptor set of each frame in flight at the lights and clusters of that frame.
+    pub fn update_descriptor_sets(&self, device: &Device, sets: &[vk::DescriptorSet]) {
+        for (frame, &set) in sets.iter().enumerate() {
+            self.write_descriptor_set(device, frame, set, [LIGHTS_BINDING, CLUSTERS_BINDING]);
+        }
+    }
+
+    /// Writes the lights of the frame at index `frame`, the GPU must be done with it.
+    ///
+    /// Without `view` the clusters are not used and every light is looked at.
+    pub fn write(&mut self, frame: usize, view: Option<ClusterView>, lights: &[PointLight]) {
+        let count = lights.len().min(MAX_LIGHTS);
+        let header = match view {
+            Some(ClusterView { view, proj, pre_rotation, extent, near, far }) => {
+                // the rotation around the view axis is moved in front of the projection,
+                // which then scales the axes of the images
+                let proj = proj * pre_rotation.transpose_sqr();
+                LightsHeader {
+                    view: pre_rotation * view,
+                    proj_scale: [proj[0][0], proj[1][1]],
+                    screen_size: [extent.width as f32, extent.height as f32],
+                    near,
+                    far,
+                    count: count as u32,
+                    clustered: 1,
+                }
+            }
+            None => LightsHeader {
+                view: Matrix4::unit(),
+                proj_scale: [1., 1.],
+                screen_size: [1., 1.],
+                near: 1.,
+                far: 2.,
+                count: count as u32,
+                clustered: 0,
+            },
+        };
+        let lights = lights[..count].iter().map(|light| GpuLight {
+            position: [light.position[0], light.position[1], light.position[2]],
+            radius: light.radius,
+            color: [light.color[0], light.color[1], light.color[2]],
+            _padding: 0.,
+        });
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.lights_size);
+            dst.cast::<LightsHeader>().write_unaligned(header);
+            let dst = dst.add(size_of::<LightsHeader>()).cast::<GpuLight>();
+            for (i, light) in lights.enumerate() {
+                dst.add(i).write_unaligned(light);
+            }
+        }
+    }
+
+    /// Records building the clusters of the frame at index `frame`.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded outside of a render pass, the clusters are written by
+    /// a compute shader once it is done.
+    pub unsafe fn record(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize) {
+        let [x, y, z] = CLUSTER_GRID;
+        unsafe {
+            device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.layout,
+                0,
+                &self.descriptor_sets[frame..=frame],
+                &[],
+            );
+            device.cmd_dispatch(buffer, (x * y * z).div_ceil(GROUP_SIZE), 1, 1);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Nothing may be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.unmap_memory(self.lights_memory);
+            device.free_memory(self.lights_memory, None);
+            device.destroy_buffer(self.lights_buffer, None);
+            device.free_memory(self.clusters_memory, None);
+            device.destroy_buffer(self.clusters_buffer, None);
+        }
+    }
+
+    fn lights_range() -> usize {
+        size_of::<LightsHeader>() + MAX_LIGHTS * size_of::<GpuLight>()
+    }
+
+    fn clusters_range() -> usize {
+        let [x, y, z] = CLUSTER_GRID;
+        (x * y * z) as usize * CLUSTER_STRIDE * size_of::<u32>()
+    }
+
+    /// Writes the lights and clusters of `frame` to the bindings `[lights, clusters]` of `set`.
+    fn write_descriptor_set(
+        &self,
+        device: &Device,
+        frame: usize,
+        set: vk::DescriptorSet,
+        [lights_binding, clusters_binding]: [u32; 2],
+    ) {
+        let lights_infos = [vk::DescriptorBufferInfo::default()
+            .buffer(self.lights_buffer)
+            .offset((frame * self.lights_size) as _)
+            .range(Self::lights_range() as _)];
+        let clusters_infos = [vk::DescriptorBufferInfo::default()
+            .buffer(self.clusters_buffer)
+            .offset((frame * self.clusters_size) as _)
+            .range(Self::clusters_range() as _)];
+        let writes = [
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(lights_binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&lights_infos),
+            vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(clusters_binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&clusters_infos),
+        ];
+        unsafe { device.update_descriptor_sets(&writes, &[]) };
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        module: vk::ShaderModule,
+        entry_point: &std::ffi::CStr,
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+        let stage_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(entry_point);
+        let pipeline_infos = [vk::ComputePipelineCreateInfo::default()
+            .stage(stage_info)
+            .layout(layout)];
+        let pipeline = unsafe {
+            device.create_compute_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+                .unwrap()[0]
+        };
+        (pipeline, layout)
+    }
+}
@@ -0,0 +1,267 @@
+use glslang::{
+    Compiler, CompilerOptions, ShaderInput, ShaderStage, SpirvVersion, Target, VulkanVersion,
+    include::{IncludeHandler, IncludeResult, IncludeType},
+};
+use std::ffi::CStr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+#[cfg(feature = "shaderc")]
+use std::sync::OnceLock;
+#[cfg(feature = "shaderc")]
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+/// A compiler that turns shader source code into SPIR-V.
+///
+/// Implementations are used from the shader compilation thread
+/// and thus must be `Send` and `Sync`.
+pub trait ShaderCompiler: Send + Sync {
+    /// A name identifying the compiler. It is part of the key of cached SPIR-V.
+    fn name(&self) -> &str;
+
+    /// The version of the compiler, it is part of the key of cached SPIR-V too, so that SPIR-V
+    /// of an older compiler is not used.
+    fn version(&self) -> String {
+        String::new()
+    }
+
+    /// Compile `source` which was loaded from `path`.
+    ///
+    /// Files included with `#include "file"` are searched relative to the including file,
+    /// see [`include_path`].
+    fn compile(
+        &self,
+        stage: ShaderStage,
+        path: &Path,
+        source: &str,
+        defines: &[(&str, Option<&str>)],
+    ) -> Result<Vec<u32>, anyhow::Error>;
+}
+
+/// The compiler of shaders that do not set one, [`Shaderc`] if the `shaderc` feature is
+/// enabled and [`Glslang`] otherwise.
+pub fn default_compiler() -> Arc<dyn ShaderCompiler> {
+    #[cfg(feature = "shaderc")]
+    return Arc::new(Shaderc);
+    #[cfg(not(feature = "shaderc"))]
+    return Arc::new(Glslang);
+}
+
+/// Returns the path of the file `header` that is included by the file at `includer`.
+pub fn include_path(includer: &Path, header: &str) -> PathBuf {
+    includer.parent().unwrap_or(Path::new("")).join(header)
+}
+
+/// Loads the files included by the shader at `path` for glslang.
+struct Includer<'a> {
+    path: &'a Path,
+}
+
+impl IncludeHandler for Includer<'_> {
+    fn include(
+        &mut self,
+        _ty: IncludeType,
+        header_name: &str,
+        includer_name: &str,
+        _include_depth: usize,
+    ) -> Option<IncludeResult> {
+        // the shader itself has no name, the included files are named by their path
+        let includer = if includer_name.is_empty() { self.path } else { Path::new(includer_name) };
+        let path = include_path(includer, header_name);
+        let data = std::fs::read_to_string(&path).ok()?;
+        Some(IncludeResult { name: path.to_string_lossy().into_owned(), data })
+    }
+}
+
+/// Whether `stage` needs at least SPIR-V 1.4.
+fn needs_spirv_1_4(stage: ShaderStage) -> bool {
+    matches!(
+        stage,
+        ShaderStage::Mesh
+            | ShaderStage::Task
+            | ShaderStage::RayGeneration
+            | ShaderStage::Intersect
+            | ShaderStage::AnyHit
+            | ShaderStage::ClosestHit
+            | ShaderStage::Miss
+            | ShaderStage::Callable
+    )
+}
+
+/// The compiler using glslang, the default unless the `shaderc` feature is enabled.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Glslang;
+
+impl ShaderCompiler for Glslang {
+    fn name(&self) -> &str {
+        "glslang"
+    }
+
+    fn version(&self) -> String {
+        let mut version = glslang_sys::glslang_version_t {
+            major: 0,
+            minor: 0,
+            patch: 0,
+            flavor: std::ptr::null(),
+        };
+        unsafe { glslang_sys::glslang_get_version(&mut version) };
+        let flavor = if version.flavor.is_null() {
+            Default::default()
+        } else {
+            unsafe { CStr::from_ptr(version.flavor) }.to_string_lossy()
+        };
+        format!("{}.{}.{}{flavor}", version.major, version.minor, version.patch)
+    }
+
+    fn compile(
+        &self,
+        stage: ShaderStage,
+        path: &Path,
+        source: &str,
+        defines: &[(&str, Option<&str>)],
+    ) -> Result<Vec<u32>, anyhow::Error> {
+        let source = source.to_owned().into();
+        let compiler = Compiler::acquire()
+            .ok_or_else(|| anyhow::anyhow!("Failed to acquire Compiler"))?;
+        let mut options = CompilerOptions::default();
+        if needs_spirv_1_4(stage) {
+            // mesh and ray tracing shaders need at least SPIR-V 1.4
+            options.target = Target::Vulkan {
+                version: VulkanVersion::Vulkan1_1,
+                spirv_version: SpirvVersion::SPIRV1_4,
+            };
+        }
+        let mut includer = Includer { path };
+        let input = ShaderInput::new(
+            &source,
+            stage,
+            &options,
+            Some(defines),
+            Some(&mut includer),
+        )?;
+        let shader = compiler.create_shader(input)?;
+        Ok(shader.compile()?)
+    }
+}
+
+/// The compiler of shaderc, run as its command line compiler `glslc`, which has to be in the
+/// `PATH`. It is the default if the `shaderc` feature is enabled.
+///
+/// Unlike glslang, shaderc optimizes the SPIR-V for speed.
+#[cfg(feature = "shaderc")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Shaderc;
+
+#[cfg(feature = "shaderc")]
+impl ShaderCompiler for Shaderc {
+    fn name(&self) -> &str {
+        "shaderc"
+    }
+
+    fn version(&self) -> String {
+        // only asked once, glslc does not change while the gallery runs
+        static VERSION: OnceLock<String> = OnceLock::new();
+        VERSION.get_or_init(|| {
+            Command::new("glslc")
+                .arg("--version")
+                .output()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+                .unwrap_or_default()
+        }).clone()
+    }
+
+    fn compile(
+        &self,
+        stage: ShaderStage,
+        path: &Path,
+        source: &str,
+        defines: &[(&str, Option<&str>)],
+    ) -> Result<Vec<u32>, anyhow::Error> {
+        let (target_env, target_spv) = if needs_spirv_1_4(stage) {
+            ("vulkan1.1", "spv1.4")
+        } else {
+            ("vulkan1.0", "spv1.0")
+        };
+        let mut command = Command::new("glslc");
+        command
+            .arg(format!("-fshader-stage={}", shaderc_stage(stage)))
+            .arg(format!("--target-env={target_env}"))
+            .arg(format!("--target-spv={target_spv}"))
+            .arg("-O");
+        if let Some(dir) = path.parent() {
+            command.arg("-I").arg(dir);
+        }
+        for (name, value) in defines {
+            match value {
+                Some(value) => command.arg(format!("-D{name}={value}")),
+                None => command.arg(format!("-D{name}")),
+            };
+        }
+        // the source is read from stdin, so that it is the same that is cached
+        let mut child = command
+            .args(["-o", "-", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| anyhow::anyhow!("Failed to start glslc, is shaderc installed? {err}"))?;
+        let mut stdin = child.stdin.take().unwrap();
+        let source = source.to_owned();
+        // write on another thread, glslc may write its output before it read everything
+        let writer = std::thread::spawn(move || stdin.write_all(source.as_bytes()));
+        let output = child.wait_with_output()?;
+        writer.join().map_err(|_| anyhow::anyhow!("Failed to write the source to glslc"))??;
+        if !output.status.success() {
+            // glslc calls the source read from stdin <stdin>
+            let file = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+            let errors = String::from_utf8_lossy(&output.stderr).replace("<stdin>", &file);
+            anyhow::bail!("{}", errors.trim());
+        }
+        anyhow::ensure!(output.stdout.len() % 4 == 0, "glslc wrote invalid SPIR-V");
+        Ok(output.stdout.chunks_exact(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect())
+    }
+}
+
+/// The name of `stage` in the `-fshader-stage` option of glslc.
+#[cfg(feature = "shaderc")]
+fn shaderc_stage(stage: ShaderStage) -> &'static str {
+    match stage {
+        ShaderStage::Vertex => "vert",
+        ShaderStage::TesselationControl => "tesc",
+        ShaderStage::TesselationEvaluation => "tese",
+        ShaderStage::Geometry => "geom",
+        ShaderStage::Fragment => "frag",
+        ShaderStage::Compute => "comp",
+        ShaderStage::RayGeneration => "rgen",
+        ShaderStage::Intersect => "rint",
+        ShaderStage::AnyHit => "rahit",
+        ShaderStage::ClosestHit => "rchit",
+        ShaderStage::Miss => "rmiss",
+        ShaderStage::Callable => "rcall",
+        ShaderStage::Task => "task",
+        ShaderStage::Mesh => "mesh",
+    }
+}
+
+#[cfg(all(test, feature = "shaderc"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_with_shaderc() {
+        let path = Path::new("color.frag");
+        let source = "#version 450
+layout(location = 0) out vec4 outColor;
+void main() { outColor = vec4(COLOR); }
+";
+        let stage = ShaderStage::Fragment;
+        let code = Shaderc.compile(stage, path, source, &[("COLOR", Some("1.0"))]).unwrap();
+        assert_eq!(code[0], 0x0723_0203);
+        let err = Shaderc.compile(stage, path, source, &[]).unwrap_err();
+        assert!(err.to_string().contains("color.frag:3"), "{err}");
+    }
+}
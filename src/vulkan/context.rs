@@ -1,13 +1,117 @@
-use super::debug::setup_debug_messenger;
+use super::debug::{setup_debug_messenger, Validation};
+use super::raytracing::RayTracing;
+use super::sampler::{SamplerCache, SamplerConfig};
 use super::swapchain::SwapchainSupportDetails;
 
 use anyhow::anyhow;
 use ash::{
-    ext::debug_utils,
-    khr::{surface, swapchain as khr_swapchain},
+    amd::buffer_marker,
+    ext::{
+        debug_utils, descriptor_indexing, full_screen_exclusive, memory_budget, mesh_shader,
+        pipeline_creation_feedback, swapchain_maintenance1,
+    },
+    khr::{
+        acceleration_structure, buffer_device_address, create_renderpass2,
+        deferred_host_operations, driver_properties, fragment_shading_rate,
+        pipeline_executable_properties, ray_tracing_pipeline, shader_float_controls,
+        shader_non_semantic_info, spirv_1_4, surface, synchronization2,
+        swapchain as khr_swapchain,
+    },
+    nv::device_diagnostic_checkpoints,
+    prelude::VkResult,
     vk, Device, Entry, Instance
 };
-use std::ffi::CStr;
+use std::{ffi::{CStr, CString}, str::FromStr, sync::Mutex};
+
+/// Which GPU to use, see [`list_gpus`] for the available ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum GpuSelection {
+    /// The first suitable GPU, discrete ones are preferred over integrated ones.
+    #[default]
+    Auto,
+    /// The GPU at the index in the list of all GPUs.
+    Index(usize),
+    /// The first GPU whose name contains the string, ignoring the case.
+    Name(String),
+}
+
+impl GpuSelection {
+    fn matches(&self, index: usize, name: &str) -> bool {
+        match self {
+            Self::Auto => true,
+            Self::Index(selected) => *selected == index,
+            Self::Name(selected) => name.to_lowercase().contains(&selected.to_lowercase()),
+        }
+    }
+}
+
+impl FromStr for GpuSelection {
+    type Err = std::convert::Infallible;
+
+    /// Parses an index or otherwise a part of a name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(s.parse().map_or_else(|_| Self::Name(s.to_owned()), Self::Index))
+    }
+}
+
+/// What another API rendering with the device needs from it, e.g. an OpenXR runtime.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRequirements {
+    /// The GPU that has to be used regardless of the [`GpuSelection`].
+    pub physical_device: Option<vk::PhysicalDevice>,
+    /// Device extensions enabled in addition to the ones of the gallery.
+    pub extensions: Vec<CString>,
+}
+
+/// The names of all GPUs with their type and Vulkan version in the order used by
+/// [`GpuSelection::Index`].
+pub fn list_gpus() -> Result<Vec<String>, anyhow::Error> {
+    let entry = unsafe { Entry::load()? };
+    let app_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_0);
+    let mut extension_names = Vec::new();
+    let mut create_flags = vk::InstanceCreateFlags::default();
+    if cfg!(any(target_os = "macos", target_os = "ios")) {
+        extension_names.push(ash::khr::portability_enumeration::NAME.as_ptr());
+        create_flags |= vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR;
+    }
+    let create_info = vk::InstanceCreateInfo::default()
+        .application_info(&app_info)
+        .enabled_extension_names(&extension_names)
+        .flags(create_flags);
+    let instance = unsafe { entry.create_instance(&create_info, None)? };
+    let devices = unsafe { instance.enumerate_physical_devices() };
+    let gpus = devices.map(|devices| {
+        devices.into_iter().map(|device| {
+            let props = unsafe { instance.get_physical_device_properties(device) };
+            format!(
+                "{} ({:?}, Vulkan {}.{}.{})",
+                props.device_name_as_c_str().unwrap_or_default().to_string_lossy(),
+                props.device_type,
+                vk::api_version_major(props.api_version),
+                vk::api_version_minor(props.api_version),
+                vk::api_version_patch(props.api_version),
+            )
+        }).collect()
+    });
+    unsafe { instance.destroy_instance(None) };
+    Ok(gpus?)
+}
+
+/// Optional instance extensions that optional device extensions depend on.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstanceExtensions {
+    /// `VK_KHR_get_surface_capabilities2`
+    pub surface_capabilities2: bool,
+    /// `VK_EXT_surface_maintenance1`, which also needs `surface_capabilities2`.
+    pub surface_maintenance: bool,
+}
+
+/// Where the commands of `VK_KHR_synchronization2` come from.
+enum Synchronization2 {
+    /// Vulkan 1.3, the commands are loaded with the device.
+    Core,
+    Extension(synchronization2::Device),
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct QueueFamiliesIndices {
@@ -16,47 +120,203 @@ pub struct QueueFamiliesIndices {
 }
 
 pub struct VkContext {
-    _entry: Entry,
+    entry: Entry,
     instance: Instance,
     debug_report_callback: Option<(debug_utils::Instance, vk::DebugUtilsMessengerEXT)>,
     surface: surface::Instance,
     surface_khr: vk::SurfaceKHR,
     physical_device: vk::PhysicalDevice,
     device: Device,
+    enabled_features: vk::PhysicalDeviceFeatures,
+    mesh_shader: Option<mesh_shader::Device>,
+    ray_tracing: Option<RayTracing>,
+    fragment_shading_rate: Option<fragment_shading_rate::Device>,
+    swapchain_maintenance: bool,
+    /// Whether several views can be rendered in one render pass, which stereo rendering needs.
+    multiview: bool,
+    full_screen_exclusive: Option<full_screen_exclusive::Device>,
+    /// Barriers and submits fall back to Vulkan 1.0 without it,
+    /// see [`Self::cmd_pipeline_barrier2`].
+    synchronization2: Option<Synchronization2>,
+    /// Only one of the extensions for crash markers is enabled, see [`Self::crash_markers`].
+    diagnostic_checkpoints: Option<device_diagnostic_checkpoints::Device>,
+    buffer_marker: Option<buffer_marker::Device>,
+    /// Whether `VK_EXT_pipeline_creation_feedback` is enabled or in the core of Vulkan 1.3.
+    pipeline_creation_feedback: bool,
+    /// Only enabled in debug builds, capturing the statistics may slow down the compilation.
+    pipeline_executable_properties: Option<pipeline_executable_properties::Device>,
+    /// Whether `VK_EXT_memory_budget` is enabled, see [`Self::vram_usage`].
+    memory_budget: bool,
+    /// Whether the driver can be queried, see [`Self::driver_info`].
+    driver_properties: bool,
     queue_families_indices: QueueFamiliesIndices,
+    samplers: Mutex<SamplerCache>,
 }
 
 impl VkContext {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         entry: Entry,
         instance: Instance,
         surface: surface::Instance,
         surface_khr: vk::SurfaceKHR,
+        instance_extensions: InstanceExtensions,
+        validation: Validation,
+        gpu: &GpuSelection,
+        requirements: &DeviceRequirements,
     ) -> Result<Self, anyhow::Error> {
-        let debug_report_callback = setup_debug_messenger(&entry, &instance);
+        let debug_report_callback = setup_debug_messenger(&entry, &instance, validation);
 
-        let (physical_device, queue_families_indices) =
-            Self::pick_physical_device(&instance, &surface, surface_khr)
-            .ok_or(anyhow!("No suitable physical device found"))?;
+        let (physical_device, queue_families_indices) = Self::pick_physical_device(
+            &instance,
+            &surface,
+            surface_khr,
+            gpu,
+            requirements.physical_device,
+        )
+        .ok_or(anyhow!("No suitable physical device found for {gpu:?}"))?;
+
+        let instance_version = unsafe { entry.try_enumerate_instance_version() }
+            .ok()
+            .flatten()
+            .unwrap_or(vk::API_VERSION_1_0);
+        let device_version =
+            unsafe { instance.get_physical_device_properties(physical_device) }.api_version;
+        let is_vulkan_1_1 = instance_version.min(device_version) >= vk::API_VERSION_1_1;
+        let is_vulkan_1_3 = instance_version.min(device_version) >= vk::API_VERSION_1_3;
+        // without it the single view shaders are used and stereo rendering is disabled
+        let supports_multiview =
+            is_vulkan_1_1 && Self::get_multiview_support(&instance, physical_device);
+        if !supports_multiview {
+            log::warn!("Multiview not supported, stereo rendering is disabled");
+        }
+        let mesh_shader_features = if is_vulkan_1_1 {
+            Self::get_mesh_shader_features(&instance, physical_device)
+        } else {
+            None
+        };
+        let supports_ray_tracing =
+            is_vulkan_1_1 && Self::get_ray_tracing_support(&instance, physical_device);
+        let supports_fragment_shading_rate = is_vulkan_1_1
+            && Self::get_fragment_shading_rate_support(&instance, physical_device);
+        let supports_swapchain_maintenance = instance_extensions.surface_maintenance
+            && Self::get_swapchain_maintenance_support(&instance, physical_device);
+        // exclusive fullscreen is only available on Windows
+        let supports_full_screen_exclusive = cfg!(windows)
+            && instance_extensions.surface_capabilities2
+            && Self::check_extensions_support(
+                &instance,
+                physical_device,
+                &[full_screen_exclusive::NAME],
+            );
+        let supports_synchronization2 =
+            Self::get_synchronization2_support(&instance, physical_device, is_vulkan_1_3);
+        let supports_memory_budget =
+            Self::check_extensions_support(&instance, physical_device, &[memory_budget::NAME]);
+        // both only tell what the GPU was doing when the device is lost
+        let supports_diagnostic_checkpoints = Self::check_extensions_support(
+            &instance,
+            physical_device,
+            &[device_diagnostic_checkpoints::NAME],
+        );
+        let supports_buffer_marker = !supports_diagnostic_checkpoints
+            && Self::check_extensions_support(&instance, physical_device, &[buffer_marker::NAME]);
+        let supports_pipeline_creation_feedback = is_vulkan_1_3
+            || Self::check_extensions_support(
+                &instance,
+                physical_device,
+                &[pipeline_creation_feedback::NAME],
+            );
+        // the printf of the shaders is a non-semantic instruction, which is core in Vulkan 1.3
+        let supports_non_semantic_info = validation.printf && !is_vulkan_1_3
+            && Self::check_extensions_support(
+                &instance,
+                physical_device,
+                &[shader_non_semantic_info::NAME],
+            );
+        if validation.printf && !is_vulkan_1_3 && !supports_non_semantic_info {
+            log::warn!("Shaders can not print, VK_KHR_shader_non_semantic_info is not supported");
+        }
+        let supports_pipeline_executable_properties = cfg!(debug_assertions)
+            && Self::get_pipeline_executable_properties_support(&instance, physical_device);
+        let supports_driver_properties = device_version >= vk::API_VERSION_1_2
+            || Self::check_extensions_support(
+                &instance,
+                physical_device,
+                &[driver_properties::NAME],
+            );
 
-        let device = Self::create_logical_device(
+        let (device, enabled_features) = Self::create_logical_device(
             &instance,
             physical_device,
             queue_families_indices,
+            mesh_shader_features,
+            supports_ray_tracing,
+            supports_fragment_shading_rate,
+            supports_swapchain_maintenance,
+            supports_multiview,
+            supports_full_screen_exclusive,
+            supports_synchronization2,
+            is_vulkan_1_3,
+            supports_diagnostic_checkpoints,
+            supports_buffer_marker,
+            supports_pipeline_creation_feedback,
+            supports_pipeline_executable_properties,
+            supports_non_semantic_info,
+            supports_memory_budget,
+            &requirements.extensions,
         )?;
+        let mesh_shader =
+            mesh_shader_features.map(|_| mesh_shader::Device::new(&instance, &device));
+        let ray_tracing = supports_ray_tracing
+            .then(|| RayTracing::new(&instance, physical_device, &device));
+        let fragment_shading_rate = supports_fragment_shading_rate
+            .then(|| fragment_shading_rate::Device::new(&instance, &device));
+        let full_screen_exclusive = supports_full_screen_exclusive
+            .then(|| full_screen_exclusive::Device::new(&instance, &device));
+        let diagnostic_checkpoints = supports_diagnostic_checkpoints
+            .then(|| device_diagnostic_checkpoints::Device::new(&instance, &device));
+        let buffer_marker = supports_buffer_marker
+            .then(|| buffer_marker::Device::new(&instance, &device));
+        let pipeline_executable_properties = supports_pipeline_executable_properties
+            .then(|| pipeline_executable_properties::Device::new(&instance, &device));
+        let synchronization2 = supports_synchronization2.then(|| if is_vulkan_1_3 {
+            Synchronization2::Core
+        } else {
+            Synchronization2::Extension(synchronization2::Device::new(&instance, &device))
+        });
 
         Ok(VkContext {
-            _entry: entry,
+            entry,
             instance,
             debug_report_callback,
             surface,
             surface_khr,
             physical_device,
             device,
+            enabled_features,
+            mesh_shader,
+            ray_tracing,
+            fragment_shading_rate,
+            swapchain_maintenance: supports_swapchain_maintenance,
+            multiview: supports_multiview,
+            full_screen_exclusive,
+            synchronization2,
+            diagnostic_checkpoints,
+            buffer_marker,
+            pipeline_creation_feedback: supports_pipeline_creation_feedback,
+            pipeline_executable_properties,
+            memory_budget: supports_memory_budget,
+            driver_properties: supports_driver_properties,
             queue_families_indices,
+            samplers: Default::default(),
         })
     }
 
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
     pub fn instance(&self) -> &Instance {
         &self.instance
     }
@@ -77,6 +337,185 @@ impl VkContext {
         &self.device
     }
 
+    /// The features enabled on the logical device.
+    ///
+    /// Required features are always enabled, optional ones only if they are supported.
+    pub fn enabled_features(&self) -> vk::PhysicalDeviceFeatures {
+        self.enabled_features
+    }
+
+    /// The loader for `VK_EXT_mesh_shader` if the extension is enabled.
+    pub fn mesh_shader(&self) -> Option<&mesh_shader::Device> {
+        self.mesh_shader.as_ref()
+    }
+
+    /// The loaders needed for hardware ray tracing if the extensions are enabled.
+    pub fn ray_tracing(&self) -> Option<&RayTracing> {
+        self.ray_tracing.as_ref()
+    }
+
+    /// The loader for `VK_KHR_fragment_shading_rate` if per pipeline shading rates are enabled.
+    pub fn fragment_shading_rate(&self) -> Option<&fragment_shading_rate::Device> {
+        self.fragment_shading_rate.as_ref()
+    }
+
+    /// Whether `VK_EXT_swapchain_maintenance1` is enabled, which allows to pass a fence
+    /// when presenting that is signaled once the presentation engine is done with the image.
+    pub fn swapchain_maintenance(&self) -> bool {
+        self.swapchain_maintenance
+    }
+
+    /// Whether the multiview feature is enabled, which allows to render both eyes in one pass.
+    pub fn multiview(&self) -> bool {
+        self.multiview
+    }
+
+    /// The loader for `VK_EXT_full_screen_exclusive` if exclusive fullscreen is supported.
+    pub fn full_screen_exclusive(&self) -> Option<&full_screen_exclusive::Device> {
+        self.full_screen_exclusive.as_ref()
+    }
+
+    /// The loaders of `VK_NV_device_diagnostic_checkpoints` and `VK_AMD_buffer_marker`,
+    /// at most one of them is enabled.
+    pub fn crash_markers(
+        &self,
+    ) -> (Option<&device_diagnostic_checkpoints::Device>, Option<&buffer_marker::Device>) {
+        (self.diagnostic_checkpoints.as_ref(), self.buffer_marker.as_ref())
+    }
+
+    /// Whether the time it took to create a pipeline can be queried when creating it.
+    pub fn pipeline_creation_feedback(&self) -> bool {
+        self.pipeline_creation_feedback
+    }
+
+    /// The loader for `VK_KHR_pipeline_executable_properties` in debug builds if supported.
+    pub fn pipeline_executable_properties(
+        &self,
+    ) -> Option<&pipeline_executable_properties::Device> {
+        self.pipeline_executable_properties.as_ref()
+    }
+
+    /// Whether `VK_KHR_synchronization2` is enabled, either as an extension or by Vulkan 1.3.
+    pub fn synchronization2(&self) -> bool {
+        self.synchronization2.is_some()
+    }
+
+    /// Records a pipeline barrier with `vkCmdPipelineBarrier2` if synchronization2 is enabled.
+    ///
+    /// Otherwise the barriers are recorded with `vkCmdPipelineBarrier`, so only the stages and
+    /// accesses of Vulkan 1.0 may be used. The stages of all barriers are merged then.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be recording.
+    pub unsafe fn cmd_pipeline_barrier2(
+        &self,
+        buffer: vk::CommandBuffer,
+        memory_barriers: &[vk::MemoryBarrier2],
+        image_barriers: &[vk::ImageMemoryBarrier2],
+    ) {
+        let dependency_info = vk::DependencyInfo::default()
+            .memory_barriers(memory_barriers)
+            .image_memory_barriers(image_barriers);
+        match self.synchronization2.as_ref() {
+            Some(Synchronization2::Core) => unsafe {
+                self.device.cmd_pipeline_barrier2(buffer, &dependency_info)
+            },
+            Some(Synchronization2::Extension(synchronization2)) => unsafe {
+                synchronization2.cmd_pipeline_barrier2(buffer, &dependency_info)
+            },
+            None => {
+                let stages = memory_barriers.iter()
+                    .map(|barrier| (barrier.src_stage_mask, barrier.dst_stage_mask))
+                    .chain(image_barriers.iter()
+                        .map(|barrier| (barrier.src_stage_mask, barrier.dst_stage_mask)))
+                    .fold(Default::default(), |(src, dst), (barrier_src, barrier_dst)| {
+                        (src | barrier_src, dst | barrier_dst)
+                    });
+                let memory_barriers = memory_barriers.iter().map(|barrier| {
+                    vk::MemoryBarrier::default()
+                        .src_access_mask(legacy_access(barrier.src_access_mask))
+                        .dst_access_mask(legacy_access(barrier.dst_access_mask))
+                }).collect::<Vec<_>>();
+                let image_barriers = image_barriers.iter().map(|barrier| {
+                    vk::ImageMemoryBarrier::default()
+                        .src_access_mask(legacy_access(barrier.src_access_mask))
+                        .dst_access_mask(legacy_access(barrier.dst_access_mask))
+                        .old_layout(barrier.old_layout)
+                        .new_layout(barrier.new_layout)
+                        .src_queue_family_index(barrier.src_queue_family_index)
+                        .dst_queue_family_index(barrier.dst_queue_family_index)
+                        .image(barrier.image)
+                        .subresource_range(barrier.subresource_range)
+                }).collect::<Vec<_>>();
+                unsafe {
+                    self.device.cmd_pipeline_barrier(
+                        buffer,
+                        legacy_stages(stages.0, vk::PipelineStageFlags::TOP_OF_PIPE),
+                        legacy_stages(stages.1, vk::PipelineStageFlags::BOTTOM_OF_PIPE),
+                        vk::DependencyFlags::empty(),
+                        &memory_barriers,
+                        &[],
+                        &image_barriers,
+                    )
+                }
+            }
+        }
+    }
+
+    /// Submits `command_buffers` to `queue`, which wait for each semaphore at its stages,
+    /// with `vkQueueSubmit2` if synchronization2 is enabled.
+    ///
+    /// Without it only the stages of Vulkan 1.0 may be used.
+    ///
+    /// # Safety
+    ///
+    /// The command buffers must be recorded and everything they use must be alive.
+    pub unsafe fn queue_submit2(
+        &self,
+        queue: vk::Queue,
+        wait_semaphores: &[(vk::Semaphore, vk::PipelineStageFlags2)],
+        command_buffers: &[vk::CommandBuffer],
+        signal_semaphores: &[vk::Semaphore],
+        fence: vk::Fence,
+    ) -> VkResult<()> {
+        if self.synchronization2.is_none() {
+            let (wait_semaphores, wait_stages): (Vec<_>, Vec<_>) = wait_semaphores.iter()
+                .map(|&(semaphore, stages)| {
+                    (semaphore, legacy_stages(stages, vk::PipelineStageFlags::TOP_OF_PIPE))
+                })
+                .unzip();
+            let submit_info = vk::SubmitInfo::default()
+                .wait_semaphores(&wait_semaphores)
+                .wait_dst_stage_mask(&wait_stages)
+                .command_buffers(command_buffers)
+                .signal_semaphores(signal_semaphores);
+            return unsafe { self.device.queue_submit(queue, &[submit_info], fence) };
+        }
+        let wait_infos = wait_semaphores.iter().map(|&(semaphore, stages)| {
+            vk::SemaphoreSubmitInfo::default().semaphore(semaphore).stage_mask(stages)
+        }).collect::<Vec<_>>();
+        let command_buffer_infos = command_buffers.iter().map(|&buffer| {
+            vk::CommandBufferSubmitInfo::default().command_buffer(buffer)
+        }).collect::<Vec<_>>();
+        // the semaphores are signaled once all commands are done
+        let signal_infos = signal_semaphores.iter().map(|&semaphore| {
+            vk::SemaphoreSubmitInfo::default()
+                .semaphore(semaphore)
+                .stage_mask(vk::PipelineStageFlags2::ALL_COMMANDS)
+        }).collect::<Vec<_>>();
+        let submit_info = vk::SubmitInfo2::default()
+            .wait_semaphore_infos(&wait_infos)
+            .command_buffer_infos(&command_buffer_infos)
+            .signal_semaphore_infos(&signal_infos);
+        match self.synchronization2.as_ref() {
+            Some(Synchronization2::Extension(synchronization2)) => unsafe {
+                synchronization2.queue_submit2(queue, &[submit_info], fence)
+            },
+            _ => unsafe { self.device.queue_submit2(queue, &[submit_info], fence) },
+        }
+    }
+
     pub fn graphics_queue_index(&self) -> u32 {
         self.queue_families_indices.graphics_index
     }
@@ -91,6 +530,62 @@ impl VkContext {
         }
     }
 
+    /// Returns the sampler for `config`, creating it on first use.
+    /// The sampler lives as long as the context.
+    pub fn sampler(&self, config: SamplerConfig) -> Result<vk::Sampler, anyhow::Error> {
+        let max_anisotropy = if self.enabled_features.sampler_anisotropy == vk::TRUE {
+            self.physical_device_properties().limits.max_sampler_anisotropy
+        } else {
+            1.
+        };
+        self.samplers.lock().unwrap().get(&self.device, max_anisotropy, config)
+    }
+
+    /// The name and the version of the driver as it reports them,
+    /// `None` if the device can not tell.
+    pub fn driver_info(&self) -> Option<String> {
+        if !self.driver_properties {
+            return None;
+        }
+        let mut driver = vk::PhysicalDeviceDriverProperties::default();
+        let mut props = vk::PhysicalDeviceProperties2::default().push_next(&mut driver);
+        unsafe {
+            self.instance.get_physical_device_properties2(self.physical_device, &mut props);
+        }
+        let name = driver.driver_name_as_c_str().unwrap_or_default().to_string_lossy();
+        let info = driver.driver_info_as_c_str().unwrap_or_default().to_string_lossy();
+        Some(format!("{name} {info}").trim().to_owned())
+    }
+
+    /// The bytes of device local memory used by this process and how many it may use,
+    /// summed over all device local heaps.
+    ///
+    /// Without `VK_EXT_memory_budget` the usage is unknown and the budget is the size
+    /// of the heaps.
+    pub fn vram_usage(&self) -> (Option<u64>, u64) {
+        let mut budget = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut props = vk::PhysicalDeviceMemoryProperties2::default();
+        if self.memory_budget {
+            props = props.push_next(&mut budget);
+        }
+        unsafe {
+            self.instance.get_physical_device_memory_properties2(self.physical_device, &mut props);
+        }
+        let mem_properties = props.memory_properties;
+        let heaps = mem_properties.memory_heaps_as_slice()
+            .iter()
+            .enumerate()
+            .filter(|(_, heap)| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|(index, heap)| (index, heap.size))
+            .collect::<Vec<_>>();
+        if !self.memory_budget {
+            return (None, heaps.iter().map(|(_, size)| size).sum());
+        }
+        let used = heaps.iter().map(|&(index, _)| budget.heap_usage[index]).sum();
+        let available = heaps.iter().map(|&(index, _)| budget.heap_budget[index]).sum();
+        (Some(used), available)
+    }
+
     pub fn get_mem_properties(&self) -> vk::PhysicalDeviceMemoryProperties {
         unsafe {
             self.instance.get_physical_device_memory_properties(self.physical_device)
@@ -186,11 +681,22 @@ impl VkContext {
         instance: &Instance,
         surface: &surface::Instance,
         surface_khr: vk::SurfaceKHR,
+        gpu: &GpuSelection,
+        required: Option<vk::PhysicalDevice>,
     ) -> Option<(vk::PhysicalDevice, QueueFamiliesIndices)> {
         let devices = unsafe { instance.enumerate_physical_devices().ok()? };
         let (device, _, queue_families_indices) = devices
             .into_iter()
-            .filter_map(|device| {
+            .enumerate()
+            .filter(|&(index, device)| {
+                if let Some(required) = required {
+                    return device == required;
+                }
+                let props = unsafe { instance.get_physical_device_properties(device) };
+                let name = props.device_name_as_c_str().unwrap_or_default().to_string_lossy();
+                gpu.matches(index, &name)
+            })
+            .filter_map(|(_, device)| {
                 if !Self::check_device_extension_support(instance, device) {
                     return None;
                 }
@@ -200,13 +706,6 @@ impl VkContext {
                     return None;
                 }
 
-                let features = unsafe { instance.get_physical_device_features(device) };
-                if features.sampler_anisotropy != vk::TRUE
-                    || features.geometry_shader != vk::TRUE
-                {
-                    return None;
-                }
-
                 let props = unsafe { instance.get_physical_device_properties(device) };
                 let priority = match props.device_type {
                     vk::PhysicalDeviceType::DISCRETE_GPU => 0,
@@ -229,11 +728,31 @@ impl VkContext {
     }
 
     /// Create the logical device to interact with the physical `device`.
+    ///
+    /// # Returns
+    ///
+    /// The device and the features that were enabled on it.
+    #[allow(clippy::too_many_arguments)]
     fn create_logical_device(
         instance: &Instance,
         device: vk::PhysicalDevice,
         queue_families_indices: QueueFamiliesIndices,
-    ) -> Result<Device, anyhow::Error> {
+        mesh_shader_features: Option<(bool, bool)>,
+        ray_tracing: bool,
+        fragment_shading_rate: bool,
+        swapchain_maintenance: bool,
+        multiview: bool,
+        full_screen_exclusive: bool,
+        synchronization2: bool,
+        is_vulkan_1_3: bool,
+        diagnostic_checkpoints: bool,
+        buffer_marker: bool,
+        pipeline_creation_feedback: bool,
+        pipeline_executable_properties: bool,
+        non_semantic_info: bool,
+        memory_budget: bool,
+        extra_extensions: &[CString],
+    ) -> Result<(Device, vk::PhysicalDeviceFeatures), anyhow::Error> {
         let graphics_family_index = queue_families_indices.graphics_index;
         let present_family_index = queue_families_indices.present_index;
         let queue_priorities = [1.0f32];
@@ -256,40 +775,346 @@ impl VkContext {
                 .collect::<Vec<_>>()
         };
 
-        let device_extensions = Self::get_required_device_extensions();
+        let mut device_extensions = Self::get_required_device_extensions().to_vec();
+        if mesh_shader_features.is_some() {
+            device_extensions.extend(Self::get_mesh_shader_device_extensions());
+        }
+        if ray_tracing {
+            device_extensions.extend(Self::get_ray_tracing_device_extensions());
+        }
+        if fragment_shading_rate {
+            device_extensions.extend(Self::get_fragment_shading_rate_device_extensions());
+        }
+        if swapchain_maintenance {
+            device_extensions.push(swapchain_maintenance1::NAME);
+        }
+        if full_screen_exclusive {
+            device_extensions.push(full_screen_exclusive::NAME);
+        }
+        if synchronization2 && !is_vulkan_1_3 {
+            device_extensions.push(synchronization2::NAME);
+        }
+        if diagnostic_checkpoints {
+            device_extensions.push(device_diagnostic_checkpoints::NAME);
+        }
+        if buffer_marker {
+            device_extensions.push(buffer_marker::NAME);
+        }
+        // the feedback is in the core of Vulkan 1.3
+        if pipeline_creation_feedback && !is_vulkan_1_3 {
+            device_extensions.push(pipeline_creation_feedback::NAME);
+        }
+        if pipeline_executable_properties {
+            device_extensions.push(pipeline_executable_properties::NAME);
+        }
+        if non_semantic_info {
+            device_extensions.push(shader_non_semantic_info::NAME);
+        }
+        if memory_budget {
+            device_extensions.push(memory_budget::NAME);
+        }
+        device_extensions.extend(extra_extensions.iter().map(CString::as_c_str));
+        // optional features can share extensions
+        device_extensions.sort();
+        device_extensions.dedup();
         let device_extensions_ptrs = device_extensions.iter()
             .map(|ext| ext.as_ptr())
             .collect::<Vec<_>>();
 
-        let device_features = vk::PhysicalDeviceFeatures::default()
-            .geometry_shader(true)
-            .sampler_anisotropy(true);
+        let supported_features = unsafe { instance.get_physical_device_features(device) };
+        let device_features = Self::negotiate_features(supported_features);
+
+        let (mesh, task) = mesh_shader_features.unwrap_or_default();
+        let mut mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default()
+            .mesh_shader(mesh)
+            .task_shader(task);
+        log::debug!("Mesh shaders supported: {mesh}, task shaders supported: {task}");
+
+        let mut buffer_device_address_features =
+            vk::PhysicalDeviceBufferDeviceAddressFeatures::default().buffer_device_address(true);
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default()
+                .acceleration_structure(true);
+        let mut ray_tracing_pipeline_features =
+            vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default()
+                .ray_tracing_pipeline(true);
+        log::debug!("Ray tracing supported: {ray_tracing}");
 
-        let device_create_info = vk::DeviceCreateInfo::default()
+        let mut fragment_shading_rate_features =
+            vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::default()
+                .pipeline_fragment_shading_rate(true);
+        log::debug!("Fragment shading rate supported: {fragment_shading_rate}");
+
+        let mut swapchain_maintenance_features =
+            vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::default()
+                .swapchain_maintenance1(true);
+        log::debug!("Swapchain maintenance supported: {swapchain_maintenance}");
+        log::debug!("Exclusive fullscreen supported: {full_screen_exclusive}");
+        log::debug!("Memory budget supported: {memory_budget}");
+        log::debug!(
+            "Diagnostic checkpoints supported: {diagnostic_checkpoints}, \
+            buffer markers supported: {buffer_marker}",
+        );
+        let mut executable_properties_features =
+            vk::PhysicalDevicePipelineExecutablePropertiesFeaturesKHR::default()
+                .pipeline_executable_info(true);
+        log::debug!(
+            "Pipeline creation feedback supported: {pipeline_creation_feedback}, \
+            executable properties enabled: {pipeline_executable_properties}",
+        );
+        let mut synchronization2_features =
+            vk::PhysicalDeviceSynchronization2Features::default().synchronization2(true);
+        log::debug!("Synchronization2 supported: {synchronization2}, Vulkan 1.3: {is_vulkan_1_3}");
+
+        let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default().multiview(true);
+
+        let mut device_create_info = vk::DeviceCreateInfo::default()
             .queue_create_infos(&queue_create_infos)
             .enabled_extension_names(&device_extensions_ptrs)
             .enabled_features(&device_features);
+        if multiview {
+            device_create_info = device_create_info.push_next(&mut multiview_features);
+        }
+        if mesh {
+            device_create_info = device_create_info.push_next(&mut mesh_shader_features);
+        }
+        if ray_tracing {
+            device_create_info = device_create_info
+                .push_next(&mut buffer_device_address_features)
+                .push_next(&mut acceleration_structure_features)
+                .push_next(&mut ray_tracing_pipeline_features);
+        }
+        if fragment_shading_rate {
+            device_create_info = device_create_info.push_next(&mut fragment_shading_rate_features);
+        }
+        if swapchain_maintenance {
+            device_create_info = device_create_info.push_next(&mut swapchain_maintenance_features);
+        }
+        if synchronization2 {
+            device_create_info = device_create_info.push_next(&mut synchronization2_features);
+        }
+        if pipeline_executable_properties {
+            device_create_info = device_create_info.push_next(&mut executable_properties_features);
+        }
 
         // Build device
         let device = unsafe {
             instance.create_device(device, &device_create_info, None)?
         };
-        Ok(device)
+        Ok((device, device_features))
+    }
+
+    /// Enables the optional core features that are in `supported`.
+    ///
+    /// None of them is required, the functionality depending on a missing feature is disabled
+    /// by checking [`Self::enabled_features`] instead.
+    fn negotiate_features(supported: vk::PhysicalDeviceFeatures) -> vk::PhysicalDeviceFeatures {
+        let enable = |feature: vk::Bool32, name: &str, disabled: &str| {
+            let enabled = feature == vk::TRUE;
+            if enabled {
+                log::debug!("{name} supported");
+            } else {
+                log::warn!("{name} not supported, {disabled}");
+            }
+            enabled
+        };
+        vk::PhysicalDeviceFeatures::default()
+            .geometry_shader(enable(
+                supported.geometry_shader,
+                "Geometry shaders",
+                "art cannot use them",
+            ))
+            .sampler_anisotropy(enable(
+                supported.sampler_anisotropy,
+                "Anisotropic filtering",
+                "textures are filtered without it",
+            ))
+            .tessellation_shader(enable(
+                supported.tessellation_shader,
+                "Tessellation shaders",
+                "art with tessellation shaders is skipped",
+            ))
+            .sample_rate_shading(enable(
+                supported.sample_rate_shading,
+                "Sample shading",
+                "art is shaded per pixel",
+            ))
     }
 
     fn check_device_extension_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+        Self::check_extensions_support(instance, device, &Self::get_required_device_extensions())
+    }
+
+    fn check_extensions_support(
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+        extensions: &[&CStr],
+    ) -> bool {
         let extension_props = unsafe {
             instance.enumerate_device_extension_properties(device).unwrap()
         };
 
-        Self::get_required_device_extensions().into_iter().all(|required_ext| {
+        extensions.iter().all(|required_ext| {
             extension_props.iter().any(|ext| {
                 let name = unsafe { CStr::from_ptr(ext.extension_name.as_ptr()) };
-                required_ext == name
+                *required_ext == name
             })
         })
     }
 
+    /// Query if rendering to several views in one render pass is supported.
+    ///
+    /// Requires Vulkan 1.1 for the instance and the device, where multiview is part of the core.
+    fn get_multiview_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+        let mut multiview_features = vk::PhysicalDeviceMultiviewFeatures::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut multiview_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        multiview_features.multiview == vk::TRUE
+    }
+
+    /// Extensions needed for `VK_EXT_mesh_shader` on a Vulkan 1.1 device.
+    fn get_mesh_shader_device_extensions() -> [&'static CStr; 3] {
+        [mesh_shader::NAME, spirv_1_4::NAME, shader_float_controls::NAME]
+    }
+
+    /// Query if mesh and task shaders are supported.
+    ///
+    /// Requires Vulkan 1.1 for the instance and the device.
+    ///
+    /// # Returns
+    ///
+    /// None if mesh shaders are not supported at all, otherwise
+    /// a tuple indicating support for mesh and task shaders.
+    fn get_mesh_shader_features(
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+    ) -> Option<(bool, bool)> {
+        if !Self::check_extensions_support(
+            instance,
+            device,
+            &Self::get_mesh_shader_device_extensions(),
+        ) {
+            return None;
+        }
+        let mut mesh_shader_features = vk::PhysicalDeviceMeshShaderFeaturesEXT::default();
+        let mut features =
+            vk::PhysicalDeviceFeatures2::default().push_next(&mut mesh_shader_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        let mesh = mesh_shader_features.mesh_shader == vk::TRUE;
+        let task = mesh_shader_features.task_shader == vk::TRUE;
+        mesh.then_some((mesh, task))
+    }
+
+    /// Extensions needed for ray tracing pipelines on a Vulkan 1.1 device.
+    fn get_ray_tracing_device_extensions() -> [&'static CStr; 7] {
+        [
+            acceleration_structure::NAME,
+            ray_tracing_pipeline::NAME,
+            deferred_host_operations::NAME,
+            buffer_device_address::NAME,
+            descriptor_indexing::NAME,
+            spirv_1_4::NAME,
+            shader_float_controls::NAME,
+        ]
+    }
+
+    /// Query if ray tracing pipelines and acceleration structures are supported.
+    ///
+    /// Requires Vulkan 1.1 for the instance and the device.
+    fn get_ray_tracing_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+        if !Self::check_extensions_support(
+            instance,
+            device,
+            &Self::get_ray_tracing_device_extensions(),
+        ) {
+            return false;
+        }
+        let mut buffer_device_address_features =
+            vk::PhysicalDeviceBufferDeviceAddressFeatures::default();
+        let mut acceleration_structure_features =
+            vk::PhysicalDeviceAccelerationStructureFeaturesKHR::default();
+        let mut ray_tracing_pipeline_features =
+            vk::PhysicalDeviceRayTracingPipelineFeaturesKHR::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut buffer_device_address_features)
+            .push_next(&mut acceleration_structure_features)
+            .push_next(&mut ray_tracing_pipeline_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        buffer_device_address_features.buffer_device_address == vk::TRUE
+            && acceleration_structure_features.acceleration_structure == vk::TRUE
+            && ray_tracing_pipeline_features.ray_tracing_pipeline == vk::TRUE
+    }
+
+    /// Extensions needed for per pipeline fragment shading rates on a Vulkan 1.1 device.
+    fn get_fragment_shading_rate_device_extensions() -> [&'static CStr; 2] {
+        [fragment_shading_rate::NAME, create_renderpass2::NAME]
+    }
+
+    /// Query if the fragment shading rate can be set per pipeline or draw call.
+    ///
+    /// Requires Vulkan 1.1 for the instance and the device.
+    fn get_fragment_shading_rate_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+        if !Self::check_extensions_support(
+            instance,
+            device,
+            &Self::get_fragment_shading_rate_device_extensions(),
+        ) {
+            return false;
+        }
+        let mut fragment_shading_rate_features =
+            vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut fragment_shading_rate_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        fragment_shading_rate_features.pipeline_fragment_shading_rate == vk::TRUE
+    }
+
+    fn get_swapchain_maintenance_support(instance: &Instance, device: vk::PhysicalDevice) -> bool {
+        if !Self::check_extensions_support(instance, device, &[swapchain_maintenance1::NAME]) {
+            return false;
+        }
+        let mut swapchain_maintenance_features =
+            vk::PhysicalDeviceSwapchainMaintenance1FeaturesEXT::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut swapchain_maintenance_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        swapchain_maintenance_features.swapchain_maintenance1 == vk::TRUE
+    }
+
+    fn get_pipeline_executable_properties_support(
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+    ) -> bool {
+        let extensions = [pipeline_executable_properties::NAME];
+        if !Self::check_extensions_support(instance, device, &extensions) {
+            return false;
+        }
+        let mut executable_properties_features =
+            vk::PhysicalDevicePipelineExecutablePropertiesFeaturesKHR::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut executable_properties_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        executable_properties_features.pipeline_executable_info == vk::TRUE
+    }
+
+    fn get_synchronization2_support(
+        instance: &Instance,
+        device: vk::PhysicalDevice,
+        is_vulkan_1_3: bool,
+    ) -> bool {
+        if !is_vulkan_1_3
+            && !Self::check_extensions_support(instance, device, &[synchronization2::NAME])
+        {
+            return false;
+        }
+        let mut synchronization2_features = vk::PhysicalDeviceSynchronization2Features::default();
+        let mut features = vk::PhysicalDeviceFeatures2::default()
+            .push_next(&mut synchronization2_features);
+        unsafe { instance.get_physical_device_features2(device, &mut features) };
+        synchronization2_features.synchronization2 == vk::TRUE
+    }
+
     fn get_required_device_extensions() -> [&'static CStr; 1] {
         #[cfg(not(any(target_os = "macos", target_os = "ios")))]
         [khr_swapchain::NAME]
@@ -344,6 +1169,7 @@ impl VkContext {
 
 impl Drop for VkContext {
     fn drop(&mut self) {
+        self.samplers.get_mut().unwrap().destroy(&self.device);
         unsafe {
             self.device.destroy_device(None);
             self.surface.destroy_surface(self.surface_khr, None);
@@ -354,3 +1180,23 @@ impl Drop for VkContext {
         }
     }
 }
+
+/// The stages of Vulkan 1.0 in `stages` or `none` if there are none.
+fn legacy_stages(
+    stages: vk::PipelineStageFlags2,
+    none: vk::PipelineStageFlags,
+) -> vk::PipelineStageFlags {
+    // the stages of Vulkan 1.0 keep their bits in synchronization2
+    debug_assert!(stages.as_raw() >> 32 == 0, "{stages:?} needs synchronization2");
+    if stages.is_empty() {
+        none
+    } else {
+        vk::PipelineStageFlags::from_raw(stages.as_raw() as u32)
+    }
+}
+
+/// The accesses of Vulkan 1.0 in `access`.
+fn legacy_access(access: vk::AccessFlags2) -> vk::AccessFlags {
+    debug_assert!(access.as_raw() >> 32 == 0, "{access:?} needs synchronization2");
+    vk::AccessFlags::from_raw(access.as_raw() as u32)
+}
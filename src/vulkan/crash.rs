@@ -0,0 +1,181 @@
+use super::{buffer, context::VkContext};
+
+use anyhow::{anyhow, Context};
+use ash::{amd::buffer_marker, nv::device_diagnostic_checkpoints, vk};
+use std::ffi::c_void;
+
+/// What is drawn between two crash markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// The pipeline of the gallery or of an art piece at this index.
+    Pipeline(usize),
+    /// The art shown on the whole screen.
+    Entered,
+    /// The art previewed in a corner.
+    Preview,
+}
+
+impl Marker {
+    /// 0 is left for buffers which were never written.
+    fn to_raw(self) -> u32 {
+        match self {
+            Self::Pipeline(index) => index as u32 + 1,
+            Self::Entered => u32::MAX,
+            Self::Preview => u32::MAX - 1,
+        }
+    }
+
+    fn from_raw(raw: u32) -> Option<Self> {
+        match raw {
+            0 => None,
+            u32::MAX => Some(Self::Entered),
+            raw if raw == u32::MAX - 1 => Some(Self::Preview),
+            raw => Some(Self::Pipeline(raw as usize - 1)),
+        }
+    }
+}
+
+/// The last markers the GPU started and finished before the device was lost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CrashReport {
+    pub started: Option<Marker>,
+    pub finished: Option<Marker>,
+}
+
+impl CrashReport {
+    /// The marker that was started but not finished, probably the one that crashed.
+    pub fn executing(&self) -> Option<Marker> {
+        self.started.filter(|&started| Some(started) != self.finished)
+    }
+}
+
+/// Marks what the GPU draws so that a lost device can tell what it was doing,
+/// e.g. an experimental raymarcher that loops forever.
+///
+/// Uses `VK_NV_device_diagnostic_checkpoints` or `VK_AMD_buffer_marker`, whichever is enabled.
+/// A marker is passed after its commands, the top of the pipe reaches it once they started
+/// and the bottom once they finished.
+pub enum CrashMarkers {
+    Checkpoints(device_diagnostic_checkpoints::Device),
+    /// The last marker written at the top and at the bottom of the pipe into a mapped buffer,
+    /// shared by all frames as they run one after another.
+    BufferMarkers {
+        loader: buffer_marker::Device,
+        buffer: vk::Buffer,
+        memory: vk::DeviceMemory,
+        ptr: *mut c_void,
+    },
+}
+
+impl CrashMarkers {
+    pub fn new(vk_context: &VkContext) -> Result<Self, anyhow::Error> {
+        match vk_context.crash_markers() {
+            (Some(checkpoints), _) => Ok(Self::Checkpoints(checkpoints.clone())),
+            (None, Some(loader)) => {
+                let (buffer, memory, _) = buffer::create_buffer(
+                    vk_context,
+                    2 * size_of::<u32>() as vk::DeviceSize,
+                    vk::BufferUsageFlags::TRANSFER_DST,
+                    vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+                );
+                let device = vk_context.device();
+                let ptr = unsafe {
+                    device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                        .context("Failed to map crash marker buffer")?
+                };
+                unsafe { ptr.cast::<[u32; 2]>().write_unaligned([0; 2]) };
+                Ok(Self::BufferMarkers { loader: loader.clone(), buffer, memory, ptr })
+            }
+            (None, None) => Err(anyhow!("Crash markers are not supported")),
+        }
+    }
+
+    /// Marks the commands recorded since the last marker as `marker`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be recording.
+    pub unsafe fn mark(&self, buffer: vk::CommandBuffer, marker: Marker) {
+        let raw = marker.to_raw();
+        match self {
+            Self::Checkpoints(checkpoints) => unsafe {
+                // the marker is only compared, it is never dereferenced
+                checkpoints.cmd_set_checkpoint(buffer, raw as usize as *const c_void);
+            },
+            Self::BufferMarkers { loader, buffer: marker_buffer, .. } => unsafe {
+                let stages = [
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                ];
+                for (slot, stage) in stages.into_iter().enumerate() {
+                    let offset = (slot * size_of::<u32>()) as vk::DeviceSize;
+                    loader.cmd_write_buffer_marker(buffer, stage, *marker_buffer, offset, raw);
+                }
+            },
+        }
+    }
+
+    /// Reads the last markers reached on `queue`, which should have lost its device.
+    pub fn report(&self, queue: vk::Queue) -> CrashReport {
+        match self {
+            Self::Checkpoints(checkpoints) => {
+                let mut data = unsafe {
+                    vec![Default::default(); checkpoints.get_queue_checkpoint_data_len(queue)]
+                };
+                unsafe { checkpoints.get_queue_checkpoint_data(queue, &mut data) };
+                let marker = |stage| data.iter()
+                    .find(|data: &&vk::CheckpointDataNV| data.stage == stage)
+                    .and_then(|data| Marker::from_raw(data.p_checkpoint_marker as usize as u32));
+                CrashReport {
+                    started: marker(vk::PipelineStageFlags::TOP_OF_PIPE),
+                    finished: marker(vk::PipelineStageFlags::BOTTOM_OF_PIPE),
+                }
+            }
+            Self::BufferMarkers { ptr, .. } => {
+                let [started, finished] = unsafe { ptr.cast::<[u32; 2]>().read_volatile() };
+                CrashReport {
+                    started: Marker::from_raw(started),
+                    finished: Marker::from_raw(finished),
+                }
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The buffer must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &ash::Device) {
+        if let Self::BufferMarkers { buffer, memory, .. } = self {
+            unsafe {
+                device.unmap_memory(*memory);
+                device.free_memory(*memory, None);
+                device.destroy_buffer(*buffer, None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_markers() {
+        let markers = [Marker::Pipeline(0), Marker::Pipeline(41), Marker::Entered, Marker::Preview];
+        for marker in markers {
+            assert_eq!(Marker::from_raw(marker.to_raw()), Some(marker));
+        }
+        assert_eq!(Marker::from_raw(0), None);
+    }
+
+    #[test]
+    fn executing_marker() {
+        let report = CrashReport {
+            started: Some(Marker::Pipeline(3)),
+            finished: Some(Marker::Pipeline(2)),
+        };
+        assert_eq!(report.executing(), Some(Marker::Pipeline(3)));
+        let report = CrashReport { finished: report.started, ..report };
+        assert_eq!(report.executing(), None);
+    }
+}
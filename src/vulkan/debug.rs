@@ -1,18 +1,98 @@
+use anyhow::anyhow;
 use ash::{
-    ext::debug_utils,
+    ext::{debug_utils, validation_features},
     vk, Entry, Instance
 };
 use std::{
+    collections::VecDeque,
     ffi::{CStr, CString},
     os::raw::{c_char, c_void},
+    str::FromStr,
+    sync::Mutex,
 };
 
-#[cfg(debug_assertions)]
-pub const ENABLE_VALIDATION_LAYERS: bool = true;
-#[cfg(not(debug_assertions))]
-pub const ENABLE_VALIDATION_LAYERS: bool = false;
-
 const REQUIRED_LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
+/// The most messages of `debugPrintfEXT` kept until they are taken, older ones are dropped.
+const MAX_PRINTF_MESSAGES: usize = 256;
+
+/// The messages the shaders printed since they were last taken, see [`take_printf_messages`].
+static PRINTF_MESSAGES: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+/// Environment variable with the validation if it is not given on the command line,
+/// see [`Validation::from_str`].
+pub const VALIDATION_ENV: &str = "SHADERPIXEL_VALIDATION";
+
+/// The validation the instance is created with.
+///
+/// Validation is enabled by default in debug builds only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Validation {
+    /// Enables the standard validation layer, required by the other options.
+    pub enabled: bool,
+    /// Instruments the shaders to validate out of bounds accesses on the GPU, which is slow.
+    pub gpu_assisted: bool,
+    /// Warns about valid but potentially slow usage of the API.
+    pub best_practices: bool,
+    /// Lets shaders print values with `debugPrintfEXT`, see [`take_printf_messages`].
+    pub printf: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Self {
+            enabled: cfg!(debug_assertions),
+            gpu_assisted: false,
+            best_practices: false,
+            printf: false,
+        }
+    }
+}
+
+impl Validation {
+    pub const OFF: Self = Self {
+        enabled: false,
+        gpu_assisted: false,
+        best_practices: false,
+        printf: false,
+    };
+
+    /// Whether the validation layer has to be configured with `VK_EXT_validation_features`.
+    pub fn needs_features(&self) -> bool {
+        self.gpu_assisted || self.best_practices || self.printf
+    }
+
+    /// The validation set in [`VALIDATION_ENV`] or the default one if it is not set or invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(VALIDATION_ENV) else {
+            return Self::default();
+        };
+        value.parse().unwrap_or_else(|err| {
+            log::warn!("Ignoring {VALIDATION_ENV}: {err}");
+            Self::default()
+        })
+    }
+}
+
+impl FromStr for Validation {
+    type Err = anyhow::Error;
+
+    /// Parses a comma separated list of `off`, `on`, `gpu`, `best-practices` and `printf`,
+    /// where all but `off` also enable the standard validation.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut validation = Self::OFF;
+        for option in s.split(',').map(str::trim) {
+            match option {
+                "off" => validation.enabled = false,
+                "on" => validation.enabled = true,
+                "gpu" => validation.gpu_assisted = true,
+                "best-practices" => validation.best_practices = true,
+                "printf" => validation.printf = true,
+                _ => return Err(anyhow!("unknown validation option {option:?}")),
+            }
+        }
+        validation.enabled |= validation.needs_features();
+        Ok(validation)
+    }
+}
 
 unsafe extern "system" fn vulkan_debug_callback(
     flag: vk::DebugUtilsMessageSeverityFlagsEXT,
@@ -22,16 +102,49 @@ unsafe extern "system" fn vulkan_debug_callback(
 ) -> vk::Bool32 {
     use vk::DebugUtilsMessageSeverityFlagsEXT as Flag;
 
+    // the messages have their own target so they can be filtered with RUST_LOG
     let message = unsafe { CStr::from_ptr((*p_callback_data).p_message) };
+    let message_id = unsafe { (*p_callback_data).p_message_id_name };
+    let is_printf = !message_id.is_null()
+        && unsafe { CStr::from_ptr(message_id) }.to_bytes().ends_with(b"DEBUG-PRINTF");
+    if is_printf {
+        let text = printf_text(&message.to_string_lossy()).to_owned();
+        log::info!(target: "printf", "{text}");
+        let mut messages = PRINTF_MESSAGES.lock().unwrap_or_else(|err| err.into_inner());
+        if messages.len() == MAX_PRINTF_MESSAGES {
+            messages.pop_front();
+        }
+        messages.push_back(text);
+        return vk::FALSE;
+    }
     match flag {
-        Flag::VERBOSE => log::debug!("{:?} - {:?}", typ, message),
-        Flag::INFO => log::info!("{:?} - {:?}", typ, message),
-        Flag::WARNING => log::warn!("{:?} - {:?}", typ, message),
-        _ => log::error!("{:?} - {:?}", typ, message),
+        Flag::VERBOSE => log::debug!(target: "validation", "{:?} - {:?}", typ, message),
+        Flag::INFO => log::info!(target: "validation", "{:?} - {:?}", typ, message),
+        Flag::WARNING => log::warn!(target: "validation", "{:?} - {:?}", typ, message),
+        _ => log::error!(target: "validation", "{:?} - {:?}", typ, message),
     }
     vk::FALSE
 }
 
+/// The text printed by the shader without what the layer added in front of it,
+/// which is on the last line after the last `|` and the command that submitted it.
+fn printf_text(message: &str) -> &str {
+    let line = message.lines().rfind(|line| !line.trim().is_empty()).unwrap_or_default();
+    let text = line.rsplit_once('|').map_or(line, |(_, text)| text).trim();
+    // newer layers start with the command that submitted the shader, e.g. `vkQueueSubmit(): `
+    text.split_once("(): ")
+        .filter(|(command, _)| command.starts_with("vk") && !command.contains(' '))
+        .map_or(text, |(_, text)| text)
+        .trim()
+}
+
+/// Takes the messages the shaders printed with `debugPrintfEXT` since the last call,
+/// the oldest first.
+pub fn take_printf_messages() -> Vec<String> {
+    let mut messages = PRINTF_MESSAGES.lock().unwrap_or_else(|err| err.into_inner());
+    messages.drain(..).collect()
+}
+
 /// Get the pointers to the validation layers names.
 /// Also return the corresponding `CString` to avoid dangling pointers.
 pub fn get_layer_names_and_pointers() -> (Vec<CString>, Vec<*const c_char>) {
@@ -49,30 +162,54 @@ pub fn get_layer_names_and_pointers() -> (Vec<CString>, Vec<*const c_char>) {
 /// Check if the required validation set in `REQUIRED_LAYERS`
 /// are supported by the Vulkan instance.
 ///
-/// # Panics
-///
-/// Panic if at least one on the layer is not supported.
-pub fn check_validation_layer_support(entry: &Entry) {
-    let supported_layers = unsafe { entry.enumerate_instance_layer_properties().unwrap() };
+/// Logs the first missing layer and returns false if at least one is not supported.
+pub fn check_validation_layer_support(entry: &Entry) -> bool {
+    let supported_layers = unsafe { entry.enumerate_instance_layer_properties() }.unwrap_or_default();
     for required in REQUIRED_LAYERS.iter() {
         let found = supported_layers.iter().any(|layer| {
-            let name = unsafe { CStr::from_ptr(layer.layer_name.as_ptr()) };
-            let name = name.to_str().expect("Failed to get layer name pointer");
-            required == &name
+            layer.layer_name_as_c_str().ok().and_then(|name| name.to_str().ok()) == Some(required)
         });
 
         if !found {
-            panic!("Validation layer not supported: {}", required);
+            log::warn!("Validation layer not supported: {}", required);
+            return false;
         }
     }
+    true
+}
+
+/// Check if the validation layer can be configured with `VK_EXT_validation_features`,
+/// which is needed for GPU-assisted validation and best practices.
+pub fn check_validation_features_support(entry: &Entry) -> bool {
+    let layer_name = CString::new(REQUIRED_LAYERS[0]).unwrap();
+    let extensions = unsafe { entry.enumerate_instance_extension_properties(Some(&layer_name)) }
+        .unwrap_or_default();
+    extensions.iter().any(|ext| ext.extension_name_as_c_str() == Ok(validation_features::NAME))
+}
+
+/// The validation features to enable in addition to the standard validation.
+pub fn enabled_validation_features(validation: Validation) -> Vec<vk::ValidationFeatureEnableEXT> {
+    let mut features = Vec::new();
+    if validation.gpu_assisted {
+        features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED);
+        features.push(vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT);
+    }
+    if validation.best_practices {
+        features.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+    }
+    if validation.printf {
+        features.push(vk::ValidationFeatureEnableEXT::DEBUG_PRINTF);
+    }
+    features
 }
 
 /// Setup the debug message if validation layers are enabled.
 pub fn setup_debug_messenger(
     entry: &Entry,
     instance: &Instance,
+    validation: Validation,
 ) -> Option<(debug_utils::Instance, vk::DebugUtilsMessengerEXT)> {
-    if !ENABLE_VALIDATION_LAYERS {
+    if !validation.enabled {
         return None;
     }
 
@@ -98,3 +235,38 @@ pub fn setup_debug_messenger(
 
     Some((debug_utils, debug_utils_messenger))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_validation() {
+        let validation = |enabled, gpu_assisted, best_practices| Validation {
+            enabled,
+            gpu_assisted,
+            best_practices,
+            printf: false,
+        };
+        assert_eq!("off".parse::<Validation>().unwrap(), validation(false, false, false));
+        assert_eq!("on".parse::<Validation>().unwrap(), validation(true, false, false));
+        assert_eq!("gpu".parse::<Validation>().unwrap(), validation(true, true, false));
+        assert_eq!(
+            "gpu, best-practices".parse::<Validation>().unwrap(),
+            validation(true, true, true),
+        );
+        assert!("everything".parse::<Validation>().is_err());
+        let printf = "printf".parse::<Validation>().unwrap();
+        assert!(printf.enabled && printf.printf && printf.needs_features());
+    }
+
+    #[test]
+    fn strip_printf_prefix() {
+        let message = "Validation Information: [ WARNING-DEBUG-PRINTF ] | MessageID = 0x76589099 \
+            | vkQueueSubmit():  value = 0.5";
+        assert_eq!(printf_text(message), "value = 0.5");
+        let message = "Debug shader printf message generated\n\nvalue = 1\n";
+        assert_eq!(printf_text(message), "value = 1");
+        assert_eq!(printf_text(""), "");
+    }
+}
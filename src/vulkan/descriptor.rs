@@ -0,0 +1,166 @@
+use anyhow::Context;
+use ash::{vk, Device};
+use std::collections::HashMap;
+
+/// Sets a pool has room for at least, later pools double in size.
+const MIN_SETS_PER_POOL: u32 = 16;
+
+/// Creates descriptor set layouts and hands out descriptor sets of them.
+///
+/// Every layout gets its own pools that are sized from the bindings of the layout
+/// and a new pool is created once they are full, so nobody has to count descriptors up front.
+#[derive(Default)]
+pub struct DescriptorAllocator {
+    layouts: HashMap<vk::DescriptorSetLayout, LayoutPools>,
+}
+
+struct LayoutPools {
+    /// The descriptors of a single set.
+    sizes: Vec<vk::DescriptorPoolSize>,
+    pools: Vec<vk::DescriptorPool>,
+    /// Sets the last pool has room for.
+    capacity: u32,
+    /// Sets left in the last pool.
+    free: u32,
+}
+
+impl DescriptorAllocator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a layout with `bindings` that is destroyed together with the allocator.
+    pub fn create_layout(
+        &mut self,
+        device: &Device,
+        bindings: &[vk::DescriptorSetLayoutBinding],
+    ) -> Result<vk::DescriptorSetLayout, anyhow::Error> {
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(bindings);
+        let layout = unsafe {
+            device.create_descriptor_set_layout(&layout_info, None)
+                .context("Failed to create descriptor set layout")?
+        };
+        self.layouts.insert(layout, LayoutPools {
+            sizes: pool_sizes(bindings),
+            pools: Vec::new(),
+            capacity: 0,
+            free: 0,
+        });
+        Ok(layout)
+    }
+
+    /// Allocates `count` descriptor sets of `layout`, which must have been created
+    /// by [`Self::create_layout`].
+    pub fn allocate(
+        &mut self,
+        device: &Device,
+        layout: vk::DescriptorSetLayout,
+        count: usize,
+    ) -> Result<Vec<vk::DescriptorSet>, anyhow::Error> {
+        let pools = self.layouts.get_mut(&layout).context("Unknown descriptor set layout")?;
+        let mut sets = Vec::with_capacity(count);
+        while sets.len() < count {
+            let needed = (count - sets.len()) as u32;
+            if pools.free == 0 {
+                pools.add_pool(device, next_capacity(pools.capacity, needed))?;
+            }
+            let layouts = vec![layout; needed.min(pools.free) as usize];
+            let alloc_info = vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(*pools.pools.last().unwrap())
+                .set_layouts(&layouts);
+            let allocated = unsafe {
+                device.allocate_descriptor_sets(&alloc_info)
+                    .context("Failed to allocate descriptor sets")?
+            };
+            pools.free -= allocated.len() as u32;
+            sets.extend(allocated);
+        }
+        Ok(sets)
+    }
+
+    /// # Safety
+    ///
+    /// No descriptor set may be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        for (layout, pools) in self.layouts.drain() {
+            unsafe {
+                for pool in pools.pools {
+                    device.destroy_descriptor_pool(pool, None);
+                }
+                device.destroy_descriptor_set_layout(layout, None);
+            }
+        }
+    }
+}
+
+impl LayoutPools {
+    fn add_pool(&mut self, device: &Device, capacity: u32) -> Result<(), anyhow::Error> {
+        let pool_sizes = self.sizes.iter()
+            .map(|size| vk::DescriptorPoolSize {
+                ty: size.ty,
+                descriptor_count: size.descriptor_count * capacity,
+            })
+            .collect::<Vec<_>>();
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(capacity);
+        let pool = unsafe {
+            device.create_descriptor_pool(&pool_info, None)
+                .context("Failed to create descriptor pool")?
+        };
+        log::debug!("Created descriptor pool for {capacity} sets");
+        self.pools.push(pool);
+        self.capacity = capacity;
+        self.free = capacity;
+        Ok(())
+    }
+}
+
+/// The number of descriptors of each type a set with `bindings` needs.
+fn pool_sizes(bindings: &[vk::DescriptorSetLayoutBinding]) -> Vec<vk::DescriptorPoolSize> {
+    let mut sizes = Vec::<vk::DescriptorPoolSize>::new();
+    for binding in bindings {
+        match sizes.iter_mut().find(|size| size.ty == binding.descriptor_type) {
+            Some(size) => size.descriptor_count += binding.descriptor_count,
+            None => sizes.push(vk::DescriptorPoolSize {
+                ty: binding.descriptor_type,
+                descriptor_count: binding.descriptor_count,
+            }),
+        }
+    }
+    sizes
+}
+
+/// Sets the next pool has room for after a pool with room for `previous` sets is full
+/// and `needed` sets are still to be allocated.
+fn next_capacity(previous: u32, needed: u32) -> u32 {
+    (previous * 2).max(needed).max(MIN_SETS_PER_POOL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_pools() {
+        let binding = |ty, count| {
+            vk::DescriptorSetLayoutBinding::default().descriptor_type(ty).descriptor_count(count)
+        };
+        let sizes = pool_sizes(&[
+            binding(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+            binding(vk::DescriptorType::SAMPLED_IMAGE, 1),
+            binding(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+            binding(vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 3),
+        ]);
+        let sizes = sizes.iter().map(|size| (size.ty, size.descriptor_count)).collect::<Vec<_>>();
+        assert_eq!(sizes, [
+            (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 2),
+            (vk::DescriptorType::SAMPLED_IMAGE, 1),
+            (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 3),
+        ]);
+
+        assert_eq!(next_capacity(0, 3), MIN_SETS_PER_POOL);
+        assert_eq!(next_capacity(MIN_SETS_PER_POOL, 3), MIN_SETS_PER_POOL * 2);
+        assert_eq!(next_capacity(MIN_SETS_PER_POOL, 100), 100);
+    }
+}
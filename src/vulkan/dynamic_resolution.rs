@@ -0,0 +1,101 @@
+/// The lowest scale, below it the gallery gets too blurry to be worth the frames.
+const MIN_SCALE_STEPS: u32 = 5;
+/// How much the scale changes at once.
+const SCALE_STEP: f32 = 0.1;
+/// The frames averaged before the scale is changed, so that it does not flicker.
+const SAMPLES: usize = 30;
+/// How much of the target frame time a frame may take after scaling up,
+/// so that the scale does not go back and forth around the target.
+const HEADROOM: f32 = 0.85;
+
+/// Scales the resolution the gallery is drawn at up and down to hold a frame rate
+/// on weaker GPUs, the post pass scales the frame to the swapchain.
+///
+/// The frame time is measured on the GPU and assumed to grow with the number of pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicResolution {
+    /// The longest the GPU may take for a frame in milliseconds.
+    target: f32,
+    /// How many steps the scale is below one.
+    steps: u32,
+    frame_times: Vec<f32>,
+}
+
+impl DynamicResolution {
+    pub fn new(target_fps: f32) -> Self {
+        Self {
+            target: 1000. / target_fps,
+            steps: 0,
+            frame_times: Vec::with_capacity(SAMPLES),
+        }
+    }
+
+    pub fn target_fps(&self) -> f32 {
+        1000. / self.target
+    }
+
+    /// The width and the height the gallery is drawn at relative to the swapchain.
+    pub fn scale(&self) -> f32 {
+        Self::step_scale(self.steps)
+    }
+
+    fn step_scale(steps: u32) -> f32 {
+        1. - steps as f32 * SCALE_STEP
+    }
+
+    /// Takes the GPU time of a frame in milliseconds, returns true if the scale changed.
+    pub fn push(&mut self, frame_time: f32) -> bool {
+        self.frame_times.push(frame_time);
+        if self.frame_times.len() < SAMPLES {
+            return false;
+        }
+        let average = self.frame_times.iter().sum::<f32>() / SAMPLES as f32;
+        self.frame_times.clear();
+        let scale = self.scale();
+        let steps = if average > self.target {
+            (self.steps + 1).min(MIN_SCALE_STEPS)
+        } else if self.steps > 0 {
+            let larger = Self::step_scale(self.steps - 1);
+            let expected = average * (larger * larger) / (scale * scale);
+            if expected < self.target * HEADROOM { self.steps - 1 } else { self.steps }
+        } else {
+            0
+        };
+        let changed = steps != self.steps;
+        self.steps = steps;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_frames(resolution: &mut DynamicResolution, frame_time: f32) -> bool {
+        (0..SAMPLES).fold(false, |changed, _| resolution.push(frame_time) || changed)
+    }
+
+    #[test]
+    fn scale_down_and_up() {
+        let mut resolution = DynamicResolution::new(50.);
+        assert!(!push_frames(&mut resolution, 15.));
+        assert_eq!(resolution.scale(), 1.);
+        assert!(push_frames(&mut resolution, 25.));
+        assert!((resolution.scale() - 0.9).abs() < 1e-6);
+        // 16ms at 0.9 would be about 20ms at full resolution, which is too close to the target
+        assert!(!push_frames(&mut resolution, 16.));
+        assert!(push_frames(&mut resolution, 12.));
+        assert_eq!(resolution.scale(), 1.);
+    }
+
+    #[test]
+    fn scale_stays_in_range() {
+        let mut resolution = DynamicResolution::new(60.);
+        for _ in 0..10 {
+            push_frames(&mut resolution, 100.);
+        }
+        assert_eq!(resolution.scale(), DynamicResolution::step_scale(MIN_SCALE_STEPS));
+        assert!(!push_frames(&mut resolution, 100.));
+        assert!((resolution.target_fps() - 60.).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,138 @@
+use ash::{khr::pipeline_executable_properties, vk};
+use std::time::Duration;
+
+/// Which details about the creation of a pipeline are logged,
+/// so that authors see when their shaders take long to compile or need many registers.
+#[derive(Clone, Default)]
+pub struct PipelineFeedback {
+    /// Whether `VK_EXT_pipeline_creation_feedback` or Vulkan 1.3 is enabled,
+    /// which tells how long the pipeline and each of its stages took to create.
+    pub creation: bool,
+    /// The loader of `VK_KHR_pipeline_executable_properties`, which tells the statistics
+    /// of the compiled shaders like their registers and spills.
+    pub executable_properties: Option<pipeline_executable_properties::Device>,
+}
+
+impl PipelineFeedback {
+    /// Flags to add to the create info of the pipeline.
+    pub fn create_flags(&self) -> vk::PipelineCreateFlags {
+        if self.executable_properties.is_some() {
+            vk::PipelineCreateFlags::CAPTURE_STATISTICS_KHR
+        } else {
+            vk::PipelineCreateFlags::empty()
+        }
+    }
+
+    /// Logs the creation feedback and the statistics of every executable of `pipeline`.
+    ///
+    /// # Safety
+    ///
+    /// `pipeline` must be created with [`Self::create_flags`].
+    pub unsafe fn log(
+        &self,
+        name: &str,
+        pipeline: vk::Pipeline,
+        creation: Option<(vk::PipelineCreationFeedback, &[vk::PipelineCreationFeedback])>,
+        stages: &[vk::ShaderStageFlags],
+    ) {
+        if let Some((total, stage_feedbacks)) = creation.filter(|(total, _)| is_valid(total)) {
+            let stage_durations = stages.iter().zip(stage_feedbacks)
+                .filter(|(_, feedback)| is_valid(feedback))
+                .map(|(stage, feedback)| format!("{stage:?} {}", format_duration(feedback)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let cached = total.flags
+                .contains(vk::PipelineCreationFeedbackFlags::APPLICATION_PIPELINE_CACHE_HIT);
+            log::info!(
+                "Created pipeline {name} in {}{} ({stage_durations})",
+                format_duration(&total),
+                if cached { " from the cache" } else { "" },
+            );
+        }
+        let Some(properties) = self.executable_properties.as_ref() else {
+            return;
+        };
+        let pipeline_info = vk::PipelineInfoKHR::default().pipeline(pipeline);
+        let executables = unsafe { properties.get_pipeline_executable_properties(&pipeline_info) };
+        let executables = match executables {
+            Ok(executables) => executables,
+            Err(err) => return log::warn!("Failed to get the executables of {name}: {err}"),
+        };
+        for (index, executable) in executables.iter().enumerate() {
+            let executable_info = vk::PipelineExecutableInfoKHR::default()
+                .pipeline(pipeline)
+                .executable_index(index as u32);
+            let statistics =
+                unsafe { properties.get_pipeline_executable_statistics(&executable_info) };
+            let statistics = match statistics {
+                Ok(statistics) => statistics,
+                Err(err) => {
+                    log::warn!("Failed to get the statistics of {name}: {err}");
+                    continue;
+                }
+            };
+            let statistics = statistics.iter()
+                .map(|statistic| {
+                    let name = statistic.name_as_c_str().unwrap_or_default().to_string_lossy();
+                    format!("{name}: {}", format_statistic(statistic))
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let executable_name = executable.name_as_c_str().unwrap_or_default().to_string_lossy();
+            log::info!(
+                "Pipeline {name} {executable_name} ({:?}, subgroup size {}): {statistics}",
+                executable.stages,
+                executable.subgroup_size,
+            );
+        }
+    }
+}
+
+fn is_valid(feedback: &vk::PipelineCreationFeedback) -> bool {
+    feedback.flags.contains(vk::PipelineCreationFeedbackFlags::VALID)
+}
+
+fn format_duration(feedback: &vk::PipelineCreationFeedback) -> String {
+    format!("{:.2?}", Duration::from_nanos(feedback.duration))
+}
+
+/// The value of `statistic` in the format the driver reported it.
+fn format_statistic(statistic: &vk::PipelineExecutableStatisticKHR) -> String {
+    // the format tells which field of the union is set
+    unsafe {
+        match statistic.format {
+            vk::PipelineExecutableStatisticFormatKHR::BOOL32 => {
+                (statistic.value.b32 == vk::TRUE).to_string()
+            }
+            vk::PipelineExecutableStatisticFormatKHR::INT64 => statistic.value.i64.to_string(),
+            vk::PipelineExecutableStatisticFormatKHR::UINT64 => statistic.value.u64.to_string(),
+            vk::PipelineExecutableStatisticFormatKHR::FLOAT64 => {
+                format!("{:.2}", statistic.value.f64)
+            }
+            format => format!("{format:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_values() {
+        let statistic = vk::PipelineExecutableStatisticKHR::default()
+            .format(vk::PipelineExecutableStatisticFormatKHR::UINT64)
+            .value(vk::PipelineExecutableStatisticValueKHR { u64: 42 });
+        assert_eq!(format_statistic(&statistic), "42");
+        let statistic = statistic
+            .format(vk::PipelineExecutableStatisticFormatKHR::BOOL32)
+            .value(vk::PipelineExecutableStatisticValueKHR { b32: vk::TRUE });
+        assert_eq!(format_statistic(&statistic), "true");
+        let feedback = vk::PipelineCreationFeedback {
+            flags: vk::PipelineCreationFeedbackFlags::VALID,
+            duration: 1_500_000,
+        };
+        assert!(is_valid(&feedback));
+        assert_eq!(format_duration(&feedback), "1.50ms");
+    }
+}
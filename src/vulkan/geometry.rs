@@ -3,18 +3,78 @@ use super::context::VkContext;
 use super::vertex::Vertex;
 
 use ash::{vk, Device};
-use std::rc::Rc;
+use std::{mem::offset_of, rc::Rc};
 
+/// Number of morph targets a geometry can blend between.
+pub const MAX_MORPH_TARGETS: usize = 4;
+/// Location of the offsets of the first morph target, the others follow at the next locations.
+pub const MORPH_TARGET_LOCATION: u32 = 4;
+
+/// The offsets of a vertex to its position in every morph target, unused targets are zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct MorphDeltas {
+    deltas: [[f32; 3]; MAX_MORPH_TARGETS],
+}
+
+impl MorphDeltas {
+    /// The offsets of every vertex at `base` to the same vertex in each of the `targets`.
+    pub fn from_targets(
+        base: &[[f32; 3]],
+        targets: &[Vec<[f32; 3]>],
+    ) -> Result<Vec<Self>, anyhow::Error> {
+        if targets.len() > MAX_MORPH_TARGETS {
+            let count = targets.len();
+            anyhow::bail!("{count} morph targets, at most {MAX_MORPH_TARGETS} are supported");
+        }
+        if let Some(target) = targets.iter().find(|target| target.len() != base.len()) {
+            anyhow::bail!(
+                "morph target has {} vertices but the model has {}",
+                target.len(),
+                base.len(),
+            );
+        }
+        Ok(base.iter().enumerate().map(|(i, pos)| {
+            let mut deltas = Self::default();
+            for (delta, target) in deltas.deltas.iter_mut().zip(targets) {
+                *delta = [0, 1, 2].map(|j| target[i][j] - pos[j]);
+            }
+            deltas
+        }).collect())
+    }
+
+    fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(1)
+            .stride(size_of::<Self>() as _)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    fn get_attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        (0..MAX_MORPH_TARGETS).map(|i| {
+            vk::VertexInputAttributeDescription::default()
+                .binding(1)
+                .location(MORPH_TARGET_LOCATION + i as u32)
+                .format(vk::Format::R32G32B32_SFLOAT)
+                .offset((offset_of!(Self, deltas) + i * size_of::<[f32; 3]>()) as _)
+        }).collect()
+    }
+}
+
+/// A vertex and an index buffer, optionally with a second vertex buffer at binding 1 with the
+/// [`MorphDeltas`] of every vertex.
 #[derive(Clone)]
 pub struct Geometry {
     rc: Option<Rc<()>>,
-    vertex_binding_description: vk::VertexInputBindingDescription,
+    vertex_binding_descriptions: Vec<vk::VertexInputBindingDescription>,
     vertex_attribute_descriptions: Vec<vk::VertexInputAttributeDescription>,
     vertex_buffer: vk::Buffer,
     vertex_buffer_memory: vk::DeviceMemory,
+    morph_buffer: Option<(vk::Buffer, vk::DeviceMemory)>,
     index_buffer: vk::Buffer,
     index_buffer_memory: vk::DeviceMemory,
     index_count: u32,
+    vertex_count: u32,
 }
 
 impl Geometry {
@@ -25,39 +85,83 @@ impl Geometry {
         vertices: &[V],
         indices: &[u32],
     ) -> Self {
+        // allow to build acceleration structures from the geometry if ray tracing is enabled
+        let extra_usage = if vk_context.ray_tracing().is_some() {
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS
+        } else {
+            vk::BufferUsageFlags::empty()
+        };
         let (vertex_buffer, vertex_buffer_memory) = Self::create_buffer_with_data::<u32, _>(
             vk_context,
             transient_command_pool,
             graphics_queue,
-            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vk::BufferUsageFlags::VERTEX_BUFFER | extra_usage,
             vertices,
         );
         let (index_buffer, index_buffer_memory) = Self::create_buffer_with_data::<u16, _>(
             vk_context,
             transient_command_pool,
             graphics_queue,
-            vk::BufferUsageFlags::INDEX_BUFFER,
+            vk::BufferUsageFlags::INDEX_BUFFER | extra_usage,
             indices,
         );
 
         Self {
             rc: Some(Rc::new(())),
-            vertex_binding_description: V::get_binding_description(),
+            vertex_binding_descriptions: vec![V::get_binding_description()],
             vertex_attribute_descriptions: V::get_attribute_descriptions(),
             vertex_buffer,
             vertex_buffer_memory,
+            morph_buffer: None,
             index_buffer,
             index_buffer_memory,
             index_count: indices.len() as _,
+            vertex_count: vertices.len() as _,
         }
     }
 
+    /// Geometry whose vertices are moved towards morph targets by the weights in the
+    /// art uniforms, `morph_deltas` needs an entry per vertex.
+    pub fn with_morph_targets<V: Vertex + Copy>(
+        vk_context: &VkContext,
+        transient_command_pool: vk::CommandPool,
+        graphics_queue: vk::Queue,
+        vertices: &[V],
+        indices: &[u32],
+        morph_deltas: &[MorphDeltas],
+    ) -> Self {
+        assert_eq!(vertices.len(), morph_deltas.len(), "every vertex needs morph deltas");
+        let mut geometry =
+            Self::new(vk_context, transient_command_pool, graphics_queue, vertices, indices);
+        geometry.morph_buffer = Some(Self::create_buffer_with_data::<u32, _>(
+            vk_context,
+            transient_command_pool,
+            graphics_queue,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            morph_deltas,
+        ));
+        geometry.vertex_binding_descriptions.push(MorphDeltas::get_binding_description());
+        geometry.vertex_attribute_descriptions.extend(MorphDeltas::get_attribute_descriptions());
+        geometry
+    }
+
     pub fn get(&self) -> Option<(vk::Buffer, vk::Buffer, u32)> {
         self.rc.as_ref().map(|_| (self.vertex_buffer, self.index_buffer, self.index_count))
     }
 
-    pub fn get_binding_description(&self) -> vk::VertexInputBindingDescription {
-        self.vertex_binding_description
+    pub fn vertex_count(&self) -> u32 {
+        self.vertex_count
+    }
+
+    /// The buffer with the [`MorphDeltas`] bound at binding 1 if there is one.
+    pub fn morph_buffer(&self) -> Option<vk::Buffer> {
+        self.rc.as_ref().and(self.morph_buffer).map(|(buffer, _)| buffer)
+    }
+
+    /// The vertices at binding 0 followed by the morph deltas if there are any.
+    pub fn get_binding_descriptions(&self) -> &[vk::VertexInputBindingDescription] {
+        &self.vertex_binding_descriptions
     }
 
     pub fn get_attribute_descriptions(&self) -> &[vk::VertexInputAttributeDescription] {
@@ -65,11 +169,20 @@ impl Geometry {
     }
 
     pub unsafe fn cleanup(mut self, device: &Device) {
+        unsafe { self.release(device) };
+    }
+
+    /// Like [`Self::cleanup`] for geometry its owner keeps, which must not draw it anymore.
+    pub unsafe fn release(&mut self, device: &Device) {
         if self.rc.take().map(|rc| Rc::strong_count(&rc) == 1).unwrap_or(false) {
             log::debug!("cleaning Geometry");
             unsafe {
                 device.free_memory(self.index_buffer_memory, None);
                 device.destroy_buffer(self.index_buffer, None);
+                if let Some((buffer, memory)) = self.morph_buffer {
+                    device.free_memory(memory, None);
+                    device.destroy_buffer(buffer, None);
+                }
                 device.free_memory(self.vertex_buffer_memory, None);
                 device.destroy_buffer(self.vertex_buffer, None);
             }
@@ -139,3 +252,20 @@ impl Drop for Geometry {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn morph_deltas() {
+        let base = [[0., 0., 0.], [1., 2., 3.]];
+        let targets = vec![vec![[1., 0., 0.], [1., 2., 3.]], vec![[0., -1., 0.], [2., 2., 2.]]];
+        let deltas = MorphDeltas::from_targets(&base, &targets).unwrap();
+        assert_eq!(deltas[0].deltas, [[1., 0., 0.], [0., -1., 0.], [0.; 3], [0.; 3]]);
+        assert_eq!(deltas[1].deltas, [[0.; 3], [1., 0., -1.], [0.; 3], [0.; 3]]);
+
+        assert!(MorphDeltas::from_targets(&base, &[vec![[0.; 3]]]).is_err());
+        assert!(MorphDeltas::from_targets(&base, &vec![base.to_vec(); 5]).is_err());
+    }
+}
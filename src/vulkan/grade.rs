@@ -0,0 +1,256 @@
+use super::{
+    context::VkContext, resources::ResourceManager, sampler::SamplerConfig, texture::Texture,
+};
+
+use ash::{vk, Device};
+
+/// An offscreen image the gallery is resolved into to be color graded by a post pass
+/// before it is shown.
+///
+/// The multisampled color attachment is the one of the swapchain, so the render pass
+/// is compatible with the one of the swapchain and the pipelines can be used with both.
+/// The depth attachment is shared too unless it is sampled by the post pass.
+pub struct GradeTarget {
+    render_pass: vk::RenderPass,
+    resolve_texture: Texture,
+    depth_texture: Option<Texture>,
+    framebuffer: vk::Framebuffer,
+}
+
+impl GradeTarget {
+    /// Create the resolve image with `extent` and a framebuffer that renders into
+    /// `color_view` and `depth_view` and resolves into it.
+    ///
+    /// With `sampled_depth` the target has its own depth image instead of `depth_view`,
+    /// which is kept for the post pass, see [`Self::depth_texture`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+        color_view: vk::ImageView,
+        depth_view: vk::ImageView,
+        sampled_depth: bool,
+    ) -> Self {
+        let device = vk_context.device();
+        let render_pass =
+            Self::create_render_pass(device, format, msaa_samples, depth_format, sampled_depth);
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1);
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+        // the frame is filtered when it is scaled up from a dynamic resolution
+        let sampler = vk_context.sampler(SamplerConfig::CLAMP).unwrap();
+        let resolve_texture = Texture::new(image, memory, view, Some(sampler));
+
+        let depth_texture = sampled_depth.then(|| {
+            let (image, memory) = ResourceManager::create_image(
+                vk_context,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                extent,
+                1,
+                msaa_samples,
+                depth_format,
+                vk::ImageTiling::OPTIMAL,
+                vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            );
+            let aspect = vk::ImageAspectFlags::DEPTH;
+            let view = ResourceManager::create_image_view(device, image, 1, depth_format, aspect);
+            let sampler = vk_context.sampler(SamplerConfig::NEAREST).unwrap();
+            Texture::new(image, memory, view, Some(sampler))
+        });
+
+        let depth_view = depth_texture.map_or(depth_view, |texture| texture.view);
+        let attachments = [color_view, depth_view, view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+
+        Self {
+            render_pass,
+            resolve_texture,
+            depth_texture,
+            framebuffer,
+        }
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    /// The resolved image in the shader read only layout after the render pass.
+    pub fn resolve_texture(&self) -> Texture {
+        self.resolve_texture
+    }
+
+    /// The multisampled depth of the gallery in the depth read only layout after the render
+    /// pass, only if it was created with `sampled_depth`.
+    pub fn depth_texture(&self) -> Option<Texture> {
+        self.depth_texture
+    }
+
+    /// A render pass that resolves the gallery for sampling, compatible with the one of the
+    /// swapchain, and keeps the depth for sampling with `sampled_depth`.
+    pub fn create_render_pass(
+        device: &Device,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+        sampled_depth: bool,
+    ) -> vk::RenderPass {
+        let (depth_store_op, depth_final_layout) = if sampled_depth {
+            (vk::AttachmentStoreOp::STORE, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+        } else {
+            (vk::AttachmentStoreOp::DONT_CARE, vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+        };
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachement_desc = vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(depth_store_op)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(depth_final_layout);
+        let resolve_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        let attachment_descs = [
+            color_attachment_desc,
+            depth_attachement_desc,
+            resolve_attachment_desc,
+        ];
+
+        let color_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+
+        let subpass_descs = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .resolve_attachments(&resolve_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref)];
+
+        // the resolve image and the depth are sampled after the render pass
+        // and the next frame has to wait for them
+        let depth_tests = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        let subpass_deps = [
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                )
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | depth_tests)
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                ),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                )
+                .src_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                        | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ),
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    /// # Safety
+    ///
+    /// The target must not be in use anymore.
+    pub unsafe fn cleanup(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+        self.resolve_texture.destroy(device);
+        if let Some(mut texture) = self.depth_texture.take() {
+            texture.destroy(device);
+        }
+    }
+}
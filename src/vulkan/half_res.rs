@@ -0,0 +1,253 @@
+use super::{
+    context::VkContext, grade::GradeTarget, pipeline::Pipeline, resources::ResourceManager,
+    sampler::SamplerConfig, texture::Texture,
+};
+
+use ash::{vk, Device};
+
+const CLEAR_VALUES: [vk::ClearValue; 2] = [
+    // the art is blended over nothing, so the image holds its color premultiplied by its alpha
+    vk::ClearValue {
+        color: vk::ClearColorValue {
+            float32: [0.0, 0.0, 0.0, 0.0],
+        },
+    },
+    vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        },
+    },
+];
+
+/// 3D art that is drawn at half the resolution of the window and scaled up into the gallery,
+/// which trades sharpness for speed with expensive ray marchers.
+///
+/// The art is drawn into its own [`HalfResTarget`] before the gallery. Then `upscale` draws
+/// the cube of the art again in the gallery and samples the image bilinearly, so the art is
+/// hidden behind the environment like before.
+pub struct HalfResArt {
+    /// The index of the art pipeline.
+    pub index: usize,
+    /// Draws the image of the art with the vertex shader of the art and `upscale.frag`.
+    /// Its descriptor sets sample the image at binding 1.
+    pub upscale: Pipeline,
+    target: Option<HalfResTarget>,
+}
+
+impl HalfResArt {
+    pub fn new(index: usize, upscale: Pipeline) -> Self {
+        Self { index, upscale, target: None }
+    }
+
+    /// Creates the target for a window of `extent`, the old one must not be in use anymore.
+    pub fn resize(
+        &mut self,
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) {
+        let device = vk_context.device();
+        if let Some(mut target) = self.target.take() {
+            unsafe { target.cleanup(device) };
+        }
+        let extent = vk::Extent2D {
+            width: extent.width.div_ceil(2),
+            height: extent.height.div_ceil(2),
+        };
+        let target = HalfResTarget::new(vk_context, extent, format, msaa_samples, depth_format);
+        let image_infos = [vk::DescriptorImageInfo::default()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(target.resolve_texture.view)];
+        let sampler_infos = [vk::DescriptorImageInfo::default()
+            .sampler(target.resolve_texture.sampler.unwrap())];
+        for &set in self.upscale.descriptor_sets() {
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::SAMPLED_IMAGE)
+                    .image_info(&image_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(9)
+                    .descriptor_type(vk::DescriptorType::SAMPLER)
+                    .image_info(&sampler_infos),
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+        self.target = Some(target);
+    }
+
+    /// Whether the art can be drawn at half resolution with `art`, its pipeline.
+    pub fn is_ready(&self, art: &Pipeline) -> bool {
+        self.target.is_some()
+            && art.active
+            && !art.is_asleep()
+            && !art.waiting_for_shaders
+            && !self.upscale.waiting_for_shaders
+    }
+
+    /// Draws `art` with the uniforms of the first view of `extent` into the target in its own
+    /// render pass, with the parameters in `draw_command` if it is culled.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be recording outside of a render pass and the art must be ready,
+    /// see [`Self::is_ready`].
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        extent: vk::Extent2D,
+        art: &Pipeline,
+        draw_command: Option<(vk::Buffer, vk::DeviceSize)>,
+    ) {
+        let target = self.target.as_ref().unwrap();
+        // the view is smaller than the window with a dynamic resolution
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D {
+                width: extent.width.div_ceil(2).min(target.extent.width),
+                height: extent.height.div_ceil(2).min(target.extent.height),
+            },
+        };
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(target.render_pass)
+            .framebuffer(target.framebuffer)
+            .render_area(rect)
+            .clear_values(&CLEAR_VALUES);
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: rect.extent.width as f32,
+            height: rect.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe {
+            device.cmd_begin_render_pass(
+                buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            device.cmd_set_viewport(buffer, 0, &[viewport]);
+            device.cmd_set_scissor(buffer, 0, &[rect]);
+            match draw_command {
+                Some(draw_command) => {
+                    art.bind_to_cmd_buffer_indirect(device, buffer, frame, 0, draw_command)
+                }
+                None => art.bind_to_cmd_buffer(device, buffer, frame, 0),
+            }
+            device.cmd_end_render_pass(buffer);
+        }
+    }
+
+    /// Destroys the target, e.g. before the swapchain is recreated.
+    ///
+    /// # Safety
+    ///
+    /// The target must not be in use anymore.
+    pub unsafe fn cleanup(&mut self, device: &Device) {
+        if let Some(mut target) = self.target.take() {
+            unsafe { target.cleanup(device) };
+        }
+    }
+}
+
+/// The images half resolution art is drawn into.
+///
+/// The render pass is the one of [`GradeTarget`] with the same formats and samples as the one
+/// of the swapchain, so the art pipelines can be used with both.
+struct HalfResTarget {
+    render_pass: vk::RenderPass,
+    extent: vk::Extent2D,
+    color_texture: Texture,
+    depth_texture: Texture,
+    /// Sampled bilinearly by `upscale.frag`.
+    resolve_texture: Texture,
+    framebuffer: vk::Framebuffer,
+}
+
+impl HalfResTarget {
+    fn new(
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> Self {
+        let device = vk_context.device();
+        let render_pass =
+            GradeTarget::create_render_pass(device, format, msaa_samples, depth_format, false);
+        let create_texture = |samples, format, usage, aspect, sampler| {
+            let (image, memory) = ResourceManager::create_image(
+                vk_context,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+                extent,
+                1,
+                samples,
+                format,
+                vk::ImageTiling::OPTIMAL,
+                usage,
+            );
+            let view = ResourceManager::create_image_view(device, image, 1, format, aspect);
+            Texture::new(image, memory, view, sampler)
+        };
+        let color_texture = create_texture(
+            msaa_samples,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT,
+            vk::ImageAspectFlags::COLOR,
+            None,
+        );
+        let depth_texture = create_texture(
+            msaa_samples,
+            depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageAspectFlags::DEPTH,
+            None,
+        );
+        let resolve_texture = create_texture(
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+            Some(vk_context.sampler(SamplerConfig::CLAMP).unwrap()),
+        );
+
+        let attachments = [color_texture.view, depth_texture.view, resolve_texture.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+
+        Self {
+            render_pass,
+            extent,
+            color_texture,
+            depth_texture,
+            resolve_texture,
+            framebuffer,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The target must not be in use anymore.
+    unsafe fn cleanup(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+        self.color_texture.destroy(device);
+        self.depth_texture.destroy(device);
+        self.resolve_texture.destroy(device);
+    }
+}
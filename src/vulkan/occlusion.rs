@@ -0,0 +1,748 @@
+use crate::math::{Matrix4, Vector3, Vector4};
+use super::{
+    buffer,
+    context::VkContext,
+    descriptor::DescriptorAllocator,
+    geometry::Geometry,
+    resources::ResourceManager,
+    sampler::SamplerConfig,
+    shader::Shader,
+    vertex::{Vertex, VertexTangents},
+};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// Levels of the depth pyramid there are descriptor sets for, enough for 64K pixels.
+const MAX_LEVELS: usize = 16;
+const PYRAMID_FORMAT: vk::Format = vk::Format::R32_SFLOAT;
+/// The workgroup sizes of `depth_pyramid.comp` and `occlusion_cull.comp`.
+const PYRAMID_GROUP_SIZE: u32 = 8;
+const CULL_GROUP_SIZE: u32 = 64;
+
+/// An art piece as it is tested against the depth pyramid, the layout of `Art` in
+/// `occlusion_cull.comp`.
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct CullBox {
+    model: Matrix4,
+    bounds_min: Vector4,
+    bounds_max: Vector4,
+    index_count: u32,
+    cullable: u32,
+    _padding: [u32; 2],
+}
+
+impl CullBox {
+    /// A box with `bounds` in the space of `model` around the `index_count` indices of the art,
+    /// the art is always drawn without bounds.
+    pub fn new(model: Matrix4, bounds: Option<[Vector3; 2]>, index_count: u32) -> Self {
+        let [min, max] = bounds.unwrap_or_default();
+        Self {
+            model,
+            bounds_min: Vector4::from([min[0], min[1], min[2], 1.]),
+            bounds_max: Vector4::from([max[0], max[1], max[2], 1.]),
+            index_count,
+            cullable: bounds.is_some() as u32,
+            _padding: [0; 2],
+        }
+    }
+}
+
+/// The header of the input of `occlusion_cull.comp` that the boxes follow.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct CullHeader {
+    view_proj: Matrix4,
+    depth_size: [f32; 2],
+    levels: u32,
+    count: u32,
+}
+
+/// Culls the art hidden behind the environment on the GPU before the gallery is drawn.
+///
+/// The environment is drawn into a depth image that is reduced to a pyramid of the farthest
+/// depths, then the bounding box of every art piece is tested against the level of the
+/// pyramid its projection fits into. The result is an indirect draw per art piece that
+/// draws no instance if the box is behind the environment, so that a heavy raymarched
+/// piece behind a wall costs nothing.
+///
+/// The depth is drawn in the same frame from the same camera, so nothing pops in late,
+/// but only the environment occludes and a single view is culled.
+pub struct OcclusionCulling {
+    extent: vk::Extent2D,
+    depth_format: vk::Format,
+    depth_image: vk::Image,
+    depth_memory: vk::DeviceMemory,
+    depth_view: vk::ImageView,
+    render_pass: vk::RenderPass,
+    framebuffer: vk::Framebuffer,
+    /// Draws the depth of the environment with the layout of the gallery.
+    prepass: (vk::Pipeline, vk::PipelineLayout),
+    pyramid_image: vk::Image,
+    pyramid_memory: vk::DeviceMemory,
+    /// The view of every level followed by the view of the whole pyramid.
+    pyramid_views: Vec<vk::ImageView>,
+    sampler: vk::Sampler,
+    reduce: (vk::Pipeline, vk::PipelineLayout),
+    reduce_sets: Vec<vk::DescriptorSet>,
+    cull: (vk::Pipeline, vk::PipelineLayout),
+    cull_sets: Vec<vk::DescriptorSet>,
+    /// The boxes and then the draw commands of every frame in flight, one after another.
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the boxes of a frame aligned to the storage buffer offset alignment.
+    input_size: usize,
+    /// Size of the boxes and draw commands of a frame.
+    frame_size: usize,
+    slots: usize,
+}
+
+impl OcclusionCulling {
+    /// Creates the passes for `slots` art pieces, at least one, drawn into a target of `extent`.
+    ///
+    /// The shaders are the vertex shader drawing the depth of the environment
+    /// and the compute shaders building the pyramid and culling the art, they are cleaned up.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        descriptor_allocator: &mut DescriptorAllocator,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        [occluder_vert, pyramid_comp, cull_comp]: [Shader; 3],
+        extent: vk::Extent2D,
+        frames_in_flight: usize,
+        slots: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let device = vk_context.device();
+        let slots = slots.max(1);
+        let depth_format = vk_context
+            .find_supported_format(
+                &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM],
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE,
+            )
+            .context("No depth format can be sampled")?;
+        let render_pass = Self::create_render_pass(device, depth_format);
+
+        let module = |shader: &Shader| {
+            shader.module(device).context("The occlusion shaders are not compiled")
+        };
+        let prepass = Self::create_prepass_pipeline(
+            device,
+            render_pass,
+            descriptor_set_layout,
+            module(&occluder_vert)?,
+            &occluder_vert.entry_point(),
+        );
+
+        let sampled = |binding| vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::COMPUTE);
+        let storage = |binding, ty| vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(ty)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::COMPUTE);
+        let reduce_layout = descriptor_allocator.create_layout(device, &[
+            sampled(0),
+            storage(1, vk::DescriptorType::STORAGE_IMAGE),
+        ])?;
+        let cull_layout = descriptor_allocator.create_layout(device, &[
+            storage(0, vk::DescriptorType::STORAGE_BUFFER),
+            storage(1, vk::DescriptorType::STORAGE_BUFFER),
+            sampled(2),
+        ])?;
+        let push_constant_range = vk::PushConstantRange::default()
+            .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            .size(size_of::<[[i32; 2]; 2]>() as _);
+        let reduce = Self::create_compute_pipeline(
+            device,
+            reduce_layout,
+            Some(push_constant_range),
+            module(&pyramid_comp)?,
+            &pyramid_comp.entry_point(),
+        );
+        let cull = Self::create_compute_pipeline(
+            device,
+            cull_layout,
+            None,
+            module(&cull_comp)?,
+            &cull_comp.entry_point(),
+        );
+        for shader in [occluder_vert, pyramid_comp, cull_comp] {
+            shader.cleanup(device);
+        }
+        let reduce_sets = descriptor_allocator.allocate(device, reduce_layout, MAX_LEVELS)?;
+        let cull_sets = descriptor_allocator.allocate(device, cull_layout, frames_in_flight)?;
+
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let input_size = (size_of::<CullHeader>() + slots * size_of::<CullBox>())
+            .next_multiple_of(alignment);
+        let frame_size = (input_size + slots * size_of::<vk::DrawIndexedIndirectCommand>())
+            .next_multiple_of(alignment);
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            (frame_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map occlusion buffer")?
+        };
+
+        let mut culling = Self {
+            extent,
+            depth_format,
+            depth_image: vk::Image::null(),
+            depth_memory: vk::DeviceMemory::null(),
+            depth_view: vk::ImageView::null(),
+            render_pass,
+            framebuffer: vk::Framebuffer::null(),
+            prepass,
+            pyramid_image: vk::Image::null(),
+            pyramid_memory: vk::DeviceMemory::null(),
+            pyramid_views: Vec::new(),
+            sampler: vk_context.sampler(SamplerConfig::NEAREST)?,
+            reduce,
+            reduce_sets,
+            cull,
+            cull_sets,
+            buffer,
+            memory,
+            ptr,
+            input_size,
+            frame_size,
+            slots,
+        };
+        culling.create_images(vk_context, command_pool, queue, extent);
+        Ok(culling)
+    }
+
+    /// Recreates the depth and the pyramid for a target of `extent`.
+    ///
+    /// # Safety
+    ///
+    /// The images must not be in use anymore.
+    pub unsafe fn resize(
+        &mut self,
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        extent: vk::Extent2D,
+    ) {
+        unsafe { self.destroy_images(vk_context.device()) };
+        self.create_images(vk_context, command_pool, queue, extent);
+    }
+
+    /// Writes the boxes tested in the frame at index `frame` seen with `view_proj`,
+    /// the GPU must be done with it.
+    pub fn write(&mut self, frame: usize, view_proj: Matrix4, boxes: &[CullBox]) {
+        let count = boxes.len().min(self.slots);
+        let header = CullHeader {
+            view_proj,
+            depth_size: [self.extent.width as f32, self.extent.height as f32],
+            levels: level_extents(self.extent).len() as u32,
+            count: count as u32,
+        };
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.frame_size);
+            dst.cast::<CullHeader>().write_unaligned(header);
+            let dst = dst.add(size_of::<CullHeader>()).cast::<CullBox>();
+            std::ptr::copy_nonoverlapping(boxes.as_ptr(), dst, count);
+        }
+    }
+
+    /// The depth of the environment drawn last, which stays in
+    /// `DEPTH_STENCIL_READ_ONLY_OPTIMAL` until the next frame draws it again.
+    ///
+    /// The view changes when the depth is resized.
+    pub fn depth_view(&self) -> vk::ImageView {
+        self.depth_view
+    }
+
+    /// The buffer and offset of the indirect draw of the art at `slot` in the frame at `frame`.
+    pub fn draw_command(&self, frame: usize, slot: usize) -> (vk::Buffer, vk::DeviceSize) {
+        let offset = frame * self.frame_size
+            + self.input_size
+            + slot * size_of::<vk::DrawIndexedIndirectCommand>();
+        (self.buffer, offset as _)
+    }
+
+    /// Records the depth of the environment `geometry` drawn with `descriptor_set`, the reduction to the
+    /// pyramid and the culling that writes the draw commands of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded outside of a render pass, the draw commands are written by
+    /// compute shaders once it is done.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        geometry: Option<&Geometry>,
+        descriptor_set: vk::DescriptorSet,
+    ) {
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+        let clear_values = [vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+        }];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffer)
+            .render_area(rect)
+            .clear_values(&clear_values);
+        let viewport = vk::Viewport {
+            x: 0.,
+            y: 0.,
+            width: self.extent.width as f32,
+            height: self.extent.height as f32,
+            min_depth: 0.,
+            max_depth: 1.,
+        };
+        let compute_barrier = |src_access, dst_access| unsafe {
+            let memory_barrier = vk::MemoryBarrier::default()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access);
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[memory_barrier],
+                &[],
+                &[],
+            );
+        };
+
+        unsafe {
+            device.cmd_begin_render_pass(
+                buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            // without geometry nothing is occluded
+            if let Some((vertex_buffer, index_buffer, index_count)) =
+                geometry.and_then(Geometry::get)
+            {
+                let (pipeline, layout) = self.prepass;
+                device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::GRAPHICS, pipeline);
+                device.cmd_set_viewport(buffer, 0, &[viewport]);
+                device.cmd_set_scissor(buffer, 0, &[rect]);
+                device.cmd_bind_vertex_buffers(buffer, 0, &[vertex_buffer], &[0]);
+                device.cmd_bind_index_buffer(buffer, index_buffer, 0, vk::IndexType::UINT32);
+                // the environment is drawn with the uniforms of the first view
+                device.cmd_bind_descriptor_sets(
+                    buffer,
+                    vk::PipelineBindPoint::GRAPHICS,
+                    layout,
+                    0,
+                    &[descriptor_set],
+                    &[0, 0],
+                );
+                device.cmd_draw_indexed(buffer, index_count, 1, 0, 0, 0);
+            }
+            device.cmd_end_render_pass(buffer);
+
+            // the previous frame may still cull with the pyramid
+            compute_barrier(vk::AccessFlags::empty(), vk::AccessFlags::SHADER_WRITE);
+            let (pipeline, layout) = self.reduce;
+            device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::COMPUTE, pipeline);
+            let mut source = [self.extent.width, self.extent.height];
+            for (level, size) in level_extents(self.extent).into_iter().enumerate() {
+                device.cmd_bind_descriptor_sets(
+                    buffer,
+                    vk::PipelineBindPoint::COMPUTE,
+                    layout,
+                    0,
+                    &self.reduce_sets[level..=level],
+                    &[],
+                );
+                let sizes = [source, size].map(|[x, y]| [x as i32, y as i32]);
+                let bytes = std::slice::from_raw_parts(
+                    sizes.as_ptr().cast::<u8>(),
+                    size_of_val(&sizes),
+                );
+                device.cmd_push_constants(buffer, layout, vk::ShaderStageFlags::COMPUTE, 0, bytes);
+                device.cmd_dispatch(
+                    buffer,
+                    size[0].div_ceil(PYRAMID_GROUP_SIZE),
+                    size[1].div_ceil(PYRAMID_GROUP_SIZE),
+                    1,
+                );
+                compute_barrier(vk::AccessFlags::SHADER_WRITE, vk::AccessFlags::SHADER_READ);
+                source = size;
+            }
+
+            let (pipeline, layout) = self.cull;
+            device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::COMPUTE, pipeline);
+            device.cmd_bind_descriptor_sets(
+                buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                layout,
+                0,
+                &self.cull_sets[frame..=frame],
+                &[],
+            );
+            device.cmd_dispatch(buffer, (self.slots as u32).div_ceil(CULL_GROUP_SIZE), 1, 1);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Nothing may be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            self.destroy_images(device);
+            for (pipeline, layout) in [self.prepass, self.reduce, self.cull] {
+                device.destroy_pipeline(pipeline, None);
+                device.destroy_pipeline_layout(layout, None);
+            }
+            device.destroy_render_pass(self.render_pass, None);
+            device.unmap_memory(self.memory);
+            device.free_memory(self.memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+    }
+
+    /// Creates the depth, its framebuffer and the pyramid for `extent` and points the
+    /// descriptor sets at them.
+    fn create_images(
+        &mut self,
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        extent: vk::Extent2D,
+    ) {
+        let device = vk_context.device();
+        self.extent = extent;
+        let (depth_image, depth_memory) = ResourceManager::create_image(
+            vk_context,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            extent,
+            1,
+            vk::SampleCountFlags::TYPE_1,
+            self.depth_format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+        );
+        self.depth_image = depth_image;
+        self.depth_memory = depth_memory;
+        self.depth_view = ResourceManager::create_image_view(
+            device,
+            depth_image,
+            1,
+            self.depth_format,
+            vk::ImageAspectFlags::DEPTH,
+        );
+        let attachments = [self.depth_view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(self.render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        self.framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+
+        let levels = level_extents(extent);
+        let [width, height] = levels[0];
+        let (pyramid_image, pyramid_memory) = ResourceManager::create_image(
+            vk_context,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            vk::Extent2D { width, height },
+            levels.len() as u32,
+            vk::SampleCountFlags::TYPE_1,
+            PYRAMID_FORMAT,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+        );
+        // the pyramid is written and sampled in the general layout
+        ResourceManager::transition_image_layout(
+            vk_context,
+            command_pool,
+            queue,
+            pyramid_image,
+            levels.len() as u32,
+            PYRAMID_FORMAT,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            1,
+        );
+        self.pyramid_image = pyramid_image;
+        self.pyramid_memory = pyramid_memory;
+        self.pyramid_views = (0..levels.len())
+            .map(|level| (level as u32, 1))
+            .chain([(0, levels.len() as u32)])
+            .map(|(base_mip_level, level_count)| {
+                let view_info = vk::ImageViewCreateInfo::default()
+                    .image(pyramid_image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(PYRAMID_FORMAT)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        base_mip_level,
+                        level_count,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    });
+                unsafe { device.create_image_view(&view_info, None).unwrap() }
+            })
+            .collect();
+
+        for level in 0..levels.len().min(MAX_LEVELS) {
+            let source = match level {
+                0 => (self.depth_view, vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL),
+                _ => (self.pyramid_views[level - 1], vk::ImageLayout::GENERAL),
+            };
+            let source_infos = [vk::DescriptorImageInfo::default()
+                .image_view(source.0)
+                .image_layout(source.1)
+                .sampler(self.sampler)];
+            let level_infos = [vk::DescriptorImageInfo::default()
+                .image_view(self.pyramid_views[level])
+                .image_layout(vk::ImageLayout::GENERAL)];
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(self.reduce_sets[level])
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&source_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(self.reduce_sets[level])
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(&level_infos),
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+        for (frame, &set) in self.cull_sets.iter().enumerate() {
+            let offset = (frame * self.frame_size) as vk::DeviceSize;
+            let input_infos = [vk::DescriptorBufferInfo::default()
+                .buffer(self.buffer)
+                .offset(offset)
+                .range(self.input_size as _)];
+            let draw_infos = [vk::DescriptorBufferInfo::default()
+                .buffer(self.buffer)
+                .offset(offset + self.input_size as vk::DeviceSize)
+                .range((self.slots * size_of::<vk::DrawIndexedIndirectCommand>()) as _)];
+            let pyramid_infos = [vk::DescriptorImageInfo::default()
+                .image_view(*self.pyramid_views.last().unwrap())
+                .image_layout(vk::ImageLayout::GENERAL)
+                .sampler(self.sampler)];
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&input_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&draw_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(2)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&pyramid_infos),
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+    }
+
+    unsafe fn destroy_images(&mut self, device: &Device) {
+        unsafe {
+            for view in self.pyramid_views.drain(..) {
+                device.destroy_image_view(view, None);
+            }
+            device.destroy_image(self.pyramid_image, None);
+            device.free_memory(self.pyramid_memory, None);
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_image_view(self.depth_view, None);
+            device.destroy_image(self.depth_image, None);
+            device.free_memory(self.depth_memory, None);
+        }
+    }
+
+    /// A render pass that only clears and draws the depth, which is sampled afterwards.
+    fn create_render_pass(device: &Device, depth_format: vk::Format) -> vk::RenderPass {
+        let attachment_descs = [vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)];
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let subpass_descs = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .depth_stencil_attachment(&depth_attachment_ref)];
+        let fragment_tests = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        let fragment_shader = vk::PipelineStageFlags::FRAGMENT_SHADER;
+        let subpass_deps = [
+            // the depth of the previous frame may still be reduced or reflected in
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::COMPUTE_SHADER | fragment_shader)
+                .dst_stage_mask(fragment_tests)
+                .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(fragment_tests)
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::COMPUTE_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ),
+        ];
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    /// A pipeline without fragment shader that draws the positions of [`VertexTangents`].
+    fn create_prepass_pipeline(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        module: vk::ShaderModule,
+        entry_point: &std::ffi::CStr,
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let shader_stage_infos = [vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(module)
+            .name(entry_point)];
+        let binding_descs = [VertexTangents::get_binding_description()];
+        let attribute_descs = [VertexTangents::get_attribute_descriptions()[0]];
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&binding_descs)
+            .vertex_attribute_descriptions(&attribute_descs);
+        let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let viewport_info = vk::PipelineViewportStateCreateInfo::default()
+            .viewport_count(1)
+            .scissor_count(1);
+        let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::BACK)
+            .front_face(vk::FrontFace::COUNTER_CLOCKWISE);
+        let multisampling_info = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+        let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .max_depth_bounds(1.0);
+        let color_blending_info = vk::PipelineColorBlendStateCreateInfo::default();
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo::default()
+            .dynamic_states(&dynamic_states);
+
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+        let pipeline_infos = [vk::GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stage_infos)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly_info)
+            .viewport_state(&viewport_info)
+            .rasterization_state(&rasterizer_info)
+            .multisample_state(&multisampling_info)
+            .depth_stencil_state(&depth_stencil_info)
+            .color_blend_state(&color_blending_info)
+            .dynamic_state(&dynamic_state_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0)];
+        let pipeline = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+                .unwrap()[0]
+        };
+        (pipeline, layout)
+    }
+
+    fn create_compute_pipeline(
+        device: &Device,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        push_constant_range: Option<vk::PushConstantRange>,
+        module: vk::ShaderModule,
+        entry_point: &std::ffi::CStr,
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let ranges = push_constant_range.as_slice();
+            let layout_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(&layouts)
+                .push_constant_ranges(ranges);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+        let stage_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(entry_point);
+        let pipeline_infos = [vk::ComputePipelineCreateInfo::default()
+            .stage(stage_info)
+            .layout(layout)];
+        let pipeline = unsafe {
+            device.create_compute_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+                .unwrap()[0]
+        };
+        (pipeline, layout)
+    }
+}
+
+/// The sizes of the levels of the depth pyramid of a depth of `extent`, every level halves
+/// the previous one rounding up, starting at half the depth and ending at a single texel.
+fn level_extents(extent: vk::Extent2D) -> Vec<[u32; 2]> {
+    let mut size = [extent.width, extent.height];
+    let mut levels = Vec::new();
+    loop {
+        size = size.map(|side| side.div_ceil(2).max(1));
+        levels.push(size);
+        if size == [1, 1] || levels.len() == MAX_LEVELS {
+            return levels;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pyramid_levels() {
+        let levels = level_extents(vk::Extent2D { width: 1920, height: 1080 });
+        assert_eq!(levels[0], [960, 540]);
+        assert_eq!(levels[1], [480, 270]);
+        assert_eq!(levels[3], [120, 68]);
+        assert_eq!(levels.last(), Some(&[1, 1]));
+        assert_eq!(levels.len(), 11);
+        assert_eq!(level_extents(vk::Extent2D { width: 1, height: 1 }), [[1, 1]]);
+
+        assert_eq!(size_of::<CullHeader>(), 80);
+        assert_eq!(size_of::<CullBox>(), 112);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::text::TextBitmap;
+use super::{buffer, context::VkContext};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// The binding of the text in the descriptor sets of the environment.
+pub const TEXT_BINDING: u32 = 19;
+/// The most pixels of text, enough for 32 lines of 64 characters.
+const MAX_BITS: usize = 1 << 17;
+/// The width, the height and the color of the text before its bits.
+const HEADER_SIZE: usize = 3 * size_of::<u32>();
+
+/// Text drawn over the gallery by `overlay.frag`, e.g. the system info.
+///
+/// The text is rendered into one bit per pixel on the CPU by [`TextBitmap`] and written into
+/// a storage buffer of the frame, so it can change every frame without waiting for the GPU.
+pub struct TextOverlay {
+    /// The text of every frame in flight, one after another.
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the text of a frame aligned to the storage buffer offset alignment.
+    frame_size: usize,
+}
+
+impl TextOverlay {
+    pub fn new(vk_context: &VkContext, frames_in_flight: usize) -> Result<Self, anyhow::Error> {
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let frame_size = Self::range().next_multiple_of(alignment);
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            (frame_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            vk_context.device()
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map text buffer")?
+        };
+        Ok(Self { buffer, memory, ptr, frame_size })
+    }
+
+    fn range() -> usize {
+        HEADER_SIZE + MAX_BITS / 8
+    }
+
+    /// Whether `text` fits into the buffer.
+    pub fn fits(text: &TextBitmap) -> bool {
+        text.bits().len() * 32 <= MAX_BITS
+    }
+
+    pub fn descriptor_set_layout_binding<'a>() -> vk::DescriptorSetLayoutBinding<'a> {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(TEXT_BINDING)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+    }
+
+    /// Points the descriptor set of each frame in flight at the text of that frame.
+    pub fn update_descriptor_sets(&self, device: &Device, sets: &[vk::DescriptorSet]) {
+        for (frame, &set) in sets.iter().enumerate() {
+            let buffer_infos = [vk::DescriptorBufferInfo::default()
+                .buffer(self.buffer)
+                .offset((frame * self.frame_size) as _)
+                .range(Self::range() as _)];
+            let write = vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(TEXT_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&buffer_infos);
+            unsafe { device.update_descriptor_sets(&[write], &[]) };
+        }
+    }
+
+    /// Writes the text of the frame at index `frame` in `color`, the GPU must be done with it.
+    /// Text that does not fit is not written, see [`Self::fits`].
+    pub fn write(&mut self, frame: usize, text: &TextBitmap, color: [f32; 3]) {
+        if !Self::fits(text) {
+            return;
+        }
+        let [red, green, blue] = color.map(|channel| (channel.clamp(0., 1.) * 255.).round() as u32);
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.frame_size);
+            let [width, height] = text.size();
+            dst.cast::<[u32; 3]>().write_unaligned([width, height, red | green << 8 | blue << 16]);
+            let bits = text.bits();
+            let dst = dst.add(HEADER_SIZE).cast::<u32>();
+            std::ptr::copy_nonoverlapping(bits.as_ptr(), dst, bits.len());
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The buffer must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.unmap_memory(self.memory);
+            device.free_memory(self.memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+    }
+}
@@ -1,22 +1,51 @@
+use crate::math::{Matrix4, Vector3, Vector4};
+use crate::script::ArtMotion;
 use super::{
+    feedback::PipelineFeedback,
     geometry::Geometry,
     shader::Shader,
-    structs::PushConstants,
-    swapchain::SwapchainProperties,
+    skin::Skin,
+    structs::{ArtTime, ArtUniforms, Proximity},
 };
 
-use ash::{vk, Device};
+use ash::{ext::mesh_shader, khr::fragment_shading_rate, vk, Device};
 use std::ffi::CString;
 
+/// Sleeping art wakes up this much closer than the sleep distance.
+const WAKE_UP_RATIO: f32 = 0.9;
+
 #[derive(Debug, Clone, Copy)]
 pub struct PipelineConfig {
     pub cull_mode: vk::CullModeFlags,
+    /// Number of vertices per patch, only used if the pipeline has tessellation shaders.
+    pub patch_control_points: u32,
+    /// Number of workgroups dispatched, only used if the pipeline has a mesh shader.
+    pub mesh_group_count: [u32; 3],
+    /// Make the fragment shading rate a dynamic state so that it can be changed per draw.
+    ///
+    /// Requires `VK_KHR_fragment_shading_rate`.
+    pub variable_shading_rate: bool,
+    /// Number of vertices drawn if the pipeline has neither geometry nor a mesh shader.
+    pub vertex_count: u32,
+    /// Run the fragment shader for at least this fraction of the samples of a pixel
+    /// instead of once per pixel, which antialiases the inside of shaders with MSAA.
+    ///
+    /// Requires the `sample_rate_shading` feature.
+    pub min_sample_shading: Option<f32>,
+    /// Dynamic offset of the art uniforms of this pipeline, see `ArtUniforms`.
+    pub art_uniform_offset: u32,
 }
 
 impl Default for PipelineConfig {
     fn default() -> Self {
         Self {
             cull_mode: vk::CullModeFlags::BACK,
+            patch_control_points: 3,
+            mesh_group_count: [1, 1, 1],
+            variable_shading_rate: false,
+            vertex_count: 3,
+            min_sample_shading: None,
+            art_uniform_offset: 0,
         }
     }
 }
@@ -29,8 +58,37 @@ pub struct Pipeline {
     pub active: bool,
     pub waiting_for_shaders: bool,
     config: PipelineConfig,
-    shaders: [Shader; 2],
-    push_constants: Option<PushConstants>,
+    shaders: Vec<Shader>,
+    fallback_shaders: Option<[Shader; 2]>,
+    uses_fallback: bool,
+    art_uniforms: Option<ArtUniforms>,
+    art_time: ArtTime,
+    /// The local time of the art without the offset of `art_time`.
+    local_time: f32,
+    /// The global time of the last art uniforms, the local time advances from there.
+    last_time: Option<f32>,
+    /// Whether the camera was within the restart distance of `art_time`.
+    is_near: bool,
+    proximity: Proximity,
+    /// Seconds the camera has been within the nearby distance of `proximity`.
+    nearby_time: Option<f32>,
+    /// Whether the camera is beyond the sleep distance of `proximity`.
+    is_asleep: bool,
+    /// Moves the art on top of its model matrix, see [`crate::script::Script`].
+    motion: Option<ArtMotion>,
+    /// Animates the joints of skinned art, see [`super::skin::JointBuffer`].
+    skin: Option<Skin>,
+    /// The box around the art in its model space if it may be culled,
+    /// see [`super::occlusion::OcclusionCulling`].
+    bounds: Option<[Vector3; 2]>,
+    mesh_shader: Option<mesh_shader::Device>,
+    fragment_shading_rate: Option<fragment_shading_rate::Device>,
+    shading_rate: vk::Extent2D,
+    /// Logs how long the pipeline took to create and its statistics.
+    feedback: Option<PipelineFeedback>,
+    /// Whether the shaders changed since the feedback was logged, so that it is not logged
+    /// again whenever the pipeline is recreated for a new swapchain.
+    feedback_pending: bool,
 }
 
 impl Pipeline {
@@ -38,38 +96,288 @@ impl Pipeline {
     pub fn new(
         name: String,
         device: &Device,
-        swapchain_properties: SwapchainProperties,
         msaa_samples: vk::SampleCountFlags,
         render_pass: vk::RenderPass,
         descriptor_set_layout: vk::DescriptorSetLayout,
         descriptor_sets: Vec<vk::DescriptorSet>,
-        geometry: Geometry,
+        geometry: Option<Geometry>,
         config: PipelineConfig,
-        shaders: [Shader; 2],
-        push_constants: Option<PushConstants>,
+        shaders: Vec<Shader>,
+        fallback_shaders: Option<[Shader; 2]>,
+        art_uniforms: Option<ArtUniforms>,
     ) -> Result<Self, anyhow::Error> {
+        let stages = shaders.iter()
+            .fold(vk::ShaderStageFlags::empty(), |stages, shader| stages | shader.stage_flags());
+        let tessellation = vk::ShaderStageFlags::TESSELLATION_CONTROL
+            | vk::ShaderStageFlags::TESSELLATION_EVALUATION;
+        if stages.intersects(tessellation) && !stages.contains(tessellation) {
+            anyhow::bail!("Pipeline {name} needs both or none of the tessellation shaders");
+        }
+
         let mut pipeline = Self {
             name,
-            geometry: Some(geometry),
+            geometry,
             pipeline_and_layout: None,
             active: true,
             waiting_for_shaders: true,
             descriptor_sets,
             config,
             shaders,
-            push_constants,
+            fallback_shaders,
+            uses_fallback: false,
+            art_uniforms,
+            art_time: ArtTime::default(),
+            local_time: 0.,
+            last_time: None,
+            is_near: false,
+            proximity: Proximity::default(),
+            nearby_time: None,
+            is_asleep: false,
+            motion: None,
+            skin: None,
+            bounds: None,
+            mesh_shader: None,
+            fragment_shading_rate: None,
+            shading_rate: vk::Extent2D { width: 1, height: 1 },
+            feedback: None,
+            feedback_pending: true,
         };
-        pipeline.recreate(device, swapchain_properties, msaa_samples, render_pass, descriptor_set_layout);
+        pipeline.recreate(device, msaa_samples, render_pass, descriptor_set_layout);
         Ok(pipeline)
     }
 
+    /// Sets the loader used to draw pipelines with a mesh shader.
+    pub fn set_mesh_shader(&mut self, mesh_shader: Option<mesh_shader::Device>) {
+        self.mesh_shader = mesh_shader;
+    }
+
+    /// Sets the loader used to change the shading rate of pipelines with a variable shading rate.
+    pub fn set_fragment_shading_rate(
+        &mut self,
+        fragment_shading_rate: Option<fragment_shading_rate::Device>,
+    ) {
+        self.fragment_shading_rate = fragment_shading_rate;
+    }
+
+    /// Sets what is logged about the creation of the pipeline the next time it is created.
+    pub fn set_feedback(&mut self, feedback: Option<PipelineFeedback>) {
+        self.feedback = feedback;
+    }
+
+    /// Returns true if the shading rate can be changed with `set_shading_rate`.
+    pub fn has_variable_shading_rate(&self) -> bool {
+        self.config.variable_shading_rate && self.fragment_shading_rate.is_some()
+    }
+
+    pub fn shading_rate(&self) -> vk::Extent2D {
+        self.shading_rate
+    }
+
+    /// Sets the size in pixels of the fragments, the command buffers need to be recorded again.
+    pub fn set_shading_rate(&mut self, shading_rate: vk::Extent2D) {
+        self.shading_rate = shading_rate;
+    }
+
+    /// Sets `PipelineConfig::min_sample_shading`, the pipeline needs to be recreated.
+    pub fn set_min_sample_shading(&mut self, min_sample_shading: Option<f32>) {
+        self.config.min_sample_shading = min_sample_shading;
+    }
+
+    /// The model matrix of the art uniforms if there are any.
+    pub fn model_matrix(&self) -> Option<Matrix4> {
+        self.art_uniforms.map(|art_uniforms| art_uniforms.model)
+    }
+
+    /// Moves the art, does nothing if there are no art uniforms.
+    pub fn set_model_matrix(&mut self, model: Matrix4) {
+        if let Some(art_uniforms) = self.art_uniforms.as_mut() {
+            art_uniforms.model = model;
+        }
+    }
+
+    /// Sets the free parameters of the art, does nothing if there are no art uniforms.
+    pub fn set_params(&mut self, params: Vector4) {
+        if let Some(art_uniforms) = self.art_uniforms.as_mut() {
+            art_uniforms.params = params;
+        }
+    }
+
+    /// Moves the art uniforms of the pipeline in the uniform buffer, the command buffers
+    /// have to be recorded again.
+    pub fn set_art_uniform_offset(&mut self, offset: u32) {
+        self.config.art_uniform_offset = offset;
+    }
+
+    /// Sets the aspect ratio of the image the art samples, does nothing if there are no
+    /// art uniforms.
+    pub fn set_image_aspect(&mut self, image_aspect: f32) {
+        if let Some(art_uniforms) = self.art_uniforms.as_mut() {
+            art_uniforms.image_aspect = image_aspect;
+        }
+    }
+
+    /// Sets the motion of a script that is applied on top of the model matrix and the params
+    /// in the art uniforms.
+    pub fn set_motion(&mut self, motion: Option<ArtMotion>) {
+        self.motion = motion;
+    }
+
+    pub fn skin(&self) -> Option<&Skin> {
+        self.skin.as_ref()
+    }
+
+    pub fn set_skin(&mut self, skin: Option<Skin>) {
+        self.skin = skin;
+    }
+
+    pub fn bounds(&self) -> Option<[Vector3; 2]> {
+        self.bounds
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<[Vector3; 2]>) {
+        self.bounds = bounds;
+    }
+
+    pub fn art_time(&self) -> ArtTime {
+        self.art_time
+    }
+
+    pub fn set_art_time(&mut self, art_time: ArtTime) {
+        self.art_time = art_time;
+    }
+
+    pub fn set_proximity(&mut self, proximity: Proximity) {
+        self.proximity = proximity;
+        self.is_asleep = false;
+    }
+
+    /// Whether the art is too far from the camera to be drawn, see [`Self::update_sleep`].
+    pub fn is_asleep(&self) -> bool {
+        self.is_asleep
+    }
+
+    /// Puts the art to sleep or wakes it up for the camera seen with `view_matrix`, see
+    /// [`Proximity::sleep_distance`].
+    ///
+    /// Returns true if it fell asleep or woke up, the command buffers have to be recorded again.
+    pub fn update_sleep(&mut self, view_matrix: Matrix4) -> bool {
+        let (Some(art_uniforms), Some(sleep_distance)) =
+            (self.art_uniforms, self.proximity.sleep_distance)
+        else {
+            return false;
+        };
+        let distance = camera_distance(art_uniforms.model, view_matrix);
+        let threshold = if self.is_asleep {
+            sleep_distance * WAKE_UP_RATIO
+        } else {
+            sleep_distance
+        };
+        let is_asleep = distance > threshold;
+        let changed = is_asleep != self.is_asleep;
+        self.is_asleep = is_asleep;
+        changed
+    }
+
+    /// The art uniforms at the global `time` in seconds seen with `view_matrix`
+    /// if there are any.
+    ///
+    /// Advances the local time of the art, so it has to be called once per frame.
+    pub fn art_uniforms(&mut self, time: f32, view_matrix: Matrix4) -> Option<ArtUniforms> {
+        let art_uniforms = self.art_uniforms?;
+        // the global time can go backwards when it is scrubbed
+        let delta = self.last_time.map_or(0., |last_time| time - last_time);
+        self.last_time = Some(time);
+        self.local_time = (self.local_time + delta * self.art_time.speed).max(0.);
+        let distance = camera_distance(art_uniforms.model, view_matrix);
+        if let Some(restart_distance) = self.art_time.restart_distance {
+            let is_near = distance < restart_distance;
+            if is_near && !self.is_near {
+                self.local_time = 0.;
+            }
+            self.is_near = is_near;
+        }
+        let nearby = (1. - distance / self.proximity.nearby_distance).max(0.);
+        self.nearby_time = (nearby > 0.)
+            .then(|| self.nearby_time.map_or(0., |nearby_time| (nearby_time + delta).max(0.)));
+        let quality = match self.proximity.low_quality_distance {
+            Some(low_quality_distance) if distance > low_quality_distance => 0,
+            _ => art_uniforms.quality,
+        };
+        let (model, params, morph_weights) = match self.motion {
+            Some(motion) => (
+                motion.model_matrix(art_uniforms.model),
+                motion.params.unwrap_or(art_uniforms.params),
+                motion.morph_weights.unwrap_or(art_uniforms.morph_weights),
+            ),
+            None => (art_uniforms.model, art_uniforms.params, art_uniforms.morph_weights),
+        };
+        Some(ArtUniforms {
+            model,
+            params,
+            morph_weights,
+            time: self.art_time.offset + self.local_time,
+            quality,
+            nearby,
+            nearby_time: self.nearby_time.unwrap_or_default(),
+            ..art_uniforms
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the vertex shader of this pipeline if it has one.
+    pub fn vertex_shader(&self) -> Option<&Shader> {
+        self.shaders.iter().find(|shader| shader.stage_flags() == vk::ShaderStageFlags::VERTEX)
+    }
+
+    /// Returns the shaders of this pipeline in the order of the stages.
+    pub fn shaders(&self) -> &[Shader] {
+        &self.shaders
+    }
+
+    /// Returns the fragment shader of this pipeline if it has one.
+    pub fn fragment_shader(&self) -> Option<&Shader> {
+        self.shaders.iter().find(|shader| shader.stage_flags() == vk::ShaderStageFlags::FRAGMENT)
+    }
+
+    /// Replaces the shader of the same stage as `shader` and returns the old one,
+    /// the pipeline has to be recreated to use it.
+    pub fn replace_shader(&mut self, shader: Shader) -> Option<Shader> {
+        let stage = shader.stage_flags();
+        self.feedback_pending = true;
+        self.shaders.iter_mut()
+            .find(|old| old.stage_flags() == stage)
+            .map(|old| std::mem::replace(old, shader))
+    }
+
+    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet] {
+        &self.descriptor_sets
+    }
+
+    /// Returns the errors of all shaders of this pipeline that failed to compile.
+    pub fn compile_errors(&self) -> Vec<String> {
+        self.shaders.iter().filter_map(|shader| shader.error()).collect()
+    }
+
+    /// Returns true if the pipeline was built with the fallback shaders
+    /// and its own shaders are now ready to replace them.
+    pub fn fallback_replaceable(&self) -> bool {
+        self.uses_fallback && self.shaders.iter().all(|shader| shader.is_ready())
+    }
+
     pub fn has_changed(&self) -> bool {
         self.shaders.iter().any(|shader| shader.code_has_changed())
     }
 
     pub fn reload_shaders(&mut self, device: &Device, forced: bool) -> bool {
-        if self.shaders[0].reload(device, forced) | self.shaders[1].reload(device, forced) {
+        let reloading = self.shaders.iter()
+            .fold(false, |reloading, shader| shader.reload(device, forced) | reloading);
+        if reloading {
             self.waiting_for_shaders = true;
+            self.feedback_pending = true;
+            self.local_time = 0.;
             unsafe {
                 self.cleanup_pip(device);
             }
@@ -82,7 +390,6 @@ impl Pipeline {
     pub fn recreate(
         &mut self,
         device: &Device,
-        swapchain_properties: SwapchainProperties,
         msaa_samples: vk::SampleCountFlags,
         render_pass: vk::RenderPass,
         descriptor_set_layout: vk::DescriptorSetLayout,
@@ -94,18 +401,43 @@ impl Pipeline {
             }
         }
 
-        let shader_modules = [self.shaders[0].module(device), self.shaders[1].module(device)];
-        if let [Some(vsm), Some(fsm)] = shader_modules {
+        let mut shaders: &[Shader] = &self.shaders;
+        let mut shader_modules = shaders.iter()
+            .map(|shader| shader.module(device))
+            .collect::<Option<Vec<_>>>();
+        self.uses_fallback = false;
+        // the fallback shaders need vertex input
+        let can_fallback = self.geometry.is_some() && !self.compile_errors().is_empty();
+        if shader_modules.is_none() && can_fallback {
+            if let Some(fallback) = self.fallback_shaders.as_ref() {
+                shaders = fallback;
+                shader_modules = shaders.iter()
+                    .map(|shader| shader.module(device))
+                    .collect::<Option<Vec<_>>>();
+                self.uses_fallback = true;
+            }
+        }
+        if let Some(shader_modules) = shader_modules {
+            let stages = shaders.iter().zip(shader_modules)
+                .map(|(shader, module)| (shader.stage_flags(), module, shader.entry_point()))
+                .collect::<Vec<_>>();
             self.waiting_for_shaders = false;
+            // the feedback of the fallback shaders tells nothing about the art
+            let feedback = self.feedback.as_ref()
+                .filter(|_| self.feedback_pending && !self.uses_fallback);
+            if feedback.is_some() {
+                self.feedback_pending = false;
+            }
             self.pipeline_and_layout = Some(Self::create_pipeline(
                 device,
-                swapchain_properties,
+                &self.name,
+                feedback,
                 self.config,
                 msaa_samples,
                 render_pass,
                 descriptor_set_layout,
-                [vsm, fsm],
-                self.geometry.as_ref().unwrap(),
+                &stages,
+                self.geometry.as_ref(),
             ));
         } else {
             self.waiting_for_shaders = true;
@@ -117,7 +449,51 @@ impl Pipeline {
         device: &Device,
         buffer: vk::CommandBuffer,
         i: usize,
+        ubo_offset: u32,
     ) {
+        let index_count = unsafe { self.bind(device, buffer, i, ubo_offset) };
+        match self.mesh_shader.as_ref() {
+            Some(mesh_shader) => unsafe {
+                let [x, y, z] = self.config.mesh_group_count;
+                mesh_shader.cmd_draw_mesh_tasks(buffer, x, y, z);
+            },
+            None if self.geometry.is_some() => unsafe {
+                device.cmd_draw_indexed(buffer, index_count, 1, 0, 0, 0);
+            },
+            None => unsafe {
+                // the vertices are generated in the vertex shader
+                device.cmd_draw(buffer, self.config.vertex_count, 1, 0, 0);
+            },
+        }
+    }
+
+    /// Like [`Self::bind_to_cmd_buffer`] but draws the indexed geometry with the parameters
+    /// in `draw_command` written on the GPU, pipelines without geometry are drawn directly.
+    pub unsafe fn bind_to_cmd_buffer_indirect(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        i: usize,
+        ubo_offset: u32,
+        (draw_buffer, offset): (vk::Buffer, vk::DeviceSize),
+    ) {
+        if self.geometry.is_none() || self.mesh_shader.is_some() {
+            return unsafe { self.bind_to_cmd_buffer(device, buffer, i, ubo_offset) };
+        }
+        unsafe {
+            self.bind(device, buffer, i, ubo_offset);
+            device.cmd_draw_indexed_indirect(buffer, draw_buffer, offset, 1, 0);
+        }
+    }
+
+    /// Binds the pipeline with its geometry and descriptor set, returns the number of indices.
+    unsafe fn bind(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        i: usize,
+        ubo_offset: u32,
+    ) -> u32 {
         let (pip_pip, pip_layout) = self.get().expect("pipeline must be initalized");
         unsafe {
             device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::GRAPHICS, pip_pip);
@@ -126,6 +502,9 @@ impl Pipeline {
             let (vertex_buffer, index_buffer, index_count) = geometry.get().unwrap();
             unsafe {
                 device.cmd_bind_vertex_buffers(buffer, 0, &[vertex_buffer], &[0]);
+                if let Some(morph_buffer) = geometry.morph_buffer() {
+                    device.cmd_bind_vertex_buffers(buffer, 1, &[morph_buffer], &[0]);
+                }
                 device.cmd_bind_index_buffer(buffer, index_buffer, 0, vk::IndexType::UINT32);
             }
             index_count
@@ -133,18 +512,6 @@ impl Pipeline {
             0
         };
 
-        fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-            unsafe {
-                ::core::slice::from_raw_parts((p as *const T) as *const u8, size_of::<T>())
-            }
-        }
-        if let Some(push_constants) = self.push_constants.as_ref() {
-            let cnsts = any_as_u8_slice(push_constants);
-            unsafe {
-                device.cmd_push_constants(buffer, pip_layout, vk::ShaderStageFlags::VERTEX, 0, cnsts);
-            }
-        }
-
         unsafe {
             device.cmd_bind_descriptor_sets(
                 buffer,
@@ -152,10 +519,24 @@ impl Pipeline {
                 pip_layout,
                 0,
                 &self.descriptor_sets[i..=i],
-                &[],
+                &[ubo_offset, self.config.art_uniform_offset],
             );
-            device.cmd_draw_indexed(buffer, index_count, 1, 0, 0, 0);
         }
+        if let Some(fragment_shading_rate) = self.fragment_shading_rate.as_ref() {
+            if self.config.variable_shading_rate {
+                // keep the rate of the pipeline, there are no per primitive or attachment rates
+                let combiner_ops = [vk::FragmentShadingRateCombinerOpKHR::KEEP; 2];
+                // ash has no wrapper for this command, so call the function pointer directly
+                unsafe {
+                    (fragment_shading_rate.fp().cmd_set_fragment_shading_rate_khr)(
+                        buffer,
+                        &self.shading_rate,
+                        &combiner_ops,
+                    );
+                }
+            }
+        }
+        index_count
     }
 
     pub fn get(&self) -> Option<(vk::Pipeline, vk::PipelineLayout)> {
@@ -177,7 +558,7 @@ impl Pipeline {
         if let Some(geometry) = self.geometry.take() {
             unsafe { geometry.cleanup(device); }
         }
-        for shader in self.shaders.iter_mut() {
+        for shader in self.shaders.iter_mut().chain(self.fallback_shaders.iter_mut().flatten()) {
             shader.cleanup(device);
         }
     }
@@ -185,52 +566,52 @@ impl Pipeline {
     #[allow(clippy::too_many_arguments)]
     fn create_pipeline(
         device: &Device,
-        swapchain_properties: SwapchainProperties,
+        name: &str,
+        feedback: Option<&PipelineFeedback>,
         config: PipelineConfig,
         msaa_samples: vk::SampleCountFlags,
         render_pass: vk::RenderPass,
         descriptor_set_layout: vk::DescriptorSetLayout,
-        shader_modules: [vk::ShaderModule; 2],
-        geometry: &Geometry,
+        stages: &[(vk::ShaderStageFlags, vk::ShaderModule, CString)],
+        geometry: Option<&Geometry>,
     ) -> (vk::Pipeline, vk::PipelineLayout) {
-        let entry_point_name = CString::new("main").unwrap();
-        let vertex_shader_state_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::VERTEX)
-            .module(shader_modules[0])
-            .name(&entry_point_name);
-        let fragment_shader_state_info = vk::PipelineShaderStageCreateInfo::default()
-            .stage(vk::ShaderStageFlags::FRAGMENT)
-            .module(shader_modules[1])
-            .name(&entry_point_name);
-        let shader_states_infos = [vertex_shader_state_info, fragment_shader_state_info];
-
-        let vertex_binding_descs = [geometry.get_binding_description()];
-        let vertex_attribute_descs = geometry.get_attribute_descriptions();
+        let shader_states_infos = stages.iter()
+            .map(|(stage, module, entry_point)| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(entry_point)
+            })
+            .collect::<Vec<_>>();
+        let has_tessellation = stages.iter()
+            .any(|(stage, _, _)| *stage == vk::ShaderStageFlags::TESSELLATION_CONTROL);
+
+        let vertex_binding_descs = geometry
+            .map(|geometry| geometry.get_binding_descriptions())
+            .unwrap_or_default();
+        let vertex_attribute_descs = geometry
+            .map(|geometry| geometry.get_attribute_descriptions())
+            .unwrap_or_default();
         let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
-            .vertex_binding_descriptions(&vertex_binding_descs)
+            .vertex_binding_descriptions(vertex_binding_descs)
             .vertex_attribute_descriptions(vertex_attribute_descs);
 
+        let topology = if has_tessellation {
+            vk::PrimitiveTopology::PATCH_LIST
+        } else {
+            vk::PrimitiveTopology::TRIANGLE_LIST
+        };
         let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
+            .topology(topology)
             .primitive_restart_enable(false);
 
-        let viewport = vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: swapchain_properties.extent.width as _,
-            height: swapchain_properties.extent.height as _,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        };
-        let viewports = [viewport];
-        let scissor = vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: swapchain_properties.extent,
-        };
-        let scissors = [scissor];
+        let tessellation_info = vk::PipelineTessellationStateCreateInfo::default()
+            .patch_control_points(config.patch_control_points);
+
+        // the viewport and scissor are dynamic so that a frame can be split into several views
         let viewport_info = vk::PipelineViewportStateCreateInfo::default()
-            .viewports(&viewports)
-            .scissors(&scissors);
+            .viewport_count(1)
+            .scissor_count(1);
 
         let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
             .depth_clamp_enable(false)
@@ -245,9 +626,9 @@ impl Pipeline {
             .depth_bias_slope_factor(0.0);
 
         let multisampling_info = vk::PipelineMultisampleStateCreateInfo::default()
-            .sample_shading_enable(false)
+            .sample_shading_enable(config.min_sample_shading.is_some())
             .rasterization_samples(msaa_samples)
-            .min_sample_shading(1.0)
+            .min_sample_shading(config.min_sample_shading.unwrap_or(1.0))
             .alpha_to_coverage_enable(false)
             .alpha_to_one_enable(false);
 
@@ -281,36 +662,71 @@ impl Pipeline {
 
         let layout = {
             let layouts = [descriptor_set_layout];
-            let ranges = [PushConstants::get_push_constant_range()];
-            let layout_info = vk::PipelineLayoutCreateInfo::default()
-                .set_layouts(&layouts)
-                .push_constant_ranges(&ranges);
+            let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
             unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
         };
 
-        let pipeline_info = vk::GraphicsPipelineCreateInfo::default()
+        let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        if config.variable_shading_rate {
+            dynamic_states.push(vk::DynamicState::FRAGMENT_SHADING_RATE_KHR);
+        }
+        let dynamic_state_info = vk::PipelineDynamicStateCreateInfo::default()
+            .dynamic_states(&dynamic_states);
+
+        let has_mesh = stages.iter()
+            .any(|(stage, _, _)| *stage == vk::ShaderStageFlags::MESH_EXT);
+        let mut pipeline_info = vk::GraphicsPipelineCreateInfo::default()
             .stages(&shader_states_infos)
-            .vertex_input_state(&vertex_input_info)
-            .input_assembly_state(&input_assembly_info)
             .viewport_state(&viewport_info)
             .rasterization_state(&rasterizer_info)
             .multisample_state(&multisampling_info)
             .depth_stencil_state(&depth_stencil_info)
             .color_blend_state(&color_blending_info)
+            .dynamic_state(&dynamic_state_info)
             .layout(layout)
             .render_pass(render_pass)
             .subpass(0);
+        if !has_mesh {
+            // mesh shader pipelines have no vertex input
+            pipeline_info = pipeline_info
+                .vertex_input_state(&vertex_input_info)
+                .input_assembly_state(&input_assembly_info);
+        }
+        if has_tessellation {
+            pipeline_info = pipeline_info.tessellation_state(&tessellation_info);
+        }
+        let mut creation_feedback = vk::PipelineCreationFeedback::default();
+        let mut stage_feedbacks = vec![vk::PipelineCreationFeedback::default(); stages.len()];
+        let mut creation_feedback_info = vk::PipelineCreationFeedbackCreateInfo::default()
+            .pipeline_creation_feedback(&mut creation_feedback)
+            .pipeline_stage_creation_feedbacks(&mut stage_feedbacks);
+        if let Some(feedback) = feedback {
+            pipeline_info = pipeline_info.flags(feedback.create_flags());
+            if feedback.creation {
+                pipeline_info = pipeline_info.push_next(&mut creation_feedback_info);
+            }
+        }
         let pipeline_infos = [pipeline_info];
 
         let pipeline = unsafe {
             device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
                 .unwrap()[0]
         };
+        if let Some(feedback) = feedback {
+            let creation = feedback.creation.then_some((creation_feedback, &stage_feedbacks[..]));
+            let stages = stages.iter().map(|(stage, _, _)| *stage).collect::<Vec<_>>();
+            unsafe { feedback.log(name, pipeline, creation, &stages) };
+        }
 
         (pipeline, layout)
     }
 }
 
+/// The distance of the camera seen with `view_matrix` to the center of the art at `model`.
+fn camera_distance(model: Matrix4, view_matrix: Matrix4) -> f32 {
+    (model[3] * view_matrix).resize::<3>().magnitude()
+}
+
 impl Drop for Pipeline {
     fn drop(&mut self) {
         if !std::thread::panicking() && self.pipeline_and_layout.is_some() {
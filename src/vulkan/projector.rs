@@ -0,0 +1,412 @@
+use super::{
+    context::VkContext,
+    pipeline::Pipeline,
+    swapchain::{SwapchainProperties, SwapchainSupportDetails},
+};
+
+use anyhow::{anyhow, Context};
+use ash::{khr::swapchain as khr_swapchain, vk, Device};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use winit::window::Window;
+
+/// A second window that shows the fragment shader of a single art piece over its whole surface,
+/// e.g. to project it onto another display.
+///
+/// It has its own surface and swapchain but is drawn together with the frames of the gallery
+/// using their descriptor sets. The art is drawn without multisampling or depth straight into
+/// the swapchain images, so the pipeline has to be created with [`Projector::render_pass`].
+pub struct Projector {
+    surface_khr: vk::SurfaceKHR,
+    swapchain: khr_swapchain::Device,
+    swapchain_khr: vk::SwapchainKHR,
+    properties: SwapchainProperties,
+    image_views: Vec<vk::ImageView>,
+    render_pass: vk::RenderPass,
+    framebuffers: Vec<vk::Framebuffer>,
+    command_pool: vk::CommandPool,
+    /// One command buffer per frame in flight, recorded again every time the frame is drawn.
+    command_buffers: Vec<vk::CommandBuffer>,
+    image_available_semaphores: Vec<vk::Semaphore>,
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    /// Size of the window in pixels.
+    dimensions: [u32; 2],
+    /// Index of the shown art in the pipelines of the app.
+    pub art: usize,
+    /// Draws the fragment shader of the art, shares its shaders with the art.
+    pub pipeline: Option<Pipeline>,
+    /// The time the art was first drawn at, its time starts at zero.
+    pub start_time: Option<f32>,
+    /// True if the swapchain is out of date and has to be recreated before drawing.
+    pub dirty: bool,
+}
+
+impl Projector {
+    /// Creates a surface for `window` and a swapchain of `dimensions` with resources for
+    /// `frames_in_flight` frames. The pipeline is left to be created by the caller.
+    pub fn new(
+        vk_context: &VkContext,
+        window: &Window,
+        dimensions: [u32; 2],
+        frames_in_flight: usize,
+        art: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let surface_khr = unsafe {
+            ash_window::create_surface(
+                vk_context.entry(),
+                vk_context.instance(),
+                window.display_handle()?.as_raw(),
+                window.window_handle()?.as_raw(),
+                None,
+            )
+            .context("Failed to create surface of second window")?
+        };
+        let supported = unsafe {
+            vk_context.surface().get_physical_device_surface_support(
+                vk_context.physical_device(),
+                vk_context.present_queue_index(),
+                surface_khr,
+            )
+        };
+        if !supported.unwrap_or(false) {
+            unsafe { vk_context.surface().destroy_surface(surface_khr, None) };
+            return Err(anyhow!("The present queue can not present to the second window"));
+        }
+
+        let device = vk_context.device();
+        let swapchain = khr_swapchain::Device::new(vk_context.instance(), device);
+        let (swapchain_khr, properties, image_views) = Self::create_swapchain(
+            vk_context,
+            &swapchain,
+            surface_khr,
+            dimensions,
+            vk::SwapchainKHR::null(),
+        );
+        let render_pass = Self::create_render_pass(device, properties.format.format);
+        let framebuffers = Self::create_framebuffers(device, &image_views, render_pass, properties);
+
+        let command_pool =
+            vk_context.create_command_pool(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER);
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(frames_in_flight as _);
+        let command_buffers = unsafe { device.allocate_command_buffers(&allocate_info)? };
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let create_semaphores = || {
+            (0..frames_in_flight)
+                .map(|_| unsafe { device.create_semaphore(&semaphore_info, None).unwrap() })
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Self {
+            surface_khr,
+            swapchain,
+            swapchain_khr,
+            properties,
+            image_views,
+            render_pass,
+            framebuffers,
+            command_pool,
+            command_buffers,
+            image_available_semaphores: create_semaphores(),
+            render_finished_semaphores: create_semaphores(),
+            dimensions,
+            art,
+            pipeline: None,
+            start_time: None,
+            dirty: false,
+        })
+    }
+
+    /// The render pass the pipeline has to be compatible with,
+    /// it only changes if the format of the surface does.
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    pub fn extent(&self) -> vk::Extent2D {
+        self.properties.extent
+    }
+
+    pub fn swapchain_khr(&self) -> vk::SwapchainKHR {
+        self.swapchain_khr
+    }
+
+    /// Marks the swapchain as dirty, it is recreated with the new size before the next draw.
+    pub fn resize(&mut self, dimensions: [u32; 2]) {
+        self.dimensions = dimensions;
+        self.dirty = true;
+    }
+
+    /// Recreates the swapchain if it is dirty and the window is not minimized.
+    ///
+    /// The frames in flight must be done using the old swapchain, which is destroyed right away.
+    ///
+    /// # Returns
+    ///
+    /// True if the render pass was recreated and the pipeline has to be recreated too.
+    pub fn recreate_swapchain_if_dirty(&mut self, vk_context: &VkContext) -> bool {
+        if !self.dirty || self.dimensions.contains(&0) {
+            return false;
+        }
+        log::debug!("Recreating swapchain of second window");
+        let device = vk_context.device();
+        // the presentation engine may still use the old images
+        unsafe { device.device_wait_idle().unwrap() };
+        self.cleanup_swapchain(device);
+        let (swapchain_khr, properties, image_views) = Self::create_swapchain(
+            vk_context,
+            &self.swapchain,
+            self.surface_khr,
+            self.dimensions,
+            self.swapchain_khr,
+        );
+        unsafe { self.swapchain.destroy_swapchain(self.swapchain_khr, None) };
+        let format_changed = properties.format.format != self.properties.format.format;
+        if format_changed {
+            unsafe { device.destroy_render_pass(self.render_pass, None) };
+            self.render_pass = Self::create_render_pass(device, properties.format.format);
+        }
+        self.framebuffers =
+            Self::create_framebuffers(device, &image_views, self.render_pass, properties);
+        self.swapchain_khr = swapchain_khr;
+        self.properties = properties;
+        self.image_views = image_views;
+        self.dirty = false;
+        format_changed
+    }
+
+    /// Acquires the next swapchain image for `frame`.
+    ///
+    /// Returns the index of the image and the semaphore signaled once it is available,
+    /// or `None` if the swapchain is dirty and nothing can be drawn.
+    pub fn acquire_next_image(&mut self, frame: usize) -> Option<(u32, vk::Semaphore)> {
+        if self.dirty {
+            return None;
+        }
+        let semaphore = self.image_available_semaphores[frame];
+        let result = unsafe {
+            self.swapchain.acquire_next_image(
+                self.swapchain_khr,
+                u64::MAX,
+                semaphore,
+                vk::Fence::null(),
+            )
+        };
+        match result {
+            Ok((image_index, _suboptimal)) => Some((image_index, semaphore)),
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.dirty = true;
+                None
+            }
+            Err(error) => panic!("Error while acquiring next image of second window. Cause: {error}"),
+        }
+    }
+
+    /// The semaphore the command buffer of `frame` signals when it is done.
+    pub fn render_finished_semaphore(&self, frame: usize) -> vk::Semaphore {
+        self.render_finished_semaphores[frame]
+    }
+
+    /// Records the command buffer of `frame` that draws the art into the image at `image_index`.
+    ///
+    /// The command buffer of the frame must not be in use anymore.
+    pub fn record_command_buffer(
+        &self,
+        device: &Device,
+        frame: usize,
+        image_index: u32,
+        ubo_offset: u32,
+    ) -> vk::CommandBuffer {
+        let buffer = self.command_buffers[frame];
+        let extent = self.properties.extent;
+        let rect = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        let clear_values = [vk::ClearValue {
+            color: vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            },
+        }];
+        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+            .render_pass(self.render_pass)
+            .framebuffer(self.framebuffers[image_index as usize])
+            .render_area(rect)
+            .clear_values(&clear_values);
+        let viewport = vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: extent.width as f32,
+            height: extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe {
+            device.reset_command_buffer(buffer, vk::CommandBufferResetFlags::empty()).unwrap();
+            device.begin_command_buffer(buffer, &vk::CommandBufferBeginInfo::default()).unwrap();
+            device.cmd_begin_render_pass(buffer, &render_pass_begin_info, vk::SubpassContents::INLINE);
+            if let Some(pipeline) = self.pipeline.as_ref().filter(|pipeline| !pipeline.waiting_for_shaders) {
+                device.cmd_set_viewport(buffer, 0, &[viewport]);
+                device.cmd_set_scissor(buffer, 0, &[rect]);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, ubo_offset);
+            }
+            device.cmd_end_render_pass(buffer);
+            device.end_command_buffer(buffer).unwrap();
+        }
+        buffer
+    }
+
+    /// Destroys everything including the surface, the window has to outlive this.
+    pub fn destroy(&mut self, vk_context: &VkContext) {
+        let device = vk_context.device();
+        self.cleanup_swapchain(device);
+        unsafe {
+            if let Some(pipeline) = self.pipeline.as_mut() {
+                pipeline.cleanup_pip(device);
+            }
+            self.swapchain.destroy_swapchain(self.swapchain_khr, None);
+            device.destroy_render_pass(self.render_pass, None);
+            for semaphore in self.image_available_semaphores.iter()
+                .chain(self.render_finished_semaphores.iter())
+            {
+                device.destroy_semaphore(*semaphore, None);
+            }
+            device.destroy_command_pool(self.command_pool, None);
+            vk_context.surface().destroy_surface(self.surface_khr, None);
+        }
+    }
+
+    fn cleanup_swapchain(&mut self, device: &Device) {
+        unsafe {
+            for framebuffer in self.framebuffers.drain(..) {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for image_view in self.image_views.drain(..) {
+                device.destroy_image_view(image_view, None);
+            }
+        }
+    }
+
+    fn create_swapchain(
+        vk_context: &VkContext,
+        swapchain: &khr_swapchain::Device,
+        surface_khr: vk::SurfaceKHR,
+        dimensions: [u32; 2],
+        old_swapchain: vk::SwapchainKHR,
+    ) -> (vk::SwapchainKHR, SwapchainProperties, Vec<vk::ImageView>) {
+        let details = SwapchainSupportDetails::new(
+            vk_context.physical_device(),
+            vk_context.surface(),
+            surface_khr,
+        );
+        // the projection of the second window is not pre-rotated
+        let properties = details.get_ideal_swapchain_properties(dimensions, false);
+        let image_count = details.image_count(None);
+
+        let graphics = vk_context.graphics_queue_index();
+        let present = vk_context.present_queue_index();
+        let families_indices = [graphics, present];
+        let mut create_info = vk::SwapchainCreateInfoKHR::default()
+            .surface(surface_khr)
+            .min_image_count(image_count)
+            .image_format(properties.format.format)
+            .image_color_space(properties.format.color_space)
+            .image_extent(properties.extent)
+            .image_array_layers(1)
+            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+            .pre_transform(properties.transform)
+            .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+            .present_mode(properties.present_mode)
+            .clipped(true)
+            .old_swapchain(old_swapchain);
+        create_info = if graphics != present {
+            create_info
+                .image_sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(&families_indices)
+        } else {
+            create_info.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+        };
+
+        let device = vk_context.device();
+        let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None).unwrap() };
+        let images = unsafe { swapchain.get_swapchain_images(swapchain_khr).unwrap() };
+        let image_views = images.iter()
+            .map(|&image| {
+                let create_info = vk::ImageViewCreateInfo::default()
+                    .image(image)
+                    .view_type(vk::ImageViewType::TYPE_2D)
+                    .format(properties.format.format)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    });
+                unsafe { device.create_image_view(&create_info, None).unwrap() }
+            })
+            .collect();
+        (swapchain_khr, properties, image_views)
+    }
+
+    /// Create a render pass that clears a swapchain image and draws into it without depth or msaa.
+    fn create_render_pass(device: &Device, format: vk::Format) -> vk::RenderPass {
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        let attachment_descs = [color_attachment_desc];
+
+        let color_attachment_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let color_attachment_refs = [color_attachment_ref];
+
+        let subpass_desc = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs);
+        let subpass_descs = [subpass_desc];
+
+        let subpass_dep = vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        let subpass_deps = [subpass_dep];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    fn create_framebuffers(
+        device: &Device,
+        image_views: &[vk::ImageView],
+        render_pass: vk::RenderPass,
+        properties: SwapchainProperties,
+    ) -> Vec<vk::Framebuffer> {
+        image_views.iter()
+            .map(|view| {
+                let attachments = [*view];
+                let framebuffer_info = vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(properties.extent.width)
+                    .height(properties.extent.height)
+                    .layers(1);
+                unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
+            })
+            .collect()
+    }
+}
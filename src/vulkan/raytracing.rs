@@ -0,0 +1,802 @@
+use crate::math::Matrix4;
+use super::{
+    buffer,
+    cmd,
+    context::VkContext,
+    geometry::Geometry,
+    shader::Shader,
+    structs::PushConstants,
+    texture::Texture,
+};
+
+use ash::{
+    khr::{acceleration_structure, buffer_device_address, ray_tracing_pipeline},
+    vk, Device, Instance,
+};
+use std::ffi::CString;
+
+/// Size of the images ray traced art is rendered into.
+pub const RAY_TRACING_EXTENT: vk::Extent2D = vk::Extent2D {
+    width: 512,
+    height: 512,
+};
+
+/// Loaders and properties needed for hardware ray tracing.
+#[derive(Clone)]
+pub struct RayTracing {
+    acceleration_structure: acceleration_structure::Device,
+    pipeline: ray_tracing_pipeline::Device,
+    buffer_device_address: buffer_device_address::Device,
+    shader_group_handle_size: u32,
+    shader_group_handle_alignment: u32,
+    shader_group_base_alignment: u32,
+}
+
+impl RayTracing {
+    pub fn new(instance: &Instance, physical_device: vk::PhysicalDevice, device: &Device) -> Self {
+        let mut ray_tracing_properties =
+            vk::PhysicalDeviceRayTracingPipelinePropertiesKHR::default();
+        let mut properties =
+            vk::PhysicalDeviceProperties2::default().push_next(&mut ray_tracing_properties);
+        unsafe { instance.get_physical_device_properties2(physical_device, &mut properties) };
+
+        Self {
+            acceleration_structure: acceleration_structure::Device::new(instance, device),
+            pipeline: ray_tracing_pipeline::Device::new(instance, device),
+            buffer_device_address: buffer_device_address::Device::new(instance, device),
+            shader_group_handle_size: ray_tracing_properties.shader_group_handle_size,
+            shader_group_handle_alignment: ray_tracing_properties.shader_group_handle_alignment,
+            shader_group_base_alignment: ray_tracing_properties.shader_group_base_alignment,
+        }
+    }
+
+    /// Returns the device address of a buffer created with `SHADER_DEVICE_ADDRESS` usage.
+    pub fn buffer_device_address(&self, buffer: vk::Buffer) -> vk::DeviceAddress {
+        let info = vk::BufferDeviceAddressInfo::default().buffer(buffer);
+        unsafe { self.buffer_device_address.get_buffer_device_address(&info) }
+    }
+}
+
+pub struct AccelerationStructure {
+    handle: vk::AccelerationStructureKHR,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    address: vk::DeviceAddress,
+}
+
+impl AccelerationStructure {
+    /// Builds a bottom level acceleration structure from the triangles of `geometry`.
+    ///
+    /// The first vertex attribute of the geometry is used as the position.
+    pub fn new_bottom_level(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        geometry: &Geometry,
+    ) -> Self {
+        let ray_tracing = vk_context.ray_tracing().expect("ray tracing must be enabled");
+        let (vertex_buffer, index_buffer, index_count) =
+            geometry.get().expect("geometry must not be cleaned up");
+        let position = geometry.get_attribute_descriptions()[0];
+
+        let triangles = vk::AccelerationStructureGeometryTrianglesDataKHR::default()
+            .vertex_format(position.format)
+            .vertex_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: ray_tracing.buffer_device_address(vertex_buffer)
+                    + position.offset as vk::DeviceAddress,
+            })
+            .vertex_stride(geometry.get_binding_descriptions()[0].stride as _)
+            .max_vertex(geometry.vertex_count().saturating_sub(1))
+            .index_type(vk::IndexType::UINT32)
+            .index_data(vk::DeviceOrHostAddressConstKHR {
+                device_address: ray_tracing.buffer_device_address(index_buffer),
+            });
+        let geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::TRIANGLES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { triangles })
+            .flags(vk::GeometryFlagsKHR::OPAQUE);
+
+        Self::build(
+            vk_context,
+            command_pool,
+            queue,
+            vk::AccelerationStructureTypeKHR::BOTTOM_LEVEL,
+            geometry,
+            index_count / 3,
+        )
+    }
+
+    /// Builds a top level acceleration structure with one instance
+    /// for every bottom level structure placed by its transformation.
+    pub fn new_top_level(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        instances: &[(&AccelerationStructure, Matrix4)],
+    ) -> Self {
+        let device = vk_context.device();
+        let ray_tracing = vk_context.ray_tracing().expect("ray tracing must be enabled");
+
+        let instances = instances.iter()
+            .enumerate()
+            .map(|(i, (blas, transform))| vk::AccelerationStructureInstanceKHR {
+                transform: to_transform_matrix(transform),
+                instance_custom_index_and_mask: vk::Packed24_8::new(i as _, 0xff),
+                instance_shader_binding_table_record_offset_and_flags: vk::Packed24_8::new(
+                    0,
+                    vk::GeometryInstanceFlagsKHR::TRIANGLE_FACING_CULL_DISABLE.as_raw() as _,
+                ),
+                acceleration_structure_reference: vk::AccelerationStructureReferenceKHR {
+                    device_handle: blas.address,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        let size = size_of_val(instances.as_slice()) as vk::DeviceSize;
+        let (instance_buffer, instance_memory, instance_mem_size) = buffer::create_buffer(
+            vk_context,
+            size,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_BUILD_INPUT_READ_ONLY_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        unsafe {
+            let data_ptr = device
+                .map_memory(instance_memory, 0, size, vk::MemoryMapFlags::empty())
+                .unwrap();
+            let mut align = ash::util::Align::new(
+                data_ptr,
+                align_of::<vk::AccelerationStructureInstanceKHR>() as _,
+                instance_mem_size,
+            );
+            align.copy_from_slice(&instances);
+            device.unmap_memory(instance_memory);
+        }
+
+        let instances_data = vk::AccelerationStructureGeometryInstancesDataKHR::default()
+            .array_of_pointers(false)
+            .data(vk::DeviceOrHostAddressConstKHR {
+                device_address: ray_tracing.buffer_device_address(instance_buffer),
+            });
+        let geometry = vk::AccelerationStructureGeometryKHR::default()
+            .geometry_type(vk::GeometryTypeKHR::INSTANCES)
+            .geometry(vk::AccelerationStructureGeometryDataKHR { instances: instances_data });
+
+        let tlas = Self::build(
+            vk_context,
+            command_pool,
+            queue,
+            vk::AccelerationStructureTypeKHR::TOP_LEVEL,
+            geometry,
+            instances.len() as _,
+        );
+
+        unsafe {
+            device.destroy_buffer(instance_buffer, None);
+            device.free_memory(instance_memory, None);
+        }
+
+        tlas
+    }
+
+    /// Create the acceleration structure and build it on the gpu using a one-time command buffer.
+    fn build(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        ty: vk::AccelerationStructureTypeKHR,
+        geometry: vk::AccelerationStructureGeometryKHR,
+        primitive_count: u32,
+    ) -> Self {
+        let device = vk_context.device();
+        let ray_tracing = vk_context.ray_tracing().expect("ray tracing must be enabled");
+        let loader = &ray_tracing.acceleration_structure;
+
+        let geometries = [geometry];
+        let mut build_info = vk::AccelerationStructureBuildGeometryInfoKHR::default()
+            .ty(ty)
+            .flags(vk::BuildAccelerationStructureFlagsKHR::PREFER_FAST_TRACE)
+            .mode(vk::BuildAccelerationStructureModeKHR::BUILD)
+            .geometries(&geometries);
+        let mut size_info = vk::AccelerationStructureBuildSizesInfoKHR::default();
+        unsafe {
+            loader.get_acceleration_structure_build_sizes(
+                vk::AccelerationStructureBuildTypeKHR::DEVICE,
+                &build_info,
+                &[primitive_count],
+                &mut size_info,
+            );
+        }
+
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            size_info.acceleration_structure_size,
+            vk::BufferUsageFlags::ACCELERATION_STRUCTURE_STORAGE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let create_info = vk::AccelerationStructureCreateInfoKHR::default()
+            .buffer(buffer)
+            .size(size_info.acceleration_structure_size)
+            .ty(ty);
+        let handle = unsafe { loader.create_acceleration_structure(&create_info, None).unwrap() };
+
+        let (scratch_buffer, scratch_memory, _) = buffer::create_buffer(
+            vk_context,
+            size_info.build_scratch_size,
+            vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        build_info = build_info
+            .dst_acceleration_structure(handle)
+            .scratch_data(vk::DeviceOrHostAddressKHR {
+                device_address: ray_tracing.buffer_device_address(scratch_buffer),
+            });
+        let range = vk::AccelerationStructureBuildRangeInfoKHR::default()
+            .primitive_count(primitive_count);
+
+        cmd::execute_one_time_commands(device, command_pool, queue, |buffer| {
+            unsafe {
+                loader.cmd_build_acceleration_structures(buffer, &[build_info], &[&[range]]);
+            }
+        });
+
+        unsafe {
+            device.destroy_buffer(scratch_buffer, None);
+            device.free_memory(scratch_memory, None);
+        }
+
+        let address_info = vk::AccelerationStructureDeviceAddressInfoKHR::default()
+            .acceleration_structure(handle);
+        let address = unsafe { loader.get_acceleration_structure_device_address(&address_info) };
+
+        Self {
+            handle,
+            buffer,
+            memory,
+            address,
+        }
+    }
+
+    pub unsafe fn cleanup(&self, device: &Device, ray_tracing: &RayTracing) {
+        unsafe {
+            ray_tracing.acceleration_structure.destroy_acceleration_structure(self.handle, None);
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Converts a column based matrix into the row based 3x4 matrix used for instances.
+fn to_transform_matrix(matrix: &Matrix4) -> vk::TransformMatrixKHR {
+    let mut out = [0.; 12];
+    for row in 0..3 {
+        for col in 0..4 {
+            out[row * 4 + col] = matrix[col][row];
+        }
+    }
+    vk::TransformMatrixKHR { matrix: out }
+}
+
+struct ShaderBindingTable {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    raygen: vk::StridedDeviceAddressRegionKHR,
+    miss: vk::StridedDeviceAddressRegionKHR,
+    hit: vk::StridedDeviceAddressRegionKHR,
+}
+
+/// A pipeline that ray traces art into one image per frame in flight.
+///
+/// The images are meant to be sampled by the graphics pipeline of the art.
+pub struct RayTracingPipeline {
+    name: String,
+    ray_tracing: RayTracing,
+    /// The ray generation, miss and closest hit shaders.
+    shaders: [Shader; 3],
+    pipeline_and_layout: Option<(vk::Pipeline, vk::PipelineLayout)>,
+    shader_binding_table: Option<ShaderBindingTable>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    images: Vec<Texture>,
+    push_constants: PushConstants,
+    pub waiting_for_shaders: bool,
+}
+
+impl RayTracingPipeline {
+    /// Creates the pipeline, it will trace into `images` which must be
+    /// in the `GENERAL` layout and have one entry per part of the uniform buffer.
+    pub fn new(
+        name: String,
+        vk_context: &VkContext,
+        shaders: [Shader; 3],
+        tlas: &AccelerationStructure,
+        uniform_buffers: &[vk::DescriptorBufferInfo],
+        images: Vec<Texture>,
+        push_constants: PushConstants,
+    ) -> Self {
+        let device = vk_context.device();
+        let ray_tracing = vk_context.ray_tracing().expect("ray tracing must be enabled").clone();
+        let descriptor_set_layout = Self::create_descriptor_set_layout(device);
+        let descriptor_pool = Self::create_descriptor_pool(device, images.len() as _);
+        let descriptor_sets = Self::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            tlas,
+            uniform_buffers,
+            &images,
+        );
+
+        let mut pipeline = Self {
+            name,
+            ray_tracing,
+            shaders,
+            pipeline_and_layout: None,
+            shader_binding_table: None,
+            descriptor_set_layout,
+            descriptor_pool,
+            descriptor_sets,
+            images,
+            push_constants,
+            waiting_for_shaders: true,
+        };
+        pipeline.recreate(vk_context);
+        pipeline
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The images the art is traced into, one for each frame in flight.
+    pub fn images(&self) -> &[Texture] {
+        &self.images
+    }
+
+    /// Traces against `tlas` from now on, the sets must not be in use.
+    pub fn set_tlas(&self, device: &Device, tlas: &AccelerationStructure) {
+        let tlases = [tlas.handle];
+        for &set in self.descriptor_sets.iter() {
+            let mut tlas_info = vk::WriteDescriptorSetAccelerationStructureKHR::default()
+                .acceleration_structures(&tlases);
+            let mut tlas_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .push_next(&mut tlas_info);
+            // the count is not derived from the pushed structure
+            tlas_descriptor_write.descriptor_count = 1;
+            unsafe { device.update_descriptor_sets(&[tlas_descriptor_write], &[]) }
+        }
+    }
+
+    /// Returns the errors of all shaders of this pipeline that failed to compile.
+    pub fn compile_errors(&self) -> Vec<String> {
+        self.shaders.iter().filter_map(|shader| shader.error()).collect()
+    }
+
+    pub fn has_changed(&self) -> bool {
+        self.shaders.iter().any(|shader| shader.code_has_changed())
+    }
+
+    pub fn reload_shaders(&mut self, device: &Device, forced: bool) -> bool {
+        let reloading = self.shaders.iter()
+            .fold(false, |reloading, shader| shader.reload(device, forced) | reloading);
+        if reloading {
+            self.waiting_for_shaders = true;
+            unsafe {
+                self.cleanup_pip(device);
+            }
+        }
+        reloading
+    }
+
+    pub fn recreate(&mut self, vk_context: &VkContext) {
+        let device = vk_context.device();
+        unsafe {
+            self.cleanup_pip(device);
+        }
+
+        let shader_modules = self.shaders.iter()
+            .map(|shader| shader.module(device))
+            .collect::<Option<Vec<_>>>();
+        let Some(shader_modules) = shader_modules else {
+            self.waiting_for_shaders = true;
+            return;
+        };
+        let stages = self.shaders.iter()
+            .zip(shader_modules)
+            .map(|(shader, module)| (shader.stage_flags(), module, shader.entry_point()))
+            .collect::<Vec<_>>();
+
+        let (pipeline, layout) = Self::create_pipeline(
+            device,
+            &self.ray_tracing,
+            self.descriptor_set_layout,
+            &stages,
+        );
+        self.shader_binding_table =
+            Some(Self::create_shader_binding_table(vk_context, &self.ray_tracing, pipeline));
+        self.pipeline_and_layout = Some((pipeline, layout));
+        self.waiting_for_shaders = false;
+    }
+
+    /// Records the commands to trace the art into the `i`th image.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be in the recording state and outside of a render pass.
+    pub unsafe fn trace_to_cmd_buffer(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        i: usize,
+    ) {
+        let (Some((pipeline, layout)), Some(sbt)) =
+            (self.pipeline_and_layout, self.shader_binding_table.as_ref())
+        else {
+            return;
+        };
+
+        // wait for the previous frame to finish sampling the image before writing to it
+        self.image_barrier(
+            device,
+            buffer,
+            i,
+            (vk::AccessFlags::SHADER_READ, vk::PipelineStageFlags::FRAGMENT_SHADER),
+            (vk::AccessFlags::SHADER_WRITE, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR),
+        );
+
+        fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
+            unsafe {
+                ::core::slice::from_raw_parts((p as *const T) as *const u8, size_of::<T>())
+            }
+        }
+        unsafe {
+            device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::RAY_TRACING_KHR, pipeline);
+            device.cmd_bind_descriptor_sets(
+                buffer,
+                vk::PipelineBindPoint::RAY_TRACING_KHR,
+                layout,
+                0,
+                &self.descriptor_sets[i..=i],
+                &[],
+            );
+            device.cmd_push_constants(
+                buffer,
+                layout,
+                vk::ShaderStageFlags::RAYGEN_KHR,
+                0,
+                any_as_u8_slice(&self.push_constants),
+            );
+            self.ray_tracing.pipeline.cmd_trace_rays(
+                buffer,
+                &sbt.raygen,
+                &sbt.miss,
+                &sbt.hit,
+                &vk::StridedDeviceAddressRegionKHR::default(),
+                RAY_TRACING_EXTENT.width,
+                RAY_TRACING_EXTENT.height,
+                1,
+            );
+        }
+
+        self.image_barrier(
+            device,
+            buffer,
+            i,
+            (vk::AccessFlags::SHADER_WRITE, vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR),
+            (vk::AccessFlags::SHADER_READ, vk::PipelineStageFlags::FRAGMENT_SHADER),
+        );
+    }
+
+    fn image_barrier(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        i: usize,
+        (src_access_mask, src_stage): (vk::AccessFlags, vk::PipelineStageFlags),
+        (dst_access_mask, dst_stage): (vk::AccessFlags, vk::PipelineStageFlags),
+    ) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.images[i].image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .src_access_mask(src_access_mask)
+            .dst_access_mask(dst_access_mask);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    fn create_descriptor_set_layout(device: &Device) -> vk::DescriptorSetLayout {
+        let tlas_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(0)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR | vk::ShaderStageFlags::CLOSEST_HIT_KHR);
+        let image_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(1)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+            .stage_flags(vk::ShaderStageFlags::RAYGEN_KHR);
+        let ubo_binding = vk::DescriptorSetLayoutBinding::default()
+            .binding(2)
+            .descriptor_count(1)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .stage_flags(
+                vk::ShaderStageFlags::RAYGEN_KHR
+                    | vk::ShaderStageFlags::MISS_KHR
+                    | vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+            );
+        let bindings = [tlas_binding, image_binding, ubo_binding];
+
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::default().bindings(&bindings);
+        unsafe { device.create_descriptor_set_layout(&layout_info, None).unwrap() }
+    }
+
+    fn create_descriptor_pool(device: &Device, size: u32) -> vk::DescriptorPool {
+        let pool_sizes = [
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::ACCELERATION_STRUCTURE_KHR,
+                descriptor_count: size,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::STORAGE_IMAGE,
+                descriptor_count: size,
+            },
+            vk::DescriptorPoolSize {
+                ty: vk::DescriptorType::UNIFORM_BUFFER,
+                descriptor_count: size,
+            },
+        ];
+        let pool_info = vk::DescriptorPoolCreateInfo::default()
+            .pool_sizes(&pool_sizes)
+            .max_sets(size);
+
+        unsafe { device.create_descriptor_pool(&pool_info, None).unwrap() }
+    }
+
+    fn create_descriptor_sets(
+        device: &Device,
+        pool: vk::DescriptorPool,
+        layout: vk::DescriptorSetLayout,
+        tlas: &AccelerationStructure,
+        uniform_buffers: &[vk::DescriptorBufferInfo],
+        images: &[Texture],
+    ) -> Vec<vk::DescriptorSet> {
+        let layouts = (0..uniform_buffers.len())
+            .map(|_| layout)
+            .collect::<Vec<_>>();
+        let alloc_info = vk::DescriptorSetAllocateInfo::default()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+        let descriptor_sets = unsafe { device.allocate_descriptor_sets(&alloc_info).unwrap() };
+
+        let tlases = [tlas.handle];
+        for ((set, buffer_info), image) in descriptor_sets.iter().zip(uniform_buffers).zip(images) {
+            let mut tlas_info = vk::WriteDescriptorSetAccelerationStructureKHR::default()
+                .acceleration_structures(&tlases);
+            let mut tlas_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::ACCELERATION_STRUCTURE_KHR)
+                .push_next(&mut tlas_info);
+            // the count is not derived from the pushed structure
+            tlas_descriptor_write.descriptor_count = 1;
+
+            let image_info = vk::DescriptorImageInfo::default()
+                .image_layout(vk::ImageLayout::GENERAL)
+                .image_view(image.view);
+            let image_infos = [image_info];
+            let image_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(1)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(&image_infos);
+
+            // the part of the buffer starts with the uniform buffer object of the first view
+            let buffer_infos = [*buffer_info];
+            let ubo_descriptor_write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(2)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .buffer_info(&buffer_infos);
+
+            let writes = [tlas_descriptor_write, image_descriptor_write, ubo_descriptor_write];
+            unsafe { device.update_descriptor_sets(&writes, &[]) }
+        }
+
+        descriptor_sets
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        ray_tracing: &RayTracing,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        stages: &[(vk::ShaderStageFlags, vk::ShaderModule, CString)],
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let shader_states_infos = stages.iter()
+            .map(|(stage, module, entry_point)| {
+                vk::PipelineShaderStageCreateInfo::default()
+                    .stage(*stage)
+                    .module(*module)
+                    .name(entry_point)
+            })
+            .collect::<Vec<_>>();
+
+        // one group each for ray generation, miss and closest hit, in this order
+        let groups = [
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                .general_shader(0)
+                .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::GENERAL)
+                .general_shader(1)
+                .closest_hit_shader(vk::SHADER_UNUSED_KHR)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+            vk::RayTracingShaderGroupCreateInfoKHR::default()
+                .ty(vk::RayTracingShaderGroupTypeKHR::TRIANGLES_HIT_GROUP)
+                .general_shader(vk::SHADER_UNUSED_KHR)
+                .closest_hit_shader(2)
+                .any_hit_shader(vk::SHADER_UNUSED_KHR)
+                .intersection_shader(vk::SHADER_UNUSED_KHR),
+        ];
+
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let ranges =
+                [PushConstants::get_push_constant_range(vk::ShaderStageFlags::RAYGEN_KHR)];
+            let layout_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(&layouts)
+                .push_constant_ranges(&ranges);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+
+        let pipeline_info = vk::RayTracingPipelineCreateInfoKHR::default()
+            .stages(&shader_states_infos)
+            .groups(&groups)
+            .max_pipeline_ray_recursion_depth(1)
+            .layout(layout);
+        let pipeline_infos = [pipeline_info];
+
+        let pipeline = unsafe {
+            ray_tracing.pipeline.create_ray_tracing_pipelines(
+                vk::DeferredOperationKHR::null(),
+                vk::PipelineCache::null(),
+                &pipeline_infos,
+                None,
+            )
+            .map_err(|(_, err)| err)
+            .unwrap()[0]
+        };
+
+        (pipeline, layout)
+    }
+
+    /// Create a host visible buffer with the handles of the three shader groups,
+    /// each in its own region aligned to the base alignment.
+    fn create_shader_binding_table(
+        vk_context: &VkContext,
+        ray_tracing: &RayTracing,
+        pipeline: vk::Pipeline,
+    ) -> ShaderBindingTable {
+        let device = vk_context.device();
+        let handle_size = ray_tracing.shader_group_handle_size as usize;
+        let handle_stride =
+            handle_size.next_multiple_of(ray_tracing.shader_group_handle_alignment as _);
+        let region_size =
+            handle_stride.next_multiple_of(ray_tracing.shader_group_base_alignment as _);
+        let group_count = 3;
+
+        let handles = unsafe {
+            ray_tracing.pipeline.get_ray_tracing_shader_group_handles(
+                pipeline,
+                0,
+                group_count as _,
+                group_count * handle_size,
+            )
+            .unwrap()
+        };
+
+        let size = (group_count * region_size) as vk::DeviceSize;
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            size,
+            vk::BufferUsageFlags::SHADER_BINDING_TABLE_KHR
+                | vk::BufferUsageFlags::SHADER_DEVICE_ADDRESS,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        unsafe {
+            let data_ptr = device
+                .map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+                .unwrap() as *mut u8;
+            for (i, handle) in handles.chunks_exact(handle_size).enumerate() {
+                let dst = data_ptr.add(i * region_size);
+                std::ptr::copy_nonoverlapping(handle.as_ptr(), dst, handle_size);
+            }
+            device.unmap_memory(memory);
+        }
+
+        let address = ray_tracing.buffer_device_address(buffer);
+        let region = |i: usize, stride: usize| vk::StridedDeviceAddressRegionKHR {
+            device_address: address + (i * region_size) as vk::DeviceAddress,
+            stride: stride as _,
+            size: region_size as _,
+        };
+        ShaderBindingTable {
+            buffer,
+            memory,
+            // the stride of the ray generation region must be equal to its size
+            raygen: region(0, region_size),
+            miss: region(1, handle_stride),
+            hit: region(2, handle_stride),
+        }
+    }
+
+    pub unsafe fn cleanup_pip(&mut self, device: &Device) {
+        if let Some((pipeline, layout)) = self.pipeline_and_layout.take() {
+            log::debug!("cleaning RayTracingPipeline {}", self.name);
+            unsafe {
+                device.destroy_pipeline(pipeline, None);
+                device.destroy_pipeline_layout(layout, None);
+            }
+        }
+        if let Some(sbt) = self.shader_binding_table.take() {
+            unsafe {
+                device.destroy_buffer(sbt.buffer, None);
+                device.free_memory(sbt.memory, None);
+            }
+        }
+    }
+
+    pub unsafe fn cleanup(&mut self, device: &Device) {
+        unsafe {
+            self.cleanup_pip(device);
+            device.destroy_descriptor_pool(self.descriptor_pool, None);
+            device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+        }
+        for image in self.images.iter_mut() {
+            image.destroy(device);
+        }
+        for shader in self.shaders.iter() {
+            shader.cleanup(device);
+        }
+        self.images.clear();
+    }
+}
+
+impl Drop for RayTracingPipeline {
+    fn drop(&mut self) {
+        if !std::thread::panicking() && self.pipeline_and_layout.is_some() {
+            log::error!("RayTracingPipeline was not cleaned up before beeing dropped");
+        }
+    }
+}
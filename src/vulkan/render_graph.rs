@@ -0,0 +1,306 @@
+use anyhow::anyhow;
+use ash::{vk, Device};
+
+/// What a pass of a [`RenderGraph`] reads or writes during a frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// The textures the CPU writes every frame, see [`super::stream::StreamTexture`].
+    Streams,
+    /// The images ray traced art is traced into.
+    TracedImages,
+    /// The indirect draws of the art written by occlusion culling.
+    DrawCommands,
+    /// The depth of the environment drawn before the gallery to cull the art.
+    Depth,
+    /// The images art is drawn into at half resolution to be scaled up in the gallery.
+    HalfResArt,
+    /// The indices of the lights reaching each cluster of the view.
+    Clusters,
+    /// The depth of the environment seen from the sun.
+    ShadowMap,
+    /// The light scattered by the fog in the froxels of the view.
+    FogVolume,
+    /// The resolved gallery that is graded before it is shown.
+    Scene,
+    /// The resolved images of both eyes in stereo.
+    Eyes,
+    /// The image of the swapchain that is presented.
+    Swapchain,
+    /// The host buffer a frame is read back into.
+    Readback,
+}
+
+/// How a pass accesses a [`Resource`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Usage {
+    /// Sampled in fragment shaders.
+    Sampled,
+    /// Written in ray tracing shaders.
+    Storage,
+    /// Written in compute shaders.
+    ComputeWrite,
+    /// Sampled or read from storage buffers in compute shaders.
+    ComputeRead,
+    /// Read as the parameters of indirect draws.
+    IndirectRead,
+    /// Read from storage buffers in fragment shaders.
+    StorageRead,
+    ColorAttachment,
+    DepthAttachment,
+    TransferSrc,
+    TransferDst,
+}
+
+impl Usage {
+    pub fn stage(self) -> vk::PipelineStageFlags {
+        match self {
+            Self::Sampled | Self::StorageRead => vk::PipelineStageFlags::FRAGMENT_SHADER,
+            Self::Storage => vk::PipelineStageFlags::RAY_TRACING_SHADER_KHR,
+            Self::ComputeWrite | Self::ComputeRead => vk::PipelineStageFlags::COMPUTE_SHADER,
+            Self::IndirectRead => vk::PipelineStageFlags::DRAW_INDIRECT,
+            Self::ColorAttachment => vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            Self::DepthAttachment => vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            Self::TransferSrc | Self::TransferDst => vk::PipelineStageFlags::TRANSFER,
+        }
+    }
+
+    pub fn access(self) -> vk::AccessFlags {
+        match self {
+            Self::Sampled | Self::StorageRead | Self::ComputeRead => vk::AccessFlags::SHADER_READ,
+            Self::Storage | Self::ComputeWrite => vk::AccessFlags::SHADER_WRITE,
+            Self::IndirectRead => vk::AccessFlags::INDIRECT_COMMAND_READ,
+            Self::ColorAttachment => vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            Self::DepthAttachment => vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            Self::TransferSrc => vk::AccessFlags::TRANSFER_READ,
+            Self::TransferDst => vk::AccessFlags::TRANSFER_WRITE,
+        }
+    }
+}
+
+/// A resource a pass has to wait for because an earlier pass used it differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Barrier {
+    pub resource: Resource,
+    pub src: Usage,
+    pub dst: Usage,
+}
+
+struct Pass<P> {
+    id: P,
+    reads: Vec<(Resource, Usage)>,
+    writes: Vec<(Resource, Usage)>,
+}
+
+/// A pass of a compiled [`RenderGraph`] with the barriers it needs before it runs.
+#[derive(Debug, PartialEq)]
+pub struct CompiledPass<P> {
+    pub id: P,
+    pub barriers: Vec<Barrier>,
+}
+
+/// The passes of a frame declared with the resources they read and write.
+///
+/// Compiling the graph drops the passes nothing depends on, orders the others so that every
+/// pass runs after the passes writing what it reads and derives the barriers between them.
+/// A resource written by several passes is written in the order the passes were added.
+pub struct RenderGraph<P> {
+    passes: Vec<Pass<P>>,
+    outputs: Vec<Resource>,
+}
+
+impl<P: Copy> RenderGraph<P> {
+    /// Creates a graph whose passes are needed if they contribute to `outputs`.
+    pub fn new(outputs: &[Resource]) -> Self {
+        Self {
+            passes: Vec::new(),
+            outputs: outputs.to_vec(),
+        }
+    }
+
+    pub fn add_pass(
+        &mut self,
+        id: P,
+        reads: &[(Resource, Usage)],
+        writes: &[(Resource, Usage)],
+    ) -> &mut Self {
+        self.passes.push(Pass {
+            id,
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+        });
+        self
+    }
+
+    /// The needed passes in the order they have to be recorded.
+    ///
+    /// Fails if the passes depend on each other in a cycle.
+    pub fn compile(&self) -> Result<Vec<CompiledPass<P>>, anyhow::Error> {
+        let needed = self.needed();
+        let order = self.order(&needed)?;
+
+        let mut last_usage = Vec::<(Resource, Usage)>::new();
+        let compiled = order.into_iter()
+            .map(|index| {
+                let pass = &self.passes[index];
+                let mut barriers = Vec::new();
+                for &(resource, usage) in pass.reads.iter().chain(&pass.writes) {
+                    match last_usage.iter_mut().find(|(last, _)| *last == resource) {
+                        Some((_, last)) => {
+                            // reading the same way twice needs no barrier
+                            if *last != usage || !is_read(usage) {
+                                barriers.push(Barrier { resource, src: *last, dst: usage });
+                            }
+                            *last = usage;
+                        }
+                        None => last_usage.push((resource, usage)),
+                    }
+                }
+                CompiledPass { id: pass.id, barriers }
+            })
+            .collect();
+        Ok(compiled)
+    }
+
+    /// Marks every pass that writes an output or something a needed pass reads.
+    fn needed(&self) -> Vec<bool> {
+        let mut needed = vec![false; self.passes.len()];
+        let mut resources = self.outputs.clone();
+        while let Some(resource) = resources.pop() {
+            for (index, pass) in self.passes.iter().enumerate() {
+                if needed[index] || !self.writes(index, resource) {
+                    continue;
+                }
+                needed[index] = true;
+                resources.extend(pass.reads.iter().map(|(read, _)| *read));
+            }
+        }
+        needed
+    }
+
+    /// Sorts the needed passes topologically, keeping the order they were added in if possible.
+    fn order(&self, needed: &[bool]) -> Result<Vec<usize>, anyhow::Error> {
+        let pending = || (0..self.passes.len()).filter(|&index| needed[index]);
+        let mut order = Vec::new();
+        let mut done = vec![false; self.passes.len()];
+        while order.len() < pending().count() {
+            let next = pending().find(|&pass| {
+                !done[pass] && pending().all(|other| done[other] || !self.depends(pass, other))
+            });
+            let Some(next) = next else {
+                return Err(anyhow!("The passes of the render graph depend on each other"));
+            };
+            done[next] = true;
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    /// Whether the pass at `pass` has to run after the pass at `other`.
+    fn depends(&self, pass: usize, other: usize) -> bool {
+        let reads_from = self.passes[pass].reads.iter()
+            .any(|&(resource, _)| self.producers(pass, resource).contains(&other));
+        // written resources are written in order and not before they were read
+        let writes_after = other < pass && self.passes[pass].writes.iter().any(|&(resource, _)| {
+            let read_earlier = (0..other).any(|earlier| self.writes(earlier, resource));
+            self.writes(other, resource) || self.reads(other, resource) && read_earlier
+        });
+        reads_from || writes_after
+    }
+
+    /// The passes whose writes of `resource` the pass at `pass` reads: those added before it
+    /// or if there are none those added after it.
+    fn producers(&self, pass: usize, resource: Resource) -> Vec<usize> {
+        let writers = (0..self.passes.len())
+            .filter(|&index| index != pass && self.writes(index, resource));
+        let (before, after): (Vec<_>, Vec<_>) = writers.partition(|&index| index < pass);
+        if before.is_empty() { after } else { before }
+    }
+
+    fn reads(&self, pass: usize, resource: Resource) -> bool {
+        self.passes[pass].reads.iter().any(|(read, _)| *read == resource)
+    }
+
+    fn writes(&self, pass: usize, resource: Resource) -> bool {
+        self.passes[pass].writes.iter().any(|(written, _)| *written == resource)
+    }
+}
+
+/// Records `barriers` as a single memory barrier.
+///
+/// Passes transition the layouts of their images themselves, the barrier only makes sure that
+/// a pass waits for what the earlier passes wrote.
+///
+/// # Safety
+///
+/// Must be recorded outside of a render pass.
+pub unsafe fn record_barriers(device: &Device, buffer: vk::CommandBuffer, barriers: &[Barrier]) {
+    if barriers.is_empty() {
+        return;
+    }
+    let (mut src_stage, mut dst_stage) = Default::default();
+    let (mut src_access, mut dst_access) = Default::default();
+    for barrier in barriers {
+        src_stage |= barrier.src.stage();
+        dst_stage |= barrier.dst.stage();
+        src_access |= barrier.src.access();
+        dst_access |= barrier.dst.access();
+    }
+    let memory_barrier = vk::MemoryBarrier::default()
+        .src_access_mask(src_access)
+        .dst_access_mask(dst_access);
+    unsafe {
+        device.cmd_pipeline_barrier(
+            buffer,
+            src_stage,
+            dst_stage,
+            vk::DependencyFlags::empty(),
+            &[memory_barrier],
+            &[],
+            &[],
+        );
+    }
+}
+
+fn is_read(usage: Usage) -> bool {
+    matches!(
+        usage,
+        Usage::Sampled
+            | Usage::IndirectRead
+            | Usage::StorageRead
+            | Usage::ComputeRead
+            | Usage::TransferSrc,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_graph() {
+        let sampled = |resource| [(resource, Usage::Sampled)];
+        let drawn = |resource| [(resource, Usage::ColorAttachment)];
+        let mut graph = RenderGraph::new(&[Resource::Swapchain]);
+        graph
+            .add_pass("grade", &sampled(Resource::Scene), &drawn(Resource::Swapchain))
+            .add_pass("trace", &[], &[(Resource::TracedImages, Usage::Storage)])
+            .add_pass("unused", &[], &drawn(Resource::Eyes))
+            .add_pass("scene", &sampled(Resource::TracedImages), &drawn(Resource::Scene))
+            .add_pass("overlay", &[], &drawn(Resource::Swapchain));
+        let compiled = graph.compile().unwrap();
+        let order = compiled.iter().map(|pass| pass.id).collect::<Vec<_>>();
+        assert_eq!(order, ["trace", "scene", "grade", "overlay"]);
+        assert_eq!(compiled[1].barriers, [Barrier {
+            resource: Resource::TracedImages,
+            src: Usage::Storage,
+            dst: Usage::Sampled,
+        }]);
+        assert_eq!(compiled[3].barriers.len(), 1);
+
+        let mut cycle = RenderGraph::new(&[Resource::Swapchain]);
+        cycle
+            .add_pass("a", &sampled(Resource::Scene), &drawn(Resource::Swapchain))
+            .add_pass("b", &[(Resource::Swapchain, Usage::TransferSrc)], &drawn(Resource::Scene));
+        assert!(cycle.compile().is_err());
+    }
+}
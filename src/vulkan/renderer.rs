@@ -0,0 +1,1798 @@
+use crate::math::Matrix4;
+use super::{
+    app::UBO_IDX_ART,
+    clusters::LightClusters,
+    context::{InstanceExtensions, VkContext},
+    crash::{CrashMarkers, Marker},
+    debug::*,
+    grade::GradeTarget,
+    half_res::HalfResArt,
+    occlusion::OcclusionCulling,
+    pipeline::Pipeline,
+    raytracing::RayTracingPipeline,
+    render_graph::{self, RenderGraph, Resource, Usage},
+    resources::ResourceManager,
+    scene::Scene,
+    shadows::ShadowCascades,
+    stereo::{StereoOutput, StereoTarget},
+    still::FrameReadback,
+    swapchain::{SwapchainProperties, SwapchainSupportDetails},
+    texture::Texture,
+    timer::GpuTimer,
+    volumetric::VolumetricFog,
+};
+
+use ash::{
+    ext::{debug_utils, validation_features},
+    khr::{get_surface_capabilities2, swapchain as khr_swapchain},
+    prelude::VkResult,
+    vk, Device, Entry, Instance,
+};
+use raw_window_handle::HasDisplayHandle;
+use std::ffi::{CStr, CString};
+use winit::window::Window;
+
+/// The indices of the pipelines in [`Renderer::pipelines`], the art follows the sky.
+pub const PIPELINE_IDX_MAIN: usize = 0;
+pub const PIPELINE_IDX_CUBE: usize = 1;
+pub const PIPELINE_IDX_SKY: usize = 2;
+pub const PIPELINE_IDX_ART: usize = 3;
+
+/// Size of the shader preview relative to the shorter side of the screen.
+const PREVIEW_SCALE: u32 = 3;
+/// The height of the screen each pixel of the text overlay is scaled up for.
+const OVERLAY_PIXEL_HEIGHT: u32 = 540;
+/// The gallery is cleared to black at the far plane.
+const CLEAR_VALUES: [vk::ClearValue; 2] = [
+    vk::ClearValue {
+        color: vk::ClearColorValue {
+            float32: [0.0, 0.0, 0.0, 1.0],
+        },
+    },
+    vk::ClearValue {
+        depth_stencil: vk::ClearDepthStencilValue {
+            depth: 1.0,
+            stencil: 0,
+        },
+    },
+];
+
+/// What the pipelines of an [`ArtRenderer`] draw into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderTarget {
+    /// The render pass of the gallery with a color and a depth attachment.
+    ///
+    /// In stereo it renders both eyes with multiview, so shaders have to pick the view matrix
+    /// of the eye with `gl_ViewIndex`.
+    pub render_pass: vk::RenderPass,
+    pub samples: vk::SampleCountFlags,
+    pub extent: vk::Extent2D,
+    pub frames_in_flight: usize,
+}
+
+/// The matrices one view of the gallery is drawn with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ViewMatrices {
+    pub view: Matrix4,
+    pub proj: Matrix4,
+    /// The view matrices of the left and the right eye in stereo, otherwise both are `view`.
+    pub eye_views: [Matrix4; 2],
+}
+
+/// What changed since the last frame a renderer was updated for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameState {
+    /// The index of the frame in flight, resources written by the CPU need one copy per frame.
+    pub frame: usize,
+    pub time: f32,
+    /// One entry per view of the split screen, see [`ArtRenderer::record`].
+    pub views: Vec<ViewMatrices>,
+}
+
+/// Art drawn by downstream code with its own resources, e.g. particles or meshes.
+///
+/// A renderer is registered with [`super::VkApp::register_renderer`] and draws into the gallery
+/// after the shader art. It is not drawn into stills, panoramas and loops.
+pub trait ArtRenderer {
+    fn name(&self) -> &str;
+
+    /// Creates the resources and pipelines of the renderer, called once when it is registered.
+    fn create(&mut self, vk_context: &VkContext, target: RenderTarget) -> Result<(), anyhow::Error>;
+
+    /// Recreates what depends on the render target, called after the window was resized
+    /// or stereo was switched on or off. Nothing of the renderer is in use anymore.
+    fn resize(
+        &mut self,
+        vk_context: &VkContext,
+        target: RenderTarget,
+    ) -> Result<(), anyhow::Error> {
+        let _ = (vk_context, target);
+        Ok(())
+    }
+
+    /// Called every frame before it is submitted once the GPU is done with the resources of
+    /// `frame.frame`.
+    fn update(&mut self, vk_context: &VkContext, frame: &FrameState) {
+        let _ = (vk_context, frame);
+    }
+
+    /// Records the draw commands of the view at index `view` of [`FrameState::views`] inside the
+    /// render pass of the gallery with the viewport of the view set.
+    ///
+    /// The command buffers are only recorded again when something in the gallery changes,
+    /// so everything changing per frame has to be read from buffers written in `update`.
+    ///
+    /// # Safety
+    ///
+    /// `buffer` must be in the recording state inside [`RenderTarget::render_pass`].
+    unsafe fn record(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize, view: usize);
+
+    /// # Safety
+    ///
+    /// Nothing of the renderer may be in use anymore.
+    unsafe fn destroy(&mut self, device: &Device);
+}
+
+/// The pipelines drawn over the screen rather than into the gallery, each exists once
+/// [`super::VkApp`] created it after the renderer.
+#[derive(Default)]
+pub struct ScreenPipelines {
+    /// Combines the eyes into the swapchain image while rendering an anaglyph.
+    pub anaglyph: Option<Pipeline>,
+    /// Reflects and grades the gallery into the swapchain image.
+    pub grade: Option<Pipeline>,
+    /// Draws the fragment shader of the previewed art in a corner of the screen.
+    pub preview: Option<Pipeline>,
+    /// Draws the fragment shader of the entered art over the whole screen instead of the gallery.
+    pub entered: Option<Pipeline>,
+    /// Draws the frame graph in the top left corner of the screen.
+    pub graph: Option<Pipeline>,
+    /// Draws the handles of the edited art.
+    pub gizmo: Option<Pipeline>,
+    /// Blends black over the whole screen while the scene fades.
+    pub fade: Option<Pipeline>,
+    /// Draws the text overlay in the top right corner of the screen.
+    pub overlay: Option<Pipeline>,
+}
+
+impl ScreenPipelines {
+    /// The pipelines drawing into the swapchain image with the composite render pass.
+    pub fn composite_mut(&mut self) -> impl Iterator<Item = &mut Pipeline> {
+        self.anaglyph.iter_mut().chain(self.grade.as_mut())
+    }
+
+    /// The pipelines drawing into the render pass of the gallery.
+    pub fn gallery_mut(&mut self) -> impl Iterator<Item = &mut Pipeline> {
+        self.preview.iter_mut()
+            .chain(self.entered.as_mut())
+            .chain(self.graph.as_mut())
+            .chain(self.gizmo.as_mut())
+            .chain(self.fade.as_mut())
+            .chain(self.overlay.as_mut())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Pipeline> {
+        self.anaglyph.iter_mut()
+            .chain(self.grade.as_mut())
+            .chain(self.preview.as_mut())
+            .chain(self.entered.as_mut())
+            .chain(self.graph.as_mut())
+            .chain(self.gizmo.as_mut())
+            .chain(self.fade.as_mut())
+            .chain(self.overlay.as_mut())
+    }
+}
+
+/// What the command buffers of a frame draw besides the scene, the resources and the pipelines
+/// of the renderer, see [`Renderer::record_command_buffers`].
+pub struct FrameInputs<'a> {
+    pub half_res_art: &'a [HalfResArt],
+    pub ray_tracing_pipelines: &'a [RayTracingPipeline],
+    pub renderers: &'a [Box<dyn ArtRenderer>],
+    pub stereo_target: Option<&'a StereoTarget>,
+    /// The target the gallery is drawn into if it is graded.
+    pub grade_target: Option<&'a GradeTarget>,
+    pub show_frame_graph: bool,
+    /// The size of the text overlay if there is one.
+    pub overlay_size: Option<[u32; 2]>,
+    /// The parts of the render target the gallery is drawn into, one per camera.
+    pub views: &'a [vk::Rect2D],
+    pub render_extent: vk::Extent2D,
+    pub ubo_stride: u32,
+    pub timer: Option<&'a GpuTimer>,
+    pub crash_markers: Option<&'a CrashMarkers>,
+    /// Draws the depth of the environment for the culling and the reflections.
+    pub occlusion: Option<&'a OcclusionCulling>,
+    pub occlusion_culling: bool,
+    pub reflections: bool,
+    pub light_clusters: Option<&'a LightClusters>,
+    pub shadows: &'a ShadowCascades,
+    pub fog_volume: Option<&'a VolumetricFog>,
+}
+
+/// The device, the swapchain and the frames in flight the gallery is drawn with.
+///
+/// It owns the pipelines of the gallery and of the screen and records the command buffers of
+/// the frames, what is drawn with them is decided by [`super::VkApp`].
+pub struct Renderer {
+    pub vk_context: VkContext,
+    pub graphics_queue: vk::Queue,
+    pub present_queue: vk::Queue,
+    pub swapchain: khr_swapchain::Device,
+    pub swapchain_khr: vk::SwapchainKHR,
+    /// Swapchains replaced on recreation with the number of frames until they are destroyed,
+    /// their images may still be presented.
+    pub retired_swapchains: Vec<(vk::SwapchainKHR, usize)>,
+    pub swapchain_properties: SwapchainProperties,
+    /// The number of swapchain images to request if the surface supports it.
+    pub preferred_image_count: Option<u32>,
+    /// Whether the swapchain is pre-rotated if the surface is rotated, see
+    /// `VkApp::can_pre_rotate`. Otherwise the compositor rotates the images.
+    pub pre_rotate: bool,
+    /// The monitor the swapchain is created for while in exclusive fullscreen.
+    pub exclusive_monitor: Option<vk::HMONITOR>,
+    /// Whether exclusive fullscreen is acquired for the current swapchain.
+    pub full_screen_exclusive_acquired: bool,
+    pub images: Vec<vk::Image>,
+    pub swapchain_image_views: Vec<vk::ImageView>,
+    pub render_pass: vk::RenderPass,
+    pub swapchain_framebuffers: Vec<vk::Framebuffer>,
+    /// A render pass without depth to draw post passes like the anaglyph into the swapchain.
+    pub composite_render_pass: vk::RenderPass,
+    pub composite_framebuffers: Vec<vk::Framebuffer>,
+    pub msaa_samples: vk::SampleCountFlags,
+    pub color_texture: Texture,
+    pub depth_format: vk::Format,
+    pub depth_texture: Texture,
+    pub command_pool: vk::CommandPool,
+    pub transient_command_pool: vk::CommandPool,
+    /// The environment, the cubemap, the sky and the art, see [`PIPELINE_IDX_ART`].
+    pub pipelines: Vec<Pipeline>,
+    pub screen: ScreenPipelines,
+    pub frames: Vec<FrameData>,
+    /// Index of the frame in `frames` that is drawn next.
+    pub current_frame: usize,
+}
+
+impl Renderer {
+    /// Creates the swapchain of a window of `dimensions`, what is drawn into it and
+    /// `frames_in_flight` frames. The pipelines are added afterwards.
+    pub fn new(
+        vk_context: VkContext,
+        dimensions: [u32; 2],
+        preferred_image_count: Option<u32>,
+        frames_in_flight: usize,
+    ) -> Self {
+        let graphics_queue = unsafe {
+            vk_context.device().get_device_queue(vk_context.graphics_queue_index(), 0)
+        };
+        let present_queue = unsafe {
+            vk_context.device().get_device_queue(vk_context.present_queue_index(), 0)
+        };
+
+        let (swapchain, swapchain_khr, properties, images) =
+            Self::create_swapchain_and_images(
+                &vk_context,
+                dimensions,
+                preferred_image_count,
+                false,
+                None,
+                vk::SwapchainKHR::null(),
+            );
+        let swapchain_image_views =
+            Self::create_swapchain_image_views(vk_context.device(), &images, properties);
+
+        let msaa_samples = vk_context.get_max_usable_sample_count();
+        log::debug!("Chosen msaa: {msaa_samples:?}");
+        let depth_format = Self::find_depth_format(&vk_context);
+
+        let render_pass =
+            Self::create_render_pass(vk_context.device(), properties, msaa_samples, depth_format);
+        let composite_render_pass =
+            Self::create_composite_render_pass(vk_context.device(), properties);
+        let composite_framebuffers = Self::create_composite_framebuffers(
+            vk_context.device(),
+            &swapchain_image_views,
+            composite_render_pass,
+            properties,
+        );
+
+        let command_pool =
+            vk_context.create_command_pool(vk::CommandPoolCreateFlags::empty());
+        let transient_command_pool =
+            vk_context.create_command_pool(vk::CommandPoolCreateFlags::TRANSIENT);
+
+        let color_texture = Self::create_color_texture(
+            &vk_context,
+            command_pool,
+            graphics_queue,
+            properties,
+            msaa_samples,
+        );
+
+        let depth_texture = Self::create_depth_texture(
+            &vk_context,
+            command_pool,
+            graphics_queue,
+            depth_format,
+            properties.extent,
+            msaa_samples,
+        );
+
+        let swapchain_framebuffers = Self::create_framebuffers(
+            vk_context.device(),
+            &swapchain_image_views,
+            color_texture,
+            depth_texture,
+            render_pass,
+            properties,
+        );
+
+        let frames = (0..frames_in_flight)
+            .map(|_| FrameData::new(vk_context.device(), vk_context.swapchain_maintenance()))
+            .collect();
+
+        Self {
+            vk_context,
+            graphics_queue,
+            present_queue,
+            swapchain,
+            swapchain_khr,
+            retired_swapchains: Vec::new(),
+            swapchain_properties: properties,
+            preferred_image_count,
+            pre_rotate: false,
+            exclusive_monitor: None,
+            full_screen_exclusive_acquired: false,
+            images,
+            swapchain_image_views,
+            render_pass,
+            swapchain_framebuffers,
+            composite_render_pass,
+            composite_framebuffers,
+            msaa_samples,
+            color_texture,
+            depth_format,
+            depth_texture,
+            command_pool,
+            transient_command_pool,
+            pipelines: Vec::new(),
+            screen: ScreenPipelines::default(),
+            frames,
+            current_frame: 0,
+        }
+    }
+
+    /// Creates a swapchain of `dimensions` that retires the current one and everything drawn
+    /// into its images. The frames in flight must be done drawing, the pipelines are cleaned up
+    /// and have to be recreated by the caller.
+    pub fn recreate_swapchain(&mut self, dimensions: [u32; 2]) {
+        self.cleanup_swapchain();
+        let device = self.vk_context.device();
+        let (swapchain, swapchain_khr, properties, images) = Self::create_swapchain_and_images(
+            &self.vk_context,
+            dimensions,
+            self.preferred_image_count,
+            self.pre_rotate,
+            self.exclusive_monitor,
+            self.swapchain_khr,
+        );
+        self.retired_swapchains.push((self.swapchain_khr, self.frames.len()));
+        let swapchain_image_views = Self::create_swapchain_image_views(device, &images, properties);
+
+        let render_pass =
+            Self::create_render_pass(device, properties, self.msaa_samples, self.depth_format);
+        let composite_render_pass = Self::create_composite_render_pass(device, properties);
+
+        let color_texture = Self::create_color_texture(
+            &self.vk_context,
+            self.command_pool,
+            self.graphics_queue,
+            properties,
+            self.msaa_samples,
+        );
+
+        let depth_texture = Self::create_depth_texture(
+            &self.vk_context,
+            self.command_pool,
+            self.graphics_queue,
+            self.depth_format,
+            properties.extent,
+            self.msaa_samples,
+        );
+
+        let swapchain_framebuffers = Self::create_framebuffers(
+            device,
+            &swapchain_image_views,
+            color_texture,
+            depth_texture,
+            render_pass,
+            properties,
+        );
+        let composite_framebuffers = Self::create_composite_framebuffers(
+            device,
+            &swapchain_image_views,
+            composite_render_pass,
+            properties,
+        );
+
+        self.swapchain = swapchain;
+        self.swapchain_khr = swapchain_khr;
+        self.swapchain_properties = properties;
+        self.images = images;
+        self.swapchain_image_views = swapchain_image_views;
+        self.render_pass = render_pass;
+        self.color_texture = color_texture;
+        self.depth_texture = depth_texture;
+        self.swapchain_framebuffers = swapchain_framebuffers;
+        self.composite_render_pass = composite_render_pass;
+        self.composite_framebuffers = composite_framebuffers;
+    }
+
+    /// Destroys everything drawn into the swapchain images and the pipelines using it,
+    /// the swapchain itself is retired by the next one.
+    fn cleanup_swapchain(&mut self) {
+        let device = self.vk_context.device();
+        unsafe {
+            self.depth_texture.destroy(device);
+            self.color_texture.destroy(device);
+            for framebuffer in self.swapchain_framebuffers.iter() {
+                device.destroy_framebuffer(*framebuffer, None);
+            }
+            for pipeline in self.pipelines.iter_mut().chain(self.screen.iter_mut()) {
+                pipeline.cleanup_pip(device);
+            }
+            for framebuffer in self.composite_framebuffers.iter() {
+                device.destroy_framebuffer(*framebuffer, None);
+            }
+            device.destroy_render_pass(self.composite_render_pass, None);
+            device.destroy_render_pass(self.render_pass, None);
+            for image_view in self.swapchain_image_views.iter() {
+                device.destroy_image_view(*image_view, None);
+            }
+        }
+    }
+
+    pub fn wait_idle(&self) -> VkResult<()> {
+        unsafe { self.vk_context.device().device_wait_idle() }
+    }
+
+    /// Waits until every frame in flight is drawn, unlike `wait_idle` presentation goes on.
+    pub fn wait_for_frames(&self) -> VkResult<()> {
+        let fences = self.frames.iter().map(|frame| frame.fence).collect::<Vec<_>>();
+        unsafe { self.vk_context.device().wait_for_fences(&fences, true, u64::MAX) }
+    }
+
+    /// Moves on to the next frame in flight and returns the index of the one to draw now.
+    pub fn next_frame(&mut self) -> usize {
+        let frame_index = self.current_frame;
+        self.current_frame = (self.current_frame + 1) % self.frames.len();
+        frame_index
+    }
+
+    /// Waits until the frame at `frame_index` is drawn and its image is done presenting.
+    pub fn wait_for_frame(&self, frame_index: usize) -> VkResult<()> {
+        let frame = &self.frames[frame_index];
+        let device = self.vk_context.device();
+        unsafe {
+            device.wait_for_fences(&[frame.fence], true, u64::MAX)?;
+            if let Some(present_fence) = frame.present_fence {
+                device.wait_for_fences(&[present_fence], true, u64::MAX)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Acquires the swapchain image the frame at `frame_index` is drawn into.
+    ///
+    /// # Returns
+    ///
+    /// The index of the image or `None` if the swapchain is dirty and needs to be recreated.
+    pub fn acquire_next_image(&mut self, frame_index: usize) -> Option<u32> {
+        // a retired swapchain is done presenting once every frame in flight has been drawn again,
+        // this is only guaranteed when waiting for the present fences of the frames
+        self.retired_swapchains.retain_mut(|(swapchain_khr, frames_left)| {
+            if *frames_left == 0 {
+                unsafe { self.swapchain.destroy_swapchain(*swapchain_khr, None) };
+                false
+            } else {
+                *frames_left -= 1;
+                true
+            }
+        });
+
+        let frame = &self.frames[frame_index];
+        let result = unsafe {
+            self.swapchain.acquire_next_image(
+                self.swapchain_khr,
+                u64::MAX,
+                frame.image_available_semaphore,
+                vk::Fence::null(),
+            )
+        };
+        let image_index = match result {
+            // ignore suboptimal swap chain here because we already acquired an image
+            Ok((image_index, _suboptimal)) => image_index,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                return None;
+            }
+            // acquired again once the swapchain is recreated
+            Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) => {
+                self.full_screen_exclusive_acquired = false;
+                return None;
+            }
+            Err(error) => panic!("Error while acquiring next image. Cause: {}", error),
+        };
+
+        // it is important to only reset the fence when we know that we are going to do work
+        unsafe { self.vk_context.device().reset_fences(&[frame.fence]).unwrap() };
+        Some(image_index)
+    }
+
+    /// Submits `command_buffers` which signal the fence of the frame at `frame_index`,
+    /// the swapchain images of `wait_semaphores` are only needed once they are drawn to.
+    pub fn submit(
+        &self,
+        frame_index: usize,
+        wait_semaphores: &[vk::Semaphore],
+        command_buffers: &[vk::CommandBuffer],
+        signal_semaphores: &[vk::Semaphore],
+    ) -> VkResult<()> {
+        let waits = wait_semaphores.iter()
+            .map(|&semaphore| (semaphore, vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT))
+            .collect::<Vec<_>>();
+        unsafe {
+            self.vk_context.queue_submit2(
+                self.graphics_queue,
+                &waits,
+                command_buffers,
+                signal_semaphores,
+                self.frames[frame_index].fence,
+            )
+        }
+    }
+
+    /// Presents the image at `image_index` together with the images of `other_swapchains`,
+    /// e.g. the one of a second window, once `wait_semaphores` are signaled.
+    ///
+    /// # Returns
+    ///
+    /// The result of every swapchain, the one of the gallery first.
+    pub fn present(
+        &mut self,
+        frame_index: usize,
+        image_index: u32,
+        wait_semaphores: &[vk::Semaphore],
+        other_swapchains: &[(vk::SwapchainKHR, u32)],
+    ) -> Vec<vk::Result> {
+        let swapchains = [self.swapchain_khr].into_iter()
+            .chain(other_swapchains.iter().map(|&(swapchain_khr, _)| swapchain_khr))
+            .collect::<Vec<_>>();
+        let images_indices = [image_index].into_iter()
+            .chain(other_swapchains.iter().map(|&(_, index)| index))
+            .collect::<Vec<_>>();
+        let mut results = vec![vk::Result::SUCCESS; swapchains.len()];
+        let mut present_info = vk::PresentInfoKHR::default()
+            .wait_semaphores(wait_semaphores)
+            .swapchains(&swapchains)
+            .image_indices(&images_indices)
+            .results(&mut results);
+        // only the swapchain of the gallery has a present fence
+        let present_fences = self.frames[frame_index].present_fence.map(|fence| {
+            let mut fences = vec![vk::Fence::null(); swapchains.len()];
+            fences[0] = fence;
+            fences
+        });
+        let mut present_fence_info = vk::SwapchainPresentFenceInfoEXT::default();
+        if let Some(present_fences) = present_fences.as_ref() {
+            let device = self.vk_context.device();
+            unsafe { device.reset_fences(&present_fences[..1]).unwrap() };
+            present_fence_info = present_fence_info.fences(present_fences);
+            present_info = present_info.push_next(&mut present_fence_info);
+        }
+        let result = unsafe {
+            self.swapchain.queue_present(self.present_queue, &present_info)
+        };
+        match result {
+            Ok(_) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {}
+            Err(vk::Result::ERROR_FULL_SCREEN_EXCLUSIVE_MODE_LOST_EXT) => {
+                self.full_screen_exclusive_acquired = false;
+            }
+            Err(error) => panic!("Failed to present queue. Cause: {}", error),
+        }
+        results
+    }
+
+    #[cfg(windows)]
+    pub fn hmonitor(window: &Window) -> Option<vk::HMONITOR> {
+        use winit::platform::windows::MonitorHandleExtWindows;
+
+        window.current_monitor().map(|monitor| monitor.hmonitor())
+    }
+
+    #[cfg(not(windows))]
+    pub fn hmonitor(_window: &Window) -> Option<vk::HMONITOR> {
+        None
+    }
+
+    /// Acquires exclusive fullscreen for the current swapchain if it was requested.
+    pub fn acquire_full_screen_exclusive(&mut self) {
+        let Some(full_screen_exclusive) = self.vk_context.full_screen_exclusive() else {
+            return;
+        };
+        if self.exclusive_monitor.is_none() || self.full_screen_exclusive_acquired {
+            return;
+        }
+        let result = unsafe {
+            full_screen_exclusive.acquire_full_screen_exclusive_mode(self.swapchain_khr)
+        };
+        match result {
+            Ok(()) => {
+                log::info!("Acquired exclusive fullscreen");
+                self.full_screen_exclusive_acquired = true;
+            }
+            Err(err) => log::warn!("Failed to acquire exclusive fullscreen: {err}"),
+        }
+    }
+
+    pub fn release_full_screen_exclusive(&mut self) {
+        let Some(full_screen_exclusive) = self.vk_context.full_screen_exclusive() else {
+            return;
+        };
+        if !std::mem::take(&mut self.full_screen_exclusive_acquired) {
+            return;
+        }
+        let result = unsafe {
+            full_screen_exclusive.release_full_screen_exclusive_mode(self.swapchain_khr)
+        };
+        if let Err(err) = result {
+            log::warn!("Failed to release exclusive fullscreen: {err}");
+        }
+    }
+
+
+    /// # Returns
+    ///
+    /// The instance, the optional extensions that are enabled and the validation that is
+    /// enabled, which is less than requested if the layers are missing.
+    pub fn create_instance(
+        entry: &Entry,
+        window: &Window,
+        mut validation: Validation,
+        extra_extensions: &[CString],
+    ) -> (Instance, InstanceExtensions, Validation) {
+        if validation.enabled && !check_validation_layer_support(entry) {
+            validation = Validation::OFF;
+        }
+        let validation_features = validation.enabled && validation.needs_features();
+        if validation_features && !check_validation_features_support(entry) {
+            log::warn!("GPU-assisted validation, best practices and printf are not supported");
+            validation.gpu_assisted = false;
+            validation.best_practices = false;
+            validation.printf = false;
+        }
+        log::info!("Validation: {validation:?}");
+
+        let app_name = CString::new("Vulkan Application").unwrap();
+        let engine_name = CString::new("No Engine").unwrap();
+        // Vulkan 1.1 is needed for multiview stereo and to enable extensions like mesh shaders,
+        // Vulkan 1.3 has synchronization2 in its core
+        let api_version = unsafe { entry.try_enumerate_instance_version() }
+            .ok()
+            .flatten()
+            .unwrap_or(vk::API_VERSION_1_0)
+            .min(vk::API_VERSION_1_3);
+        let app_info = vk::ApplicationInfo::default()
+            .application_name(app_name.as_c_str())
+            .application_version(vk::make_api_version(0, 0, 1, 0))
+            .engine_name(engine_name.as_c_str())
+            .engine_version(vk::make_api_version(0, 0, 1, 0))
+            .api_version(api_version);
+
+        let extension_names =
+            ash_window::enumerate_required_extensions(window.display_handle().unwrap().as_raw())
+                .unwrap();
+        let mut extension_names = extension_names.to_vec();
+        if validation.enabled {
+            extension_names.push(debug_utils::NAME.as_ptr());
+        }
+        if validation.needs_features() {
+            extension_names.push(validation_features::NAME.as_ptr());
+        }
+        #[cfg(any(target_os = "macos", target_os = "ios"))]
+        {
+            extension_names.push(ash::khr::portability_enumeration::NAME.as_ptr());
+            // Enabling this extension is a requirement when using `VK_KHR_portability_subset`
+            extension_names.push(ash::khr::get_physical_device_properties2::NAME.as_ptr());
+        }
+        let available_extensions =
+            unsafe { entry.enumerate_instance_extension_properties(None) }.unwrap_or_default();
+        let is_available = |required_ext: &CStr| {
+            available_extensions.iter().any(|ext| ext.extension_name_as_c_str() == Ok(required_ext))
+        };
+        // needed for exclusive fullscreen and the swapchain maintenance of the device,
+        // which allows to wait until a retired swapchain is done presenting
+        let surface_capabilities2 = is_available(get_surface_capabilities2::NAME);
+        if surface_capabilities2 {
+            extension_names.push(get_surface_capabilities2::NAME.as_ptr());
+        }
+        let surface_maintenance =
+            surface_capabilities2 && is_available(vk::EXT_SURFACE_MAINTENANCE1_NAME);
+        if surface_maintenance {
+            extension_names.push(vk::EXT_SURFACE_MAINTENANCE1_NAME.as_ptr());
+        }
+        let instance_extensions = InstanceExtensions { surface_capabilities2, surface_maintenance };
+        for extension in extra_extensions {
+            let enabled = extension_names.iter()
+                .any(|&name| unsafe { CStr::from_ptr(name) } == extension.as_c_str());
+            if !enabled {
+                extension_names.push(extension.as_ptr());
+            }
+        }
+
+        let (_layer_names, layer_names_ptrs) = get_layer_names_and_pointers();
+
+        let create_flags = if cfg!(any(target_os = "macos", target_os = "ios")) {
+            vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+        } else {
+            vk::InstanceCreateFlags::default()
+        };
+        let mut instance_create_info = vk::InstanceCreateInfo::default()
+            .application_info(&app_info)
+            .enabled_extension_names(&extension_names)
+            .flags(create_flags);
+        if validation.enabled {
+            instance_create_info = instance_create_info.enabled_layer_names(&layer_names_ptrs);
+        }
+        let enabled_features = enabled_validation_features(validation);
+        let mut features = vk::ValidationFeaturesEXT::default()
+            .enabled_validation_features(&enabled_features);
+        if !enabled_features.is_empty() {
+            instance_create_info = instance_create_info.push_next(&mut features);
+        }
+
+        let instance = unsafe { entry.create_instance(&instance_create_info, None).unwrap() };
+        (instance, instance_extensions, validation)
+    }
+
+    /// Create the swapchain with optimal settings possible with `device`.
+    ///
+    /// # Returns
+    ///
+    /// A tuple containing the swapchain loader and the actual swapchain.
+    /// Creates the swapchain, `old_swapchain` is retired by it but still has to be destroyed.
+    fn create_swapchain_and_images(
+        vk_context: &VkContext,
+        dimensions: [u32; 2],
+        preferred_image_count: Option<u32>,
+        pre_rotate: bool,
+        exclusive_monitor: Option<vk::HMONITOR>,
+        old_swapchain: vk::SwapchainKHR,
+    ) -> (
+        khr_swapchain::Device,
+        vk::SwapchainKHR,
+        SwapchainProperties,
+        Vec<vk::Image>,
+    ) {
+        let details = SwapchainSupportDetails::new(
+            vk_context.physical_device(),
+            vk_context.surface(),
+            vk_context.surface_khr(),
+        );
+        let properties = details.get_ideal_swapchain_properties(dimensions, pre_rotate);
+
+        let format = properties.format;
+        let present_mode = properties.present_mode;
+        let extent = properties.extent;
+        let image_count = details.image_count(preferred_image_count);
+        match preferred_image_count {
+            Some(preferred) if preferred != image_count => log::warn!(
+                "The display takes {image_count} swapchain images instead of {preferred}",
+            ),
+            _ => log::debug!("Swapchain images: {image_count}"),
+        }
+
+        log::debug!("Creating swapchain.");
+
+        let graphics = vk_context.graphics_queue_index();
+        let present = vk_context.present_queue_index();
+        let families_indices = [graphics, present];
+
+        // the app decides when to acquire exclusive fullscreen, which needs the monitor
+        let mut full_screen_exclusive_info = vk::SurfaceFullScreenExclusiveInfoEXT::default()
+            .full_screen_exclusive(vk::FullScreenExclusiveEXT::APPLICATION_CONTROLLED);
+        let mut full_screen_exclusive_win32_info =
+            vk::SurfaceFullScreenExclusiveWin32InfoEXT::default()
+                .hmonitor(exclusive_monitor.unwrap_or_default());
+
+        // stereo rendering copies both eyes into the swapchain images
+        let mut image_usage =
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_DST;
+        if properties.readable {
+            image_usage |= vk::ImageUsageFlags::TRANSFER_SRC;
+        }
+
+        let create_info = {
+            let mut builder = vk::SwapchainCreateInfoKHR::default()
+                .surface(vk_context.surface_khr())
+                .min_image_count(image_count)
+                .image_format(format.format)
+                .image_color_space(format.color_space)
+                .image_extent(extent)
+                .image_array_layers(1)
+                .image_usage(image_usage);
+
+            builder = if graphics != present {
+                builder
+                    .image_sharing_mode(vk::SharingMode::CONCURRENT)
+                    .queue_family_indices(&families_indices)
+            } else {
+                builder.image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+            };
+            if exclusive_monitor.is_some() && vk_context.full_screen_exclusive().is_some() {
+                builder = builder
+                    .push_next(&mut full_screen_exclusive_info)
+                    .push_next(&mut full_screen_exclusive_win32_info);
+            }
+
+            builder
+                .pre_transform(properties.transform)
+                .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                .present_mode(present_mode)
+                .clipped(true)
+                .old_swapchain(old_swapchain)
+        };
+
+        let swapchain = khr_swapchain::Device::new(vk_context.instance(), vk_context.device());
+        let swapchain_khr = unsafe { swapchain.create_swapchain(&create_info, None).unwrap() };
+        let images = unsafe { swapchain.get_swapchain_images(swapchain_khr).unwrap() };
+        (swapchain, swapchain_khr, properties, images)
+    }
+
+    /// Create one image view for each image of the swapchain.
+    fn create_swapchain_image_views(
+        device: &Device,
+        swapchain_images: &[vk::Image],
+        swapchain_properties: SwapchainProperties,
+    ) -> Vec<vk::ImageView> {
+        swapchain_images.iter()
+            .map(|image| {
+                ResourceManager::create_image_view(
+                    device,
+                    *image,
+                    1,
+                    swapchain_properties.format.format,
+                    vk::ImageAspectFlags::COLOR,
+                )
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn create_render_pass(
+        device: &Device,
+        swapchain_properties: SwapchainProperties,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> vk::RenderPass {
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(swapchain_properties.format.format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachement_desc = vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_desc = vk::AttachmentDescription::default()
+            .format(swapchain_properties.format.format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        let attachment_descs = [
+            color_attachment_desc,
+            depth_attachement_desc,
+            resolve_attachment_desc,
+        ];
+
+        let color_attachment_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let color_attachment_refs = [color_attachment_ref];
+
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+
+        let resolve_attachment_ref = vk::AttachmentReference::default()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_refs = [resolve_attachment_ref];
+
+        let subpass_desc = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .resolve_attachments(&resolve_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref);
+        let subpass_descs = [subpass_desc];
+
+        let subpass_dep = vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(
+                vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            );
+        let subpass_deps = [subpass_dep];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    /// Create a render pass that writes directly into a swapchain image without depth or msaa.
+    fn create_composite_render_pass(
+        device: &Device,
+        swapchain_properties: SwapchainProperties,
+    ) -> vk::RenderPass {
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(swapchain_properties.format.format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+        let attachment_descs = [color_attachment_desc];
+
+        let color_attachment_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let color_attachment_refs = [color_attachment_ref];
+
+        let subpass_desc = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs);
+        let subpass_descs = [subpass_desc];
+
+        let subpass_dep = vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE);
+        let subpass_deps = [subpass_dep];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    fn create_framebuffers(
+        device: &Device,
+        image_views: &[vk::ImageView],
+        color_texture: Texture,
+        depth_texture: Texture,
+        render_pass: vk::RenderPass,
+        swapchain_properties: SwapchainProperties,
+    ) -> Vec<vk::Framebuffer> {
+        image_views.iter()
+            .map(|view| [color_texture.view, depth_texture.view, *view])
+            .map(|attachments| {
+                let framebuffer_info = vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(swapchain_properties.extent.width)
+                    .height(swapchain_properties.extent.height)
+                    .layers(1);
+                unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn create_composite_framebuffers(
+        device: &Device,
+        image_views: &[vk::ImageView],
+        render_pass: vk::RenderPass,
+        swapchain_properties: SwapchainProperties,
+    ) -> Vec<vk::Framebuffer> {
+        image_views.iter()
+            .map(|view| [*view])
+            .map(|attachments| {
+                let framebuffer_info = vk::FramebufferCreateInfo::default()
+                    .render_pass(render_pass)
+                    .attachments(&attachments)
+                    .width(swapchain_properties.extent.width)
+                    .height(swapchain_properties.extent.height)
+                    .layers(1);
+                unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
+            })
+            .collect::<Vec<_>>()
+    }
+
+    fn create_color_texture(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        transition_queue: vk::Queue,
+        swapchain_properties: SwapchainProperties,
+        msaa_samples: vk::SampleCountFlags,
+    ) -> Texture {
+        let format = swapchain_properties.format.format;
+        let (image, memory) = ResourceManager::create_image(
+            vk_context,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            swapchain_properties.extent,
+            1,
+            msaa_samples,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+        );
+
+        ResourceManager::transition_image_layout(
+            vk_context,
+            command_pool,
+            transition_queue,
+            image,
+            1,
+            format,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+            1,
+        );
+
+        let view = ResourceManager::create_image_view(
+            vk_context.device(),
+            image,
+            1,
+            format,
+            vk::ImageAspectFlags::COLOR,
+        );
+
+        Texture::new(image, memory, view, None)
+    }
+
+    /// Create the depth buffer texture (image, memory and view).
+    ///
+    /// This function also transitions the image to be ready to be used
+    /// as a depth/stencil attachement.
+    fn create_depth_texture(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        transition_queue: vk::Queue,
+        format: vk::Format,
+        extent: vk::Extent2D,
+        msaa_samples: vk::SampleCountFlags,
+    ) -> Texture {
+        let (image, mem) = ResourceManager::create_image(
+            vk_context,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            extent,
+            1,
+            msaa_samples,
+            format,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+        );
+
+        let device = vk_context.device();
+        ResourceManager::transition_image_layout(
+            vk_context,
+            command_pool,
+            transition_queue,
+            image,
+            1,
+            format,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+            1,
+        );
+
+        let aspect = vk::ImageAspectFlags::DEPTH;
+        let view = ResourceManager::create_image_view(device, image, 1, format, aspect);
+
+        Texture::new(image, mem, view, None)
+    }
+
+    fn find_depth_format(vk_context: &VkContext) -> vk::Format {
+        let candidates = [
+            vk::Format::D32_SFLOAT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ];
+        vk_context
+            .find_supported_format(
+                &candidates,
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT,
+            )
+            .expect("Failed to find a supported depth format")
+    }
+
+    /// Records a command buffer for every swapchain image that draws `scene` with the
+    /// descriptor sets and the ray traced images of `frame`.
+    pub fn record_command_buffers(
+        &self,
+        frame: usize,
+        scene: &Scene,
+        resources: &ResourceManager,
+        inputs: &FrameInputs,
+    ) -> Vec<vk::CommandBuffer> {
+        let &FrameInputs {
+            half_res_art,
+            ray_tracing_pipelines,
+            stereo_target,
+            views,
+            render_extent,
+            timer,
+            crash_markers,
+            occlusion,
+            occlusion_culling,
+            reflections,
+            light_clusters,
+            shadows,
+            fog_volume,
+            ..
+        } = inputs;
+        let device = self.vk_context.device();
+        let pipelines = &self.pipelines;
+        let pipeline_entered = self.screen.entered.as_ref();
+        let readback = self.frames[frame].readback.as_ref();
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(self.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(self.swapchain_framebuffers.len() as _);
+        let buffers = unsafe { device.allocate_command_buffers(&allocate_info).unwrap() };
+
+        // grading is skipped in stereo
+        let grade = inputs.grade_target.zip(self.screen.grade.as_ref())
+            .filter(|_| stereo_target.is_none());
+        // only a single view of the gallery is culled, clustered and fogged with froxels
+        let single_view =
+            views.len() == 1 && stereo_target.is_none() && pipeline_entered.is_none();
+        let occlusion = occlusion.filter(|_| single_view);
+        let light_clusters = light_clusters.filter(|_| single_view);
+        let fog_volume = fog_volume.filter(|_| single_view);
+        // the depth of the environment is drawn with the culling and reflected in the post pass
+        let reflections = reflections && occlusion.is_some() && grade.is_some();
+        let culled = occlusion.filter(|_| occlusion_culling);
+        // the images are scaled up to a single view
+        let half_res_art = half_res_art.iter()
+            .filter(|art| single_view && art.is_ready(&pipelines[art.index]))
+            .collect::<Vec<_>>();
+        let passes = Self::frame_graph(
+            !ray_tracing_pipelines.is_empty(),
+            culled.is_some(),
+            !half_res_art.is_empty(),
+            reflections,
+            light_clusters.is_some(),
+            fog_volume.is_some(),
+            stereo_target.map(StereoTarget::output),
+            self.screen.anaglyph.as_ref().is_some_and(|pipeline| !pipeline.waiting_for_shaders),
+            grade.is_some(),
+            readback.is_some(),
+        ).compile().unwrap();
+        let swapchain_extent = self.swapchain_properties.extent;
+
+        for (i, &buffer) in buffers.iter().enumerate() {
+            // begin command buffer
+            let command_buffer_begin_info = vk::CommandBufferBeginInfo::default()
+                .flags(vk::CommandBufferUsageFlags::SIMULTANEOUS_USE);
+            unsafe {
+                device.begin_command_buffer(buffer, &command_buffer_begin_info).unwrap()
+            };
+            if let Some(timer) = timer {
+                unsafe { timer.begin_frame(device, buffer, frame) };
+            }
+
+            for pass in passes.iter() {
+                unsafe { render_graph::record_barriers(device, buffer, &pass.barriers) };
+                match pass.id {
+                    FramePass::Upload => unsafe {
+                        for texture in resources.streams() {
+                            texture.record_upload(device, buffer, frame);
+                        }
+                    },
+                    FramePass::Trace => {
+                        for pipeline in ray_tracing_pipelines.iter() {
+                            unsafe { pipeline.trace_to_cmd_buffer(device, buffer, frame) };
+                        }
+                    }
+                    FramePass::Occlusion => unsafe {
+                        let main = &pipelines[PIPELINE_IDX_MAIN];
+                        let geometry = main.geometry.as_ref();
+                        let set = main.descriptor_sets()[frame];
+                        occlusion.unwrap().record(device, buffer, frame, geometry, set);
+                    },
+                    FramePass::Clusters => unsafe {
+                        light_clusters.unwrap().record(device, buffer, frame);
+                    },
+                    FramePass::Shadows => unsafe {
+                        let geometry = pipelines[PIPELINE_IDX_MAIN].geometry.as_ref();
+                        shadows.record(device, buffer, frame, geometry);
+                    },
+                    FramePass::Fog => unsafe {
+                        fog_volume.unwrap().record(device, buffer, frame);
+                    },
+                    FramePass::HalfRes => {
+                        for art in half_res_art.iter() {
+                            let pipeline = &pipelines[art.index];
+                            let draw_command = culled
+                                .filter(|_| pipeline.bounds().is_some())
+                                .map(|occlusion| occlusion.draw_command(frame, art.index));
+                            unsafe {
+                                // the art is timed here as scaling it up takes little time
+                                if let Some(timer) = timer {
+                                    timer.begin_pipeline(device, buffer, frame, art.index);
+                                }
+                                let extent = views[0].extent;
+                                art.record(device, buffer, frame, extent, pipeline, draw_command);
+                                if let Some(timer) = timer {
+                                    timer.end_pipeline(device, buffer, frame, art.index);
+                                }
+                                if let Some(markers) = crash_markers {
+                                    markers.mark(buffer, Marker::Pipeline(art.index));
+                                }
+                            }
+                        }
+                    }
+                    FramePass::Gallery => {
+                        let (render_pass, framebuffer, extent) = match (stereo_target, grade) {
+                            (Some(target), _) => {
+                                (target.render_pass(), target.framebuffer(), target.extent())
+                            }
+                            (None, Some((target, _))) => {
+                                (target.render_pass(), target.framebuffer(), render_extent)
+                            }
+                            (None, None) => {
+                                (self.render_pass, self.swapchain_framebuffers[i], swapchain_extent)
+                            }
+                        };
+                        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                            .render_pass(render_pass)
+                            .framebuffer(framebuffer)
+                            .render_area(vk::Rect2D {
+                                offset: vk::Offset2D { x: 0, y: 0 },
+                                extent,
+                            })
+                            .clear_values(&CLEAR_VALUES);
+                        unsafe {
+                            device.cmd_begin_render_pass(
+                                buffer,
+                                &render_pass_begin_info,
+                                vk::SubpassContents::INLINE,
+                            );
+                            self.record_gallery(
+                                buffer,
+                                frame,
+                                extent,
+                                scene,
+                                inputs,
+                                &half_res_art,
+                                culled,
+                            );
+                            device.cmd_end_render_pass(buffer);
+                        }
+                    }
+                    FramePass::SideBySide => unsafe {
+                        let target = stereo_target.unwrap();
+                        target.copy_side_by_side(device, buffer, self.images[i], swapchain_extent);
+                    },
+                    // the eyes and the graded gallery are drawn into the swapchain image
+                    FramePass::Anaglyph | FramePass::Grade => {
+                        let pipeline = match grade {
+                            Some((_, pipeline)) => pipeline,
+                            None => self.screen.anaglyph.as_ref().unwrap(),
+                        };
+                        let rect = vk::Rect2D {
+                            offset: vk::Offset2D { x: 0, y: 0 },
+                            extent: swapchain_extent,
+                        };
+                        let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                            .render_pass(self.composite_render_pass)
+                            .framebuffer(self.composite_framebuffers[i])
+                            .render_area(rect);
+                        unsafe {
+                            device.cmd_begin_render_pass(
+                                buffer,
+                                &render_pass_begin_info,
+                                vk::SubpassContents::INLINE,
+                            );
+                            Self::set_viewport(device, buffer, rect);
+                            pipeline.bind_to_cmd_buffer(device, buffer, frame, 0);
+                            device.cmd_end_render_pass(buffer);
+                        }
+                    }
+                    FramePass::Readback => unsafe {
+                        if let Some(timer) = timer {
+                            timer.end_frame(device, buffer, frame);
+                        }
+                        readback.unwrap().record_copy(device, buffer, self.images[i]);
+                    },
+                }
+            }
+            if let (Some(timer), None) = (timer, readback) {
+                unsafe { timer.end_frame(device, buffer, frame) };
+            }
+
+            // end command buffer
+            unsafe { device.end_command_buffer(buffer).unwrap() };
+        }
+
+        buffers
+    }
+
+    /// The passes of a frame and what they read and write, depending on whether there is
+    /// ray traced art, the art is culled, art is drawn at half resolution, the floors reflect,
+    /// the lights are clustered, the fog is volumetric, how the eyes are shown in stereo and
+    /// whether the frame is graded and read back.
+    #[allow(clippy::too_many_arguments)]
+    fn frame_graph(
+        ray_tracing: bool,
+        occlusion: bool,
+        half_res: bool,
+        reflections: bool,
+        clusters: bool,
+        fog: bool,
+        stereo: Option<StereoOutput>,
+        anaglyph_ready: bool,
+        grade: bool,
+        readback: bool,
+    ) -> RenderGraph<FramePass> {
+        let mut graph = RenderGraph::new(&[Resource::Swapchain, Resource::Readback]);
+        graph.add_pass(FramePass::Upload, &[], &[(Resource::Streams, Usage::TransferDst)]);
+        let mut gallery_reads = vec![(Resource::Streams, Usage::Sampled)];
+        if ray_tracing {
+            // ray traced art has to be traced before it is sampled in the render pass
+            graph.add_pass(FramePass::Trace, &[], &[(Resource::TracedImages, Usage::Storage)]);
+            gallery_reads.push((Resource::TracedImages, Usage::Sampled));
+        }
+        let mut occlusion_writes = Vec::new();
+        if occlusion {
+            // the art is drawn with the draw commands the culling writes
+            occlusion_writes.push((Resource::DrawCommands, Usage::ComputeWrite));
+            gallery_reads.push((Resource::DrawCommands, Usage::IndirectRead));
+        }
+        if reflections {
+            // the reflections are marched through the depth drawn for the culling
+            occlusion_writes.push((Resource::Depth, Usage::DepthAttachment));
+        }
+        if !occlusion_writes.is_empty() {
+            graph.add_pass(FramePass::Occlusion, &[], &occlusion_writes);
+        }
+        if clusters {
+            // the environment looks up its lights in the clusters
+            graph.add_pass(
+                FramePass::Clusters,
+                &[],
+                &[(Resource::Clusters, Usage::ComputeWrite)],
+            );
+            gallery_reads.push((Resource::Clusters, Usage::StorageRead));
+        }
+        // the environment samples the shadows of the sun
+        graph.add_pass(
+            FramePass::Shadows,
+            &[],
+            &[(Resource::ShadowMap, Usage::DepthAttachment)],
+        );
+        gallery_reads.push((Resource::ShadowMap, Usage::Sampled));
+        if fog {
+            // the environment looks up the fog lit by the shadowed sun
+            graph.add_pass(
+                FramePass::Fog,
+                &[(Resource::ShadowMap, Usage::ComputeRead)],
+                &[(Resource::FogVolume, Usage::ComputeWrite)],
+            );
+            gallery_reads.push((Resource::FogVolume, Usage::Sampled));
+        }
+        if half_res {
+            // the art drawn at half resolution is sampled to scale it up in the gallery
+            let mut half_res_reads = vec![(Resource::Streams, Usage::Sampled)];
+            if occlusion {
+                half_res_reads.push((Resource::DrawCommands, Usage::IndirectRead));
+            }
+            graph.add_pass(
+                FramePass::HalfRes,
+                &half_res_reads,
+                &[(Resource::HalfResArt, Usage::ColorAttachment)],
+            );
+            gallery_reads.push((Resource::HalfResArt, Usage::Sampled));
+        }
+        let gallery = match (stereo, grade) {
+            (Some(_), _) => Resource::Eyes,
+            (None, true) => Resource::Scene,
+            (None, false) => Resource::Swapchain,
+        };
+        graph.add_pass(FramePass::Gallery, &gallery_reads, &[(gallery, Usage::ColorAttachment)]);
+        // show the eyes on the screen
+        match stereo {
+            Some(StereoOutput::SideBySide) => {
+                graph.add_pass(
+                    FramePass::SideBySide,
+                    &[(Resource::Eyes, Usage::TransferSrc)],
+                    &[(Resource::Swapchain, Usage::TransferDst)],
+                );
+            }
+            Some(StereoOutput::Anaglyph) if anaglyph_ready => {
+                graph.add_pass(
+                    FramePass::Anaglyph,
+                    &[(Resource::Eyes, Usage::Sampled)],
+                    &[(Resource::Swapchain, Usage::ColorAttachment)],
+                );
+            }
+            _ => {}
+        }
+        if grade {
+            let mut grade_reads = vec![(Resource::Scene, Usage::Sampled)];
+            if reflections {
+                grade_reads.push((Resource::Depth, Usage::Sampled));
+            }
+            graph.add_pass(
+                FramePass::Grade,
+                &grade_reads,
+                &[(Resource::Swapchain, Usage::ColorAttachment)],
+            );
+        }
+        if readback {
+            graph.add_pass(
+                FramePass::Readback,
+                &[(Resource::Swapchain, Usage::TransferSrc)],
+                &[(Resource::Readback, Usage::TransferDst)],
+            );
+        }
+        graph
+    }
+
+    /// Records the draws of the gallery, or the entered art, and everything that is drawn on top
+    /// of it into the render pass that `buffer` is in.
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn record_gallery(
+        &self,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        extent: vk::Extent2D,
+        scene: &Scene,
+        inputs: &FrameInputs,
+        half_res_art: &[&HalfResArt],
+        occlusion: Option<&OcclusionCulling>,
+    ) {
+        let &FrameInputs { renderers, views, ubo_stride, timer, crash_markers, .. } = inputs;
+        let device = self.vk_context.device();
+        let pipelines = &self.pipelines;
+        let screen = &self.screen;
+        let pipeline_entered = screen.entered.as_ref();
+        let pipeline_graph = screen.graph.as_ref().filter(|_| inputs.show_frame_graph);
+        let pipeline_gizmo = screen.gizmo.as_ref().filter(|_| scene.edited_art().is_some());
+        let pipeline_fade = screen.fade.as_ref().filter(|_| scene.fade().is_some());
+        let overlay = screen.overlay.as_ref().zip(inputs.overlay_size);
+        let art_ubo_offset = UBO_IDX_ART as u32 * ubo_stride;
+        let full_rect = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent,
+        };
+        // entered art replaces the gallery
+        if let Some(pipeline) = pipeline_entered {
+            if !pipeline.waiting_for_shaders {
+                unsafe {
+                    Self::set_viewport(device, buffer, full_rect);
+                    pipeline.bind_to_cmd_buffer(device, buffer, frame, art_ubo_offset);
+                    if let Some(markers) = crash_markers {
+                        markers.mark(buffer, Marker::Entered);
+                    }
+                }
+            }
+        }
+        let views = if pipeline_entered.is_some() { &[] } else { views };
+        let pipeline_preview = screen.preview.as_ref().filter(|_| pipeline_entered.is_none());
+
+        // every view draws the whole scene with its own uniform buffer
+        for (view, &rect) in views.iter().enumerate() {
+            unsafe { Self::set_viewport(device, buffer, rect) };
+            // the pipelines are only timed in the first view
+            let timer = timer.filter(|_| view == 0);
+            for (index, pipeline) in pipelines.iter().enumerate() {
+                if !pipeline.active || pipeline.waiting_for_shaders || pipeline.is_asleep() {
+                    continue;
+                }
+                // art drawn at half resolution was timed when it was drawn
+                let (pipeline, timer) = match half_res_art.iter().find(|art| art.index == index) {
+                    Some(art) => (&art.upscale, None),
+                    None => (pipeline, timer),
+                };
+                unsafe {
+                    if let Some(timer) = timer {
+                        timer.begin_pipeline(device, buffer, frame, index);
+                    }
+                    // bind pipeline, vertex and index buffer
+                    // bind descriptor set
+                    // draw
+                    let ubo_offset = view as u32 * ubo_stride;
+                    match occlusion.filter(|_| pipeline.bounds().is_some()) {
+                        Some(occlusion) => pipeline.bind_to_cmd_buffer_indirect(
+                            device,
+                            buffer,
+                            frame,
+                            ubo_offset,
+                            occlusion.draw_command(frame, index),
+                        ),
+                        None => pipeline.bind_to_cmd_buffer(device, buffer, frame, ubo_offset),
+                    }
+                    if let Some(timer) = timer {
+                        timer.end_pipeline(device, buffer, frame, index);
+                    }
+                    if let Some(markers) = crash_markers {
+                        markers.mark(buffer, Marker::Pipeline(index));
+                    }
+                }
+            }
+            for renderer in renderers {
+                unsafe { renderer.record(device, buffer, frame, view) };
+            }
+        }
+
+        // the handles of the edited art are drawn over the art in the first view
+        let pipeline_gizmo = pipeline_gizmo.filter(|pipeline| !pipeline.waiting_for_shaders);
+        if let Some((pipeline, &rect)) = pipeline_gizmo.zip(views.first()) {
+            unsafe {
+                Self::set_viewport(device, buffer, rect);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, 0);
+            }
+        }
+
+        // the preview and the frame graph are drawn on top of everything else
+        let clear_attachments = [
+            vk::ClearAttachment {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                color_attachment: 0,
+                clear_value: CLEAR_VALUES[0],
+            },
+            vk::ClearAttachment {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                color_attachment: 0,
+                clear_value: CLEAR_VALUES[1],
+            },
+        ];
+        if let Some(pipeline) = pipeline_preview.filter(|pipeline| !pipeline.waiting_for_shaders) {
+            let rect = Self::preview_rect(extent);
+            let clear_rects = [vk::ClearRect {
+                rect,
+                base_array_layer: 0,
+                layer_count: 1,
+            }];
+            unsafe {
+                device.cmd_clear_attachments(buffer, &clear_attachments, &clear_rects);
+                Self::set_viewport(device, buffer, rect);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, art_ubo_offset);
+                if let Some(markers) = crash_markers {
+                    markers.mark(buffer, Marker::Preview);
+                }
+            }
+        }
+        if let Some(pipeline) = pipeline_graph.filter(|pipeline| !pipeline.waiting_for_shaders) {
+            let rect = Self::graph_rect(extent);
+            let clear_rects = [vk::ClearRect {
+                rect,
+                base_array_layer: 0,
+                layer_count: 1,
+            }];
+            unsafe {
+                device.cmd_clear_attachments(buffer, &clear_attachments, &clear_rects);
+                Self::set_viewport(device, buffer, rect);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, 0);
+            }
+        }
+        if let Some(pipeline) = pipeline_fade.filter(|pipeline| !pipeline.waiting_for_shaders) {
+            unsafe {
+                Self::set_viewport(device, buffer, full_rect);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, 0);
+            }
+        }
+        // the text stays readable while fading
+        let overlay = overlay.filter(|(pipeline, _)| !pipeline.waiting_for_shaders);
+        if let Some((pipeline, size)) = overlay {
+            let rect = Self::overlay_rect(extent, size);
+            let clear_rects = [vk::ClearRect {
+                rect,
+                base_array_layer: 0,
+                layer_count: 1,
+            }];
+            unsafe {
+                device.cmd_clear_attachments(buffer, &clear_attachments, &clear_rects);
+                Self::set_viewport(device, buffer, rect);
+                pipeline.bind_to_cmd_buffer(device, buffer, frame, 0);
+            }
+        }
+    }
+
+    /// The square in the bottom right corner of a render target of `extent` with the preview.
+    pub fn preview_rect(extent: vk::Extent2D) -> vk::Rect2D {
+        let size = extent.width.min(extent.height) / PREVIEW_SCALE;
+        let margin = size / 16;
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: (extent.width - size - margin) as i32,
+                y: (extent.height - size - margin) as i32,
+            },
+            extent: vk::Extent2D { width: size, height: size },
+        }
+    }
+
+    /// The rectangle in the top left corner of a render target of `extent` with the frame graph.
+    fn graph_rect(extent: vk::Extent2D) -> vk::Rect2D {
+        let height = extent.width.min(extent.height) / PREVIEW_SCALE;
+        let margin = height / 16;
+        vk::Rect2D {
+            offset: vk::Offset2D { x: margin as i32, y: margin as i32 },
+            extent: vk::Extent2D { width: (2 * height).min(extent.width - 2 * margin), height },
+        }
+    }
+
+    /// The rectangle in the top right corner of a render target of `extent` with the text
+    /// overlay of `size` pixels, which are scaled up on large targets.
+    fn overlay_rect(extent: vk::Extent2D, [width, height]: [u32; 2]) -> vk::Rect2D {
+        let scale = (extent.height / OVERLAY_PIXEL_HEIGHT).max(1);
+        let margin = extent.width.min(extent.height) / PREVIEW_SCALE / 16;
+        let width = (width * scale).min(extent.width.saturating_sub(2 * margin)).max(1);
+        let height = (height * scale).min(extent.height.saturating_sub(2 * margin)).max(1);
+        vk::Rect2D {
+            offset: vk::Offset2D {
+                x: extent.width.saturating_sub(width + margin) as i32,
+                y: margin as i32,
+            },
+            extent: vk::Extent2D { width, height },
+        }
+    }
+
+    /// Restricts the following draws to `rect`.
+    unsafe fn set_viewport(device: &Device, buffer: vk::CommandBuffer, rect: vk::Rect2D) {
+        let viewport = vk::Viewport {
+            x: rect.offset.x as f32,
+            y: rect.offset.y as f32,
+            width: rect.extent.width as f32,
+            height: rect.extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        unsafe {
+            device.cmd_set_viewport(buffer, 0, &[viewport]);
+            device.cmd_set_scissor(buffer, 0, &[rect]);
+        }
+    }
+
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.release_full_screen_exclusive();
+        self.cleanup_swapchain();
+        let device = self.vk_context.device();
+        unsafe {
+            let retired = self.retired_swapchains.drain(..).map(|(swapchain_khr, _)| swapchain_khr);
+            for swapchain_khr in retired.chain([self.swapchain_khr]) {
+                self.swapchain.destroy_swapchain(swapchain_khr, None);
+            }
+        }
+        for frame in self.frames.iter_mut() {
+            frame.destroy(device, self.command_pool);
+        }
+        unsafe {
+            for pipeline in self.pipelines.iter_mut() {
+                pipeline.cleanup(device);
+            }
+            // the preview and the entered art share the shaders of their art
+            let screen = &mut self.screen;
+            let own_shaders = screen.anaglyph.iter_mut()
+                .chain(screen.grade.as_mut())
+                .chain(screen.graph.as_mut())
+                .chain(screen.gizmo.as_mut())
+                .chain(screen.fade.as_mut())
+                .chain(screen.overlay.as_mut());
+            for pipeline in own_shaders {
+                pipeline.cleanup(device);
+            }
+            device.destroy_command_pool(self.transient_command_pool, None);
+            device.destroy_command_pool(self.command_pool, None);
+        }
+    }
+}
+
+/// The passes recorded into the command buffer of a frame, see [`Renderer::frame_graph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FramePass {
+    /// Uploads the textures the CPU writes every frame.
+    Upload,
+    /// Traces the ray traced art.
+    Trace,
+    /// Draws the depth of the environment and culls the art hidden behind it.
+    Occlusion,
+    /// Assigns the point lights to the clusters of the view.
+    Clusters,
+    /// Draws the depth of the environment into the cascades of the shadow map.
+    Shadows,
+    /// Lights the fog in the froxels of the view.
+    Fog,
+    /// Draws the art that is scaled up in the gallery at half resolution.
+    HalfRes,
+    /// Draws the gallery, or the entered art, with everything on top of it.
+    Gallery,
+    SideBySide,
+    Anaglyph,
+    /// Grades the gallery into the swapchain image.
+    Grade,
+    /// Copies the swapchain image into the host buffer of a still.
+    Readback,
+}
+
+/// The resources of a frame that is drawn while the previous frames may still be in flight.
+pub struct FrameData {
+    pub image_available_semaphore: vk::Semaphore,
+    pub render_finished_semaphore: vk::Semaphore,
+    pub fence: vk::Fence,
+    /// Signaled once the image presented by this frame is done presenting,
+    /// only available with swapchain maintenance.
+    pub present_fence: Option<vk::Fence>,
+    /// One command buffer per swapchain image, all using the descriptor sets of this frame.
+    pub command_buffers: Vec<vk::CommandBuffer>,
+    /// The buffer the drawn image is copied into while capturing frames.
+    pub readback: Option<FrameReadback>,
+}
+
+impl FrameData {
+    pub fn new(device: &Device, swapchain_maintenance: bool) -> Self {
+        let semaphore_info = vk::SemaphoreCreateInfo::default();
+        let fence_info = vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED);
+        unsafe {
+            Self {
+                image_available_semaphore: device.create_semaphore(&semaphore_info, None).unwrap(),
+                render_finished_semaphore: device.create_semaphore(&semaphore_info, None).unwrap(),
+                fence: device.create_fence(&fence_info, None).unwrap(),
+                present_fence: swapchain_maintenance
+                    .then(|| device.create_fence(&fence_info, None).unwrap()),
+                command_buffers: Vec::new(),
+                readback: None,
+            }
+        }
+    }
+
+    pub fn free_command_buffers(&mut self, device: &Device, pool: vk::CommandPool) {
+        if !self.command_buffers.is_empty() {
+            unsafe { device.free_command_buffers(pool, &self.command_buffers) };
+            self.command_buffers.clear();
+        }
+    }
+
+    fn destroy(&mut self, device: &Device, pool: vk::CommandPool) {
+        self.free_command_buffers(device, pool);
+        unsafe {
+            device.destroy_semaphore(self.image_available_semaphore, None);
+            device.destroy_semaphore(self.render_finished_semaphore, None);
+            device.destroy_fence(self.fence, None);
+            if let Some(present_fence) = self.present_fence {
+                device.destroy_fence(present_fence, None);
+            }
+            if let Some(readback) = self.readback.as_mut() {
+                readback.destroy(device);
+            }
+        }
+    }
+}
@@ -0,0 +1,1064 @@
+use crate::animation::Animation;
+use crate::fs::{self, FileWatcher};
+use crate::video::VideoDecoder;
+use super::{
+    buffer, cmd,
+    context::VkContext,
+    geometry::Geometry,
+    sampler::{SamplerConfig, TextureSamplers},
+    shader::Shader,
+    stream::StreamTexture,
+    texture::Texture,
+    volume::Volume,
+};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use image::ImageReader;
+use std::{
+    mem::{align_of, size_of},
+    path::{Path, PathBuf},
+};
+
+/// The faces of a cubemap in the order of its layers.
+const CUBEMAP_FACES: [&str; 6] = ["left", "right", "top", "bottom", "back", "front"];
+/// Multiplies the colors of the day skybox if there is no night skybox.
+const NIGHT_TINT: [f32; 3] = [0.08, 0.1, 0.2];
+/// Largest staging buffer for textures, larger images are uploaded in bands of rows
+/// one after another so that huge photos do not need a second host copy of all pixels.
+const STAGING_SIZE: usize = 16 << 20;
+
+/// The textures the gallery is drawn with, each bound where its field says.
+#[derive(Clone, Copy)]
+pub struct Textures {
+    /// The image the environment is textured with at binding 1 of the environment.
+    pub image: Texture,
+    /// The day and the night skybox at the bindings 1 and 2 of the skybox.
+    pub cubemap: Texture,
+    pub cubemap_night: Texture,
+    /// The image the art samples at binding 1.
+    pub art_image: Texture,
+    /// Bound at binding 4 of the art without a volume.
+    pub volume_empty: Texture,
+    /// The 2D and the 3D noise at the bindings 5 and 6, see [`crate::noise`].
+    pub noise: Texture,
+    pub noise_3d: Texture,
+    /// Bound at binding 8 while no video is played.
+    pub video_empty: Texture,
+    /// The normal map and the baked light of the environment at the bindings 11 and 12.
+    pub normal_map: Texture,
+    pub lightmap: Texture,
+}
+
+/// The built-in shaders that pipelines are created with while the gallery runs.
+pub struct RuntimeShaders {
+    /// Draws the fragment shader of the previewed or the entered art over a viewport.
+    pub preview_vert: Shader,
+    /// Drawn instead of art whose shaders failed to compile.
+    pub error: [Shader; 2],
+    /// The fragment shader of the environment with a lightmap while there is none
+    /// and the one without while there is one.
+    pub main_frag_unused: Shader,
+}
+
+/// The textures, the models and the shaders the pipelines are created from and the helpers
+/// to create images.
+pub struct ResourceManager {
+    pub textures: Textures,
+    /// The cube the skybox and the 3D art are drawn in, shared by their pipelines.
+    pub skybox: Geometry,
+    /// The quad the 2D art is drawn on, shared by its pipelines.
+    pub quad: Geometry,
+    pub shaders: RuntimeShaders,
+    /// The volumes of the art at binding 4, see [`Volume`].
+    pub volumes: Vec<Texture>,
+    pub samplers: TextureSamplers,
+    /// The file of `textures.image`.
+    pub image_path: PathBuf,
+    /// Width over height of `textures.image`.
+    pub image_aspect: f32,
+    /// Width over height of `textures.art_image`.
+    pub art_image_aspect: f32,
+    /// The state of the keyboard of the scene uploaded every frame.
+    pub keyboard_texture: StreamTexture,
+    /// The video art samples at binding 8, see [`super::VkApp::set_video`].
+    pub video: Option<VideoStream>,
+    /// Replaces the image the environment is textured with if it is animated.
+    pub animated_image: Option<AnimatedImage>,
+    /// Loads the images and the models of the art again when their files change.
+    pub file_watcher: FileWatcher,
+}
+
+impl ResourceManager {
+    pub fn create_image_view(
+        device: &Device,
+        image: vk::Image,
+        mip_levels: u32,
+        format: vk::Format,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> vk::ImageView {
+        let create_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: mip_levels,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+
+        unsafe { device.create_image_view(&create_info, None).unwrap() }
+    }
+
+    /// Loads the six faces of a cubemap as RGBA pixels, returns their size and pixels.
+    pub fn load_cubemap_images<P: AsRef<Path>>(
+        pathes: &[P; 6],
+    ) -> Result<(vk::Extent2D, Vec<Vec<u8>>), anyhow::Error> {
+        let mut dims = None;
+        let mut images = Vec::new();
+        for path in pathes.iter() {
+            let image = ImageReader::open(path)
+                .with_context(|| format!("Failed to open image at {:?}", path.as_ref()))?
+                .decode()
+                .with_context(|| format!("Failed to decode image at {:?}", path.as_ref()))?;
+            let image_as_rgb = image.to_rgba8();
+            let width = image_as_rgb.width();
+            let height = image_as_rgb.height();
+            if let Some((w, h)) = dims {
+                if w != width || h != height {
+                    return Err(anyhow::anyhow!("cubemap images must have all the same size"))
+                }
+            } else {
+                dims = Some((width, height));
+            }
+            let pixels = image_as_rgb.into_raw();
+            images.push(pixels);
+        }
+        let (width, height) = dims.unwrap();
+        Ok((vk::Extent2D { width, height }, images))
+    }
+
+    /// Creates the day and night cubemaps from the faces in `dir` and its `night` subdirectory.
+    /// Without night faces the day faces are darkened instead.
+    pub fn create_cubemaps<P: AsRef<Path>>(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        dir: P,
+        sampler: SamplerConfig,
+    ) -> Result<(Texture, Texture), anyhow::Error> {
+        let dir = dir.as_ref();
+        let (day_dims, day_images) = Self::load_cubemap_images(
+            &CUBEMAP_FACES.map(|face| dir.join(format!("{face}.png"))),
+        )?;
+        let (night_dims, night_images) = Self::load_cubemap_images(
+            &CUBEMAP_FACES.map(|face| dir.join(format!("night/{face}.png"))),
+        ).unwrap_or_else(|err| {
+            log::warn!("Using the darkened day skybox at night: {err:#}");
+            let mut images = day_images.clone();
+            for pixel in images.iter_mut().flat_map(|image| image.chunks_exact_mut(4)) {
+                for (channel, tint) in pixel.iter_mut().zip(NIGHT_TINT) {
+                    *channel = (*channel as f32 * tint) as u8;
+                }
+            }
+            (day_dims, images)
+        });
+        let day = Self::create_cubemap(
+            vk_context,
+            command_pool,
+            copy_queue,
+            day_dims,
+            day_images,
+            sampler,
+        )?;
+        let night = Self::create_cubemap(
+            vk_context,
+            command_pool,
+            copy_queue,
+            night_dims,
+            night_images,
+            sampler,
+        )?;
+        Ok((day, night))
+    }
+
+    /// Creates a cubemap from the faces loaded with `load_cubemap_images`.
+    pub fn create_cubemap(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        extent: vk::Extent2D,
+        images: Vec<Vec<u8>>,
+        sampler: SamplerConfig,
+    ) -> Result<Texture, anyhow::Error> {
+        let max_mip_levels = ((extent.width.min(extent.height) as f32).log2().floor() + 1.0) as u32;
+        let image_size = (images[0].len() * size_of::<u8>()) as vk::DeviceSize;
+        let device = vk_context.device();
+
+        let (buffer, memory, mem_size) = buffer::create_buffer(
+            vk_context,
+            image_size * 6,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        unsafe {
+            for (i, image) in images.into_iter().enumerate() {
+                let offset = image_size * i as vk::DeviceSize;
+                let ptr = device
+                    .map_memory(memory, offset, image_size, vk::MemoryMapFlags::empty())
+                    .context("Failed to map memory for cubemap image")?;
+                let mut align = ash::util::Align::new(ptr, align_of::<u8>() as _, mem_size);
+                align.copy_from_slice(&image);
+                device.unmap_memory(memory);
+            }
+        }
+
+        let (image, image_memory) = {
+            let image_info = vk::ImageCreateInfo::default()
+                .image_type(vk::ImageType::TYPE_2D)
+                .extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                })
+                .mip_levels(max_mip_levels)
+                .array_layers(6)
+                .format(vk::Format::R8G8B8A8_UNORM)
+                .tiling(vk::ImageTiling::OPTIMAL)
+                .initial_layout(vk::ImageLayout::UNDEFINED)
+                .usage(vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST
+                    | vk::ImageUsageFlags::SAMPLED)
+                .sharing_mode(vk::SharingMode::EXCLUSIVE)
+                .samples(vk::SampleCountFlags::TYPE_1)
+                .flags(vk::ImageCreateFlags::CUBE_COMPATIBLE);
+            let device = vk_context.device();
+            let image = unsafe { device.create_image(&image_info, None).unwrap() };
+            let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+            let mem_type_index = vk_context.find_memory_type(
+                mem_requirements,
+                vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            );
+            let alloc_info = vk::MemoryAllocateInfo::default()
+                .allocation_size(mem_requirements.size)
+                .memory_type_index(mem_type_index);
+            let memory = unsafe {
+                let mem = device.allocate_memory(&alloc_info, None).unwrap();
+                device.bind_image_memory(image, mem, 0).unwrap();
+                mem
+            };
+            (image, memory)
+        };
+
+        // Transition the image layout and copy the buffer into the image
+        // and transition the layout again to be readable from fragment shader.
+        {
+            Self::transition_image_layout(
+                vk_context,
+                command_pool,
+                copy_queue,
+                image,
+                max_mip_levels,
+                vk::Format::R8G8B8A8_UNORM,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                6,
+            );
+
+            Self::copy_buffer_to_image(device, command_pool, copy_queue, buffer, image, extent, 6);
+
+            Self::generate_mipmaps(
+                vk_context,
+                command_pool,
+                copy_queue,
+                image,
+                extent,
+                vk::Format::R8G8B8A8_UNORM,
+                max_mip_levels,
+                6,
+            );
+        }
+
+        unsafe {
+            device.destroy_buffer(buffer, None);
+            device.free_memory(memory, None);
+        }
+
+        let create_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::CUBE)
+            .format(vk::Format::R8G8B8A8_UNORM)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: max_mip_levels,
+                base_array_layer: 0,
+                layer_count: 6,
+            });
+        let image_view = unsafe {
+            device.create_image_view(&create_info, None).unwrap()
+        };
+
+        let sampler = vk_context.sampler(sampler)?;
+
+        Ok(Texture::new(image, image_memory, image_view, Some(sampler)))
+    }
+
+    /// Loads the image at `path` into a texture with y pointing up, returns it with its size.
+    pub fn create_texture_image<P: AsRef<Path>>(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        path: P,
+        sampler: SamplerConfig,
+    ) -> Result<(Texture, vk::Extent2D), anyhow::Error> {
+        let image = fs::load_image(&path)?.flipv();
+        // huge photos are shown smaller instead of failing to load
+        let max_dimension = vk_context.physical_device_properties().limits.max_image_dimension2_d;
+        let (width, height) = (image.width(), image.height());
+        let image = fs::fit_image(image, max_dimension);
+        if (image.width(), image.height()) != (width, height) {
+            log::warn!(
+                "Scaled {} down from {width}x{height} to {}x{} to fit the limit of the GPU",
+                path.as_ref().display(),
+                image.width(),
+                image.height(),
+            );
+        }
+        let image_as_rgb = image.to_rgba8();
+        let extent = vk::Extent2D {
+            width: image_as_rgb.width(),
+            height: image_as_rgb.height(),
+        };
+        let texture = Self::create_texture_from_rgba(
+            vk_context,
+            command_pool,
+            copy_queue,
+            extent,
+            &image_as_rgb.into_raw(),
+            sampler,
+        )?;
+        Ok((texture, extent))
+    }
+
+    /// Creates a 2D texture with mipmaps from RGBA `pixels` row by row.
+    pub fn create_texture_from_rgba(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        extent: vk::Extent2D,
+        pixels: &[u8],
+        sampler: SamplerConfig,
+    ) -> Result<Texture, anyhow::Error> {
+        let max_mip_levels = ((extent.width.min(extent.height) as f32).log2().floor() + 1.0) as u32;
+        let device = vk_context.device();
+        let row_size = extent.width as usize * 4;
+        assert_eq!(pixels.len(), row_size * extent.height as usize, "Pixels do not match extent");
+        let band_rows = (STAGING_SIZE / row_size).clamp(1, extent.height as usize);
+        let staging_size = (band_rows * row_size) as vk::DeviceSize;
+
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            staging_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(memory, 0, staging_size, vk::MemoryMapFlags::empty())
+                .context("Failed to map memory for texture image")? as *mut u8
+        };
+
+        let (image, image_memory) = Self::create_image(
+            vk_context,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            extent,
+            max_mip_levels,
+            vk::SampleCountFlags::TYPE_1,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageTiling::OPTIMAL,
+            vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::TRANSFER_DST
+                | vk::ImageUsageFlags::SAMPLED,
+        );
+
+        // Transition the image layout and copy the buffer into the image
+        // and transition the layout again to be readable from fragment shader.
+        {
+            Self::transition_image_layout(
+                vk_context,
+                command_pool,
+                copy_queue,
+                image,
+                max_mip_levels,
+                vk::Format::R8G8B8A8_UNORM,
+                vk::ImageLayout::UNDEFINED,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                1,
+            );
+
+            // every band waits for its copy so the staging buffer can be reused
+            for (band, rows) in pixels.chunks(band_rows * row_size).enumerate() {
+                unsafe { std::ptr::copy_nonoverlapping(rows.as_ptr(), ptr, rows.len()) };
+                let offset = (band * band_rows) as i32;
+                let band_extent = vk::Extent2D {
+                    width: extent.width,
+                    height: (rows.len() / row_size) as u32,
+                };
+                Self::copy_buffer_to_image_rows(
+                    device,
+                    command_pool,
+                    copy_queue,
+                    buffer,
+                    image,
+                    offset,
+                    band_extent,
+                );
+            }
+
+            Self::generate_mipmaps(
+                vk_context,
+                command_pool,
+                copy_queue,
+                image,
+                extent,
+                vk::Format::R8G8B8A8_UNORM,
+                max_mip_levels,
+                1,
+            );
+        }
+
+        unsafe {
+            device.unmap_memory(memory);
+            device.destroy_buffer(buffer, None);
+            device.free_memory(memory, None);
+        }
+
+        let image_view = Self::create_image_view(
+            device,
+            image,
+            max_mip_levels,
+            vk::Format::R8G8B8A8_UNORM,
+            vk::ImageAspectFlags::COLOR,
+        );
+
+        let sampler = vk_context.sampler(sampler)?;
+
+        Ok(Texture::new(image, image_memory, image_view, Some(sampler)))
+    }
+
+    /// Creates a 3D texture without mipmaps from the texels of `volume`.
+    pub fn create_volume_texture(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        volume: &Volume,
+        sampler: SamplerConfig,
+    ) -> Result<Texture, anyhow::Error> {
+        let (extent, texels) = volume.texels()?;
+        let texture = Self::create_texture_3d(
+            vk_context,
+            command_pool,
+            copy_queue,
+            extent,
+            &texels,
+            sampler,
+        )?;
+        log::debug!("Created volume {volume:?}");
+        Ok(texture)
+    }
+
+    /// Creates a 3D texture without mipmaps from RGBA `texels` with x changing fastest, then y, then z.
+    pub fn create_texture_3d(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        copy_queue: vk::Queue,
+        [width, height, depth]: [u32; 3],
+        texels: &[u8],
+        sampler: SamplerConfig,
+    ) -> Result<Texture, anyhow::Error> {
+        let max_dimension = vk_context.physical_device_properties().limits.max_image_dimension3_d;
+        if width.max(height).max(depth) > max_dimension {
+            anyhow::bail!("3D texture {width}x{height}x{depth} is larger than {max_dimension} in a dimension");
+        }
+        let extent = vk::Extent3D { width, height, depth };
+        let format = vk::Format::R8G8B8A8_UNORM;
+        let image_size = texels.len() as vk::DeviceSize;
+        let device = vk_context.device();
+
+        let (buffer, memory, mem_size) = buffer::create_buffer(
+            vk_context,
+            image_size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        unsafe {
+            let ptr = device.map_memory(memory, 0, image_size, vk::MemoryMapFlags::empty())
+                .context("Failed to map memory for 3D texture")?;
+            let mut align = ash::util::Align::new(ptr, align_of::<u8>() as _, mem_size);
+            align.copy_from_slice(texels);
+            device.unmap_memory(memory);
+        }
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_3D)
+            .extent(extent)
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1);
+        let image = unsafe {
+            device.create_image(&image_info, None).context("Failed to create 3D texture image")?
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let image_memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None)
+                .context("Failed to allocate memory for 3D texture image")?;
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+
+        Self::transition_image_layout(
+            vk_context,
+            command_pool,
+            copy_queue,
+            image,
+            1,
+            format,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            1,
+        );
+        cmd::execute_one_time_commands(device, command_pool, copy_queue, |command_buffer| {
+            let region = vk::BufferImageCopy::default()
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image_extent(extent);
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                )
+            }
+        });
+        Self::transition_image_layout(
+            vk_context,
+            command_pool,
+            copy_queue,
+            image,
+            1,
+            format,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            1,
+        );
+
+        unsafe {
+            device.destroy_buffer(buffer, None);
+            device.free_memory(memory, None);
+        }
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_3D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let image_view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+        let sampler = vk_context.sampler(sampler)?;
+
+        Ok(Texture::new(image, image_memory, image_view, Some(sampler)))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_image(
+        vk_context: &VkContext,
+        mem_properties: vk::MemoryPropertyFlags,
+        extent: vk::Extent2D,
+        mip_levels: u32,
+        sample_count: vk::SampleCountFlags,
+        format: vk::Format,
+        tiling: vk::ImageTiling,
+        usage: vk::ImageUsageFlags,
+    ) -> (vk::Image, vk::DeviceMemory) {
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(mip_levels)
+            .array_layers(1)
+            .format(format)
+            .tiling(tiling)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(sample_count)
+            .flags(vk::ImageCreateFlags::empty());
+
+        let device = vk_context.device();
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(mem_requirements, mem_properties);
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+
+        (image, memory)
+    }
+
+    /// Transitions all mip levels of `image` from `old_layout` to `new_layout` and waits for it.
+    ///
+    /// # Panics
+    ///
+    /// If one of the layouts is not handled by [`Self::layout_usage`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn transition_image_layout(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        transition_queue: vk::Queue,
+        image: vk::Image,
+        mip_levels: u32,
+        format: vk::Format,
+        old_layout: vk::ImageLayout,
+        new_layout: vk::ImageLayout,
+        layer_count: u32,
+    ) {
+        let device = vk_context.device();
+        cmd::execute_one_time_commands(device, command_pool, transition_queue, |buffer| {
+            let (src_stage, src_access) = Self::layout_usage(old_layout);
+            let (dst_stage, dst_access) = Self::layout_usage(new_layout);
+
+            let aspect_mask = if new_layout == vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL {
+                let mut mask = vk::ImageAspectFlags::DEPTH;
+                if Self::has_stencil_component(format) {
+                    mask |= vk::ImageAspectFlags::STENCIL;
+                }
+                mask
+            } else {
+                vk::ImageAspectFlags::COLOR
+            };
+
+            let barrier = vk::ImageMemoryBarrier2::default()
+                .src_stage_mask(src_stage)
+                .src_access_mask(src_access)
+                .dst_stage_mask(dst_stage)
+                .dst_access_mask(dst_access)
+                .old_layout(old_layout)
+                .new_layout(new_layout)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(image)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask,
+                    base_mip_level: 0,
+                    level_count: mip_levels,
+                    base_array_layer: 0,
+                    layer_count,
+                });
+            unsafe { vk_context.cmd_pipeline_barrier2(buffer, &[], &[barrier]) };
+        });
+    }
+
+    /// The stages and the accesses that use an image in `layout` after a transition,
+    /// which have to be done before it is transitioned again.
+    fn layout_usage(layout: vk::ImageLayout) -> (vk::PipelineStageFlags2, vk::AccessFlags2) {
+        match layout {
+            vk::ImageLayout::UNDEFINED => (vk::PipelineStageFlags2::NONE, vk::AccessFlags2::NONE),
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+                vk::PipelineStageFlags2::TRANSFER,
+                vk::AccessFlags2::TRANSFER_WRITE,
+            ),
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL => (
+                vk::PipelineStageFlags2::FRAGMENT_SHADER,
+                vk::AccessFlags2::SHADER_READ,
+            ),
+            vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL => (
+                vk::PipelineStageFlags2::EARLY_FRAGMENT_TESTS,
+                vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_READ
+                    | vk::AccessFlags2::DEPTH_STENCIL_ATTACHMENT_WRITE,
+            ),
+            vk::ImageLayout::GENERAL => (
+                vk::PipelineStageFlags2::ALL_COMMANDS,
+                vk::AccessFlags2::SHADER_READ | vk::AccessFlags2::SHADER_WRITE,
+            ),
+            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+                vk::PipelineStageFlags2::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags2::COLOR_ATTACHMENT_READ | vk::AccessFlags2::COLOR_ATTACHMENT_WRITE,
+            ),
+            _ => panic!("Unsupported layout transition from or to {layout:?}"),
+        }
+    }
+
+    pub fn copy_buffer_to_image(
+        device: &Device,
+        command_pool: vk::CommandPool,
+        transition_queue: vk::Queue,
+        buffer: vk::Buffer,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        layer_count: u32,
+    ) {
+        cmd::execute_one_time_commands(device, command_pool, transition_queue, |command_buffer| {
+            let region = vk::BufferImageCopy::default()
+                .buffer_offset(0)
+                .buffer_row_length(0)
+                .buffer_image_height(0)
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count,
+                })
+                .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+                .image_extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                });
+            let regions = [region];
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &regions,
+                )
+            }
+        })
+    }
+
+    /// Copies the rows of `buffer` into the rows of the first layer of `image`
+    /// starting at row `y`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn copy_buffer_to_image_rows(
+        device: &Device,
+        command_pool: vk::CommandPool,
+        transition_queue: vk::Queue,
+        buffer: vk::Buffer,
+        image: vk::Image,
+        y: i32,
+        extent: vk::Extent2D,
+    ) {
+        cmd::execute_one_time_commands(device, command_pool, transition_queue, |command_buffer| {
+            let region = vk::BufferImageCopy::default()
+                .image_subresource(vk::ImageSubresourceLayers {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    mip_level: 0,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image_offset(vk::Offset3D { x: 0, y, z: 0 })
+                .image_extent(vk::Extent3D {
+                    width: extent.width,
+                    height: extent.height,
+                    depth: 1,
+                });
+            unsafe {
+                device.cmd_copy_buffer_to_image(
+                    command_buffer,
+                    buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[region],
+                )
+            }
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn generate_mipmaps(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        transfer_queue: vk::Queue,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        mip_levels: u32,
+        layer_count: u32,
+    ) {
+        let format_properties = unsafe {
+            vk_context.instance()
+                .get_physical_device_format_properties(vk_context.physical_device(), format)
+        };
+        if !format_properties.optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
+        {
+            panic!("Linear blitting is not supported for format {:?}.", format)
+        }
+
+        cmd::execute_one_time_commands(
+            vk_context.device(),
+            command_pool,
+            transfer_queue,
+            |buffer| {
+                let mut barrier = vk::ImageMemoryBarrier::default()
+                    .image(image)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        base_array_layer: 0,
+                        layer_count,
+                        level_count: 1,
+                        ..Default::default()
+                    });
+
+                let mut mip_width = extent.width as i32;
+                let mut mip_height = extent.height as i32;
+                for level in 1..mip_levels {
+                    let next_mip_width = if mip_width > 1 {
+                        mip_width / 2
+                    } else {
+                        mip_width
+                    };
+                    let next_mip_height = if mip_height > 1 {
+                        mip_height / 2
+                    } else {
+                        mip_height
+                    };
+
+                    barrier.subresource_range.base_mip_level = level - 1;
+                    barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+                    barrier.new_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+                    barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+                    barrier.dst_access_mask = vk::AccessFlags::TRANSFER_READ;
+                    let barriers = [barrier];
+
+                    unsafe {
+                        vk_context.device().cmd_pipeline_barrier(
+                            buffer,
+                            vk::PipelineStageFlags::TRANSFER,
+                            vk::PipelineStageFlags::TRANSFER,
+                            vk::DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &barriers,
+                        )
+                    };
+
+                    let blit = vk::ImageBlit::default()
+                        .src_offsets([
+                            vk::Offset3D { x: 0, y: 0, z: 0 },
+                            vk::Offset3D {
+                                x: mip_width,
+                                y: mip_height,
+                                z: 1,
+                            },
+                        ])
+                        .src_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: level - 1,
+                            base_array_layer: 0,
+                            layer_count,
+                        })
+                        .dst_offsets([
+                            vk::Offset3D { x: 0, y: 0, z: 0 },
+                            vk::Offset3D {
+                                x: next_mip_width,
+                                y: next_mip_height,
+                                z: 1,
+                            },
+                        ])
+                        .dst_subresource(vk::ImageSubresourceLayers {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            mip_level: level,
+                            base_array_layer: 0,
+                            layer_count,
+                        });
+                    let blits = [blit];
+
+                    unsafe {
+                        vk_context.device().cmd_blit_image(
+                            buffer,
+                            image,
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            image,
+                            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                            &blits,
+                            vk::Filter::LINEAR,
+                        )
+                    };
+
+                    barrier.old_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+                    barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+                    barrier.src_access_mask = vk::AccessFlags::TRANSFER_READ;
+                    barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+                    let barriers = [barrier];
+
+                    unsafe {
+                        vk_context.device().cmd_pipeline_barrier(
+                            buffer,
+                            vk::PipelineStageFlags::TRANSFER,
+                            vk::PipelineStageFlags::FRAGMENT_SHADER,
+                            vk::DependencyFlags::empty(),
+                            &[],
+                            &[],
+                            &barriers,
+                        )
+                    };
+
+                    mip_width = next_mip_width;
+                    mip_height = next_mip_height;
+                }
+
+                barrier.subresource_range.base_mip_level = mip_levels - 1;
+                barrier.old_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+                barrier.new_layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+                barrier.src_access_mask = vk::AccessFlags::TRANSFER_WRITE;
+                barrier.dst_access_mask = vk::AccessFlags::SHADER_READ;
+                let barriers = [barrier];
+
+                unsafe {
+                    vk_context.device().cmd_pipeline_barrier(
+                        buffer,
+                        vk::PipelineStageFlags::TRANSFER,
+                        vk::PipelineStageFlags::FRAGMENT_SHADER,
+                        vk::DependencyFlags::empty(),
+                        &[],
+                        &[],
+                        &barriers,
+                    )
+                };
+            },
+        );
+    }
+
+    /// Loads the frames of the image at `path` if it is animated.
+    ///
+    /// Only the first frame is shown if they fail to load.
+    pub fn load_animation(path: &Path) -> Option<Animation> {
+        Animation::load(path).unwrap_or_else(|err| {
+            log::warn!("Showing only the first frame of {}: {err:#}", path.display());
+            None
+        })
+    }
+
+    fn has_stencil_component(format: vk::Format) -> bool {
+        format == vk::Format::D32_SFLOAT_S8_UINT || format == vk::Format::D24_UNORM_S8_UINT
+    }
+
+    /// The textures the CPU writes every frame.
+    pub fn streams(&self) -> impl Iterator<Item = &StreamTexture> {
+        let video = self.video.as_ref().map(|video| &video.texture);
+        let animated = self.animated_image.as_ref().map(|animated| &animated.texture);
+        std::iter::once(&self.keyboard_texture).chain(video).chain(animated)
+    }
+
+    /// # Safety
+    ///
+    /// The textures must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        let textures = &mut self.textures;
+        let named = [
+            &mut textures.image,
+            &mut textures.cubemap,
+            &mut textures.cubemap_night,
+            &mut textures.art_image,
+            &mut textures.volume_empty,
+            &mut textures.noise,
+            &mut textures.noise_3d,
+            &mut textures.video_empty,
+            &mut textures.normal_map,
+            &mut textures.lightmap,
+        ];
+        for texture in named.into_iter().chain(self.volumes.iter_mut()) {
+            texture.destroy(device);
+        }
+        let shaders = &self.shaders;
+        let shaders = [&shaders.preview_vert, &shaders.main_frag_unused].into_iter()
+            .chain(&shaders.error);
+        for shader in shaders {
+            shader.cleanup(device);
+        }
+        unsafe {
+            self.skybox.release(device);
+            self.quad.release(device);
+            self.keyboard_texture.destroy(device);
+            if let Some(video) = self.video.as_mut() {
+                video.texture.destroy(device);
+            }
+            if let Some(animated) = self.animated_image.as_mut() {
+                animated.texture.destroy(device);
+            }
+        }
+    }
+}
+
+/// A video that is decoded while it is shown, see [`super::VkApp::set_video`].
+pub struct VideoStream {
+    pub decoder: VideoDecoder,
+    pub texture: StreamTexture,
+    /// The number of the video frame in the staging buffer of each frame in flight.
+    pub uploaded: Vec<Option<u64>>,
+}
+
+impl VideoStream {
+    /// Writes the video frame shown at `time` for the frame in flight `frame`
+    /// unless it is already there.
+    pub fn update(&mut self, frame: usize, time: f32) {
+        if let Some((number, texels)) = self.decoder.frame(time) {
+            if self.uploaded[frame] != Some(number) {
+                self.texture.write(frame, texels);
+                self.uploaded[frame] = Some(number);
+            }
+        }
+    }
+}
+
+/// The frames of an animated image that are shown one after another,
+/// see [`super::VkApp::animate_image`].
+pub struct AnimatedImage {
+    pub animation: Animation,
+    pub texture: StreamTexture,
+    /// The time the first frame was shown.
+    pub start: Option<f32>,
+    /// The index of the animation frame in the staging buffer of each frame in flight.
+    pub uploaded: Vec<Option<usize>>,
+}
+
+impl AnimatedImage {
+    /// Writes the animation frame shown at `time` for the frame in flight `frame`
+    /// unless it is already there.
+    pub fn update(&mut self, frame: usize, time: f32) {
+        let start = *self.start.get_or_insert(time);
+        let index = self.animation.frame_at(time - start);
+        if self.uploaded[frame] != Some(index) {
+            self.texture.write(frame, self.animation.frame(index));
+            self.uploaded[frame] = Some(index);
+        }
+    }
+}
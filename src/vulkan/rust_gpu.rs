@@ -0,0 +1,151 @@
+use notify_debouncer_full::{new_debouncer, notify};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc,
+    thread,
+    time::Duration,
+};
+
+const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
+
+/// A crate of shaders written in Rust with rust-gpu, built into the SPIR-V module art loads.
+///
+/// Art using the module has to name its entry points, e.g. with
+/// [`crate::ArtPiece::frag_entry_point`], since rust-gpu puts all shaders of a crate into one
+/// module. The art reloads when the build writes the module again.
+#[derive(Debug, Clone)]
+pub struct RustGpuCrate {
+    /// The directory with the `Cargo.toml` of the crate.
+    pub dir: PathBuf,
+    /// The program and its arguments run in `dir` to build the crate.
+    pub command: Vec<String>,
+    /// The SPIR-V module written by `command`.
+    pub output: PathBuf,
+}
+
+impl RustGpuCrate {
+    /// The crate in `dir` built with cargo-gpu, which writes `output` into its parent directory.
+    pub fn new<D: Into<PathBuf>, P: Into<PathBuf>>(dir: D, output: P) -> Self {
+        let output = output.into();
+        let output_dir = output.parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let output_dir = std::path::absolute(output_dir).unwrap_or_else(|_| output_dir.into());
+        let command = ["cargo", "gpu", "build", "--output-dir"].into_iter()
+            .map(str::to_owned)
+            .chain([output_dir.to_string_lossy().into_owned()])
+            .collect();
+        Self {
+            dir: dir.into(),
+            command,
+            output,
+        }
+    }
+
+    /// Replaces the build command, e.g. with a `cargo run` of a binary using `spirv-builder`.
+    pub fn command<I: IntoIterator<Item = S>, S: Into<String>>(mut self, command: I) -> Self {
+        self.command = command.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Runs the build command and returns its error output if it fails.
+    pub fn build(&self) -> Result<(), anyhow::Error> {
+        let [program, args @ ..] = self.command.as_slice() else {
+            anyhow::bail!("no build command for rust-gpu crate {}", self.dir.display());
+        };
+        log::info!("building rust-gpu crate {}", self.dir.display());
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(&self.dir)
+            .output()
+            .map_err(|err| anyhow::anyhow!("failed to run {program}: {err}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!(
+                "building rust-gpu crate {} failed with {}:\n{}",
+                self.dir.display(),
+                output.status,
+                stderr.trim_end(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Builds the crate if its module is missing and spawns a thread building it again
+    /// whenever one of its sources changes.
+    pub fn watch(self) -> Result<(), anyhow::Error> {
+        if !self.output.exists() {
+            self.build()?;
+        }
+        let dir = std::fs::canonicalize(&self.dir)?;
+        thread::spawn(move || {
+            let (tx, rx) = mpsc::channel();
+            let mut debouncer = match new_debouncer(DEBOUNCE_TIME, None, tx) {
+                Ok(debouncer) => debouncer,
+                Err(err) => {
+                    log::error!("failed to create file watcher: {err}");
+                    return;
+                }
+            };
+            if let Err(err) = debouncer.watch(&dir, notify::RecursiveMode::Recursive) {
+                log::error!("failed to watch {}: {err}", dir.display());
+                return;
+            }
+            for res in rx {
+                match res {
+                    Ok(events) => {
+                        let changed = events.iter()
+                            .filter(|event| !event.kind.is_access())
+                            .flat_map(|event| &event.paths)
+                            .any(|path| is_source(&dir, path));
+                        if !changed {
+                            continue;
+                        }
+                        if let Err(err) = self.build() {
+                            log::error!("{err:#}");
+                        }
+                    }
+                    Err(e) => log::info!("watch error: {:?}", e),
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Whether a change of `path` can change the module built from the crate in `dir`.
+///
+/// Everything below `target` is ignored, the build itself writes there.
+fn is_source(dir: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(dir) else {
+        return false;
+    };
+    !relative.starts_with("target")
+        && path.extension().is_some_and(|ext| ext == "rs" || ext == "toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_command() {
+        let rust_gpu = RustGpuCrate::new("shaders", "/art/shaders.spv");
+        assert_eq!(rust_gpu.command, ["cargo", "gpu", "build", "--output-dir", "/art"]);
+
+        let rust_gpu = rust_gpu.command(["cargo", "run", "--release"]);
+        assert_eq!(rust_gpu.command, ["cargo", "run", "--release"]);
+    }
+
+    #[test]
+    fn source_changes() {
+        let dir = Path::new("/shaders");
+        assert!(is_source(dir, Path::new("/shaders/src/lib.rs")));
+        assert!(is_source(dir, Path::new("/shaders/Cargo.toml")));
+        assert!(!is_source(dir, Path::new("/shaders/target/spirv/shaders.spv")));
+        assert!(!is_source(dir, Path::new("/shaders/target/debug/build.rs")));
+        assert!(!is_source(dir, Path::new("/shaders/README.md")));
+        assert!(!is_source(dir, Path::new("/other/src/lib.rs")));
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::Context;
+use ash::{vk, Device};
+use std::collections::HashMap;
+
+/// How a texture is sampled, textures with the same config share a sampler.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerConfig {
+    pub address_mode: vk::SamplerAddressMode,
+    /// Used for magnification, minification and between mip levels.
+    /// Linear filtering also enables anisotropic filtering.
+    pub filter: vk::Filter,
+    /// Added to the mip level, negative values make textures sharper.
+    pub mip_lod_bias: f32,
+}
+
+impl SamplerConfig {
+    pub const REPEAT: Self = Self {
+        address_mode: vk::SamplerAddressMode::REPEAT,
+        filter: vk::Filter::LINEAR,
+        mip_lod_bias: 0.,
+    };
+    pub const CLAMP: Self = Self {
+        address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        filter: vk::Filter::LINEAR,
+        mip_lod_bias: 0.,
+    };
+    pub const NEAREST: Self = Self {
+        address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE,
+        filter: vk::Filter::NEAREST,
+        mip_lod_bias: 0.,
+    };
+
+    fn key(&self) -> (vk::SamplerAddressMode, vk::Filter, u32) {
+        (self.address_mode, self.filter, self.mip_lod_bias.to_bits())
+    }
+}
+
+/// The samplers of the textures loaded from files.
+#[derive(Clone, Copy, Debug)]
+pub struct TextureSamplers {
+    /// The image the environment is textured with.
+    pub image: SamplerConfig,
+    /// The image art can sample.
+    pub art: SamplerConfig,
+    pub cubemap: SamplerConfig,
+}
+
+/// Creates every sampler once and destroys them all at the end.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<(vk::SamplerAddressMode, vk::Filter, u32), vk::Sampler>,
+}
+
+impl SamplerCache {
+    pub fn get(
+        &mut self,
+        device: &Device,
+        max_anisotropy: f32,
+        config: SamplerConfig,
+    ) -> Result<vk::Sampler, anyhow::Error> {
+        if let Some(sampler) = self.samplers.get(&config.key()) {
+            return Ok(*sampler);
+        }
+        let linear = config.filter == vk::Filter::LINEAR;
+        let mipmap_mode = if linear {
+            vk::SamplerMipmapMode::LINEAR
+        } else {
+            vk::SamplerMipmapMode::NEAREST
+        };
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(config.filter)
+            .min_filter(config.filter)
+            .address_mode_u(config.address_mode)
+            .address_mode_v(config.address_mode)
+            .address_mode_w(config.address_mode)
+            // a maximum of 1 means anisotropic filtering is not enabled on the device
+            .anisotropy_enable(linear && max_anisotropy > 1.)
+            .max_anisotropy(max_anisotropy.min(16.))
+            .border_color(vk::BorderColor::INT_OPAQUE_BLACK)
+            .unnormalized_coordinates(false)
+            .compare_enable(false)
+            .compare_op(vk::CompareOp::ALWAYS)
+            .mipmap_mode(mipmap_mode)
+            .mip_lod_bias(config.mip_lod_bias)
+            .min_lod(0.0)
+            .max_lod(vk::LOD_CLAMP_NONE);
+        let sampler = unsafe {
+            device.create_sampler(&sampler_info, None)
+                .with_context(|| format!("Failed to create sampler for {config:?}"))?
+        };
+        log::debug!("Created sampler for {config:?}");
+        self.samplers.insert(config.key(), sampler);
+        Ok(sampler)
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        for (_, sampler) in self.samplers.drain() {
+            unsafe { device.destroy_sampler(sampler, None) };
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::gizmo::{Gizmo, GizmoMode};
+use crate::keyboard::KeyboardState;
+use crate::math::{Matrix4, Projection, Vector3, Vector4};
+use super::structs::{Fog, Sky};
+
+/// What the gallery looks like and where it is seen from, independent of how it is rendered.
+///
+/// The indices of art refer to the pipelines of [`super::VkApp`].
+pub struct Scene {
+    pub view_matrix: Matrix4,
+    /// Tracked view matrices of the left and the right eye, e.g. from a VR runtime.
+    ///
+    /// They replace the eyes derived from `view_matrix` when rendering in stereo.
+    pub tracked_eye_views: Option<[Matrix4; 2]>,
+    /// View matrices of additional cameras, each rendered into its own part of the screen.
+    ///
+    /// The first view always uses `view_matrix`. Splitting is disabled when rendering in stereo.
+    pub split_view_matrices: Vec<Matrix4>,
+    pub projection: Projection,
+    pub fog: Fog,
+    pub sky: Sky,
+    pub model_matrix: Matrix4,
+    pub texture_weight: f32,
+    /// Mouse passed to the entered art, see [`super::structs::UniformBufferObject::mouse`].
+    pub art_mouse: Vector4,
+    /// The state of the keyboard uploaded to a texture every frame.
+    pub keyboard: KeyboardState,
+    /// Indices of the 2D art.
+    art_2d: Vec<usize>,
+    preview_art: Option<usize>,
+    entered_art: Option<usize>,
+    /// The time the entered art was first drawn at, its time starts at zero.
+    entered_time: Option<f32>,
+    edited_art: Option<usize>,
+    /// The position of the edited art before it is snapped to the grid.
+    edit_position: Vector3,
+    gizmo: Gizmo,
+    fade: Option<f32>,
+}
+
+impl Scene {
+    /// A scene with the 2D art at the indices `art_2d` seen from the origin.
+    pub fn new(art_2d: Vec<usize>) -> Self {
+        Self {
+            view_matrix: Matrix4::unit(),
+            tracked_eye_views: None,
+            split_view_matrices: Vec::new(),
+            projection: Projection::default(),
+            fog: Fog::default(),
+            sky: Sky::default(),
+            model_matrix: Matrix4::unit(),
+            texture_weight: 0.,
+            art_mouse: Vector4::default(),
+            keyboard: KeyboardState::default(),
+            art_2d,
+            preview_art: None,
+            entered_art: None,
+            entered_time: None,
+            edited_art: None,
+            edit_position: Vector3::default(),
+            gizmo: Gizmo::default(),
+            fade: None,
+        }
+    }
+
+    pub fn art_2d(&self) -> &[usize] {
+        &self.art_2d
+    }
+
+    pub fn add_2d_art(&mut self, index: usize) {
+        self.art_2d.push(index);
+    }
+
+    /// Index of the previewed art.
+    pub fn preview_art(&self) -> Option<usize> {
+        self.preview_art
+    }
+
+    /// Previews the next 2D art, or nothing after the last one, and returns its index.
+    pub fn cycle_preview_art(&mut self) -> Option<usize> {
+        self.preview_art = match self.preview_art {
+            Some(index) => self.art_2d.iter().copied().find(|&art| art > index),
+            None => self.art_2d.first().copied(),
+        };
+        self.preview_art
+    }
+
+    /// Index of the entered art.
+    pub fn entered_art(&self) -> Option<usize> {
+        self.entered_art
+    }
+
+    /// Enters the art at `index`, or none, with its time and mouse reset.
+    pub fn enter_art(&mut self, index: Option<usize>) {
+        self.entered_art = index;
+        self.entered_time = None;
+        self.art_mouse = Vector4::default();
+    }
+
+    pub fn exit_art(&mut self) {
+        self.entered_art = None;
+    }
+
+    /// The time of the entered art at the time `time` of the gallery.
+    pub fn entered_time(&mut self, time: f32) -> f32 {
+        time - *self.entered_time.get_or_insert(time)
+    }
+
+    /// Index of the art moved by [`super::VkApp::transform_edited_art`].
+    pub fn edited_art(&self) -> Option<usize> {
+        self.edited_art
+    }
+
+    /// Edits the art at `index`, or none, placed by `model`.
+    pub fn edit_art(&mut self, index: Option<usize>, model: Option<Matrix4>) {
+        self.edited_art = index;
+        if let Some(model) = model {
+            self.edit_position = model[3].resize();
+        }
+        self.gizmo.end_drag();
+        self.gizmo.hovered = None;
+    }
+
+    /// Stops editing and returns the index of the edited art.
+    pub fn stop_editing(&mut self) -> Option<usize> {
+        self.gizmo.end_drag();
+        self.edited_art.take()
+    }
+
+    pub fn edit_position(&self) -> Vector3 {
+        self.edit_position
+    }
+
+    pub fn set_edit_position(&mut self, position: Vector3) {
+        self.edit_position = position;
+    }
+
+    /// The handles the edited art is dragged with.
+    pub fn gizmo(&self) -> &Gizmo {
+        &self.gizmo
+    }
+
+    pub fn gizmo_mut(&mut self) -> &mut Gizmo {
+        &mut self.gizmo
+    }
+
+    /// Switches what dragging the handles does, a drag in progress is dropped.
+    pub fn set_gizmo_mode(&mut self, mode: GizmoMode) {
+        self.gizmo.end_drag();
+        self.gizmo.mode = mode;
+        self.gizmo.hovered = None;
+    }
+
+    /// Opacity of the black the frame is faded to, see [`super::VkApp::set_fade`].
+    pub fn fade(&self) -> Option<f32> {
+        self.fade
+    }
+
+    /// Fades to `fade` clamped to 0 to 1, returns true if fading started or stopped.
+    pub fn set_fade(&mut self, fade: Option<f32>) -> bool {
+        let was_fading = self.fade.is_some();
+        self.fade = fade.map(|fade| fade.clamp(0., 1.));
+        was_fading != self.fade.is_some()
+    }
+
+    /// Forgets the art at `index` that was removed from the pipelines, the indices of the
+    /// art after it move down by one.
+    pub fn remove_art(&mut self, index: usize) {
+        if self.preview_art == Some(index) {
+            self.preview_art = None;
+        }
+        if self.entered_art == Some(index) {
+            self.entered_art = None;
+        }
+        if self.edited_art == Some(index) {
+            self.edited_art = None;
+            self.gizmo.end_drag();
+        }
+        let shift = |other: usize| if other > index { other - 1 } else { other };
+        self.art_2d.retain(|&other| other != index);
+        for other in self.art_2d.iter_mut() {
+            *other = shift(*other);
+        }
+        self.preview_art = self.preview_art.map(shift);
+        self.entered_art = self.entered_art.map(shift);
+        self.edited_art = self.edited_art.map(shift);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_art_shifts_indices() {
+        let mut scene = Scene::new(vec![3, 5, 7]);
+        assert_eq!(scene.cycle_preview_art(), Some(3));
+        assert_eq!(scene.cycle_preview_art(), Some(5));
+        scene.enter_art(Some(7));
+        scene.edit_art(Some(4), None);
+
+        scene.remove_art(4);
+        assert_eq!(scene.art_2d(), [3, 4, 6]);
+        assert_eq!(scene.preview_art(), Some(4));
+        assert_eq!(scene.entered_art(), Some(6));
+        assert_eq!(scene.edited_art(), None);
+
+        scene.remove_art(4);
+        assert_eq!(scene.art_2d(), [3, 5]);
+        assert_eq!(scene.preview_art(), None);
+        assert_eq!(scene.cycle_preview_art(), Some(3));
+    }
+}
@@ -1,16 +1,20 @@
-use crate::math::Matrix4;
+use crate::fs;
+use crate::math::{Matrix4, Vector4};
+
+use super::compiler::{default_compiler, include_path, ShaderCompiler};
+use super::structs::{ArtTime, Framing, Proximity};
+use super::volume::Volume;
 
 use ash::{vk, Device};
-use glslang::{
-    self,
-    Compiler, CompilerOptions, ShaderInput, ShaderStage,
-};
-use notify_debouncer_full::{new_debouncer, notify};
+use glslang::ShaderStage;
+use notify_debouncer_full::{new_debouncer, notify, DebounceEventResult};
 use std::{
     collections::{HashMap, HashSet},
+    env,
+    ffi::CString,
     io::Cursor,
     path::{Path, PathBuf},
-    sync::{Arc, RwLock},
+    sync::{Arc, OnceLock, RwLock},
     sync::mpsc::{self, Sender},
     time::Duration,
     thread,
@@ -19,42 +23,108 @@ use std::{
 const DEBOUNCE_TIME: Duration = Duration::from_millis(500);
 
 pub struct Shaders {
+    /// `main_vert`, `cube_vert` and `error_vert` select the view of an eye with multiview,
+    /// see [`Self::use_single_view`] for devices without it.
     pub main_vert: Shader,
     pub main_frag: Shader,
+    /// `main_frag` lit by a baked lightmap, see [`crate::lightmap`].
+    pub main_lightmap_frag: Shader,
     pub cube_vert: Shader,
     pub cube_frag: Shader,
+    /// Procedural sky drawn on the skybox instead of the cubemap.
+    pub sky_frag: Shader,
+    /// Used for art whose shaders failed to compile.
+    pub error_vert: Shader,
+    pub error_frag: Shader,
+    /// Used to combine the views of both eyes into a red/cyan anaglyph.
+    pub fullscreen_vert: Shader,
+    pub anaglyph_frag: Shader,
+    /// Draws the fragment shader of 2D art over a whole viewport.
+    pub preview_vert: Shader,
+    /// Draws the frame times as a graph with `fullscreen_vert`.
+    pub graph_frag: Shader,
+    /// Fades the frame to black with `fullscreen_vert`.
+    pub fade_frag: Shader,
+    /// Draws the handles of the edited art with `fullscreen_vert`, see [`crate::gizmo`].
+    pub gizmo_frag: Shader,
+    /// Draws text over the gallery with `fullscreen_vert`.
+    pub overlay_frag: Shader,
+    /// Scales up the image of half resolution art with the vertex shader of the art.
+    pub upscale_frag: Shader,
+    /// Grades the frame with a color lookup table with `fullscreen_vert`.
+    pub grade_frag: Shader,
+    /// `grade_frag` reading the depth of the gallery from a multisampled image.
+    pub grade_multisampled_frag: Shader,
+    /// Draws the depth of the environment the art is culled against.
+    pub occluder_vert: Shader,
+    /// Reduce the depth to a pyramid and cull the art with it.
+    pub depth_pyramid_comp: Shader,
+    pub occlusion_cull_comp: Shader,
+    /// Assigns the point lights to the clusters of the view.
+    pub light_clusters_comp: Shader,
+    /// Draws the depth of the environment into the cascades of the shadow map.
+    pub shadow_vert: Shader,
+    /// Lights and integrates the fog in the froxels of the view.
+    pub volumetric_fog_comp: Shader,
+    /// `main_vert`, `cube_vert` and `error_vert` compiled without multiview.
+    pub single_view_verts: [Shader; 3],
     pub shaders_art: Vec<ShaderArt>,
 }
 
 impl Shaders {
-    pub fn watch_art(&self) {
-        let shaders_by_path = self.shaders_art.iter()
-            .flat_map(|shader| [shader.vert.clone(), shader.frag.clone()])
-            .filter_map(|shader| shader.path()
-                        .and_then(|path| std::fs::canonicalize(&path).ok())
-                        .map(|path| (path, shader)))
-            .collect::<HashMap<_, _>>();
+    /// Replaces the vertex shaders that need multiview by ones that always render the first view.
+    pub fn use_single_view(&mut self) {
+        let [main_vert, cube_vert, error_vert] = self.single_view_verts.clone();
+        self.main_vert = main_vert;
+        self.cube_vert = cube_vert;
+        self.error_vert = error_vert;
+    }
+
+    /// Starts watching the files of the art shaders, a shader is marked as changed when its
+    /// file or a file it includes changes.
+    ///
+    /// The shaders of art added later are watched with the returned [`ShaderWatcher`].
+    pub fn watch_art(&self) -> ShaderWatcher {
+        let (tx, rx) = mpsc::channel();
+        let watcher = ShaderWatcher { sender: tx.clone() };
+        for shader in self.shaders_art.iter().flat_map(ShaderArt::shaders) {
+            watcher.watch(shader);
+        }
 
         thread::spawn(move || {
-            let (tx, rx) = mpsc::channel();
-            let mut debouncer = match new_debouncer(DEBOUNCE_TIME, None, tx) {
+            let mut debouncer = match new_debouncer(DEBOUNCE_TIME, None, move |result| {
+                let _ = tx.send(WatchEvent::Files(result));
+            }) {
                 Ok(debouncer) => debouncer,
                 Err(err) => {
                     log::error!("failed to create file watcher: {err}");
                     return;
                 }
             };
-            let dirs_to_watch = shaders_by_path.keys()
-                .filter_map(|path| path.parent())
-                .collect::<HashSet<_>>();
-            for path in dirs_to_watch {
-                if let Err(err) = debouncer.watch(path, notify::RecursiveMode::Recursive) {
-                    log::error!("failed to watch {}: {err}", path.display());
-                } else {
-                    log::debug!("watching file {}", path.display());
-                }
-            }
-            for res in rx {
+            // the same file can be used by several shaders, e.g. with different defines
+            let mut shaders_by_path = HashMap::<_, Vec<_>>::new();
+            let mut watched_dirs = HashSet::new();
+            for event in rx {
+                let res = match event {
+                    WatchEvent::Shader(shader) => {
+                        for path in watched_paths(&shader) {
+                            let dir = path.parent().map(Path::to_owned);
+                            if let Some(dir) = dir.filter(|dir| !watched_dirs.contains(dir)) {
+                                match debouncer.watch(&dir, notify::RecursiveMode::Recursive) {
+                                    Ok(()) => log::debug!("watching file {}", dir.display()),
+                                    Err(err) => {
+                                        log::error!("failed to watch {}: {err}", dir.display());
+                                    }
+                                }
+                                watched_dirs.insert(dir);
+                            }
+                            shaders_by_path.entry(path).or_insert_with(Vec::new)
+                                .push(shader.clone());
+                        }
+                        continue;
+                    }
+                    WatchEvent::Files(res) => res,
+                };
                 match res {
                     Ok(events) => {
                         for event in events {
@@ -62,17 +132,21 @@ impl Shaders {
                             use notify::event::{AccessKind::*, AccessMode::*, ModifyKind::*};
 
                             //log::info!("event: {:?}", event);
-                            if let Access(Close(Write)) | Modify(Data(_)) = event.kind {
+                            // builds may replace the file instead of writing into it
+                            if let Access(Close(Write)) | Modify(Data(_) | Name(_)) | Create(_)
+                                = event.kind
+                            {
                                 for path in &event.paths {
-                                    if let Some(shader) = shaders_by_path.get(path) {
-                                        if let Some(path) = shader.path() {
-                                            log::info!("shader changed {}", path.display());
-                                            let Ok(mut inner) = shader.inner.write() else {
-                                                log::error!("Lock poisoned");
-                                                continue;
-                                            };
-                                            inner.code_has_changed = true;
-                                        }
+                                    let Some(shaders) = shaders_by_path.get(path) else {
+                                        continue;
+                                    };
+                                    log::info!("shader changed {}", path.display());
+                                    for shader in shaders {
+                                        let Ok(mut inner) = shader.inner.write() else {
+                                            log::error!("Lock poisoned");
+                                            continue;
+                                        };
+                                        inner.code_has_changed = true;
                                     }
                                 }
                             }
@@ -82,15 +156,112 @@ impl Shaders {
                 }
             }
         });
+        watcher
+    }
+}
+
+/// What the thread watching the art shaders receives.
+enum WatchEvent {
+    Files(DebounceEventResult),
+    /// A shader to watch from now on.
+    Shader(Shader),
+}
+
+/// Watches the shaders of art added while the gallery runs, see [`Shaders::watch_art`].
+pub struct ShaderWatcher {
+    sender: Sender<WatchEvent>,
+}
+
+impl ShaderWatcher {
+    /// Marks `shader` as changed whenever its file or a file it includes changes.
+    pub fn watch(&self, shader: &Shader) {
+        if self.sender.send(WatchEvent::Shader(shader.clone())).is_err() {
+            log::error!("failed to watch shader, the watcher is gone");
+        }
     }
 }
 
+/// The canonical paths of the file of `shader` and of the files it includes.
+fn watched_paths(shader: &Shader) -> Vec<PathBuf> {
+    let Some(path) = shader.path() else {
+        return Vec::new();
+    };
+    // a shader is also recompiled when one of the files it includes changes
+    let source = std::fs::read_to_string(&path).unwrap_or_default();
+    let mut paths = find_includes(&path, &source);
+    paths.push(path);
+    paths.into_iter().filter_map(|path| std::fs::canonicalize(path).ok()).collect()
+}
+
 pub struct ShaderArt {
     pub name: String,
     pub is_3d: bool,
+    /// The vertex shader or a mesh shader for art without vertex input.
     pub vert: Shader,
     pub frag: Shader,
+    /// Optional tessellation control and evaluation shaders.
+    pub tessellation: Option<[Shader; 2]>,
+    /// Optional ray generation, miss and closest hit shaders.
+    ///
+    /// The art is then ray traced into an image that `frag` can sample at binding 1.
+    pub ray_tracing: Option<[Shader; 3]>,
+    /// An OBJ or glTF model the art is drawn with instead of the quad or the cube.
+    pub model_path: Option<PathBuf>,
+    /// OBJ models with the same faces as `model_path` whose positions the vertices blend towards,
+    /// see `Geometry::with_morph_targets`.
+    pub morph_targets: Vec<PathBuf>,
+    pub morph_weights: Vector4,
+    /// The animation of a skinned glTF model that is played, the first one if `None`.
+    pub animation: Option<String>,
     pub model_matrix: Matrix4,
+    /// Free parameters the shaders can read from their art uniforms.
+    pub params: Vector4,
+    pub time: ArtTime,
+    pub proximity: Proximity,
+    /// An optional 3D texture `frag` can sample at binding 4.
+    pub volume: Option<Volume>,
+    /// How 2D art fits the image at binding 1 into its quad.
+    pub framing: Framing,
+    /// Draws 3D art at half the resolution of the window and scales it up.
+    pub half_resolution: bool,
+}
+
+impl ShaderArt {
+    /// Returns true if the art is drawn with a mesh shader instead of a vertex shader.
+    pub fn is_mesh(&self) -> bool {
+        self.vert.stage_flags() == vk::ShaderStageFlags::MESH_EXT
+    }
+
+    /// Returns all shaders in the order of the pipeline stages followed by the ray tracing shaders.
+    pub fn shaders(&self) -> Vec<&Shader> {
+        let mut shaders = vec![&self.vert];
+        shaders.extend(self.tessellation.iter().flatten());
+        shaders.push(&self.frag);
+        shaders.extend(self.ray_tracing.iter().flatten());
+        shaders
+    }
+
+    pub fn shaders_mut(&mut self) -> Vec<&mut Shader> {
+        let mut shaders = vec![&mut self.vert];
+        shaders.extend(self.tessellation.iter_mut().flatten());
+        shaders.push(&mut self.frag);
+        shaders.extend(self.ray_tracing.iter_mut().flatten());
+        shaders
+    }
+
+    /// The `#define`s the fragment shader is compiled with, the other shaders of the art
+    /// have the same unless they are shared with other art.
+    pub fn defines(&self) -> Vec<(String, Option<String>)> {
+        self.frag.defines()
+    }
+
+    /// Returns the shaders of the graphics pipeline, the ray tracing shaders are dropped.
+    pub fn into_shaders(self) -> Vec<Shader> {
+        let mut shaders = vec![self.vert];
+        shaders.extend(self.tessellation.into_iter().flatten());
+        shaders.push(self.frag);
+        shaders
+    }
 }
 
 pub struct Shader {
@@ -102,6 +273,18 @@ impl Shader {
         self.inner.read().ok()?.path.clone()
     }
 
+    /// The `#define`s prepended to the source, see [`ShaderInner::define`].
+    pub fn defines(&self) -> Vec<(String, Option<String>)> {
+        self.inner.read().map(|inner| inner.defines.clone()).unwrap_or_default()
+    }
+
+    /// Defines `name` for the next compilation, replacing an earlier value of it.
+    pub fn set_define(&self, name: &str, value: Option<&str>) {
+        let mut inner = self.inner.write().unwrap();
+        inner.defines.retain(|(defined, _)| defined != name);
+        inner.defines.push((name.to_owned(), value.map(str::to_owned)));
+    }
+
     pub fn set_hot_reload(&mut self, sender: Sender<Shader>) {
         let mut inner = self.inner.write().unwrap();
         if inner.compile_sender.is_some() {
@@ -117,6 +300,44 @@ impl Shader {
         self.inner.read().map(|inner| inner.code_has_changed).unwrap_or(false)
     }
 
+    pub fn stage_flags(&self) -> vk::ShaderStageFlags {
+        let stage = self.inner.read().map(|inner| inner.stage).unwrap_or(ShaderStage::Vertex);
+        match stage {
+            ShaderStage::Vertex => vk::ShaderStageFlags::VERTEX,
+            ShaderStage::TesselationControl => vk::ShaderStageFlags::TESSELLATION_CONTROL,
+            ShaderStage::TesselationEvaluation => vk::ShaderStageFlags::TESSELLATION_EVALUATION,
+            ShaderStage::Geometry => vk::ShaderStageFlags::GEOMETRY,
+            ShaderStage::Fragment => vk::ShaderStageFlags::FRAGMENT,
+            ShaderStage::Compute => vk::ShaderStageFlags::COMPUTE,
+            ShaderStage::RayGeneration => vk::ShaderStageFlags::RAYGEN_KHR,
+            ShaderStage::Intersect => vk::ShaderStageFlags::INTERSECTION_KHR,
+            ShaderStage::AnyHit => vk::ShaderStageFlags::ANY_HIT_KHR,
+            ShaderStage::ClosestHit => vk::ShaderStageFlags::CLOSEST_HIT_KHR,
+            ShaderStage::Miss => vk::ShaderStageFlags::MISS_KHR,
+            ShaderStage::Callable => vk::ShaderStageFlags::CALLABLE_KHR,
+            ShaderStage::Task => vk::ShaderStageFlags::TASK_EXT,
+            ShaderStage::Mesh => vk::ShaderStageFlags::MESH_EXT,
+        }
+    }
+
+    pub fn entry_point(&self) -> CString {
+        self.inner.read().map(|inner| inner.entry_point.clone()).unwrap_or_else(|_| c"main".into())
+    }
+
+    /// Returns true if there is code or a module that can be used for a pipeline.
+    pub fn is_ready(&self) -> bool {
+        self.inner.read()
+            .map(|inner| inner.module.is_some() || inner.code.is_some())
+            .unwrap_or(false)
+    }
+
+    /// Returns the error of the last failed compilation if there is one.
+    ///
+    /// The error is cleared as soon as the shader compiles successfully again.
+    pub fn error(&self) -> Option<String> {
+        self.inner.read().ok()?.error.clone()
+    }
+
     pub fn reload(&self, device: &Device, forced: bool) -> bool {
         let path = self.inner.read().unwrap()
             .path.as_ref().expect("shader must have a path set to load it").clone();
@@ -166,6 +387,11 @@ impl Shader {
         let result = self.compile_code_helper();
         let mut inner = self.inner.write().map_err(|_| anyhow::anyhow!("Lock poisoned"))?;
         inner.is_compiling = false;
+        inner.error = match (&result, inner.path.as_ref()) {
+            (Ok(_), _) => None,
+            (Err(err), Some(path)) => Some(format_compile_error(path, err)),
+            (Err(err), None) => Some(format!("{err:#}")),
+        };
         result
     }
 
@@ -177,9 +403,11 @@ impl Shader {
         let path = inner.path.as_ref()
             .ok_or_else(|| anyhow::anyhow!("Cannot compile a Shader without path"))?
             .clone();
+        let defines = inner.defines.clone();
+        let compiler = Arc::clone(&inner.compiler);
         drop(inner); // do not keep the lock while compiling
 
-        let code = ShaderInner::compile_code(stage, &path)?;
+        let code = ShaderInner::compile_code(compiler.as_ref(), stage, &path, &defines)?;
         let mut inner = self.inner.write().map_err(|_| anyhow::anyhow!("Lock poisoned"))?;
         inner.code = Some(code);
         inner.module = None;
@@ -200,6 +428,13 @@ impl Clone for Shader {
     }
 }
 
+/// Two handles are equal if they share the same shader.
+impl PartialEq for Shader {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
 impl From<ShaderInner> for Shader {
     fn from(value: ShaderInner) -> Self {
         Self {
@@ -211,11 +446,15 @@ impl From<ShaderInner> for Shader {
 pub struct ShaderInner {
     stage: ShaderStage,
     path: Option<PathBuf>,
+    defines: Vec<(String, Option<String>)>,
+    compiler: Arc<dyn ShaderCompiler>,
+    entry_point: CString,
     code: Option<Box<[u32]>>,
     module: Option<vk::ShaderModule>,
     compile_sender: Option<Sender<Shader>>,
     is_compiling: bool,
     code_has_changed: bool,
+    error: Option<String>,
 }
 
 impl ShaderInner {
@@ -223,19 +462,53 @@ impl ShaderInner {
         Self {
             stage,
             path: None,
+            defines: Vec::new(),
+            compiler: default_compiler(),
+            entry_point: c"main".into(),
             code: None,
             module: None,
             compile_sender: None,
             is_compiling: false,
             code_has_changed: false,
+            error: None,
         }
     }
 
+    /// Sets the path to load the shader from.
+    ///
+    /// Files with the extension `spv` are loaded as precompiled SPIR-V,
+    /// every other file is compiled as GLSL.
     pub fn path<P: Into<PathBuf>>(mut self, path: P) -> Self {
         self.path = Some(path.into());
         self
     }
 
+    /// Adds a `#define` that is prepended to the source when compiling the shader.
+    ///
+    /// This only has an effect on GLSL shaders loaded from a path.
+    pub fn define<K: Into<String>>(mut self, name: K, value: Option<&str>) -> Self {
+        self.defines.push((name.into(), value.map(str::to_owned)));
+        self
+    }
+
+    /// Sets the name of the entry point, the default is `main`.
+    ///
+    /// This allows to use a SPIR-V module with several entry points for different shaders.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` contains a nul byte.
+    pub fn entry_point(mut self, name: &str) -> Self {
+        self.entry_point = CString::new(name).expect("entry point name must not contain nul bytes");
+        self
+    }
+
+    /// Sets the compiler used for GLSL shaders, the default is [`default_compiler`].
+    pub fn compiler<C: ShaderCompiler + 'static>(mut self, compiler: C) -> Self {
+        self.compiler = Arc::new(compiler);
+        self
+    }
+
     pub fn bytes(mut self, bytes: &[u8]) -> Result<Self, anyhow::Error> {
         let mut cursor = Cursor::new(bytes);
         let code = ash::util::read_spv(&mut cursor)?;
@@ -255,25 +528,71 @@ impl ShaderInner {
         Ok(())
     }
 
-    fn compile_code(stage: ShaderStage, path: &Path) -> Result<Box<[u32]>, anyhow::Error> {
+    fn compile_code(
+        compiler: &dyn ShaderCompiler,
+        stage: ShaderStage,
+        path: &Path,
+        defines: &[(String, Option<String>)],
+    ) -> Result<Box<[u32]>, anyhow::Error> {
         // try not to panic in this function to keep the compile thread going
 
-        log::debug!("compiling Shader {} of stage {:?}", path.display(), stage);
-        let source = std::fs::read_to_string(path)?.into();
-        let compiler = Compiler::acquire()
-            .ok_or_else(|| anyhow::anyhow!("Failed to acquire Compiler"))?;
-        let input = ShaderInput::new(
-            &source,
-            stage,
-            &CompilerOptions::default(),
-            None,
-            None,
-        )?;
-        let shader = compiler.create_shader(input)?;
-        let code = shader.compile()?;
+        if path.extension().is_some_and(|ext| ext == "spv") {
+            log::debug!("loading SPIR-V Shader {} of stage {:?}", path.display(), stage);
+            let code = ash::util::read_spv(&mut fs::load(path)?)?;
+            return Ok(code.into());
+        }
+
+        let source = std::fs::read_to_string(path)?;
+        let included = find_includes(path, &source).iter()
+            .map(|path| std::fs::read_to_string(path).unwrap_or_default())
+            .collect::<Vec<_>>();
+        let cache_path = Self::cache_path(compiler, stage, &source, &included, defines);
+        if let Ok(code) = fs::load(&cache_path).and_then(|mut file| ash::util::read_spv(&mut file)) {
+            log::debug!("loaded Shader {} from cache {}", path.display(), cache_path.display());
+            return Ok(code.into());
+        }
+
+        log::debug!("compiling Shader {} of stage {:?} with {}", path.display(), stage, compiler.name());
+        let defines = defines.iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+            .collect::<Vec<_>>();
+        let code = compiler.compile(stage, path, &source, &defines)?;
+
+        let bytes = code.iter().flat_map(|word| word.to_le_bytes()).collect::<Vec<_>>();
+        if let Err(err) = write_cache(&cache_path, &bytes) {
+            log::warn!("failed to cache Shader {} at {}: {err}", path.display(), cache_path.display());
+        }
         Ok(code.into())
     }
 
+    /// Returns the path where the SPIR-V compiled from `source` with `defines` is cached.
+    ///
+    /// `included` are the sources of the files `source` includes.
+    fn cache_path(
+        compiler: &dyn ShaderCompiler,
+        stage: ShaderStage,
+        source: &str,
+        included: &[String],
+        defines: &[(String, Option<String>)],
+    ) -> PathBuf {
+        let mut hash = Fnv1a::new()
+            .part(compiler.name().as_bytes())
+            .part(compiler.version().as_bytes())
+            .part(format!("{stage:?}").as_bytes())
+            .part(source.as_bytes());
+        for included in included {
+            hash = hash.part(included.as_bytes());
+        }
+        for (name, value) in defines {
+            hash = hash.part(name.as_bytes());
+            hash = match value {
+                Some(value) => hash.part(&[1]).part(value.as_bytes()),
+                None => hash.part(&[0]),
+            };
+        }
+        cache_dir().join(format!("{:016x}.spv", hash.0))
+    }
+
     fn cleanup(&mut self, device: &Device) -> bool {
         if let Some(module) = self.module.take() {
             if let Some(path) = self.path.as_ref() {
@@ -291,6 +610,110 @@ impl ShaderInner {
     }
 }
 
+/// Returns the files `source` loaded from `path` includes with `#include "file"`,
+/// directly or through other included files.
+fn find_includes(path: &Path, source: &str) -> Vec<PathBuf> {
+    let mut includes = Vec::new();
+    let mut pending = vec![(path.to_owned(), source.to_owned())];
+    while let Some((includer, source)) = pending.pop() {
+        for line in source.lines() {
+            let Some(header) = line.trim().strip_prefix("#include")
+                .and_then(|rest| rest.trim().strip_prefix('"'))
+                .and_then(|rest| rest.split_once('"'))
+                .map(|(header, _)| header)
+            else {
+                continue;
+            };
+            let path = include_path(&includer, header);
+            if includes.contains(&path) {
+                continue;
+            }
+            if let Ok(source) = std::fs::read_to_string(&path) {
+                pending.push((path.clone(), source));
+            }
+            includes.push(path);
+        }
+    }
+    includes
+}
+
+/// Formats a compilation error so that every glslang message points to the source file.
+///
+/// glslang reports locations as `<string number>:<line>` and since we always compile
+/// a single string the string number is replaced by the path of the shader.
+fn format_compile_error(path: &Path, err: &anyhow::Error) -> String {
+    let file = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    format!("{err:#}")
+        .lines()
+        .map(|line| line.trim_end())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.replacen(" 0:", &format!(" {file}:"), 1))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The 64-bit FNV-1a hash of the key of cached SPIR-V, unlike `DefaultHasher` it is the same
+/// for every build, so the cache stays valid when the gallery is built with another Rust.
+#[derive(Debug, Clone, Copy)]
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    /// Adds `bytes` after their length, so that it matters where one part ends.
+    fn part(mut self, bytes: &[u8]) -> Self {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+        self
+    }
+}
+
+/// The directory of cached SPIR-V, in the cache directory of the user, e.g.
+/// `~/.cache/shaderpixel/shaders`, or next to the executable if there is none.
+fn cache_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let home = || env::var_os("HOME").map(PathBuf::from);
+        let user_cache = if cfg!(windows) {
+            env::var_os("LOCALAPPDATA").map(PathBuf::from)
+        } else if cfg!(target_os = "macos") {
+            home().map(|home| home.join("Library/Caches"))
+        } else {
+            env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+                .filter(|dir| dir.is_absolute())
+                .or_else(|| home().map(|home| home.join(".cache")))
+        };
+        user_cache
+            .filter(|dir| dir.is_absolute())
+            .map(|dir| dir.join("shaderpixel").join("shaders"))
+            .or_else(|| Some(env::current_exe().ok()?.parent()?.join("shader-cache")))
+            .unwrap_or_else(|| PathBuf::from("shader-cache"))
+    })
+}
+
+/// Writes `bytes` to a temporary file next to `path` and renames it, so that a gallery
+/// compiling the same shader at the same time never reads half of the file.
+fn write_cache(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new(""));
+    std::fs::create_dir_all(dir)?;
+    let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = dir.join(temp_name);
+    std::fs::write(&temp_path, bytes)
+        .and_then(|_| std::fs::rename(&temp_path, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp_path);
+        })
+}
+
 impl Drop for ShaderInner {
     fn drop(&mut self) {
         if !std::thread::panicking() &&  self.module.is_some() {
@@ -298,3 +721,78 @@ impl Drop for ShaderInner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::Glslang;
+
+    #[test]
+    fn compile_with_defines() {
+        let path = std::env::temp_dir().join("shaderpixel_define_test.frag");
+        std::fs::write(&path, "#version 450
+layout(location = 0) out vec4 outColor;
+void main() {
+#ifdef RED
+    outColor = vec4(1.0, 0.0, 0.0, 1.0);
+#else
+    outColor = vec4(0.0, 0.0, 1.0, 1.0);
+#endif
+}
+").unwrap();
+        let compile = |defines: &[(String, Option<String>)]| {
+            ShaderInner::compile_code(&Glslang, ShaderStage::Fragment, &path, defines).unwrap()
+        };
+        let plain = compile(&[]);
+        let red = compile(&[("RED".to_owned(), None)]);
+        assert_ne!(plain, red);
+        assert_eq!(compile(&[("RED".to_owned(), None)]), red);
+    }
+
+    #[test]
+    fn stable_cache_key() {
+        // the test vectors of FNV-1a
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.0
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+        assert_ne!(Fnv1a::new().part(b"ab").part(b"c").0, Fnv1a::new().part(b"a").part(b"bc").0);
+
+        let path = |defines: &[(String, Option<String>)]| {
+            ShaderInner::cache_path(&Glslang, ShaderStage::Fragment, "void main() {}", &[], defines)
+        };
+        assert_eq!(path(&[]), path(&[]));
+        assert_ne!(path(&[]), path(&[("RED".to_owned(), None)]));
+        let empty = path(&[("RED".to_owned(), Some(String::new()))]);
+        assert_ne!(path(&[("RED".to_owned(), None)]), empty);
+        assert!(path(&[]).starts_with(cache_dir()));
+    }
+
+    #[test]
+    fn compile_with_includes() {
+        let dir = std::env::temp_dir().join("shaderpixel_include_test");
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        let path = dir.join("include.frag");
+        std::fs::write(&path, "#version 450
+#extension GL_GOOGLE_include_directive : require
+#include \"common/color.glsl\"
+layout(location = 0) out vec4 outColor;
+void main() {
+    outColor = color();
+}
+").unwrap();
+        std::fs::write(dir.join("common/color.glsl"), "#include \"red.glsl\"
+vec4 color() { return vec4(RED, 0.0, 0.0, 1.0); }
+").unwrap();
+        std::fs::write(dir.join("common/red.glsl"), "#define RED 1.0\n").unwrap();
+
+        let source = std::fs::read_to_string(&path).unwrap();
+        let includes = find_includes(&path, &source);
+        assert_eq!(includes, [dir.join("common/color.glsl"), dir.join("common/red.glsl")]);
+        ShaderInner::compile_code(&Glslang, ShaderStage::Fragment, &path, &[]).unwrap();
+    }
+}
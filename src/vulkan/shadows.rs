@@ -0,0 +1,646 @@
+use crate::math::{self, Matrix4, Projection, Rad, Vector3, Vector4};
+use super::{
+    buffer,
+    context::VkContext,
+    descriptor::DescriptorAllocator,
+    geometry::Geometry,
+    shader::Shader,
+    vertex::{Vertex, VertexTangents},
+};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// The bindings of the cascades and of the shadow map in the descriptor sets of the environment.
+pub const CASCADES_BINDING: u32 = 15;
+pub const SHADOW_MAP_BINDING: u32 = 16;
+/// The number of cascades, `cascadeViewProj` in the shaders has room for as many.
+pub const CASCADE_COUNT: usize = 4;
+/// Width and height of the shadow map of each cascade.
+const SHADOW_SIZE: u32 = 2048;
+/// The distance from the camera up to which there are shadows.
+const SHADOW_DISTANCE: f32 = 40.;
+/// Blend between uniform (0) and logarithmic (1) distances of the cascades.
+const SPLIT_LAMBDA: f32 = 0.75;
+/// How far towards the sun casters in front of a cascade are drawn.
+const CASTER_DISTANCE: f32 = 50.;
+
+/// A cascade of the shadow map covering a slice of the view frustum.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Cascade {
+    /// From world space to the clip space of the shadow map.
+    pub view_proj: Matrix4,
+    /// The size of a texel of the shadow map in world units.
+    pub texel_size: f32,
+}
+
+/// The cascades as they are read by the shaders.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct CascadeData {
+    model: Matrix4,
+    view_proj: [Matrix4; CASCADE_COUNT],
+    texel_sizes: [f32; CASCADE_COUNT],
+    count: u32,
+    _padding: [u32; 3],
+}
+
+/// Shadows of the sun on the environment from a shadow map split into cascades.
+///
+/// The view frustum up to [`SHADOW_DISTANCE`] is split into slices that grow with the
+/// distance, and each slice gets a layer of the shadow map, so that near shadows are crisp
+/// while far ones cover a lot of ground. The cascades are fitted around bounding spheres
+/// of the slices and snapped to texels, so they do not shimmer when the camera moves or turns.
+/// The environment samples the nearest cascade covering it with a filtered 3x3 kernel.
+///
+/// Only the environment casts shadows.
+pub struct ShadowCascades {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    /// The view of every layer followed by the view of the whole array.
+    views: Vec<vk::ImageView>,
+    sampler: vk::Sampler,
+    render_pass: vk::RenderPass,
+    framebuffers: Vec<vk::Framebuffer>,
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    /// The cascades of every frame in flight, one after another.
+    buffer: vk::Buffer,
+    buffer_memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the cascades of a frame aligned to the storage buffer offset alignment.
+    frame_size: usize,
+}
+
+impl ShadowCascades {
+    /// The shader is the vertex shader drawing the depth of the environment, it is cleaned up.
+    pub fn new(
+        vk_context: &VkContext,
+        descriptor_allocator: &mut DescriptorAllocator,
+        shader: Shader,
+        frames_in_flight: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let device = vk_context.device();
+        let depth_format = vk_context
+            .find_supported_format(
+                &[vk::Format::D32_SFLOAT, vk::Format::D16_UNORM],
+                vk::ImageTiling::OPTIMAL,
+                vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT
+                    | vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR,
+            )
+            .context("No depth format can be filtered")?;
+        let render_pass = Self::create_render_pass(device, depth_format);
+
+        let descriptor_set_layout = descriptor_allocator.create_layout(device, &[
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::VERTEX),
+        ])?;
+        let module = shader.module(device).context("The shadow shader is not compiled")?;
+        let (pipeline, layout) = Self::create_pipeline(
+            device,
+            render_pass,
+            descriptor_set_layout,
+            module,
+            &shader.entry_point(),
+        );
+        shader.cleanup(device);
+        let descriptor_sets = descriptor_allocator
+            .allocate(device, descriptor_set_layout, frames_in_flight)?;
+
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let frame_size = size_of::<CascadeData>().next_multiple_of(alignment);
+        let (buffer, buffer_memory, _) = buffer::create_buffer(
+            vk_context,
+            (frame_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(buffer_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map cascade buffer")?
+        };
+
+        let (image, memory, views) = Self::create_image(vk_context, depth_format);
+        let framebuffers = views[..CASCADE_COUNT].iter().map(|&view| {
+            let attachments = [view];
+            let framebuffer_info = vk::FramebufferCreateInfo::default()
+                .render_pass(render_pass)
+                .attachments(&attachments)
+                .width(SHADOW_SIZE)
+                .height(SHADOW_SIZE)
+                .layers(1);
+            unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() }
+        }).collect();
+        // shadowed where the depth is farther than the shadow map, filtered between texels
+        let sampler_info = vk::SamplerCreateInfo::default()
+            .mag_filter(vk::Filter::LINEAR)
+            .min_filter(vk::Filter::LINEAR)
+            .mipmap_mode(vk::SamplerMipmapMode::NEAREST)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .compare_enable(true)
+            .compare_op(vk::CompareOp::LESS_OR_EQUAL)
+            .border_color(vk::BorderColor::FLOAT_OPAQUE_WHITE);
+        let sampler = unsafe {
+            device.create_sampler(&sampler_info, None).context("Failed to create shadow sampler")?
+        };
+
+        let shadows = Self {
+            image,
+            memory,
+            views,
+            sampler,
+            render_pass,
+            framebuffers,
+            pipeline,
+            layout,
+            descriptor_sets,
+            buffer,
+            buffer_memory,
+            ptr,
+            frame_size,
+        };
+        for (frame, &set) in shadows.descriptor_sets.iter().enumerate() {
+            let buffer_infos = [shadows.buffer_info(frame)];
+            let write = vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&buffer_infos);
+            unsafe { device.update_descriptor_sets(&[write], &[]) };
+        }
+        Ok(shadows)
+    }
+
+    pub fn descriptor_set_layout_bindings<'a>() -> [vk::DescriptorSetLayoutBinding<'a>; 2] {
+        [
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(CASCADES_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(SHADOW_MAP_BINDING)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT),
+        ]
+    }
+
+    /// Points the descriptor set of each frame in flight at the cascades of that frame
+    /// and at the shadow map.
+    pub fn update_descriptor_sets(&self, device: &Device, sets: &[vk::DescriptorSet]) {
+        for (frame, &set) in sets.iter().enumerate() {
+            let buffer_infos = [self.buffer_info(frame)];
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_view(*self.views.last().unwrap())
+                .image_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)
+                .sampler(self.sampler)];
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(CASCADES_BINDING)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&buffer_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(SHADOW_MAP_BINDING)
+                    .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                    .image_info(&image_infos),
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+    }
+
+    /// Writes the `cascades` of the frame at index `frame` with the `model` matrix of the
+    /// environment, the GPU must be done with it. Without cascades nothing is shadowed.
+    pub fn write(&mut self, frame: usize, model: Matrix4, cascades: &[Cascade]) {
+        let count = cascades.len().min(CASCADE_COUNT);
+        let mut data = CascadeData {
+            model,
+            view_proj: [Matrix4::unit(); CASCADE_COUNT],
+            texel_sizes: [0.; CASCADE_COUNT],
+            count: count as u32,
+            _padding: [0; 3],
+        };
+        for (i, cascade) in cascades[..count].iter().enumerate() {
+            data.view_proj[i] = cascade.view_proj;
+            data.texel_sizes[i] = cascade.texel_size;
+        }
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.frame_size);
+            dst.cast::<CascadeData>().write_unaligned(data);
+        }
+    }
+
+    /// Records drawing the depth of the environment `geometry` into every cascade of `frame`.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded outside of a render pass, the shadow map can be sampled in fragment
+    /// shaders once it is done.
+    pub unsafe fn record(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        geometry: Option<&Geometry>,
+    ) {
+        let clear_values = [vk::ClearValue {
+            depth_stencil: vk::ClearDepthStencilValue { depth: 1.0, stencil: 0 },
+        }];
+        for (cascade, &framebuffer) in self.framebuffers.iter().enumerate() {
+            let render_pass_begin_info = vk::RenderPassBeginInfo::default()
+                .render_pass(self.render_pass)
+                .framebuffer(framebuffer)
+                .render_area(vk::Rect2D {
+                    offset: vk::Offset2D { x: 0, y: 0 },
+                    extent: vk::Extent2D { width: SHADOW_SIZE, height: SHADOW_SIZE },
+                })
+                .clear_values(&clear_values);
+            unsafe {
+                device.cmd_begin_render_pass(
+                    buffer,
+                    &render_pass_begin_info,
+                    vk::SubpassContents::INLINE,
+                );
+                // without geometry nothing is shadowed
+                if let Some((vertex_buffer, index_buffer, index_count)) =
+                    geometry.and_then(Geometry::get)
+                {
+                    let bind_point = vk::PipelineBindPoint::GRAPHICS;
+                    device.cmd_bind_pipeline(buffer, bind_point, self.pipeline);
+                    device.cmd_bind_vertex_buffers(buffer, 0, &[vertex_buffer], &[0]);
+                    device.cmd_bind_index_buffer(buffer, index_buffer, 0, vk::IndexType::UINT32);
+                    device.cmd_bind_descriptor_sets(
+                        buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        self.layout,
+                        0,
+                        &self.descriptor_sets[frame..=frame],
+                        &[],
+                    );
+                    device.cmd_push_constants(
+                        buffer,
+                        self.layout,
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        &(cascade as u32).to_ne_bytes(),
+                    );
+                    device.cmd_draw_indexed(buffer, index_count, 1, 0, 0, 0);
+                }
+                device.cmd_end_render_pass(buffer);
+            }
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Nothing may be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            for framebuffer in self.framebuffers.drain(..) {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for view in self.views.drain(..) {
+                device.destroy_image_view(view, None);
+            }
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.destroy_render_pass(self.render_pass, None);
+            device.unmap_memory(self.buffer_memory);
+            device.free_memory(self.buffer_memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+    }
+
+    fn buffer_info(&self, frame: usize) -> vk::DescriptorBufferInfo {
+        vk::DescriptorBufferInfo::default()
+            .buffer(self.buffer)
+            .offset((frame * self.frame_size) as _)
+            .range(size_of::<CascadeData>() as _)
+    }
+
+    /// The depth array with a layer per cascade and its views.
+    fn create_image(
+        vk_context: &VkContext,
+        format: vk::Format,
+    ) -> (vk::Image, vk::DeviceMemory, Vec<vk::ImageView>) {
+        let device = vk_context.device();
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: SHADOW_SIZE,
+                height: SHADOW_SIZE,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(CASCADE_COUNT as u32)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1);
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+
+        let views = (0..CASCADE_COUNT as u32)
+            .map(|layer| (vk::ImageViewType::TYPE_2D, layer, 1))
+            .chain([(vk::ImageViewType::TYPE_2D_ARRAY, 0, CASCADE_COUNT as u32)])
+            .map(|(view_type, base_array_layer, layer_count)| {
+                let view_info = vk::ImageViewCreateInfo::default()
+                    .image(image)
+                    .view_type(view_type)
+                    .format(format)
+                    .subresource_range(vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::DEPTH,
+                        base_mip_level: 0,
+                        level_count: 1,
+                        base_array_layer,
+                        layer_count,
+                    });
+                unsafe { device.create_image_view(&view_info, None).unwrap() }
+            })
+            .collect();
+        (image, memory, views)
+    }
+
+    /// A render pass that only clears and draws the depth, which is sampled afterwards.
+    fn create_render_pass(device: &Device, depth_format: vk::Format) -> vk::RenderPass {
+        let attachment_descs = [vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL)];
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let subpass_descs = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .depth_stencil_attachment(&depth_attachment_ref)];
+        let fragment_tests = vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+            | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS;
+        let fragment_shader = vk::PipelineStageFlags::FRAGMENT_SHADER;
+        let subpass_deps = [
+            // the shadow map of the previous frame may still be sampled, also by the fog
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(fragment_shader | vk::PipelineStageFlags::COMPUTE_SHADER)
+                .dst_stage_mask(fragment_tests)
+                .dst_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(fragment_tests)
+                .src_access_mask(vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::FRAGMENT_SHADER)
+                .dst_access_mask(vk::AccessFlags::SHADER_READ),
+        ];
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    /// A pipeline without fragment shader that draws the positions of [`VertexTangents`]
+    /// into the cascade given as push constant.
+    fn create_pipeline(
+        device: &Device,
+        render_pass: vk::RenderPass,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        module: vk::ShaderModule,
+        entry_point: &std::ffi::CStr,
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let shader_stage_infos = [vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::VERTEX)
+            .module(module)
+            .name(entry_point)];
+        let binding_descs = [VertexTangents::get_binding_description()];
+        let attribute_descs = [VertexTangents::get_attribute_descriptions()[0]];
+        let vertex_input_info = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(&binding_descs)
+            .vertex_attribute_descriptions(&attribute_descs);
+        let input_assembly_info = vk::PipelineInputAssemblyStateCreateInfo::default()
+            .topology(vk::PrimitiveTopology::TRIANGLE_LIST);
+        let viewports = [vk::Viewport {
+            x: 0.,
+            y: 0.,
+            width: SHADOW_SIZE as f32,
+            height: SHADOW_SIZE as f32,
+            min_depth: 0.,
+            max_depth: 1.,
+        }];
+        let scissors = [vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: vk::Extent2D { width: SHADOW_SIZE, height: SHADOW_SIZE },
+        }];
+        let viewport_info = vk::PipelineViewportStateCreateInfo::default()
+            .viewports(&viewports)
+            .scissors(&scissors);
+        // both sides cast shadows, the bias keeps surfaces from shadowing themselves
+        let rasterizer_info = vk::PipelineRasterizationStateCreateInfo::default()
+            .polygon_mode(vk::PolygonMode::FILL)
+            .line_width(1.0)
+            .cull_mode(vk::CullModeFlags::NONE)
+            .depth_bias_enable(true)
+            .depth_bias_constant_factor(1.25)
+            .depth_bias_slope_factor(1.75);
+        let multisampling_info = vk::PipelineMultisampleStateCreateInfo::default()
+            .rasterization_samples(vk::SampleCountFlags::TYPE_1);
+        let depth_stencil_info = vk::PipelineDepthStencilStateCreateInfo::default()
+            .depth_test_enable(true)
+            .depth_write_enable(true)
+            .depth_compare_op(vk::CompareOp::LESS)
+            .max_depth_bounds(1.0);
+        let color_blending_info = vk::PipelineColorBlendStateCreateInfo::default();
+
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let ranges = [vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::VERTEX)
+                .size(size_of::<u32>() as _)];
+            let layout_info = vk::PipelineLayoutCreateInfo::default()
+                .set_layouts(&layouts)
+                .push_constant_ranges(&ranges);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+        let pipeline_infos = [vk::GraphicsPipelineCreateInfo::default()
+            .stages(&shader_stage_infos)
+            .vertex_input_state(&vertex_input_info)
+            .input_assembly_state(&input_assembly_info)
+            .viewport_state(&viewport_info)
+            .rasterization_state(&rasterizer_info)
+            .multisample_state(&multisampling_info)
+            .depth_stencil_state(&depth_stencil_info)
+            .color_blend_state(&color_blending_info)
+            .layout(layout)
+            .render_pass(render_pass)
+            .subpass(0)];
+        let pipeline = unsafe {
+            device.create_graphics_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+                .unwrap()[0]
+        };
+        (pipeline, layout)
+    }
+}
+
+/// The distances from the camera at which the cascades end, from uniform steps
+/// blended with logarithmic ones by [`SPLIT_LAMBDA`].
+fn split_distances(near: f32, far: f32) -> [f32; CASCADE_COUNT] {
+    std::array::from_fn(|i| {
+        let t = (i + 1) as f32 / CASCADE_COUNT as f32;
+        let uniform = near + (far - near) * t;
+        let logarithmic = near * (far / near).powf(t);
+        uniform + (logarithmic - uniform) * SPLIT_LAMBDA
+    })
+}
+
+/// Fits the cascades around the view seen through `view` and `projection` with `aspect`,
+/// lit by the sun in the direction `sun`.
+pub fn fit_cascades(
+    view: Matrix4,
+    projection: Projection,
+    aspect: f32,
+    sun: Vector3,
+) -> [Cascade; CASCADE_COUNT] {
+    // the camera is the inverse of the rigid view matrix
+    let row = |i: usize| Vector3::from([view[0][i], view[1][i], view[2][i]]);
+    let backward = row(2);
+    let eye = -(row(0) * view[3][0] + row(1) * view[3][1] + backward * view[3][2]);
+
+    let tan_y = (Rad::from(projection.fovy).0 / 2.).tan();
+    let tan_x = tan_y * aspect;
+    // how far the corners of a slice are from the axis of the view per unit of distance
+    let spread_sq = tan_x * tan_x + tan_y * tan_y;
+    let up = if sun.y().abs() > 0.99 {
+        Vector3::from([0., 0., 1.])
+    } else {
+        Vector3::from([0., 1., 0.])
+    };
+    let light_view = Matrix4::look_to_rh(Vector3::default(), -sun, up);
+
+    let far = SHADOW_DISTANCE.min(projection.far);
+    let splits = split_distances(projection.near, far);
+    std::array::from_fn(|i| {
+        let near = if i == 0 { projection.near } else { splits[i - 1] };
+        let far = splits[i];
+        // the smallest sphere around the slice, which only depends on the distances,
+        // so the cascades do not change size when the camera turns
+        let center = ((far + near) * (1. + spread_sq) / 2.).min(far);
+        let radius = ((center - near).powi(2) + near * near * spread_sq)
+            .max((far - center).powi(2) + far * far * spread_sq)
+            .sqrt();
+        let radius = (radius * 16.).ceil() / 16.;
+        let center = eye - backward * center;
+
+        // snap the center to texels, so the shadows do not shimmer when the camera moves
+        let texel_size = 2. * radius / SHADOW_SIZE as f32;
+        let center = Vector4::from([center[0], center[1], center[2], 1.]) * light_view;
+        let [x, y] = [center[0], center[1]].map(|c| (c / texel_size).floor() * texel_size);
+        let proj = math::orthographic(
+            x - radius,
+            x + radius,
+            y - radius,
+            y + radius,
+            -(center[2] + radius + CASTER_DISTANCE),
+            -(center[2] - radius),
+        );
+        Cascade { view_proj: proj * light_view, texel_size }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Deg;
+
+    fn project(matrix: Matrix4, point: Vector3) -> Vector4 {
+        Vector4::from([point[0], point[1], point[2], 1.]) * matrix
+    }
+
+    #[test]
+    fn cascades_cover_their_slices() {
+        let eye = Vector3::from([1., 2., 3.]);
+        let forward = Vector3::from([0.3, -0.2, -1.]).normalize();
+        let view = Matrix4::look_to_rh(eye, forward, Vector3::from([0., 1., 0.]));
+        let projection = Projection::default();
+        let aspect = 16. / 9.;
+        let sun = Vector3::from([0.5, 0.8, 0.3]).normalize();
+        let cascades = fit_cascades(view, projection, aspect, sun);
+        let splits = split_distances(projection.near, SHADOW_DISTANCE);
+        assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!((splits[CASCADE_COUNT - 1] - SHADOW_DISTANCE).abs() < 1e-3);
+
+        let right = forward.cross(Vector3::from([0., 1., 0.])).normalize();
+        let up = right.cross(forward);
+        let tan_y = (Rad::from(projection.fovy).0 / 2.).tan();
+        for (i, cascade) in cascades.iter().enumerate() {
+            let near = if i == 0 { projection.near } else { splits[i - 1] };
+            for distance in [near, splits[i]] {
+                for [sx, sy] in [[-1., -1.], [-1., 1.], [1., -1.], [1., 1.]] {
+                    let corner = eye
+                        + forward * distance
+                        + right * (sx * tan_y * aspect * distance)
+                        + up * (sy * tan_y * distance);
+                    let clip = project(cascade.view_proj, corner);
+                    assert!(clip[0].abs() <= 1. && clip[1].abs() <= 1., "{i}: {clip:?}");
+                    assert!((0. ..=1.).contains(&clip[2]), "{i}: {clip:?}");
+                }
+            }
+        }
+        assert!(cascades.windows(2).all(|pair| pair[0].texel_size < pair[1].texel_size));
+    }
+
+    #[test]
+    fn cascades_are_stable() {
+        let projection = Projection { fovy: Deg(60.), ..Default::default() };
+        let sun = Vector3::from([-0.4, 0.7, 0.2]).normalize();
+        let fit = |eye: [f32; 3], yaw: f32| {
+            let forward = Vector3::from([yaw.sin(), 0., -yaw.cos()]);
+            let view = Matrix4::look_to_rh(eye.into(), forward, Vector3::from([0., 1., 0.]));
+            fit_cascades(view, projection, 1.5, sun)
+        };
+        let a = fit([0., 1.5, 0.], 0.);
+        let b = fit([0.37, 1.5, -1.21], 1.1);
+        for (a, b) in a.iter().zip(b.iter()) {
+            // turning keeps the size and moving shifts the map by whole texels
+            assert_eq!(a.texel_size, b.texel_size);
+            let origin = Vector3::default();
+            let shift = (project(a.view_proj, origin) - project(b.view_proj, origin))
+                * (SHADOW_SIZE as f32 / 2.);
+            for texels in [shift[0], shift[1]] {
+                assert!((texels - texels.round()).abs() < 0.05, "{texels}");
+            }
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::gltf::{Animation, Skeleton};
+use crate::math::Matrix4;
+use super::buffer;
+use super::context::VkContext;
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// The binding of the joint matrices of all skinned art.
+pub const JOINT_BINDING: u32 = 10;
+
+/// The skeleton of skinned art and the animation it plays.
+pub struct Skin {
+    pub skeleton: Skeleton,
+    pub animation: Option<Animation>,
+}
+
+impl Skin {
+    /// The joint matrices at the local `time` of the art.
+    pub fn joint_matrices(&self, time: f32) -> Vec<Matrix4> {
+        self.skeleton.joint_matrices(self.animation.as_ref(), time)
+    }
+}
+
+/// A host visible storage buffer with the joint matrices of every skinned art piece for each
+/// frame in flight. An art piece finds its matrices at its `joint_offset` in the art uniforms.
+pub struct JointBuffer {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the matrices of a frame aligned to the storage buffer offset alignment.
+    frame_size: usize,
+    joint_count: usize,
+}
+
+impl JointBuffer {
+    /// A buffer with room for `joint_count` matrices per frame, at least one.
+    pub fn new(
+        vk_context: &VkContext,
+        frames_in_flight: usize,
+        joint_count: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let joint_count = joint_count.max(1);
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let frame_size = (joint_count * size_of::<Matrix4>()).next_multiple_of(alignment);
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            (frame_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            vk_context.device()
+                .map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map joint buffer")?
+        };
+        Ok(Self { buffer, memory, ptr, frame_size, joint_count })
+    }
+
+    pub fn descriptor_set_layout_binding<'a>(
+        stage_flags: vk::ShaderStageFlags,
+    ) -> vk::DescriptorSetLayoutBinding<'a> {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(JOINT_BINDING)
+            .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+            .descriptor_count(1)
+            .stage_flags(stage_flags)
+    }
+
+    /// Points the descriptor set of each frame in flight at the matrices of that frame.
+    pub fn update_descriptor_sets(&self, device: &Device, sets: &[vk::DescriptorSet]) {
+        for (frame, set) in sets.iter().enumerate() {
+            let buffer_infos = [vk::DescriptorBufferInfo::default()
+                .buffer(self.buffer)
+                .offset((frame * self.frame_size) as _)
+                .range((self.joint_count * size_of::<Matrix4>()) as _)];
+            let write = vk::WriteDescriptorSet::default()
+                .dst_set(*set)
+                .dst_binding(JOINT_BINDING)
+                .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                .buffer_info(&buffer_infos);
+            unsafe { device.update_descriptor_sets(&[write], &[]) };
+        }
+    }
+
+    /// Writes `matrices` at `offset` of the frame at index `frame`, the GPU must be done with it.
+    pub fn write(&mut self, frame: usize, offset: usize, matrices: &[Matrix4]) {
+        let count = matrices.len().min(self.joint_count.saturating_sub(offset));
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.frame_size).cast::<Matrix4>();
+            std::ptr::copy_nonoverlapping(matrices.as_ptr(), dst.add(offset), count);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The buffer must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.unmap_memory(self.memory);
+            device.free_memory(self.memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use super::{context::VkContext, sampler::SamplerConfig, texture::Texture};
+
+use ash::{vk, Device};
+
+/// The number of views rendered by a [`StereoTarget`], one for each eye.
+pub const EYE_COUNT: u32 = 2;
+/// Render to the left eye at view index 0 and the right eye at view index 1.
+const VIEW_MASK: u32 = 0b11;
+
+/// How the views of both eyes are shown on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoOutput {
+    /// The left eye on the left half and the right eye on the right half of the screen.
+    SideBySide,
+    /// Both eyes combined into a red/cyan anaglyph for colored glasses.
+    Anaglyph,
+}
+
+/// Offscreen attachments to render the left and the right eye in a single render pass.
+///
+/// Every attachment is an image array with one layer per eye and the render pass uses
+/// `VK_KHR_multiview` so every draw call is broadcast to both layers. The shaders select
+/// the view matrix of an eye with `gl_ViewIndex`.
+///
+/// Depending on the output the resolved image is left in the transfer source layout
+/// to be copied side by side into a swapchain image or in the shader read only layout
+/// to be sampled by a post pass.
+pub struct StereoTarget {
+    output: StereoOutput,
+    extent: vk::Extent2D,
+    render_pass: vk::RenderPass,
+    color_texture: Texture,
+    depth_texture: Texture,
+    resolve_texture: Texture,
+    framebuffer: vk::Framebuffer,
+}
+
+impl StereoTarget {
+    /// Create the attachments with `extent` for each eye.
+    pub fn new(
+        vk_context: &VkContext,
+        output: StereoOutput,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> Self {
+        let device = vk_context.device();
+        let render_pass =
+            Self::create_render_pass(device, output, format, msaa_samples, depth_format);
+
+        let color_texture = Self::create_layered_texture(
+            vk_context,
+            extent,
+            msaa_samples,
+            format,
+            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let depth_texture = Self::create_layered_texture(
+            vk_context,
+            extent,
+            msaa_samples,
+            depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageAspectFlags::DEPTH,
+        );
+        let mut resolve_texture = Self::create_layered_texture(
+            vk_context,
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT
+                | vk::ImageUsageFlags::TRANSFER_SRC
+                | vk::ImageUsageFlags::SAMPLED,
+            vk::ImageAspectFlags::COLOR,
+        );
+        resolve_texture.sampler = Some(vk_context.sampler(SamplerConfig::NEAREST).unwrap());
+
+        // with multiview the framebuffer has a single layer, the views are selected by the mask
+        let attachments = [color_texture.view, depth_texture.view, resolve_texture.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+
+        Self {
+            output,
+            extent,
+            render_pass,
+            color_texture,
+            depth_texture,
+            resolve_texture,
+            framebuffer,
+        }
+    }
+
+    pub fn output(&self) -> StereoOutput {
+        self.output
+    }
+
+    /// The extent of a single eye.
+    pub fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    /// The resolved image with the left eye in layer 0 and the right eye in layer 1.
+    pub fn resolve_texture(&self) -> Texture {
+        self.resolve_texture
+    }
+
+    /// Copy the left eye to the left half and the right eye to the right half of `image`.
+    ///
+    /// `image` must have the format of this target and has to be at least twice as wide.
+    /// It is transitioned from an undefined layout to the presentation layout.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded after the render pass of this target has ended
+    /// and the output of this target must be side by side.
+    pub unsafe fn copy_side_by_side(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        image: vk::Image,
+        image_extent: vk::Extent2D,
+    ) {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        // the acquire semaphore is waited on at the color attachment output stage
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(range)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+
+        // with an odd width a column in the middle is not covered by either eye
+        if image_extent.width != EYE_COUNT * self.extent.width {
+            let clear_color = vk::ClearColorValue {
+                float32: [0.0, 0.0, 0.0, 1.0],
+            };
+            let barrier = barrier
+                .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+            unsafe {
+                device.cmd_clear_color_image(
+                    buffer,
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &clear_color,
+                    &[range],
+                );
+                device.cmd_pipeline_barrier(
+                    buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[barrier],
+                );
+            }
+        }
+
+        let regions = (0..EYE_COUNT)
+            .map(|eye| {
+                vk::ImageCopy::default()
+                    .src_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: eye,
+                        layer_count: 1,
+                    })
+                    .dst_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        mip_level: 0,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    })
+                    .dst_offset(vk::Offset3D {
+                        x: (eye * (image_extent.width - self.extent.width)) as i32,
+                        y: 0,
+                        z: 0,
+                    })
+                    .extent(vk::Extent3D {
+                        width: self.extent.width,
+                        height: self.extent.height,
+                        depth: 1,
+                    })
+            })
+            .collect::<Vec<_>>();
+        unsafe {
+            device.cmd_copy_image(
+                buffer,
+                self.resolve_texture.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &regions,
+            );
+        }
+
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(range)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty());
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    /// Scale the view of `eye` to the whole of `image`, e.g. a swapchain image of a headset.
+    ///
+    /// `image` is in the color attachment layout before and after.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded after the render pass of this target has ended
+    /// and the output of this target must be side by side.
+    #[cfg(feature = "openxr")]
+    pub unsafe fn blit_eye(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        eye: u32,
+        image: vk::Image,
+        image_extent: vk::Extent2D,
+    ) {
+        let range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        };
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(range)
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+
+        let corner = |extent: vk::Extent2D| vk::Offset3D {
+            x: extent.width as i32,
+            y: extent.height as i32,
+            z: 1,
+        };
+        let region = vk::ImageBlit::default()
+            .src_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: eye,
+                layer_count: 1,
+            })
+            .src_offsets([vk::Offset3D::default(), corner(self.extent)])
+            .dst_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .dst_offsets([vk::Offset3D::default(), corner(image_extent)]);
+        unsafe {
+            device.cmd_blit_image(
+                buffer,
+                self.resolve_texture.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+                vk::Filter::LINEAR,
+            );
+        }
+
+        let barrier = barrier
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::empty());
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    fn create_render_pass(
+        device: &Device,
+        output: StereoOutput,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> vk::RenderPass {
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachement_desc = vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(match output {
+                StereoOutput::SideBySide => vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                StereoOutput::Anaglyph => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            });
+        let attachment_descs = [
+            color_attachment_desc,
+            depth_attachement_desc,
+            resolve_attachment_desc,
+        ];
+
+        let color_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+
+        let subpass_descs = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .resolve_attachments(&resolve_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref)];
+
+        // the resolve image is read after the render pass and the next frame has to wait for it
+        let (read_stage, read_access) = match output {
+            StereoOutput::SideBySide => {
+                (vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_READ)
+            }
+            StereoOutput::Anaglyph => {
+                (vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::SHADER_READ)
+            }
+        };
+        let subpass_deps = [
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | read_stage)
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(read_stage)
+                .dst_access_mask(read_access),
+        ];
+
+        let view_masks = [VIEW_MASK];
+        // both eyes see almost the same, this allows implementations to render them together
+        let correlation_masks = [VIEW_MASK];
+        let mut multiview_info = vk::RenderPassMultiviewCreateInfo::default()
+            .view_masks(&view_masks)
+            .correlation_masks(&correlation_masks);
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps)
+            .push_next(&mut multiview_info);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    /// Create an image array with one layer per eye and a view of all layers.
+    fn create_layered_texture(
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        sample_count: vk::SampleCountFlags,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Texture {
+        let device = vk_context.device();
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(EYE_COUNT)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(sample_count);
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D_ARRAY)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: EYE_COUNT,
+            });
+        let view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+
+        Texture::new(image, memory, view, None)
+    }
+
+    /// # Safety
+    ///
+    /// The target must not be in use anymore.
+    pub unsafe fn cleanup(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+        }
+        self.color_texture.destroy(device);
+        self.depth_texture.destroy(device);
+        self.resolve_texture.destroy(device);
+    }
+}
@@ -0,0 +1,516 @@
+use crate::math::Matrix4;
+use super::{buffer, context::VkContext, texture::Texture};
+
+use anyhow::anyhow;
+use ash::{vk, Device};
+use image::RgbaImage;
+
+/// The camera a still is rendered with instead of the one of the window.
+#[derive(Debug, Clone, Copy)]
+pub struct StillCamera {
+    pub extent: vk::Extent2D,
+    pub view: Matrix4,
+    pub proj: Matrix4,
+    /// The time of the art drawn over the whole still instead of the time since it was entered.
+    pub art_time: Option<f32>,
+}
+
+/// Offscreen attachments to render a still image that can be larger than the window in tiles.
+///
+/// The render pass has the same attachments as the one of the gallery, so the pipelines
+/// of the gallery can draw into it. The resolved tile is left in the transfer source layout
+/// and copied into a host visible buffer to be read back.
+pub struct StillTarget {
+    format: vk::Format,
+    render_pass: vk::RenderPass,
+    color_texture: Texture,
+    depth_texture: Texture,
+    resolve_texture: Texture,
+    framebuffer: vk::Framebuffer,
+    buffer: vk::Buffer,
+    buffer_memory: vk::DeviceMemory,
+}
+
+impl StillTarget {
+    /// Create the attachments for tiles of at most `extent`, see [`can_read_back`].
+    pub fn new(
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> Result<Self, anyhow::Error> {
+        if !can_read_back(format) {
+            return Err(anyhow!("Can not read back images with the format {format:?}"));
+        }
+        let device = vk_context.device();
+        let render_pass = Self::create_render_pass(device, format, msaa_samples, depth_format);
+
+        let color_texture = Self::create_texture(
+            vk_context,
+            extent,
+            msaa_samples,
+            format,
+            vk::ImageUsageFlags::TRANSIENT_ATTACHMENT | vk::ImageUsageFlags::COLOR_ATTACHMENT,
+            vk::ImageAspectFlags::COLOR,
+        );
+        let depth_texture = Self::create_texture(
+            vk_context,
+            extent,
+            msaa_samples,
+            depth_format,
+            vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            vk::ImageAspectFlags::DEPTH,
+        );
+        let resolve_texture = Self::create_texture(
+            vk_context,
+            extent,
+            vk::SampleCountFlags::TYPE_1,
+            format,
+            vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+            vk::ImageAspectFlags::COLOR,
+        );
+
+        let attachments = [color_texture.view, depth_texture.view, resolve_texture.view];
+        let framebuffer_info = vk::FramebufferCreateInfo::default()
+            .render_pass(render_pass)
+            .attachments(&attachments)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(1);
+        let framebuffer = unsafe { device.create_framebuffer(&framebuffer_info, None).unwrap() };
+
+        let (buffer, buffer_memory, _) = buffer::create_buffer(
+            vk_context,
+            4 * extent.width as vk::DeviceSize * extent.height as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+
+        Ok(Self {
+            format,
+            render_pass,
+            color_texture,
+            depth_texture,
+            resolve_texture,
+            framebuffer,
+            buffer,
+            buffer_memory,
+        })
+    }
+
+    pub fn render_pass(&self) -> vk::RenderPass {
+        self.render_pass
+    }
+
+    pub fn framebuffer(&self) -> vk::Framebuffer {
+        self.framebuffer
+    }
+
+    /// Copy the part of the resolved image with `extent` into the buffer that is read back.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded after the render pass of this target has ended.
+    pub unsafe fn copy_to_buffer(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        extent: vk::Extent2D,
+    ) {
+        unsafe {
+            copy_image_to_buffer(device, buffer, self.resolve_texture.image, self.buffer, extent);
+        }
+    }
+
+    /// Read the RGBA pixels of a tile with `extent` after it was copied into the buffer.
+    ///
+    /// # Safety
+    ///
+    /// The copy recorded with [`StillTarget::copy_to_buffer`] must have completed.
+    pub unsafe fn read(&self, device: &Device, extent: vk::Extent2D) -> Vec<u8> {
+        unsafe { read_rgba(device, self.buffer_memory, extent, self.format) }
+    }
+
+    fn create_render_pass(
+        device: &Device,
+        format: vk::Format,
+        msaa_samples: vk::SampleCountFlags,
+        depth_format: vk::Format,
+    ) -> vk::RenderPass {
+        let color_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+        let depth_attachement_desc = vk::AttachmentDescription::default()
+            .format(depth_format)
+            .samples(msaa_samples)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_desc = vk::AttachmentDescription::default()
+            .format(format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .final_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL);
+        let attachment_descs = [
+            color_attachment_desc,
+            depth_attachement_desc,
+            resolve_attachment_desc,
+        ];
+
+        let color_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(0)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+        let depth_attachment_ref = vk::AttachmentReference::default()
+            .attachment(1)
+            .layout(vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL);
+        let resolve_attachment_refs = [vk::AttachmentReference::default()
+            .attachment(2)
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+
+        let subpass_descs = [vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(&color_attachment_refs)
+            .resolve_attachments(&resolve_attachment_refs)
+            .depth_stencil_attachment(&depth_attachment_ref)];
+
+        // the previous tile has to be copied before the next one is resolved
+        let subpass_deps = [
+            vk::SubpassDependency::default()
+                .src_subpass(vk::SUBPASS_EXTERNAL)
+                .dst_subpass(0)
+                .src_stage_mask(
+                    vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                        | vk::PipelineStageFlags::TRANSFER,
+                )
+                .src_access_mask(vk::AccessFlags::empty())
+                .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .dst_access_mask(
+                    vk::AccessFlags::COLOR_ATTACHMENT_READ
+                        | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                ),
+            vk::SubpassDependency::default()
+                .src_subpass(0)
+                .dst_subpass(vk::SUBPASS_EXTERNAL)
+                .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+                .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
+                .dst_stage_mask(vk::PipelineStageFlags::TRANSFER)
+                .dst_access_mask(vk::AccessFlags::TRANSFER_READ),
+        ];
+
+        let render_pass_info = vk::RenderPassCreateInfo::default()
+            .attachments(&attachment_descs)
+            .subpasses(&subpass_descs)
+            .dependencies(&subpass_deps);
+
+        unsafe { device.create_render_pass(&render_pass_info, None).unwrap() }
+    }
+
+    fn create_texture(
+        vk_context: &VkContext,
+        extent: vk::Extent2D,
+        sample_count: vk::SampleCountFlags,
+        format: vk::Format,
+        usage: vk::ImageUsageFlags,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> Texture {
+        let device = vk_context.device();
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(sample_count);
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+
+        Texture::new(image, memory, view, None)
+    }
+
+    /// # Safety
+    ///
+    /// The target must not be in use anymore.
+    pub unsafe fn cleanup(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_framebuffer(self.framebuffer, None);
+            device.destroy_render_pass(self.render_pass, None);
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.buffer_memory, None);
+        }
+        self.color_texture.destroy(device);
+        self.depth_texture.destroy(device);
+        self.resolve_texture.destroy(device);
+    }
+}
+
+/// A host visible buffer the swapchain image drawn by a frame is copied into, e.g. to record it.
+pub struct FrameReadback {
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    extent: vk::Extent2D,
+    format: vk::Format,
+    /// True if a frame that copies into the buffer was submitted and is not read yet.
+    pub pending: bool,
+}
+
+impl FrameReadback {
+    /// Create a buffer for images of `extent` and `format`, see [`can_read_back`].
+    pub fn new(vk_context: &VkContext, extent: vk::Extent2D, format: vk::Format) -> Self {
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            4 * extent.width as vk::DeviceSize * extent.height as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        Self {
+            buffer,
+            memory,
+            extent,
+            format,
+            pending: false,
+        }
+    }
+
+    /// Copy `image` into the buffer, it is in the presentation layout before and after.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded after everything is drawn into `image`, which has to be a swapchain
+    /// image of the extent and the format of this buffer that can be copied from.
+    pub unsafe fn record_copy(&self, device: &Device, buffer: vk::CommandBuffer, image: vk::Image) {
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+            .new_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE | vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::TRANSFER_READ);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT | vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+            copy_image_to_buffer(device, buffer, image, self.buffer, self.extent);
+            let barrier = barrier
+                .old_layout(vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+                .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
+                .src_access_mask(vk::AccessFlags::TRANSFER_READ)
+                .dst_access_mask(vk::AccessFlags::empty());
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+        }
+    }
+
+    /// Read the copied image.
+    ///
+    /// # Safety
+    ///
+    /// The frame that copied into the buffer must be done drawing.
+    pub unsafe fn read(&self, device: &Device) -> RgbaImage {
+        let pixels = unsafe { read_rgba(device, self.memory, self.extent, self.format) };
+        RgbaImage::from_raw(self.extent.width, self.extent.height, pixels).unwrap()
+    }
+
+    /// # Safety
+    ///
+    /// The buffer must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_buffer(self.buffer, None);
+            device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// Only images with four 8 bit channels can be read back.
+pub fn can_read_back(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::B8G8R8A8_UNORM
+            | vk::Format::B8G8R8A8_SRGB
+            | vk::Format::R8G8B8A8_UNORM
+            | vk::Format::R8G8B8A8_SRGB
+    )
+}
+
+/// Copy the top left `extent` of `image` in the transfer source layout into `dst`
+/// and make it visible to the host.
+unsafe fn copy_image_to_buffer(
+    device: &Device,
+    buffer: vk::CommandBuffer,
+    image: vk::Image,
+    dst: vk::Buffer,
+    extent: vk::Extent2D,
+) {
+    let region = vk::BufferImageCopy::default()
+        .buffer_row_length(extent.width)
+        .buffer_image_height(extent.height)
+        .image_subresource(vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: 1,
+        })
+        .image_extent(vk::Extent3D {
+            width: extent.width,
+            height: extent.height,
+            depth: 1,
+        });
+    let barrier = vk::BufferMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::HOST_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(dst)
+        .offset(0)
+        .size(vk::WHOLE_SIZE);
+    unsafe {
+        device.cmd_copy_image_to_buffer(
+            buffer,
+            image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            dst,
+            &[region],
+        );
+        device.cmd_pipeline_barrier(
+            buffer,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::HOST,
+            vk::DependencyFlags::empty(),
+            &[],
+            &[barrier],
+            &[],
+        );
+    }
+}
+
+/// Read the RGBA pixels of an image of `extent` and `format` copied into `memory`.
+///
+/// The pixels are opaque like the window, whatever alpha the shaders wrote.
+unsafe fn read_rgba(
+    device: &Device,
+    memory: vk::DeviceMemory,
+    extent: vk::Extent2D,
+    format: vk::Format,
+) -> Vec<u8> {
+    let size = 4 * extent.width as usize * extent.height as usize;
+    let mut pixels = unsafe {
+        let ptr = device.map_memory(memory, 0, size as _, vk::MemoryMapFlags::empty()).unwrap();
+        let pixels = std::slice::from_raw_parts(ptr as *const u8, size).to_vec();
+        device.unmap_memory(memory);
+        pixels
+    };
+    let bgra = matches!(format, vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB);
+    for pixel in pixels.chunks_exact_mut(4) {
+        if bgra {
+            pixel.swap(0, 2);
+        }
+        pixel[3] = u8::MAX;
+    }
+    pixels
+}
+
+/// Splits an image of `extent` into tiles of at most `max_tile` pixels in each direction,
+/// row by row from the top left.
+pub fn tiles(extent: vk::Extent2D, max_tile: u32) -> Vec<vk::Rect2D> {
+    (0..extent.height)
+        .step_by(max_tile as usize)
+        .flat_map(|y| {
+            (0..extent.width).step_by(max_tile as usize).map(move |x| vk::Rect2D {
+                offset: vk::Offset2D { x: x as i32, y: y as i32 },
+                extent: vk::Extent2D {
+                    width: max_tile.min(extent.width - x),
+                    height: max_tile.min(extent.height - y),
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_cover_extent() {
+        let extent = vk::Extent2D { width: 5000, height: 3000 };
+        let tiles = tiles(extent, 2048);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles[2].offset, vk::Offset2D { x: 4096, y: 0 });
+        assert_eq!(tiles[2].extent, vk::Extent2D { width: 904, height: 2048 });
+        assert_eq!(tiles[5].extent, vk::Extent2D { width: 904, height: 952 });
+        let area: u32 = tiles.iter().map(|tile| tile.extent.width * tile.extent.height).sum();
+        assert_eq!(area, extent.width * extent.height);
+    }
+
+    #[test]
+    fn single_tile() {
+        let extent = vk::Extent2D { width: 800, height: 600 };
+        assert_eq!(tiles(extent, 2048), vec![vk::Rect2D { offset: vk::Offset2D::default(), extent }]);
+    }
+}
@@ -0,0 +1,215 @@
+use super::{buffer, cmd, context::VkContext, sampler::SamplerConfig, texture::Texture};
+
+use anyhow::Context;
+use ash::{vk, Device};
+
+/// A 2D texture that is written by the CPU while frames are in flight, like the keyboard
+/// state or the frames of a video.
+///
+/// Every frame in flight writes its texels into its own part of a mapped staging buffer and
+/// its command buffers copy them into the texture before anything is drawn, so that the
+/// command buffers do not have to be recorded again when the texels change.
+pub struct StreamTexture {
+    texture: Texture,
+    extent: vk::Extent2D,
+    /// Bytes of the texels of one frame in flight.
+    frame_size: usize,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    /// The buffer stays mapped for the lifetime of the texture.
+    ptr: *mut u8,
+}
+
+impl StreamTexture {
+    /// Create a texture of `extent` with `format` for `frames_in_flight` frames
+    /// that has all texels set to zero.
+    ///
+    /// Only formats with a size of `texel_size` bytes and without mipmaps are supported.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        format: vk::Format,
+        texel_size: usize,
+        extent: vk::Extent2D,
+        sampler: SamplerConfig,
+        frames_in_flight: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let device = vk_context.device();
+        let max_dimension = vk_context.physical_device_properties().limits.max_image_dimension2_d;
+        anyhow::ensure!(
+            extent.width.max(extent.height) <= max_dimension,
+            "A texture of {}x{} is larger than the maximum of {max_dimension}",
+            extent.width,
+            extent.height,
+        );
+        let frame_size = extent.width as usize * extent.height as usize * texel_size;
+        let size = (frame_size * frames_in_flight) as vk::DeviceSize;
+        let (buffer, memory, _) = buffer::create_buffer(
+            vk_context,
+            size,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(memory, 0, size, vk::MemoryMapFlags::empty())
+                .context("Failed to map staging buffer")?
+        };
+        unsafe { std::ptr::write_bytes(ptr as *mut u8, 0, size as usize) };
+
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_2D)
+            .extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(format)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1);
+        let image = unsafe {
+            device.create_image(&image_info, None).context("Failed to create streamed image")?
+        };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let image_memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None)
+                .context("Failed to allocate memory for streamed image")?;
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_2D)
+            .format(format)
+            .subresource_range(Self::range());
+        let view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+        let sampler = vk_context.sampler(sampler)?;
+
+        let stream = Self {
+            texture: Texture::new(image, image_memory, view, Some(sampler)),
+            extent,
+            frame_size,
+            buffer,
+            memory,
+            ptr: ptr as *mut u8,
+        };
+        // stills may be rendered before the first frame uploads the texels
+        cmd::execute_one_time_commands(device, command_pool, queue, |buffer| unsafe {
+            stream.record_upload(device, buffer, 0);
+        });
+        Ok(stream)
+    }
+
+    pub fn texture(&self) -> Texture {
+        self.texture
+    }
+
+    /// Writes the texels uploaded by the command buffers of `frame` row by row.
+    ///
+    /// The previous command buffer of `frame` must have completed.
+    pub fn write(&mut self, frame: usize, texels: &[u8]) {
+        assert_eq!(texels.len(), self.frame_size, "Texels do not match the size of the texture");
+        unsafe {
+            let dst = self.ptr.add(frame * self.frame_size);
+            std::ptr::copy_nonoverlapping(texels.as_ptr(), dst, self.frame_size);
+        }
+    }
+
+    /// Records the copy of the texels of `frame` into the texture.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded outside of a render pass before the texture is sampled.
+    pub unsafe fn record_upload(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize) {
+        // the whole image is overwritten, but the previous frames may still read it
+        let barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.texture.image)
+            .subresource_range(Self::range())
+            .src_access_mask(vk::AccessFlags::empty())
+            .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE);
+        let region = vk::BufferImageCopy::default()
+            .buffer_offset((frame * self.frame_size) as vk::DeviceSize)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_extent(vk::Extent3D {
+                width: self.extent.width,
+                height: self.extent.height,
+                depth: 1,
+            });
+        let barrier_read = barrier
+            .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier],
+            );
+            device.cmd_copy_buffer_to_image(
+                buffer,
+                self.buffer,
+                self.texture.image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &[region],
+            );
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[barrier_read],
+            );
+        }
+    }
+
+    fn range() -> vk::ImageSubresourceRange {
+        vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count: 1,
+        }
+    }
+
+    /// # Safety
+    ///
+    /// The texture must not be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.unmap_memory(self.memory);
+            device.free_memory(self.memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+        self.texture.destroy(device);
+    }
+}
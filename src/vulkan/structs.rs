@@ -1,6 +1,11 @@
-use crate::math::{Matrix4, Vector2};
+use crate::math::{Matrix4, Vector2, Vector3, Vector4};
+use crate::quality::MAX_QUALITY;
 
 use ash::vk;
+use std::f32::consts::TAU;
+
+/// Number of frames shown in the frame graph.
+pub const FRAME_GRAPH_LEN: usize = 128;
 
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
@@ -11,15 +16,293 @@ pub struct UniformBufferObject {
     pub resolution: Vector2,
     pub texture_weight: f32,
     pub time: f32,
+    /// The view matrices of the left and the right eye selected with `gl_ViewIndex`
+    /// if the shaders are compiled with `MULTIVIEW`, else the first one is used.
+    ///
+    /// Both are equal to `view` unless rendering in stereo.
+    pub eye_views: [Matrix4; 2],
+    /// Shadertoy style mouse in pixels from the bottom left, only set for entered art.
+    ///
+    /// `xy` is the cursor position while the left button is held, `zw` where it was pressed.
+    pub mouse: Vector4,
+    pub fog_color: Vector3,
+    pub fog_density: f32,
+    pub fog_height_falloff: f32,
+    pub sky_rotation: f32,
+    pub day_weight: f32,
+    pub ambient_light: f32,
+    pub sun_direction: Vector3,
+    /// Opacity of the black the frame is faded to, see `VkApp::set_fade`.
+    pub fade: f32,
+    /// Frame times in milliseconds, four per element, see [`FrameGraph`].
+    pub frame_times: [Vector4; FRAME_GRAPH_LEN / 4],
+    pub gpu_frame_times: [Vector4; FRAME_GRAPH_LEN / 4],
+    /// How much the color lookup table changes the frame, see `VkApp::set_grade_strength`.
+    pub grade_strength: f32,
+    /// Width over height of the image the environment is textured with, so that it is not
+    /// stretched over its generated texture coordinates. One if the model has its own.
+    pub texture_aspect: f32,
+    /// Depth of the height map of the environment, see `VkApp::set_normal_map`.
+    pub parallax_scale: f32,
+    /// How much polished floors reflect the gallery, see `VkApp::set_reflections`.
+    pub reflection_strength: f32,
+    /// One if the environment is fogged with the froxels, see `VkApp::set_volumetric_fog`.
+    pub volumetric_fog: f32,
+    /// How much what is out of focus is blurred, see `VkApp::set_depth_of_field`.
+    pub dof_aperture: f32,
+    /// Where the distance of the focus is taken from, from 0 to 1 from the top left.
+    pub focus_point: Vector2,
+    /// The view and the projection of the last frame the motion blur reprojects with.
+    pub previous_view_proj: Matrix4,
+    /// How much of the last frame the motion blur smears over, see `VkApp::set_motion_blur`.
+    pub shutter: f32,
+    /// The intensities of the stylistic effects, see `VkApp::set_post_effects`.
+    pub chromatic_aberration: f32,
+    pub vignette: f32,
+    pub film_grain: f32,
+    /// How much smaller the gallery is drawn than the frame it is scaled up to,
+    /// see `VkApp::set_dynamic_resolution`.
+    pub render_scale: f32,
+    /// The [`crate::gizmo::GizmoMode`] of the handles of the edited art as a number.
+    pub gizmo_mode: f32,
+    /// The axis of the highlighted handle, -1 if none is, see [`crate::gizmo::Gizmo::active_axis`].
+    pub gizmo_axis: f32,
+    pub _padding: f32,
+    /// Where the handles are drawn, see [`crate::gizmo::Gizmo::frame`].
+    pub gizmo: Matrix4,
 }
 
 impl UniformBufferObject {
-    pub fn get_descriptor_set_layout_binding<'a>() -> vk::DescriptorSetLayoutBinding<'a> {
+    /// The binding is dynamic, every view of a frame has its own uniform buffer object
+    /// at an offset given when binding the descriptor set.
+    pub fn get_descriptor_set_layout_binding<'a>(
+        stage_flags: vk::ShaderStageFlags,
+    ) -> vk::DescriptorSetLayoutBinding<'a> {
         vk::DescriptorSetLayoutBinding::default()
             .binding(0)
-            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
+            .descriptor_count(1)
+            .stage_flags(stage_flags)
+    }
+}
+
+/// The times of the last frames shown in a graph over the scene, the oldest first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameGraph {
+    /// Time between the frames in milliseconds.
+    pub frame_times: [f32; FRAME_GRAPH_LEN],
+    /// Time the GPU took to draw the frames in milliseconds, zero if unknown.
+    pub gpu_frame_times: [f32; FRAME_GRAPH_LEN],
+}
+
+impl Default for FrameGraph {
+    fn default() -> Self {
+        Self {
+            frame_times: [0.; FRAME_GRAPH_LEN],
+            gpu_frame_times: [0.; FRAME_GRAPH_LEN],
+        }
+    }
+}
+
+impl FrameGraph {
+    /// Appends the times of a frame and drops those of the oldest frame.
+    pub fn push(&mut self, frame_time: f32, gpu_frame_time: Option<f32>) {
+        self.frame_times.rotate_left(1);
+        self.frame_times[FRAME_GRAPH_LEN - 1] = frame_time;
+        self.gpu_frame_times.rotate_left(1);
+        self.gpu_frame_times[FRAME_GRAPH_LEN - 1] = gpu_frame_time.unwrap_or_default();
+    }
+
+    /// The times packed into vectors as the uniform buffer object holds them.
+    pub fn packed(times: &[f32; FRAME_GRAPH_LEN]) -> [Vector4; FRAME_GRAPH_LEN / 4] {
+        std::array::from_fn(|i| Vector4::from([
+            times[4 * i],
+            times[4 * i + 1],
+            times[4 * i + 2],
+            times[4 * i + 3],
+        ]))
+    }
+}
+
+/// The uniforms of a single art piece, every art has its own at a dynamic offset.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct ArtUniforms {
+    pub model: Matrix4,
+    /// Free parameters of the art, see `ShaderArt::params`.
+    pub params: Vector4,
+    /// The local time of the art in seconds, see [`ArtTime`].
+    pub time: f32,
+    /// Width over height of the image at binding 1.
+    pub image_aspect: f32,
+    /// The [`Framing`] of the image at binding 1 as an integer.
+    pub framing: u32,
+    /// Index of the first joint matrix of skinned art at binding 10, see `JointBuffer`.
+    pub joint_offset: u32,
+    /// How far the vertices move towards each morph target, see `Geometry::with_morph_targets`.
+    pub morph_weights: Vector4,
+    /// The quality level from 0 to `MAX_QUALITY` expensive art can lower its iteration counts
+    /// with, see `VkApp::set_quality`.
+    pub quality: u32,
+    /// How close the camera is within the nearby distance of [`Proximity`], from 0 at the
+    /// distance to 1 at the art.
+    pub nearby: f32,
+    /// Seconds since the camera came within the nearby distance, 0 while it is farther away.
+    pub nearby_time: f32,
+}
+
+/// How 2D art shows the image it samples at binding 1 if its aspect ratio differs from the quad.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Framing {
+    /// The image is stretched over the whole quad.
+    #[default]
+    Stretch = 0,
+    /// The quad shrinks to the aspect ratio of the image.
+    Fit = 1,
+    /// The image keeps its aspect ratio inside the quad with bars at two sides.
+    Letterbox = 2,
+}
+
+/// How the local time of an art piece advances, so that not all art animates in lockstep.
+///
+/// The local time starts at zero when the shaders of the art are loaded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtTime {
+    /// Seconds added to the local time.
+    pub offset: f32,
+    /// How fast the local time advances relative to the global time.
+    pub speed: f32,
+    /// Restarts the local time when the camera comes closer than this distance to the art.
+    pub restart_distance: Option<f32>,
+    /// Seconds of local time after which the animation repeats, the length of exported loops.
+    pub loop_period: Option<f32>,
+}
+
+impl Default for ArtTime {
+    fn default() -> Self {
+        Self {
+            offset: 0.,
+            speed: 1.,
+            restart_distance: None,
+            loop_period: None,
+        }
+    }
+}
+
+/// How an art piece reacts to the distance of the camera, so that art far away costs less
+/// and art can wake up when a visitor comes close.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Proximity {
+    /// Within this distance the viewer is nearby, see [`ArtUniforms::nearby`].
+    pub nearby_distance: f32,
+    /// Beyond this distance the art is drawn at the lowest quality level.
+    pub low_quality_distance: Option<f32>,
+    /// Beyond this distance the art is not drawn at all. It is drawn again a bit closer,
+    /// so that it does not flicker when the camera stops at the distance.
+    pub sleep_distance: Option<f32>,
+}
+
+impl Default for Proximity {
+    fn default() -> Self {
+        Self {
+            nearby_distance: 3.,
+            low_quality_distance: None,
+            sleep_distance: None,
+        }
+    }
+}
+
+impl ArtUniforms {
+    pub fn get_descriptor_set_layout_binding<'a>(
+        stage_flags: vk::ShaderStageFlags,
+    ) -> vk::DescriptorSetLayoutBinding<'a> {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(3)
+            .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC)
             .descriptor_count(1)
-            .stage_flags(vk::ShaderStageFlags::VERTEX)
+            .stage_flags(stage_flags)
+    }
+}
+
+impl Default for ArtUniforms {
+    fn default() -> Self {
+        Self {
+            model: Matrix4::unit(),
+            params: Vector4::default(),
+            time: 0.,
+            image_aspect: 1.,
+            framing: Framing::Stretch as u32,
+            joint_offset: 0,
+            morph_weights: Vector4::default(),
+            quality: MAX_QUALITY,
+            nearby: 0.,
+            nearby_time: 0.,
+        }
+    }
+}
+
+/// Exponential height fog, the density falls off exponentially with the height above zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub color: Vector3,
+    /// Density at height zero, zero disables the fog.
+    pub density: f32,
+    /// How fast the density decreases with the height.
+    pub height_falloff: f32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            color: Vector3::from([0.6, 0.65, 0.7]),
+            density: 0.1,
+            height_falloff: 0.25,
+        }
+    }
+}
+
+/// The sky and the light at a time of day, see [`Sky::at_time_of_day`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sky {
+    /// Rotation of the skybox around the y-axis in radians.
+    pub rotation: f32,
+    /// Weight of the day skybox, the night skybox has a weight of `1 - day_weight`.
+    pub day_weight: f32,
+    /// Brightness of the environment from 0 to 1.
+    pub ambient_light: f32,
+    /// Direction towards the sun in world space.
+    pub sun_direction: Vector3,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Self::at_time_of_day(12.)
+    }
+}
+
+impl Sky {
+    /// Tilt of the path of the sun towards the south.
+    const SUN_TILT: f32 = 0.4;
+
+    /// The sky at a time of day in hours, the sun rises at 6 and sets at 18.
+    pub fn at_time_of_day(hours: f32) -> Self {
+        let angle = (hours - 6.) / 24. * TAU;
+        let sun_direction = Vector3::from([
+            angle.cos() * Self::SUN_TILT.cos(),
+            angle.sin() * Self::SUN_TILT.cos(),
+            Self::SUN_TILT.sin(),
+        ]);
+        // blend smoothly during dawn and dusk
+        let t = ((sun_direction.y() + 0.1) / 0.3).clamp(0., 1.);
+        let day_weight = t * t * (3. - 2. * t);
+        Self {
+            // the clouds drift with the sun
+            rotation: angle,
+            day_weight,
+            ambient_light: 0.25 + 0.75 * day_weight,
+            sun_direction,
+        }
     }
 }
 
@@ -30,9 +313,9 @@ pub struct PushConstants {
 }
 
 impl PushConstants {
-    pub fn get_push_constant_range() -> vk::PushConstantRange {
+    pub fn get_push_constant_range(stage_flags: vk::ShaderStageFlags) -> vk::PushConstantRange {
         vk::PushConstantRange {
-            stage_flags: vk::ShaderStageFlags::VERTEX,
+            stage_flags,
             offset: 0,
             size: size_of::<Self>() as _,
         }
@@ -1,3 +1,5 @@
+use crate::math::{Deg, Matrix4};
+
 use ash::khr::surface;
 use ash::vk;
 
@@ -39,16 +41,42 @@ impl SwapchainSupportDetails {
     pub fn get_ideal_swapchain_properties(
         &self,
         preferred_dimensions: [u32; 2],
+        pre_rotate: bool,
     ) -> SwapchainProperties {
         let format = Self::choose_swapchain_surface_format(&self.formats);
         let present_mode = Self::choose_swapchain_surface_present_mode(&self.present_modes);
-        let extent = Self::choose_swapchain_extent(self.capabilities, preferred_dimensions);
-        log::debug!("Swapchain format: {format:?}, mode: {present_mode:?}, extent: {extent:?}");
+        let transform = Self::choose_swapchain_transform(self.capabilities, pre_rotate);
+        let mut extent = Self::choose_swapchain_extent(self.capabilities, preferred_dimensions);
+        // the images are in the natural orientation of the display when pre-rotating
+        if transform.intersects(SwapchainProperties::SIDEWAYS) {
+            std::mem::swap(&mut extent.width, &mut extent.height);
+        }
+        log::debug!(
+            "Swapchain format: {format:?}, mode: {present_mode:?}, extent: {extent:?}, \
+            transform: {transform:?}"
+        );
         SwapchainProperties {
             format,
             present_mode,
             extent,
+            transform,
+            surface_transform: self.capabilities.current_transform,
+            readable: self.capabilities.supported_usage_flags
+                .contains(vk::ImageUsageFlags::TRANSFER_SRC),
+        }
+    }
+
+    /// The number of swapchain images to request, `preferred` clamped to the limits of the
+    /// surface or one more than the minimum if there is no preference.
+    pub fn image_count(&self, preferred: Option<u32>) -> u32 {
+        let min = self.capabilities.min_image_count;
+        let max = self.capabilities.max_image_count;
+        let mut image_count = preferred.unwrap_or(min + 1).max(min);
+        // a maximum of 0 means there is no limit
+        if max > 0 && image_count > max {
+            image_count = max;
         }
+        image_count
     }
 
     /// Choose the swapchain surface format.
@@ -87,6 +115,28 @@ impl SwapchainSupportDetails {
         }
     }
 
+    /// Choose the transform of the swapchain images.
+    ///
+    /// Will pre-rotate if the surface is rotated and `pre_rotate` is set, otherwise and
+    /// for mirrored surfaces the compositor rotates the images if possible.
+    fn choose_swapchain_transform(
+        capabilities: vk::SurfaceCapabilitiesKHR,
+        pre_rotate: bool,
+    ) -> vk::SurfaceTransformFlagsKHR {
+        let current = capabilities.current_transform;
+        let rotations = vk::SurfaceTransformFlagsKHR::IDENTITY
+            | vk::SurfaceTransformFlagsKHR::ROTATE_90
+            | vk::SurfaceTransformFlagsKHR::ROTATE_180
+            | vk::SurfaceTransformFlagsKHR::ROTATE_270;
+        if (pre_rotate && rotations.contains(current))
+            || !capabilities.supported_transforms.contains(vk::SurfaceTransformFlagsKHR::IDENTITY)
+        {
+            current
+        } else {
+            vk::SurfaceTransformFlagsKHR::IDENTITY
+        }
+    }
+
     /// Choose the swapchain extent.
     ///
     /// If a current extent is defined it will be returned.
@@ -113,4 +163,44 @@ pub struct SwapchainProperties {
     pub format: vk::SurfaceFormatKHR,
     pub present_mode: vk::PresentModeKHR,
     pub extent: vk::Extent2D,
+    /// The transform of the images relative to the natural orientation of the surface.
+    ///
+    /// Rotations are applied to the projection so the compositor does not have to.
+    pub transform: vk::SurfaceTransformFlagsKHR,
+    /// The current transform of the surface, `transform` is only the same if pre-rotating.
+    pub surface_transform: vk::SurfaceTransformFlagsKHR,
+    /// Whether the images can be copied from, e.g. to record them.
+    pub readable: bool,
+}
+
+impl SwapchainProperties {
+    const SIDEWAYS: vk::SurfaceTransformFlagsKHR = vk::SurfaceTransformFlagsKHR::from_raw(
+        vk::SurfaceTransformFlagsKHR::ROTATE_90.as_raw()
+            | vk::SurfaceTransformFlagsKHR::ROTATE_270.as_raw()
+    );
+
+    /// Whether the images are rotated by 90 or 270 degrees relative to the window.
+    pub fn is_sideways(&self) -> bool {
+        self.transform.intersects(Self::SIDEWAYS)
+    }
+
+    /// The rotation around the view axis applied after the projection.
+    pub fn pre_rotation(&self) -> Matrix4 {
+        let angle = match self.transform {
+            vk::SurfaceTransformFlagsKHR::ROTATE_90 => 90.,
+            vk::SurfaceTransformFlagsKHR::ROTATE_180 => 180.,
+            vk::SurfaceTransformFlagsKHR::ROTATE_270 => 270.,
+            _ => return Matrix4::unit(),
+        };
+        Matrix4::from_angle_z(Deg(angle))
+    }
+
+    /// The aspect ratio of `extent` as seen in the window.
+    pub fn aspect(&self, extent: vk::Extent2D) -> f32 {
+        if self.is_sideways() {
+            extent.height as f32 / extent.width as f32
+        } else {
+            extent.width as f32 / extent.height as f32
+        }
+    }
 }
@@ -5,6 +5,7 @@ pub struct Texture {
     pub image: vk::Image,
     pub memory: vk::DeviceMemory,
     pub view: vk::ImageView,
+    /// Owned by the sampler cache of the context and not destroyed with the texture.
     pub sampler: Option<vk::Sampler>,
 }
 
@@ -25,9 +26,6 @@ impl Texture {
 
     pub fn destroy(&mut self, device: &Device) {
         unsafe {
-            if let Some(sampler) = self.sampler.take() {
-                device.destroy_sampler(sampler, None);
-            }
             device.destroy_image_view(self.view, None);
             device.destroy_image(self.image, None);
             device.free_memory(self.memory, None);
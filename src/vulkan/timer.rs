@@ -0,0 +1,162 @@
+use super::{cmd, context::VkContext, stereo::EYE_COUNT};
+
+use anyhow::anyhow;
+use ash::{vk, Device};
+
+/// A timestamp written inside a multiview render pass takes one query per view,
+/// so every timestamp has room for the views of both eyes.
+const QUERIES_PER_TIMESTAMP: u32 = EYE_COUNT;
+const TIMESTAMP_FRAME_START: u32 = 0;
+const TIMESTAMP_FRAME_END: u32 = 1;
+const TIMESTAMP_FIRST_PIPELINE: u32 = 2;
+
+/// How long the GPU took to draw a frame in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GpuTimes {
+    /// From the start to the end of the command buffer of the frame.
+    pub frame: f32,
+    /// The time of every pipeline in the first view, `None` if it did not draw.
+    pub pipelines: Vec<Option<f32>>,
+}
+
+/// Measures the GPU time of frames and of the pipelines drawn in them with timestamp queries.
+///
+/// Every frame in flight has its own query pool which is reset at the start of its command
+/// buffers and read once the frame is done.
+pub struct GpuTimer {
+    query_pools: Vec<vk::QueryPool>,
+    pipeline_count: usize,
+    /// Nanoseconds per timestamp tick.
+    timestamp_period: f32,
+}
+
+impl GpuTimer {
+    /// Creates the query pools and resets them with a command buffer from `command_pool`.
+    pub fn new(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        frames_in_flight: usize,
+        pipeline_count: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let limits = vk_context.physical_device_properties().limits;
+        if limits.timestamp_compute_and_graphics != vk::TRUE {
+            return Err(anyhow!("Timestamp queries are not supported"));
+        }
+        let timestamp_count = TIMESTAMP_FIRST_PIPELINE + 2 * pipeline_count as u32;
+        let create_info = vk::QueryPoolCreateInfo::default()
+            .query_type(vk::QueryType::TIMESTAMP)
+            .query_count(timestamp_count * QUERIES_PER_TIMESTAMP);
+        let device = vk_context.device();
+        let query_pools = (0..frames_in_flight)
+            .map(|_| unsafe { device.create_query_pool(&create_info, None) })
+            .collect::<Result<Vec<_>, _>>()?;
+        let timer = Self {
+            query_pools,
+            pipeline_count,
+            timestamp_period: limits.timestamp_period,
+        };
+        // queries have to be reset before they can be read, even if they are not written
+        cmd::execute_one_time_commands(device, command_pool, queue, |buffer| {
+            for &pool in timer.query_pools.iter() {
+                unsafe { device.cmd_reset_query_pool(buffer, pool, 0, timer.query_count()) };
+            }
+        });
+        Ok(timer)
+    }
+
+    fn query_count(&self) -> u32 {
+        (TIMESTAMP_FIRST_PIPELINE + 2 * self.pipeline_count as u32) * QUERIES_PER_TIMESTAMP
+    }
+
+    /// Resets the queries of `frame` and writes the start of the frame.
+    ///
+    /// Must be recorded outside of a render pass.
+    pub unsafe fn begin_frame(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize) {
+        let pool = self.query_pools[frame];
+        unsafe {
+            device.cmd_reset_query_pool(buffer, pool, 0, self.query_count());
+            let stage = vk::PipelineStageFlags::TOP_OF_PIPE;
+            self.write(device, buffer, frame, TIMESTAMP_FRAME_START, stage);
+        }
+    }
+
+    pub unsafe fn end_frame(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize) {
+        let stage = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+        unsafe { self.write(device, buffer, frame, TIMESTAMP_FRAME_END, stage) };
+    }
+
+    /// Writes the start of the pipeline at `index` before it draws.
+    pub unsafe fn begin_pipeline(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        index: usize,
+    ) {
+        let timestamp = TIMESTAMP_FIRST_PIPELINE + 2 * index as u32;
+        let stage = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+        unsafe { self.write(device, buffer, frame, timestamp, stage) };
+    }
+
+    /// Writes the end of the pipeline at `index` after it drew.
+    pub unsafe fn end_pipeline(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        index: usize,
+    ) {
+        let timestamp = TIMESTAMP_FIRST_PIPELINE + 2 * index as u32 + 1;
+        let stage = vk::PipelineStageFlags::BOTTOM_OF_PIPE;
+        unsafe { self.write(device, buffer, frame, timestamp, stage) };
+    }
+
+    unsafe fn write(
+        &self,
+        device: &Device,
+        buffer: vk::CommandBuffer,
+        frame: usize,
+        timestamp: u32,
+        stage: vk::PipelineStageFlags,
+    ) {
+        let query = timestamp * QUERIES_PER_TIMESTAMP;
+        unsafe { device.cmd_write_timestamp(buffer, stage, self.query_pools[frame], query) };
+    }
+
+    /// Reads the times of `frame`, the frame must be done drawing.
+    ///
+    /// Returns `None` if the frame was not drawn with timestamps yet.
+    pub fn read(&self, device: &Device, frame: usize) -> Option<GpuTimes> {
+        let read = |timestamp: u32| {
+            let mut data = [0u64];
+            let query = timestamp * QUERIES_PER_TIMESTAMP;
+            unsafe {
+                device.get_query_pool_results(
+                    self.query_pools[frame],
+                    query,
+                    &mut data,
+                    vk::QueryResultFlags::TYPE_64,
+                )
+            }.ok().map(|_| data[0])
+        };
+        let millis = |start: u64, end: u64| {
+            end.wrapping_sub(start) as f32 * self.timestamp_period / 1_000_000.
+        };
+        let frame = millis(read(TIMESTAMP_FRAME_START)?, read(TIMESTAMP_FRAME_END)?);
+        let pipelines = (0..self.pipeline_count as u32)
+            .map(|index| {
+                let start = read(TIMESTAMP_FIRST_PIPELINE + 2 * index)?;
+                let end = read(TIMESTAMP_FIRST_PIPELINE + 2 * index + 1)?;
+                Some(millis(start, end))
+            })
+            .collect();
+        Some(GpuTimes { frame, pipelines })
+    }
+
+    pub fn destroy(&mut self, device: &Device) {
+        for pool in self.query_pools.drain(..) {
+            unsafe { device.destroy_query_pool(pool, None) };
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use crate::gltf::SkinnedVertex;
+use crate::lightmap::LightmapLayout;
+use crate::math::Vector3;
+
 use ash::vk;
 use std::mem::offset_of;
 
@@ -35,17 +39,78 @@ impl Vertex for VertexSimple {
     }
 }
 
+/// A vertex of the environment with the directions its texture coordinates grow in,
+/// so that the fragment shader can read a normal map in tangent space.
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
-pub struct VertexColorCoords {
+pub struct VertexTangents {
     pos: [f32; 3],
     color: [f32; 3],
     coords: [f32; 2],
+    tangent: [f32; 3],
+    bitangent: [f32; 3],
+    lightmap_coords: [f32; 2],
+}
+
+impl VertexTangents {
+    /// Gives each triangle in `indices` its own corners with the coordinates of the triangle
+    /// in a lightmap laid out by [`LightmapLayout`], returns the new vertices and indices.
+    pub fn with_lightmap_coords(vertices: &[Self], indices: &[u32]) -> (Vec<Self>, Vec<u32>) {
+        let positions = vertices.iter().map(|vertex| vertex.pos).collect::<Vec<_>>();
+        let layout = LightmapLayout::new(indices.len() / 3);
+        let vertices = indices.iter()
+            .zip(layout.coords(&positions, indices))
+            .map(|(&index, lightmap_coords)| Self { lightmap_coords, ..vertices[index as usize] })
+            .collect::<Vec<_>>();
+        let indices = (0..vertices.len() as u32).collect();
+        (vertices, indices)
+    }
+
+
+    /// Sets the tangents and bitangents of `vertices` to the sum of the ones of the triangles
+    /// in `indices` they are part of, weighted by the area of the triangles.
+    ///
+    /// Triangles whose texture coordinates do not span an area add nothing,
+    /// so vertices only on such triangles keep zero vectors.
+    pub fn compute_tangents(vertices: &mut [Self], indices: &[u32]) {
+        for vertex in vertices.iter_mut() {
+            vertex.tangent = [0.; 3];
+            vertex.bitangent = [0.; 3];
+        }
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize]);
+            let edge_1 = Vector3::from(b.pos) - Vector3::from(a.pos);
+            let edge_2 = Vector3::from(c.pos) - Vector3::from(a.pos);
+            let [du_1, dv_1] = [b.coords[0] - a.coords[0], b.coords[1] - a.coords[1]];
+            let [du_2, dv_2] = [c.coords[0] - a.coords[0], c.coords[1] - a.coords[1]];
+            let det = du_1 * dv_2 - du_2 * dv_1;
+            if det.abs() < f32::EPSILON {
+                continue;
+            }
+            // the edges divided by the determinant are not normalized to weight by area
+            let area = edge_1.cross(edge_2).magnitude();
+            let tangent = (edge_1 * dv_2 - edge_2 * dv_1) * (area / det);
+            let bitangent = (edge_2 * du_1 - edge_1 * du_2) * (area / det);
+            for &index in triangle {
+                let vertex = &mut vertices[index as usize];
+                vertex.tangent = (Vector3::from(vertex.tangent) + tangent).into();
+                vertex.bitangent = (Vector3::from(vertex.bitangent) + bitangent).into();
+            }
+        }
+    }
 }
 
-impl Vertex for VertexColorCoords {
+impl Vertex for VertexTangents {
+    /// A vertex without tangents, see [`Self::compute_tangents`].
     fn new(pos: [f32; 3], color: [f32; 3], coords: [f32; 2]) -> Self {
-        Self { pos, color, coords }
+        Self {
+            pos,
+            color,
+            coords,
+            tangent: [0.; 3],
+            bitangent: [0.; 3],
+            lightmap_coords: [0.; 2],
+        }
     }
 
     fn get_binding_description() -> vk::VertexInputBindingDescription {
@@ -71,6 +136,101 @@ impl Vertex for VertexColorCoords {
             .location(2)
             .format(vk::Format::R32G32_SFLOAT)
             .offset(offset_of!(Self, coords) as _);
-        vec![position_desc, color_desc, coords_desc]
+        let tangent_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(3)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Self, tangent) as _);
+        let bitangent_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(4)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Self, bitangent) as _);
+        let lightmap_coords_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(5)
+            .format(vk::Format::R32G32_SFLOAT)
+            .offset(offset_of!(Self, lightmap_coords) as _);
+        vec![
+            position_desc,
+            color_desc,
+            coords_desc,
+            tangent_desc,
+            bitangent_desc,
+            lightmap_coords_desc,
+        ]
+    }
+}
+
+/// A vertex moved by up to four joints, see [`crate::gltf::SkinnedVertex`].
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct VertexSkinned {
+    pos: [f32; 3],
+    joints: [u32; 4],
+    weights: [f32; 4],
+}
+
+impl From<SkinnedVertex> for VertexSkinned {
+    fn from(vertex: SkinnedVertex) -> Self {
+        Self { pos: vertex.position, joints: vertex.joints, weights: vertex.weights }
+    }
+}
+
+impl Vertex for VertexSkinned {
+    /// A vertex that only moves with the first joint.
+    fn new(pos: [f32; 3], _: [f32; 3], _: [f32; 2]) -> Self {
+        Self { pos, joints: [0; 4], weights: [1., 0., 0., 0.] }
+    }
+
+    fn get_binding_description() -> vk::VertexInputBindingDescription {
+        vk::VertexInputBindingDescription::default()
+            .binding(0)
+            .stride(size_of::<Self>() as _)
+            .input_rate(vk::VertexInputRate::VERTEX)
+    }
+
+    fn get_attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        let position_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(0)
+            .format(vk::Format::R32G32B32_SFLOAT)
+            .offset(offset_of!(Self, pos) as _);
+        let joints_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(1)
+            .format(vk::Format::R32G32B32A32_UINT)
+            .offset(offset_of!(Self, joints) as _);
+        let weights_desc = vk::VertexInputAttributeDescription::default()
+            .binding(0)
+            .location(2)
+            .format(vk::Format::R32G32B32A32_SFLOAT)
+            .offset(offset_of!(Self, weights) as _);
+        vec![position_desc, joints_desc, weights_desc]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents() {
+        // a quad in the xy plane with u along -y and v along x, and one without coordinates
+        let mut vertices = [
+            ([0., 0., 0.], [0., 0.]),
+            ([0., -1., 0.], [1., 0.]),
+            ([1., -1., 0.], [1., 1.]),
+            ([1., 0., 0.], [0., 1.]),
+            ([0., 0., 1.], [0., 0.]),
+        ].map(|(pos, coords)| VertexTangents::new(pos, [1.; 3], coords));
+        VertexTangents::compute_tangents(&mut vertices, &[0, 1, 2, 2, 3, 0, 0, 3, 4]);
+        for vertex in &vertices[..4] {
+            let tangent = Vector3::from(vertex.tangent).normalize();
+            let bitangent = Vector3::from(vertex.bitangent).normalize();
+            assert!((tangent - Vector3::from([0., -1., 0.])).magnitude() < 1e-6);
+            assert!((bitangent - Vector3::from([1., 0., 0.])).magnitude() < 1e-6);
+        }
+        assert_eq!(vertices[4].tangent, [0.; 3]);
     }
 }
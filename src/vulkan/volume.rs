@@ -0,0 +1,118 @@
+use anyhow::Context;
+use image::ImageReader;
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// The texels of a 3D texture art can sample at binding 4, e.g. for volume rendering.
+#[derive(Clone)]
+pub enum Volume {
+    /// A directory with one image per slice along z, ordered by file name.
+    /// The first slice is at z = 0 and every slice must have the same size.
+    /// Like other textures the slices are flipped so that y points up.
+    Slices(PathBuf),
+    /// Computed by calling `fill` with the normalized coordinates of the center of every texel.
+    Procedural {
+        extent: [u32; 3],
+        fill: fn([f32; 3]) -> [u8; 4],
+    },
+}
+
+impl fmt::Debug for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Slices(path) => f.debug_tuple("Slices").field(path).finish(),
+            Self::Procedural { extent, .. } => {
+                f.debug_struct("Procedural").field("extent", extent).finish_non_exhaustive()
+            }
+        }
+    }
+}
+
+impl Volume {
+    /// A single white texel, sampled by art without a volume of its own.
+    pub const EMPTY: Self = Self::Procedural {
+        extent: [1, 1, 1],
+        fill: |_| [255; 4],
+    };
+
+    /// Loads or computes the RGBA texels with x changing fastest, then y, then z.
+    ///
+    /// Returns the extent and the texels.
+    pub fn texels(&self) -> Result<([u32; 3], Vec<u8>), anyhow::Error> {
+        match self {
+            Self::Slices(dir) => {
+                let mut paths = fs::read_dir(dir)
+                    .with_context(|| format!("Failed to read slices in {}", dir.display()))?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                paths.retain(|path| path.is_file());
+                paths.sort();
+                anyhow::ensure!(!paths.is_empty(), "No slices in {}", dir.display());
+
+                let mut size = None;
+                let mut texels = Vec::new();
+                for path in paths.iter() {
+                    let slice = ImageReader::open(path)
+                        .with_context(|| format!("Failed to open slice {}", path.display()))?
+                        .decode()
+                        .with_context(|| format!("Failed to decode slice {}", path.display()))?
+                        .flipv()
+                        .to_rgba8();
+                    let dims = slice.dimensions();
+                    if *size.get_or_insert(dims) != dims {
+                        anyhow::bail!(
+                            "Slice {} is {}x{} but the first one is {}x{}",
+                            path.display(),
+                            dims.0,
+                            dims.1,
+                            size.unwrap().0,
+                            size.unwrap().1,
+                        );
+                    }
+                    texels.extend_from_slice(&slice);
+                }
+                let (width, height) = size.unwrap();
+                Ok(([width, height, paths.len() as u32], texels))
+            }
+            Self::Procedural { extent, fill } => {
+                let [width, height, depth] = *extent;
+                let mut texels = Vec::with_capacity((width * height * depth * 4) as usize);
+                for z in 0..depth {
+                    for y in 0..height {
+                        for x in 0..width {
+                            texels.extend(fill([
+                                (x as f32 + 0.5) / width as f32,
+                                (y as f32 + 0.5) / height as f32,
+                                (z as f32 + 0.5) / depth as f32,
+                            ]));
+                        }
+                    }
+                }
+                Ok((*extent, texels))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn procedural_texel_order() {
+        let volume = Volume::Procedural {
+            extent: [2, 1, 2],
+            fill: |[x, y, z]| [(x * 4.) as u8, (y * 4.) as u8, (z * 4.) as u8, 255],
+        };
+        let (extent, texels) = volume.texels().unwrap();
+        assert_eq!(extent, [2, 1, 2]);
+        assert_eq!(texels, [
+            1, 2, 1, 255,
+            3, 2, 1, 255,
+            1, 2, 3, 255,
+            3, 2, 3, 255,
+        ]);
+    }
+}
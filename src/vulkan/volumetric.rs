@@ -0,0 +1,365 @@
+use crate::math::{Matrix4, Vector3};
+use super::{
+    buffer,
+    clusters::{CLUSTERS_BINDING, LIGHTS_BINDING},
+    context::VkContext,
+    descriptor::DescriptorAllocator,
+    resources::ResourceManager,
+    sampler::SamplerConfig,
+    shader::Shader,
+    shadows::{CASCADES_BINDING, SHADOW_MAP_BINDING},
+};
+
+use anyhow::Context;
+use ash::{vk, Device};
+use std::ffi::c_void;
+
+/// The binding of the fog volume in the descriptor sets of the environment.
+pub const VOLUME_BINDING: u32 = 17;
+/// The tiles across and down the view and the slices along its depth, `GRID` in the shaders.
+///
+/// The slices grow from 0.5 to 100 units away from the camera, the fog beyond stays the same.
+const FROXEL_GRID: [u32; 3] = [160, 90, 64];
+const VOLUME_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+/// The workgroup size of `volumetric_fog.comp` across and down the view.
+const GROUP_SIZE: u32 = 8;
+
+/// The fog of the view the froxels are lit for.
+#[derive(Clone, Copy, Debug)]
+pub struct FogView {
+    pub view: Matrix4,
+    pub proj: Matrix4,
+    /// The color of the fog lit by the ambient light.
+    pub color: Vector3,
+    pub density: f32,
+    pub height_falloff: f32,
+    pub sun_direction: Vector3,
+    /// How bright the sun is from 0 at night to 1 at day.
+    pub sun_light: f32,
+}
+
+/// The fog as it is read by `volumetric_fog.comp`.
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct FogData {
+    view: Matrix4,
+    proj: Matrix4,
+    color: [f32; 3],
+    density: f32,
+    sun_direction: [f32; 3],
+    height_falloff: f32,
+    sun_light: f32,
+    _padding: [f32; 3],
+}
+
+/// Fog lit by the sun and the point lights, so that the sun falls through the gallery
+/// in visible shafts and the lights glow in the air around them.
+///
+/// The view is split into froxels, tiles across the screen and slices growing exponentially
+/// with the distance. A compute shader walks along the slices of each tile, lights the fog
+/// in every froxel with the shadowed sun and the point lights and writes the light scattered
+/// towards the camera and the transmittance so far into a 3D image. The environment then
+/// looks up its froxel instead of computing the fog itself.
+///
+/// Only a single view is fogged this way and only the environment, the art keeps its fog.
+pub struct VolumetricFog {
+    image: vk::Image,
+    memory: vk::DeviceMemory,
+    view: vk::ImageView,
+    sampler: vk::Sampler,
+    pipeline: vk::Pipeline,
+    layout: vk::PipelineLayout,
+    descriptor_sets: Vec<vk::DescriptorSet>,
+    /// The fog of every frame in flight, one after another.
+    buffer: vk::Buffer,
+    buffer_memory: vk::DeviceMemory,
+    ptr: *mut c_void,
+    /// Size of the fog of a frame aligned to the storage buffer offset alignment.
+    frame_size: usize,
+}
+
+impl VolumetricFog {
+    /// The shader is the compute shader lighting the froxels, it is cleaned up.
+    ///
+    /// The descriptor sets still have to be pointed at the lights and the shadows with
+    /// [`Self::descriptor_sets`].
+    pub fn new(
+        vk_context: &VkContext,
+        command_pool: vk::CommandPool,
+        queue: vk::Queue,
+        descriptor_allocator: &mut DescriptorAllocator,
+        shader: Shader,
+        frames_in_flight: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let device = vk_context.device();
+        let binding = |binding, descriptor_type| vk::DescriptorSetLayoutBinding::default()
+            .binding(binding)
+            .descriptor_type(descriptor_type)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::COMPUTE);
+        // the lights and the shadows are at the same bindings as for the environment,
+        // the clusters are unused but written with the lights
+        let descriptor_set_layout = descriptor_allocator.create_layout(device, &[
+            binding(0, vk::DescriptorType::STORAGE_IMAGE),
+            binding(1, vk::DescriptorType::STORAGE_BUFFER),
+            binding(LIGHTS_BINDING, vk::DescriptorType::STORAGE_BUFFER),
+            binding(CLUSTERS_BINDING, vk::DescriptorType::STORAGE_BUFFER),
+            binding(CASCADES_BINDING, vk::DescriptorType::STORAGE_BUFFER),
+            binding(SHADOW_MAP_BINDING, vk::DescriptorType::COMBINED_IMAGE_SAMPLER),
+        ])?;
+        let module = shader.module(device).context("The fog shader is not compiled")?;
+        let (pipeline, layout) = Self::create_pipeline(
+            device,
+            descriptor_set_layout,
+            module,
+            &shader.entry_point(),
+        );
+        shader.cleanup(device);
+        let descriptor_sets = descriptor_allocator
+            .allocate(device, descriptor_set_layout, frames_in_flight)?;
+
+        let alignment = vk_context.physical_device_properties()
+            .limits
+            .min_storage_buffer_offset_alignment as usize;
+        let frame_size = size_of::<FogData>().next_multiple_of(alignment);
+        let (buffer, buffer_memory, _) = buffer::create_buffer(
+            vk_context,
+            (frame_size * frames_in_flight) as _,
+            vk::BufferUsageFlags::STORAGE_BUFFER,
+            vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT,
+        );
+        let ptr = unsafe {
+            device.map_memory(buffer_memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty())
+                .context("Failed to map fog buffer")?
+        };
+        let (image, memory, view) = Self::create_image(vk_context);
+        // the volume stays in the general layout, so that it can be bound before it is written
+        ResourceManager::transition_image_layout(
+            vk_context,
+            command_pool,
+            queue,
+            image,
+            1,
+            VOLUME_FORMAT,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            1,
+        );
+
+        let fog = Self {
+            image,
+            memory,
+            view,
+            sampler: vk_context.sampler(SamplerConfig::CLAMP)?,
+            pipeline,
+            layout,
+            descriptor_sets,
+            buffer,
+            buffer_memory,
+            ptr,
+            frame_size,
+        };
+        for (frame, &set) in fog.descriptor_sets.iter().enumerate() {
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_view(fog.view)
+                .image_layout(vk::ImageLayout::GENERAL)];
+            let buffer_infos = [vk::DescriptorBufferInfo::default()
+                .buffer(fog.buffer)
+                .offset((frame * fog.frame_size) as _)
+                .range(size_of::<FogData>() as _)];
+            let writes = [
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                    .image_info(&image_infos),
+                vk::WriteDescriptorSet::default()
+                    .dst_set(set)
+                    .dst_binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&buffer_infos),
+            ];
+            unsafe { device.update_descriptor_sets(&writes, &[]) };
+        }
+        Ok(fog)
+    }
+
+    /// The descriptor sets of the frames in flight, whose lights and shadows have to be
+    /// written like those of the environment.
+    pub fn descriptor_sets(&self) -> &[vk::DescriptorSet] {
+        &self.descriptor_sets
+    }
+
+    pub fn descriptor_set_layout_binding<'a>() -> vk::DescriptorSetLayoutBinding<'a> {
+        vk::DescriptorSetLayoutBinding::default()
+            .binding(VOLUME_BINDING)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(1)
+            .stage_flags(vk::ShaderStageFlags::FRAGMENT)
+    }
+
+    /// Points the descriptor sets at the fog volume.
+    pub fn update_descriptor_sets(&self, device: &Device, sets: &[vk::DescriptorSet]) {
+        for &set in sets.iter() {
+            let image_infos = [vk::DescriptorImageInfo::default()
+                .image_view(self.view)
+                .image_layout(vk::ImageLayout::GENERAL)
+                .sampler(self.sampler)];
+            let write = vk::WriteDescriptorSet::default()
+                .dst_set(set)
+                .dst_binding(VOLUME_BINDING)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .image_info(&image_infos);
+            unsafe { device.update_descriptor_sets(&[write], &[]) };
+        }
+    }
+
+    /// Writes the fog of the frame at index `frame`, the GPU must be done with it.
+    pub fn write(&mut self, frame: usize, fog: FogView) {
+        let data = FogData {
+            view: fog.view,
+            proj: fog.proj,
+            color: [fog.color[0], fog.color[1], fog.color[2]],
+            density: fog.density,
+            sun_direction: [fog.sun_direction[0], fog.sun_direction[1], fog.sun_direction[2]],
+            height_falloff: fog.height_falloff,
+            sun_light: fog.sun_light,
+            _padding: [0.; 3],
+        };
+        unsafe {
+            let dst = self.ptr.cast::<u8>().add(frame * self.frame_size);
+            dst.cast::<FogData>().write_unaligned(data);
+        }
+    }
+
+    /// Records lighting the froxels of the frame at index `frame`.
+    ///
+    /// # Safety
+    ///
+    /// Must be recorded outside of a render pass, the volume is written by a compute shader
+    /// once it is done. The lights and the shadow map must have been written before.
+    pub unsafe fn record(&self, device: &Device, buffer: vk::CommandBuffer, frame: usize) {
+        let [x, y, _] = FROXEL_GRID;
+        // the previous frame may still sample the volume, which is overwritten completely
+        let image_barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(vk::ImageLayout::UNDEFINED)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(self.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .dst_access_mask(vk::AccessFlags::SHADER_WRITE);
+        unsafe {
+            device.cmd_pipeline_barrier(
+                buffer,
+                vk::PipelineStageFlags::FRAGMENT_SHADER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[image_barrier],
+            );
+            device.cmd_bind_pipeline(buffer, vk::PipelineBindPoint::COMPUTE, self.pipeline);
+            device.cmd_bind_descriptor_sets(
+                buffer,
+                vk::PipelineBindPoint::COMPUTE,
+                self.layout,
+                0,
+                &self.descriptor_sets[frame..=frame],
+                &[],
+            );
+            device.cmd_dispatch(buffer, x.div_ceil(GROUP_SIZE), y.div_ceil(GROUP_SIZE), 1);
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Nothing may be in use anymore.
+    pub unsafe fn destroy(&mut self, device: &Device) {
+        unsafe {
+            device.destroy_image_view(self.view, None);
+            device.destroy_image(self.image, None);
+            device.free_memory(self.memory, None);
+            device.destroy_pipeline(self.pipeline, None);
+            device.destroy_pipeline_layout(self.layout, None);
+            device.unmap_memory(self.buffer_memory);
+            device.free_memory(self.buffer_memory, None);
+            device.destroy_buffer(self.buffer, None);
+        }
+    }
+
+    /// The 3D image with a texel per froxel and its view.
+    fn create_image(vk_context: &VkContext) -> (vk::Image, vk::DeviceMemory, vk::ImageView) {
+        let device = vk_context.device();
+        let [width, height, depth] = FROXEL_GRID;
+        let image_info = vk::ImageCreateInfo::default()
+            .image_type(vk::ImageType::TYPE_3D)
+            .extent(vk::Extent3D { width, height, depth })
+            .mip_levels(1)
+            .array_layers(1)
+            .format(VOLUME_FORMAT)
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .initial_layout(vk::ImageLayout::UNDEFINED)
+            .usage(vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .samples(vk::SampleCountFlags::TYPE_1);
+        let image = unsafe { device.create_image(&image_info, None).unwrap() };
+        let mem_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let mem_type_index = vk_context.find_memory_type(
+            mem_requirements,
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+        );
+        let alloc_info = vk::MemoryAllocateInfo::default()
+            .allocation_size(mem_requirements.size)
+            .memory_type_index(mem_type_index);
+        let memory = unsafe {
+            let mem = device.allocate_memory(&alloc_info, None).unwrap();
+            device.bind_image_memory(image, mem, 0).unwrap();
+            mem
+        };
+        let view_info = vk::ImageViewCreateInfo::default()
+            .image(image)
+            .view_type(vk::ImageViewType::TYPE_3D)
+            .format(VOLUME_FORMAT)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        let view = unsafe { device.create_image_view(&view_info, None).unwrap() };
+        (image, memory, view)
+    }
+
+    fn create_pipeline(
+        device: &Device,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        module: vk::ShaderModule,
+        entry_point: &std::ffi::CStr,
+    ) -> (vk::Pipeline, vk::PipelineLayout) {
+        let layout = {
+            let layouts = [descriptor_set_layout];
+            let layout_info = vk::PipelineLayoutCreateInfo::default().set_layouts(&layouts);
+            unsafe { device.create_pipeline_layout(&layout_info, None).unwrap() }
+        };
+        let stage_info = vk::PipelineShaderStageCreateInfo::default()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(module)
+            .name(entry_point);
+        let pipeline_infos = [vk::ComputePipelineCreateInfo::default()
+            .stage(stage_info)
+            .layout(layout)];
+        let pipeline = unsafe {
+            device.create_compute_pipelines(vk::PipelineCache::null(), &pipeline_infos, None)
+                .unwrap()[0]
+        };
+        (pipeline, layout)
+    }
+}
@@ -0,0 +1,494 @@
+use super::{context::VkContext, stereo::{StereoTarget, EYE_COUNT}};
+use crate::math::{Matrix4, Vector3};
+
+use anyhow::{anyhow, bail, Context};
+use ash::{vk, vk::Handle, Device, Instance};
+use openxr as xr;
+use std::ffi::CString;
+
+const VIEW_TYPE: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+
+/// Renders the gallery into a headset with the OpenXR loader found at runtime.
+///
+/// The runtime is set up in three steps because it has a say in how Vulkan is created:
+/// [`OpenXr::new`] before the instance for [`OpenXr::instance_extensions`], then
+/// [`OpenXr::physical_device`] and [`OpenXr::device_extensions`] before the device and
+/// finally [`OpenXr::start_session`] with the created device, which has to be idle
+/// when this is dropped.
+///
+/// Every frame the eyes of a side by side [`StereoTarget`] are scaled into a swapchain per eye,
+/// so both eyes are rendered at the size of the window whatever size the runtime recommends.
+pub struct OpenXr {
+    // the session is declared first to be destroyed before the instance
+    session: Option<Session>,
+    instance: xr::Instance,
+    system: xr::SystemId,
+    views: Vec<xr::ViewConfigurationView>,
+    instance_extensions: Vec<CString>,
+    device_extensions: Vec<CString>,
+    /// Whether the runtime wants frames, between the ready and the stopping state of the session.
+    running: bool,
+    frame: Option<Frame>,
+}
+
+struct Session {
+    device: Device,
+    swapchains: Vec<Swapchain>,
+    space: xr::Space,
+    waiter: xr::FrameWaiter,
+    stream: xr::FrameStream<xr::Vulkan>,
+    handle: xr::Session<xr::Vulkan>,
+    command_pool: vk::CommandPool,
+    /// One command buffer per frame in flight, recorded again every time the frame is drawn.
+    command_buffers: Vec<vk::CommandBuffer>,
+}
+
+struct Swapchain {
+    handle: xr::Swapchain<xr::Vulkan>,
+    images: Vec<vk::Image>,
+    extent: vk::Extent2D,
+}
+
+/// A frame the runtime waited for, it has to be ended with [`OpenXr::end_frame`].
+struct Frame {
+    display_time: xr::Time,
+    should_render: bool,
+    views: Vec<xr::View>,
+    /// The number of swapchains whose images are acquired and have to be released.
+    acquired: usize,
+}
+
+impl OpenXr {
+    /// Loads the OpenXR loader and creates an instance for the headset of the runtime.
+    pub fn new() -> Result<Self, anyhow::Error> {
+        let entry = unsafe { xr::Entry::load() }.context("Failed to load the OpenXR loader")?;
+        let available =
+            entry.enumerate_extensions().context("xrEnumerateInstanceExtensionProperties")?;
+        if !available.khr_vulkan_enable {
+            bail!("The OpenXR runtime does not support XR_KHR_vulkan_enable");
+        }
+        let mut extensions = xr::ExtensionSet::default();
+        extensions.khr_vulkan_enable = true;
+        let application_info = xr::ApplicationInfo {
+            application_name: "shaderpixel",
+            application_version: 1,
+            engine_name: "",
+            engine_version: 1,
+            api_version: xr::Version::new(1, 0, 0),
+        };
+        let instance = entry.create_instance(&application_info, &extensions, &[])
+            .context("xrCreateInstance")?;
+        let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)
+            .context("No headset found")?;
+        let views = instance.enumerate_view_configuration_views(system, VIEW_TYPE)
+            .context("xrEnumerateViewConfigurationViews")?;
+        if views.len() != EYE_COUNT as usize {
+            bail!("The headset has {} views instead of {EYE_COUNT}", views.len());
+        }
+        // the runtime has to be asked for the requirements before a session is created
+        let requirements = instance.graphics_requirements::<xr::Vulkan>(system)
+            .context("xrGetVulkanGraphicsRequirementsKHR")?;
+        let [min, max] =
+            [requirements.min_api_version_supported, requirements.max_api_version_supported];
+        log::debug!(
+            "OpenXR needs Vulkan {}.{} to {}.{}",
+            min.major(),
+            min.minor(),
+            max.major(),
+            max.minor(),
+        );
+        let instance_extensions = instance.vulkan_legacy_instance_extensions(system)
+            .context("xrGetVulkanInstanceExtensionsKHR")?;
+        let device_extensions = instance.vulkan_legacy_device_extensions(system)
+            .context("xrGetVulkanDeviceExtensionsKHR")?;
+        let [width, height] =
+            [views[0].recommended_image_rect_width, views[0].recommended_image_rect_height];
+        log::info!("OpenXR headset with {width}x{height} pixels per eye");
+        Ok(Self {
+            session: None,
+            instance,
+            system,
+            views,
+            instance_extensions: extension_names(&instance_extensions),
+            device_extensions: extension_names(&device_extensions),
+            running: false,
+            frame: None,
+        })
+    }
+
+    /// The Vulkan instance extensions the runtime needs.
+    pub fn instance_extensions(&self) -> &[CString] {
+        &self.instance_extensions
+    }
+
+    /// The Vulkan device extensions the runtime needs.
+    pub fn device_extensions(&self) -> &[CString] {
+        &self.device_extensions
+    }
+
+    /// The GPU the headset is connected to, which has to be used for the device.
+    pub fn physical_device(
+        &self,
+        instance: &Instance,
+    ) -> Result<vk::PhysicalDevice, anyhow::Error> {
+        let physical_device = unsafe {
+            self.instance.vulkan_graphics_device(self.system, instance.handle().as_raw() as _)
+        };
+        let physical_device = physical_device.context("xrGetVulkanGraphicsDeviceKHR")?;
+        Ok(vk::PhysicalDevice::from_raw(physical_device as u64))
+    }
+
+    /// Creates a session on the device of `vk_context` with a swapchain per eye in `format`
+    /// or a format the runtime prefers.
+    ///
+    /// The session starts running once the runtime is ready, see [`OpenXr::begin_frame`].
+    pub fn start_session(
+        &mut self,
+        vk_context: &VkContext,
+        format: vk::Format,
+        frames_in_flight: usize,
+    ) -> Result<(), anyhow::Error> {
+        let create_info = xr::vulkan::SessionCreateInfo {
+            instance: vk_context.instance().handle().as_raw() as _,
+            physical_device: vk_context.physical_device().as_raw() as _,
+            device: vk_context.device().handle().as_raw() as _,
+            queue_family_index: vk_context.graphics_queue_index(),
+            queue_index: 0,
+        };
+        let (handle, waiter, stream) = unsafe {
+            self.instance.create_session::<xr::Vulkan>(self.system, &create_info)
+        }.context("xrCreateSession")?;
+        // the head is at the origin when the session starts and so at the camera of the gallery
+        let space = handle
+            .create_reference_space(xr::ReferenceSpaceType::LOCAL, xr::Posef::IDENTITY)
+            .context("xrCreateReferenceSpace")?;
+
+        let format = swapchain_format(&handle, format)?;
+        log::debug!("OpenXR swapchain format: {format:?}");
+        let swapchains = self.views.iter()
+            .map(|view| create_swapchain(&handle, view, format))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // everything created so far is destroyed with the session if allocating fails
+        let mut session = Session {
+            device: vk_context.device().clone(),
+            swapchains,
+            space,
+            waiter,
+            stream,
+            handle,
+            command_pool: vk_context
+                .create_command_pool(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+            command_buffers: Vec::new(),
+        };
+        let allocate_info = vk::CommandBufferAllocateInfo::default()
+            .command_pool(session.command_pool)
+            .level(vk::CommandBufferLevel::PRIMARY)
+            .command_buffer_count(frames_in_flight as _);
+        session.command_buffers =
+            unsafe { vk_context.device().allocate_command_buffers(&allocate_info)? };
+        self.session = Some(session);
+        Ok(())
+    }
+
+    /// True while the runtime wants frames, which are then shown in the headset.
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Handles the events of the runtime and waits until it wants the next frame.
+    ///
+    /// Returns the view matrices of the eyes relative to where the head was when the session
+    /// started and the vertical field of view of the headset, or `None` if nothing is shown.
+    pub fn begin_frame(&mut self) -> Option<([Matrix4; 2], f32)> {
+        if let Err(err) = self.poll_events() {
+            log::error!("Failed to handle the OpenXR events: {err:#}");
+        }
+        let session = self.session.as_mut().filter(|_| self.running)?;
+        let state = match session.waiter.wait() {
+            Ok(state) => state,
+            Err(err) => {
+                log::error!("xrWaitFrame failed: {err}");
+                return None;
+            }
+        };
+        if let Err(err) = session.stream.begin() {
+            log::error!("xrBeginFrame failed: {err}");
+            return None;
+        }
+
+        let views = session.locate_views(state.predicted_display_time);
+        let should_render = state.should_render && views.is_some();
+        let eye_views = views.as_ref().filter(|_| should_render).map(|views| {
+            let eye_views = [view_matrix(views[0].pose), view_matrix(views[1].pose)];
+            let fovy = views.iter()
+                .map(|view| view.fov.angle_up.max(-view.fov.angle_down) * 2.)
+                .fold(0., f32::max);
+            (eye_views, fovy)
+        });
+        self.frame = Some(Frame {
+            display_time: state.predicted_display_time,
+            should_render,
+            views: views.unwrap_or_default(),
+            acquired: 0,
+        });
+        eye_views
+    }
+
+    /// Records the command buffer of `frame` that scales the eyes of `target` into
+    /// the swapchain images of the headset, if the runtime wants the current frame rendered.
+    ///
+    /// # Safety
+    ///
+    /// The command buffer of the frame must not be in use anymore and it has to be
+    /// submitted after the render pass of `target`, whose output must be side by side.
+    pub unsafe fn record_command_buffer(
+        &mut self,
+        device: &Device,
+        frame: usize,
+        target: &StereoTarget,
+    ) -> Option<vk::CommandBuffer> {
+        let session = self.session.as_mut()?;
+        let xr_frame = self.frame.as_mut().filter(|frame| frame.should_render)?;
+        let mut images = Vec::with_capacity(session.swapchains.len());
+        for swapchain in session.swapchains.iter_mut() {
+            let index = swapchain.handle.acquire_image().context("xrAcquireSwapchainImage");
+            let result = index.and_then(|index| {
+                swapchain.handle.wait_image(xr::Duration::INFINITE)
+                    .context("xrWaitSwapchainImage")?;
+                Ok(index)
+            });
+            let index = match result {
+                Ok(index) => index,
+                Err(err) => {
+                    log::error!("{err:#}");
+                    // the images acquired so far are released without being drawn to
+                    xr_frame.should_render = false;
+                    xr_frame.acquired = images.len();
+                    return None;
+                }
+            };
+            images.push((swapchain.images[index as usize], swapchain.extent));
+        }
+        xr_frame.acquired = images.len();
+
+        let buffer = session.command_buffers[frame];
+        unsafe {
+            device.reset_command_buffer(buffer, vk::CommandBufferResetFlags::empty()).unwrap();
+            device.begin_command_buffer(buffer, &vk::CommandBufferBeginInfo::default()).unwrap();
+            for (eye, (image, extent)) in images.into_iter().enumerate() {
+                target.blit_eye(device, buffer, eye as u32, image, extent);
+            }
+            device.end_command_buffer(buffer).unwrap();
+        }
+        Some(buffer)
+    }
+
+    /// Releases the swapchain images and shows them in the headset with the field of view
+    /// `fovy` the eyes were rendered with, or shows nothing if they were not rendered.
+    ///
+    /// The command buffer has to be submitted to the graphics queue before,
+    /// the runtime waits for it before reading the images.
+    pub fn end_frame(&mut self, fovy: f32, aspect: f32) {
+        let (Some(session), Some(frame)) = (self.session.as_mut(), self.frame.take()) else {
+            return;
+        };
+        for swapchain in session.swapchains.iter_mut().take(frame.acquired) {
+            if let Err(err) = swapchain.handle.release_image() {
+                log::error!("xrReleaseSwapchainImage failed: {err}");
+            }
+        }
+
+        // the symmetric frustum of the gallery is shown, not the one the runtime would prefer
+        let half_height = fovy / 2.;
+        let half_width = (half_height.tan() * aspect).atan();
+        let fov = xr::Fovf {
+            angle_left: -half_width,
+            angle_right: half_width,
+            angle_up: half_height,
+            angle_down: -half_height,
+        };
+        let projection_views = frame.views.iter().zip(session.swapchains.iter())
+            .map(|(view, swapchain)| {
+                let image_rect = xr::Rect2Di {
+                    offset: xr::Offset2Di { x: 0, y: 0 },
+                    extent: xr::Extent2Di {
+                        width: swapchain.extent.width as i32,
+                        height: swapchain.extent.height as i32,
+                    },
+                };
+                xr::CompositionLayerProjectionView::new()
+                    .pose(view.pose)
+                    .fov(fov)
+                    .sub_image(xr::SwapchainSubImage::new()
+                        .swapchain(&swapchain.handle)
+                        .image_rect(image_rect)
+                        .image_array_index(0))
+            })
+            .collect::<Vec<_>>();
+        let layer = xr::CompositionLayerProjection::new()
+            .space(&session.space)
+            .views(&projection_views);
+        let shown = frame.should_render && frame.acquired == session.swapchains.len();
+        let layers: &[&xr::CompositionLayerBase<_>] = if shown { &[&layer] } else { &[] };
+        let result =
+            session.stream.end(frame.display_time, xr::EnvironmentBlendMode::OPAQUE, layers);
+        if let Err(err) = result {
+            log::error!("xrEndFrame failed: {err}");
+        }
+    }
+
+    /// Starts and stops the session when the runtime asks for it.
+    fn poll_events(&mut self) -> Result<(), anyhow::Error> {
+        let mut buffer = xr::EventDataBuffer::new();
+        while let Some(event) = self.instance.poll_event(&mut buffer).context("xrPollEvent")? {
+            let xr::Event::SessionStateChanged(changed) = event else {
+                continue;
+            };
+            let Some(session) = self.session.as_ref()
+                .filter(|session| session.handle.as_raw() == changed.session())
+            else {
+                continue;
+            };
+            log::debug!("OpenXR session state: {:?}", changed.state());
+            match changed.state() {
+                xr::SessionState::READY => {
+                    session.handle.begin(VIEW_TYPE).context("xrBeginSession")?;
+                    self.running = true;
+                    log::info!("OpenXR session started");
+                }
+                xr::SessionState::STOPPING => {
+                    self.running = false;
+                    session.handle.end().context("xrEndSession")?;
+                    log::info!("OpenXR session stopped");
+                }
+                xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
+                    self.running = false;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Session {
+    /// The poses and fields of view of both eyes at `time`, `None` if they are not tracked.
+    fn locate_views(&self, time: xr::Time) -> Option<Vec<xr::View>> {
+        let (state, views) = match self.handle.locate_views(VIEW_TYPE, time, &self.space) {
+            Ok(located) => located,
+            Err(err) => {
+                log::error!("xrLocateViews failed: {err}");
+                return None;
+            }
+        };
+        let tracked = xr::ViewStateFlags::ORIENTATION_VALID | xr::ViewStateFlags::POSITION_VALID;
+        (state.contains(tracked) && views.len() == EYE_COUNT as usize).then_some(views)
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        // the swapchains, the space and the session are destroyed with their handles
+        unsafe { self.device.destroy_command_pool(self.command_pool, None) };
+    }
+}
+
+/// Picks `preferred` if the runtime supports it, else the first format the runtime prefers.
+fn swapchain_format(
+    session: &xr::Session<xr::Vulkan>,
+    preferred: vk::Format,
+) -> Result<vk::Format, anyhow::Error> {
+    let formats = session.enumerate_swapchain_formats().context("xrEnumerateSwapchainFormats")?;
+    let formats = formats.into_iter().map(|format| vk::Format::from_raw(format as i32));
+    let mut formats = formats.collect::<Vec<_>>();
+    if formats.contains(&preferred) {
+        return Ok(preferred);
+    }
+    formats.retain(|&format| matches!(
+        format,
+        vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+            | vk::Format::B8G8R8A8_UNORM | vk::Format::R8G8B8A8_UNORM,
+    ));
+    formats.first().copied().ok_or_else(|| anyhow!("No supported OpenXR swapchain format"))
+}
+
+/// Creates a swapchain in `format` at the size the runtime recommends for `view`.
+fn create_swapchain(
+    session: &xr::Session<xr::Vulkan>,
+    view: &xr::ViewConfigurationView,
+    format: vk::Format,
+) -> Result<Swapchain, anyhow::Error> {
+    let extent = vk::Extent2D {
+        width: view.recommended_image_rect_width,
+        height: view.recommended_image_rect_height,
+    };
+    let handle = session.create_swapchain(&xr::SwapchainCreateInfo {
+        create_flags: xr::SwapchainCreateFlags::EMPTY,
+        usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT
+            | xr::SwapchainUsageFlags::TRANSFER_DST,
+        format: format.as_raw() as _,
+        sample_count: 1,
+        width: extent.width,
+        height: extent.height,
+        face_count: 1,
+        array_size: 1,
+        mip_count: 1,
+    });
+    let handle = handle.context("xrCreateSwapchain")?;
+    let images = handle.enumerate_images().context("xrEnumerateSwapchainImages")?;
+    let images = images.into_iter().map(vk::Image::from_raw).collect();
+    Ok(Swapchain { handle, images, extent })
+}
+
+/// The extensions of a list of names separated by spaces.
+fn extension_names(names: &str) -> Vec<CString> {
+    names.split_whitespace().map(|name| CString::new(name).unwrap()).collect()
+}
+
+/// The view matrix of an eye at `pose`, the inverse of the rigid transformation of the pose.
+fn view_matrix(pose: xr::Posef) -> Matrix4 {
+    let xr::Quaternionf { x, y, z, w } = pose.orientation;
+    // the rows of the rotation of the pose are the columns of the inverse rotation
+    let rotation = [
+        [1. - 2. * (y * y + z * z), 2. * (x * y - z * w), 2. * (x * z + y * w)],
+        [2. * (x * y + z * w), 1. - 2. * (x * x + z * z), 2. * (y * z - x * w)],
+        [2. * (x * z - y * w), 2. * (y * z + x * w), 1. - 2. * (x * x + y * y)],
+    ];
+    let position = Vector3::from([pose.position.x, pose.position.y, pose.position.z]);
+    let translation = [0, 1, 2].map(|i| {
+        -Vector3::from([rotation[0][i], rotation[1][i], rotation[2][i]]).dot(position)
+    });
+    Matrix4::from([
+        [rotation[0][0], rotation[0][1], rotation[0][2], 0.],
+        [rotation[1][0], rotation[1][1], rotation[1][2], 0.],
+        [rotation[2][0], rotation[2][1], rotation[2][2], 0.],
+        [translation[0], translation[1], translation[2], 1.],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_matrix_of_pose() {
+        let position = xr::Vector3f { x: 1., y: 1.6, z: -2. };
+        let pose = xr::Posef { position, ..xr::Posef::IDENTITY };
+        let view = Matrix4::from_translation(Vector3::from([-1., -1.6, 2.]));
+        assert_eq!(view_matrix(pose), view);
+
+        // a quarter turn to the left looks along -x
+        let half = std::f32::consts::FRAC_PI_4;
+        let pose = xr::Posef {
+            orientation: xr::Quaternionf { x: 0., y: half.sin(), z: 0., w: half.cos() },
+            position: xr::Vector3f { x: 1., y: 2., z: 3. },
+        };
+        let eye = Vector3::from([1., 2., 3.]);
+        let expected = Matrix4::look_to_rh(eye, [-1., 0., 0.].into(), [0., 1., 0.].into());
+        let view = view_matrix(pose);
+        for (col, expected_col) in (0..4).map(|col| (view[col], expected[col])) {
+            assert!((col - expected_col).magnitude() < 1e-5, "{view:?} != {expected:?}");
+        }
+    }
+}